@@ -37,6 +37,26 @@ class AdBlockEngine {
         nativeShouldBlock(engineHandle, url)
     }
     
+    /**
+     * Check a request made by a page, e.g. from WebView shouldInterceptRequest
+     *
+     * @param pageUrl URL of the page making the request, enabling third-party rules
+     * @param resourceType Rule option name such as "script" or "image";
+     *   guessed from the URL when null
+     */
+    fun shouldBlock(url: String, pageUrl: String?, resourceType: String?): Boolean = lock.read {
+        if (engineHandle == 0L) return false
+        nativeShouldBlockEx(engineHandle, url, pageUrl, resourceType)
+    }
+    
+    /**
+     * Get a stylesheet hiding the ad elements of a page domain, for injection
+     */
+    fun getCssRules(domain: String): String? = lock.read {
+        if (engineHandle == 0L) return null
+        nativeGetCssRules(engineHandle, domain)
+    }
+    
     /**
      * Load a filter list
      */
@@ -50,6 +70,17 @@ class AdBlockEngine {
      */
     fun loadFilters(filters: String): Boolean = loadFilterList(filters)
     
+    /**
+     * Update the configured filter lists on a native thread
+     *
+     * Returns once the update has started; [callback] reports the outcome,
+     * so a worker doesn't have to block a thread waiting for it.
+     */
+    fun updateFiltersAsync(callback: FilterUpdateCallback): Boolean = lock.read {
+        if (engineHandle == 0L) return false
+        nativeUpdateFiltersAsync(engineHandle, callback)
+    }
+    
     /**
      * Get statistics
      */
@@ -76,6 +107,14 @@ class AdBlockEngine {
         nativeResetStats(engineHandle)
     }
     
+    /**
+     * Start a new statistics session, e.g. when the VPN connects
+     */
+    fun startSession(): Boolean = lock.write {
+        if (engineHandle == 0L) return false
+        nativeStartSession(engineHandle)
+    }
+    
     /**
      * Destroy the engine and free native resources
      */
@@ -141,15 +180,35 @@ class AdBlockEngine {
     @Keep
     private external fun nativeShouldBlock(handle: Long, url: String): Boolean
     
+    @Keep
+    private external fun nativeShouldBlockEx(
+        handle: Long,
+        url: String,
+        pageUrl: String?,
+        resourceType: String?
+    ): Boolean
+    
+    @Keep
+    private external fun nativeGetCssRules(handle: Long, domain: String): String?
+    
     @Keep
     private external fun nativeLoadFilterList(handle: Long, filterList: String): Boolean
     
+    @Keep
+    private external fun nativeUpdateFiltersAsync(
+        handle: Long,
+        callback: FilterUpdateCallback
+    ): Boolean
+    
     @Keep
     private external fun nativeGetStats(handle: Long): String?
     
     @Keep
     private external fun nativeResetStats(handle: Long): Boolean
     
+    @Keep
+    private external fun nativeStartSession(handle: Long): Boolean
+    
     @Keep
     private external fun nativeGetMetrics(handle: Long): String?
 }
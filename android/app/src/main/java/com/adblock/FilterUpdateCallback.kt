@@ -0,0 +1,21 @@
+package com.adblock
+
+import androidx.annotation.Keep
+
+/**
+ * Outcome of [AdBlockEngine.updateFiltersAsync]
+ *
+ * Called once, on a native background thread.
+ */
+@Keep
+interface FilterUpdateCallback {
+    /**
+     * The updated lists are live with [ruleCount] network rules
+     */
+    fun onSuccess(ruleCount: Int)
+    
+    /**
+     * The update failed; the previous rules stay in place
+     */
+    fun onError(message: String)
+}
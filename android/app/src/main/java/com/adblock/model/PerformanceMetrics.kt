@@ -25,6 +25,15 @@ data class PerformanceMetrics(
     @SerializedName("min_processing_time_ns")
     val minProcessingTimeNs: Long = 0,
     
+    @SerializedName("p50_processing_time_ns")
+    val p50ProcessingTimeNs: Long = 0,
+    
+    @SerializedName("p95_processing_time_ns")
+    val p95ProcessingTimeNs: Long = 0,
+    
+    @SerializedName("p99_processing_time_ns")
+    val p99ProcessingTimeNs: Long = 0,
+    
     @SerializedName("filter_count")
     val filterCount: Int = 0,
     
@@ -46,6 +55,12 @@ data class PerformanceMetrics(
     @SerializedName("cache_size")
     val cacheSize: Int = 0,
     
+    @SerializedName("matches_by_category")
+    val matchesByCategory: Map<String, Long> = emptyMap(),
+    
+    @SerializedName("blocks_by_list")
+    val blocksByList: Map<String, Long> = emptyMap(),
+    
     @SerializedName("block_rate")
     val blockRate: Double = 0.0,
     
@@ -74,6 +89,21 @@ data class PerformanceMetrics(
         return minProcessingTimeNs / 1000.0
     }
     
+    /**
+     * Get 99th percentile processing time in microseconds
+     */
+    fun getP99ProcessingTimeMicros(): Double {
+        return p99ProcessingTimeNs / 1000.0
+    }
+    
+    /**
+     * Percentage of blocked requests caused by a filter list
+     */
+    fun listBlockShare(list: String): Double {
+        val blocks = blocksByList[list] ?: return 0.0
+        return if (blockedRequests > 0) blocks * 100.0 / blockedRequests else 0.0
+    }
+    
     /**
      * Get memory usage in MB
      */
@@ -1,5 +1,67 @@
-use adblock_core::{AdBlockCore, Config};
+use adblock_core::filter_list::{is_cosmetic_rule, is_skipped_line};
+use adblock_core::memory_optimization::OptimizedFilterStorage;
+use adblock_core::{AdBlockCore, Config, FilterEngine};
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// System allocator keeping count of the live heap bytes
+struct CountingAllocator;
+
+static LIVE_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        LIVE_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        LIVE_BYTES.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        LIVE_BYTES.fetch_add(new_size, Ordering::Relaxed);
+        LIVE_BYTES.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Build a value, returning it with the heap bytes it holds
+fn measure_heap<T>(build: impl FnOnce() -> T) -> (T, usize) {
+    let before = LIVE_BYTES.load(Ordering::Relaxed);
+    let value = build();
+    let after = LIVE_BYTES.load(Ordering::Relaxed);
+    (value, after.saturating_sub(before))
+}
+
+/// Rule layout before `OptimizedFilterStorage`: one `Arc<str>` per rule
+#[allow(dead_code)]
+enum PerRuleAllocation {
+    Domain(Arc<str>),
+    Pattern(Arc<str>),
+    SubdomainPattern(Arc<str>),
+    Exception(Arc<str>),
+}
+
+impl PerRuleAllocation {
+    fn new(rule: &str) -> Self {
+        if let Some(exception) = rule.strip_prefix("@@") {
+            Self::Exception(exception.into())
+        } else if let Some(host) = rule.strip_prefix("||") {
+            Self::SubdomainPattern(host.into())
+        } else if rule.contains(['*', '/', '^', '$']) {
+            Self::Pattern(rule.into())
+        } else {
+            Self::Domain(rule.into())
+        }
+    }
+}
 
 fn benchmark_filter_engine(c: &mut Criterion) {
     let mut group = c.benchmark_group("filter_engine");
@@ -18,7 +80,8 @@ fn benchmark_filter_engine(c: &mut Criterion) {
 ||amazon-adsystem.com^
 "#;
 
-    core.load_filter_list(filter_rules);
+    core.load_filter_list(filter_rules)
+        .expect("Failed to load filter list");
 
     group.bench_function("should_block_ad_url", |b| {
         b.iter(|| core.should_block(black_box("https://doubleclick.net/ads/banner.js")))
@@ -62,7 +125,8 @@ fn benchmark_filter_loading(c: &mut Criterion) {
         b.iter(|| {
             let config = Config::default();
             let mut core = AdBlockCore::new(config).expect("Failed to create AdBlockCore");
-            core.load_filter_list(black_box(small_filter_list));
+            core.load_filter_list(black_box(small_filter_list))
+                .expect("Failed to load filter list");
         })
     });
 
@@ -70,12 +134,80 @@ fn benchmark_filter_loading(c: &mut Criterion) {
         b.iter(|| {
             let config = Config::default();
             let mut core = AdBlockCore::new(config).expect("Failed to create AdBlockCore");
-            core.load_filter_list(black_box(large_filter_list));
+            core.load_filter_list(black_box(large_filter_list))
+                .expect("Failed to load filter list");
+        })
+    });
+
+    group.finish();
+}
+
+/// Filter list for the storage benchmarks
+///
+/// Set `ADBLOCK_BENCH_LIST` to the path of a full EasyList download; the
+/// bundled sample is used otherwise, which is too small for meaningful
+/// memory figures.
+fn bench_list() -> String {
+    match std::env::var("ADBLOCK_BENCH_LIST") {
+        Ok(path) => std::fs::read_to_string(&path).expect("Failed to read ADBLOCK_BENCH_LIST"),
+        Err(_) => include_str!("../tests/fixtures/easylist_sample.txt").to_string(),
+    }
+}
+
+fn benchmark_rule_storage(c: &mut Criterion) {
+    let mut group = c.benchmark_group("rule_storage");
+    let list = bench_list();
+
+    let network_rules: Vec<&str> = list
+        .lines()
+        .map(str::trim)
+        .filter(|line| !is_skipped_line(line) && !is_cosmetic_rule(line))
+        .collect();
+
+    // Heap bytes measured by the counting allocator, not estimated
+    let (per_rule, per_rule_bytes) = measure_heap(|| {
+        network_rules
+            .iter()
+            .map(|rule| PerRuleAllocation::new(rule))
+            .collect::<Vec<_>>()
+    });
+    let (storage, arena_bytes) = measure_heap(|| {
+        let mut storage = OptimizedFilterStorage::new();
+        for rule in &network_rules {
+            storage.push(0, rule);
+        }
+        storage.compact();
+        storage
+    });
+    let (engine, engine_bytes) = measure_heap(|| {
+        let mut engine = FilterEngine::from_filter_list(&list).expect("Failed to load filter list");
+        engine.compact();
+        engine
+    });
+    println!(
+        "rule_storage: {} rules, per-rule allocations {} bytes, arena {} bytes, whole engine {} bytes",
+        network_rules.len(),
+        per_rule_bytes,
+        arena_bytes,
+        engine_bytes
+    );
+    drop((per_rule, storage, engine));
+
+    group.bench_function("load_and_compact", |b| {
+        b.iter(|| {
+            let mut engine = FilterEngine::from_filter_list(black_box(&list))
+                .expect("Failed to load filter list");
+            engine.compact()
         })
     });
 
     group.finish();
 }
 
-criterion_group!(benches, benchmark_filter_engine, benchmark_filter_loading);
+criterion_group!(
+    benches,
+    benchmark_filter_engine,
+    benchmark_filter_loading,
+    benchmark_rule_storage
+);
 criterion_main!(benches);
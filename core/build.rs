@@ -0,0 +1,28 @@
+//! Build script
+//!
+//! With the `c-header` feature enabled, regenerates `include/adblock_core.h`
+//! from the `extern "C"` API in `src/ffi.rs`.
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    #[cfg(feature = "cbindgen")]
+    generate_header();
+}
+
+#[cfg(feature = "cbindgen")]
+fn generate_header() {
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR not set");
+    let config = cbindgen::Config::from_file(format!("{crate_dir}/cbindgen.toml"))
+        .expect("Failed to read cbindgen.toml");
+
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    println!("cargo:rerun-if-changed=src/ffi.rs");
+
+    match cbindgen::generate_with_config(&crate_dir, config) {
+        Ok(bindings) => {
+            bindings.write_to_file(format!("{crate_dir}/include/adblock_core.h"));
+        }
+        Err(e) => println!("cargo:warning=Failed to generate C header: {e}"),
+    }
+}
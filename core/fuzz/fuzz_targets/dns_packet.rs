@@ -0,0 +1,21 @@
+//! Raw DNS messages and IP packets, as read from the VPN interface
+
+#![no_main]
+
+use adblock_core::network::NetworkFilter;
+use libfuzzer_sys::fuzz_target;
+use once_cell::sync::Lazy;
+
+static FILTER: Lazy<NetworkFilter> = Lazy::new(|| {
+    let mut filter = NetworkFilter::new();
+    filter.add_blocked_domain("ads.example.com");
+    filter
+});
+
+fuzz_target!(|data: &[u8]| {
+    let _ = adblock_core::dns_server::check_query(&FILTER, data);
+    if let Ok(info) = adblock_core::packet::parse_packet(data) {
+        let _ = adblock_core::packet::verify_checksums(data, &info);
+    }
+    let _ = adblock_core::packet::answer_dns_packet(&FILTER, data);
+});
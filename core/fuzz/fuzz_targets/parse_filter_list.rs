@@ -0,0 +1,24 @@
+//! Whole filter lists, as downloaded from a community list
+
+#![no_main]
+
+use adblock_core::filter_list::ParseMode;
+use adblock_core::rule_set::RuleSet;
+use adblock_core::FilterListLoader;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(content) = std::str::from_utf8(data) else {
+        return;
+    };
+
+    let loader = FilterListLoader::new();
+    let _ = loader.parse_with_report(content, ParseMode::Strict);
+    let _ = loader.parse_mixed(content);
+    let _ = adblock_core::lint::lint(content);
+
+    let rule_set = RuleSet::from_filter_list(content);
+    if let Ok(engine) = rule_set.build_engine() {
+        let _ = engine.should_block("https://ads.example.com/banner.js");
+    }
+});
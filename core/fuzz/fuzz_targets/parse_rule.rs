@@ -0,0 +1,42 @@
+//! Single rules, through every rule syntax's parser
+
+#![no_main]
+
+use adblock_core::cosmetic::{CosmeticFilter, ElemHideException};
+use adblock_core::csp::CspRule;
+use adblock_core::json_prune::JsonPruneRule;
+use adblock_core::procedural::ProceduralFilter;
+use adblock_core::removeparam::RemoveParamRule;
+use adblock_core::resources::RedirectRule;
+use adblock_core::rules::RuleParser;
+use adblock_core::scriptlet::ScriptletRule;
+use adblock_core::FilterEngine;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(rule) = std::str::from_utf8(data) else {
+        return;
+    };
+    // One line, as the list parsers hand rules over
+    let rule = rule.lines().next().unwrap_or_default();
+
+    let _ = CosmeticFilter::parse(rule);
+    let _ = ElemHideException::parse(rule);
+    let _ = ProceduralFilter::parse(rule);
+    // Deep nesting, beyond libFuzzer's default input size
+    let depth = 1_000;
+    let nested = format!("##{}{rule}{}", "a:has(".repeat(depth), ")".repeat(depth));
+    let _ = ProceduralFilter::parse(&nested);
+    let _ = ScriptletRule::parse(rule);
+    let _ = JsonPruneRule::parse(rule);
+    let _ = RemoveParamRule::parse(rule);
+    let _ = RedirectRule::parse(rule);
+    let _ = CspRule::parse(rule);
+    let _ = adblock_core::domain_filter::parse_domain_rule(rule);
+    let _ = RuleParser::new().parse_rule(rule);
+
+    if let Ok(engine) = FilterEngine::from_filter_list(rule) {
+        let _ = engine.should_block("https://example.com/ads/banner.js?id=1");
+        let _ = engine.get_cosmetic_result("https://example.com/");
+    }
+});
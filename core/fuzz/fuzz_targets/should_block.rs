@@ -0,0 +1,30 @@
+//! Arbitrary request and page URLs against a real filter list
+
+#![no_main]
+
+use adblock_core::resource_type::RequestContext;
+use adblock_core::FilterEngine;
+use libfuzzer_sys::fuzz_target;
+use once_cell::sync::Lazy;
+
+static ENGINE: Lazy<FilterEngine> = Lazy::new(|| {
+    FilterEngine::from_filter_list(include_str!("../../tests/fixtures/easylist_sample.txt"))
+        .expect("sample list builds")
+});
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(input) = std::str::from_utf8(data) else {
+        return;
+    };
+    // The request URL, optionally followed by the page URL on a new line
+    let (url, page_url) = match input.split_once('\n') {
+        Some((url, page_url)) => (url, Some(page_url)),
+        None => (input, None),
+    };
+
+    let _ = ENGINE.should_block(url);
+    if let Some(page_url) = page_url {
+        let context = RequestContext::from_url(url).with_document_url(page_url);
+        let _ = ENGINE.should_block_with_context(url, &context);
+    }
+});
@@ -0,0 +1,652 @@
+/* AdBlock Core C API. Generated by cbindgen - do not edit. */
+
+#ifndef ADBLOCK_CORE_H
+#define ADBLOCK_CORE_H
+
+#include <stdbool.h>
+#include <stdint.h>
+
+// Error codes written to the `error` out-parameter
+//
+// Values stay below 32, which [`AdblockStatus`] keeps for its own codes.
+typedef enum {
+  // The call succeeded
+  ADBLOCK_ERROR_CODE_OK = 0,
+  // The engine or a required argument was null, or a handle was destroyed
+  ADBLOCK_ERROR_CODE_NULL_POINTER = 1,
+  // A string argument was not valid UTF-8
+  ADBLOCK_ERROR_CODE_INVALID_UTF8 = 2,
+  // The engine lock was poisoned by a panic
+  ADBLOCK_ERROR_CODE_LOCK_POISONED = 3,
+  // The engine could not be created or a filter list failed to parse
+  ADBLOCK_ERROR_CODE_ENGINE_FAILURE = 4,
+  // The result could not be serialized
+  ADBLOCK_ERROR_CODE_SERIALIZATION_FAILED = 5,
+  // The operation is not supported by this engine
+  ADBLOCK_ERROR_CODE_NOT_SUPPORTED = 6,
+  // The config JSON is malformed or fails validation
+  ADBLOCK_ERROR_CODE_INVALID_CONFIG = 7,
+  // An argument was rejected, e.g. an invalid subscription link
+  ADBLOCK_ERROR_CODE_INVALID_ARGUMENT = 8,
+  // The call panicked; the panic was recorded by the crash reporter
+  ADBLOCK_ERROR_CODE_PANICKED = 9,
+  // The engine was busy, e.g. swapping in new rules; retry the call
+  ADBLOCK_ERROR_CODE_ENGINE_BUSY = 10,
+} AdblockErrorCode;
+
+// Per-site blocking mode, see [`SiteMode`]
+typedef enum {
+  // Nothing is blocked or hidden
+  ADBLOCK_SITE_MODE_OFF = 0,
+  // Network rules only
+  ADBLOCK_SITE_MODE_STANDARD = 1,
+  // Network and cosmetic rules
+  ADBLOCK_SITE_MODE_AGGRESSIVE = 2,
+} AdblockSiteMode;
+
+// Status returned by mutating calls
+//
+// Codes it shares with [`AdblockErrorCode`] have the same values; codes
+// only statuses have start at 32.
+typedef enum {
+  // The call succeeded
+  ADBLOCK_STATUS_OK = 0,
+  // The engine or a required argument was null, or a handle was destroyed
+  ADBLOCK_STATUS_NULL_POINTER = 1,
+  // A string argument was not valid UTF-8
+  ADBLOCK_STATUS_INVALID_UTF8 = 2,
+  // The engine lock was poisoned by a panic
+  ADBLOCK_STATUS_LOCK_POISONED = 3,
+  // The operation is not supported by this engine
+  ADBLOCK_STATUS_NOT_SUPPORTED = 6,
+  // The call panicked; the panic was recorded by the crash reporter
+  ADBLOCK_STATUS_PANICKED = 9,
+  // The engine was busy, e.g. swapping in new rules; retry the call
+  ADBLOCK_STATUS_ENGINE_BUSY = 10,
+  // The rules could not be parsed
+  ADBLOCK_STATUS_PARSE_ERROR = 32,
+  // A file could not be read or written
+  ADBLOCK_STATUS_IO_ERROR = 33,
+} AdblockStatus;
+
+// Verdict on a request, see [`crate::BlockKind`]
+typedef enum {
+  // The request is allowed
+  ADBLOCK_VERDICT_ALLOWED = 0,
+  // Blocked as an ad or tracker
+  ADBLOCK_VERDICT_AD = 1,
+  // Blocked by a malware/phishing protection list; show an interstitial
+  ADBLOCK_VERDICT_MALWARE = 2,
+  // Blocked because the site is in a blocked content category
+  ADBLOCK_VERDICT_CATEGORY = 3,
+} AdblockVerdict;
+
+// Callback receiving engine events as JSON (e.g. `{"type":"rule_set_updated",...}`)
+//
+// The JSON string is only valid during the call. The callback runs on the
+// thread that published the event, possibly while the engine is locked, so
+// it must not call back into the engine.
+typedef void (*AdblockEventCallback)(const char *event_json, void *user_data);
+
+// Callback receiving the outcome of `adblock_engine_update_filters_async`
+//
+// On success `error_message` is null and `rule_count` is the number of
+// network rules now active. Otherwise `error_message` describes the
+// failure and is only valid during the call.
+typedef void (*AdblockUpdateCallback)(uintptr_t rule_count,
+                                      const char *error_message,
+                                      void *user_data);
+
+// Create a new AdBlock engine
+void *adblock_engine_create(AdblockErrorCode *error);
+
+// Create an engine from a persisted config JSON
+//
+// Older config versions are upgraded first. An unparseable or invalid
+// config returns null with `InvalidConfig`; call
+// `adblock_validate_config` for the reasons.
+void *adblock_engine_create_with_config(const char *config_json, AdblockErrorCode *error);
+
+// Validate a persisted config JSON
+//
+// Returns a JSON array of errors (empty when valid), each with a `kind`
+// and the offending values. The returned string must be freed with
+// `adblock_free_string`
+char *adblock_validate_config(const char *config_json, AdblockErrorCode *error);
+
+// Destroy an AdBlock engine
+//
+// The handle is invalid from now on; destroying it again does nothing.
+void adblock_engine_destroy(void *engine);
+
+// Register a callback for engine events, replacing any previous one
+//
+// Pass a null callback to stop receiving events.
+bool adblock_engine_set_event_callback(void *engine,
+                                       AdblockEventCallback callback,
+                                       void *user_data,
+                                       AdblockErrorCode *error);
+
+// Register a callback for every checked request, replacing any previous one
+//
+// It receives the request as JSON (`{"domain":...,"blocked":true,...}`),
+// only valid during the call, on the checking thread. Use it to keep a
+// live blocked counter without polling statistics; it must not call back
+// into the engine. Pass a null callback to stop receiving requests.
+bool adblock_engine_set_block_event_callback(void *engine,
+                                             AdblockEventCallback callback,
+                                             void *user_data,
+                                             AdblockErrorCode *error);
+
+// Check if a URL should be blocked
+bool adblock_engine_should_block(void *engine, const char *url, AdblockErrorCode *error);
+
+// Check a URL, returning whether it is blocked and as what
+//
+// Statistics are tracked as with `adblock_engine_should_block`.
+AdblockVerdict adblock_engine_check_url(void *engine, const char *url, AdblockErrorCode *error);
+
+// Check a URL requested by an app or package
+//
+// Like `adblock_engine_check_url`, but the app is recorded in the query log.
+AdblockVerdict adblock_engine_check_url_for_app(void *engine,
+                                                const char *url,
+                                                const char *app,
+                                                AdblockErrorCode *error);
+
+// Check a request made by a page, e.g. from a WebView's
+// `shouldInterceptRequest`
+//
+// `page_url` enables `$third-party` and per-site options; `resource_type`
+// is a rule option name such as `script` or `xmlhttprequest`. Either may
+// be null, and the type is then guessed from the URL. An unknown type is
+// rejected with `InvalidArgument`. Statistics are tracked as with
+// `adblock_engine_should_block`.
+bool adblock_engine_should_block_request(void *engine,
+                                         const char *url,
+                                         const char *page_url,
+                                         const char *resource_type,
+                                         AdblockErrorCode *error);
+
+// Explain a URL's verdict for a developer screen, as JSON
+//
+// Lists every matching rule with its source list instead of the first
+// one, without recording statistics. The engine's whitelist and pause
+// state are not considered.
+// The returned string must be freed with `adblock_free_string`
+char *adblock_engine_explain(void *engine, const char *url, AdblockErrorCode *error);
+
+// Add a single rule to the engine
+bool adblock_engine_add_rule(void *engine, const char *rule, AdblockErrorCode *error);
+
+// Add a single rule to the engine, returning a status
+//
+// The rule is kept as a user rule, so it survives filter list reloads.
+// Adding a rule twice succeeds; comments and malformed rules are
+// rejected with `ParseError`. `EngineBusy` means the rules couldn't be
+// patched in time and the call can be retried.
+AdblockStatus adblock_engine_add_rule_status(void *engine, const char *rule);
+
+// Remove a rule added with `adblock_engine_add_rule`
+bool adblock_engine_remove_rule(void *engine, const char *rule, AdblockErrorCode *error);
+
+// Remove a rule added with `adblock_engine_add_rule`, returning a status
+//
+// Returns `NotSupported` if the rule was not added that way; rules from
+// filter lists go away with their list.
+AdblockStatus adblock_engine_remove_rule_status(void *engine, const char *rule);
+
+// Number of network rules in the engine, added rules included
+uintptr_t adblock_engine_rule_count(void *engine, AdblockErrorCode *error);
+
+// Load a filter list
+bool adblock_engine_load_filter_list(void *engine,
+                                     const char *filter_list,
+                                     AdblockErrorCode *error);
+
+// Load a filter list, returning a status
+AdblockStatus adblock_engine_load_filter_list_status(void *engine, const char *filter_list);
+
+// Subscribe to a filter list (or replace its content) and rebuild the rules
+//
+// The engine is rebuilt from every enabled registered list, replacing
+// rules loaded with `adblock_engine_load_filter_list`. New content for an
+// enabled list is applied in place as a diff against the previous copy,
+// and a `rule_set_patched` event reports the added and removed counts.
+bool adblock_engine_add_filter_list(void *engine,
+                                    const char *url,
+                                    const char *content,
+                                    AdblockErrorCode *error);
+
+// Subscribe to a filter list, returning a status
+AdblockStatus adblock_engine_add_filter_list_status(void *engine,
+                                                    const char *url,
+                                                    const char *content);
+
+// Subscribe to a malware/phishing protection list (or replace its
+// content) and rebuild the rules
+//
+// Requests blocked by its rules get `AdblockVerdict::Malware` from
+// `adblock_engine_check_url`.
+bool adblock_engine_add_security_list(void *engine,
+                                      const char *url,
+                                      const char *content,
+                                      AdblockErrorCode *error);
+
+// Subscribe to a malware/phishing protection list, returning a status
+AdblockStatus adblock_engine_add_security_list_status(void *engine,
+                                                      const char *url,
+                                                      const char *content);
+
+// Unsubscribe from a filter list and rebuild the rules
+bool adblock_engine_remove_filter_list(void *engine, const char *url, AdblockErrorCode *error);
+
+// Unsubscribe from a filter list, returning a status
+//
+// Returns `NotSupported` if the list is not registered.
+AdblockStatus adblock_engine_remove_filter_list_status(void *engine, const char *url);
+
+// Enable or disable a registered filter list and rebuild the rules
+bool adblock_engine_set_filter_list_enabled(void *engine,
+                                            const char *url,
+                                            bool enabled,
+                                            AdblockErrorCode *error);
+
+// Enable or disable a registered filter list, returning a status
+//
+// Returns `NotSupported` if the list is not registered.
+AdblockStatus adblock_engine_set_filter_list_enabled_status(void *engine,
+                                                            const char *url,
+                                                            bool enabled);
+
+// Get the registered filter lists and their metadata as a JSON array
+//
+// The returned string must be freed with `adblock_free_string`
+char *adblock_engine_get_filter_lists(void *engine, AdblockErrorCode *error);
+
+// Get the engine state for an About/Diagnostics screen as JSON
+//
+// Holds the engine `version`, `rule_counts` by kind, the enabled `lists`
+// with their versions, when a list was `last_updated` and an estimate of
+// the rules' `memory_bytes`.
+// The returned string must be freed with `adblock_free_string`
+char *adblock_engine_get_info(void *engine, AdblockErrorCode *error);
+
+// Update the configured filter lists on a background thread, e.g. from a
+// WorkManager job or background task, without blocking the caller
+//
+// Lists are fetched as by the update scheduler: lists not due yet come
+// from the cache, and downloads are deferred on a metered connection.
+// Once the thread is started, `callback` is called exactly once on it,
+// after the new rules are live; it may call back into the engine.
+// Updates of one engine run one at a time.
+bool adblock_engine_update_filters_async(void *engine,
+                                         AdblockUpdateCallback callback,
+                                         void *user_data,
+                                         AdblockErrorCode *error);
+
+// Get statistics as JSON string
+char *adblock_engine_get_stats(void *engine, AdblockErrorCode *error);
+
+// Get the blocked-request counts of one host as JSON
+//
+// Returns `null` as JSON if nothing was blocked on the host.
+// The returned string must be freed with `adblock_free_string`
+char *adblock_engine_get_domain_stats(void *engine, const char *domain, AdblockErrorCode *error);
+
+// Get a page of the blocked domains as a JSON array, most blocked first
+//
+// The returned string must be freed with `adblock_free_string`
+char *adblock_engine_top_blocked(void *engine,
+                                 uintptr_t offset,
+                                 uintptr_t limit,
+                                 AdblockErrorCode *error);
+
+// Write statistics JSON into a caller-provided buffer
+//
+// Returns the buffer size needed, including the NUL terminator. The JSON
+// is written only if it fits in `cap` bytes; otherwise `buf` holds an
+// empty string (if `cap > 0`) and the caller should retry with a buffer of
+// the returned size. Returns 0 on error. No heap allocation is made.
+uintptr_t adblock_engine_get_stats_buf(void *engine,
+                                       char *buf,
+                                       uintptr_t cap,
+                                       AdblockErrorCode *error);
+
+// Back up config, custom rules, whitelist and statistics as JSON
+//
+// The returned string must be freed with `adblock_free_string`
+char *adblock_engine_create_backup(void *engine, AdblockErrorCode *error);
+
+// Write a gzip-compressed backup into a caller-provided buffer
+//
+// Returns the size of the compressed backup. It is written only if it
+// fits in `cap` bytes; otherwise the caller should retry with a larger
+// buffer. The backup is timestamped, so its size can differ slightly
+// between calls; use the size returned by the call that wrote it.
+// Returns 0 on error.
+uintptr_t adblock_engine_create_backup_gzip(void *engine,
+                                            uint8_t *buf,
+                                            uintptr_t cap,
+                                            AdblockErrorCode *error);
+
+// Restore a backup from JSON or gzip-compressed JSON bytes
+//
+// Config, custom rules, whitelist and statistics are replaced; the loaded
+// filter lists are kept.
+bool adblock_engine_restore_backup(void *engine,
+                                   const uint8_t *data,
+                                   uintptr_t len,
+                                   AdblockErrorCode *error);
+
+// Restore a backup, returning a status
+//
+// Returns `ParseError` if the backup is malformed or invalid.
+AdblockStatus adblock_engine_restore_backup_status(void *engine,
+                                                   const uint8_t *data,
+                                                   uintptr_t len);
+
+// Export custom rules and whitelist as EasyList-compatible text
+//
+// The returned string must be freed with `adblock_free_string`
+char *adblock_engine_export_user_rules(void *engine, AdblockErrorCode *error);
+
+// Import custom rules and whitelist entries from filter list text
+//
+// Returns the import report as JSON (added, duplicate and invalid lines).
+// The returned string must be freed with `adblock_free_string`
+char *adblock_engine_import_user_rules(void *engine, const char *text, AdblockErrorCode *error);
+
+// Pause blocking for `seconds`
+bool adblock_engine_pause(void *engine, uint64_t seconds, AdblockErrorCode *error);
+
+// Pause blocking for `seconds`, returning a status
+AdblockStatus adblock_engine_pause_status(void *engine, uint64_t seconds);
+
+// End a manual pause; scheduled pause windows still apply
+bool adblock_engine_resume(void *engine, AdblockErrorCode *error);
+
+// End a manual pause, returning a status
+AdblockStatus adblock_engine_resume_status(void *engine);
+
+// Report whether the device is idle and charging
+//
+// Call from the platform's idle and power callbacks. Cache compaction,
+// rule reordering and other maintenance run in the background while the
+// device is idle; some tasks also wait for it to be charging.
+bool adblock_engine_set_device_conditions(void *engine,
+                                          bool idle,
+                                          bool charging,
+                                          AdblockErrorCode *error);
+
+// Report whether the device is idle and charging, returning a status
+AdblockStatus adblock_engine_set_device_conditions_status(void *engine, bool idle, bool charging);
+
+// Whether blocking is paused now, manually or by the pause schedule
+bool adblock_engine_is_paused(void *engine, AdblockErrorCode *error);
+
+// Replace the pause schedule with a JSON array of windows, e.g.
+// `[{"days":["Mon","Tue"],"start":"09:00:00","end":"17:00:00"}]`
+bool adblock_engine_set_pause_schedule(void *engine,
+                                       const char *schedule_json,
+                                       AdblockErrorCode *error);
+
+// Replace the pause schedule, returning a status
+//
+// Returns `ParseError` if the JSON is not a list of windows.
+AdblockStatus adblock_engine_set_pause_schedule_status(void *engine, const char *schedule_json);
+
+// Get the built-in filter list catalog as a JSON array
+//
+// Each list has an `id`, `title`, `category`, `region`, `languages`,
+// `size_kb`, `recommended` flag and its download `urls`. The returned
+// string must be freed with `adblock_free_string`
+char *adblock_get_list_catalog(AdblockErrorCode *error);
+
+// Parse a subscription link or filter list URL into JSON
+//
+// Accepts `abp:subscribe?location=…&title=…` links and plain http(s)
+// URLs. The result has the list `url`, its `title` and the `preset_id`
+// of a catalog list, or is null with `InvalidArgument`. The returned
+// string must be freed with `adblock_free_string`
+char *adblock_parse_subscription_link(const char *link, AdblockErrorCode *error);
+
+// Enable or disable a catalog list by ID
+bool adblock_engine_set_list_preset_enabled(void *engine,
+                                            const char *id,
+                                            bool enabled,
+                                            AdblockErrorCode *error);
+
+// Enable or disable a catalog list by ID, returning a status
+//
+// An ID missing from the catalog reports [`AdblockStatus::ParseError`].
+AdblockStatus adblock_engine_set_list_preset_enabled_status(void *engine,
+                                                            const char *id,
+                                                            bool enabled);
+
+// Set the blocking mode of a site and its subdomains
+//
+// The mode is persisted in the config and included in backups.
+bool adblock_engine_set_site_mode(void *engine,
+                                  const char *domain,
+                                  AdblockSiteMode mode,
+                                  AdblockErrorCode *error);
+
+// Set the blocking mode of a site, returning a status
+AdblockStatus adblock_engine_set_site_mode_status(void *engine,
+                                                  const char *domain,
+                                                  AdblockSiteMode mode);
+
+// Go back to the default mode for a site
+//
+// Returns `false` with `Ok` if the site had no mode set.
+bool adblock_engine_clear_site_mode(void *engine, const char *domain, AdblockErrorCode *error);
+
+// Get the blocking mode that applies to a host
+AdblockSiteMode adblock_engine_get_site_mode(void *engine,
+                                             const char *host,
+                                             AdblockErrorCode *error);
+
+// Get the sites with a mode set as a JSON object, e.g. `{"example.com":"off"}`
+//
+// The returned string must be freed with `adblock_free_string`
+char *adblock_engine_get_site_modes(void *engine, AdblockErrorCode *error);
+
+// Start logging checked requests for the activity screen
+//
+// Entries go to a binary file at `path` capped at `max_bytes` (0 for the
+// default cap), or stay in memory if `path` is null.
+bool adblock_engine_enable_query_log(void *engine,
+                                     const char *path,
+                                     uint64_t max_bytes,
+                                     AdblockErrorCode *error);
+
+// Start logging checked requests, returning a status
+AdblockStatus adblock_engine_enable_query_log_status(void *engine,
+                                                     const char *path,
+                                                     uint64_t max_bytes);
+
+// Remove every query log entry
+bool adblock_engine_clear_query_log(void *engine, AdblockErrorCode *error);
+
+// Remove every query log entry, returning a status
+AdblockStatus adblock_engine_clear_query_log_status(void *engine);
+
+// Search the query log
+//
+// `query_json` holds optional `domain`, `verdict`, `since_ms`, `until_ms`
+// and `app` criteria plus `offset` and `limit` (default 50), e.g.
+// `{"verdict":"blocked","offset":50}`. Returns
+// `{"total":N,"entries":[...]}` with entries newest first, where `total`
+// counts every match. Fails with `NotSupported` if the log is disabled.
+// The returned string must be freed with `adblock_free_string`
+char *adblock_engine_search_query_log(void *engine,
+                                      const char *query_json,
+                                      AdblockErrorCode *error);
+
+// Get the recorded crash reports as a JSON array, newest first
+//
+// The returned string must be freed with `adblock_free_string`
+char *adblock_get_crash_reports_json(void *engine, AdblockErrorCode *error);
+
+// Get the engine's recent log records as a JSON array, oldest first
+//
+// Each record has a `timestamp`, `level`, `target` and `message`, plus
+// `fields` for structured values. The log is shared by every engine in
+// the process and keeps the last few hundred records; debug records are
+// only kept while a config has `debug` set. The returned string must be
+// freed with `adblock_free_string`
+char *adblock_get_recent_logs(AdblockErrorCode *error);
+
+// Delete all recorded crash reports, including persisted ones
+bool adblock_clear_crash_reports(void *engine, AdblockErrorCode *error);
+
+// Delete all recorded crash reports, returning a status
+AdblockStatus adblock_clear_crash_reports_status(void *engine);
+
+// Enable or disable analytics
+//
+// Disabling deletes buffered events and pending batches.
+bool adblock_analytics_set_enabled(void *engine, bool enabled, AdblockErrorCode *error);
+
+// Enable or disable analytics, returning a status
+AdblockStatus adblock_analytics_set_enabled_status(void *engine, bool enabled);
+
+// Get the oldest analytics batch awaiting upload as JSON
+//
+// The object has `id`, `event_count` and `ndjson` fields. Returns null with
+// `Ok` when nothing is pending, and `NotSupported` if the config has no
+// `analytics_dir`. The batch is returned again until
+// `adblock_analytics_mark_uploaded` is called with its id.
+// The returned string must be freed with `adblock_free_string`
+char *adblock_analytics_export_batch(void *engine, AdblockErrorCode *error);
+
+// Delete an uploaded analytics batch
+bool adblock_analytics_mark_uploaded(void *engine, const char *batch_id, AdblockErrorCode *error);
+
+// Delete an uploaded analytics batch, returning a status
+//
+// Returns `NotSupported` if no batch with that id is pending.
+AdblockStatus adblock_analytics_mark_uploaded_status(void *engine, const char *batch_id);
+
+// Reset statistics
+//
+// # Safety
+// The engine pointer must be valid
+bool adblock_engine_reset_stats(void *engine, AdblockErrorCode *error);
+
+// Reset statistics, returning a status
+AdblockStatus adblock_engine_reset_stats_status(void *engine);
+
+// Start a new statistics session, keeping the lifetime totals
+//
+// # Safety
+// The engine pointer must be valid
+bool adblock_engine_start_session(void *engine, AdblockErrorCode *error);
+
+// Start a new statistics session, returning a status
+AdblockStatus adblock_engine_start_session_status(void *engine);
+
+// Get performance metrics
+//
+// # Safety
+// The engine pointer must be valid
+//
+// Returns a JSON string with performance metrics
+char *adblock_engine_get_metrics(void *engine, AdblockErrorCode *error);
+
+// Get performance metrics in the Prometheus text exposition format
+//
+// For serving from a local scrape endpoint when the engine runs behind a
+// proxy. The returned string must be freed with `adblock_free_string`
+char *adblock_engine_get_metrics_prometheus(void *engine, AdblockErrorCode *error);
+
+// Export the active rules as Safari content blocker JSON
+//
+// # Safety
+// The engine pointer must be valid
+//
+// The returned string must be freed with `adblock_free_string`
+char *adblock_engine_export_content_blocker_json(void *engine, AdblockErrorCode *error);
+
+// Get the element hiding selectors for a page domain as a JSON array
+//
+// # Safety
+// The engine pointer must be valid
+//
+// The returned string must be freed with `adblock_free_string`
+char *adblock_engine_get_cosmetic_rules(void *engine, const char *domain, AdblockErrorCode *error);
+
+// Get a stylesheet hiding the page domain's elements, for injection into
+// the page
+//
+// Holds the selectors of `adblock_engine_get_cosmetic_rules`, each as a
+// `display: none !important` rule; empty if no rule applies.
+// The returned string must be freed with `adblock_free_string`
+char *adblock_engine_get_cosmetic_css(void *engine, const char *domain, AdblockErrorCode *error);
+
+// Get the procedural cosmetic filters for a page domain as a JSON array
+//
+// # Safety
+// The engine pointer must be valid
+//
+// The returned string must be freed with `adblock_free_string`
+char *adblock_engine_get_procedural_filters(void *engine,
+                                            const char *domain,
+                                            AdblockErrorCode *error);
+
+// Create an empty network filter for the DNS path of a VPN service
+//
+// Destroy it with `adblock_network_filter_destroy`.
+void *adblock_network_filter_create(AdblockErrorCode *error);
+
+// Create a network filter following an engine's rules, whitelist and
+// content category filter
+//
+// Rule reloads of the engine apply to the filter. It stays valid after
+// the engine is destroyed and must be destroyed separately.
+void *adblock_engine_create_network_filter(void *engine, AdblockErrorCode *error);
+
+// Destroy a network filter
+//
+// The handle is invalid from now on; destroying it again does nothing.
+void adblock_network_filter_destroy(void *filter);
+
+// Block a domain and its subdomains
+bool adblock_network_filter_add_blocked_domain(void *filter,
+                                               const char *domain,
+                                               AdblockErrorCode *error);
+
+// Block a domain, returning a status
+AdblockStatus adblock_network_filter_add_blocked_domain_status(void *filter, const char *domain);
+
+// Check if a domain is blocked
+bool adblock_network_filter_is_domain_blocked(void *filter,
+                                              const char *domain,
+                                              AdblockErrorCode *error);
+
+// Answer a DNS query packet (the UDP payload) if its domain is blocked
+//
+// Returns the size of the response packet. It is written only if it fits
+// in `cap` bytes; otherwise the caller should retry with a larger buffer.
+// Returns 0 if the query is not answered locally, because its domain is
+// not blocked or it is not a single-question query: forward it upstream.
+// Returns 0 with an error code set on failure.
+//
+// # Safety
+// `packet` must point to `len` readable bytes
+uintptr_t adblock_network_filter_process_dns_packet(void *filter,
+                                                    const uint8_t *packet,
+                                                    uintptr_t len,
+                                                    uint8_t *buf,
+                                                    uintptr_t cap,
+                                                    AdblockErrorCode *error);
+
+// Free a string allocated by the library
+//
+// # Safety
+// The pointer must have been returned by a function from this library
+// and must not have been freed already.
+void adblock_free_string(char *s);
+
+#endif /* ADBLOCK_CORE_H */
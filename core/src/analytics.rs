@@ -1,7 +1,25 @@
-use std::sync::{Arc, Mutex};
-use std::collections::HashMap;
+use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
-use chrono::{DateTime, Utc, Duration};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Events buffered in memory before a flush is forced
+const MAX_BUFFERED_EVENTS: usize = 1000;
+
+/// Default on-disk budget for pending batches
+pub const DEFAULT_MAX_DISK_BYTES: u64 = 1024 * 1024;
+
+/// Requests between checks of the average match time
+pub const PERFORMANCE_CHECK_INTERVAL: u64 = 1000;
+
+/// Average match time above which a performance warning is tracked
+pub const SLOW_MATCH_THRESHOLD_NS: u64 = 1_000_000;
+
+const BATCH_PREFIX: &str = "batch_";
+const BATCH_EXTENSION: &str = "ndjson";
 
 /// Privacy-focused analytics system
 /// Only collects anonymous usage data to improve the app
@@ -11,9 +29,24 @@ pub struct Analytics {
     /// Session information
     session: Arc<Mutex<SessionInfo>>,
     /// Whether analytics is enabled
-    enabled: bool,
+    enabled: AtomicBool,
     /// Anonymous user ID
     anonymous_id: String,
+    /// Directory for flushed batches awaiting upload
+    batch_dir: Option<PathBuf>,
+    /// Oldest batches are dropped once pending batches exceed this size
+    max_disk_bytes: u64,
+}
+
+/// A flushed batch of events, one JSON object per line
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnalyticsBatch {
+    /// Identifier passed to [`Analytics::mark_uploaded`]
+    pub id: String,
+    /// Number of events in the batch
+    pub event_count: usize,
+    /// Newline-delimited JSON events
+    pub ndjson: String,
 }
 
 #[derive(Debug, Clone)]
@@ -67,6 +100,7 @@ struct SessionInfo {
 }
 
 #[derive(Debug, Clone)]
+#[allow(dead_code)]
 enum MetricValue {
     Count(u64),
     Sum(f64),
@@ -89,27 +123,48 @@ impl Analytics {
                 last_activity: Utc::now(),
                 properties: HashMap::new(),
             })),
-            enabled: true,
+            enabled: AtomicBool::new(true),
             anonymous_id: Self::generate_anonymous_id(),
+            batch_dir: None,
+            max_disk_bytes: DEFAULT_MAX_DISK_BYTES,
+        }
+    }
+
+    /// Create an instance flushing batches to `dir`
+    pub fn with_batch_dir<P: AsRef<Path>>(dir: P) -> Self {
+        Self {
+            batch_dir: Some(dir.as_ref().to_path_buf()),
+            ..Self::new()
         }
     }
 
+    /// Limit the total size of pending batches on disk
+    pub fn set_max_disk_bytes(&mut self, max_disk_bytes: u64) {
+        self.max_disk_bytes = max_disk_bytes;
+    }
+
     /// Enable or disable analytics
-    pub fn set_enabled(&mut self, enabled: bool) {
-        self.enabled = enabled;
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::SeqCst);
         if !enabled {
             // Clear all data when disabled
-            if let Ok(mut store) = self.events.lock() {
-                store.events.clear();
-                store.metrics.clear();
-                store.daily_active.clear();
-            }
+            self.clear();
         }
     }
 
+    /// Whether events are being recorded
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::SeqCst)
+    }
+
     /// Track an event
-    pub fn track_event(&self, name: &str, category: EventCategory, properties: HashMap<String, serde_json::Value>) {
-        if !self.enabled {
+    pub fn track_event(
+        &self,
+        name: &str,
+        category: EventCategory,
+        properties: HashMap<String, serde_json::Value>,
+    ) {
+        if !self.is_enabled() {
             return;
         }
 
@@ -127,9 +182,9 @@ impl Analytics {
             session_id,
         };
 
-        if let Ok(mut store) = self.events.lock() {
+        let buffer_full = if let Ok(mut store) = self.events.lock() {
             // Add to events buffer
-            if store.events.len() < 1000 {
+            if store.events.len() < MAX_BUFFERED_EVENTS {
                 store.events.push(event);
             }
 
@@ -137,6 +192,15 @@ impl Analytics {
             if let Ok(mut session) = self.session.lock() {
                 session.last_activity = Utc::now();
             }
+            store.events.len() >= MAX_BUFFERED_EVENTS
+        } else {
+            false
+        };
+
+        if buffer_full && self.batch_dir.is_some() {
+            if let Err(e) = self.flush() {
+                tracing::warn!("Failed to flush analytics events: {e}");
+            }
         }
     }
 
@@ -166,13 +230,16 @@ impl Analytics {
 
     /// Record a metric value
     pub fn record_metric(&self, name: &str, value: f64) {
-        if !self.enabled {
+        if !self.is_enabled() {
             return;
         }
 
         if let Ok(mut store) = self.events.lock() {
-            let metric = store.metrics.entry(name.to_string()).or_insert(MetricValue::Count(0));
-            
+            let metric = store
+                .metrics
+                .entry(name.to_string())
+                .or_insert(MetricValue::Count(0));
+
             match metric {
                 MetricValue::Count(count) => {
                     *count += 1;
@@ -211,14 +278,18 @@ impl Analytics {
 
         // Track session start
         self.track_event("session_start", EventCategory::Lifecycle, HashMap::new());
-        
+
         // Update daily active user
         if let Ok(mut store) = self.events.lock() {
-            store.daily_active.insert(self.anonymous_id.clone(), Utc::now());
-            
+            store
+                .daily_active
+                .insert(self.anonymous_id.clone(), Utc::now());
+
             // Clean up old entries (older than 30 days)
             let cutoff = Utc::now() - Duration::days(30);
-            store.daily_active.retain(|_, timestamp| *timestamp > cutoff);
+            store
+                .daily_active
+                .retain(|_, timestamp| *timestamp > cutoff);
         }
     }
 
@@ -228,7 +299,7 @@ impl Analytics {
             let duration = (session.last_activity - session.start_time).num_seconds();
             let mut properties = HashMap::new();
             properties.insert("duration_seconds".to_string(), serde_json::json!(duration));
-            
+
             drop(session); // Release lock before tracking event
             self.track_event("session_end", EventCategory::Lifecycle, properties);
         }
@@ -248,7 +319,10 @@ impl Analytics {
                     EventCategory::Error => "error",
                     EventCategory::Feature => "feature",
                 };
-                *summary.events_by_category.entry(category_name.to_string()).or_insert(0) += 1;
+                *summary
+                    .events_by_category
+                    .entry(category_name.to_string())
+                    .or_insert(0) += 1;
             }
 
             // Get metric summaries
@@ -292,23 +366,128 @@ impl Analytics {
     /// Export events for analysis
     pub fn export_events(&self, limit: usize) -> Vec<AnalyticsEvent> {
         if let Ok(store) = self.events.lock() {
-            store.events.iter()
-                .rev()
-                .take(limit)
-                .cloned()
-                .collect()
+            store.events.iter().rev().take(limit).cloned().collect()
         } else {
             Vec::new()
         }
     }
 
-    /// Clear all analytics data
+    /// Clear all analytics data, including pending batches
     pub fn clear(&self) {
         if let Ok(mut store) = self.events.lock() {
             store.events.clear();
             store.metrics.clear();
             store.daily_active.clear();
         }
+        for (path, _) in self.pending_batches() {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    /// Write buffered events to a new batch file
+    ///
+    /// Returns the batch id, or `None` if nothing was buffered. The oldest
+    /// batches are deleted afterwards if the disk budget is exceeded.
+    pub fn flush(&self) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let dir = self
+            .batch_dir
+            .as_ref()
+            .ok_or("No analytics batch directory configured")?;
+        let mut store = self.events.lock().map_err(|_| "Analytics lock poisoned")?;
+        if store.events.is_empty() {
+            return Ok(None);
+        }
+
+        let mut ndjson = String::new();
+        for event in &store.events {
+            ndjson.push_str(&serde_json::to_string(event)?);
+            ndjson.push('\n');
+        }
+        // Timestamp and sequence so names sort oldest first
+        static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+        let id = format!(
+            "{BATCH_PREFIX}{}_{:06}",
+            Utc::now().format("%Y%m%d%H%M%S%3f"),
+            SEQUENCE.fetch_add(1, Ordering::SeqCst) % 1_000_000
+        );
+        fs::create_dir_all(dir)?;
+        fs::write(dir.join(format!("{id}.{BATCH_EXTENSION}")), ndjson)?;
+        store.events.clear();
+        drop(store);
+
+        self.enforce_disk_budget();
+        Ok(Some(id))
+    }
+
+    /// Oldest batch awaiting upload
+    ///
+    /// Buffered events are flushed first if no batch is pending. The batch
+    /// stays on disk until [`Analytics::mark_uploaded`] is called.
+    pub fn export_batch(&self) -> Result<Option<AnalyticsBatch>, Box<dyn std::error::Error>> {
+        if self.pending_batches().is_empty() {
+            self.flush()?;
+        }
+        let Some((path, _)) = self.pending_batches().into_iter().next() else {
+            return Ok(None);
+        };
+
+        let ndjson = fs::read_to_string(&path)?;
+        Ok(Some(AnalyticsBatch {
+            id: batch_id(&path).unwrap_or_default().to_string(),
+            event_count: ndjson.lines().count(),
+            ndjson,
+        }))
+    }
+
+    /// Delete an uploaded batch, returning whether it was pending
+    pub fn mark_uploaded(&self, id: &str) -> bool {
+        self.pending_batches()
+            .into_iter()
+            .find(|(path, _)| batch_id(path) == Some(id))
+            .is_some_and(|(path, _)| fs::remove_file(path).is_ok())
+    }
+
+    /// Pending batch files and their sizes, oldest first
+    fn pending_batches(&self) -> Vec<(PathBuf, u64)> {
+        let Some(entries) = self
+            .batch_dir
+            .as_ref()
+            .and_then(|dir| fs::read_dir(dir).ok())
+        else {
+            return Vec::new();
+        };
+        let mut batches: Vec<(PathBuf, u64)> = entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| batch_id(path).is_some())
+            .map(|path| {
+                let size = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+                (path, size)
+            })
+            .collect();
+        batches.sort();
+        batches
+    }
+
+    /// Delete the oldest batches until pending batches fit the budget
+    ///
+    /// The newest batch is always kept.
+    fn enforce_disk_budget(&self) {
+        let mut batches = self.pending_batches();
+        let mut total: u64 = batches.iter().map(|(_, size)| size).sum();
+        batches.pop();
+        for (path, size) in batches {
+            if total <= self.max_disk_bytes {
+                break;
+            }
+            tracing::warn!(
+                "Analytics disk budget exceeded, dropping {}",
+                path.display()
+            );
+            if fs::remove_file(&path).is_ok() {
+                total -= size;
+            }
+        }
     }
 
     /// Generate anonymous ID based on device characteristics
@@ -319,6 +498,22 @@ impl Analytics {
     }
 }
 
+/// Id of a batch file, or `None` for other files
+fn batch_id(path: &Path) -> Option<&str> {
+    if path.extension()? != BATCH_EXTENSION {
+        return None;
+    }
+    path.file_stem()?
+        .to_str()
+        .filter(|stem| stem.starts_with(BATCH_PREFIX))
+}
+
+impl Default for Analytics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct AnalyticsSummary {
     pub total_events: usize,
@@ -331,18 +526,64 @@ pub struct AnalyticsSummary {
 /// Pre-defined analytics events
 pub mod events {
     use super::*;
+    use crate::event_bus::{EngineEvent, EventBus, SubscriptionId};
+
+    /// Record engine events (filter updates, reloads, rule changes, memory
+    /// pressure, crashes)
+    pub fn subscribe_to(analytics: Arc<Analytics>, bus: &EventBus) -> SubscriptionId {
+        bus.subscribe(move |event| match event {
+            EngineEvent::UpdateCompleted {
+                rules_count,
+                duration_ms,
+            } => filter_updated(&analytics, *rules_count, *duration_ms),
+            EngineEvent::MemoryPressure { freed_entries } => {
+                performance_warning(&analytics, "memory_pressure", *freed_entries as f64)
+            }
+            EngineEvent::CrashRecorded { error_type, .. } => {
+                analytics.track_error("crash_recorded", &format!("{error_type:?}"))
+            }
+            EngineEvent::RuleSetUpdated { rule_count } => engine_reloaded(&analytics, *rule_count),
+            EngineEvent::RuleSetPatched {
+                added,
+                removed,
+                rule_count,
+            } => rules_patched(&analytics, *added, *removed, *rule_count),
+        })
+    }
+
+    /// Track the engine being replaced with a new rule set
+    pub fn engine_reloaded(analytics: &Analytics, rule_count: usize) {
+        let mut properties = HashMap::new();
+        properties.insert("rule_count".to_string(), serde_json::json!(rule_count));
+        analytics.track_event("engine_reloaded", EventCategory::Lifecycle, properties);
+    }
+
+    /// Track rules added to and removed from the active rule set
+    pub fn rules_patched(analytics: &Analytics, added: usize, removed: usize, rule_count: usize) {
+        let mut properties = HashMap::new();
+        properties.insert("added".to_string(), serde_json::json!(added));
+        properties.insert("removed".to_string(), serde_json::json!(removed));
+        properties.insert("rule_count".to_string(), serde_json::json!(rule_count));
+        analytics.track_event("rule_count_changed", EventCategory::Action, properties);
+    }
 
     /// Track app launch
     pub fn app_launch(analytics: &Analytics, launch_time_ms: u64) {
         let mut properties = HashMap::new();
-        properties.insert("launch_time_ms".to_string(), serde_json::json!(launch_time_ms));
+        properties.insert(
+            "launch_time_ms".to_string(),
+            serde_json::json!(launch_time_ms),
+        );
         analytics.track_event("app_launch", EventCategory::Lifecycle, properties);
     }
 
     /// Track VPN connection
     pub fn vpn_connected(analytics: &Analytics, connection_time_ms: u64) {
         let mut properties = HashMap::new();
-        properties.insert("connection_time_ms".to_string(), serde_json::json!(connection_time_ms));
+        properties.insert(
+            "connection_time_ms".to_string(),
+            serde_json::json!(connection_time_ms),
+        );
         analytics.track_event("vpn_connected", EventCategory::Action, properties);
     }
 
@@ -382,7 +623,11 @@ pub mod events {
         let mut properties = HashMap::new();
         properties.insert("metric".to_string(), serde_json::json!(metric));
         properties.insert("value".to_string(), serde_json::json!(value));
-        analytics.track_event("performance_warning", EventCategory::Performance, properties);
+        analytics.track_event(
+            "performance_warning",
+            EventCategory::Performance,
+            properties,
+        );
     }
 }
 
@@ -393,12 +638,12 @@ mod tests {
     #[test]
     fn test_analytics_basic() {
         let analytics = Analytics::new();
-        
+
         // Track some events
         analytics.track_action("test_action");
         analytics.track_feature("test_feature", HashMap::new());
         analytics.track_performance("load_time", 150.0);
-        
+
         // Check summary
         let summary = analytics.get_summary();
         assert_eq!(summary.total_events, 3);
@@ -410,13 +655,13 @@ mod tests {
     #[test]
     fn test_metrics() {
         let analytics = Analytics::new();
-        
+
         // Record some metrics
         analytics.increment_counter("clicks");
         analytics.increment_counter("clicks");
         analytics.record_metric("response_time", 100.0);
         analytics.record_metric("response_time", 200.0);
-        
+
         let summary = analytics.get_summary();
         assert!(summary.metrics.contains_key("clicks"));
         assert!(summary.metrics.contains_key("response_time"));
@@ -424,14 +669,49 @@ mod tests {
 
     #[test]
     fn test_disabled_analytics() {
-        let mut analytics = Analytics::new();
+        let analytics = Analytics::new();
         analytics.set_enabled(false);
-        
+
         // Track events while disabled
         analytics.track_action("test_action");
-        
+
         // Should not record anything
         let summary = analytics.get_summary();
         assert_eq!(summary.total_events, 0);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_batch_export_and_disk_budget() {
+        let dir = std::env::temp_dir().join(format!("adblock_analytics_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let mut analytics = Analytics::with_batch_dir(&dir);
+
+        analytics.track_action("first");
+        analytics.track_action("second");
+        let batch = analytics.export_batch().unwrap().unwrap();
+        assert_eq!(batch.event_count, 2);
+        let event: AnalyticsEvent =
+            serde_json::from_str(batch.ndjson.lines().next().unwrap()).unwrap();
+        assert_eq!(event.name, "first");
+        assert_eq!(analytics.get_summary().total_events, 0);
+
+        // Unacknowledged batches are exported again
+        assert_eq!(analytics.export_batch().unwrap().unwrap().id, batch.id);
+        assert!(analytics.mark_uploaded(&batch.id));
+        assert!(!analytics.mark_uploaded(&batch.id));
+        assert!(analytics.export_batch().unwrap().is_none());
+
+        // Only the newest batch fits a tiny budget
+        analytics.set_max_disk_bytes(1);
+        analytics.track_action("old");
+        analytics.flush().unwrap();
+        analytics.track_action("new");
+        let newest = analytics.flush().unwrap().unwrap();
+        assert_eq!(analytics.export_batch().unwrap().unwrap().id, newest);
+        assert_eq!(analytics.pending_batches().len(), 1);
+
+        analytics.set_enabled(false);
+        assert!(analytics.pending_batches().is_empty());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
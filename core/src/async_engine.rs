@@ -0,0 +1,207 @@
+//! Async engine facade for high-throughput proxies
+//!
+//! [`AsyncAdBlock`] checks URLs against a shared engine or core without
+//! recording statistics on the request path. Statistics updates are sent
+//! over a bounded channel to a background task that applies them in
+//! batches, so tokio tasks handling thousands of requests per second only
+//! pay for the match.
+
+use crate::filter_engine::{BlockDecision, BlockKind, FilterEngine};
+use crate::resource_type::RequestContext;
+use crate::{utils, AdBlockCore, SharedStatistics};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::{mpsc, oneshot};
+
+/// Most updates received from the channel at once
+const MAX_BATCH: usize = 512;
+
+/// Most updates waiting for the statistics task
+pub const UPDATE_QUEUE_CAPACITY: usize = 8192;
+
+/// Message to the statistics task
+enum StatUpdate {
+    Decision {
+        domain: String,
+        size: u64,
+        /// Allowed because the core's blocking is paused
+        paused: bool,
+        blocked: bool,
+        kind: Option<BlockKind>,
+    },
+    /// Answered once every earlier update is applied
+    Flush(oneshot::Sender<()>),
+}
+
+/// What URLs are checked against
+enum Checker {
+    /// Only the engine's rules
+    Engine(parking_lot::RwLock<Arc<FilterEngine>>),
+    /// The core's full decision, with its whitelist, pause and site modes
+    Core(Arc<parking_lot::RwLock<AdBlockCore>>),
+}
+
+/// Engine facade safe to share between tokio tasks
+pub struct AsyncAdBlock {
+    checker: Checker,
+    updates: mpsc::Sender<StatUpdate>,
+    dropped_updates: AtomicU64,
+}
+
+impl AsyncAdBlock {
+    /// Check against `engine`, recording statistics into `statistics`
+    ///
+    /// Only the engine's rules apply, as with the proxy's `FilterEngine`
+    /// checker. Must be called within a tokio runtime, which runs the
+    /// statistics task until the facade is dropped.
+    pub fn new(engine: Arc<FilterEngine>, statistics: Arc<SharedStatistics>) -> Self {
+        Self::with_checker(
+            Checker::Engine(parking_lot::RwLock::new(engine)),
+            statistics,
+        )
+    }
+
+    /// Check through a core, feeding its statistics
+    ///
+    /// Requests get the core's own decision, so its whitelist, pause and
+    /// site modes apply, and engines it swaps in are picked up. Must be
+    /// called within a tokio runtime.
+    pub fn from_core(core: Arc<parking_lot::RwLock<AdBlockCore>>) -> Self {
+        let statistics = core.read().shared_statistics();
+        Self::with_checker(Checker::Core(core), statistics)
+    }
+
+    fn with_checker(checker: Checker, statistics: Arc<SharedStatistics>) -> Self {
+        let (updates, receiver) = mpsc::channel(UPDATE_QUEUE_CAPACITY);
+        tokio::spawn(apply_updates(receiver, statistics));
+        AsyncAdBlock {
+            checker,
+            updates,
+            dropped_updates: AtomicU64::new(0),
+        }
+    }
+
+    /// Check a URL, queueing its statistics update
+    pub fn check_url(&self, url: &str) -> BlockDecision {
+        self.check_url_with_size(url, 0)
+    }
+
+    /// Check a URL of a response of `size` bytes
+    pub fn check_url_with_size(&self, url: &str, size: u64) -> BlockDecision {
+        self.check_request(url, &RequestContext::from_url(url), size)
+    }
+
+    /// Check a request of a known resource type and page
+    ///
+    /// Site modes need the page, so only apply through this call or to
+    /// top-level document requests.
+    pub fn check_request(&self, url: &str, context: &RequestContext, size: u64) -> BlockDecision {
+        let decision = match &self.checker {
+            Checker::Engine(engine) => engine.read().should_block_with_context(url, context),
+            Checker::Core(core) => core.read().should_block_with_context(url, context),
+        };
+        self.queue(url, size, &decision);
+        decision
+    }
+
+    /// Check several URLs against the same engine
+    pub fn check_many<'a>(&self, urls: impl IntoIterator<Item = &'a str>) -> Vec<BlockDecision> {
+        let check = |decide: &dyn Fn(&str) -> BlockDecision| -> Vec<BlockDecision> {
+            urls.into_iter()
+                .map(|url| {
+                    let decision = decide(url);
+                    self.queue(url, 0, &decision);
+                    decision
+                })
+                .collect()
+        };
+        match &self.checker {
+            Checker::Engine(engine) => {
+                let engine = Arc::clone(&engine.read());
+                check(&|url| engine.should_block(url))
+            }
+            Checker::Core(core) => {
+                let core = core.read();
+                check(&|url| core.should_block(url))
+            }
+        }
+    }
+
+    /// Queue a statistics update, dropping it if the task is too far behind
+    fn queue(&self, url: &str, size: u64, decision: &BlockDecision) {
+        let update = StatUpdate::Decision {
+            domain: utils::extract_domain(url),
+            size,
+            paused: decision.reason.as_deref() == Some(crate::PAUSED_REASON),
+            blocked: decision.should_block,
+            kind: decision.kind,
+        };
+        if let Err(mpsc::error::TrySendError::Full(_)) = self.updates.try_send(update) {
+            self.dropped_updates.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Statistics updates dropped because the queue was full
+    pub fn dropped_updates(&self) -> u64 {
+        self.dropped_updates.load(Ordering::Relaxed)
+    }
+
+    /// Wait until the statistics reflect every check queued so far
+    pub async fn flush(&self) {
+        let (done, applied) = oneshot::channel();
+        if self.updates.send(StatUpdate::Flush(done)).await.is_ok() {
+            let _ = applied.await;
+        }
+    }
+
+    /// Check against a new engine, e.g. after the core reloaded its rules
+    ///
+    /// A facade made with [`AsyncAdBlock::from_core`] always checks against
+    /// the core's current engine, so this has no effect on it.
+    pub fn swap_engine(&self, engine: Arc<FilterEngine>) {
+        if let Checker::Engine(current) = &self.checker {
+            *current.write() = engine;
+        }
+    }
+
+    /// Engine URLs are checked against
+    pub fn engine(&self) -> Arc<FilterEngine> {
+        match &self.checker {
+            Checker::Engine(engine) => Arc::clone(&engine.read()),
+            Checker::Core(core) => core.read().engine(),
+        }
+    }
+}
+
+/// Apply queued updates in batches until every sender is dropped
+async fn apply_updates(
+    mut receiver: mpsc::Receiver<StatUpdate>,
+    statistics: Arc<SharedStatistics>,
+) {
+    let mut batch = Vec::with_capacity(MAX_BATCH);
+    while receiver.recv_many(&mut batch, MAX_BATCH).await > 0 {
+        for update in batch.drain(..) {
+            match update {
+                StatUpdate::Decision {
+                    domain,
+                    size,
+                    paused,
+                    blocked,
+                    kind,
+                } => match kind {
+                    // Counted as the core counts them in `track_decision`
+                    _ if paused => statistics.record_paused(&domain, size),
+                    _ if !blocked => statistics.record_allowed(&domain, size),
+                    Some(BlockKind::Malware) => statistics.record_malware(&domain, size),
+                    Some(BlockKind::Category(category)) => {
+                        statistics.record_category_blocked(&domain, size, category)
+                    }
+                    _ => statistics.record_blocked(&domain, size),
+                },
+                StatUpdate::Flush(done) => {
+                    let _ = done.send(());
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,119 @@
+//! Exception audit log
+//!
+//! Records every case where an exception rule overrode a blocking rule, so
+//! users can verify that acceptable-ads style exceptions behave as expected.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// Default number of overrides kept in the audit log
+pub const DEFAULT_AUDIT_CAPACITY: usize = 500;
+
+/// A single exception-over-block override
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ExceptionOverride {
+    pub timestamp: SystemTime,
+    /// URL that was allowed
+    pub url: String,
+    /// Exception rule that allowed the request
+    pub exception_rule: String,
+    /// Blocking rule that would otherwise have matched
+    pub overridden_rule: String,
+}
+
+/// Bounded log of exception overrides
+#[derive(Debug)]
+pub struct ExceptionAuditLog {
+    entries: Mutex<VecDeque<ExceptionOverride>>,
+    capacity: usize,
+}
+
+impl ExceptionAuditLog {
+    /// Create an audit log keeping at most `capacity` entries
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: Mutex::new(VecDeque::with_capacity(capacity.min(1024))),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Record an override, evicting the oldest entry when full
+    pub fn record(&self, url: &str, exception_rule: &str, overridden_rule: &str) {
+        if let Ok(mut entries) = self.entries.lock() {
+            if entries.len() >= self.capacity {
+                entries.pop_front();
+            }
+            entries.push_back(ExceptionOverride {
+                timestamp: SystemTime::now(),
+                url: url.to_string(),
+                exception_rule: exception_rule.to_string(),
+                overridden_rule: overridden_rule.to_string(),
+            });
+        }
+    }
+
+    /// Get the most recent overrides, newest first
+    pub fn entries(&self, limit: usize) -> Vec<ExceptionOverride> {
+        self.entries
+            .lock()
+            .map(|entries| entries.iter().rev().take(limit).cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Number of recorded overrides
+    pub fn len(&self) -> usize {
+        self.entries.lock().map(|e| e.len()).unwrap_or(0)
+    }
+
+    /// Whether the log is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Maximum number of entries kept
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Clear all recorded overrides
+    pub fn clear(&self) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.clear();
+        }
+    }
+
+    /// Export the log to JSON, oldest first
+    pub fn export_json(&self) -> Result<String, Box<dyn std::error::Error>> {
+        let entries: Vec<ExceptionOverride> = self
+            .entries
+            .lock()
+            .map(|entries| entries.iter().cloned().collect())
+            .unwrap_or_default();
+        Ok(serde_json::to_string_pretty(&entries)?)
+    }
+}
+
+impl Default for ExceptionAuditLog {
+    fn default() -> Self {
+        Self::new(DEFAULT_AUDIT_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audit_log_is_bounded() {
+        let log = ExceptionAuditLog::new(2);
+        log.record("https://a.com/ad", "@@||a.com^", "||a.com^");
+        log.record("https://b.com/ad", "@@||b.com^", "||b.com^");
+        log.record("https://c.com/ad", "@@||c.com^", "||c.com^");
+
+        let entries = log.entries(10);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].url, "https://c.com/ad");
+        assert_eq!(entries[1].url, "https://b.com/ad");
+    }
+}
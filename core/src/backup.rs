@@ -27,6 +27,26 @@ pub struct StatisticsBackup {
     pub top_domains: Vec<DomainBackup>,
 }
 
+impl StatisticsBackup {
+    /// Overwrite statistics with the backed up totals and domains
+    pub fn restore_into(&self, statistics: &mut crate::Statistics) {
+        statistics.restore(
+            self.blocked_count,
+            self.allowed_count,
+            self.data_saved,
+            self.top_domains
+                .iter()
+                .map(|domain| crate::DomainStats {
+                    domain: domain.domain.clone(),
+                    count: domain.count,
+                    data_saved: domain.data_saved,
+                    category: Default::default(),
+                })
+                .collect(),
+        );
+    }
+}
+
 /// Domain statistics for backup
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DomainBackup {
@@ -37,7 +57,10 @@ pub struct DomainBackup {
 
 impl BackupData {
     /// Current backup format version
-    pub const CURRENT_VERSION: u32 = 1;
+    ///
+    /// Version 2 backups hold a versioned config; version 1 backups are
+    /// upgraded on import.
+    pub const CURRENT_VERSION: u32 = 2;
 
     /// Create a new backup
     pub fn create(
@@ -72,16 +95,76 @@ impl BackupData {
         Ok(serde_json::to_string_pretty(self)?)
     }
 
-    /// Import backup from JSON string
+    /// Export backup as gzip-compressed JSON
+    pub fn to_gzip(&self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        Ok(crate::compression::gzip_compress(
+            serde_json::to_string(self)?.as_bytes(),
+        ))
+    }
+
+    /// Import backup from gzip-compressed or plain JSON
+    pub fn from_bytes(data: &[u8]) -> Result<Self, Box<dyn std::error::Error>> {
+        if crate::compression::is_gzip(data) {
+            let json = crate::compression::gzip_decompress(data)?;
+            Self::from_json(std::str::from_utf8(&json)?)
+        } else {
+            Self::from_json(std::str::from_utf8(data)?)
+        }
+    }
+
+    /// Import backup from JSON string, upgrading older versions
     pub fn from_json(json: &str) -> Result<Self, Box<dyn std::error::Error>> {
-        let backup: BackupData = serde_json::from_str(json)?;
+        let mut value: serde_json::Value = serde_json::from_str(json)?;
+        let version = value
+            .get("version")
+            .and_then(serde_json::Value::as_u64)
+            .ok_or("Backup has no version")?;
 
         // Validate version compatibility
-        if backup.version > Self::CURRENT_VERSION {
+        if version > u64::from(Self::CURRENT_VERSION) {
             return Err("Backup version is too new".into());
         }
 
-        Ok(backup)
+        if let Some(config) = value.get_mut("config") {
+            crate::Config::migrate_value(config)?;
+        }
+        value["version"] = Self::CURRENT_VERSION.into();
+
+        Ok(serde_json::from_value(value)?)
+    }
+
+    /// Drop custom rules already covered by the subscribed lists
+    ///
+    /// Rules are checked with the importer's settings; the returned report
+    /// lists which rules were duplicate or redundant.
+    pub fn dedup_custom_rules(
+        &mut self,
+        importer: &crate::rule_import::RuleImporter,
+    ) -> crate::rule_import::ImportReport {
+        let rules = std::mem::take(&mut self.custom_rules);
+        let report = importer.import(&[], rules.iter().map(String::as_str));
+        self.custom_rules = report.user_rules.clone();
+        report
+    }
+
+    /// Custom rules and whitelist as filter list text
+    pub fn user_rules_text(&self) -> String {
+        crate::rule_import::export_user_rules(&self.custom_rules, &self.config.whitelist)
+    }
+
+    /// Merge user rules and whitelist text into the backup
+    ///
+    /// Duplicates are skipped and invalid lines reported, see
+    /// `RuleImporter::import_text`.
+    pub fn import_user_rules_text(
+        &mut self,
+        text: &str,
+        importer: &crate::rule_import::RuleImporter,
+    ) -> crate::rule_import::TextImportReport {
+        let report = importer.import_text(&self.custom_rules, &self.config.whitelist, text);
+        self.custom_rules = report.rules.user_rules.clone();
+        self.config.whitelist = report.whitelist.clone();
+        report
     }
 
     /// Validate backup data
@@ -99,23 +182,57 @@ impl BackupData {
         }
 
         // Validate config
-        if self.config.max_memory_mb == 0 {
-            return Err("Invalid configuration in backup".into());
+        if let Err(errors) = self.config.validate() {
+            let messages: Vec<String> = errors.iter().map(ToString::to_string).collect();
+            return Err(format!("Invalid configuration in backup: {}", messages.join("; ")).into());
         }
 
         Ok(())
     }
 }
 
+/// File name prefix of automatic backups
+const AUTO_BACKUP_PREFIX: &str = "adblock_backup_";
+
+/// Blocked request count between automatic backups
+pub const BLOCKED_MILESTONE: u64 = 1000;
+
+/// Which automatic backups to keep
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// Number of most recent backups always kept
+    pub keep_last: usize,
+    /// Weeks for which the newest backup of each week is also kept
+    pub weekly_for_weeks: usize,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            keep_last: 5,
+            weekly_for_weeks: 4,
+        }
+    }
+}
+
 /// Backup manager for handling backup operations
 pub struct BackupManager {
     backup_dir: Option<std::path::PathBuf>,
+    retention: RetentionPolicy,
 }
 
 impl BackupManager {
     /// Create a new backup manager
     pub fn new(backup_dir: Option<std::path::PathBuf>) -> Self {
-        BackupManager { backup_dir }
+        BackupManager {
+            backup_dir,
+            retention: RetentionPolicy::default(),
+        }
+    }
+
+    /// Set the retention policy applied after each automatic backup
+    pub fn set_retention(&mut self, retention: RetentionPolicy) {
+        self.retention = retention;
     }
 
     /// Save backup to file
@@ -184,6 +301,8 @@ impl BackupManager {
     }
 
     /// Create automatic backup with timestamp
+    ///
+    /// Older automatic backups outside the retention policy are pruned.
     pub fn create_auto_backup(
         &self,
         config: crate::Config,
@@ -192,12 +311,69 @@ impl BackupManager {
     ) -> Result<String, Box<dyn std::error::Error>> {
         let backup = BackupData::create(config, custom_rules, statistics);
 
-        // Generate filename with timestamp
-        let timestamp = chrono::Utc::now().format("%Y%m%d_%H%M%S");
-        let filename = format!("adblock_backup_{timestamp}.json");
+        // Generate filename with timestamp, numbered if taken
+        let timestamp = chrono::Utc::now().format("%Y%m%d_%H%M%S_%3f");
+        let mut filename = format!("{AUTO_BACKUP_PREFIX}{timestamp}.json");
+        if let Some(dir) = &self.backup_dir {
+            let mut n = 1;
+            while dir.join(&filename).exists() {
+                filename = format!("{AUTO_BACKUP_PREFIX}{timestamp}_{n}.json");
+                n += 1;
+            }
+        }
 
         self.save_backup(&backup, &filename)?;
+        self.prune()?;
 
         Ok(filename)
     }
+
+    /// Delete automatic backups outside the retention policy
+    ///
+    /// Keeps the `keep_last` newest backups plus the newest backup of each
+    /// of the last `weekly_for_weeks` weeks. Returns the deleted file names.
+    pub fn prune(&self) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let backup_dir = self
+            .backup_dir
+            .as_ref()
+            .ok_or("No backup directory configured")?;
+        let now = chrono::Utc::now().naive_utc();
+
+        let mut weeks_kept = std::collections::HashSet::new();
+        let mut deleted = Vec::new();
+        let auto_backups = self
+            .list_backups()?
+            .into_iter()
+            .filter_map(|name| auto_backup_time(&name).map(|time| (name, time)));
+
+        for (index, (name, time)) in auto_backups.enumerate() {
+            let week = (now - time).num_weeks();
+            let keep_weekly = (0..self.retention.weekly_for_weeks as i64).contains(&week)
+                && weeks_kept.insert(week);
+            if index < self.retention.keep_last || keep_weekly {
+                continue;
+            }
+            std::fs::remove_file(backup_dir.join(&name))?;
+            deleted.push(name);
+        }
+
+        Ok(deleted)
+    }
+
+    /// Load the newest backup that can be read and passes validation
+    pub fn restore_latest(&self) -> Result<BackupData, Box<dyn std::error::Error>> {
+        for filename in self.list_backups()? {
+            match self.load_backup(&filename) {
+                Ok(backup) => return Ok(backup),
+                Err(e) => tracing::warn!("Skipping unreadable backup {filename}: {e}"),
+            }
+        }
+        Err("No usable backup found".into())
+    }
+}
+
+/// Creation time encoded in an automatic backup's file name
+fn auto_backup_time(filename: &str) -> Option<chrono::NaiveDateTime> {
+    let timestamp = filename.strip_prefix(AUTO_BACKUP_PREFIX)?.get(..15)?;
+    chrono::NaiveDateTime::parse_from_str(timestamp, "%Y%m%d_%H%M%S").ok()
 }
@@ -0,0 +1,252 @@
+//! Command line tool for compiling and debugging filter lists
+//!
+//! ```text
+//! adblock-cli compile --lists easylist.txt [--lists more.txt] -o compiled.json
+//! adblock-cli check <url> --lists easylist.txt | --compiled compiled.json
+//! adblock-cli dump <url> --lists easylist.txt
+//! adblock-cli bench --lists easylist.txt [--urls urls.txt] [--iterations 1000]
+//! ```
+
+use adblock_core::compiled::CompiledList;
+use adblock_core::utils::extract_domain;
+use adblock_core::FilterEngine;
+use std::process::ExitCode;
+use std::time::Instant;
+
+const USAGE: &str = "\
+Usage:
+  adblock-cli compile --lists <file>... -o <output>
+  adblock-cli check <url> (--lists <file>... | --compiled <file>)
+  adblock-cli dump <url> (--lists <file>... | --compiled <file>)
+  adblock-cli bench (--lists <file>... | --compiled <file>) [--urls <file>] [--iterations <n>]";
+
+/// URLs used by `bench` when no `--urls` file is given
+const SAMPLE_URLS: &[&str] = &[
+    "https://doubleclick.net/ads/banner.js",
+    "https://www.example.com/index.html",
+    "https://cdn.example.com/static/app.js",
+    "https://googlesyndication.com/pagead/show_ads.js",
+    "https://news.example.org/article?id=42&utm_source=feed",
+];
+
+type CliResult = Result<(), Box<dyn std::error::Error>>;
+
+/// Parsed command line options
+#[derive(Default)]
+struct Options {
+    positional: Vec<String>,
+    lists: Vec<String>,
+    compiled: Option<String>,
+    output: Option<String>,
+    urls: Option<String>,
+    iterations: usize,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut options = Options {
+            iterations: 1000,
+            ..Options::default()
+        };
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .cloned()
+                    .ok_or_else(|| format!("Missing value for {arg}"))
+            };
+            match arg.as_str() {
+                "--lists" | "-l" => options.lists.push(value()?),
+                "--compiled" | "-c" => options.compiled = Some(value()?),
+                "--output" | "-o" => options.output = Some(value()?),
+                "--urls" => options.urls = Some(value()?),
+                "--iterations" | "-n" => options.iterations = value()?.parse()?,
+                flag if flag.starts_with('-') => {
+                    return Err(format!("Unknown option {flag}").into())
+                }
+                _ => options.positional.push(arg.clone()),
+            }
+        }
+
+        Ok(options)
+    }
+
+    /// Concatenated text of every `--lists` file
+    fn read_lists(&self) -> Result<String, Box<dyn std::error::Error>> {
+        if self.lists.is_empty() {
+            return Err("No filter lists given (use --lists)".into());
+        }
+        let mut content = String::new();
+        for path in &self.lists {
+            let list =
+                std::fs::read_to_string(path).map_err(|e| format!("Failed to read {path}: {e}"))?;
+            content.push_str(&list);
+            content.push('\n');
+        }
+        Ok(content)
+    }
+
+    /// Build the engine from `--compiled` or `--lists`
+    fn load_engine(&self) -> Result<FilterEngine, Box<dyn std::error::Error>> {
+        match &self.compiled {
+            Some(path) => CompiledList::from_json(&std::fs::read_to_string(path)?)?.to_engine(),
+            None => FilterEngine::from_filter_list(&self.read_lists()?),
+        }
+    }
+
+    fn url(&self) -> Result<&str, Box<dyn std::error::Error>> {
+        self.positional
+            .first()
+            .map(String::as_str)
+            .ok_or_else(|| "Missing URL".into())
+    }
+}
+
+fn compile(options: &Options) -> CliResult {
+    let output = options.output.as_ref().ok_or("Missing --output")?;
+    let compiled = CompiledList::compile(&options.read_lists()?);
+    std::fs::write(output, compiled.to_json()?)?;
+    println!("Compiled {} rules into {}", compiled.rules.len(), output);
+    if compiled.cancelled_rules > 0 {
+        println!(
+            "Cancelled {} rules with $badfilter",
+            compiled.cancelled_rules
+        );
+    }
+    Ok(())
+}
+
+fn check(options: &Options) -> CliResult {
+    let url = options.url()?;
+    let engine = options.load_engine()?;
+    let decision = engine.should_block(url);
+
+    if decision.should_block {
+        println!("BLOCKED {url}");
+    } else {
+        println!("ALLOWED {url}");
+    }
+    if let Some(rule) = engine.matching_rule(url) {
+        println!("  rule: {rule}");
+    }
+    if let Some(resource) = decision.redirect_resource {
+        println!("  redirect: {resource}");
+    }
+    Ok(())
+}
+
+fn dump(options: &Options) -> CliResult {
+    let url = options.url()?;
+    let engine = options.load_engine()?;
+    let host = extract_domain(url);
+    let domain = host.split(':').next().unwrap_or(&host);
+
+    let decision = engine.should_block(url);
+    println!("url: {url}");
+    println!("blocked: {}", decision.should_block);
+    println!(
+        "network rule: {}",
+        engine.matching_rule(url).as_deref().unwrap_or("-")
+    );
+    println!(
+        "redirect: {}",
+        decision.redirect_resource.as_deref().unwrap_or("-")
+    );
+    println!(
+        "rewritten url: {}",
+        engine.rewrite_url(url).as_deref().unwrap_or("-")
+    );
+    for directive in engine.get_csp_directives(url, domain) {
+        println!("csp: {directive}");
+    }
+
+    let cosmetic = engine.get_cosmetic_result(url);
+    if cosmetic.generichide {
+        println!("generichide: true");
+    }
+    if cosmetic.specifichide {
+        println!("specifichide: true");
+    }
+    for selector in &cosmetic.hide_selectors {
+        println!("hide: {selector}");
+    }
+    for filter in engine.procedural_filters().filters_for_domain(domain) {
+        println!("procedural: {}", filter.selector);
+    }
+    for rule in engine
+        .scriptlets()
+        .rules()
+        .iter()
+        .filter(|rule| rule.applies_to(domain))
+    {
+        println!("scriptlet: {}", rule.name);
+    }
+    Ok(())
+}
+
+fn bench(options: &Options) -> CliResult {
+    let started = Instant::now();
+    let engine = options.load_engine()?;
+    let load_time = started.elapsed();
+    let stats = engine.get_pattern_stats();
+
+    let urls: Vec<String> = match &options.urls {
+        Some(path) => std::fs::read_to_string(path)?
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::to_string)
+            .collect(),
+        None => SAMPLE_URLS.iter().map(|url| url.to_string()).collect(),
+    };
+    if urls.is_empty() {
+        return Err("No URLs to benchmark".into());
+    }
+
+    let started = Instant::now();
+    let mut blocked = 0usize;
+    for _ in 0..options.iterations {
+        for url in &urls {
+            if engine.should_block(url).should_block {
+                blocked += 1;
+            }
+        }
+    }
+    let elapsed = started.elapsed();
+    let checks = (options.iterations * urls.len()).max(1);
+
+    println!("compiled patterns: {}", stats.compiled_patterns);
+    println!("untokenized rules: {}", stats.untokenized_rules);
+    println!("load time: {:.2} ms", load_time.as_secs_f64() * 1000.0);
+    println!("checks: {checks} ({blocked} blocked)");
+    println!(
+        "average: {:.0} ns/url",
+        elapsed.as_nanos() as f64 / checks as f64
+    );
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let Some((command, rest)) = args.split_first() else {
+        eprintln!("{USAGE}");
+        return ExitCode::from(2);
+    };
+
+    let result = Options::parse(rest).and_then(|options| match command.as_str() {
+        "compile" => compile(&options),
+        "check" => check(&options),
+        "dump" => dump(&options),
+        "bench" => bench(&options),
+        _ => Err(format!("Unknown command {command}\n{USAGE}").into()),
+    });
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::from(2)
+        }
+    }
+}
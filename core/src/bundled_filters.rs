@@ -0,0 +1,20 @@
+//! Filter list bundled with the crate
+//!
+//! A gzip snapshot of common ad and tracker domains is embedded (with the
+//! `bundled-filters` feature) so blocking works right after install,
+//! before the first list download or when the device is offline.
+
+use crate::compression;
+
+/// Gzip-compressed snapshot bundled with the crate
+const EMBEDDED_LIST: &[u8] = include_bytes!("../data/bundled_filters.txt.gz");
+
+/// Source name of the bundled rules in match explanations
+pub const LIST_NAME: &str = "bundled";
+
+/// Text of the bundled filter list
+pub fn filter_list() -> String {
+    let content =
+        compression::gzip_decompress(EMBEDDED_LIST).expect("bundled filter list is valid gzip");
+    String::from_utf8(content).expect("bundled filter list is UTF-8")
+}
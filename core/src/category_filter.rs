@@ -0,0 +1,157 @@
+//! Content category blocking
+//!
+//! Parental-control style blocking of whole categories of sites (adult,
+//! gambling, social networks, ...) driven by per-category domain lists.
+//! Lists are loaded for any number of categories, but only the enabled
+//! categories block. The same filter can be shared by the `FilterEngine`
+//! and the DNS-level `NetworkFilter`.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+/// Category of site content that can be blocked as a whole
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Enum))]
+#[serde(rename_all = "snake_case")]
+pub enum ContentCategory {
+    Adult,
+    Gambling,
+    SocialNetworks,
+    Dating,
+    Gaming,
+    Streaming,
+}
+
+/// Domain lists per content category, with the categories to block
+#[derive(Debug, Clone, Default)]
+pub struct CategoryFilter {
+    domains: BTreeMap<ContentCategory, HashSet<String>>,
+    enabled: BTreeSet<ContentCategory>,
+}
+
+impl CategoryFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the domains of a category list, returning how many were added
+    ///
+    /// Plain domains, hosts-file lines (`0.0.0.0 example.com`) and `||domain^`
+    /// rules are understood; `#` and `!` comments are skipped. Loading a
+    /// list does not enable its category.
+    pub fn load_list(&mut self, category: ContentCategory, content: &str) -> usize {
+        let domains = self.domains.entry(category).or_default();
+        let before = domains.len();
+        domains.extend(content.lines().filter_map(parse_list_line));
+        domains.len() - before
+    }
+
+    /// Block or stop blocking a category
+    pub fn set_enabled(&mut self, category: ContentCategory, enabled: bool) {
+        if enabled {
+            self.enabled.insert(category);
+        } else {
+            self.enabled.remove(&category);
+        }
+    }
+
+    pub fn is_enabled(&self, category: ContentCategory) -> bool {
+        self.enabled.contains(&category)
+    }
+
+    /// Categories being blocked
+    pub fn enabled_categories(&self) -> impl Iterator<Item = ContentCategory> + '_ {
+        self.enabled.iter().copied()
+    }
+
+    /// Number of listed domains across all categories
+    pub fn len(&self) -> usize {
+        self.domains.values().map(HashSet::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Enabled category a host or one of its parent domains is listed in
+    pub fn blocked_category(&self, host: &str) -> Option<ContentCategory> {
+        if self.enabled.is_empty() {
+            return None;
+        }
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        let mut candidate = host.as_str();
+        loop {
+            let listed = self.enabled.iter().find(|category| {
+                self.domains
+                    .get(category)
+                    .is_some_and(|domains| domains.contains(candidate))
+            });
+            if let Some(&category) = listed {
+                return Some(category);
+            }
+            candidate = candidate.split_once('.')?.1;
+        }
+    }
+
+    /// Estimated heap memory in bytes
+    pub fn memory_usage(&self) -> usize {
+        self.domains
+            .values()
+            .flatten()
+            .map(|domain| domain.len() + std::mem::size_of::<String>())
+            .sum()
+    }
+}
+
+/// Domain of a category list line
+fn parse_list_line(line: &str) -> Option<String> {
+    let line = line.split('#').next().unwrap_or("").trim();
+    if line.is_empty() || line.starts_with('!') {
+        return None;
+    }
+    // Hosts-file lines put the domain after the address
+    let domain = line.split_whitespace().last()?;
+    let domain = domain
+        .strip_prefix("||")
+        .map_or(domain, |rule| rule.trim_end_matches('^'));
+    let domain = domain.trim_matches('.').to_ascii_lowercase();
+    let valid = domain.contains('.')
+        && domain
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-');
+    valid.then_some(domain)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_list_line() {
+        assert_eq!(
+            parse_list_line("0.0.0.0 Casino.example # comment").as_deref(),
+            Some("casino.example")
+        );
+        assert_eq!(
+            parse_list_line("||bet.example^").as_deref(),
+            Some("bet.example")
+        );
+        assert_eq!(parse_list_line("! Title: Gambling"), None);
+        assert_eq!(parse_list_line("localhost"), None);
+    }
+
+    #[test]
+    fn test_only_enabled_categories_block() {
+        let mut filter = CategoryFilter::new();
+        filter.load_list(ContentCategory::Gambling, "casino.example");
+        filter.load_list(ContentCategory::SocialNetworks, "social.example");
+        filter.set_enabled(ContentCategory::Gambling, true);
+
+        assert_eq!(
+            filter.blocked_category("www.casino.example"),
+            Some(ContentCategory::Gambling)
+        );
+        assert_eq!(filter.blocked_category("social.example"), None);
+        assert_eq!(filter.blocked_category("example"), None);
+    }
+}
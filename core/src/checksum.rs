@@ -0,0 +1,143 @@
+//! Filter list checksums
+//!
+//! Supports SHA-256 pinning of downloads and the `! Checksum:` header of
+//! Adblock Plus style lists: the base64 MD5 of the list with the checksum
+//! line removed, carriage returns dropped and blank lines collapsed.
+
+use sha2::{Digest, Sha256};
+
+/// SHA-256 of a download, hex encoded
+pub fn sha256_hex(content: &str) -> String {
+    hex::encode(Sha256::digest(content.as_bytes()))
+}
+
+/// Value of the `! Checksum:` header, if the list has one
+pub fn embedded_checksum(content: &str) -> Option<&str> {
+    content.lines().find_map(checksum_header_value)
+}
+
+/// Compute the `! Checksum:` value of a list
+pub fn list_checksum(content: &str) -> String {
+    let mut normalized = String::with_capacity(content.len());
+    for line in content.split('\n') {
+        let line = line.trim_end_matches('\r');
+        if line.is_empty() || checksum_header_value(line).is_some() {
+            continue;
+        }
+        if !normalized.is_empty() {
+            normalized.push('\n');
+        }
+        normalized.push_str(line);
+    }
+    base64_unpadded(&md5(normalized.as_bytes()))
+}
+
+/// Parse a `! Checksum: <value>` line
+fn checksum_header_value(line: &str) -> Option<&str> {
+    let comment = line.trim().strip_prefix('!')?.trim_start();
+    let rest = comment.get(..8)?;
+    if !rest.eq_ignore_ascii_case("checksum") {
+        return None;
+    }
+    let value =
+        comment[8..].trim_start_matches(|c: char| c.is_whitespace() || c == ':' || c == '-');
+    value.split_whitespace().next()
+}
+
+/// Standard base64 without `=` padding
+fn base64_unpadded(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+        for i in 0..=chunk.len() {
+            encoded.push(ALPHABET[((n >> (18 - 6 * i)) & 0x3f) as usize] as char);
+        }
+    }
+    encoded
+}
+
+/// MD5 digest (RFC 1321), only used for the legacy checksum header
+fn md5(input: &[u8]) -> [u8; 16] {
+    const SHIFTS: [u32; 64] = [
+        7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 5, 9, 14, 20, 5, 9, 14, 20, 5,
+        9, 14, 20, 5, 9, 14, 20, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 6, 10,
+        15, 21, 6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21,
+    ];
+    let constants: Vec<u32> = (0..64)
+        .map(|i: i32| (f64::from(i + 1).sin().abs() * 4294967296.0) as u32)
+        .collect();
+
+    let mut message = input.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((input.len() as u64).wrapping_mul(8)).to_le_bytes());
+
+    let mut state: [u32; 4] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476];
+    for block in message.chunks(64) {
+        let words: Vec<u32> = block
+            .chunks(4)
+            .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+            .collect();
+        let [mut a, mut b, mut c, mut d] = state;
+
+        for i in 0..64 {
+            let (f, g) = match i / 16 {
+                0 => ((b & c) | (!b & d), i),
+                1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                2 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _ => (c ^ (b | !d), (7 * i) % 16),
+            };
+            let rotated = a
+                .wrapping_add(f)
+                .wrapping_add(constants[i])
+                .wrapping_add(words[g])
+                .rotate_left(SHIFTS[i]);
+            a = d;
+            d = c;
+            c = b;
+            b = b.wrapping_add(rotated);
+        }
+
+        state[0] = state[0].wrapping_add(a);
+        state[1] = state[1].wrapping_add(b);
+        state[2] = state[2].wrapping_add(c);
+        state[3] = state[3].wrapping_add(d);
+    }
+
+    let mut digest = [0u8; 16];
+    for (i, word) in state.iter().enumerate() {
+        digest[i * 4..i * 4 + 4].copy_from_slice(&word.to_le_bytes());
+    }
+    digest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_md5_and_list_checksum() {
+        assert_eq!(hex::encode(md5(b"")), "d41d8cd98f00b204e9800998ecf8427e");
+        assert_eq!(
+            hex::encode(md5(b"The quick brown fox jumps over the lazy dog")),
+            "9e107d9d372bb6826bd81d3542a419d6"
+        );
+
+        let list = "[Adblock Plus 2.0]\r\n! Checksum: placeholder\r\n\r\n||ads.com^\n";
+        assert_eq!(embedded_checksum(list), Some("placeholder"));
+        // Same text without the header and blank lines
+        assert_eq!(
+            list_checksum(list),
+            base64_unpadded(&md5(b"[Adblock Plus 2.0]\n||ads.com^"))
+        );
+    }
+}
@@ -0,0 +1,144 @@
+//! Rule set A/B comparison
+//!
+//! Evaluates a URL corpus against two engines (e.g. before and after a list
+//! update) and reports every URL whose verdict changed, together with the
+//! rule responsible on each side.
+
+use crate::filter_engine::FilterEngine;
+use serde::{Deserialize, Serialize};
+
+/// Verdict of one engine for a URL
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Verdict {
+    /// Whether the URL is blocked
+    pub blocked: bool,
+    /// Rule that decided the verdict (`@@` prefix for exceptions)
+    pub rule: Option<String>,
+}
+
+impl Verdict {
+    fn evaluate(engine: &FilterEngine, url: &str) -> Self {
+        let rule = engine.matching_rule(url);
+        Self {
+            blocked: rule.as_deref().is_some_and(|r| !r.starts_with("@@")),
+            rule,
+        }
+    }
+}
+
+/// A URL whose verdict differs between the two engines
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VerdictDiff {
+    pub url: String,
+    pub baseline: Verdict,
+    pub candidate: Verdict,
+}
+
+/// Result of comparing two engines
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ComparisonReport {
+    /// Number of URLs evaluated
+    pub total: usize,
+    /// URLs allowed by the baseline but blocked by the candidate
+    pub newly_blocked: usize,
+    /// URLs blocked by the baseline but allowed by the candidate
+    pub newly_allowed: usize,
+    /// Every differing verdict, in corpus order
+    pub differences: Vec<VerdictDiff>,
+}
+
+impl ComparisonReport {
+    /// Whether both engines agree on every URL
+    pub fn is_identical(&self) -> bool {
+        self.differences.is_empty()
+    }
+
+    /// Export the report as JSON
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+/// Compare the verdicts of `baseline` and `candidate` over a URL corpus
+pub fn compare_engines<'a, I>(
+    baseline: &FilterEngine,
+    candidate: &FilterEngine,
+    urls: I,
+) -> ComparisonReport
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let mut report = ComparisonReport::default();
+
+    for url in urls {
+        report.total += 1;
+
+        let before = Verdict::evaluate(baseline, url);
+        let after = Verdict::evaluate(candidate, url);
+        if before.blocked == after.blocked {
+            continue;
+        }
+
+        if after.blocked {
+            report.newly_blocked += 1;
+        } else {
+            report.newly_allowed += 1;
+        }
+        report.differences.push(VerdictDiff {
+            url: url.to_string(),
+            baseline: before,
+            candidate: after,
+        });
+    }
+
+    report
+}
+
+/// Compare two engines over a corpus with one URL per line
+///
+/// Blank lines and lines starting with `#` are skipped.
+pub fn compare_corpus(
+    baseline: &FilterEngine,
+    candidate: &FilterEngine,
+    corpus: &str,
+) -> ComparisonReport {
+    compare_engines(
+        baseline,
+        candidate,
+        corpus
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#')),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compare_reports_changed_verdicts() {
+        let baseline = FilterEngine::from_filter_list("||ads.com^\n||tracker.com^").unwrap();
+        let candidate =
+            FilterEngine::from_filter_list("||ads.com^\n@@||ads.com/ok.js\n*/banner/*").unwrap();
+
+        let report = compare_corpus(
+            &baseline,
+            &candidate,
+            "# corpus\nhttps://ads.com/x.js\nhttps://ads.com/ok.js\nhttps://tracker.com/t\nhttps://example.com/banner/1.png\n",
+        );
+
+        assert_eq!(report.total, 4);
+        assert_eq!(report.newly_blocked, 1);
+        assert_eq!(report.newly_allowed, 2);
+
+        let ok = &report.differences[0];
+        assert_eq!(ok.url, "https://ads.com/ok.js");
+        assert!(ok.baseline.blocked);
+        assert_eq!(ok.candidate.rule.as_deref(), Some("@@||ads.com/ok.js"));
+
+        let banner = &report.differences[2];
+        assert_eq!(banner.baseline.rule, None);
+        assert_eq!(banner.candidate.rule.as_deref(), Some("*/banner/*"));
+    }
+}
@@ -0,0 +1,381 @@
+//! Compiled filter list formats
+//!
+//! A compiled list holds the rule lines of one or more filter lists with
+//! comments, headers, duplicates and `$badfilter`-cancelled rules removed,
+//! tagged with the format and crate version and a hash of the source text,
+//! so a stale artifact can be detected. Its rules are parsed again when an
+//! engine is built from it.
+//!
+//! A compiled engine goes further and holds the engine's rules as built:
+//! the network rule arena, offsets and flags, the token indices and the
+//! parsed cosmetic, scriptlet and modifier rules. Loading it parses no
+//! rule, which is what [`CompileCache`] keeps on disk for app restarts.
+
+use crate::filter_engine::CompiledRules;
+use crate::filter_list::{apply_badfilters, is_skipped_line};
+use crate::FilterEngine;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// Version of the compiled format
+pub const COMPILED_FORMAT_VERSION: u32 = 1;
+
+/// Version of the compiled engine format
+pub const COMPILED_ENGINE_VERSION: u32 = 1;
+
+/// Subdirectory of the cache directory holding compiled engines
+const COMPILE_CACHE_DIR: &str = "compiled";
+
+/// Most compiled engines kept in the cache
+const MAX_CACHED_ENGINES: usize = 4;
+
+/// Serialized engine input
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CompiledList {
+    /// Compiled format version
+    pub format_version: u32,
+    /// Version of the crate that compiled the list
+    pub crate_version: String,
+    /// SHA-256 of the source text, hex encoded
+    pub source_hash: String,
+    /// Rule lines in source order
+    pub rules: Vec<String>,
+    /// Number of rules cancelled by `$badfilter` rules
+    #[serde(default)]
+    pub cancelled_rules: usize,
+}
+
+impl CompiledList {
+    /// Compile the text of one or more concatenated filter lists
+    ///
+    /// `$badfilter` rules are applied here: they and the rules they cancel
+    /// are left out.
+    pub fn compile(content: &str) -> Self {
+        let mut seen = HashSet::new();
+        let (lines, cancelled_rules) = apply_badfilters(
+            content
+                .lines()
+                .map(str::trim)
+                .filter(|line| !is_skipped_line(line))
+                .filter(|line| seen.insert(*line)),
+        );
+        let rules = lines.into_iter().map(str::to_string).collect();
+
+        CompiledList {
+            format_version: COMPILED_FORMAT_VERSION,
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            source_hash: source_hash(content),
+            rules,
+            cancelled_rules,
+        }
+    }
+
+    /// Export to JSON
+    pub fn to_json(&self) -> Result<String, Box<dyn std::error::Error>> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// Import from JSON, rejecting other format or crate versions
+    pub fn from_json(json: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let compiled: CompiledList = serde_json::from_str(json)?;
+
+        if compiled.format_version != COMPILED_FORMAT_VERSION {
+            return Err("Unsupported compiled list format".into());
+        }
+        if compiled.crate_version != env!("CARGO_PKG_VERSION") {
+            return Err("Compiled list was built by another engine version".into());
+        }
+
+        Ok(compiled)
+    }
+
+    /// Whether the list was compiled from this source text
+    pub fn matches_source(&self, content: &str) -> bool {
+        self.source_hash == source_hash(content)
+    }
+
+    /// Build an engine by parsing the compiled rules
+    pub fn to_engine(&self) -> Result<FilterEngine, Box<dyn std::error::Error>> {
+        FilterEngine::from_filter_list(&self.rules.join("\n"))
+    }
+}
+
+/// Serialized engine, loaded without parsing its rules
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompiledEngine {
+    /// Compiled engine format version
+    pub format_version: u32,
+    /// Version of the crate that compiled the engine
+    pub crate_version: String,
+    /// SHA-256 of the source text, hex encoded
+    pub source_hash: String,
+    rules: CompiledRules,
+}
+
+impl CompiledEngine {
+    /// Compile the text of one or more concatenated filter lists
+    pub fn compile(content: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let engine = FilterEngine::from_filter_list(content)?;
+        Ok(Self::from_engine(&engine, content))
+    }
+
+    /// Store an engine built from `content`
+    pub fn from_engine(engine: &FilterEngine, content: &str) -> Self {
+        CompiledEngine {
+            format_version: COMPILED_ENGINE_VERSION,
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            source_hash: source_hash(content),
+            rules: engine.to_compiled(),
+        }
+    }
+
+    /// Export to JSON
+    pub fn to_json(&self) -> Result<String, Box<dyn std::error::Error>> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// Import from JSON, rejecting other format or crate versions
+    pub fn from_json(json: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        #[derive(Deserialize)]
+        struct Versions {
+            format_version: u32,
+            crate_version: String,
+        }
+
+        // Checked first, as another version's rules may not deserialize
+        let versions: Versions = serde_json::from_str(json)?;
+        if versions.format_version != COMPILED_ENGINE_VERSION {
+            return Err("Unsupported compiled engine format".into());
+        }
+        if versions.crate_version != env!("CARGO_PKG_VERSION") {
+            return Err("Compiled engine was built by another engine version".into());
+        }
+
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Whether the engine was compiled from this source text
+    pub fn matches_source(&self, content: &str) -> bool {
+        self.source_hash == source_hash(content)
+    }
+
+    /// Restore the engine without parsing its rules
+    pub fn into_engine(self) -> FilterEngine {
+        FilterEngine::from_compiled(self.rules)
+    }
+}
+
+/// Compiled engines stored on disk by source hash
+///
+/// Re-creating an engine from list text it has already seen, as on every
+/// app restart, loads the compiled engine instead of parsing the text
+/// again. Entries written by another crate version are recompiled, and
+/// only the most recently used engines are kept.
+#[derive(Debug, Clone)]
+pub struct CompileCache {
+    dir: PathBuf,
+}
+
+impl CompileCache {
+    /// Cache under a platform cache directory
+    pub fn new(cache_dir: impl AsRef<Path>) -> Self {
+        CompileCache {
+            dir: cache_dir.as_ref().join(COMPILE_CACHE_DIR),
+        }
+    }
+
+    fn entry_path(&self, hash: &str) -> PathBuf {
+        self.dir.join(format!("{hash}.json"))
+    }
+
+    /// Cached compiled engine of a source text, if it is current
+    pub fn load(&self, content: &str) -> Option<CompiledEngine> {
+        let path = self.entry_path(&source_hash(content));
+        let compiled = CompiledEngine::from_json(&std::fs::read_to_string(&path).ok()?)
+            .ok()
+            .filter(|compiled| compiled.matches_source(content))?;
+        // Mark the entry as recently used for eviction
+        let _ = std::fs::File::options()
+            .append(true)
+            .open(&path)
+            .and_then(|file| file.set_modified(std::time::SystemTime::now()));
+        Some(compiled)
+    }
+
+    /// Engine for a source text, built and cached on a miss
+    ///
+    /// A cache that can't be written is logged and otherwise ignored.
+    pub fn engine(&self, content: &str) -> Result<FilterEngine, Box<dyn std::error::Error>> {
+        if let Some(compiled) = self.load(content) {
+            return Ok(compiled.into_engine());
+        }
+        let engine = FilterEngine::from_filter_list(content)?;
+        if let Err(e) = self.store(&CompiledEngine::from_engine(&engine, content)) {
+            tracing::warn!("Failed to cache compiled filter engine: {e}");
+        }
+        Ok(engine)
+    }
+
+    /// Write a compiled engine, replacing the entry of its source text
+    pub fn store(&self, compiled: &CompiledEngine) -> Result<(), Box<dyn std::error::Error>> {
+        std::fs::create_dir_all(&self.dir)?;
+        let path = self.entry_path(&compiled.source_hash);
+        let temp_path = path.with_extension("tmp");
+        std::fs::write(&temp_path, compiled.to_json()?)?;
+        std::fs::rename(&temp_path, &path)?;
+        self.evict();
+        Ok(())
+    }
+
+    /// Delete all but the most recently used entries
+    fn evict(&self) {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return;
+        };
+        let mut files: Vec<(std::time::SystemTime, PathBuf)> = entries
+            .filter_map(Result::ok)
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.path())))
+            .collect();
+        files.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+        for (_, path) in files.into_iter().skip(MAX_CACHED_ENGINES) {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+
+    /// Delete every cached engine
+    pub fn clear(&self) -> std::io::Result<()> {
+        match std::fs::remove_dir_all(&self.dir) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// SHA-256 of a filter list's text, hex encoded
+pub fn source_hash(content: &str) -> String {
+    hex::encode(Sha256::digest(content.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compile_roundtrip() {
+        let source = "! Title: Test\n||ads.com^\n\n||ads.com^\nexample.com##.ad";
+        let compiled = CompiledList::compile(source);
+        assert_eq!(compiled.rules, vec!["||ads.com^", "example.com##.ad"]);
+        assert!(compiled.matches_source(source));
+
+        let restored = CompiledList::from_json(&compiled.to_json().unwrap()).unwrap();
+        assert_eq!(restored, compiled);
+
+        let engine = restored.to_engine().unwrap();
+        assert!(engine.should_block("https://ads.com/x").should_block);
+        assert_eq!(engine.get_cosmetic_selectors("example.com"), vec![".ad"]);
+
+        let mut stale = compiled;
+        stale.crate_version = "0.0.0".to_string();
+        assert!(CompiledList::from_json(&stale.to_json().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_compiled_engine_loads_without_parsing() {
+        let source = "||ads.com^\n*/banner/*\n@@||cdn.com/banner/ok\n||cdn.com^$removeparam=/^utm_/\nexample.com##.ad\nexample.com##.post:has-text(Sponsored)";
+        let compiled = CompiledEngine::compile(source).unwrap();
+        assert!(compiled.matches_source(source));
+
+        let engine = CompiledEngine::from_json(&compiled.to_json().unwrap())
+            .unwrap()
+            .into_engine();
+        assert!(engine.should_block("https://ads.com/x").should_block);
+        assert!(
+            engine
+                .should_block("https://site.com/banner/1.png")
+                .should_block
+        );
+        assert!(
+            !engine
+                .should_block("https://cdn.com/banner/ok")
+                .should_block
+        );
+        assert_eq!(
+            engine.rewrite_url("https://cdn.com/a?utm_source=x&id=1"),
+            Some("https://cdn.com/a?id=1".to_string())
+        );
+        assert_eq!(engine.get_cosmetic_selectors("example.com"), vec![".ad"]);
+        assert_eq!(engine.procedural_filters().len(), 1);
+        assert_eq!(engine.get_pattern_stats().untokenized_rules, 0);
+
+        // The stored rules are used as they are, not parsed from list text
+        let json = CompiledEngine::compile("||ads.com^")
+            .unwrap()
+            .to_json()
+            .unwrap();
+        assert_eq!(json.matches("ads.com").count(), 1);
+        let engine = CompiledEngine::from_json(&json.replace("ads.com", "adz.com"))
+            .unwrap()
+            .into_engine();
+        assert!(engine.should_block("https://adz.com/x").should_block);
+        assert!(!engine.should_block("https://ads.com/x").should_block);
+
+        let mut stale = CompiledEngine::compile(source).unwrap();
+        stale.crate_version = "0.0.0".to_string();
+        assert!(CompiledEngine::from_json(&stale.to_json().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_compile_cache() {
+        let dir =
+            std::env::temp_dir().join(format!("adblock_compile_cache_{}", std::process::id()));
+        let cache = CompileCache::new(&dir);
+        let source = "||ads.com^\nexample.com##.ad";
+
+        assert!(cache.load(source).is_none());
+        let engine = cache.engine(source).unwrap();
+        assert!(engine.should_block("https://ads.com/x").should_block);
+        let cached = cache.load(source).expect("engine was cached");
+        assert_eq!(
+            cached.into_engine().get_cosmetic_selectors("example.com"),
+            vec![".ad"]
+        );
+
+        // Entries from another engine version are recompiled
+        let mut stale = CompiledEngine::compile(source).unwrap();
+        stale.crate_version = "0.0.0".to_string();
+        std::fs::write(
+            cache.entry_path(&stale.source_hash),
+            stale.to_json().unwrap(),
+        )
+        .unwrap();
+        assert!(cache.load(source).is_none());
+        cache.engine(source).unwrap();
+        assert_eq!(
+            cache.load(source).unwrap().crate_version,
+            env!("CARGO_PKG_VERSION")
+        );
+
+        for i in 0..MAX_CACHED_ENGINES + 2 {
+            cache.engine(&format!("||ads{i}.com^")).unwrap();
+        }
+        assert_eq!(
+            std::fs::read_dir(&cache.dir).unwrap().count(),
+            MAX_CACHED_ENGINES
+        );
+
+        cache.clear().unwrap();
+        assert!(!dir.join(COMPILE_CACHE_DIR).exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_compile_applies_badfilter() {
+        let source = "||ads.com^\n||ads.com^$script\n||track.com^\n||ads.com^$badfilter\n||ads.com^$script,badfilter";
+        let compiled = CompiledList::compile(source);
+        assert_eq!(compiled.rules, vec!["||track.com^"]);
+        assert_eq!(compiled.cancelled_rules, 2);
+    }
+}
@@ -0,0 +1,244 @@
+//! Gzip compression for exported payloads and downloaded lists
+//!
+//! Wraps raw deflate from `miniz_oxide` in the gzip container (RFC 1952)
+//! so exports can be opened with standard tools. Downloads are decoded
+//! from their `Content-Encoding`, and `.gz`/`.zst` lists are recognized
+//! by their magic number; zstd needs the `zstd` feature.
+
+use miniz_oxide::deflate::compress_to_vec;
+use miniz_oxide::inflate::{decompress_to_vec_with_limit, decompress_to_vec_zlib_with_limit};
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+const METHOD_DEFLATE: u8 = 8;
+const FLAG_HCRC: u8 = 0x02;
+const FLAG_EXTRA: u8 = 0x04;
+const FLAG_NAME: u8 = 0x08;
+const FLAG_COMMENT: u8 = 0x10;
+
+/// Deflate level used for exports
+const COMPRESSION_LEVEL: u8 = 6;
+
+/// Largest decompressed payload accepted, so a small malicious download
+/// can't exhaust memory
+const MAX_DECODED_LEN: usize = 128 * 1024 * 1024;
+
+/// Compression applied to a payload
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Identity,
+    Gzip,
+    /// zlib-wrapped deflate, as sent for `Content-Encoding: deflate`
+    Deflate,
+    Zstd,
+}
+
+impl Encoding {
+    /// Encoding named by a `Content-Encoding` header, or `None` if it is
+    /// not supported (e.g. `br`)
+    ///
+    /// Of several comma-separated encodings the last applied is returned.
+    pub fn from_header(value: &str) -> Option<Self> {
+        let value = value.rsplit(',').next().unwrap_or(value).trim();
+        match value.to_ascii_lowercase().as_str() {
+            "" | "identity" => Some(Encoding::Identity),
+            "gzip" | "x-gzip" => Some(Encoding::Gzip),
+            "deflate" => Some(Encoding::Deflate),
+            "zstd" => Some(Encoding::Zstd),
+            _ => None,
+        }
+    }
+
+    /// Encoding recognized from a payload's magic number
+    ///
+    /// Deflate has none, so it is reported as `Identity`.
+    pub fn sniff(data: &[u8]) -> Self {
+        if is_gzip(data) {
+            Encoding::Gzip
+        } else if data.starts_with(&ZSTD_MAGIC) {
+            Encoding::Zstd
+        } else {
+            Encoding::Identity
+        }
+    }
+}
+
+/// Decode a payload of a known encoding
+pub fn decode(data: &[u8], encoding: Encoding) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    match encoding {
+        Encoding::Identity => Ok(data.to_vec()),
+        Encoding::Gzip => gzip_decompress(data),
+        // Some servers send raw deflate despite the header
+        Encoding::Deflate => decompress_to_vec_zlib_with_limit(data, MAX_DECODED_LEN)
+            .or_else(|_| decompress_to_vec_with_limit(data, MAX_DECODED_LEN))
+            .map_err(|e| format!("Invalid deflate data: {e:?}").into()),
+        Encoding::Zstd => zstd_decompress(data),
+    }
+}
+
+/// Decode a downloaded filter list
+///
+/// The `Content-Encoding` header is applied first; a body that is still
+/// gzip or zstd, such as a `.gz` list, is then decoded by its magic
+/// number. Filter lists are text, so they never start with either.
+pub fn decode_download(
+    data: &[u8],
+    content_encoding: Option<&str>,
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let data = match content_encoding {
+        Some(header) => {
+            let encoding = Encoding::from_header(header)
+                .ok_or_else(|| format!("Unsupported Content-Encoding: {header}"))?;
+            decode(data, encoding)?
+        }
+        None => data.to_vec(),
+    };
+    match Encoding::sniff(&data) {
+        Encoding::Identity => Ok(data),
+        encoding => decode(&data, encoding),
+    }
+}
+
+#[cfg(feature = "zstd")]
+fn zstd_decompress(data: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    use std::io::Read;
+
+    let decoder = ruzstd::decoding::StreamingDecoder::new(data)
+        .map_err(|e| format!("Invalid zstd stream: {e}"))?;
+    let mut decoded = Vec::new();
+    decoder
+        .take(MAX_DECODED_LEN as u64 + 1)
+        .read_to_end(&mut decoded)?;
+    if decoded.len() > MAX_DECODED_LEN {
+        return Err("Decompressed payload is too large".into());
+    }
+    Ok(decoded)
+}
+
+#[cfg(not(feature = "zstd"))]
+fn zstd_decompress(_data: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    Err("zstd support is not enabled".into())
+}
+
+/// Compress bytes into a gzip stream
+pub fn gzip_compress(data: &[u8]) -> Vec<u8> {
+    let deflated = compress_to_vec(data, COMPRESSION_LEVEL);
+
+    let mut gzip = Vec::with_capacity(deflated.len() + 18);
+    gzip.extend_from_slice(&GZIP_MAGIC);
+    gzip.push(METHOD_DEFLATE);
+    // No flags, no mtime, no extra flags, unknown OS
+    gzip.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0xff]);
+    gzip.extend_from_slice(&deflated);
+    gzip.extend_from_slice(&crc32(data).to_le_bytes());
+    gzip.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    gzip
+}
+
+/// Decompress a gzip stream, checking its CRC and length
+pub fn gzip_decompress(gzip: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    if gzip.len() < 18 || gzip[..2] != GZIP_MAGIC || gzip[2] != METHOD_DEFLATE {
+        return Err("Not a gzip stream".into());
+    }
+    let flags = gzip[3];
+    let mut pos = 10;
+
+    if flags & FLAG_EXTRA != 0 {
+        let len = usize::from(u16::from_le_bytes([
+            *gzip.get(pos).ok_or("Truncated gzip header")?,
+            *gzip.get(pos + 1).ok_or("Truncated gzip header")?,
+        ]));
+        pos += 2 + len;
+    }
+    for flag in [FLAG_NAME, FLAG_COMMENT] {
+        if flags & flag != 0 {
+            let end = gzip[pos.min(gzip.len())..]
+                .iter()
+                .position(|&b| b == 0)
+                .ok_or("Truncated gzip header")?;
+            pos += end + 1;
+        }
+    }
+    if flags & FLAG_HCRC != 0 {
+        pos += 2;
+    }
+    if pos + 8 > gzip.len() {
+        return Err("Truncated gzip stream".into());
+    }
+
+    let (body, trailer) = gzip[pos..].split_at(gzip.len() - pos - 8);
+    let data = decompress_to_vec_with_limit(body, MAX_DECODED_LEN)
+        .map_err(|e| format!("Invalid deflate data: {e:?}"))?;
+
+    let expected_crc = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+    let expected_len = u32::from_le_bytes([trailer[4], trailer[5], trailer[6], trailer[7]]);
+    if crc32(&data) != expected_crc || data.len() as u32 != expected_len {
+        return Err("Gzip checksum mismatch".into());
+    }
+    Ok(data)
+}
+
+/// Whether bytes start with the gzip magic number
+pub fn is_gzip(data: &[u8]) -> bool {
+    data.starts_with(&GZIP_MAGIC)
+}
+
+/// CRC-32 (IEEE) as used by gzip
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gzip_roundtrip() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+
+        let data = "||ads.com^\n".repeat(100);
+        let gzip = gzip_compress(data.as_bytes());
+        assert!(is_gzip(&gzip));
+        assert!(gzip.len() < data.len());
+        assert_eq!(gzip_decompress(&gzip).unwrap(), data.as_bytes());
+
+        let mut corrupted = gzip.clone();
+        let last = corrupted.len() - 5;
+        corrupted[last] ^= 0xff;
+        assert!(gzip_decompress(&corrupted).is_err());
+    }
+
+    #[test]
+    fn test_decode_download() {
+        let list = b"! Title: Ads\n||ads.com^\n";
+        let gzip = gzip_compress(list);
+        let zlib = miniz_oxide::deflate::compress_to_vec_zlib(list, 6);
+
+        assert_eq!(decode_download(list, None).unwrap(), list);
+        assert_eq!(decode_download(&gzip, None).unwrap(), list);
+        assert_eq!(decode_download(&gzip, Some("gzip")).unwrap(), list);
+        assert_eq!(decode_download(&zlib, Some("deflate")).unwrap(), list);
+        assert!(decode_download(list, Some("br")).is_err());
+        assert_eq!(Encoding::from_header("deflate, gzip"), Some(Encoding::Gzip));
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_decode_zstd_download() {
+        let list = "||ads.com^\n".repeat(100);
+        let zstd = ruzstd::encoding::compress_to_vec(
+            list.as_bytes(),
+            ruzstd::encoding::CompressionLevel::Fastest,
+        );
+        assert_eq!(Encoding::sniff(&zstd), Encoding::Zstd);
+        assert_eq!(decode_download(&zstd, None).unwrap(), list.as_bytes());
+    }
+}
@@ -0,0 +1,211 @@
+//! Config validation and migration
+//!
+//! Persisted configs carry a `version`. Older versions are upgraded one
+//! step at a time on load, and [`Config::validate`] reports every invalid
+//! field instead of silently falling back to defaults.
+
+use crate::Config;
+use serde::Serialize;
+use serde_json::Value;
+
+/// Version of the persisted config format
+pub const CONFIG_VERSION: u32 = 2;
+
+/// Allowed `max_memory_mb` range
+pub const MEMORY_LIMIT_MB: (usize, usize) = (1, 2048);
+
+/// Allowed `update_interval` range in seconds (15 minutes to 30 days)
+pub const UPDATE_INTERVAL_SECS: (u64, u64) = (15 * 60, 30 * 24 * 60 * 60);
+
+/// An invalid config
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ConfigError {
+    /// The JSON could not be parsed
+    Malformed { message: String },
+    /// The config was written by a newer version
+    UnsupportedVersion { version: u32 },
+    /// `max_memory_mb` is outside [`MEMORY_LIMIT_MB`]
+    MemoryOutOfRange {
+        value: usize,
+        min: usize,
+        max: usize,
+    },
+    /// `update_interval` is outside [`UPDATE_INTERVAL_SECS`]
+    IntervalOutOfRange { value: u64, min: u64, max: u64 },
+    /// A filter list URL is not an http(s) URL
+    InvalidUrl { url: String },
+    /// `list_presets` names a list missing from the catalog
+    UnknownListPreset { id: String },
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Malformed { message } => write!(f, "Malformed config: {message}"),
+            ConfigError::UnsupportedVersion { version } => {
+                write!(f, "Config version {version} is not supported")
+            }
+            ConfigError::MemoryOutOfRange { value, min, max } => {
+                write!(f, "max_memory_mb {value} is outside {min}..={max}")
+            }
+            ConfigError::IntervalOutOfRange { value, min, max } => {
+                write!(f, "update_interval {value}s is outside {min}..={max}")
+            }
+            ConfigError::InvalidUrl { url } => write!(f, "Invalid filter list URL: {url}"),
+            ConfigError::UnknownListPreset { id } => write!(f, "Unknown filter list preset: {id}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Upgrade a config of `version` to the next version
+type Migration = fn(&mut serde_json::Map<String, Value>);
+
+/// Migrations indexed by the version they upgrade from, starting at 1
+const MIGRATIONS: &[Migration] = &[migrate_v1_to_v2];
+
+/// v1 had no cache directory, whitelist or preset, and stored an unset
+/// custom rules path as an empty string
+fn migrate_v1_to_v2(config: &mut serde_json::Map<String, Value>) {
+    if config.get("custom_rules_path") == Some(&Value::String(String::new())) {
+        config.insert("custom_rules_path".to_string(), Value::Null);
+    }
+    if config.get("filter_lists") == Some(&Value::Null) {
+        config.remove("filter_lists");
+    }
+    config.entry("cache_dir").or_insert(Value::Null);
+    config
+        .entry("whitelist")
+        .or_insert(Value::Array(Vec::new()));
+    config.entry("preset").or_insert(Value::Null);
+}
+
+impl Config {
+    /// Upgrade a persisted config in place to [`CONFIG_VERSION`]
+    ///
+    /// Configs without a `version` are treated as version 1.
+    pub fn migrate_value(value: &mut Value) -> Result<(), ConfigError> {
+        let config = value
+            .as_object_mut()
+            .ok_or_else(|| ConfigError::Malformed {
+                message: "Config is not an object".to_string(),
+            })?;
+        let version = config.get("version").and_then(Value::as_u64).unwrap_or(1) as u32;
+        if version == 0 || version > CONFIG_VERSION {
+            return Err(ConfigError::UnsupportedVersion { version });
+        }
+
+        for migration in &MIGRATIONS[version as usize - 1..] {
+            migration(config);
+        }
+        config.insert("version".to_string(), Value::from(CONFIG_VERSION));
+        Ok(())
+    }
+
+    /// Parse a persisted config, upgrading older versions
+    pub fn from_json(json: &str) -> Result<Self, ConfigError> {
+        let malformed = |e: serde_json::Error| ConfigError::Malformed {
+            message: e.to_string(),
+        };
+        let mut value: Value = serde_json::from_str(json).map_err(malformed)?;
+        Self::migrate_value(&mut value)?;
+        serde_json::from_value(value).map_err(malformed)
+    }
+
+    /// Serialize for persisting
+    pub fn to_json(&self) -> Result<String, Box<dyn std::error::Error>> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// Check every field, returning all problems found
+    pub fn validate(&self) -> Result<(), Vec<ConfigError>> {
+        let mut errors = Vec::new();
+
+        let (min, max) = MEMORY_LIMIT_MB;
+        if !(min..=max).contains(&self.max_memory_mb) {
+            errors.push(ConfigError::MemoryOutOfRange {
+                value: self.max_memory_mb,
+                min,
+                max,
+            });
+        }
+
+        let (min, max) = UPDATE_INTERVAL_SECS;
+        if !(min..=max).contains(&self.update_interval) {
+            errors.push(ConfigError::IntervalOutOfRange {
+                value: self.update_interval,
+                min,
+                max,
+            });
+        }
+
+        for url in &self.filter_lists {
+            if !is_valid_list_url(url) {
+                errors.push(ConfigError::InvalidUrl { url: url.clone() });
+            }
+        }
+
+        let catalog = crate::list_catalog::ListCatalog::builtin();
+        for id in &self.list_presets {
+            if catalog.get(id).is_none() {
+                errors.push(ConfigError::UnknownListPreset { id: id.clone() });
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// Whether a filter list URL is an http(s) URL with a host
+fn is_valid_list_url(url: &str) -> bool {
+    let Some(rest) = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+    else {
+        return false;
+    };
+    let host = rest.split(['/', '?', '#']).next().unwrap_or("");
+    let host = host.rsplit_once(':').map_or(host, |(host, _port)| host);
+    !host.is_empty()
+        && !url.chars().any(char::is_whitespace)
+        && host
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrate_v1_and_validate() {
+        let config = Config::from_json(
+            r#"{"debug":false,"max_memory_mb":30,"update_interval":86400,
+                "filter_lists":["https://easylist.to/easylist/easylist.txt"],
+                "custom_rules_path":""}"#,
+        )
+        .unwrap();
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(config.custom_rules_path, None);
+        assert!(config.validate().is_ok());
+
+        let invalid = Config {
+            max_memory_mb: 0,
+            update_interval: 10,
+            filter_lists: vec!["ftp://lists.example/list.txt".to_string()],
+            ..Config::default()
+        };
+        assert_eq!(invalid.validate().unwrap_err().len(), 3);
+
+        assert_eq!(
+            Config::from_json(r#"{"version":99}"#).unwrap_err(),
+            ConfigError::UnsupportedVersion { version: 99 }
+        );
+    }
+}
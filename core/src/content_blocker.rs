@@ -0,0 +1,208 @@
+//! Safari content blocker export
+//!
+//! Converts the engine's rules into the JSON format consumed by
+//! `SFContentBlockerManager` on iOS.
+
+use crate::filter_engine::{FilterEngine, FilterRule};
+use serde::{Deserialize, Serialize};
+
+/// Regex prefix matching the scheme and any subdomain (`||` anchor)
+const DOMAIN_ANCHOR: &str = "^[^:]+://+([^:/]+\\.)?";
+
+/// Regex approximation of the `^` separator placeholder
+const SEPARATOR: &str = "[/:?=&]";
+
+/// A single content blocker rule
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContentBlockerRule {
+    pub trigger: ContentBlockerTrigger,
+    pub action: ContentBlockerAction,
+}
+
+/// When a content blocker rule applies
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContentBlockerTrigger {
+    #[serde(rename = "url-filter")]
+    pub url_filter: String,
+    #[serde(rename = "if-domain", skip_serializing_if = "Option::is_none")]
+    pub if_domain: Option<Vec<String>>,
+    #[serde(rename = "unless-domain", skip_serializing_if = "Option::is_none")]
+    pub unless_domain: Option<Vec<String>>,
+}
+
+/// What a content blocker rule does
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContentBlockerAction {
+    #[serde(rename = "type")]
+    pub action_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub selector: Option<String>,
+}
+
+impl ContentBlockerRule {
+    fn block(url_filter: String) -> Self {
+        Self {
+            trigger: ContentBlockerTrigger {
+                url_filter,
+                if_domain: None,
+                unless_domain: None,
+            },
+            action: ContentBlockerAction {
+                action_type: "block".to_string(),
+                selector: None,
+            },
+        }
+    }
+
+    fn ignore_previous(url_filter: String) -> Self {
+        Self {
+            trigger: ContentBlockerTrigger {
+                url_filter,
+                if_domain: None,
+                unless_domain: None,
+            },
+            action: ContentBlockerAction {
+                action_type: "ignore-previous-rules".to_string(),
+                selector: None,
+            },
+        }
+    }
+}
+
+/// Convert a filter pattern into a content blocker `url-filter` regex
+pub fn pattern_to_url_filter(pattern: &str) -> String {
+    let mut regex = String::new();
+    let mut rest = pattern;
+
+    if let Some(stripped) = rest.strip_prefix("||") {
+        regex.push_str(DOMAIN_ANCHOR);
+        rest = stripped;
+    } else if let Some(stripped) = rest.strip_prefix('|') {
+        regex.push('^');
+        rest = stripped;
+    }
+
+    let end_anchor = rest.ends_with('|');
+    let rest = rest.trim_end_matches('|');
+
+    for ch in rest.chars() {
+        match ch {
+            '*' => regex.push_str(".*"),
+            '^' => regex.push_str(SEPARATOR),
+            '\\' | '.' | '+' | '?' | '(' | ')' | '[' | ']' | '{' | '}' | '$' | '|' => {
+                regex.push('\\');
+                regex.push(ch);
+            }
+            _ => regex.push(ch),
+        }
+    }
+
+    if end_anchor {
+        regex.push('$');
+    }
+
+    if regex.is_empty() {
+        ".*".to_string()
+    } else {
+        regex
+    }
+}
+
+/// Build content blocker rules from an engine
+///
+/// Blocking and element hiding rules come first, followed by exceptions
+/// as `ignore-previous-rules` so they override everything before them.
+pub fn build_rules(engine: &FilterEngine) -> Vec<ContentBlockerRule> {
+    let mut rules = Vec::new();
+    let mut exceptions = Vec::new();
+
+    for rule in engine.rules() {
+        match rule {
+            FilterRule::Domain(domain) => {
+                rules.push(ContentBlockerRule::block(pattern_to_url_filter(domain)));
+            }
+            FilterRule::SubdomainPattern(domain) => {
+                rules.push(ContentBlockerRule::block(pattern_to_url_filter(&format!(
+                    "||{domain}^"
+                ))));
+            }
+            FilterRule::Pattern(pattern) => {
+                rules.push(ContentBlockerRule::block(pattern_to_url_filter(pattern)));
+            }
+            FilterRule::Exception(pattern) => {
+                exceptions.push(ContentBlockerRule::ignore_previous(pattern_to_url_filter(
+                    pattern,
+                )));
+            }
+        }
+    }
+
+    for filter in engine.cosmetic_filters().filters() {
+        let to_domain_list = |domains: &[String]| {
+            if domains.is_empty() {
+                None
+            } else {
+                Some(domains.iter().map(|d| format!("*{d}")).collect())
+            }
+        };
+
+        rules.push(ContentBlockerRule {
+            trigger: ContentBlockerTrigger {
+                url_filter: ".*".to_string(),
+                if_domain: to_domain_list(&filter.domains),
+                // Safari rejects triggers with both if-domain and unless-domain
+                unless_domain: if filter.domains.is_empty() {
+                    to_domain_list(&filter.excluded_domains)
+                } else {
+                    None
+                },
+            },
+            action: ContentBlockerAction {
+                action_type: "css-display-none".to_string(),
+                selector: Some(filter.selector.clone()),
+            },
+        });
+    }
+
+    rules.extend(exceptions);
+    rules
+}
+
+/// Export an engine as Safari content blocker JSON
+pub fn export_json(engine: &FilterEngine) -> Result<String, serde_json::Error> {
+    serde_json::to_string(&build_rules(engine))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pattern_to_url_filter() {
+        assert_eq!(
+            pattern_to_url_filter("||ads.com^"),
+            "^[^:]+://+([^:/]+\\.)?ads\\.com[/:?=&]"
+        );
+        assert_eq!(pattern_to_url_filter("*/ads/*"), ".*/ads/.*");
+        assert_eq!(
+            pattern_to_url_filter("|https://x.com/a|"),
+            "^https://x\\.com/a$"
+        );
+    }
+
+    #[test]
+    fn test_exceptions_come_last() {
+        let engine =
+            FilterEngine::from_filter_list("@@||good.com^\n||ads.com^\nexample.com##.ad").unwrap();
+        let rules = build_rules(&engine);
+
+        assert_eq!(rules.len(), 3);
+        assert_eq!(rules[0].action.action_type, "block");
+        assert_eq!(rules[1].action.action_type, "css-display-none");
+        assert_eq!(
+            rules[1].trigger.if_domain,
+            Some(vec!["*example.com".to_string()])
+        );
+        assert_eq!(rules[2].action.action_type, "ignore-previous-rules");
+    }
+}
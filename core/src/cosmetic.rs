@@ -0,0 +1,340 @@
+//! Cosmetic (element hiding) filters
+//!
+//! Stores `##` rules and `#@#` exceptions from filter lists and resolves the
+//! CSS selectors that apply to a given page domain. `$elemhide`,
+//! `$generichide` and `$specifichide` request modifiers disable cosmetic
+//! filtering per site.
+
+/// A single element hiding rule
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct CosmeticFilter {
+    /// CSS selector to hide
+    pub selector: String,
+    /// Domains the rule applies to (empty = all domains)
+    pub domains: Vec<String>,
+    /// Domains the rule is disabled on (`~domain`)
+    pub excluded_domains: Vec<String>,
+    /// Whether this is a `#@#` rule that unhides the selector
+    pub exception: bool,
+}
+
+impl CosmeticFilter {
+    /// Parse an element hiding rule like `example.com,~sub.example.com##.ad`
+    /// or an exception like `example.com#@#.ad`
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = line.trim();
+        let (separator_pos, exception) = match line.find("#@#") {
+            Some(pos) => (pos, true),
+            None => (line.find("##")?, false),
+        };
+        let domains_part = &line[..separator_pos];
+        let selector = line[separator_pos + if exception { 3 } else { 2 }..].trim();
+
+        // `##+js(...)` rules are scriptlets and `:has(...)` & co. are
+        // procedural filters, not plain selectors
+        if selector.is_empty()
+            || selector.starts_with("+js(")
+            || crate::procedural::is_procedural(selector)
+        {
+            return None;
+        }
+
+        let mut domains = Vec::new();
+        let mut excluded_domains = Vec::new();
+        for domain in domains_part.split(',') {
+            let domain = domain.trim().to_lowercase();
+            let domain = crate::idn::to_ascii(&domain).unwrap_or(domain);
+            if domain.is_empty() {
+                continue;
+            }
+            if let Some(excluded) = domain.strip_prefix('~') {
+                excluded_domains.push(excluded.to_string());
+            } else {
+                domains.push(domain);
+            }
+        }
+
+        Some(CosmeticFilter {
+            selector: selector.to_string(),
+            domains,
+            excluded_domains,
+            exception,
+        })
+    }
+
+    /// Whether the rule applies to every domain
+    pub fn is_generic(&self) -> bool {
+        self.domains.is_empty()
+    }
+
+    /// Check if the rule applies to a page domain
+    pub fn applies_to(&self, domain: &str) -> bool {
+        let domain = domain.to_lowercase();
+        let domain = crate::idn::to_ascii(&domain).unwrap_or(domain);
+
+        if self
+            .excluded_domains
+            .iter()
+            .any(|excluded| domain_matches(&domain, excluded))
+        {
+            return false;
+        }
+
+        self.is_generic() || self.domains.iter().any(|d| domain_matches(&domain, d))
+    }
+}
+
+/// Check if `domain` equals `rule_domain` or is a subdomain of it
+pub(crate) fn domain_matches(domain: &str, rule_domain: &str) -> bool {
+    domain == rule_domain
+        || (domain.len() > rule_domain.len()
+            && domain.ends_with(rule_domain)
+            && domain.as_bytes()[domain.len() - rule_domain.len() - 1] == b'.')
+}
+
+/// Which cosmetic filters a request modifier disables
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum ElemHideKind {
+    /// `$elemhide`: all element hiding rules
+    All,
+    /// `$generichide`: rules without a domain
+    Generic,
+    /// `$specifichide`: rules limited to domains
+    Specific,
+}
+
+/// A parsed `@@...$elemhide` / `$generichide` / `$specifichide` rule
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ElemHideException {
+    /// URL pattern of the documents the exception covers
+    pub scope: String,
+    /// Which cosmetic filters are disabled
+    pub kind: ElemHideKind,
+}
+
+impl ElemHideException {
+    /// Parse a rule like `@@||example.com^$generichide`
+    ///
+    /// Returns `None` for non-exception rules and rules without one of the
+    /// modifiers (or their `ehide`/`ghide`/`shide` aliases).
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = line.trim().strip_prefix("@@")?;
+        let options_pos = line.rfind('$')?;
+        let scope = &line[..options_pos];
+
+        let kind = line[options_pos + 1..]
+            .split(',')
+            .find_map(|option| match option.trim() {
+                "elemhide" | "ehide" => Some(ElemHideKind::All),
+                "generichide" | "ghide" => Some(ElemHideKind::Generic),
+                "specifichide" | "shide" => Some(ElemHideKind::Specific),
+                _ => None,
+            })?;
+
+        if scope.is_empty() {
+            return None;
+        }
+
+        Some(ElemHideException {
+            scope: scope.to_string(),
+            kind,
+        })
+    }
+}
+
+/// Cosmetic filtering to apply to a document
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CosmeticResult {
+    /// Selectors to hide
+    pub hide_selectors: Vec<String>,
+    /// Generic rules were disabled (`$generichide` or `$elemhide`)
+    pub generichide: bool,
+    /// Domain-specific rules were disabled (`$specifichide` or `$elemhide`)
+    pub specifichide: bool,
+}
+
+/// Stylesheet hiding elements matching the selectors, for injection into a page
+///
+/// Each selector gets its own rule, so one the browser doesn't support
+/// doesn't disable the others. Selectors with braces, which could inject
+/// other style rules, are skipped.
+pub fn hiding_stylesheet(selectors: &[String]) -> String {
+    selectors
+        .iter()
+        .filter(|selector| !selector.contains(['{', '}']))
+        .map(|selector| format!("{selector} {{ display: none !important; }}\n"))
+        .collect()
+}
+
+/// Collection of element hiding rules
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct CosmeticFilterSet {
+    filters: Vec<CosmeticFilter>,
+}
+
+impl CosmeticFilterSet {
+    /// Create an empty set
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse and add a rule line, returning whether it was a cosmetic rule
+    pub fn add_rule(&mut self, line: &str) -> bool {
+        match CosmeticFilter::parse(line) {
+            Some(filter) => {
+                self.filters.push(filter);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove one rule equal to the parsed line, returning whether one was removed
+    pub fn remove_rule(&mut self, line: &str) -> bool {
+        let Some(filter) = CosmeticFilter::parse(line) else {
+            return false;
+        };
+        match self.filters.iter().position(|existing| *existing == filter) {
+            Some(index) => {
+                self.filters.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Add all `##` and `#@#` rules from a filter list
+    pub fn load_filter_list(&mut self, content: &str) {
+        for line in content.lines() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('!') {
+                continue;
+            }
+            if trimmed.contains("##") || trimmed.contains("#@#") {
+                self.add_rule(trimmed);
+            }
+        }
+    }
+
+    /// Get the selectors that apply to a page domain
+    pub fn selectors_for_domain(&self, domain: &str) -> Vec<String> {
+        self.cosmetic_result(domain, false, false).hide_selectors
+    }
+
+    /// Resolve the selectors for a page domain, skipping generic and/or
+    /// domain-specific rules and any selector unhidden by a `#@#` rule
+    pub fn cosmetic_result(
+        &self,
+        domain: &str,
+        generichide: bool,
+        specifichide: bool,
+    ) -> CosmeticResult {
+        let applicable: Vec<&CosmeticFilter> = self
+            .filters
+            .iter()
+            .filter(|filter| filter.applies_to(domain))
+            .collect();
+
+        let hide_selectors = applicable
+            .iter()
+            .filter(|filter| !filter.exception)
+            .filter(|filter| {
+                if filter.is_generic() {
+                    !generichide
+                } else {
+                    !specifichide
+                }
+            })
+            .filter(|filter| {
+                !applicable
+                    .iter()
+                    .any(|other| other.exception && other.selector == filter.selector)
+            })
+            .map(|filter| filter.selector.clone())
+            .collect();
+
+        CosmeticResult {
+            hide_selectors,
+            generichide,
+            specifichide,
+        }
+    }
+
+    /// All stored filters
+    pub fn filters(&self) -> &[CosmeticFilter] {
+        &self.filters
+    }
+
+    /// Number of stored filters
+    pub fn len(&self) -> usize {
+        self.filters.len()
+    }
+
+    /// Whether the set is empty
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cosmetic_domain_matching() {
+        let mut set = CosmeticFilterSet::new();
+        set.load_filter_list("##.ad\nexample.com,~shop.example.com##.sidebar-ad\n||ads.com^");
+
+        assert_eq!(set.len(), 2);
+        assert_eq!(
+            set.selectors_for_domain("www.example.com"),
+            vec![".ad", ".sidebar-ad"]
+        );
+        assert_eq!(set.selectors_for_domain("shop.example.com"), vec![".ad"]);
+        assert_eq!(set.selectors_for_domain("notexample.com"), vec![".ad"]);
+    }
+
+    #[test]
+    fn test_cosmetic_exceptions() {
+        let mut set = CosmeticFilterSet::new();
+        set.load_filter_list(
+            "##.ad
+##.promo
+example.com##.sidebar
+shop.example.com#@#.ad",
+        );
+
+        assert_eq!(
+            set.selectors_for_domain("shop.example.com"),
+            vec![".promo", ".sidebar"]
+        );
+        assert_eq!(
+            set.cosmetic_result("example.com", true, false)
+                .hide_selectors,
+            vec![".sidebar"]
+        );
+        assert_eq!(
+            set.cosmetic_result("example.com", false, true)
+                .hide_selectors,
+            vec![".ad", ".promo"]
+        );
+
+        let rule = ElemHideException::parse("@@||example.com^$ghide").unwrap();
+        assert_eq!(rule.kind, ElemHideKind::Generic);
+        assert!(ElemHideException::parse("||example.com^$elemhide").is_none());
+    }
+
+    #[test]
+    fn test_hiding_stylesheet() {
+        let selectors = vec![
+            ".ad".to_string(),
+            ".x{} body { background: red }".to_string(),
+            "div[id^=\"banner\"]".to_string(),
+        ];
+        assert_eq!(
+            hiding_stylesheet(&selectors),
+            ".ad { display: none !important; }\ndiv[id^=\"banner\"] { display: none !important; }\n"
+        );
+        assert_eq!(hiding_stylesheet(&[]), "");
+    }
+}
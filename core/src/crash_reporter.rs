@@ -1,13 +1,13 @@
-use std::fmt::Write as FmtWrite;
-use std::sync::{Arc, Mutex};
-use std::collections::VecDeque;
-use std::path::Path;
-use std::fs::{self, File};
-use std::io::Write;
-use serde::{Deserialize, Serialize};
+use crate::event_bus::{EngineEvent, EventBus};
 use chrono::{DateTime, Utc};
 use once_cell::sync::Lazy;
 use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex, Once, Weak};
 
 /// Privacy-respecting crash reporter
 /// Only collects technical data necessary for debugging
@@ -20,6 +20,10 @@ pub struct CrashReporter {
     reports_path: Option<String>,
     /// Whether crash reporting is enabled
     enabled: bool,
+    /// Bus notified of recorded crashes
+    event_bus: Option<Arc<EventBus>>,
+    /// Active rule count, filled into reports that don't set one
+    filter_rules_count: Mutex<Option<u32>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -79,11 +83,13 @@ pub struct CrashContext {
 impl CrashReporter {
     /// Create a new crash reporter
     pub fn new(reports_path: Option<String>) -> Self {
-        let mut reporter = Self {
+        let reporter = Self {
             reports: Arc::new(Mutex::new(VecDeque::with_capacity(100))),
             max_reports: 100,
             reports_path,
             enabled: true,
+            event_bus: None,
+            filter_rules_count: Mutex::new(None),
         };
 
         // Load existing reports if path is provided
@@ -105,11 +111,30 @@ impl CrashReporter {
         }
     }
 
+    /// Publish recorded crashes on a bus
+    pub fn set_event_bus(&mut self, bus: Arc<EventBus>) {
+        self.event_bus = Some(bus);
+    }
+
+    /// Track the active rule count for later reports
+    pub fn set_filter_rules_count(&self, count: usize) {
+        if let Ok(mut rules_count) = self.filter_rules_count.lock() {
+            *rules_count = Some(u32::try_from(count).unwrap_or(u32::MAX));
+        }
+    }
+
     /// Report a crash
-    pub fn report_crash(&self, error_type: CrashType, message: String, context: CrashContext) {
+    ///
+    /// A missing `filter_rules_count` is filled from the tracked count.
+    pub fn report_crash(&self, error_type: CrashType, message: String, mut context: CrashContext) {
         if !self.enabled {
             return;
         }
+        if context.filter_rules_count.is_none() {
+            context.filter_rules_count = self.tracked_rules_count();
+        }
+
+        tracing::error!("Crash reported: {:?} - {}", error_type, message);
 
         let report = CrashReport {
             id: uuid::Uuid::new_v4().to_string(),
@@ -136,24 +161,25 @@ impl CrashReporter {
             self.save_report(&report, path);
         }
 
-        log::error!("Crash reported: {:?} - {}", error_type, message);
+        if let Some(ref bus) = self.event_bus {
+            bus.publish(&EngineEvent::CrashRecorded {
+                report_id: report.id.clone(),
+                error_type: report.error_type.clone(),
+            });
+        }
     }
 
     /// Report an exception with automatic context capture
     pub fn report_exception(&self, exception: &str, context: Option<CrashContext>) {
         let ctx = context.unwrap_or_else(|| self.capture_context());
-        self.report_crash(
-            CrashType::Exception,
-            exception.to_string(),
-            ctx,
-        );
+        self.report_crash(CrashType::Exception, exception.to_string(), ctx);
     }
 
     /// Report out of memory condition
     pub fn report_oom(&self, memory_usage_mb: u32) {
         let mut context = self.capture_context();
         context.memory_usage_mb = Some(memory_usage_mb);
-        
+
         self.report_crash(
             CrashType::OutOfMemory,
             format!("Out of memory at {}MB", memory_usage_mb),
@@ -161,10 +187,15 @@ impl CrashReporter {
         );
     }
 
+    /// Rule count last reported by the engine
+    fn tracked_rules_count(&self) -> Option<u32> {
+        self.filter_rules_count.lock().ok().and_then(|count| *count)
+    }
+
     /// Capture current context for crash reporting
     fn capture_context(&self) -> CrashContext {
         CrashContext {
-            filter_rules_count: None, // Would be set by caller
+            filter_rules_count: self.tracked_rules_count(),
             memory_usage_mb: Self::get_memory_usage(),
             vpn_active: None, // Would be set by caller
             last_action: None,
@@ -175,11 +206,7 @@ impl CrashReporter {
     /// Get recent crash reports
     pub fn get_reports(&self, limit: usize) -> Vec<CrashReport> {
         if let Ok(reports) = self.reports.lock() {
-            reports.iter()
-                .rev()
-                .take(limit)
-                .cloned()
-                .collect()
+            reports.iter().rev().take(limit).cloned().collect()
         } else {
             Vec::new()
         }
@@ -190,7 +217,7 @@ impl CrashReporter {
         if let Ok(reports) = self.reports.lock() {
             let total = reports.len();
             let mut by_type = std::collections::HashMap::new();
-            
+
             for report in reports.iter() {
                 let type_name = match &report.error_type {
                     CrashType::Native => "Native",
@@ -220,7 +247,7 @@ impl CrashReporter {
         if let Ok(mut reports) = self.reports.lock() {
             reports.clear();
         }
-        
+
         // Also clear persisted reports
         if let Some(ref path) = self.reports_path {
             let _ = fs::remove_dir_all(path);
@@ -235,7 +262,7 @@ impl CrashReporter {
         }
 
         let mut loaded_reports = Vec::new();
-        
+
         if let Ok(entries) = fs::read_dir(reports_dir) {
             for entry in entries.flatten() {
                 if let Ok(content) = fs::read_to_string(entry.path()) {
@@ -248,7 +275,8 @@ impl CrashReporter {
 
         // Sort by timestamp and keep only recent ones
         loaded_reports.sort_by_key(|r| r.timestamp);
-        let to_keep = loaded_reports.into_iter()
+        let to_keep = loaded_reports
+            .into_iter()
             .rev()
             .take(self.max_reports)
             .collect::<Vec<_>>();
@@ -264,13 +292,13 @@ impl CrashReporter {
     fn save_report(&self, report: &CrashReport, base_path: &str) {
         let reports_dir = Path::new(base_path);
         if let Err(e) = fs::create_dir_all(reports_dir) {
-            log::error!("Failed to create crash reports directory: {}", e);
+            tracing::error!("Failed to create crash reports directory: {}", e);
             return;
         }
 
         let filename = format!("crash_{}.json", report.id);
         let file_path = reports_dir.join(filename);
-        
+
         if let Ok(mut file) = File::create(file_path) {
             if let Ok(json) = serde_json::to_string_pretty(report) {
                 let _ = file.write_all(json.as_bytes());
@@ -285,34 +313,32 @@ impl CrashReporter {
             Regex::new(r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Z|a-z]{2,}\b")
                 .expect("Invalid email regex pattern")
         });
-        
+
         static IP_REGEX: Lazy<Regex> = Lazy::new(|| {
-            Regex::new(r"\b(?:[0-9]{1,3}\.){3}[0-9]{1,3}\b")
-                .expect("Invalid IP regex pattern")
+            Regex::new(r"\b(?:[0-9]{1,3}\.){3}[0-9]{1,3}\b").expect("Invalid IP regex pattern")
         });
-        
+
         static PHONE_REGEX: Lazy<Regex> = Lazy::new(|| {
-            Regex::new(r"\b\d{3}[-.]?\d{3}[-.]?\d{4}\b")
-                .expect("Invalid phone regex pattern")
+            Regex::new(r"\b\d{3}[-.]?\d{3}[-.]?\d{4}\b").expect("Invalid phone regex pattern")
         });
-        
+
         let mut sanitized = message.to_string();
-        
+
         // Remove email addresses
         sanitized = EMAIL_REGEX.replace_all(&sanitized, "[EMAIL]").to_string();
-        
+
         // Remove IP addresses
         sanitized = IP_REGEX.replace_all(&sanitized, "[IP]").to_string();
-        
+
         // Remove phone numbers
         sanitized = PHONE_REGEX.replace_all(&sanitized, "[PHONE]").to_string();
-        
+
         // Truncate if too long
         if sanitized.len() > 1000 {
             sanitized.truncate(1000);
             sanitized.push_str("...");
         }
-        
+
         sanitized
     }
 
@@ -324,7 +350,7 @@ impl CrashReporter {
             let backtrace = Backtrace::new();
             Some(format!("{:?}", backtrace))
         }
-        
+
         #[cfg(not(feature = "backtrace"))]
         None
     }
@@ -335,12 +361,12 @@ impl CrashReporter {
         {
             "Android".to_string() // Would get actual version from JNI
         }
-        
+
         #[cfg(target_os = "ios")]
         {
             "iOS".to_string() // Would get actual version from system
         }
-        
+
         #[cfg(not(any(target_os = "android", target_os = "ios")))]
         {
             format!("{} {}", std::env::consts::OS, std::env::consts::ARCH)
@@ -354,12 +380,12 @@ impl CrashReporter {
         {
             "Android Device".to_string()
         }
-        
+
         #[cfg(target_os = "ios")]
         {
             "iOS Device".to_string()
         }
-        
+
         #[cfg(not(any(target_os = "android", target_os = "ios")))]
         {
             "Unknown Device".to_string()
@@ -382,29 +408,58 @@ pub struct CrashStatistics {
     pub newest_crash: Option<DateTime<Utc>>,
 }
 
+/// Reporter receiving panics, replaced by each [`install_panic_handler`] call
+static PANIC_REPORTER: Lazy<Mutex<Weak<CrashReporter>>> = Lazy::new(|| Mutex::new(Weak::new()));
+
 /// Panic handler that reports crashes
+///
+/// The hook is installed once and chains to the previously installed hook;
+/// later calls only redirect reports to `reporter`. Panics are no longer
+/// reported once the reporter is dropped.
 pub fn install_panic_handler(reporter: Arc<CrashReporter>) {
-    std::panic::set_hook(Box::new(move |panic_info| {
-        let message = if let Some(s) = panic_info.payload().downcast_ref::<&str>() {
-            s.to_string()
-        } else if let Some(s) = panic_info.payload().downcast_ref::<String>() {
-            s.clone()
-        } else {
-            "Unknown panic".to_string()
-        };
+    if let Ok(mut current) = PANIC_REPORTER.lock() {
+        *current = Arc::downgrade(&reporter);
+    }
 
-        let location = if let Some(location) = panic_info.location() {
-            format!(" at {}:{}:{}", location.file(), location.line(), location.column())
-        } else {
-            String::new()
-        };
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |panic_info| {
+            report_panic(panic_info);
+            previous(panic_info);
+        }));
+    });
+}
 
-        reporter.report_crash(
-            CrashType::Native,
-            format!("Panic: {}{}", message, location),
-            CrashContext::default(),
-        );
-    }));
+/// Report a panic to the current panic reporter, if any
+fn report_panic(panic_info: &std::panic::PanicHookInfo<'_>) {
+    let Some(reporter) = PANIC_REPORTER.lock().ok().and_then(|r| r.upgrade()) else {
+        return;
+    };
+    let message = if let Some(s) = panic_info.payload().downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = panic_info.payload().downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "Unknown panic".to_string()
+    };
+
+    let location = if let Some(location) = panic_info.location() {
+        format!(
+            " at {}:{}:{}",
+            location.file(),
+            location.line(),
+            location.column()
+        )
+    } else {
+        String::new()
+    };
+
+    reporter.report_crash(
+        CrashType::Native,
+        format!("Panic: {}{}", message, location),
+        reporter.capture_context(),
+    );
 }
 
 #[cfg(test)]
@@ -414,14 +469,14 @@ mod tests {
     #[test]
     fn test_crash_reporter() {
         let reporter = CrashReporter::new(None);
-        
+
         // Report a crash
         reporter.report_crash(
             CrashType::Exception,
             "Test exception".to_string(),
             CrashContext::default(),
         );
-        
+
         // Check that it was recorded
         let reports = reporter.get_reports(10);
         assert_eq!(reports.len(), 1);
@@ -438,15 +493,27 @@ mod tests {
     #[test]
     fn test_crash_statistics() {
         let reporter = CrashReporter::new(None);
-        
+
         // Report different types of crashes
-        reporter.report_crash(CrashType::Exception, "Test 1".to_string(), CrashContext::default());
-        reporter.report_crash(CrashType::OutOfMemory, "Test 2".to_string(), CrashContext::default());
-        reporter.report_crash(CrashType::Exception, "Test 3".to_string(), CrashContext::default());
-        
+        reporter.report_crash(
+            CrashType::Exception,
+            "Test 1".to_string(),
+            CrashContext::default(),
+        );
+        reporter.report_crash(
+            CrashType::OutOfMemory,
+            "Test 2".to_string(),
+            CrashContext::default(),
+        );
+        reporter.report_crash(
+            CrashType::Exception,
+            "Test 3".to_string(),
+            CrashContext::default(),
+        );
+
         let stats = reporter.get_statistics();
         assert_eq!(stats.total_crashes, 3);
         assert_eq!(*stats.crashes_by_type.get("Exception").unwrap(), 2);
         assert_eq!(*stats.crashes_by_type.get("OOM").unwrap(), 1);
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,122 @@
+//! Content-Security-Policy injection (`$csp`)
+//!
+//! Parses `$csp` rules whose directives the intercepting layer appends to
+//! document responses, neutralizing inline ad scripts without blocking the
+//! page itself.
+
+use crate::cosmetic::domain_matches;
+
+/// A parsed `$csp` rule
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct CspRule {
+    /// URL pattern the rule is limited to (`None` = all URLs)
+    pub scope: Option<String>,
+    /// CSP directive to inject; `None` only for `@@...$csp` exceptions,
+    /// which disable every directive
+    pub directive: Option<String>,
+    /// Source domains the rule applies to (`domain=`, empty = all)
+    pub domains: Vec<String>,
+    /// Source domains the rule is disabled on (`domain=~...`)
+    pub excluded_domains: Vec<String>,
+    /// Whether this is an `@@` rule that removes directives
+    pub exception: bool,
+}
+
+impl CspRule {
+    /// Parse a rule like `||example.com^$csp=script-src 'self',domain=a.com`
+    ///
+    /// Returns `None` if the line has no `csp` option.
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = line.trim();
+        let (exception, line) = match line.strip_prefix("@@") {
+            Some(stripped) => (true, stripped),
+            None => (false, line),
+        };
+
+        let options_pos = line.rfind('$')?;
+        let scope = &line[..options_pos];
+
+        let mut directive = None;
+        let mut has_csp = false;
+        let mut domains = Vec::new();
+        let mut excluded_domains = Vec::new();
+
+        for option in line[options_pos + 1..].split(',') {
+            let option = option.trim();
+            if option == "csp" {
+                has_csp = true;
+            } else if let Some(value) = option.strip_prefix("csp=") {
+                has_csp = true;
+                let value = value.trim();
+                if !value.is_empty() {
+                    directive = Some(value.to_string());
+                }
+            } else if let Some(value) = option.strip_prefix("domain=") {
+                for domain in value.split('|') {
+                    let domain = domain.trim().to_lowercase();
+                    if let Some(excluded) = domain.strip_prefix('~') {
+                        excluded_domains.push(excluded.to_string());
+                    } else if !domain.is_empty() {
+                        domains.push(domain);
+                    }
+                }
+            }
+        }
+
+        // Only exceptions may omit the directive
+        if !has_csp || (directive.is_none() && !exception) {
+            return None;
+        }
+
+        Some(CspRule {
+            scope: (!scope.is_empty()).then(|| scope.to_string()),
+            directive,
+            domains,
+            excluded_domains,
+            exception,
+        })
+    }
+
+    /// Check the `domain=` option against the source domain
+    pub fn applies_to_source(&self, source_domain: &str) -> bool {
+        let source_domain = source_domain.to_lowercase();
+
+        if self
+            .excluded_domains
+            .iter()
+            .any(|excluded| domain_matches(&source_domain, excluded))
+        {
+            return false;
+        }
+
+        self.domains.is_empty()
+            || self
+                .domains
+                .iter()
+                .any(|d| domain_matches(&source_domain, d))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_csp_rules() {
+        let rule = CspRule::parse(
+            "||example.com^$csp=script-src 'self',domain=example.com|~m.example.com",
+        )
+        .unwrap();
+        assert_eq!(rule.scope.as_deref(), Some("||example.com^"));
+        assert_eq!(rule.directive.as_deref(), Some("script-src 'self'"));
+        assert!(rule.applies_to_source("www.example.com"));
+        assert!(!rule.applies_to_source("m.example.com"));
+
+        let exception = CspRule::parse("@@||example.com^$csp").unwrap();
+        assert!(exception.exception);
+        assert!(exception.directive.is_none());
+
+        assert!(CspRule::parse("||example.com^$csp").is_none());
+        assert!(CspRule::parse("||example.com^$script").is_none());
+    }
+}
@@ -0,0 +1,179 @@
+//! Chromium declarativeNetRequest export
+//!
+//! Converts the engine's network rules into MV3 `declarativeNetRequest`
+//! static rules so the same filter data can drive a companion browser
+//! extension.
+
+use crate::filter_engine::{FilterEngine, FilterRule};
+use serde::{Deserialize, Serialize};
+
+/// Guaranteed minimum number of static rules per MV3 extension
+pub const MV3_STATIC_RULE_LIMIT: usize = 30_000;
+
+/// Export options
+#[derive(Debug, Clone)]
+pub struct DnrExportOptions {
+    /// Maximum number of rules to emit
+    pub max_rules: usize,
+    /// Id of the first emitted rule
+    pub start_id: u32,
+    /// Priority of blocking rules
+    pub block_priority: u32,
+    /// Priority of exception rules (must be higher than `block_priority`)
+    pub allow_priority: u32,
+}
+
+impl Default for DnrExportOptions {
+    fn default() -> Self {
+        Self {
+            max_rules: MV3_STATIC_RULE_LIMIT,
+            start_id: 1,
+            block_priority: 1,
+            allow_priority: 2,
+        }
+    }
+}
+
+/// A single declarativeNetRequest rule
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DnrRule {
+    pub id: u32,
+    pub priority: u32,
+    pub action: DnrAction,
+    pub condition: DnrCondition,
+}
+
+/// Rule action
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DnrAction {
+    #[serde(rename = "type")]
+    pub action_type: String,
+}
+
+/// Rule condition
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DnrCondition {
+    pub url_filter: String,
+}
+
+/// Result of an export
+#[derive(Debug, Clone)]
+pub struct DnrExport {
+    /// Emitted rules
+    pub rules: Vec<DnrRule>,
+    /// Rules dropped because of `max_rules`
+    pub truncated: usize,
+    /// Rules that cannot be expressed as a `urlFilter`
+    pub unsupported: usize,
+}
+
+impl DnrExport {
+    /// Serialize the rules as a ruleset JSON file
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(&self.rules)
+    }
+}
+
+/// Convert a filter pattern into a `urlFilter`
+///
+/// `urlFilter` understands `||`, `|`, `^` and `*` natively, so patterns pass
+/// through unchanged. Regex rules and non-ASCII patterns are rejected.
+fn to_url_filter(pattern: &str) -> Option<String> {
+    let is_regex = pattern.len() > 1 && pattern.starts_with('/') && pattern.ends_with('/');
+    if pattern.is_empty() || is_regex || !pattern.is_ascii() {
+        return None;
+    }
+
+    // A filter starting with "||*" is rejected by Chromium
+    if pattern.starts_with("||*") {
+        return None;
+    }
+
+    Some(pattern.to_string())
+}
+
+/// Export the engine's network rules
+///
+/// Exceptions are emitted first so they survive truncation; dropping an
+/// exception would break pages, while dropping a block only lets an ad
+/// through.
+pub fn export(engine: &FilterEngine, options: &DnrExportOptions) -> DnrExport {
+    let mut allow = Vec::new();
+    let mut block = Vec::new();
+    let mut unsupported = 0;
+
+    for rule in engine.rules() {
+        let (target, pattern) = match rule {
+            FilterRule::Exception(pattern) => (&mut allow, pattern.to_string()),
+            FilterRule::SubdomainPattern(domain) => (&mut block, format!("||{domain}^")),
+            FilterRule::Domain(pattern) | FilterRule::Pattern(pattern) => {
+                (&mut block, pattern.to_string())
+            }
+        };
+
+        match to_url_filter(&pattern) {
+            Some(url_filter) => target.push(url_filter),
+            None => unsupported += 1,
+        }
+    }
+
+    let total = allow.len() + block.len();
+    let candidates = allow
+        .into_iter()
+        .map(|f| (f, "allow", options.allow_priority))
+        .chain(
+            block
+                .into_iter()
+                .map(|f| (f, "block", options.block_priority)),
+        );
+
+    let rules: Vec<DnrRule> = candidates
+        .take(options.max_rules)
+        .zip(options.start_id..)
+        .map(|((url_filter, action_type, priority), id)| DnrRule {
+            id,
+            priority,
+            action: DnrAction {
+                action_type: action_type.to_string(),
+            },
+            condition: DnrCondition { url_filter },
+        })
+        .collect();
+
+    DnrExport {
+        truncated: total - rules.len(),
+        rules,
+        unsupported,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dnr_export_with_cap() {
+        let engine = FilterEngine::from_filter_list(
+            "||ads.com^\n*/banner/*\n@@||ads.com/ok.js\n/^https?:\\/\\/x/",
+        )
+        .unwrap();
+
+        let export = export(
+            &engine,
+            &DnrExportOptions {
+                max_rules: 2,
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(export.rules.len(), 2);
+        assert_eq!(export.truncated, 1);
+        assert_eq!(export.unsupported, 1);
+        assert_eq!(export.rules[0].action.action_type, "allow");
+        assert_eq!(export.rules[0].priority, 2);
+        assert_eq!(export.rules[1].condition.url_filter, "||ads.com^");
+        assert_eq!(export.rules[1].id, 2);
+        assert!(export.to_json().unwrap().contains("\"urlFilter\""));
+    }
+}
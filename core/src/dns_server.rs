@@ -0,0 +1,490 @@
+//! Local DNS proxy server
+//!
+//! Binds a UDP and a TCP socket (port 53 by default) and answers queries
+//! for blocked domains from a [`NetworkFilter`], following its block
+//! response policy. Other queries are forwarded to an
+//! upstream resolver over the same transport they arrived on, by a bounded
+//! set of worker threads. This is the "DNS mode" building block for rooted
+//! devices and desktops.
+
+use crate::network::{DnsAnswer, DnsQuery, DnsQueryType, DnsResponse, NetworkFilter};
+use crate::worker_pool::WorkerPool;
+use crate::SharedStatistics;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// Time to live of answers for blocked domains, in seconds
+const BLOCKED_TTL: u32 = 60;
+
+/// Largest DNS message read over UDP
+const MAX_UDP_MESSAGE: usize = 4096;
+
+/// How often the UDP loop checks for shutdown
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Settings of the DNS server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DnsServerConfig {
+    /// Address of both the UDP and the TCP socket; port 0 picks a free port
+    pub listen: SocketAddr,
+    /// Resolver queries for unblocked domains are forwarded to
+    pub upstream: SocketAddr,
+    /// How long to wait for the upstream resolver
+    pub upstream_timeout: Duration,
+    /// Most forwarded UDP queries, and most TCP connections, served at once
+    ///
+    /// As many again wait in a queue; queries and connections beyond that
+    /// are dropped.
+    pub max_in_flight: usize,
+}
+
+impl Default for DnsServerConfig {
+    fn default() -> Self {
+        DnsServerConfig {
+            listen: SocketAddr::from(([127, 0, 0, 1], 53)),
+            upstream: SocketAddr::from(([1, 1, 1, 1], 53)),
+            upstream_timeout: Duration::from_secs(5),
+            max_in_flight: 64,
+        }
+    }
+}
+
+/// Question of a DNS query message
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Question {
+    id: u16,
+    domain: String,
+    qtype: u16,
+    /// End of the question section in the message
+    end: usize,
+}
+
+/// Parse the header and the single question of a query message
+fn parse_question(message: &[u8]) -> Option<Question> {
+    let header = message.get(..12)?;
+    let id = u16::from_be_bytes([header[0], header[1]]);
+    let is_response = header[2] & 0x80 != 0;
+    let question_count = u16::from_be_bytes([header[4], header[5]]);
+    if is_response || question_count != 1 {
+        return None;
+    }
+    let mut labels = Vec::new();
+    let mut offset = 12;
+    loop {
+        let len = *message.get(offset)? as usize;
+        offset += 1;
+        if len == 0 {
+            break;
+        }
+        // Compression pointers don't occur in a lone question
+        if len > 63 {
+            return None;
+        }
+        let label = message.get(offset..offset + len)?;
+        labels.push(std::str::from_utf8(label).ok()?.to_ascii_lowercase());
+        offset += len;
+    }
+    let qtype = u16::from_be_bytes(message.get(offset..offset + 2)?.try_into().ok()?);
+    // Skip the question class
+    message.get(offset + 2..offset + 4)?;
+    Some(Question {
+        id,
+        domain: labels.join("."),
+        qtype,
+        end: offset + 4,
+    })
+}
+
+fn query_type(qtype: u16) -> DnsQueryType {
+    match qtype {
+        1 => DnsQueryType::A,
+        5 => DnsQueryType::CNAME,
+        15 => DnsQueryType::MX,
+        16 => DnsQueryType::TXT,
+        28 => DnsQueryType::AAAA,
+        other => DnsQueryType::Other(other),
+    }
+}
+
+/// Response to a blocked query, echoing its question
+fn blocked_message(query: &[u8], question: &Question, response: &DnsResponse) -> Vec<u8> {
+    let answers = &response.answers;
+    let mut message = Vec::with_capacity(question.end + 16 * answers.len());
+    message.extend_from_slice(&question.id.to_be_bytes());
+    // Response flag, the query's opcode and recursion desired bit
+    message.push(0x80 | (query[2] & 0x79));
+    // Recursion available
+    message.push(0x80 | response.response_code.code());
+    message.extend_from_slice(&1u16.to_be_bytes());
+    let answer_count = answers
+        .iter()
+        .filter(|answer| matches!(answer, DnsAnswer::A(_) | DnsAnswer::AAAA(_)))
+        .count() as u16;
+    message.extend_from_slice(&answer_count.to_be_bytes());
+    message.extend_from_slice(&[0, 0, 0, 0]);
+    message.extend_from_slice(&query[12..question.end]);
+    for answer in answers {
+        let (rtype, data) = match answer {
+            DnsAnswer::A(ip) => (1u16, ip.octets().to_vec()),
+            DnsAnswer::AAAA(ip) => (28u16, ip.octets().to_vec()),
+            _ => continue,
+        };
+        // Name as a pointer to the question
+        message.extend_from_slice(&[0xC0, 0x0C]);
+        message.extend_from_slice(&rtype.to_be_bytes());
+        message.extend_from_slice(&1u16.to_be_bytes());
+        message.extend_from_slice(&BLOCKED_TTL.to_be_bytes());
+        message.extend_from_slice(&(data.len() as u16).to_be_bytes());
+        message.extend_from_slice(&data);
+    }
+    message
+}
+
+/// DNS query message checked against a filter
+#[derive(Debug, Clone)]
+pub struct CheckedQuery {
+    pub domain: String,
+    pub response: DnsResponse,
+    /// Response message for a blocked query; `None` means the query should
+    /// be forwarded upstream
+    pub answer: Option<Vec<u8>>,
+}
+
+/// Check a DNS query message against a filter, building the response
+/// message if it is blocked
+///
+/// Returns `None` for messages that are not a single-question query.
+pub fn check_query(filter: &NetworkFilter, message: &[u8]) -> Option<CheckedQuery> {
+    let question = parse_question(message)?;
+    let response = filter.process_dns_query(&DnsQuery {
+        domain: question.domain.clone(),
+        query_type: query_type(question.qtype),
+        transaction_id: question.id,
+    });
+    let answer = response
+        .blocked
+        .then(|| blocked_message(message, &question, &response));
+    Some(CheckedQuery {
+        domain: question.domain,
+        response,
+        answer,
+    })
+}
+
+/// What to do with a received message
+enum Handling {
+    Answer(Vec<u8>),
+    Forward,
+    Drop,
+}
+
+/// State shared by the UDP and TCP sides of the server
+struct Resolver {
+    filter: Arc<NetworkFilter>,
+    statistics: Option<Arc<SharedStatistics>>,
+    config: DnsServerConfig,
+}
+
+impl Resolver {
+    fn handle(&self, message: &[u8]) -> Handling {
+        let Some(checked) = check_query(&self.filter, message) else {
+            return Handling::Drop;
+        };
+        self.track(&checked.domain, &checked.response);
+        match checked.answer {
+            Some(answer) => Handling::Answer(answer),
+            None => Handling::Forward,
+        }
+    }
+
+    fn track(&self, domain: &str, response: &DnsResponse) {
+        let Some(stats) = &self.statistics else {
+            return;
+        };
+        match (response.blocked, response.category) {
+            (true, Some(category)) => stats.record_category_blocked(domain, 0, category),
+            (true, None) => stats.record_blocked(domain, 0),
+            (false, _) => stats.record_allowed(domain, 0),
+        }
+    }
+
+    fn forward_udp(&self, message: &[u8]) -> io::Result<Vec<u8>> {
+        let bind: SocketAddr = if self.config.upstream.is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            (std::net::Ipv6Addr::UNSPECIFIED, 0).into()
+        };
+        let socket = UdpSocket::bind(bind)?;
+        socket.set_read_timeout(Some(self.config.upstream_timeout))?;
+        socket.connect(self.config.upstream)?;
+        socket.send(message)?;
+        let mut buffer = vec![0u8; MAX_UDP_MESSAGE];
+        let len = socket.recv(&mut buffer)?;
+        buffer.truncate(len);
+        Ok(buffer)
+    }
+
+    fn forward_tcp(&self, message: &[u8]) -> io::Result<Vec<u8>> {
+        let mut upstream =
+            TcpStream::connect_timeout(&self.config.upstream, self.config.upstream_timeout)?;
+        upstream.set_read_timeout(Some(self.config.upstream_timeout))?;
+        write_tcp_message(&mut upstream, message)?;
+        read_tcp_message(&mut upstream)
+    }
+}
+
+/// Read a length-prefixed DNS message from a TCP stream
+fn read_tcp_message(stream: &mut TcpStream) -> io::Result<Vec<u8>> {
+    let mut len = [0u8; 2];
+    stream.read_exact(&mut len)?;
+    let mut message = vec![0u8; u16::from_be_bytes(len) as usize];
+    stream.read_exact(&mut message)?;
+    Ok(message)
+}
+
+fn write_tcp_message(stream: &mut TcpStream, message: &[u8]) -> io::Result<()> {
+    let len = u16::try_from(message.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "DNS message too large"))?;
+    let mut framed = len.to_be_bytes().to_vec();
+    framed.extend_from_slice(message);
+    stream.write_all(&framed)
+}
+
+/// Running DNS server; stops when shut down or dropped
+pub struct DnsServer {
+    udp_addr: SocketAddr,
+    tcp_addr: SocketAddr,
+    stopped: Arc<AtomicBool>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl DnsServer {
+    /// Bind the sockets and serve in the background
+    ///
+    /// Blocked and allowed queries are recorded in `statistics` if set.
+    pub fn start(
+        config: DnsServerConfig,
+        filter: Arc<NetworkFilter>,
+        statistics: Option<Arc<SharedStatistics>>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let udp = UdpSocket::bind(config.listen)?;
+        let udp_addr = udp.local_addr()?;
+        // Same port for TCP, also when the UDP one was picked by the system
+        let tcp = TcpListener::bind(udp_addr)?;
+        let tcp_addr = tcp.local_addr()?;
+        udp.set_read_timeout(Some(POLL_INTERVAL))?;
+
+        let resolver = Arc::new(Resolver {
+            filter,
+            statistics,
+            config,
+        });
+        let stopped = Arc::new(AtomicBool::new(false));
+        let threads = vec![
+            spawn_udp_loop(udp, Arc::clone(&resolver), Arc::clone(&stopped))?,
+            spawn_tcp_loop(tcp, resolver, Arc::clone(&stopped))?,
+        ];
+        Ok(DnsServer {
+            udp_addr,
+            tcp_addr,
+            stopped,
+            threads,
+        })
+    }
+
+    /// Address of the UDP socket
+    pub fn udp_addr(&self) -> SocketAddr {
+        self.udp_addr
+    }
+
+    /// Address of the TCP socket
+    pub fn tcp_addr(&self) -> SocketAddr {
+        self.tcp_addr
+    }
+
+    /// Stop serving; queries being forwarded are still answered
+    ///
+    /// TCP connections are closed after the query they are serving.
+    pub fn shutdown(&mut self) {
+        if self.threads.is_empty() {
+            return;
+        }
+        self.stopped.store(true, Ordering::SeqCst);
+        // Wake the TCP accept loop so it sees the flag
+        let _ = TcpStream::connect(self.tcp_addr);
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for DnsServer {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+fn spawn_udp_loop(
+    socket: UdpSocket,
+    resolver: Arc<Resolver>,
+    stopped: Arc<AtomicBool>,
+) -> io::Result<JoinHandle<()>> {
+    std::thread::Builder::new()
+        .name("adblock-dns-udp".to_string())
+        .spawn(move || {
+            let max_in_flight = resolver.config.max_in_flight;
+            let Ok(workers) = WorkerPool::new("adblock-dns-upstream", max_in_flight, max_in_flight)
+            else {
+                tracing::warn!("DNS server could not start its upstream workers");
+                return;
+            };
+            let socket = Arc::new(socket);
+            let mut buffer = vec![0u8; MAX_UDP_MESSAGE];
+            while !stopped.load(Ordering::SeqCst) {
+                let Ok((len, client)) = socket.recv_from(&mut buffer) else {
+                    continue;
+                };
+                let message = buffer[..len].to_vec();
+                match resolver.handle(&message) {
+                    Handling::Answer(answer) => {
+                        let _ = socket.send_to(&answer, client);
+                    }
+                    Handling::Forward => {
+                        let (socket, resolver) = (Arc::clone(&socket), Arc::clone(&resolver));
+                        // Upstream round trips don't hold up other queries
+                        let queued =
+                            workers.try_execute(move || match resolver.forward_udp(&message) {
+                                Ok(answer) => {
+                                    let _ = socket.send_to(&answer, client);
+                                }
+                                Err(e) => tracing::debug!("DNS upstream query failed: {e}"),
+                            });
+                        if !queued {
+                            tracing::debug!("DNS query from {client} dropped, too many in flight");
+                        }
+                    }
+                    Handling::Drop => {}
+                }
+            }
+        })
+}
+
+fn spawn_tcp_loop(
+    listener: TcpListener,
+    resolver: Arc<Resolver>,
+    stopped: Arc<AtomicBool>,
+) -> io::Result<JoinHandle<()>> {
+    std::thread::Builder::new()
+        .name("adblock-dns-tcp".to_string())
+        .spawn(move || {
+            let max_in_flight = resolver.config.max_in_flight;
+            let Ok(workers) =
+                WorkerPool::new("adblock-dns-tcp-client", max_in_flight, max_in_flight)
+            else {
+                tracing::warn!("DNS server could not start its TCP workers");
+                return;
+            };
+            for client in listener.incoming() {
+                if stopped.load(Ordering::SeqCst) {
+                    break;
+                }
+                let Ok(client) = client else {
+                    continue;
+                };
+                let (resolver, client_stopped) = (Arc::clone(&resolver), Arc::clone(&stopped));
+                // A rejected connection is closed when the job is dropped
+                let queued = workers.try_execute(move || {
+                    if let Err(e) = serve_tcp_client(client, &resolver, &client_stopped) {
+                        tracing::debug!("DNS TCP connection failed: {e}");
+                    }
+                });
+                if !queued {
+                    tracing::debug!("DNS TCP connection dropped, too many in flight");
+                }
+            }
+        })
+}
+
+/// Answer the queries of a TCP connection until the client closes it
+fn serve_tcp_client(
+    mut client: TcpStream,
+    resolver: &Resolver,
+    stopped: &AtomicBool,
+) -> io::Result<()> {
+    client.set_read_timeout(Some(resolver.config.upstream_timeout))?;
+    while !stopped.load(Ordering::SeqCst) {
+        let message = match read_tcp_message(&mut client) {
+            Ok(message) => message,
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        let answer = match resolver.handle(&message) {
+            Handling::Answer(answer) => answer,
+            Handling::Forward => resolver.forward_tcp(&message)?,
+            Handling::Drop => return Ok(()),
+        };
+        write_tcp_message(&mut client, &answer)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::DnsResponseCode;
+    use std::net::Ipv4Addr;
+
+    /// Query message for a domain and type
+    fn query(id: u16, domain: &str, qtype: u16) -> Vec<u8> {
+        let mut message = id.to_be_bytes().to_vec();
+        message.extend_from_slice(&[0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+        for label in domain.split('.') {
+            message.push(label.len() as u8);
+            message.extend_from_slice(label.as_bytes());
+        }
+        message.push(0);
+        message.extend_from_slice(&qtype.to_be_bytes());
+        message.extend_from_slice(&1u16.to_be_bytes());
+        message
+    }
+
+    #[test]
+    fn test_parse_question() {
+        let message = query(0x1234, "Ads.Example.com", 28);
+        let question = parse_question(&message).unwrap();
+        assert_eq!(question.id, 0x1234);
+        assert_eq!(question.domain, "ads.example.com");
+        assert_eq!(question.qtype, 28);
+        assert_eq!(question.end, message.len());
+        assert_eq!(parse_question(&message[..message.len() - 1]), None);
+    }
+
+    #[test]
+    fn test_blocked_message() {
+        let message = query(7, "ads.example.com", 1);
+        let question = parse_question(&message).unwrap();
+        let mut response = DnsResponse {
+            transaction_id: 7,
+            answers: vec![DnsAnswer::A(Ipv4Addr::UNSPECIFIED)],
+            response_code: DnsResponseCode::NoError,
+            blocked: true,
+            flagged: false,
+            category: None,
+        };
+
+        let null = blocked_message(&message, &question, &response);
+        assert_eq!(&null[..4], &[0, 7, 0x81, 0x80]);
+        assert_eq!(&null[6..8], &[0, 1]);
+        assert_eq!(&null[null.len() - 6..], &[0, 4, 0, 0, 0, 0]);
+
+        response.answers.clear();
+        response.response_code = DnsResponseCode::NxDomain;
+        let nxdomain = blocked_message(&message, &question, &response);
+        assert_eq!(&nxdomain[2..4], &[0x81, 0x83]);
+        assert_eq!(&nxdomain[12..], &message[12..]);
+    }
+}
@@ -0,0 +1,131 @@
+//! Newly-registered-domain policy
+//!
+//! Freshly registered domains are a common tracker/malware signal. The core
+//! has no registration data of its own, so the caller supplies a resolver
+//! (RDAP/WHOIS lookup, bundled NRD feed, ...) and this module applies the
+//! age threshold.
+
+/// Supplies the registration age of a domain
+pub trait DomainAgeResolver: Send + Sync {
+    /// Age of the domain in days, or `None` if unknown
+    fn domain_age_days(&self, domain: &str) -> Option<u32>;
+}
+
+impl<F> DomainAgeResolver for F
+where
+    F: Fn(&str) -> Option<u32> + Send + Sync,
+{
+    fn domain_age_days(&self, domain: &str) -> Option<u32> {
+        self(domain)
+    }
+}
+
+/// What to do with a domain younger than the threshold
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DomainAgeAction {
+    /// Block the domain
+    Block,
+    /// Allow the domain but flag it for the caller
+    Flag,
+}
+
+/// Outcome of evaluating a domain against the policy
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DomainAgeVerdict {
+    /// Domain is at least as old as the threshold
+    Allowed,
+    /// The resolver has no age for this domain
+    Unknown,
+    /// Domain is too young and should be flagged
+    Flagged { age_days: u32 },
+    /// Domain is too young and should be blocked
+    Blocked { age_days: u32 },
+}
+
+impl DomainAgeVerdict {
+    /// Whether the verdict blocks the domain
+    pub fn is_blocked(&self) -> bool {
+        matches!(self, DomainAgeVerdict::Blocked { .. })
+    }
+
+    /// Whether the verdict flags the domain
+    pub fn is_flagged(&self) -> bool {
+        matches!(self, DomainAgeVerdict::Flagged { .. })
+    }
+}
+
+/// Policy blocking or flagging domains younger than `min_age_days`
+pub struct DomainAgePolicy {
+    min_age_days: u32,
+    action: DomainAgeAction,
+    resolver: Box<dyn DomainAgeResolver>,
+}
+
+impl DomainAgePolicy {
+    /// Create a new policy with the given threshold, action and resolver
+    pub fn new<R>(min_age_days: u32, action: DomainAgeAction, resolver: R) -> Self
+    where
+        R: DomainAgeResolver + 'static,
+    {
+        Self {
+            min_age_days,
+            action,
+            resolver: Box::new(resolver),
+        }
+    }
+
+    /// Minimum domain age in days
+    pub fn min_age_days(&self) -> u32 {
+        self.min_age_days
+    }
+
+    /// Action taken for young domains
+    pub fn action(&self) -> DomainAgeAction {
+        self.action
+    }
+
+    /// Evaluate a domain against the policy
+    pub fn evaluate(&self, domain: &str) -> DomainAgeVerdict {
+        let normalized = domain.trim_matches('.').to_lowercase();
+
+        match self.resolver.domain_age_days(&normalized) {
+            None => DomainAgeVerdict::Unknown,
+            Some(age_days) if age_days >= self.min_age_days => DomainAgeVerdict::Allowed,
+            Some(age_days) => match self.action {
+                DomainAgeAction::Block => DomainAgeVerdict::Blocked { age_days },
+                DomainAgeAction::Flag => DomainAgeVerdict::Flagged { age_days },
+            },
+        }
+    }
+}
+
+impl std::fmt::Debug for DomainAgePolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DomainAgePolicy")
+            .field("min_age_days", &self.min_age_days)
+            .field("action", &self.action)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_domain_age_policy() {
+        let policy =
+            DomainAgePolicy::new(30, DomainAgeAction::Block, |domain: &str| match domain {
+                "fresh.example" => Some(3),
+                "old.example" => Some(4000),
+                _ => None,
+            });
+
+        assert_eq!(
+            policy.evaluate("Fresh.Example."),
+            DomainAgeVerdict::Blocked { age_days: 3 }
+        );
+        assert_eq!(policy.evaluate("old.example"), DomainAgeVerdict::Allowed);
+        assert_eq!(policy.evaluate("other.example"), DomainAgeVerdict::Unknown);
+    }
+}
@@ -0,0 +1,195 @@
+//! Domain-level rule matching
+//!
+//! The part of the engine a DNS filter needs: parsing whole-domain rules
+//! out of filter lists and hosts files, and matching domains against them.
+//! It only uses the standard library, so it is all that is compiled with
+//! default features off, e.g. for a DNS filter in router firmware:
+//!
+//! ```text
+//! cargo build --no-default-features
+//! ```
+
+use std::collections::HashSet;
+
+/// Whether a trimmed line is empty, a comment or a list header
+pub fn is_skipped_line(line: &str) -> bool {
+    line.is_empty() || line.starts_with('!') || line.starts_with('[')
+}
+
+/// Whether a trimmed line is an element hiding, procedural or scriptlet rule
+pub fn is_cosmetic_rule(line: &str) -> bool {
+    line.contains("##") || line.contains("#@#") || line.contains("#?#") || line.contains("#@?#")
+}
+
+/// Whole-domain rule of a filter list line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DomainRule {
+    Block(String),
+    /// `@@` exception for the domain
+    Allow(String),
+}
+
+/// Whole-domain rule of a filter list line, if it has one
+///
+/// `||domain^` rules without options, their `@@` exceptions, hosts-file
+/// lines (`0.0.0.0 domain`) and plain domains qualify; rules limited to
+/// paths, patterns or request options don't apply at the DNS level.
+pub fn parse_domain_rule(line: &str) -> Option<DomainRule> {
+    let line = line.trim();
+    if is_skipped_line(line) || is_cosmetic_rule(line) {
+        return None;
+    }
+    let (rule, allow) = match line.strip_prefix("@@") {
+        Some(rule) => (rule, true),
+        None => (line, false),
+    };
+    let domain = match rule.strip_prefix("||") {
+        Some(rule) => rule.strip_suffix('^')?,
+        // Hosts-file lines put the domain after the address
+        None => match rule.split_whitespace().collect::<Vec<_>>().as_slice() {
+            [domain] => domain,
+            ["0.0.0.0" | "127.0.0.1" | "::" | "::1", domain] => domain,
+            _ => return None,
+        },
+    };
+    let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+    let valid = domain.contains('.')
+        && !domain.starts_with('.')
+        && domain
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_');
+    if !valid || domain == "localhost.localdomain" {
+        return None;
+    }
+    Some(if allow {
+        DomainRule::Allow(domain)
+    } else {
+        DomainRule::Block(domain)
+    })
+}
+
+/// Blocked and allowed domains of one or more filter lists
+///
+/// A rule covers its domain and every subdomain; exceptions win over blocks.
+#[derive(Debug, Clone, Default)]
+pub struct DomainFilter {
+    blocked: HashSet<String>,
+    allowed: HashSet<String>,
+}
+
+impl DomainFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Filter with the whole-domain rules of a filter list
+    pub fn from_filter_list(content: &str) -> Self {
+        let mut filter = Self::new();
+        filter.add_list(content);
+        filter
+    }
+
+    /// Add the whole-domain rules of a filter list; other lines are ignored
+    pub fn add_list(&mut self, content: &str) {
+        for line in content.lines() {
+            self.add_rule(line);
+        }
+    }
+
+    /// Add a rule line, returning whether it was a whole-domain rule
+    pub fn add_rule(&mut self, line: &str) -> bool {
+        match parse_domain_rule(line) {
+            Some(DomainRule::Block(domain)) => self.blocked.insert(domain),
+            Some(DomainRule::Allow(domain)) => self.allowed.insert(domain),
+            None => return false,
+        };
+        true
+    }
+
+    /// Remove a rule line, returning whether it was present
+    pub fn remove_rule(&mut self, line: &str) -> bool {
+        match parse_domain_rule(line) {
+            Some(DomainRule::Block(domain)) => self.blocked.remove(&domain),
+            Some(DomainRule::Allow(domain)) => self.allowed.remove(&domain),
+            None => false,
+        }
+    }
+
+    /// Whether a domain is blocked and not excepted
+    pub fn is_blocked(&self, domain: &str) -> bool {
+        self.blocks_domain(domain) && !self.allows_domain(domain)
+    }
+
+    /// Whether a domain or one of its parents has a whole-domain block
+    pub fn blocks_domain(&self, domain: &str) -> bool {
+        matches_parent(&self.blocked, domain)
+    }
+
+    /// Whether a domain or one of its parents has a whole-domain exception
+    pub fn allows_domain(&self, domain: &str) -> bool {
+        matches_parent(&self.allowed, domain)
+    }
+
+    /// Number of blocked domains
+    pub fn blocked_domain_count(&self) -> usize {
+        self.blocked.len()
+    }
+}
+
+/// Whether a set holds a domain or one of its parent domains
+fn matches_parent(domains: &HashSet<String>, domain: &str) -> bool {
+    if domains.is_empty() {
+        return false;
+    }
+    let domain = domain.trim_matches('.').to_ascii_lowercase();
+    let mut candidate = domain.as_str();
+    loop {
+        if domains.contains(candidate) {
+            return true;
+        }
+        match candidate.split_once('.') {
+            Some((_, parent)) => candidate = parent,
+            None => return false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_domain_rule() {
+        let block = |domain: &str| Some(DomainRule::Block(domain.to_string()));
+        assert_eq!(parse_domain_rule("||Ads.example^"), block("ads.example"));
+        assert_eq!(
+            parse_domain_rule("0.0.0.0 tracker.example"),
+            block("tracker.example")
+        );
+        assert_eq!(parse_domain_rule("plain.example"), block("plain.example"));
+        assert_eq!(
+            parse_domain_rule("@@||cdn.example^"),
+            Some(DomainRule::Allow("cdn.example".to_string()))
+        );
+        assert_eq!(parse_domain_rule("||ads.example^$script"), None);
+        assert_eq!(parse_domain_rule("||ads.example/banner"), None);
+        assert_eq!(parse_domain_rule("example.com##.ad"), None);
+        assert_eq!(parse_domain_rule("! comment.example"), None);
+    }
+
+    #[test]
+    fn test_domain_filter() {
+        let mut filter = DomainFilter::from_filter_list(
+            "! Hosts\n0.0.0.0 tracker.example\n||ads.example^\n@@||ok.ads.example^\n||example.com/banner/",
+        );
+        assert!(filter.is_blocked("img.ads.example."));
+        assert!(filter.is_blocked("Tracker.Example"));
+        assert!(!filter.is_blocked("ok.ads.example"));
+        assert!(!filter.is_blocked("example.com"));
+        assert_eq!(filter.blocked_domain_count(), 2);
+
+        assert!(filter.remove_rule("||ads.example^"));
+        assert!(!filter.is_blocked("img.ads.example"));
+        assert!(!filter.remove_rule("example.com##.ad"));
+    }
+}
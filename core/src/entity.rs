@@ -0,0 +1,114 @@
+//! Site ownership entities
+//!
+//! Maps the domains of one organization to a shared entity (like
+//! Disconnect's entity list), so `fbcdn.net` requests on `facebook.com`
+//! count as first-party for `$third-party` rules. A small map is embedded;
+//! hosts can [`install`] a newer one at runtime.
+
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+/// Map bundled with the crate
+const EMBEDDED_MAP: &str = include_str!("../data/entities.json");
+
+static CURRENT: Lazy<RwLock<Arc<EntityMap>>> = Lazy::new(|| {
+    RwLock::new(Arc::new(
+        EntityMap::parse(EMBEDDED_MAP).expect("embedded entity map is valid"),
+    ))
+});
+
+/// Entity feed: domains listed per entity name
+#[derive(Debug, Deserialize)]
+struct EntityFeed {
+    #[serde(default)]
+    version: u32,
+    entities: BTreeMap<String, Vec<String>>,
+}
+
+/// Domains with a known owner
+#[derive(Debug, Clone, Default)]
+pub struct EntityMap {
+    version: u32,
+    /// Entity name of each domain
+    domains: HashMap<String, Arc<str>>,
+}
+
+impl EntityMap {
+    /// Parse an entity feed
+    ///
+    /// The feed is a JSON object such as
+    /// `{"version": 1, "entities": {"Meta": ["facebook.com", "fbcdn.net"]}}`.
+    /// A domain listed under several entities keeps the last one.
+    pub fn parse(json: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let feed: EntityFeed = serde_json::from_str(json)?;
+        let mut domains = HashMap::new();
+        for (entity, entity_domains) in feed.entities {
+            let entity: Arc<str> = Arc::from(entity);
+            for domain in entity_domains {
+                let domain = domain.trim().trim_end_matches('.').to_ascii_lowercase();
+                if !domain.is_empty() {
+                    domains.insert(domain, Arc::clone(&entity));
+                }
+            }
+        }
+        Ok(EntityMap {
+            version: feed.version,
+            domains,
+        })
+    }
+
+    /// Feed version the map was parsed from
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Entity owning a lowercase host: that of the closest listed parent
+    /// domain
+    pub fn entity_of(&self, host: &str) -> Option<&str> {
+        let mut domain = host.trim_end_matches('.');
+        loop {
+            if let Some(entity) = self.domains.get(domain) {
+                return Some(entity);
+            }
+            domain = domain.split_once('.')?.1;
+        }
+    }
+
+    /// Number of domains with an owner
+    pub fn len(&self) -> usize {
+        self.domains.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.domains.is_empty()
+    }
+}
+
+/// The map currently used by `utils::same_entity`
+pub fn current() -> Arc<EntityMap> {
+    Arc::clone(&CURRENT.read())
+}
+
+/// Replace the map used by `utils::same_entity`, returning its domain count
+///
+/// Feeds older than the installed map are rejected.
+pub fn install(json: &str) -> Result<usize, Box<dyn std::error::Error>> {
+    let map = EntityMap::parse(json)?;
+    if map.is_empty() {
+        return Err("Entity feed has no domains".into());
+    }
+    let mut current = CURRENT.write();
+    if map.version < current.version {
+        return Err(format!(
+            "Entity feed version {} is older than {}",
+            map.version, current.version
+        )
+        .into());
+    }
+    let count = map.len();
+    *current = Arc::new(map);
+    Ok(count)
+}
@@ -0,0 +1,132 @@
+//! Engine event bus
+//!
+//! Modules publish engine-wide notifications here instead of calling each
+//! other directly. Statistics, analytics, the memory optimizer and the FFI
+//! callback layer subscribe to the events they care about.
+
+use crate::crash_reporter::CrashType;
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// A notification published on the bus
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EngineEvent {
+    /// The active rule set was replaced or changed
+    RuleSetUpdated { rule_count: usize },
+    /// Rules were added to and removed from the active rule set in place
+    RuleSetPatched {
+        added: usize,
+        removed: usize,
+        rule_count: usize,
+    },
+    /// The memory optimizer freed cache entries under memory pressure
+    MemoryPressure { freed_entries: usize },
+    /// A filter list update finished
+    UpdateCompleted {
+        rules_count: usize,
+        duration_ms: u64,
+    },
+    /// A crash report was recorded
+    CrashRecorded {
+        report_id: String,
+        error_type: CrashType,
+    },
+}
+
+/// Handle returned by [`EventBus::subscribe`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SubscriptionId(u64);
+
+type Listener = Arc<dyn Fn(&EngineEvent) + Send + Sync>;
+
+/// Synchronous publish/subscribe bus
+#[derive(Default)]
+pub struct EventBus {
+    listeners: parking_lot::RwLock<Vec<(SubscriptionId, Listener)>>,
+    next_id: AtomicU64,
+}
+
+impl EventBus {
+    /// Create a bus without subscribers
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a listener called for every published event
+    pub fn subscribe<F>(&self, listener: F) -> SubscriptionId
+    where
+        F: Fn(&EngineEvent) + Send + Sync + 'static,
+    {
+        let id = SubscriptionId(self.next_id.fetch_add(1, Ordering::Relaxed));
+        self.listeners.write().push((id, Arc::new(listener)));
+        id
+    }
+
+    /// Remove a listener, returning whether it was registered
+    pub fn unsubscribe(&self, id: SubscriptionId) -> bool {
+        let mut listeners = self.listeners.write();
+        let before = listeners.len();
+        listeners.retain(|(listener_id, _)| *listener_id != id);
+        listeners.len() != before
+    }
+
+    /// Deliver an event to every listener on the calling thread
+    ///
+    /// Listeners may subscribe or unsubscribe while an event is delivered;
+    /// the change applies from the next event.
+    pub fn publish(&self, event: &EngineEvent) {
+        let listeners: Vec<Listener> = self
+            .listeners
+            .read()
+            .iter()
+            .map(|(_, listener)| Arc::clone(listener))
+            .collect();
+
+        for listener in listeners {
+            listener(event);
+        }
+    }
+
+    /// Number of registered listeners
+    pub fn subscriber_count(&self) -> usize {
+        self.listeners.read().len()
+    }
+}
+
+impl std::fmt::Debug for EventBus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventBus")
+            .field("subscribers", &self.subscriber_count())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    #[test]
+    fn test_publish_and_unsubscribe() {
+        let bus = EventBus::new();
+        let received = Arc::new(AtomicUsize::new(0));
+
+        let counter = Arc::clone(&received);
+        let id = bus.subscribe(move |event| {
+            if let EngineEvent::RuleSetUpdated { rule_count } = event {
+                counter.fetch_add(*rule_count, Ordering::SeqCst);
+            }
+        });
+
+        bus.publish(&EngineEvent::RuleSetUpdated { rule_count: 3 });
+        bus.publish(&EngineEvent::MemoryPressure { freed_entries: 1 });
+        assert_eq!(received.load(Ordering::SeqCst), 3);
+
+        assert!(bus.unsubscribe(id));
+        assert!(!bus.unsubscribe(id));
+        bus.publish(&EngineEvent::RuleSetUpdated { rule_count: 3 });
+        assert_eq!(received.load(Ordering::SeqCst), 3);
+    }
+}
@@ -1,217 +1,2744 @@
 //! FFI (Foreign Function Interface) bindings
 //!
 //! C-compatible API for Android/iOS integration
+//!
+//! Every fallible call takes a trailing `error` out-parameter. It may be
+//! null; otherwise it receives [`AdblockErrorCode::Ok`] on success or the
+//! cause of the failure.
+//!
+//! Mutating calls also have `_status` variants that return an
+//! [`AdblockStatus`] directly; the boolean versions wrap them.
+//!
+//! A panic never unwinds into the caller: the call returns its failure
+//! value (null, `false`, zero) with [`AdblockErrorCode::Panicked`], and the
+//! panic is recorded by the crash reporter. An engine that panicked while
+//! changing its rules reports `LockPoisoned` from then on and should be
+//! destroyed.
+//!
+//! Engine and network filter handles are opaque integers, not pointers,
+//! checked on every call: a destroyed or unknown handle is rejected with
+//! `NullPointer` rather than crashing, and destroying an engine another
+//! thread is still using frees it once that call returns.
+//!
+//! Engine handles may be shared between threads. URL checks and other
+//! reads run concurrently; calls that change rules, configuration or the
+//! query log wait for them and run one at a time.
 
-use crate::{AdBlockCore, Config};
+use crate::event_bus::SubscriptionId;
+use crate::filter_registry::FilterListRegistry;
+use crate::filter_updater::FilterListDiff;
+use crate::handle_registry::HandleRegistry;
+use crate::maintenance::DeviceConditions;
+use crate::network::NetworkFilter;
+use crate::site_policy::SiteMode;
+use crate::{AdBlockCore, Config, RuleError};
 use std::ffi::{CStr, CString};
+use std::fmt;
 use std::os::raw::{c_char, c_void};
 use std::ptr;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+
+/// Engines handed out by `adblock_engine_create`
+static ENGINES: HandleRegistry<AdBlockEngine> = HandleRegistry::new();
+
+/// Network filters handed out by `adblock_network_filter_create`
+static NETWORK_FILTERS: HandleRegistry<AdBlockNetworkFilter> = HandleRegistry::new();
 
 /// Opaque handle for the AdBlock engine
 pub struct AdBlockEngine {
-    core: Mutex<AdBlockCore>,
+    core: RwLock<AdBlockCore>,
+    /// Event bus subscription of the registered callback
+    event_subscription: Mutex<Option<SubscriptionId>>,
+    /// Subscribed filter lists
+    lists: Mutex<FilterListRegistry>,
+    /// Held while a filter update runs, so updates run one at a time
+    updating: Mutex<()>,
+}
+
+/// Callback receiving engine events as JSON (e.g. `{"type":"rule_set_updated",...}`)
+///
+/// The JSON string is only valid during the call. The callback runs on the
+/// thread that published the event, possibly while the engine is locked, so
+/// it must not call back into the engine.
+pub type AdblockEventCallback =
+    Option<extern "C" fn(event_json: *const c_char, user_data: *mut c_void)>;
+
+/// Caller context passed back to the event callback
+struct CallbackContext {
+    callback: extern "C" fn(event_json: *const c_char, user_data: *mut c_void),
+    user_data: *mut c_void,
+}
+
+// The caller guarantees `user_data` may be used from any thread
+unsafe impl Send for CallbackContext {}
+unsafe impl Sync for CallbackContext {}
+
+impl CallbackContext {
+    fn notify(&self, event_json: &CStr) {
+        (self.callback)(event_json.as_ptr(), self.user_data);
+    }
+}
+
+/// Callback receiving the outcome of `adblock_engine_update_filters_async`
+///
+/// On success `error_message` is null and `rule_count` is the number of
+/// network rules now active. Otherwise `error_message` describes the
+/// failure and is only valid during the call.
+pub type AdblockUpdateCallback =
+    Option<extern "C" fn(rule_count: usize, error_message: *const c_char, user_data: *mut c_void)>;
+
+/// Caller context passed back to the update callback
+struct UpdateCallbackContext {
+    callback:
+        extern "C" fn(rule_count: usize, error_message: *const c_char, user_data: *mut c_void),
+    user_data: *mut c_void,
+}
+
+// The caller guarantees `user_data` may be used from any thread
+unsafe impl Send for UpdateCallbackContext {}
+
+impl UpdateCallbackContext {
+    fn finish(self, result: Result<usize, String>) {
+        match result {
+            Ok(rule_count) => (self.callback)(rule_count, ptr::null(), self.user_data),
+            Err(message) => {
+                let message = CString::new(message.replace('\0', "")).unwrap_or_default();
+                (self.callback)(0, message.as_ptr(), self.user_data);
+            }
+        }
+    }
+}
+
+/// Error codes written to the `error` out-parameter
+///
+/// Values stay below 32, which [`AdblockStatus`] keeps for its own codes.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdblockErrorCode {
+    /// The call succeeded
+    Ok = 0,
+    /// The engine or a required argument was null, or a handle was destroyed
+    NullPointer = 1,
+    /// A string argument was not valid UTF-8
+    InvalidUtf8 = 2,
+    /// The engine lock was poisoned by a panic
+    LockPoisoned = 3,
+    /// The engine could not be created or a filter list failed to parse
+    EngineFailure = 4,
+    /// The result could not be serialized
+    SerializationFailed = 5,
+    /// The operation is not supported by this engine
+    NotSupported = 6,
+    /// The config JSON is malformed or fails validation
+    InvalidConfig = 7,
+    /// An argument was rejected, e.g. an invalid subscription link
+    InvalidArgument = 8,
+    /// The call panicked; the panic was recorded by the crash reporter
+    Panicked = 9,
+    /// The engine was busy, e.g. swapping in new rules; retry the call
+    EngineBusy = 10,
+}
+
+/// Verdict on a request, see [`crate::BlockKind`]
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdblockVerdict {
+    /// The request is allowed
+    Allowed = 0,
+    /// Blocked as an ad or tracker
+    Ad = 1,
+    /// Blocked by a malware/phishing protection list; show an interstitial
+    Malware = 2,
+    /// Blocked because the site is in a blocked content category
+    Category = 3,
+}
+
+impl From<&crate::BlockDecision> for AdblockVerdict {
+    fn from(decision: &crate::BlockDecision) -> Self {
+        match decision.kind {
+            _ if !decision.should_block => AdblockVerdict::Allowed,
+            Some(crate::BlockKind::Malware) => AdblockVerdict::Malware,
+            Some(crate::BlockKind::Category(_)) => AdblockVerdict::Category,
+            _ => AdblockVerdict::Ad,
+        }
+    }
+}
+
+/// Per-site blocking mode, see [`SiteMode`]
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdblockSiteMode {
+    /// Nothing is blocked or hidden
+    Off = 0,
+    /// Network rules only
+    Standard = 1,
+    /// Network and cosmetic rules
+    Aggressive = 2,
+}
+
+impl From<AdblockSiteMode> for SiteMode {
+    fn from(mode: AdblockSiteMode) -> Self {
+        match mode {
+            AdblockSiteMode::Off => SiteMode::Off,
+            AdblockSiteMode::Standard => SiteMode::Standard,
+            AdblockSiteMode::Aggressive => SiteMode::Aggressive,
+        }
+    }
+}
+
+impl From<SiteMode> for AdblockSiteMode {
+    fn from(mode: SiteMode) -> Self {
+        match mode {
+            SiteMode::Off => AdblockSiteMode::Off,
+            SiteMode::Standard => AdblockSiteMode::Standard,
+            SiteMode::Aggressive => AdblockSiteMode::Aggressive,
+        }
+    }
+}
+
+/// Status returned by mutating calls
+///
+/// Codes it shares with [`AdblockErrorCode`] have the same values; codes
+/// only statuses have start at 32.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdblockStatus {
+    /// The call succeeded
+    Ok = 0,
+    /// The engine or a required argument was null, or a handle was destroyed
+    NullPointer = 1,
+    /// A string argument was not valid UTF-8
+    InvalidUtf8 = 2,
+    /// The engine lock was poisoned by a panic
+    LockPoisoned = 3,
+    /// The operation is not supported by this engine
+    NotSupported = 6,
+    /// The call panicked; the panic was recorded by the crash reporter
+    Panicked = 9,
+    /// The engine was busy, e.g. swapping in new rules; retry the call
+    EngineBusy = 10,
+    /// The rules could not be parsed
+    ParseError = 32,
+    /// A file could not be read or written
+    IoError = 33,
+}
+
+impl From<AdblockStatus> for AdblockErrorCode {
+    fn from(status: AdblockStatus) -> Self {
+        match status {
+            AdblockStatus::Ok => AdblockErrorCode::Ok,
+            AdblockStatus::NullPointer => AdblockErrorCode::NullPointer,
+            AdblockStatus::InvalidUtf8 => AdblockErrorCode::InvalidUtf8,
+            AdblockStatus::ParseError => AdblockErrorCode::EngineFailure,
+            AdblockStatus::LockPoisoned => AdblockErrorCode::LockPoisoned,
+            AdblockStatus::NotSupported => AdblockErrorCode::NotSupported,
+            AdblockStatus::IoError => AdblockErrorCode::EngineFailure,
+            AdblockStatus::Panicked => AdblockErrorCode::Panicked,
+            AdblockStatus::EngineBusy => AdblockErrorCode::EngineBusy,
+        }
+    }
+}
+
+impl From<RuleError> for AdblockStatus {
+    fn from(error: RuleError) -> Self {
+        match error {
+            RuleError::Invalid { .. } => AdblockStatus::ParseError,
+            RuleError::Busy => AdblockStatus::EngineBusy,
+        }
+    }
+}
+
+/// Value a call returns when it panicked
+pub trait PanicFallback {
+    fn panic_fallback() -> Self;
+}
+
+impl PanicFallback for () {
+    fn panic_fallback() -> Self {}
+}
+
+impl PanicFallback for bool {
+    fn panic_fallback() -> Self {
+        false
+    }
+}
+
+impl PanicFallback for u8 {
+    fn panic_fallback() -> Self {
+        0
+    }
+}
+
+impl PanicFallback for i64 {
+    fn panic_fallback() -> Self {
+        0
+    }
+}
+
+impl PanicFallback for usize {
+    fn panic_fallback() -> Self {
+        0
+    }
+}
+
+impl<T> PanicFallback for *mut T {
+    fn panic_fallback() -> Self {
+        ptr::null_mut()
+    }
+}
+
+impl PanicFallback for AdblockStatus {
+    fn panic_fallback() -> Self {
+        AdblockStatus::Panicked
+    }
+}
+
+impl PanicFallback for AdblockVerdict {
+    fn panic_fallback() -> Self {
+        AdblockVerdict::Allowed
+    }
 }
 
-/// Convert C string to Rust string safely
-fn c_str_to_rust(ptr: *const c_char) -> Option<&'static str> {
+impl PanicFallback for AdblockSiteMode {
+    fn panic_fallback() -> Self {
+        SiteMode::default().into()
+    }
+}
+
+/// Run the body of an exported function, stopping panics from unwinding
+/// into the caller
+///
+/// A panic is recorded by the panic handler each engine installs, then
+/// reported as `Panicked` through the nullable `error` out-parameter.
+/// Native glue wrapping this API, like the JNI bindings, guards its own
+/// exported functions with it too.
+pub fn catch_panic<T: PanicFallback>(error: *mut AdblockErrorCode, body: impl FnOnce() -> T) -> T {
+    match std::panic::catch_unwind(std::panic::AssertUnwindSafe(body)) {
+        Ok(result) => result,
+        Err(_) => {
+            set_error(error, AdblockErrorCode::Panicked);
+            T::panic_fallback()
+        }
+    }
+}
+
+/// Report a status through the `error` out-parameter as a boolean result
+fn status_to_bool(status: AdblockStatus, error: *mut AdblockErrorCode) -> bool {
+    set_error(error, status.into());
+    status == AdblockStatus::Ok
+}
+
+/// Convert a C string argument into a status on failure
+fn c_str_status(ptr: *const c_char) -> Result<&'static str, AdblockStatus> {
     if ptr.is_null() {
-        return None;
+        return Err(AdblockStatus::NullPointer);
     }
+    unsafe { CStr::from_ptr(ptr) }
+        .to_str()
+        .map_err(|_| AdblockStatus::InvalidUtf8)
+}
 
-    unsafe { CStr::from_ptr(ptr).to_str().ok() }
+/// Write an error code to a nullable out-parameter
+fn set_error(error: *mut AdblockErrorCode, code: AdblockErrorCode) {
+    if !error.is_null() {
+        unsafe { *error = code };
+    }
 }
 
-/// Get engine reference safely
-fn get_engine_ref(engine: *mut c_void) -> Option<&'static AdBlockEngine> {
-    if engine.is_null() {
+/// Convert a C string argument, reporting null or invalid UTF-8
+fn c_str_arg(ptr: *const c_char, error: *mut AdblockErrorCode) -> Option<&'static str> {
+    if ptr.is_null() {
+        set_error(error, AdblockErrorCode::NullPointer);
         return None;
     }
 
-    Some(unsafe { &*(engine as *mut AdBlockEngine) })
+    match unsafe { CStr::from_ptr(ptr) }.to_str() {
+        Ok(s) => Some(s),
+        Err(_) => {
+            set_error(error, AdblockErrorCode::InvalidUtf8);
+            None
+        }
+    }
+}
+
+/// Get engine reference, reporting a null handle
+fn engine_arg(engine: *mut c_void, error: *mut AdblockErrorCode) -> Option<Arc<AdBlockEngine>> {
+    let engine = get_engine_ref(engine);
+    if engine.is_none() {
+        set_error(error, AdblockErrorCode::NullPointer);
+    }
+    engine
+}
+
+/// Convert a Rust string into a C string owned by the caller
+fn into_c_string(s: String, error: *mut AdblockErrorCode) -> *mut c_char {
+    match CString::new(s) {
+        Ok(cstring) => {
+            set_error(error, AdblockErrorCode::Ok);
+            cstring.into_raw()
+        }
+        Err(_) => {
+            set_error(error, AdblockErrorCode::SerializationFailed);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Look up the engine of a handle
+fn get_engine_ref(engine: *mut c_void) -> Option<Arc<AdBlockEngine>> {
+    ENGINES.get(engine as usize)
 }
 
 /// Create a new AdBlock engine
 #[no_mangle]
-pub extern "C" fn adblock_engine_create() -> *mut c_void {
-    let config = Config::default();
+pub extern "C" fn adblock_engine_create(error: *mut AdblockErrorCode) -> *mut c_void {
+    catch_panic(error, || create_engine(Config::default(), error))
+}
 
-    match AdBlockCore::new(config) {
-        Ok(core) => {
-            let engine = Box::new(AdBlockEngine {
-                core: Mutex::new(core),
-            });
-            Box::into_raw(engine) as *mut c_void
+/// Register a core built from `config` under a new engine handle
+fn create_engine(config: Config, error: *mut AdblockErrorCode) -> *mut c_void {
+    let handle = AdBlockCore::new(config).ok().and_then(|core| {
+        ENGINES.insert(AdBlockEngine {
+            core: RwLock::new(core),
+            event_subscription: Mutex::new(None),
+            lists: Mutex::new(FilterListRegistry::new()),
+            updating: Mutex::new(()),
+        })
+    });
+    match handle {
+        Some(handle) => {
+            set_error(error, AdblockErrorCode::Ok);
+            handle as *mut c_void
+        }
+        None => {
+            set_error(error, AdblockErrorCode::EngineFailure);
+            ptr::null_mut()
         }
-        Err(_) => ptr::null_mut(),
     }
 }
 
+/// Create an engine from a persisted config JSON
+///
+/// Older config versions are upgraded first. An unparseable or invalid
+/// config returns null with `InvalidConfig`; call
+/// `adblock_validate_config` for the reasons.
+#[no_mangle]
+pub extern "C" fn adblock_engine_create_with_config(
+    config_json: *const c_char,
+    error: *mut AdblockErrorCode,
+) -> *mut c_void {
+    catch_panic(error, || {
+        let Some(config_json) = c_str_arg(config_json, error) else {
+            return ptr::null_mut();
+        };
+        let config = match Config::from_json(config_json) {
+            Ok(config) if config.validate().is_ok() => config,
+            _ => {
+                set_error(error, AdblockErrorCode::InvalidConfig);
+                return ptr::null_mut();
+            }
+        };
+
+        create_engine(config, error)
+    })
+}
+
+/// Validate a persisted config JSON
+///
+/// Returns a JSON array of errors (empty when valid), each with a `kind`
+/// and the offending values. The returned string must be freed with
+/// `adblock_free_string`
+#[no_mangle]
+pub extern "C" fn adblock_validate_config(
+    config_json: *const c_char,
+    error: *mut AdblockErrorCode,
+) -> *mut c_char {
+    catch_panic(error, || {
+        let Some(config_json) = c_str_arg(config_json, error) else {
+            return ptr::null_mut();
+        };
+        let errors = match Config::from_json(config_json) {
+            Ok(config) => config.validate().err().unwrap_or_default(),
+            Err(e) => vec![e],
+        };
+
+        match serde_json::to_string(&errors) {
+            Ok(json) => into_c_string(json, error),
+            Err(_) => {
+                set_error(error, AdblockErrorCode::SerializationFailed);
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
 /// Destroy an AdBlock engine
+///
+/// The handle is invalid from now on; destroying it again does nothing.
 #[no_mangle]
 pub extern "C" fn adblock_engine_destroy(engine: *mut c_void) {
-    if engine.is_null() {
-        return;
-    }
+    catch_panic(ptr::null_mut(), || {
+        // Dropped once calls still running on other threads return
+        ENGINES.remove(engine as usize);
+    })
+}
 
-    unsafe {
-        let _ = Box::from_raw(engine as *mut AdBlockEngine);
-        // Box will be dropped, cleaning up the engine
-    }
+/// Register a callback for engine events, replacing any previous one
+///
+/// Pass a null callback to stop receiving events.
+#[no_mangle]
+pub extern "C" fn adblock_engine_set_event_callback(
+    engine: *mut c_void,
+    callback: AdblockEventCallback,
+    user_data: *mut c_void,
+    error: *mut AdblockErrorCode,
+) -> bool {
+    catch_panic(error, || {
+        let Some(engine) = engine_arg(engine, error) else {
+            return false;
+        };
+
+        let bus = match engine.core.read() {
+            Ok(core) => core.events().clone(),
+            Err(_) => {
+                set_error(error, AdblockErrorCode::LockPoisoned);
+                return false;
+            }
+        };
+        let Ok(mut subscription) = engine.event_subscription.lock() else {
+            set_error(error, AdblockErrorCode::LockPoisoned);
+            return false;
+        };
+
+        if let Some(id) = subscription.take() {
+            bus.unsubscribe(id);
+        }
+        if let Some(callback) = callback {
+            let context = CallbackContext {
+                callback,
+                user_data,
+            };
+            *subscription = Some(bus.subscribe(move |event| {
+                let Ok(json) = serde_json::to_string(event) else {
+                    return;
+                };
+                if let Ok(json) = CString::new(json) {
+                    context.notify(&json);
+                }
+            }));
+        }
+
+        set_error(error, AdblockErrorCode::Ok);
+        true
+    })
+}
+
+/// Register a callback for every checked request, replacing any previous one
+///
+/// It receives the request as JSON (`{"domain":...,"blocked":true,...}`),
+/// only valid during the call, on the checking thread. Use it to keep a
+/// live blocked counter without polling statistics; it must not call back
+/// into the engine. Pass a null callback to stop receiving requests.
+#[no_mangle]
+pub extern "C" fn adblock_engine_set_block_event_callback(
+    engine: *mut c_void,
+    callback: AdblockEventCallback,
+    user_data: *mut c_void,
+    error: *mut AdblockErrorCode,
+) -> bool {
+    catch_panic(error, || {
+        let Some(engine) = engine_arg(engine, error) else {
+            return false;
+        };
+        let Ok(core) = engine.core.read() else {
+            set_error(error, AdblockErrorCode::LockPoisoned);
+            return false;
+        };
+
+        match callback {
+            Some(callback) => {
+                let context = CallbackContext {
+                    callback,
+                    user_data,
+                };
+                core.set_event_listener(Box::new(move |event| {
+                    let Ok(json) = serde_json::to_string(event) else {
+                        return;
+                    };
+                    if let Ok(json) = CString::new(json) {
+                        context.notify(&json);
+                    }
+                }));
+            }
+            None => core.clear_event_listener(),
+        }
+
+        set_error(error, AdblockErrorCode::Ok);
+        true
+    })
 }
 
 /// Check if a URL should be blocked
 #[no_mangle]
-pub extern "C" fn adblock_engine_should_block(engine: *mut c_void, url: *const c_char) -> bool {
-    let engine = match get_engine_ref(engine) {
-        Some(e) => e,
-        None => return false,
-    };
+pub extern "C" fn adblock_engine_should_block(
+    engine: *mut c_void,
+    url: *const c_char,
+    error: *mut AdblockErrorCode,
+) -> bool {
+    catch_panic(error, || {
+        let Some(engine) = engine_arg(engine, error) else {
+            return false;
+        };
 
-    let url_str = match c_str_to_rust(url) {
-        Some(s) => s,
-        None => return false,
-    };
+        let Some(url_str) = c_str_arg(url, error) else {
+            return false;
+        };
+
+        let Ok(core) = engine.core.read() else {
+            set_error(error, AdblockErrorCode::LockPoisoned);
+            return false;
+        };
+        // We need a dummy size for statistics tracking
+        let decision = core.check_url(url_str, 0);
+        set_error(error, AdblockErrorCode::Ok);
+        decision.should_block
+    })
+}
+
+/// Check a URL, returning whether it is blocked and as what
+///
+/// Statistics are tracked as with `adblock_engine_should_block`.
+#[no_mangle]
+pub extern "C" fn adblock_engine_check_url(
+    engine: *mut c_void,
+    url: *const c_char,
+    error: *mut AdblockErrorCode,
+) -> AdblockVerdict {
+    catch_panic(error, || {
+        let Some(engine) = engine_arg(engine, error) else {
+            return AdblockVerdict::Allowed;
+        };
+        let Some(url) = c_str_arg(url, error) else {
+            return AdblockVerdict::Allowed;
+        };
+
+        let Ok(core) = engine.core.read() else {
+            set_error(error, AdblockErrorCode::LockPoisoned);
+            return AdblockVerdict::Allowed;
+        };
+        let decision = core.check_url(url, 0);
+        set_error(error, AdblockErrorCode::Ok);
+        AdblockVerdict::from(&decision)
+    })
+}
 
-    match engine.core.lock() {
-        Ok(mut core) => {
-            // We need a dummy size for statistics tracking
-            let decision = core.check_url(url_str, 0);
-            decision.should_block
+/// Check a URL requested by an app or package
+///
+/// Like `adblock_engine_check_url`, but the app is recorded in the query log.
+#[no_mangle]
+pub extern "C" fn adblock_engine_check_url_for_app(
+    engine: *mut c_void,
+    url: *const c_char,
+    app: *const c_char,
+    error: *mut AdblockErrorCode,
+) -> AdblockVerdict {
+    catch_panic(error, || {
+        let Some(engine) = engine_arg(engine, error) else {
+            return AdblockVerdict::Allowed;
+        };
+        let (Some(url), Some(app)) = (c_str_arg(url, error), c_str_arg(app, error)) else {
+            return AdblockVerdict::Allowed;
+        };
+
+        let Ok(core) = engine.core.read() else {
+            set_error(error, AdblockErrorCode::LockPoisoned);
+            return AdblockVerdict::Allowed;
+        };
+        let decision = core.check_url_for_app(url, 0, app);
+        set_error(error, AdblockErrorCode::Ok);
+        AdblockVerdict::from(&decision)
+    })
+}
+
+/// Check a request made by a page, e.g. from a WebView's
+/// `shouldInterceptRequest`
+///
+/// `page_url` enables `$third-party` and per-site options; `resource_type`
+/// is a rule option name such as `script` or `xmlhttprequest`. Either may
+/// be null, and the type is then guessed from the URL. An unknown type is
+/// rejected with `InvalidArgument`. Statistics are tracked as with
+/// `adblock_engine_should_block`.
+#[no_mangle]
+pub extern "C" fn adblock_engine_should_block_request(
+    engine: *mut c_void,
+    url: *const c_char,
+    page_url: *const c_char,
+    resource_type: *const c_char,
+    error: *mut AdblockErrorCode,
+) -> bool {
+    catch_panic(error, || {
+        let Some(engine) = engine_arg(engine, error) else {
+            return false;
+        };
+        let Some(url) = c_str_arg(url, error) else {
+            return false;
+        };
+
+        let mut context = crate::resource_type::RequestContext::from_url(url);
+        if !resource_type.is_null() {
+            let Some(name) = c_str_arg(resource_type, error) else {
+                return false;
+            };
+            let Some(resource_type) = crate::resource_type::ResourceType::from_option_name(name)
+            else {
+                set_error(error, AdblockErrorCode::InvalidArgument);
+                return false;
+            };
+            context.resource_type = resource_type;
         }
-        Err(_) => false,
-    }
+        if !page_url.is_null() {
+            let Some(page_url) = c_str_arg(page_url, error) else {
+                return false;
+            };
+            context = context.with_document_url(page_url);
+        }
+
+        let Ok(core) = engine.core.read() else {
+            set_error(error, AdblockErrorCode::LockPoisoned);
+            return false;
+        };
+        let decision = core.check_request(url, &context, 0);
+        set_error(error, AdblockErrorCode::Ok);
+        decision.should_block
+    })
+}
+
+/// Explain a URL's verdict for a developer screen, as JSON
+///
+/// Lists every matching rule with its source list instead of the first
+/// one, without recording statistics. The engine's whitelist and pause
+/// state are not considered.
+/// The returned string must be freed with `adblock_free_string`
+#[no_mangle]
+pub extern "C" fn adblock_engine_explain(
+    engine: *mut c_void,
+    url: *const c_char,
+    error: *mut AdblockErrorCode,
+) -> *mut c_char {
+    catch_panic(error, || {
+        let Some(engine) = engine_arg(engine, error) else {
+            return ptr::null_mut();
+        };
+        let Some(url) = c_str_arg(url, error) else {
+            return ptr::null_mut();
+        };
+
+        let Ok(core) = engine.core.read() else {
+            set_error(error, AdblockErrorCode::LockPoisoned);
+            return ptr::null_mut();
+        };
+        let context = crate::resource_type::RequestContext::from_url(url);
+        match serde_json::to_string(&core.engine().explain(url, &context)) {
+            Ok(json) => into_c_string(json, error),
+            Err(_) => {
+                set_error(error, AdblockErrorCode::SerializationFailed);
+                ptr::null_mut()
+            }
+        }
+    })
 }
 
 /// Add a single rule to the engine
 #[no_mangle]
-pub extern "C" fn adblock_engine_add_rule(engine: *mut c_void, rule: *const c_char) -> bool {
-    let engine = match get_engine_ref(engine) {
-        Some(e) => e,
-        None => return false,
-    };
+pub extern "C" fn adblock_engine_add_rule(
+    engine: *mut c_void,
+    rule: *const c_char,
+    error: *mut AdblockErrorCode,
+) -> bool {
+    catch_panic(error, || {
+        status_to_bool(adblock_engine_add_rule_status(engine, rule), error)
+    })
+}
 
-    let _rule_str = match c_str_to_rust(rule) {
-        Some(s) => s,
-        None => return false,
-    };
+/// Add a single rule to the engine, returning a status
+///
+/// The rule is kept as a user rule, so it survives filter list reloads.
+/// Adding a rule twice succeeds; comments and malformed rules are
+/// rejected with `ParseError`. `EngineBusy` means the rules couldn't be
+/// patched in time and the call can be retried.
+#[no_mangle]
+pub extern "C" fn adblock_engine_add_rule_status(
+    engine: *mut c_void,
+    rule: *const c_char,
+) -> AdblockStatus {
+    catch_panic(ptr::null_mut(), || {
+        let Some(engine) = get_engine_ref(engine) else {
+            return AdblockStatus::NullPointer;
+        };
+        let rule = match c_str_status(rule) {
+            Ok(rule) => rule,
+            Err(status) => return status,
+        };
+
+        let Ok(mut core) = engine.core.write() else {
+            return AdblockStatus::LockPoisoned;
+        };
+        match core.add_custom_rule(rule) {
+            Ok(_) => AdblockStatus::Ok,
+            Err(e) => e.into(),
+        }
+    })
+}
+
+/// Remove a rule added with `adblock_engine_add_rule`
+#[no_mangle]
+pub extern "C" fn adblock_engine_remove_rule(
+    engine: *mut c_void,
+    rule: *const c_char,
+    error: *mut AdblockErrorCode,
+) -> bool {
+    catch_panic(error, || {
+        status_to_bool(adblock_engine_remove_rule_status(engine, rule), error)
+    })
+}
+
+/// Remove a rule added with `adblock_engine_add_rule`, returning a status
+///
+/// Returns `NotSupported` if the rule was not added that way; rules from
+/// filter lists go away with their list.
+#[no_mangle]
+pub extern "C" fn adblock_engine_remove_rule_status(
+    engine: *mut c_void,
+    rule: *const c_char,
+) -> AdblockStatus {
+    catch_panic(ptr::null_mut(), || {
+        let Some(engine) = get_engine_ref(engine) else {
+            return AdblockStatus::NullPointer;
+        };
+        let rule = match c_str_status(rule) {
+            Ok(rule) => rule,
+            Err(status) => return status,
+        };
+
+        let Ok(mut core) = engine.core.write() else {
+            return AdblockStatus::LockPoisoned;
+        };
+        match core.remove_custom_rule(rule) {
+            Ok(true) => AdblockStatus::Ok,
+            Ok(false) => AdblockStatus::NotSupported,
+            Err(e) => e.into(),
+        }
+    })
+}
+
+/// Number of network rules in the engine, added rules included
+#[no_mangle]
+pub extern "C" fn adblock_engine_rule_count(
+    engine: *mut c_void,
+    error: *mut AdblockErrorCode,
+) -> usize {
+    catch_panic(error, || {
+        let Some(engine) = engine_arg(engine, error) else {
+            return 0;
+        };
+
+        let Ok(core) = engine.core.read() else {
+            set_error(error, AdblockErrorCode::LockPoisoned);
+            return 0;
+        };
+        set_error(error, AdblockErrorCode::Ok);
+        core.rule_count()
+    })
+}
+
+/// Load a filter list
+#[no_mangle]
+pub extern "C" fn adblock_engine_load_filter_list(
+    engine: *mut c_void,
+    filter_list: *const c_char,
+    error: *mut AdblockErrorCode,
+) -> bool {
+    catch_panic(error, || {
+        status_to_bool(
+            adblock_engine_load_filter_list_status(engine, filter_list),
+            error,
+        )
+    })
+}
+
+/// Load a filter list, returning a status
+#[no_mangle]
+pub extern "C" fn adblock_engine_load_filter_list_status(
+    engine: *mut c_void,
+    filter_list: *const c_char,
+) -> AdblockStatus {
+    catch_panic(ptr::null_mut(), || {
+        let Some(engine) = get_engine_ref(engine) else {
+            return AdblockStatus::NullPointer;
+        };
+
+        let filter_list_str = match c_str_status(filter_list) {
+            Ok(s) => s,
+            Err(status) => return status,
+        };
+
+        swap_in_filter_list(&engine, filter_list_str)
+    })
+}
+
+/// Build an engine from a filter list and swap it into the core
+///
+/// The engine is built before taking the core lock so requests are not
+/// held up, and swapped in place so statistics and event subscriptions
+/// are kept.
+fn swap_in_filter_list(engine: &AdBlockEngine, filter_list: &str) -> AdblockStatus {
+    swap_in_rule_set(
+        engine,
+        crate::rule_set::RuleSet::from_filter_list(filter_list),
+    )
+}
 
-    match engine.core.lock() {
+/// Build an engine from a rule set and swap both into the core
+fn swap_in_rule_set(engine: &AdBlockEngine, rule_set: crate::rule_set::RuleSet) -> AdblockStatus {
+    let Ok(filter_engine) = rule_set.build_engine() else {
+        return AdblockStatus::ParseError;
+    };
+    match engine.core.read() {
         Ok(core) => {
-            // For simplicity, we'll recreate the engine with the new rule
-            // In a real implementation, we'd want to add rules dynamically
-            drop(core);
-            true
+            core.swap_rule_set(rule_set, filter_engine);
+            AdblockStatus::Ok
         }
-        Err(_) => false,
+        Err(_) => AdblockStatus::LockPoisoned,
+    }
+}
+
+/// Download the configured filter lists and swap in the new rules,
+/// returning the new rule count
+fn update_filters(engine: &AdBlockEngine) -> Result<usize, String> {
+    let _updating = engine
+        .updating
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    let (update_config, events) = {
+        let core = engine.core.read().map_err(|_| "Engine lock poisoned")?;
+        (core.config().update_config(), Arc::clone(core.events()))
+    };
+    let mut updater = crate::FilterUpdater::new(update_config).map_err(|e| e.to_string())?;
+    updater.set_event_bus(events);
+    let content = updater.auto_update().map_err(|e| e.to_string())?;
+
+    match swap_in_filter_list(engine, &content) {
+        AdblockStatus::Ok => {}
+        status => return Err(format!("Failed to load the updated lists: {status:?}")),
     }
+    let core = engine.core.read().map_err(|_| "Engine lock poisoned")?;
+    Ok(core.rule_count())
+}
+
+/// Rebuild the engine rules from the enabled registered lists
+fn rebuild_from_lists(engine: &AdBlockEngine, lists: &FilterListRegistry) -> AdblockStatus {
+    swap_in_rule_set(engine, lists.rule_set())
+}
+
+/// Subscribe to a filter list (or replace its content) and rebuild the rules
+///
+/// The engine is rebuilt from every enabled registered list, replacing
+/// rules loaded with `adblock_engine_load_filter_list`. New content for an
+/// enabled list is applied in place as a diff against the previous copy,
+/// and a `rule_set_patched` event reports the added and removed counts.
+#[no_mangle]
+pub extern "C" fn adblock_engine_add_filter_list(
+    engine: *mut c_void,
+    url: *const c_char,
+    content: *const c_char,
+    error: *mut AdblockErrorCode,
+) -> bool {
+    catch_panic(error, || {
+        status_to_bool(
+            adblock_engine_add_filter_list_status(engine, url, content),
+            error,
+        )
+    })
+}
+
+/// Subscribe to a filter list, returning a status
+#[no_mangle]
+pub extern "C" fn adblock_engine_add_filter_list_status(
+    engine: *mut c_void,
+    url: *const c_char,
+    content: *const c_char,
+) -> AdblockStatus {
+    catch_panic(ptr::null_mut(), || {
+        let Some(engine) = get_engine_ref(engine) else {
+            return AdblockStatus::NullPointer;
+        };
+        let (url, content) = match (c_str_status(url), c_str_status(content)) {
+            (Ok(url), Ok(content)) => (url, content),
+            (Err(status), _) | (_, Err(status)) => return status,
+        };
+
+        let Ok(mut lists) = engine.lists.lock() else {
+            return AdblockStatus::LockPoisoned;
+        };
+
+        // An update of an enabled list is applied as a diff; security lists are
+        // rebuilt so their rules stay marked
+        let diff = lists
+            .get(url)
+            .filter(|info| info.enabled && !info.security)
+            .and_then(|_| lists.content(url))
+            .map(|old| FilterListDiff::compute(old, content));
+        lists.add_list(url, content);
+
+        let Some(diff) = diff else {
+            return rebuild_from_lists(&engine, &lists);
+        };
+        let patched = match engine.core.write() {
+            Ok(mut core) => core.apply_filter_diff(&diff).is_ok(),
+            Err(_) => return AdblockStatus::LockPoisoned,
+        };
+        if patched {
+            AdblockStatus::Ok
+        } else {
+            rebuild_from_lists(&engine, &lists)
+        }
+    })
+}
+
+/// Subscribe to a malware/phishing protection list (or replace its
+/// content) and rebuild the rules
+///
+/// Requests blocked by its rules get `AdblockVerdict::Malware` from
+/// `adblock_engine_check_url`.
+#[no_mangle]
+pub extern "C" fn adblock_engine_add_security_list(
+    engine: *mut c_void,
+    url: *const c_char,
+    content: *const c_char,
+    error: *mut AdblockErrorCode,
+) -> bool {
+    catch_panic(error, || {
+        status_to_bool(
+            adblock_engine_add_security_list_status(engine, url, content),
+            error,
+        )
+    })
+}
+
+/// Subscribe to a malware/phishing protection list, returning a status
+#[no_mangle]
+pub extern "C" fn adblock_engine_add_security_list_status(
+    engine: *mut c_void,
+    url: *const c_char,
+    content: *const c_char,
+) -> AdblockStatus {
+    catch_panic(ptr::null_mut(), || {
+        let Some(engine) = get_engine_ref(engine) else {
+            return AdblockStatus::NullPointer;
+        };
+        let (url, content) = match (c_str_status(url), c_str_status(content)) {
+            (Ok(url), Ok(content)) => (url, content),
+            (Err(status), _) | (_, Err(status)) => return status,
+        };
+
+        let Ok(mut lists) = engine.lists.lock() else {
+            return AdblockStatus::LockPoisoned;
+        };
+        lists.add_list(url, content);
+        lists.set_security(url, true);
+        rebuild_from_lists(&engine, &lists)
+    })
+}
+
+/// Unsubscribe from a filter list and rebuild the rules
+#[no_mangle]
+pub extern "C" fn adblock_engine_remove_filter_list(
+    engine: *mut c_void,
+    url: *const c_char,
+    error: *mut AdblockErrorCode,
+) -> bool {
+    catch_panic(error, || {
+        status_to_bool(adblock_engine_remove_filter_list_status(engine, url), error)
+    })
+}
+
+/// Unsubscribe from a filter list, returning a status
+///
+/// Returns `NotSupported` if the list is not registered.
+#[no_mangle]
+pub extern "C" fn adblock_engine_remove_filter_list_status(
+    engine: *mut c_void,
+    url: *const c_char,
+) -> AdblockStatus {
+    catch_panic(ptr::null_mut(), || {
+        let Some(engine) = get_engine_ref(engine) else {
+            return AdblockStatus::NullPointer;
+        };
+        let url = match c_str_status(url) {
+            Ok(url) => url,
+            Err(status) => return status,
+        };
+
+        let Ok(mut lists) = engine.lists.lock() else {
+            return AdblockStatus::LockPoisoned;
+        };
+        if !lists.remove_list(url) {
+            return AdblockStatus::NotSupported;
+        }
+        rebuild_from_lists(&engine, &lists)
+    })
+}
+
+/// Enable or disable a registered filter list and rebuild the rules
+#[no_mangle]
+pub extern "C" fn adblock_engine_set_filter_list_enabled(
+    engine: *mut c_void,
+    url: *const c_char,
+    enabled: bool,
+    error: *mut AdblockErrorCode,
+) -> bool {
+    catch_panic(error, || {
+        status_to_bool(
+            adblock_engine_set_filter_list_enabled_status(engine, url, enabled),
+            error,
+        )
+    })
+}
+
+/// Enable or disable a registered filter list, returning a status
+///
+/// Returns `NotSupported` if the list is not registered.
+#[no_mangle]
+pub extern "C" fn adblock_engine_set_filter_list_enabled_status(
+    engine: *mut c_void,
+    url: *const c_char,
+    enabled: bool,
+) -> AdblockStatus {
+    catch_panic(ptr::null_mut(), || {
+        let Some(engine) = get_engine_ref(engine) else {
+            return AdblockStatus::NullPointer;
+        };
+        let url = match c_str_status(url) {
+            Ok(url) => url,
+            Err(status) => return status,
+        };
+
+        let Ok(mut lists) = engine.lists.lock() else {
+            return AdblockStatus::LockPoisoned;
+        };
+        if !lists.set_enabled(url, enabled) {
+            return AdblockStatus::NotSupported;
+        }
+        rebuild_from_lists(&engine, &lists)
+    })
+}
+
+/// Get the registered filter lists and their metadata as a JSON array
+///
+/// The returned string must be freed with `adblock_free_string`
+#[no_mangle]
+pub extern "C" fn adblock_engine_get_filter_lists(
+    engine: *mut c_void,
+    error: *mut AdblockErrorCode,
+) -> *mut c_char {
+    catch_panic(error, || {
+        let Some(engine) = engine_arg(engine, error) else {
+            return ptr::null_mut();
+        };
+
+        let Ok(lists) = engine.lists.lock() else {
+            set_error(error, AdblockErrorCode::LockPoisoned);
+            return ptr::null_mut();
+        };
+        match lists.to_json() {
+            Ok(json) => into_c_string(json, error),
+            Err(_) => {
+                set_error(error, AdblockErrorCode::SerializationFailed);
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Get the engine state for an About/Diagnostics screen as JSON
+///
+/// Holds the engine `version`, `rule_counts` by kind, the enabled `lists`
+/// with their versions, when a list was `last_updated` and an estimate of
+/// the rules' `memory_bytes`.
+/// The returned string must be freed with `adblock_free_string`
+#[no_mangle]
+pub extern "C" fn adblock_engine_get_info(
+    engine: *mut c_void,
+    error: *mut AdblockErrorCode,
+) -> *mut c_char {
+    catch_panic(error, || {
+        let Some(engine) = engine_arg(engine, error) else {
+            return ptr::null_mut();
+        };
+
+        let (Ok(core), Ok(lists)) = (engine.core.read(), engine.lists.lock()) else {
+            set_error(error, AdblockErrorCode::LockPoisoned);
+            return ptr::null_mut();
+        };
+        let filter_engine = core.engine();
+        let enabled: Vec<_> = lists
+            .lists()
+            .into_iter()
+            .filter(|list| list.enabled)
+            .collect();
+        let info = serde_json::json!({
+            "version": env!("CARGO_PKG_VERSION"),
+            "compiled_format_version": crate::compiled::COMPILED_FORMAT_VERSION,
+            "rule_counts": filter_engine.rule_counts(),
+            "lists": enabled,
+            "last_updated": enabled.iter().map(|list| list.last_updated).max(),
+            "memory_bytes": filter_engine.memory_usage(),
+        });
+        into_c_string(info.to_string(), error)
+    })
+}
+
+/// Update the configured filter lists on a background thread, e.g. from a
+/// WorkManager job or background task, without blocking the caller
+///
+/// Lists are fetched as by the update scheduler: lists not due yet come
+/// from the cache, and downloads are deferred on a metered connection.
+/// Once the thread is started, `callback` is called exactly once on it,
+/// after the new rules are live; it may call back into the engine.
+/// Updates of one engine run one at a time.
+#[no_mangle]
+pub extern "C" fn adblock_engine_update_filters_async(
+    engine: *mut c_void,
+    callback: AdblockUpdateCallback,
+    user_data: *mut c_void,
+    error: *mut AdblockErrorCode,
+) -> bool {
+    catch_panic(error, || {
+        let Some(engine) = engine_arg(engine, error) else {
+            return false;
+        };
+        let Some(callback) = callback else {
+            set_error(error, AdblockErrorCode::NullPointer);
+            return false;
+        };
+
+        let context = UpdateCallbackContext {
+            callback,
+            user_data,
+        };
+        let spawned = std::thread::Builder::new()
+            .name("adblock-update".to_string())
+            .spawn(move || {
+                let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                    update_filters(&engine)
+                }))
+                .unwrap_or_else(|_| Err("Filter update panicked".to_string()));
+                // Release the engine before the callback, which may destroy it
+                drop(engine);
+                context.finish(result);
+            });
+        match spawned {
+            Ok(_) => {
+                set_error(error, AdblockErrorCode::Ok);
+                true
+            }
+            Err(_) => {
+                set_error(error, AdblockErrorCode::EngineFailure);
+                false
+            }
+        }
+    })
+}
+
+/// Get statistics as JSON string
+#[no_mangle]
+pub extern "C" fn adblock_engine_get_stats(
+    engine: *mut c_void,
+    error: *mut AdblockErrorCode,
+) -> *mut c_char {
+    catch_panic(error, || {
+        let Some(engine) = engine_arg(engine, error) else {
+            return ptr::null_mut();
+        };
+
+        let Ok(core) = engine.core.read() else {
+            set_error(error, AdblockErrorCode::LockPoisoned);
+            return ptr::null_mut();
+        };
+        let mut json = String::new();
+        let _ = write_stats_json(&core, &mut json);
+        into_c_string(json, error)
+    })
+}
+
+/// Get the blocked-request counts of one host as JSON
+///
+/// Returns `null` as JSON if nothing was blocked on the host.
+/// The returned string must be freed with `adblock_free_string`
+#[no_mangle]
+pub extern "C" fn adblock_engine_get_domain_stats(
+    engine: *mut c_void,
+    domain: *const c_char,
+    error: *mut AdblockErrorCode,
+) -> *mut c_char {
+    catch_panic(error, || {
+        let Some(engine) = engine_arg(engine, error) else {
+            return ptr::null_mut();
+        };
+        let Some(domain) = c_str_arg(domain, error) else {
+            return ptr::null_mut();
+        };
+
+        let Ok(core) = engine.core.read() else {
+            set_error(error, AdblockErrorCode::LockPoisoned);
+            return ptr::null_mut();
+        };
+        match serde_json::to_string(&core.domain_stats(domain)) {
+            Ok(json) => into_c_string(json, error),
+            Err(_) => {
+                set_error(error, AdblockErrorCode::SerializationFailed);
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Get a page of the blocked domains as a JSON array, most blocked first
+///
+/// The returned string must be freed with `adblock_free_string`
+#[no_mangle]
+pub extern "C" fn adblock_engine_top_blocked(
+    engine: *mut c_void,
+    offset: usize,
+    limit: usize,
+    error: *mut AdblockErrorCode,
+) -> *mut c_char {
+    catch_panic(error, || {
+        let Some(engine) = engine_arg(engine, error) else {
+            return ptr::null_mut();
+        };
+
+        let Ok(core) = engine.core.read() else {
+            set_error(error, AdblockErrorCode::LockPoisoned);
+            return ptr::null_mut();
+        };
+        match serde_json::to_string(&core.top_blocked(offset, limit)) {
+            Ok(json) => into_c_string(json, error),
+            Err(_) => {
+                set_error(error, AdblockErrorCode::SerializationFailed);
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Write the statistics JSON representation
+///
+/// Reads the live counters rather than taking a snapshot, which would copy
+/// the per-domain stats.
+fn write_stats_json(core: &AdBlockCore, out: &mut impl fmt::Write) -> fmt::Result {
+    let stats = core.shared_statistics();
+    let session = stats.session();
+    write!(
+        out,
+        concat!(
+            r#"{{"blocked_count":{},"allowed_count":{},"data_saved":{},"paused":{},"paused_count":{},"malware_count":{},"#,
+            r#""session_blocked_count":{},"session_allowed_count":{},"session_data_saved":{},"session_paused_count":{}}}"#
+        ),
+        stats.get_blocked_count(),
+        stats.get_allowed_count(),
+        stats.get_data_saved(),
+        core.is_paused(),
+        stats.get_paused_count(),
+        stats.get_malware_count(),
+        session.blocked_count,
+        session.allowed_count,
+        session.data_saved,
+        session.paused_count
+    )
+}
+
+/// Writer that fills a caller buffer and counts the full output length
+struct BufWriter<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl fmt::Write for BufWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let end = self.len + s.len();
+        if end <= self.buf.len() {
+            self.buf[self.len..end].copy_from_slice(s.as_bytes());
+        }
+        self.len = end;
+        Ok(())
+    }
+}
+
+/// Write statistics JSON into a caller-provided buffer
+///
+/// Returns the buffer size needed, including the NUL terminator. The JSON
+/// is written only if it fits in `cap` bytes; otherwise `buf` holds an
+/// empty string (if `cap > 0`) and the caller should retry with a buffer of
+/// the returned size. Returns 0 on error. No heap allocation is made.
+#[no_mangle]
+pub extern "C" fn adblock_engine_get_stats_buf(
+    engine: *mut c_void,
+    buf: *mut c_char,
+    cap: usize,
+    error: *mut AdblockErrorCode,
+) -> usize {
+    catch_panic(error, || {
+        let Some(engine) = engine_arg(engine, error) else {
+            return 0;
+        };
+        if buf.is_null() && cap > 0 {
+            set_error(error, AdblockErrorCode::NullPointer);
+            return 0;
+        }
+
+        let Ok(core) = engine.core.read() else {
+            set_error(error, AdblockErrorCode::LockPoisoned);
+            return 0;
+        };
+
+        let buf: &mut [u8] = if cap == 0 {
+            &mut []
+        } else {
+            unsafe { std::slice::from_raw_parts_mut(buf as *mut u8, cap) }
+        };
+        // Reserve the last byte for the NUL terminator
+        let (body, _) = buf.split_at_mut(cap.saturating_sub(1));
+        let mut writer = BufWriter { buf: body, len: 0 };
+        let _ = write_stats_json(&core, &mut writer);
+        let needed = writer.len + 1;
+
+        if needed <= cap {
+            buf[needed - 1] = 0;
+        } else if cap > 0 {
+            buf[0] = 0;
+        }
+
+        set_error(error, AdblockErrorCode::Ok);
+        needed
+    })
+}
+
+/// Back up config, custom rules, whitelist and statistics as JSON
+///
+/// The returned string must be freed with `adblock_free_string`
+#[no_mangle]
+pub extern "C" fn adblock_engine_create_backup(
+    engine: *mut c_void,
+    error: *mut AdblockErrorCode,
+) -> *mut c_char {
+    catch_panic(error, || {
+        let Some(engine) = engine_arg(engine, error) else {
+            return ptr::null_mut();
+        };
+
+        let Ok(core) = engine.core.read() else {
+            set_error(error, AdblockErrorCode::LockPoisoned);
+            return ptr::null_mut();
+        };
+        match core.create_backup().to_json() {
+            Ok(json) => into_c_string(json, error),
+            Err(_) => {
+                set_error(error, AdblockErrorCode::SerializationFailed);
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Write a gzip-compressed backup into a caller-provided buffer
+///
+/// Returns the size of the compressed backup. It is written only if it
+/// fits in `cap` bytes; otherwise the caller should retry with a larger
+/// buffer. The backup is timestamped, so its size can differ slightly
+/// between calls; use the size returned by the call that wrote it.
+/// Returns 0 on error.
+#[no_mangle]
+pub extern "C" fn adblock_engine_create_backup_gzip(
+    engine: *mut c_void,
+    buf: *mut u8,
+    cap: usize,
+    error: *mut AdblockErrorCode,
+) -> usize {
+    catch_panic(error, || {
+        let Some(engine) = engine_arg(engine, error) else {
+            return 0;
+        };
+        if buf.is_null() && cap > 0 {
+            set_error(error, AdblockErrorCode::NullPointer);
+            return 0;
+        }
+
+        let Ok(core) = engine.core.read() else {
+            set_error(error, AdblockErrorCode::LockPoisoned);
+            return 0;
+        };
+        let Ok(gzip) = core.create_backup().to_gzip() else {
+            set_error(error, AdblockErrorCode::SerializationFailed);
+            return 0;
+        };
+
+        if gzip.len() <= cap {
+            unsafe { ptr::copy_nonoverlapping(gzip.as_ptr(), buf.cast::<u8>(), gzip.len()) };
+        }
+        set_error(error, AdblockErrorCode::Ok);
+        gzip.len()
+    })
+}
+
+/// Restore a backup from JSON or gzip-compressed JSON bytes
+///
+/// Config, custom rules, whitelist and statistics are replaced; the loaded
+/// filter lists are kept.
+#[no_mangle]
+pub extern "C" fn adblock_engine_restore_backup(
+    engine: *mut c_void,
+    data: *const u8,
+    len: usize,
+    error: *mut AdblockErrorCode,
+) -> bool {
+    catch_panic(error, || {
+        status_to_bool(
+            adblock_engine_restore_backup_status(engine, data, len),
+            error,
+        )
+    })
+}
+
+/// Restore a backup, returning a status
+///
+/// Returns `ParseError` if the backup is malformed or invalid.
+#[no_mangle]
+pub extern "C" fn adblock_engine_restore_backup_status(
+    engine: *mut c_void,
+    data: *const u8,
+    len: usize,
+) -> AdblockStatus {
+    catch_panic(ptr::null_mut(), || {
+        let Some(engine) = get_engine_ref(engine) else {
+            return AdblockStatus::NullPointer;
+        };
+        if data.is_null() {
+            return AdblockStatus::NullPointer;
+        }
+
+        let bytes = unsafe { std::slice::from_raw_parts(data.cast::<u8>(), len) };
+        let Ok(backup) = crate::backup::BackupData::from_bytes(bytes) else {
+            return AdblockStatus::ParseError;
+        };
+        let Ok(mut core) = engine.core.write() else {
+            return AdblockStatus::LockPoisoned;
+        };
+        match core.restore_backup(&backup) {
+            Ok(()) => AdblockStatus::Ok,
+            Err(_) => AdblockStatus::ParseError,
+        }
+    })
+}
+
+/// Export custom rules and whitelist as EasyList-compatible text
+///
+/// The returned string must be freed with `adblock_free_string`
+#[no_mangle]
+pub extern "C" fn adblock_engine_export_user_rules(
+    engine: *mut c_void,
+    error: *mut AdblockErrorCode,
+) -> *mut c_char {
+    catch_panic(error, || {
+        let Some(engine) = engine_arg(engine, error) else {
+            return ptr::null_mut();
+        };
+
+        let Ok(core) = engine.core.read() else {
+            set_error(error, AdblockErrorCode::LockPoisoned);
+            return ptr::null_mut();
+        };
+        into_c_string(core.export_user_rules(), error)
+    })
+}
+
+/// Import custom rules and whitelist entries from filter list text
+///
+/// Returns the import report as JSON (added, duplicate and invalid lines).
+/// The returned string must be freed with `adblock_free_string`
+#[no_mangle]
+pub extern "C" fn adblock_engine_import_user_rules(
+    engine: *mut c_void,
+    text: *const c_char,
+    error: *mut AdblockErrorCode,
+) -> *mut c_char {
+    catch_panic(error, || {
+        let Some(engine) = engine_arg(engine, error) else {
+            return ptr::null_mut();
+        };
+        let Some(text) = c_str_arg(text, error) else {
+            return ptr::null_mut();
+        };
+
+        let Ok(mut core) = engine.core.write() else {
+            set_error(error, AdblockErrorCode::LockPoisoned);
+            return ptr::null_mut();
+        };
+        let Ok(report) = core.import_user_rules(text) else {
+            set_error(error, AdblockErrorCode::EngineFailure);
+            return ptr::null_mut();
+        };
+        match serde_json::to_string(&report) {
+            Ok(json) => into_c_string(json, error),
+            Err(_) => {
+                set_error(error, AdblockErrorCode::SerializationFailed);
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Pause blocking for `seconds`
+#[no_mangle]
+pub extern "C" fn adblock_engine_pause(
+    engine: *mut c_void,
+    seconds: u64,
+    error: *mut AdblockErrorCode,
+) -> bool {
+    catch_panic(error, || {
+        status_to_bool(adblock_engine_pause_status(engine, seconds), error)
+    })
+}
+
+/// Pause blocking for `seconds`, returning a status
+#[no_mangle]
+pub extern "C" fn adblock_engine_pause_status(engine: *mut c_void, seconds: u64) -> AdblockStatus {
+    catch_panic(ptr::null_mut(), || {
+        let Some(engine) = get_engine_ref(engine) else {
+            return AdblockStatus::NullPointer;
+        };
+
+        let Ok(mut core) = engine.core.write() else {
+            return AdblockStatus::LockPoisoned;
+        };
+        core.pause(std::time::Duration::from_secs(seconds));
+        AdblockStatus::Ok
+    })
+}
+
+/// End a manual pause; scheduled pause windows still apply
+#[no_mangle]
+pub extern "C" fn adblock_engine_resume(engine: *mut c_void, error: *mut AdblockErrorCode) -> bool {
+    catch_panic(error, || {
+        status_to_bool(adblock_engine_resume_status(engine), error)
+    })
+}
+
+/// End a manual pause, returning a status
+#[no_mangle]
+pub extern "C" fn adblock_engine_resume_status(engine: *mut c_void) -> AdblockStatus {
+    catch_panic(ptr::null_mut(), || {
+        let Some(engine) = get_engine_ref(engine) else {
+            return AdblockStatus::NullPointer;
+        };
+
+        let Ok(mut core) = engine.core.write() else {
+            return AdblockStatus::LockPoisoned;
+        };
+        core.resume();
+        AdblockStatus::Ok
+    })
+}
+
+/// Report whether the device is idle and charging
+///
+/// Call from the platform's idle and power callbacks. Cache compaction,
+/// rule reordering and other maintenance run in the background while the
+/// device is idle; some tasks also wait for it to be charging.
+#[no_mangle]
+pub extern "C" fn adblock_engine_set_device_conditions(
+    engine: *mut c_void,
+    idle: bool,
+    charging: bool,
+    error: *mut AdblockErrorCode,
+) -> bool {
+    catch_panic(error, || {
+        status_to_bool(
+            adblock_engine_set_device_conditions_status(engine, idle, charging),
+            error,
+        )
+    })
+}
+
+/// Report whether the device is idle and charging, returning a status
+#[no_mangle]
+pub extern "C" fn adblock_engine_set_device_conditions_status(
+    engine: *mut c_void,
+    idle: bool,
+    charging: bool,
+) -> AdblockStatus {
+    catch_panic(ptr::null_mut(), || {
+        let Some(engine) = get_engine_ref(engine) else {
+            return AdblockStatus::NullPointer;
+        };
+
+        let Ok(core) = engine.core.read() else {
+            return AdblockStatus::LockPoisoned;
+        };
+        core.set_device_conditions(DeviceConditions { idle, charging });
+        AdblockStatus::Ok
+    })
+}
+
+/// Whether blocking is paused now, manually or by the pause schedule
+#[no_mangle]
+pub extern "C" fn adblock_engine_is_paused(
+    engine: *mut c_void,
+    error: *mut AdblockErrorCode,
+) -> bool {
+    catch_panic(error, || {
+        let Some(engine) = engine_arg(engine, error) else {
+            return false;
+        };
+
+        let Ok(core) = engine.core.read() else {
+            set_error(error, AdblockErrorCode::LockPoisoned);
+            return false;
+        };
+        set_error(error, AdblockErrorCode::Ok);
+        core.is_paused()
+    })
+}
+
+/// Replace the pause schedule with a JSON array of windows, e.g.
+/// `[{"days":["Mon","Tue"],"start":"09:00:00","end":"17:00:00"}]`
+#[no_mangle]
+pub extern "C" fn adblock_engine_set_pause_schedule(
+    engine: *mut c_void,
+    schedule_json: *const c_char,
+    error: *mut AdblockErrorCode,
+) -> bool {
+    catch_panic(error, || {
+        status_to_bool(
+            adblock_engine_set_pause_schedule_status(engine, schedule_json),
+            error,
+        )
+    })
+}
+
+/// Replace the pause schedule, returning a status
+///
+/// Returns `ParseError` if the JSON is not a list of windows.
+#[no_mangle]
+pub extern "C" fn adblock_engine_set_pause_schedule_status(
+    engine: *mut c_void,
+    schedule_json: *const c_char,
+) -> AdblockStatus {
+    catch_panic(ptr::null_mut(), || {
+        let Some(engine) = get_engine_ref(engine) else {
+            return AdblockStatus::NullPointer;
+        };
+        let json = match c_str_status(schedule_json) {
+            Ok(json) => json,
+            Err(status) => return status,
+        };
+        let Ok(schedule) = serde_json::from_str(json) else {
+            return AdblockStatus::ParseError;
+        };
+
+        let Ok(mut core) = engine.core.write() else {
+            return AdblockStatus::LockPoisoned;
+        };
+        let mut config = core.config().clone();
+        config.pause_schedule = schedule;
+        match core.update_config(config) {
+            Ok(()) => AdblockStatus::Ok,
+            Err(_) => AdblockStatus::ParseError,
+        }
+    })
+}
+
+/// Get the built-in filter list catalog as a JSON array
+///
+/// Each list has an `id`, `title`, `category`, `region`, `languages`,
+/// `size_kb`, `recommended` flag and its download `urls`. The returned
+/// string must be freed with `adblock_free_string`
+#[no_mangle]
+pub extern "C" fn adblock_get_list_catalog(error: *mut AdblockErrorCode) -> *mut c_char {
+    catch_panic(error, || {
+        match crate::list_catalog::ListCatalog::builtin().to_json() {
+            Ok(json) => into_c_string(json, error),
+            Err(_) => {
+                set_error(error, AdblockErrorCode::SerializationFailed);
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Parse a subscription link or filter list URL into JSON
+///
+/// Accepts `abp:subscribe?location=…&title=…` links and plain http(s)
+/// URLs. The result has the list `url`, its `title` and the `preset_id`
+/// of a catalog list, or is null with `InvalidArgument`. The returned
+/// string must be freed with `adblock_free_string`
+#[no_mangle]
+pub extern "C" fn adblock_parse_subscription_link(
+    link: *const c_char,
+    error: *mut AdblockErrorCode,
+) -> *mut c_char {
+    catch_panic(error, || {
+        let Some(link) = c_str_arg(link, error) else {
+            return ptr::null_mut();
+        };
+        let Ok(subscription) = crate::subscription::parse_link(link) else {
+            set_error(error, AdblockErrorCode::InvalidArgument);
+            return ptr::null_mut();
+        };
+        match serde_json::to_string(&subscription) {
+            Ok(json) => into_c_string(json, error),
+            Err(_) => {
+                set_error(error, AdblockErrorCode::SerializationFailed);
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Enable or disable a catalog list by ID
+#[no_mangle]
+pub extern "C" fn adblock_engine_set_list_preset_enabled(
+    engine: *mut c_void,
+    id: *const c_char,
+    enabled: bool,
+    error: *mut AdblockErrorCode,
+) -> bool {
+    catch_panic(error, || {
+        status_to_bool(
+            adblock_engine_set_list_preset_enabled_status(engine, id, enabled),
+            error,
+        )
+    })
+}
+
+/// Enable or disable a catalog list by ID, returning a status
+///
+/// An ID missing from the catalog reports [`AdblockStatus::ParseError`].
+#[no_mangle]
+pub extern "C" fn adblock_engine_set_list_preset_enabled_status(
+    engine: *mut c_void,
+    id: *const c_char,
+    enabled: bool,
+) -> AdblockStatus {
+    catch_panic(ptr::null_mut(), || {
+        let Some(engine) = get_engine_ref(engine) else {
+            return AdblockStatus::NullPointer;
+        };
+        let id = match c_str_status(id) {
+            Ok(id) => id,
+            Err(status) => return status,
+        };
+
+        let Ok(mut core) = engine.core.write() else {
+            return AdblockStatus::LockPoisoned;
+        };
+        match core.set_list_preset_enabled(id, enabled) {
+            Ok(()) => AdblockStatus::Ok,
+            Err(_) => AdblockStatus::ParseError,
+        }
+    })
+}
+
+/// Set the blocking mode of a site and its subdomains
+///
+/// The mode is persisted in the config and included in backups.
+#[no_mangle]
+pub extern "C" fn adblock_engine_set_site_mode(
+    engine: *mut c_void,
+    domain: *const c_char,
+    mode: AdblockSiteMode,
+    error: *mut AdblockErrorCode,
+) -> bool {
+    catch_panic(error, || {
+        status_to_bool(
+            adblock_engine_set_site_mode_status(engine, domain, mode),
+            error,
+        )
+    })
+}
+
+/// Set the blocking mode of a site, returning a status
+#[no_mangle]
+pub extern "C" fn adblock_engine_set_site_mode_status(
+    engine: *mut c_void,
+    domain: *const c_char,
+    mode: AdblockSiteMode,
+) -> AdblockStatus {
+    catch_panic(ptr::null_mut(), || {
+        let Some(engine) = get_engine_ref(engine) else {
+            return AdblockStatus::NullPointer;
+        };
+        let domain = match c_str_status(domain) {
+            Ok(domain) => domain,
+            Err(status) => return status,
+        };
+
+        let Ok(mut core) = engine.core.write() else {
+            return AdblockStatus::LockPoisoned;
+        };
+        core.set_site_mode(domain, mode.into());
+        AdblockStatus::Ok
+    })
+}
+
+/// Go back to the default mode for a site
+///
+/// Returns `false` with `Ok` if the site had no mode set.
+#[no_mangle]
+pub extern "C" fn adblock_engine_clear_site_mode(
+    engine: *mut c_void,
+    domain: *const c_char,
+    error: *mut AdblockErrorCode,
+) -> bool {
+    catch_panic(error, || {
+        let Some(engine) = engine_arg(engine, error) else {
+            return false;
+        };
+        let Some(domain) = c_str_arg(domain, error) else {
+            return false;
+        };
+
+        let Ok(mut core) = engine.core.write() else {
+            set_error(error, AdblockErrorCode::LockPoisoned);
+            return false;
+        };
+        set_error(error, AdblockErrorCode::Ok);
+        core.clear_site_mode(domain)
+    })
+}
+
+/// Get the blocking mode that applies to a host
+#[no_mangle]
+pub extern "C" fn adblock_engine_get_site_mode(
+    engine: *mut c_void,
+    host: *const c_char,
+    error: *mut AdblockErrorCode,
+) -> AdblockSiteMode {
+    catch_panic(error, || {
+        let default = SiteMode::default().into();
+        let Some(engine) = engine_arg(engine, error) else {
+            return default;
+        };
+        let Some(host) = c_str_arg(host, error) else {
+            return default;
+        };
+
+        let Ok(core) = engine.core.read() else {
+            set_error(error, AdblockErrorCode::LockPoisoned);
+            return default;
+        };
+        set_error(error, AdblockErrorCode::Ok);
+        core.site_mode(host).into()
+    })
+}
+
+/// Get the sites with a mode set as a JSON object, e.g. `{"example.com":"off"}`
+///
+/// The returned string must be freed with `adblock_free_string`
+#[no_mangle]
+pub extern "C" fn adblock_engine_get_site_modes(
+    engine: *mut c_void,
+    error: *mut AdblockErrorCode,
+) -> *mut c_char {
+    catch_panic(error, || {
+        let Some(engine) = engine_arg(engine, error) else {
+            return ptr::null_mut();
+        };
+
+        let Ok(core) = engine.core.read() else {
+            set_error(error, AdblockErrorCode::LockPoisoned);
+            return ptr::null_mut();
+        };
+        match serde_json::to_string(&core.config().site_policy) {
+            Ok(json) => into_c_string(json, error),
+            Err(_) => {
+                set_error(error, AdblockErrorCode::SerializationFailed);
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Start logging checked requests for the activity screen
+///
+/// Entries go to a binary file at `path` capped at `max_bytes` (0 for the
+/// default cap), or stay in memory if `path` is null.
+#[no_mangle]
+pub extern "C" fn adblock_engine_enable_query_log(
+    engine: *mut c_void,
+    path: *const c_char,
+    max_bytes: u64,
+    error: *mut AdblockErrorCode,
+) -> bool {
+    catch_panic(error, || {
+        status_to_bool(
+            adblock_engine_enable_query_log_status(engine, path, max_bytes),
+            error,
+        )
+    })
+}
+
+/// Start logging checked requests, returning a status
+#[no_mangle]
+pub extern "C" fn adblock_engine_enable_query_log_status(
+    engine: *mut c_void,
+    path: *const c_char,
+    max_bytes: u64,
+) -> AdblockStatus {
+    catch_panic(ptr::null_mut(), || {
+        let Some(engine) = get_engine_ref(engine) else {
+            return AdblockStatus::NullPointer;
+        };
+        let path = if path.is_null() {
+            None
+        } else {
+            match c_str_status(path) {
+                Ok(path) => Some(std::path::Path::new(path)),
+                Err(status) => return status,
+            }
+        };
+        let max_bytes = match max_bytes {
+            0 => crate::query_log::DEFAULT_QUERY_LOG_BYTES,
+            max_bytes => max_bytes,
+        };
+
+        let Ok(mut core) = engine.core.write() else {
+            return AdblockStatus::LockPoisoned;
+        };
+        match core.enable_query_log(path, max_bytes) {
+            Ok(()) => AdblockStatus::Ok,
+            Err(_) => AdblockStatus::IoError,
+        }
+    })
+}
+
+/// Remove every query log entry
+#[no_mangle]
+pub extern "C" fn adblock_engine_clear_query_log(
+    engine: *mut c_void,
+    error: *mut AdblockErrorCode,
+) -> bool {
+    catch_panic(error, || {
+        status_to_bool(adblock_engine_clear_query_log_status(engine), error)
+    })
+}
+
+/// Remove every query log entry, returning a status
+#[no_mangle]
+pub extern "C" fn adblock_engine_clear_query_log_status(engine: *mut c_void) -> AdblockStatus {
+    catch_panic(ptr::null_mut(), || {
+        let Some(engine) = get_engine_ref(engine) else {
+            return AdblockStatus::NullPointer;
+        };
+
+        let Ok(mut core) = engine.core.write() else {
+            return AdblockStatus::LockPoisoned;
+        };
+        match core.clear_query_log() {
+            Ok(()) => AdblockStatus::Ok,
+            Err(_) => AdblockStatus::IoError,
+        }
+    })
+}
+
+/// Query log search request
+#[derive(serde::Deserialize)]
+struct QueryLogSearch {
+    #[serde(flatten)]
+    filter: crate::query_log::QueryFilter,
+    #[serde(default)]
+    offset: usize,
+    #[serde(default = "default_query_log_limit")]
+    limit: usize,
+}
+
+fn default_query_log_limit() -> usize {
+    50
+}
+
+/// Search the query log
+///
+/// `query_json` holds optional `domain`, `verdict`, `since_ms`, `until_ms`
+/// and `app` criteria plus `offset` and `limit` (default 50), e.g.
+/// `{"verdict":"blocked","offset":50}`. Returns
+/// `{"total":N,"entries":[...]}` with entries newest first, where `total`
+/// counts every match. Fails with `NotSupported` if the log is disabled.
+/// The returned string must be freed with `adblock_free_string`
+#[no_mangle]
+pub extern "C" fn adblock_engine_search_query_log(
+    engine: *mut c_void,
+    query_json: *const c_char,
+    error: *mut AdblockErrorCode,
+) -> *mut c_char {
+    catch_panic(error, || {
+        let Some(engine) = engine_arg(engine, error) else {
+            return ptr::null_mut();
+        };
+        let Some(query_json) = c_str_arg(query_json, error) else {
+            return ptr::null_mut();
+        };
+        let Ok(search) = serde_json::from_str::<QueryLogSearch>(query_json) else {
+            set_error(error, AdblockErrorCode::EngineFailure);
+            return ptr::null_mut();
+        };
+
+        let Ok(core) = engine.core.read() else {
+            set_error(error, AdblockErrorCode::LockPoisoned);
+            return ptr::null_mut();
+        };
+        let Some(log) = core.query_log() else {
+            set_error(error, AdblockErrorCode::NotSupported);
+            return ptr::null_mut();
+        };
+        let result = serde_json::json!({
+            "total": log.count(&search.filter),
+            "entries": log.search(&search.filter, search.offset, search.limit),
+        });
+        into_c_string(result.to_string(), error)
+    })
+}
+
+/// Get the recorded crash reports as a JSON array, newest first
+///
+/// The returned string must be freed with `adblock_free_string`
+#[no_mangle]
+pub extern "C" fn adblock_get_crash_reports_json(
+    engine: *mut c_void,
+    error: *mut AdblockErrorCode,
+) -> *mut c_char {
+    catch_panic(error, || {
+        let Some(engine) = engine_arg(engine, error) else {
+            return ptr::null_mut();
+        };
+
+        let Ok(core) = engine.core.read() else {
+            set_error(error, AdblockErrorCode::LockPoisoned);
+            return ptr::null_mut();
+        };
+        let reports = core.crash_reporter().get_reports(usize::MAX);
+        match serde_json::to_string(&reports) {
+            Ok(json) => into_c_string(json, error),
+            Err(_) => {
+                set_error(error, AdblockErrorCode::SerializationFailed);
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Get the engine's recent log records as a JSON array, oldest first
+///
+/// Each record has a `timestamp`, `level`, `target` and `message`, plus
+/// `fields` for structured values. The log is shared by every engine in
+/// the process and keeps the last few hundred records; debug records are
+/// only kept while a config has `debug` set. The returned string must be
+/// freed with `adblock_free_string`
+#[no_mangle]
+pub extern "C" fn adblock_get_recent_logs(error: *mut AdblockErrorCode) -> *mut c_char {
+    catch_panic(error, || {
+        match serde_json::to_string(&crate::logging::recent_logs()) {
+            Ok(json) => into_c_string(json, error),
+            Err(_) => {
+                set_error(error, AdblockErrorCode::SerializationFailed);
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Delete all recorded crash reports, including persisted ones
+#[no_mangle]
+pub extern "C" fn adblock_clear_crash_reports(
+    engine: *mut c_void,
+    error: *mut AdblockErrorCode,
+) -> bool {
+    catch_panic(error, || {
+        status_to_bool(adblock_clear_crash_reports_status(engine), error)
+    })
+}
+
+/// Delete all recorded crash reports, returning a status
+#[no_mangle]
+pub extern "C" fn adblock_clear_crash_reports_status(engine: *mut c_void) -> AdblockStatus {
+    catch_panic(ptr::null_mut(), || {
+        let Some(engine) = get_engine_ref(engine) else {
+            return AdblockStatus::NullPointer;
+        };
+
+        let Ok(core) = engine.core.read() else {
+            return AdblockStatus::LockPoisoned;
+        };
+        core.crash_reporter().clear_reports();
+        AdblockStatus::Ok
+    })
+}
+
+/// Enable or disable analytics
+///
+/// Disabling deletes buffered events and pending batches.
+#[no_mangle]
+pub extern "C" fn adblock_analytics_set_enabled(
+    engine: *mut c_void,
+    enabled: bool,
+    error: *mut AdblockErrorCode,
+) -> bool {
+    catch_panic(error, || {
+        status_to_bool(adblock_analytics_set_enabled_status(engine, enabled), error)
+    })
+}
+
+/// Enable or disable analytics, returning a status
+#[no_mangle]
+pub extern "C" fn adblock_analytics_set_enabled_status(
+    engine: *mut c_void,
+    enabled: bool,
+) -> AdblockStatus {
+    catch_panic(ptr::null_mut(), || {
+        let Some(engine) = get_engine_ref(engine) else {
+            return AdblockStatus::NullPointer;
+        };
+
+        let Ok(core) = engine.core.read() else {
+            return AdblockStatus::LockPoisoned;
+        };
+        core.analytics().set_enabled(enabled);
+        AdblockStatus::Ok
+    })
+}
+
+/// Get the oldest analytics batch awaiting upload as JSON
+///
+/// The object has `id`, `event_count` and `ndjson` fields. Returns null with
+/// `Ok` when nothing is pending, and `NotSupported` if the config has no
+/// `analytics_dir`. The batch is returned again until
+/// `adblock_analytics_mark_uploaded` is called with its id.
+/// The returned string must be freed with `adblock_free_string`
+#[no_mangle]
+pub extern "C" fn adblock_analytics_export_batch(
+    engine: *mut c_void,
+    error: *mut AdblockErrorCode,
+) -> *mut c_char {
+    catch_panic(error, || {
+        let Some(engine) = engine_arg(engine, error) else {
+            return ptr::null_mut();
+        };
+
+        let analytics = match engine.core.read() {
+            Ok(core) => Arc::clone(core.analytics()),
+            Err(_) => {
+                set_error(error, AdblockErrorCode::LockPoisoned);
+                return ptr::null_mut();
+            }
+        };
+        let batch = match analytics.export_batch() {
+            Ok(Some(batch)) => batch,
+            Ok(None) => {
+                set_error(error, AdblockErrorCode::Ok);
+                return ptr::null_mut();
+            }
+            Err(_) => {
+                set_error(error, AdblockErrorCode::NotSupported);
+                return ptr::null_mut();
+            }
+        };
+        match serde_json::to_string(&batch) {
+            Ok(json) => into_c_string(json, error),
+            Err(_) => {
+                set_error(error, AdblockErrorCode::SerializationFailed);
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Delete an uploaded analytics batch
+#[no_mangle]
+pub extern "C" fn adblock_analytics_mark_uploaded(
+    engine: *mut c_void,
+    batch_id: *const c_char,
+    error: *mut AdblockErrorCode,
+) -> bool {
+    catch_panic(error, || {
+        status_to_bool(
+            adblock_analytics_mark_uploaded_status(engine, batch_id),
+            error,
+        )
+    })
+}
+
+/// Delete an uploaded analytics batch, returning a status
+///
+/// Returns `NotSupported` if no batch with that id is pending.
+#[no_mangle]
+pub extern "C" fn adblock_analytics_mark_uploaded_status(
+    engine: *mut c_void,
+    batch_id: *const c_char,
+) -> AdblockStatus {
+    catch_panic(ptr::null_mut(), || {
+        let Some(engine) = get_engine_ref(engine) else {
+            return AdblockStatus::NullPointer;
+        };
+        let batch_id = match c_str_status(batch_id) {
+            Ok(batch_id) => batch_id,
+            Err(status) => return status,
+        };
+
+        let Ok(core) = engine.core.read() else {
+            return AdblockStatus::LockPoisoned;
+        };
+        if core.analytics().mark_uploaded(batch_id) {
+            AdblockStatus::Ok
+        } else {
+            AdblockStatus::NotSupported
+        }
+    })
+}
+
+/// Reset statistics
+///
+/// # Safety
+/// The engine pointer must be valid
+#[no_mangle]
+pub extern "C" fn adblock_engine_reset_stats(
+    engine: *mut c_void,
+    error: *mut AdblockErrorCode,
+) -> bool {
+    catch_panic(error, || {
+        status_to_bool(adblock_engine_reset_stats_status(engine), error)
+    })
+}
+
+/// Reset statistics, returning a status
+#[no_mangle]
+pub extern "C" fn adblock_engine_reset_stats_status(engine: *mut c_void) -> AdblockStatus {
+    catch_panic(ptr::null_mut(), || {
+        let Some(engine) = get_engine_ref(engine) else {
+            return AdblockStatus::NullPointer;
+        };
+
+        let Ok(core) = engine.core.read() else {
+            return AdblockStatus::LockPoisoned;
+        };
+        core.reset_statistics();
+        AdblockStatus::Ok
+    })
+}
+
+/// Start a new statistics session, keeping the lifetime totals
+///
+/// # Safety
+/// The engine pointer must be valid
+#[no_mangle]
+pub extern "C" fn adblock_engine_start_session(
+    engine: *mut c_void,
+    error: *mut AdblockErrorCode,
+) -> bool {
+    catch_panic(error, || {
+        status_to_bool(adblock_engine_start_session_status(engine), error)
+    })
+}
+
+/// Start a new statistics session, returning a status
+#[no_mangle]
+pub extern "C" fn adblock_engine_start_session_status(engine: *mut c_void) -> AdblockStatus {
+    catch_panic(ptr::null_mut(), || {
+        let Some(engine) = get_engine_ref(engine) else {
+            return AdblockStatus::NullPointer;
+        };
+
+        let Ok(core) = engine.core.read() else {
+            return AdblockStatus::LockPoisoned;
+        };
+        core.start_statistics_session();
+        AdblockStatus::Ok
+    })
+}
+
+/// Get performance metrics
+///
+/// # Safety
+/// The engine pointer must be valid
+///
+/// Returns a JSON string with performance metrics
+#[no_mangle]
+pub extern "C" fn adblock_engine_get_metrics(
+    engine: *mut c_void,
+    error: *mut AdblockErrorCode,
+) -> *mut c_char {
+    catch_panic(error, || {
+        let Some(engine) = engine_arg(engine, error) else {
+            return ptr::null_mut();
+        };
+
+        let Ok(core) = engine.core.read() else {
+            set_error(error, AdblockErrorCode::LockPoisoned);
+            return ptr::null_mut();
+        };
+        let metrics = core.engine().get_metrics().snapshot();
+
+        match metrics.to_json() {
+            Ok(json) => into_c_string(json, error),
+            Err(_) => {
+                set_error(error, AdblockErrorCode::SerializationFailed);
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Get performance metrics in the Prometheus text exposition format
+///
+/// For serving from a local scrape endpoint when the engine runs behind a
+/// proxy. The returned string must be freed with `adblock_free_string`
+#[no_mangle]
+pub extern "C" fn adblock_engine_get_metrics_prometheus(
+    engine: *mut c_void,
+    error: *mut AdblockErrorCode,
+) -> *mut c_char {
+    catch_panic(error, || {
+        let Some(engine) = engine_arg(engine, error) else {
+            return ptr::null_mut();
+        };
+
+        let Ok(core) = engine.core.read() else {
+            set_error(error, AdblockErrorCode::LockPoisoned);
+            return ptr::null_mut();
+        };
+        into_c_string(
+            core.engine().get_metrics().snapshot().to_prometheus(),
+            error,
+        )
+    })
+}
+
+/// Export the active rules as Safari content blocker JSON
+///
+/// # Safety
+/// The engine pointer must be valid
+///
+/// The returned string must be freed with `adblock_free_string`
+#[no_mangle]
+pub extern "C" fn adblock_engine_export_content_blocker_json(
+    engine: *mut c_void,
+    error: *mut AdblockErrorCode,
+) -> *mut c_char {
+    catch_panic(error, || {
+        let Some(engine) = engine_arg(engine, error) else {
+            return ptr::null_mut();
+        };
+
+        let Ok(core) = engine.core.read() else {
+            set_error(error, AdblockErrorCode::LockPoisoned);
+            return ptr::null_mut();
+        };
+        match crate::content_blocker::export_json(&core.engine()) {
+            Ok(json) => into_c_string(json, error),
+            Err(_) => {
+                set_error(error, AdblockErrorCode::SerializationFailed);
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Get the element hiding selectors for a page domain as a JSON array
+///
+/// # Safety
+/// The engine pointer must be valid
+///
+/// The returned string must be freed with `adblock_free_string`
+#[no_mangle]
+pub extern "C" fn adblock_engine_get_cosmetic_rules(
+    engine: *mut c_void,
+    domain: *const c_char,
+    error: *mut AdblockErrorCode,
+) -> *mut c_char {
+    catch_panic(error, || {
+        let Some(engine) = engine_arg(engine, error) else {
+            return ptr::null_mut();
+        };
+
+        let Some(domain_str) = c_str_arg(domain, error) else {
+            return ptr::null_mut();
+        };
+
+        let Ok(core) = engine.core.read() else {
+            set_error(error, AdblockErrorCode::LockPoisoned);
+            return ptr::null_mut();
+        };
+        let selectors = if core.site_mode(domain_str).applies_cosmetic_rules() {
+            core.engine().get_cosmetic_selectors(domain_str)
+        } else {
+            Vec::new()
+        };
+        match serde_json::to_string(&selectors) {
+            Ok(json) => into_c_string(json, error),
+            Err(_) => {
+                set_error(error, AdblockErrorCode::SerializationFailed);
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Get a stylesheet hiding the page domain's elements, for injection into
+/// the page
+///
+/// Holds the selectors of `adblock_engine_get_cosmetic_rules`, each as a
+/// `display: none !important` rule; empty if no rule applies.
+/// The returned string must be freed with `adblock_free_string`
+#[no_mangle]
+pub extern "C" fn adblock_engine_get_cosmetic_css(
+    engine: *mut c_void,
+    domain: *const c_char,
+    error: *mut AdblockErrorCode,
+) -> *mut c_char {
+    catch_panic(error, || {
+        let Some(engine) = engine_arg(engine, error) else {
+            return ptr::null_mut();
+        };
+        let Some(domain) = c_str_arg(domain, error) else {
+            return ptr::null_mut();
+        };
+
+        let Ok(core) = engine.core.read() else {
+            set_error(error, AdblockErrorCode::LockPoisoned);
+            return ptr::null_mut();
+        };
+        if !core.site_mode(domain).applies_cosmetic_rules() {
+            return into_c_string(String::new(), error);
+        }
+        let selectors = core.engine().get_cosmetic_selectors(domain);
+        into_c_string(crate::cosmetic::hiding_stylesheet(&selectors), error)
+    })
+}
+
+/// Get the procedural cosmetic filters for a page domain as a JSON array
+///
+/// # Safety
+/// The engine pointer must be valid
+///
+/// The returned string must be freed with `adblock_free_string`
+#[no_mangle]
+pub extern "C" fn adblock_engine_get_procedural_filters(
+    engine: *mut c_void,
+    domain: *const c_char,
+    error: *mut AdblockErrorCode,
+) -> *mut c_char {
+    catch_panic(error, || {
+        let Some(engine) = engine_arg(engine, error) else {
+            return ptr::null_mut();
+        };
+
+        let Some(domain_str) = c_str_arg(domain, error) else {
+            return ptr::null_mut();
+        };
+
+        let Ok(core) = engine.core.read() else {
+            set_error(error, AdblockErrorCode::LockPoisoned);
+            return ptr::null_mut();
+        };
+        if !core.site_mode(domain_str).applies_cosmetic_rules() {
+            return into_c_string("[]".to_string(), error);
+        }
+        into_c_string(core.engine().get_procedural_filters(domain_str), error)
+    })
+}
+
+/// Opaque handle for a DNS-level network filter
+pub struct AdBlockNetworkFilter {
+    filter: RwLock<NetworkFilter>,
+}
+
+/// Look up the network filter of a handle
+fn network_filter_ref(filter: *mut c_void) -> Option<Arc<AdBlockNetworkFilter>> {
+    NETWORK_FILTERS.get(filter as usize)
+}
+
+/// Get network filter reference, reporting a null handle
+fn network_filter_arg(
+    filter: *mut c_void,
+    error: *mut AdblockErrorCode,
+) -> Option<Arc<AdBlockNetworkFilter>> {
+    let filter = network_filter_ref(filter);
+    if filter.is_none() {
+        set_error(error, AdblockErrorCode::NullPointer);
+    }
+    filter
+}
+
+/// Register a network filter, returning null if no handle is left
+fn into_network_filter_handle(filter: NetworkFilter) -> *mut c_void {
+    NETWORK_FILTERS
+        .insert(AdBlockNetworkFilter {
+            filter: RwLock::new(filter),
+        })
+        .map_or(ptr::null_mut(), |handle| handle as *mut c_void)
+}
+
+/// Create an empty network filter for the DNS path of a VPN service
+///
+/// Destroy it with `adblock_network_filter_destroy`.
+#[no_mangle]
+pub extern "C" fn adblock_network_filter_create(error: *mut AdblockErrorCode) -> *mut c_void {
+    catch_panic(error, || {
+        set_error(error, AdblockErrorCode::Ok);
+        into_network_filter_handle(NetworkFilter::new())
+    })
 }
 
-/// Load a filter list
+/// Create a network filter following an engine's rules, whitelist and
+/// content category filter
+///
+/// Rule reloads of the engine apply to the filter. It stays valid after
+/// the engine is destroyed and must be destroyed separately.
 #[no_mangle]
-pub extern "C" fn adblock_engine_load_filter_list(
+pub extern "C" fn adblock_engine_create_network_filter(
     engine: *mut c_void,
-    filter_list: *const c_char,
-) -> bool {
-    let engine = match get_engine_ref(engine) {
-        Some(e) => e,
-        None => return false,
-    };
+    error: *mut AdblockErrorCode,
+) -> *mut c_void {
+    catch_panic(error, || {
+        let Some(engine) = engine_arg(engine, error) else {
+            return ptr::null_mut();
+        };
 
-    let filter_list_str = match c_str_to_rust(filter_list) {
-        Some(s) => s,
-        None => return false,
-    };
+        let Ok(core) = engine.core.read() else {
+            set_error(error, AdblockErrorCode::LockPoisoned);
+            return ptr::null_mut();
+        };
+        set_error(error, AdblockErrorCode::Ok);
+        into_network_filter_handle(core.network_filter())
+    })
+}
 
-    match engine.core.lock() {
-        Ok(mut core) => {
-            // Create a new AdBlockCore from the filter list
-            match AdBlockCore::from_filter_list(filter_list_str) {
-                Ok(new_core) => {
-                    *core = new_core;
-                    true
-                }
-                Err(_) => false,
-            }
-        }
-        Err(_) => false,
-    }
+/// Destroy a network filter
+///
+/// The handle is invalid from now on; destroying it again does nothing.
+#[no_mangle]
+pub extern "C" fn adblock_network_filter_destroy(filter: *mut c_void) {
+    catch_panic(ptr::null_mut(), || {
+        NETWORK_FILTERS.remove(filter as usize);
+    })
 }
 
-/// Get statistics as JSON string
+/// Block a domain and its subdomains
 #[no_mangle]
-pub extern "C" fn adblock_engine_get_stats(engine: *mut c_void) -> *mut c_char {
-    let engine = match get_engine_ref(engine) {
-        Some(e) => e,
-        None => return ptr::null_mut(),
-    };
+pub extern "C" fn adblock_network_filter_add_blocked_domain(
+    filter: *mut c_void,
+    domain: *const c_char,
+    error: *mut AdblockErrorCode,
+) -> bool {
+    catch_panic(error, || {
+        status_to_bool(
+            adblock_network_filter_add_blocked_domain_status(filter, domain),
+            error,
+        )
+    })
+}
 
-    match engine.core.lock() {
-        Ok(core) => {
-            let stats = core.get_statistics();
-
-            // Create a simple JSON representation
-            let json = format!(
-                r#"{{"blocked_count":{},"allowed_count":{},"data_saved":{}}}"#,
-                stats.get_blocked_count(),
-                stats.get_allowed_count(),
-                stats.get_data_saved()
-            );
+/// Block a domain, returning a status
+#[no_mangle]
+pub extern "C" fn adblock_network_filter_add_blocked_domain_status(
+    filter: *mut c_void,
+    domain: *const c_char,
+) -> AdblockStatus {
+    catch_panic(ptr::null_mut(), || {
+        let Some(filter) = network_filter_ref(filter) else {
+            return AdblockStatus::NullPointer;
+        };
+        let domain = match c_str_status(domain) {
+            Ok(domain) => domain,
+            Err(status) => return status,
+        };
 
-            match CString::new(json) {
-                Ok(cstring) => cstring.into_raw(),
-                Err(_) => ptr::null_mut(),
-            }
-        }
-        Err(_) => ptr::null_mut(),
-    }
+        let Ok(mut filter) = filter.filter.write() else {
+            return AdblockStatus::LockPoisoned;
+        };
+        filter.add_blocked_domain(domain);
+        AdblockStatus::Ok
+    })
 }
 
-/// Reset statistics
-///
-/// # Safety
-/// The engine pointer must be valid
+/// Check if a domain is blocked
 #[no_mangle]
-pub extern "C" fn adblock_engine_reset_stats(engine: *mut c_void) -> bool {
-    let Some(engine) = get_engine_ref(engine) else {
-        return false;
-    };
+pub extern "C" fn adblock_network_filter_is_domain_blocked(
+    filter: *mut c_void,
+    domain: *const c_char,
+    error: *mut AdblockErrorCode,
+) -> bool {
+    catch_panic(error, || {
+        let Some(filter) = network_filter_arg(filter, error) else {
+            return false;
+        };
+        let Some(domain) = c_str_arg(domain, error) else {
+            return false;
+        };
 
-    match engine.core.lock() {
-        Ok(core) => {
-            core.reset_statistics();
-            true
-        }
-        Err(_) => false,
-    }
+        let Ok(filter) = filter.filter.read() else {
+            set_error(error, AdblockErrorCode::LockPoisoned);
+            return false;
+        };
+        set_error(error, AdblockErrorCode::Ok);
+        filter.is_blocked(domain)
+    })
 }
 
-/// Get performance metrics
+/// Answer a DNS query packet (the UDP payload) if its domain is blocked
 ///
-/// # Safety
-/// The engine pointer must be valid
+/// Returns the size of the response packet. It is written only if it fits
+/// in `cap` bytes; otherwise the caller should retry with a larger buffer.
+/// Returns 0 if the query is not answered locally, because its domain is
+/// not blocked or it is not a single-question query: forward it upstream.
+/// Returns 0 with an error code set on failure.
 ///
-/// Returns a JSON string with performance metrics
+/// # Safety
+/// `packet` must point to `len` readable bytes
 #[no_mangle]
-pub extern "C" fn adblock_engine_get_metrics(engine: *mut c_void) -> *mut c_char {
-    let Some(engine) = get_engine_ref(engine) else {
-        return ptr::null_mut();
-    };
+pub extern "C" fn adblock_network_filter_process_dns_packet(
+    filter: *mut c_void,
+    packet: *const u8,
+    len: usize,
+    buf: *mut u8,
+    cap: usize,
+    error: *mut AdblockErrorCode,
+) -> usize {
+    catch_panic(error, || {
+        let Some(filter) = network_filter_arg(filter, error) else {
+            return 0;
+        };
+        if packet.is_null() || (buf.is_null() && cap > 0) {
+            set_error(error, AdblockErrorCode::NullPointer);
+            return 0;
+        }
 
-    match engine.core.lock() {
-        Ok(core) => {
-            let metrics = core.engine().get_metrics().snapshot();
-
-            match metrics.to_json() {
-                Ok(json) => match CString::new(json) {
-                    Ok(cstring) => cstring.into_raw(),
-                    Err(_) => ptr::null_mut(),
-                },
-                Err(_) => ptr::null_mut(),
+        let packet = unsafe { std::slice::from_raw_parts(packet.cast::<u8>(), len) };
+        let checked = match filter.filter.read() {
+            Ok(filter) => crate::dns_server::check_query(&filter, packet),
+            Err(_) => {
+                set_error(error, AdblockErrorCode::LockPoisoned);
+                return 0;
             }
+        };
+        set_error(error, AdblockErrorCode::Ok);
+        let Some(answer) = checked.and_then(|checked| checked.answer) else {
+            return 0;
+        };
+
+        if answer.len() <= cap {
+            unsafe { ptr::copy_nonoverlapping(answer.as_ptr(), buf.cast::<u8>(), answer.len()) };
         }
-        Err(_) => ptr::null_mut(),
-    }
+        answer.len()
+    })
 }
 
 /// Free a string allocated by the library
@@ -221,12 +2748,14 @@ pub extern "C" fn adblock_engine_get_metrics(engine: *mut c_void) -> *mut c_char
 /// and must not have been freed already.
 #[no_mangle]
 pub unsafe extern "C" fn adblock_free_string(s: *mut c_char) {
-    if s.is_null() {
-        return;
-    }
+    catch_panic(ptr::null_mut(), || {
+        if s.is_null() {
+            return;
+        }
 
-    let _ = CString::from_raw(s);
-    // CString will be dropped, freeing the memory
+        let _ = CString::from_raw(s);
+        // CString will be dropped, freeing the memory
+    })
 }
 
 #[cfg(test)]
@@ -236,7 +2765,7 @@ mod tests {
 
     #[test]
     fn test_ffi_create_destroy() {
-        let engine = adblock_engine_create();
+        let engine = adblock_engine_create(ptr::null_mut());
         assert!(!engine.is_null());
         adblock_engine_destroy(engine);
     }
@@ -244,52 +2773,71 @@ mod tests {
     #[test]
     fn test_ffi_null_safety() {
         // Should handle null engine
-        assert!(!adblock_engine_should_block(ptr::null_mut(), ptr::null()));
+        let mut error = AdblockErrorCode::Ok;
+        assert!(!adblock_engine_should_block(
+            ptr::null_mut(),
+            ptr::null(),
+            &mut error
+        ));
+        assert_eq!(error, AdblockErrorCode::NullPointer);
 
         // Should handle null URL
-        let engine = adblock_engine_create();
-        assert!(!adblock_engine_should_block(engine, ptr::null()));
+        let engine = adblock_engine_create(ptr::null_mut());
+        assert!(!adblock_engine_should_block(
+            engine,
+            ptr::null(),
+            ptr::null_mut()
+        ));
         adblock_engine_destroy(engine);
     }
 
     #[test]
     fn test_ffi_blocking() {
-        let engine = adblock_engine_create();
+        let engine = adblock_engine_create(ptr::null_mut());
         assert!(!engine.is_null());
 
         // Load a filter list
         let filter_list = CString::new("||doubleclick.net^").unwrap();
         assert!(adblock_engine_load_filter_list(
             engine,
-            filter_list.as_ptr()
+            filter_list.as_ptr(),
+            ptr::null_mut()
         ));
 
         // Test blocking
         let blocked_url = CString::new("https://doubleclick.net/ads").unwrap();
-        assert!(adblock_engine_should_block(engine, blocked_url.as_ptr()));
+        assert!(adblock_engine_should_block(
+            engine,
+            blocked_url.as_ptr(),
+            ptr::null_mut()
+        ));
 
         let safe_url = CString::new("https://example.com").unwrap();
-        assert!(!adblock_engine_should_block(engine, safe_url.as_ptr()));
+        assert!(!adblock_engine_should_block(
+            engine,
+            safe_url.as_ptr(),
+            ptr::null_mut()
+        ));
 
         adblock_engine_destroy(engine);
     }
 
     #[test]
     fn test_ffi_statistics() {
-        let engine = adblock_engine_create();
+        let engine = adblock_engine_create(ptr::null_mut());
 
         // Generate some statistics
         let filter_list = CString::new("||ads.com^").unwrap();
-        adblock_engine_load_filter_list(engine, filter_list.as_ptr());
+        adblock_engine_load_filter_list(engine, filter_list.as_ptr(), ptr::null_mut());
 
         let url1 = CString::new("https://ads.com/banner").unwrap();
         let url2 = CString::new("https://safe.com").unwrap();
 
-        adblock_engine_should_block(engine, url1.as_ptr());
-        adblock_engine_should_block(engine, url2.as_ptr());
+        adblock_engine_should_block(engine, url1.as_ptr(), ptr::null_mut());
+        adblock_engine_should_block(engine, url2.as_ptr(), ptr::null_mut());
 
         // Get stats
-        let stats_ptr = adblock_engine_get_stats(engine);
+        let stats_ptr = adblock_engine_get_stats(engine, ptr::null_mut());
         assert!(!stats_ptr.is_null());
 
         unsafe {
@@ -303,6 +2851,768 @@ mod tests {
         unsafe {
             adblock_free_string(stats_ptr);
         }
+
+        // A new session keeps the lifetime totals
+        assert!(adblock_engine_start_session(engine, ptr::null_mut()));
+        let stats_ptr = adblock_engine_get_stats(engine, ptr::null_mut());
+        unsafe {
+            let stats_str = CStr::from_ptr(stats_ptr).to_str().unwrap();
+            assert!(stats_str.contains(r#""blocked_count":1,"#));
+            assert!(stats_str.contains(r#""session_blocked_count":0,"#));
+            adblock_free_string(stats_ptr);
+        }
+        adblock_engine_destroy(engine);
+    }
+
+    #[test]
+    fn test_ffi_event_callback() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        static EVENTS: AtomicUsize = AtomicUsize::new(0);
+        extern "C" fn on_event(event_json: *const c_char, _user_data: *mut c_void) {
+            let json = unsafe { CStr::from_ptr(event_json) }.to_str().unwrap();
+            assert!(json.contains("\"type\":\"rule_set_updated\""));
+            EVENTS.fetch_add(1, Ordering::SeqCst);
+        }
+
+        let engine = adblock_engine_create(ptr::null_mut());
+        let mut error = AdblockErrorCode::NullPointer;
+        assert!(adblock_engine_set_event_callback(
+            engine,
+            Some(on_event),
+            ptr::null_mut(),
+            &mut error
+        ));
+        assert_eq!(error, AdblockErrorCode::Ok);
+
+        let filter_list = CString::new("||ads.com^").unwrap();
+        adblock_engine_load_filter_list(engine, filter_list.as_ptr(), ptr::null_mut());
+        assert_eq!(EVENTS.load(Ordering::SeqCst), 1);
+
+        adblock_engine_set_event_callback(engine, None, ptr::null_mut(), ptr::null_mut());
+        adblock_engine_load_filter_list(engine, filter_list.as_ptr(), ptr::null_mut());
+        assert_eq!(EVENTS.load(Ordering::SeqCst), 1);
+
+        adblock_engine_destroy(engine);
+    }
+
+    #[test]
+    fn test_ffi_block_event_callback() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        static BLOCKED: AtomicUsize = AtomicUsize::new(0);
+        extern "C" fn on_request(event_json: *const c_char, _user_data: *mut c_void) {
+            let json = unsafe { CStr::from_ptr(event_json) }.to_str().unwrap();
+            let event: crate::BlockEvent = serde_json::from_str(json).unwrap();
+            if event.blocked {
+                assert_eq!(event.domain, "ads.com");
+                BLOCKED.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let engine = adblock_engine_create(ptr::null_mut());
+        let filter_list = CString::new("||ads.com^").unwrap();
+        adblock_engine_load_filter_list(engine, filter_list.as_ptr(), ptr::null_mut());
+        let mut error = AdblockErrorCode::NullPointer;
+        assert!(adblock_engine_set_block_event_callback(
+            engine,
+            Some(on_request),
+            ptr::null_mut(),
+            &mut error
+        ));
+        assert_eq!(error, AdblockErrorCode::Ok);
+
+        let ad = CString::new("https://ads.com/banner.js").unwrap();
+        let page = CString::new("https://example.com/").unwrap();
+        adblock_engine_should_block(engine, ad.as_ptr(), ptr::null_mut());
+        adblock_engine_should_block(engine, page.as_ptr(), ptr::null_mut());
+        assert_eq!(BLOCKED.load(Ordering::SeqCst), 1);
+
+        adblock_engine_set_block_event_callback(engine, None, ptr::null_mut(), ptr::null_mut());
+        adblock_engine_should_block(engine, ad.as_ptr(), ptr::null_mut());
+        assert_eq!(BLOCKED.load(Ordering::SeqCst), 1);
+        adblock_engine_destroy(engine);
+    }
+
+    #[test]
+    fn test_ffi_filter_list_registry() {
+        let engine = adblock_engine_create(ptr::null_mut());
+        let ads_url = CString::new("https://lists.example/ads.txt").unwrap();
+        let ads = CString::new("! Title: Ads\n||ads.com^").unwrap();
+        let privacy_url = CString::new("https://lists.example/privacy.txt").unwrap();
+        let privacy = CString::new("||tracker.net^").unwrap();
+        let ad = CString::new("https://ads.com/banner.js").unwrap();
+        let tracker = CString::new("https://tracker.net/pixel").unwrap();
+
+        let mut error = AdblockErrorCode::NullPointer;
+        assert!(adblock_engine_add_filter_list(
+            engine,
+            ads_url.as_ptr(),
+            ads.as_ptr(),
+            &mut error
+        ));
+        assert_eq!(error, AdblockErrorCode::Ok);
+        adblock_engine_add_filter_list(engine, privacy_url.as_ptr(), privacy.as_ptr(), &mut error);
+        assert!(adblock_engine_should_block(
+            engine,
+            ad.as_ptr(),
+            ptr::null_mut()
+        ));
+
+        // Disabling a list drops its rules
+        assert!(adblock_engine_set_filter_list_enabled(
+            engine,
+            ads_url.as_ptr(),
+            false,
+            &mut error
+        ));
+        assert!(!adblock_engine_should_block(
+            engine,
+            ad.as_ptr(),
+            ptr::null_mut()
+        ));
+        assert!(adblock_engine_should_block(
+            engine,
+            tracker.as_ptr(),
+            ptr::null_mut()
+        ));
+
+        // A new version of an enabled list is patched in
+        let updated = CString::new("||tracker.org^").unwrap();
+        assert!(adblock_engine_add_filter_list(
+            engine,
+            privacy_url.as_ptr(),
+            updated.as_ptr(),
+            &mut error
+        ));
+        assert!(!adblock_engine_should_block(
+            engine,
+            tracker.as_ptr(),
+            ptr::null_mut()
+        ));
+
+        let json_ptr = adblock_engine_get_filter_lists(engine, &mut error);
+        let json = unsafe { CStr::from_ptr(json_ptr) }.to_str().unwrap();
+        assert!(json.contains("\"title\":\"Ads\""));
+        assert!(json.contains("\"enabled\":false"));
+        unsafe { adblock_free_string(json_ptr) };
+
+        assert_eq!(
+            adblock_engine_remove_filter_list_status(engine, ads_url.as_ptr()),
+            AdblockStatus::Ok
+        );
+        assert_eq!(
+            adblock_engine_remove_filter_list_status(engine, ads_url.as_ptr()),
+            AdblockStatus::NotSupported
+        );
+
+        adblock_engine_destroy(engine);
+    }
+
+    #[test]
+    fn test_ffi_query_log_search() {
+        let engine = adblock_engine_create(ptr::null_mut());
+        let rules = CString::new("||ads.com^").unwrap();
+        adblock_engine_load_filter_list(engine, rules.as_ptr(), ptr::null_mut());
+        let mut error = AdblockErrorCode::NullPointer;
+        assert!(adblock_engine_enable_query_log(
+            engine,
+            ptr::null(),
+            0,
+            &mut error
+        ));
+
+        let app = CString::new("com.example.news").unwrap();
+        for url in [
+            "https://ads.com/a.js",
+            "https://example.com/",
+            "https://ads.com/b.js",
+        ] {
+            let url = CString::new(url).unwrap();
+            adblock_engine_check_url_for_app(engine, url.as_ptr(), app.as_ptr(), ptr::null_mut());
+        }
+
+        let query = CString::new(r#"{"verdict":"blocked","limit":1}"#).unwrap();
+        let json_ptr = adblock_engine_search_query_log(engine, query.as_ptr(), &mut error);
+        assert_eq!(error, AdblockErrorCode::Ok);
+        let json: serde_json::Value =
+            serde_json::from_str(unsafe { CStr::from_ptr(json_ptr) }.to_str().unwrap()).unwrap();
+        unsafe { adblock_free_string(json_ptr) };
+        assert_eq!(json["total"], 2);
+        assert_eq!(json["entries"].as_array().unwrap().len(), 1);
+        assert_eq!(json["entries"][0]["domain"], "ads.com");
+        assert_eq!(json["entries"][0]["app"], "com.example.news");
+
+        assert_eq!(
+            adblock_engine_clear_query_log_status(engine),
+            AdblockStatus::Ok
+        );
+        adblock_engine_destroy(engine);
+    }
+
+    #[test]
+    fn test_ffi_security_list_verdict() {
+        let engine = adblock_engine_create(ptr::null_mut());
+        let ads_url = CString::new("https://lists.example/ads.txt").unwrap();
+        let ads = CString::new("||ads.com^").unwrap();
+        let security_url = CString::new("https://lists.example/malware.txt").unwrap();
+        let security = CString::new("||phishing.example^").unwrap();
+        let mut error = AdblockErrorCode::NullPointer;
+
+        adblock_engine_add_filter_list(engine, ads_url.as_ptr(), ads.as_ptr(), &mut error);
+        assert!(adblock_engine_add_security_list(
+            engine,
+            security_url.as_ptr(),
+            security.as_ptr(),
+            &mut error
+        ));
+        assert_eq!(error, AdblockErrorCode::Ok);
+
+        let verdict = |url: &str| {
+            let url = CString::new(url).unwrap();
+            adblock_engine_check_url(engine, url.as_ptr(), ptr::null_mut())
+        };
+        assert_eq!(
+            verdict("https://phishing.example/login"),
+            AdblockVerdict::Malware
+        );
+        assert_eq!(verdict("https://ads.com/banner.js"), AdblockVerdict::Ad);
+        assert_eq!(verdict("https://example.com/"), AdblockVerdict::Allowed);
+
+        let stats_ptr = adblock_engine_get_stats(engine, ptr::null_mut());
+        let stats = unsafe { CStr::from_ptr(stats_ptr) }.to_str().unwrap();
+        assert!(stats.contains(r#""blocked_count":2,"#));
+        assert!(stats.contains(r#""malware_count":1,"#));
+        unsafe { adblock_free_string(stats_ptr) };
+
+        adblock_engine_destroy(engine);
+    }
+
+    #[test]
+    fn test_ffi_pause() {
+        let engine = adblock_engine_create(ptr::null_mut());
+        let url = CString::new("https://doubleclick.net/ad.js").unwrap();
+        let mut error = AdblockErrorCode::Ok;
+
+        assert!(adblock_engine_pause(engine, 60, &mut error));
+        assert!(adblock_engine_is_paused(engine, &mut error));
+        assert!(!adblock_engine_should_block(
+            engine,
+            url.as_ptr(),
+            &mut error
+        ));
+        let json_ptr = adblock_engine_get_stats(engine, &mut error);
+        let json = unsafe { CStr::from_ptr(json_ptr) }.to_str().unwrap();
+        assert!(json.contains(r#""paused":true,"paused_count":1"#));
+        unsafe { adblock_free_string(json_ptr) };
+
+        assert!(adblock_engine_resume(engine, &mut error));
+        assert!(adblock_engine_should_block(
+            engine,
+            url.as_ptr(),
+            &mut error
+        ));
+
+        let invalid = CString::new("[{}]").unwrap();
+        assert_eq!(
+            adblock_engine_set_pause_schedule_status(engine, invalid.as_ptr()),
+            AdblockStatus::ParseError
+        );
+        let nightly = CString::new(r#"[{"start":"22:00:00","end":"06:00:00"}]"#).unwrap();
+        assert!(adblock_engine_set_pause_schedule(
+            engine,
+            nightly.as_ptr(),
+            &mut error
+        ));
+        adblock_engine_destroy(engine);
+    }
+
+    #[test]
+    fn test_ffi_device_conditions() {
+        let engine = adblock_engine_create(ptr::null_mut());
+        let mut error = AdblockErrorCode::NullPointer;
+
+        assert!(adblock_engine_set_device_conditions(
+            engine, true, true, &mut error
+        ));
+        assert_eq!(error, AdblockErrorCode::Ok);
+        assert_eq!(
+            adblock_engine_set_device_conditions_status(ptr::null_mut(), true, false),
+            AdblockStatus::NullPointer
+        );
+        adblock_engine_destroy(engine);
+    }
+
+    #[test]
+    fn test_ffi_site_modes() {
+        let engine = adblock_engine_create(ptr::null_mut());
+        let domain = CString::new("example.com").unwrap();
+        let host = CString::new("www.example.com").unwrap();
+        let mut error = AdblockErrorCode::Ok;
+
+        assert!(adblock_engine_set_site_mode(
+            engine,
+            domain.as_ptr(),
+            AdblockSiteMode::Off,
+            &mut error
+        ));
+        assert_eq!(
+            adblock_engine_get_site_mode(engine, host.as_ptr(), &mut error),
+            AdblockSiteMode::Off
+        );
+
+        let json_ptr = adblock_engine_get_site_modes(engine, &mut error);
+        let json = unsafe { CStr::from_ptr(json_ptr) }.to_str().unwrap();
+        assert_eq!(json, r#"{"example.com":"off"}"#);
+        unsafe { adblock_free_string(json_ptr) };
+
+        assert!(adblock_engine_clear_site_mode(
+            engine,
+            domain.as_ptr(),
+            &mut error
+        ));
+        assert_eq!(
+            adblock_engine_get_site_mode(engine, host.as_ptr(), &mut error),
+            AdblockSiteMode::Aggressive
+        );
+        adblock_engine_destroy(engine);
+    }
+
+    #[test]
+    fn test_ffi_config_validation() {
+        let valid = CString::new(r#"{"max_memory_mb":30,"filter_lists":[]}"#).unwrap();
+        let invalid = CString::new(r#"{"max_memory_mb":0,"filter_lists":[]}"#).unwrap();
+        let mut error = AdblockErrorCode::Ok;
+
+        let engine = adblock_engine_create_with_config(valid.as_ptr(), &mut error);
+        assert!(!engine.is_null());
+        assert_eq!(error, AdblockErrorCode::Ok);
+        adblock_engine_destroy(engine);
+
+        let engine = adblock_engine_create_with_config(invalid.as_ptr(), &mut error);
+        assert!(engine.is_null());
+        assert_eq!(error, AdblockErrorCode::InvalidConfig);
+
+        let json_ptr = adblock_validate_config(invalid.as_ptr(), &mut error);
+        let json = unsafe { CStr::from_ptr(json_ptr) }.to_str().unwrap();
+        assert!(json.contains("\"kind\":\"memory_out_of_range\""));
+        unsafe { adblock_free_string(json_ptr) };
+    }
+
+    #[test]
+    fn test_ffi_backup_roundtrip() {
+        let source = adblock_engine_create(ptr::null_mut());
+        let url = CString::new("https://doubleclick.net/ad.js").unwrap();
+        adblock_engine_should_block(source, url.as_ptr(), ptr::null_mut());
+
+        let mut error = AdblockErrorCode::NullPointer;
+        let needed = adblock_engine_create_backup_gzip(source, ptr::null_mut(), 0, &mut error);
+        assert_eq!(error, AdblockErrorCode::Ok);
+        // The backup is timestamped, so keep the size of the written copy
+        let mut gzip = vec![0u8; needed + 64];
+        let written =
+            adblock_engine_create_backup_gzip(source, gzip.as_mut_ptr(), gzip.len(), &mut error);
+        gzip.truncate(written);
+        adblock_engine_destroy(source);
+
+        let target = adblock_engine_create(ptr::null_mut());
+        assert!(adblock_engine_restore_backup(
+            target,
+            gzip.as_ptr(),
+            gzip.len(),
+            &mut error
+        ));
+        let json_ptr = adblock_engine_create_backup(target, &mut error);
+        let json = unsafe { CStr::from_ptr(json_ptr) }.to_str().unwrap();
+        assert!(json.contains("\"blocked_count\": 1"));
+        unsafe { adblock_free_string(json_ptr) };
+
+        let garbage = b"not a backup";
+        assert_eq!(
+            adblock_engine_restore_backup_status(target, garbage.as_ptr(), garbage.len()),
+            AdblockStatus::ParseError
+        );
+        adblock_engine_destroy(target);
+    }
+
+    #[test]
+    fn test_ffi_crash_reports() {
+        let engine = adblock_engine_create(ptr::null_mut());
+        let mut error = AdblockErrorCode::NullPointer;
+        get_engine_ref(engine)
+            .unwrap()
+            .core
+            .read()
+            .unwrap()
+            .crash_reporter()
+            .report_exception("Filter compilation failed", None);
+
+        let json_ptr = adblock_get_crash_reports_json(engine, &mut error);
+        assert_eq!(error, AdblockErrorCode::Ok);
+        let json = unsafe { CStr::from_ptr(json_ptr) }.to_str().unwrap();
+        let reports: serde_json::Value = serde_json::from_str(json).unwrap();
+        assert_eq!(reports.as_array().unwrap().len(), 1);
+        assert!(
+            reports[0]["context"]["filter_rules_count"]
+                .as_u64()
+                .unwrap()
+                > 0
+        );
+        unsafe { adblock_free_string(json_ptr) };
+
+        assert!(adblock_clear_crash_reports(engine, &mut error));
+        let json_ptr = adblock_get_crash_reports_json(engine, &mut error);
+        assert_eq!(unsafe { CStr::from_ptr(json_ptr) }.to_str().unwrap(), "[]");
+        unsafe { adblock_free_string(json_ptr) };
+        adblock_engine_destroy(engine);
+    }
+
+    #[test]
+    fn test_ffi_analytics_export() {
+        let dir =
+            std::env::temp_dir().join(format!("adblock_ffi_analytics_{}", std::process::id()));
+        let config = serde_json::json!({
+            "analytics_dir": dir,
+            "analytics_enabled": true,
+            "filter_lists": [],
+        })
+        .to_string();
+        let config = CString::new(config).unwrap();
+        let mut error = AdblockErrorCode::NullPointer;
+        let engine = adblock_engine_create_with_config(config.as_ptr(), &mut error);
+        assert_eq!(error, AdblockErrorCode::Ok);
+
+        let analytics = Arc::clone(
+            get_engine_ref(engine)
+                .unwrap()
+                .core
+                .read()
+                .unwrap()
+                .analytics(),
+        );
+        analytics.track_action("filters_opened");
+
+        let json_ptr = adblock_analytics_export_batch(engine, &mut error);
+        let batch: crate::analytics::AnalyticsBatch =
+            serde_json::from_str(unsafe { CStr::from_ptr(json_ptr) }.to_str().unwrap()).unwrap();
+        unsafe { adblock_free_string(json_ptr) };
+        assert_eq!(batch.event_count, 1);
+
+        let batch_id = CString::new(batch.id).unwrap();
+        assert!(adblock_analytics_mark_uploaded(
+            engine,
+            batch_id.as_ptr(),
+            &mut error
+        ));
+        assert!(adblock_analytics_export_batch(engine, &mut error).is_null());
+        assert_eq!(error, AdblockErrorCode::Ok);
+
+        assert!(adblock_analytics_set_enabled(engine, false, &mut error));
+        analytics.track_action("ignored");
+        assert!(adblock_analytics_export_batch(engine, &mut error).is_null());
+        adblock_engine_destroy(engine);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_ffi_stats_buf() {
+        let engine = adblock_engine_create(ptr::null_mut());
+        let mut error = AdblockErrorCode::NullPointer;
+
+        let needed = adblock_engine_get_stats_buf(engine, ptr::null_mut(), 0, &mut error);
+        assert_eq!(error, AdblockErrorCode::Ok);
+        assert!(needed > 1);
+
+        let mut small = [1 as c_char; 4];
+        assert_eq!(
+            adblock_engine_get_stats_buf(engine, small.as_mut_ptr(), small.len(), &mut error),
+            needed
+        );
+        assert_eq!(small[0], 0);
+
+        let mut buf = vec![1 as c_char; needed];
+        assert_eq!(
+            adblock_engine_get_stats_buf(engine, buf.as_mut_ptr(), buf.len(), &mut error),
+            needed
+        );
+        let stats = unsafe { CStr::from_ptr(buf.as_ptr()) }.to_str().unwrap();
+        assert_eq!(stats.len() + 1, needed);
+        assert!(stats.contains("blocked_count"));
+
+        adblock_engine_destroy(engine);
+    }
+
+    #[test]
+    fn test_ffi_domain_stats() {
+        let engine = adblock_engine_create(ptr::null_mut());
+        let filter_list = CString::new("||ads.com^").unwrap();
+        adblock_engine_load_filter_list(engine, filter_list.as_ptr(), ptr::null_mut());
+        let url = CString::new("https://ads.com/banner.js").unwrap();
+        for _ in 0..3 {
+            assert!(adblock_engine_should_block(
+                engine,
+                url.as_ptr(),
+                ptr::null_mut()
+            ));
+        }
+
+        let mut error = AdblockErrorCode::NullPointer;
+        let domain = CString::new("ads.com").unwrap();
+        let json_ptr = adblock_engine_get_domain_stats(engine, domain.as_ptr(), &mut error);
+        assert_eq!(error, AdblockErrorCode::Ok);
+        let stats: crate::DomainStats =
+            serde_json::from_str(unsafe { CStr::from_ptr(json_ptr) }.to_str().unwrap()).unwrap();
+        assert_eq!(stats.count, 3);
+        unsafe { adblock_free_string(json_ptr) };
+
+        let domain = CString::new("example.com").unwrap();
+        let json_ptr = adblock_engine_get_domain_stats(engine, domain.as_ptr(), &mut error);
+        assert_eq!(
+            unsafe { CStr::from_ptr(json_ptr) }.to_str().unwrap(),
+            "null"
+        );
+        unsafe { adblock_free_string(json_ptr) };
+
+        adblock_engine_destroy(engine);
+    }
+
+    #[test]
+    fn test_ffi_top_blocked() {
+        let engine = adblock_engine_create(ptr::null_mut());
+        let filter_list = CString::new("||ads.com^\n||tracker.com^").unwrap();
+        adblock_engine_load_filter_list(engine, filter_list.as_ptr(), ptr::null_mut());
+        for url in [
+            "https://ads.com/a.js",
+            "https://ads.com/b.js",
+            "https://tracker.com/t.gif",
+        ] {
+            let url = CString::new(url).unwrap();
+            assert!(adblock_engine_should_block(
+                engine,
+                url.as_ptr(),
+                ptr::null_mut()
+            ));
+        }
+
+        let mut error = AdblockErrorCode::NullPointer;
+        let json_ptr = adblock_engine_top_blocked(engine, 1, 10, &mut error);
+        assert_eq!(error, AdblockErrorCode::Ok);
+        let page: Vec<crate::DomainStats> =
+            serde_json::from_str(unsafe { CStr::from_ptr(json_ptr) }.to_str().unwrap()).unwrap();
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0].domain, "tracker.com");
+        unsafe { adblock_free_string(json_ptr) };
+
+        adblock_engine_destroy(engine);
+    }
+
+    #[test]
+    fn test_ffi_explain() {
+        let engine = adblock_engine_create(ptr::null_mut());
+        let filter_list = CString::new("||ads.com^\n@@||ads.com/ok.js").unwrap();
+        adblock_engine_load_filter_list(engine, filter_list.as_ptr(), ptr::null_mut());
+
+        let mut error = AdblockErrorCode::NullPointer;
+        let url = CString::new("https://ads.com/ok.js").unwrap();
+        let json_ptr = adblock_engine_explain(engine, url.as_ptr(), &mut error);
+        assert_eq!(error, AdblockErrorCode::Ok);
+        let json: serde_json::Value =
+            serde_json::from_str(unsafe { CStr::from_ptr(json_ptr) }.to_str().unwrap()).unwrap();
+        assert_eq!(json["should_block"], false);
+        assert_eq!(json["resource_type"], "script");
+        assert_eq!(json["matches"][0]["rule"], "@@||ads.com/ok.js");
+        assert_eq!(json["matches"][1]["rule"], "||ads.com^");
+        unsafe { adblock_free_string(json_ptr) };
+
+        adblock_engine_destroy(engine);
+    }
+
+    #[test]
+    fn test_ffi_list_catalog() {
+        let mut error = AdblockErrorCode::NullPointer;
+        let json_ptr = adblock_get_list_catalog(&mut error);
+        assert_eq!(error, AdblockErrorCode::Ok);
+        let catalog: serde_json::Value =
+            serde_json::from_str(unsafe { CStr::from_ptr(json_ptr) }.to_str().unwrap()).unwrap();
+        assert_eq!(catalog[0]["id"], "easylist");
+        unsafe { adblock_free_string(json_ptr) };
+
+        let link = CString::new("abp:subscribe?location=https%3A%2F%2Fa.example%2Fl.txt").unwrap();
+        let json_ptr = adblock_parse_subscription_link(link.as_ptr(), &mut error);
+        assert_eq!(error, AdblockErrorCode::Ok);
+        let subscription: serde_json::Value =
+            serde_json::from_str(unsafe { CStr::from_ptr(json_ptr) }.to_str().unwrap()).unwrap();
+        assert_eq!(subscription["url"], "https://a.example/l.txt");
+        unsafe { adblock_free_string(json_ptr) };
+        let link = CString::new("abp:subscribe?title=List").unwrap();
+        assert!(adblock_parse_subscription_link(link.as_ptr(), &mut error).is_null());
+        assert_eq!(error, AdblockErrorCode::InvalidArgument);
+
+        let engine = adblock_engine_create(ptr::null_mut());
+        let id = CString::new("easylist-germany").unwrap();
+        assert!(adblock_engine_set_list_preset_enabled(
+            engine,
+            id.as_ptr(),
+            true,
+            &mut error
+        ));
+        let unknown = CString::new("no-such-list").unwrap();
+        assert_eq!(
+            adblock_engine_set_list_preset_enabled_status(engine, unknown.as_ptr(), true),
+            AdblockStatus::ParseError
+        );
+        assert_eq!(
+            get_engine_ref(engine)
+                .unwrap()
+                .core
+                .read()
+                .unwrap()
+                .config()
+                .list_presets,
+            ["easylist-germany"]
+        );
+
+        adblock_engine_destroy(engine);
+    }
+
+    #[test]
+    fn test_ffi_ios_surface() {
+        let engine = adblock_engine_create(ptr::null_mut());
+        let filter_list = CString::new("||ads.com^\nexample.com##.banner").unwrap();
+        adblock_engine_load_filter_list(engine, filter_list.as_ptr(), ptr::null_mut());
+
+        let mut error = AdblockErrorCode::NullPointer;
+        let json_ptr = adblock_engine_export_content_blocker_json(engine, &mut error);
+        assert_eq!(error, AdblockErrorCode::Ok);
+        let json = unsafe { CStr::from_ptr(json_ptr) }
+            .to_str()
+            .unwrap()
+            .to_string();
+        assert!(json.contains("css-display-none"));
+        unsafe { adblock_free_string(json_ptr) };
+
+        let domain = CString::new("www.example.com").unwrap();
+        let rules_ptr = adblock_engine_get_cosmetic_rules(engine, domain.as_ptr(), &mut error);
+        let rules = unsafe { CStr::from_ptr(rules_ptr) }
+            .to_str()
+            .unwrap()
+            .to_string();
+        assert_eq!(rules, r#"[".banner"]"#);
+        unsafe { adblock_free_string(rules_ptr) };
+
+        adblock_engine_destroy(engine);
+    }
+
+    #[test]
+    fn test_ffi_network_filter() {
+        let filter = adblock_network_filter_create(ptr::null_mut());
+        let domain = CString::new("ads.example").unwrap();
+        assert!(adblock_network_filter_add_blocked_domain(
+            filter,
+            domain.as_ptr(),
+            ptr::null_mut()
+        ));
+        let subdomain = CString::new("cdn.ads.example").unwrap();
+        assert!(adblock_network_filter_is_domain_blocked(
+            filter,
+            subdomain.as_ptr(),
+            ptr::null_mut()
+        ));
+
+        // A query for ads.example
+        let mut query = vec![0x12u8, 0x34, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0];
+        query.extend_from_slice(b"\x03ads\x07example\x00\x00\x01\x00\x01");
+        let mut error = AdblockErrorCode::NullPointer;
+        let needed = adblock_network_filter_process_dns_packet(
+            filter,
+            query.as_ptr(),
+            query.len(),
+            ptr::null_mut(),
+            0,
+            &mut error,
+        );
+        assert_eq!(error, AdblockErrorCode::Ok);
+        let mut response = vec![0u8; needed];
+        assert_eq!(
+            adblock_network_filter_process_dns_packet(
+                filter,
+                query.as_ptr(),
+                query.len(),
+                response.as_mut_ptr(),
+                response.len(),
+                ptr::null_mut(),
+            ),
+            needed
+        );
+        assert_eq!(&response[..2], &[0x12, 0x34]);
+        assert_eq!(&response[needed - 4..], &[0, 0, 0, 0]);
+
+        // Unblocked queries are left to the upstream resolver
+        let allowed = [&query[..12], b"\x02ok\x07example\x00\x00\x01\x00\x01"].concat();
+        assert_eq!(
+            adblock_network_filter_process_dns_packet(
+                filter,
+                allowed.as_ptr(),
+                allowed.len(),
+                response.as_mut_ptr(),
+                response.len(),
+                &mut error,
+            ),
+            0
+        );
+        assert_eq!(error, AdblockErrorCode::Ok);
+        adblock_network_filter_destroy(filter);
+    }
+
+    #[test]
+    fn test_ffi_status_codes() {
+        assert_eq!(
+            adblock_engine_reset_stats_status(ptr::null_mut()),
+            AdblockStatus::NullPointer
+        );
+
+        let engine = adblock_engine_create(ptr::null_mut());
+        let list = CString::new("||ads.com^").unwrap();
+        assert_eq!(
+            adblock_engine_load_filter_list_status(engine, list.as_ptr()),
+            AdblockStatus::Ok
+        );
+
+        let invalid = [0xffu8, 0];
+        assert_eq!(
+            adblock_engine_add_rule_status(engine, invalid.as_ptr() as *const c_char),
+            AdblockStatus::InvalidUtf8
+        );
+
+        // The boolean wrapper reports the same cause through `error`
+        let mut error = AdblockErrorCode::Ok;
+        let rule = CString::new("||tracker.com^").unwrap();
+        assert!(!adblock_engine_remove_rule(
+            engine,
+            rule.as_ptr(),
+            &mut error
+        ));
+        assert_eq!(error, AdblockErrorCode::NotSupported);
+
         adblock_engine_destroy(engine);
+
+        // Platform code handling both enums sees one meaning per value
+        for status in [
+            AdblockStatus::Ok,
+            AdblockStatus::NullPointer,
+            AdblockStatus::InvalidUtf8,
+            AdblockStatus::LockPoisoned,
+            AdblockStatus::NotSupported,
+            AdblockStatus::Panicked,
+            AdblockStatus::EngineBusy,
+            AdblockStatus::ParseError,
+            AdblockStatus::IoError,
+        ] {
+            let code = AdblockErrorCode::from(status);
+            assert!(
+                status as i32 == code as i32 || status as i32 >= 32 && (code as i32) < 32,
+                "{status:?} and {code:?}"
+            );
+        }
     }
 }
@@ -2,17 +2,107 @@
 //!
 //! TDD Implementation - Starting with minimal code to pass tests
 
-use crate::metrics::{PerfTimer, PerformanceMetrics};
+use crate::audit::ExceptionAuditLog;
+use crate::category_filter::{CategoryFilter, ContentCategory};
+use crate::cosmetic::{CosmeticFilterSet, CosmeticResult, ElemHideException, ElemHideKind};
+use crate::csp::CspRule;
+use crate::filter_updater::FilterListDiff;
+use crate::json_prune::JsonPruneSet;
+use crate::memory_optimization::OptimizedFilterStorage;
+use crate::metrics::{PerfTimer, PerformanceMetrics, RuleCategory};
+use crate::procedural::ProceduralFilterSet;
+use crate::removeparam::{self, RemoveParamRule};
+use crate::resource_type::{self, RequestContext, RequestKind, ResourceType};
+use crate::resources::RedirectRule;
+use crate::rule_hits::{RuleHitCounter, RuleHitReport};
+use crate::scriptlet::ScriptletSet;
+use crate::token_index::TokenIndex;
+use crate::verdict_cache::{CachedVerdict, VerdictCache};
 use aho_corasick::AhoCorasick;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
 /// Result of a block decision
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Record))]
 pub struct BlockDecision {
     /// Whether the request should be blocked
     pub should_block: bool,
     /// Optional reason for the decision
     pub reason: Option<String>,
+    /// Stub resource to serve instead of failing the blocked request
+    pub redirect_resource: Option<String>,
+    /// What the request was blocked as; `None` if it is allowed
+    pub kind: Option<BlockKind>,
+    /// How the host should stop the request
+    pub verdict: Verdict,
+}
+
+/// How a request is stopped, by the type it was checked as
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Enum))]
+pub enum Verdict {
+    #[default]
+    Allow,
+    /// Fail a subresource request
+    BlockRequest,
+    /// Cancel navigation to the page, e.g. by a `$document` rule
+    BlockDocument,
+    /// Cancel a `window.open` popup, matched by a `$popup` rule
+    BlockPopup,
+}
+
+impl Verdict {
+    /// Verdict on a request of a resource type
+    pub fn of(should_block: bool, resource_type: ResourceType) -> Self {
+        match resource_type {
+            _ if !should_block => Verdict::Allow,
+            ResourceType::Document => Verdict::BlockDocument,
+            ResourceType::Popup => Verdict::BlockPopup,
+            _ => Verdict::BlockRequest,
+        }
+    }
+}
+
+/// What a blocked request was blocked as
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Enum))]
+pub enum BlockKind {
+    /// Matched an ad or tracker rule
+    Ad,
+    /// Matched a rule from a malware/phishing protection list; apps show
+    /// an interstitial rather than silently dropping the request
+    Malware,
+    /// The site is listed in a blocked content category
+    Category(ContentCategory),
+}
+
+/// Network rule matching a URL, as listed by [`FilterEngine::explain`]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct RuleMatch {
+    /// Rule text; exceptions keep their `@@` prefix
+    pub rule: String,
+    pub exception: bool,
+    /// Name of the list the rule was loaded from, if it was named
+    pub source: Option<String>,
+    /// Whether the list is a malware/phishing protection list
+    pub security: bool,
+}
+
+/// Every network rule matching a request, for debugging false positives
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct Explanation {
+    /// URL after normalization, as the rules see it
+    pub url: String,
+    /// Resource type the rules' type options were checked against
+    pub resource_type: ResourceType,
+    /// Blocked content category of the host, which no exception overrides
+    pub category: Option<ContentCategory>,
+    /// Exceptions, then blocking rules, each in rule order
+    pub matches: Vec<RuleMatch>,
+    /// Verdict the matches add up to
+    pub should_block: bool,
+    pub redirect_resource: Option<String>,
 }
 
 /// Pattern matching statistics
@@ -22,166 +112,601 @@ pub struct PatternStats {
     pub compiled_patterns: usize,
     /// Whether Aho-Corasick is used
     pub uses_aho_corasick: bool,
+    /// Pattern and exception rules checked for every URL because they have
+    /// no usable token
+    pub untokenized_rules: usize,
+    /// Rules dropped because a `$badfilter` rule cancelled them
+    pub cancelled_rules: usize,
+}
+
+/// Number of rules of each kind, e.g. for a diagnostics screen
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+pub struct RuleCounts {
+    /// Network blocking rules
+    pub block: usize,
+    /// `@@` network exceptions
+    pub exception: usize,
+    /// Element hiding rules and their `#@#` exceptions
+    pub cosmetic: usize,
+    pub procedural: usize,
+    pub scriptlet: usize,
+    pub json_prune: usize,
+    pub removeparam: usize,
+    pub redirect: usize,
+    pub csp: usize,
 }
 
 /// Type of filter rule
-#[derive(Debug, Clone)]
-enum FilterRule {
+///
+/// Rules are stored compactly in an [`OptimizedFilterStorage`] arena; this
+/// is a view of one stored rule.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum FilterRule<'a> {
     /// Simple domain blocking (e.g., "doubleclick.net")
-    Domain(String),
+    Domain(&'a str),
     /// Pattern with wildcards (e.g., "*/ads/*")
-    Pattern(String),
+    Pattern(&'a str),
     /// Subdomain pattern (e.g., "||domain.com^")
-    SubdomainPattern(String),
+    SubdomainPattern(&'a str),
     /// Exception rule (e.g., "@@||example.com/ads/acceptable")
-    Exception(String),
+    Exception(&'a str),
 }
 
-/// Pattern info for tracking rule types
-#[derive(Debug, Clone)]
-struct PatternInfo {
-    pattern: String,
-    rule_type: PatternType,
+/// Storage flags of each rule type
+const DOMAIN_FLAG: u8 = 0;
+const PATTERN_FLAG: u8 = 1;
+const SUBDOMAIN_FLAG: u8 = 2;
+const EXCEPTION_FLAG: u8 = 3;
+/// Bits of the storage flags holding the rule type
+const KIND_MASK: u8 = 0b011;
+/// Storage flag of rules stored as `pattern$options` with resource type options
+const OPTIONS_FLAG: u8 = 0b100;
+
+/// Split `$` resource type and party options (e.g. `$websocket,~script`
+/// or `$third-party`) off a rule
+///
+/// Rules with other options are left whole.
+fn split_type_options(rule: &str) -> (&str, Option<&str>) {
+    match rule.rsplit_once('$') {
+        Some((pattern, options))
+            if !pattern.is_empty()
+                && !options.is_empty()
+                && options.split(',').all(|option| {
+                    ResourceType::is_type_option(option) || resource_type::is_party_option(option)
+                }) =>
+        {
+            (pattern, Some(options))
+        }
+        _ => (rule, None),
+    }
 }
 
-#[derive(Debug, Clone, PartialEq)]
-enum PatternType {
-    Domain,
-    Subdomain,
+/// Split stored rule text into its pattern and resource type options
+fn split_stored(flags: u8, text: &str) -> (&str, Option<&str>) {
+    if flags & OPTIONS_FLAG == 0 {
+        return (text, None);
+    }
+    match text.rsplit_once('$') {
+        Some((pattern, options)) => (pattern, Some(options)),
+        None => (text, None),
+    }
+}
+
+/// Original text of a stored rule, including its options
+fn stored_rule_text(flags: u8, text: &str) -> String {
+    let (pattern, options) = split_stored(flags, text);
+    let rule = FilterRule::from_stored(flags, pattern).text();
+    match options {
+        Some(options) => format!("{rule}${options}"),
+        None => rule,
+    }
+}
+
+impl<'a> FilterRule<'a> {
+    /// Parse a raw rule string
+    fn parse(raw_rule: &'a str) -> Self {
+        if let Some(stripped) = raw_rule.strip_prefix("@@") {
+            FilterRule::Exception(stripped)
+        } else if let Some(stripped) = raw_rule.strip_prefix("||") {
+            if let Some(domain) = stripped.strip_suffix('^') {
+                FilterRule::SubdomainPattern(domain)
+            } else {
+                FilterRule::Pattern(raw_rule)
+            }
+        } else if raw_rule.contains('*') || (raw_rule.starts_with("/") && raw_rule.ends_with("/*"))
+        {
+            FilterRule::Pattern(raw_rule)
+        } else {
+            FilterRule::Domain(raw_rule)
+        }
+    }
+
+    /// Rebuild a rule from its storage flags and pattern
+    fn from_stored(flags: u8, text: &'a str) -> Self {
+        match flags & KIND_MASK {
+            PATTERN_FLAG => FilterRule::Pattern(text),
+            SUBDOMAIN_FLAG => FilterRule::SubdomainPattern(text),
+            EXCEPTION_FLAG => FilterRule::Exception(text),
+            _ => FilterRule::Domain(text),
+        }
+    }
+
+    /// Storage flags and text
+    fn to_stored(self) -> (u8, &'a str) {
+        match self {
+            FilterRule::Domain(text) => (DOMAIN_FLAG, text),
+            FilterRule::Pattern(text) => (PATTERN_FLAG, text),
+            FilterRule::SubdomainPattern(text) => (SUBDOMAIN_FLAG, text),
+            FilterRule::Exception(text) => (EXCEPTION_FLAG, text),
+        }
+    }
+
+    /// Rule text as reported by `FilterEngine::matching_rule`
+    fn text(&self) -> String {
+        match self {
+            FilterRule::Domain(pattern) | FilterRule::Pattern(pattern) => pattern.to_string(),
+            FilterRule::SubdomainPattern(domain) => format!("||{domain}^"),
+            FilterRule::Exception(pattern) => format!("@@{pattern}"),
+        }
+    }
+}
+
+/// Rules of a filter engine as built, ready to be stored
+///
+/// Network rules are kept in their storage with the token indices and the
+/// other rules as parsed, so [`FilterEngine::from_compiled`] restores an
+/// engine without parsing a rule.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct CompiledRules {
+    rules: OptimizedFilterStorage,
+    pattern_index: TokenIndex,
+    exception_index: TokenIndex,
+    indexed_rules: usize,
+    badfilters: HashSet<(u8, String)>,
+    cancelled_rules: usize,
+    cosmetic_filters: CosmeticFilterSet,
+    elemhide_exceptions: Vec<ElemHideException>,
+    procedural_filters: ProceduralFilterSet,
+    scriptlets: ScriptletSet,
+    json_prune_rules: JsonPruneSet,
+    removeparam_rules: Vec<RemoveParamRule>,
+    redirect_rules: Vec<RedirectRule>,
+    csp_rules: Vec<CspRule>,
+    /// Source list name of each network rule, by matched rule text
+    rule_sources: HashMap<String, String>,
+    security_rules: HashSet<String>,
 }
 
 /// Main filter engine for ad blocking
 pub struct FilterEngine {
     /// Compiled filter rules
-    rules: Vec<FilterRule>,
+    rules: OptimizedFilterStorage,
     /// Aho-Corasick automaton for fast domain matching
     domain_matcher: Option<Arc<AhoCorasick>>,
-    /// Pattern info for matched patterns
-    pattern_info: Vec<PatternInfo>,
+    /// Rule index of each Aho-Corasick pattern
+    pattern_rules: Vec<u32>,
+    /// Token index of the wildcard and path pattern rules
+    pattern_index: TokenIndex,
+    /// Token index of the exception rules
+    exception_index: TokenIndex,
+    /// Rules from this index on were added after the token indices were
+    /// built and are checked for every URL
+    indexed_rules: usize,
+    /// Stored form of the rules cancelled by `$badfilter` rules
+    badfilters: HashSet<(u8, String)>,
+    /// Number of rules dropped by `badfilters` so far
+    cancelled_rules: usize,
+    /// Element hiding rules
+    cosmetic_filters: CosmeticFilterSet,
+    /// `$elemhide` / `$generichide` / `$specifichide` exceptions
+    elemhide_exceptions: Vec<ElemHideException>,
+    /// Procedural element hiding rules
+    procedural_filters: ProceduralFilterSet,
+    /// Scriptlet injection rules
+    scriptlets: ScriptletSet,
+    /// `json-prune` response pruning rules
+    json_prune_rules: JsonPruneSet,
+    /// Query parameter stripping rules
+    removeparam_rules: Vec<RemoveParamRule>,
+    /// `$redirect` rules
+    redirect_rules: Vec<RedirectRule>,
+    /// `$csp` rules
+    csp_rules: Vec<CspRule>,
     /// Performance metrics
     metrics: PerformanceMetrics,
+    /// Source list of each network rule, keyed by its matched rule text
+    rule_sources: HashMap<String, Arc<str>>,
+    /// Rules from malware/phishing protection lists, by matched rule text
+    security_rules: HashSet<String>,
+    /// Exception-over-block audit log (disabled when `None`)
+    exception_audit: Option<Arc<ExceptionAuditLog>>,
+    /// Verdicts by normalized URL and request kind (disabled when `None`)
+    verdict_cache: Option<VerdictCache>,
+    /// Per-rule hit counters (disabled when `None`)
+    rule_hits: Option<Arc<RuleHitCounter>>,
+    /// Content category blocking (disabled when `None`)
+    category_filter: Option<Arc<CategoryFilter>>,
+}
+
+/// Copy with the same rules and settings, e.g. to patch while the original
+/// is still checking requests
+///
+/// The copy shares the performance metrics, rule hit counters and exception
+/// audit log of the original; its verdict cache starts empty.
+impl Clone for FilterEngine {
+    fn clone(&self) -> Self {
+        FilterEngine {
+            rules: self.rules.clone(),
+            domain_matcher: self.domain_matcher.clone(),
+            pattern_rules: self.pattern_rules.clone(),
+            pattern_index: self.pattern_index.clone(),
+            exception_index: self.exception_index.clone(),
+            indexed_rules: self.indexed_rules,
+            badfilters: self.badfilters.clone(),
+            cancelled_rules: self.cancelled_rules,
+            cosmetic_filters: self.cosmetic_filters.clone(),
+            elemhide_exceptions: self.elemhide_exceptions.clone(),
+            procedural_filters: self.procedural_filters.clone(),
+            scriptlets: self.scriptlets.clone(),
+            json_prune_rules: self.json_prune_rules.clone(),
+            removeparam_rules: self.removeparam_rules.clone(),
+            redirect_rules: self.redirect_rules.clone(),
+            csp_rules: self.csp_rules.clone(),
+            metrics: self.metrics.clone(),
+            rule_sources: self.rule_sources.clone(),
+            security_rules: self.security_rules.clone(),
+            exception_audit: self.exception_audit.clone(),
+            verdict_cache: self
+                .verdict_cache
+                .as_ref()
+                .map(|cache| VerdictCache::new(cache.ttl(), cache.capacity())),
+            rule_hits: self.rule_hits.clone(),
+            category_filter: self.category_filter.clone(),
+        }
+    }
 }
 
 impl FilterEngine {
     /// Create a filter engine from a filter list string
     pub fn from_filter_list(filter_list: &str) -> Result<Self, Box<dyn std::error::Error>> {
-        use crate::filter_list::FilterListLoader;
+        let mut engine = Self::empty();
+        for line in filter_list.lines() {
+            engine.add_list_line(line);
+        }
+        engine.compile_patterns();
+        Ok(engine)
+    }
+
+    /// Create a filter engine from a filter list, loading the engine
+    /// compiled into `cache_dir` by an earlier call with the same text
+    pub fn from_filter_list_cached(
+        filter_list: &str,
+        cache_dir: impl AsRef<std::path::Path>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        crate::compiled::CompileCache::new(cache_dir).engine(filter_list)
+    }
 
-        let loader = FilterListLoader::new();
-        let raw_rules = loader.parse_filter_list(filter_list)?;
+    /// Create a filter engine by reading a filter list line by line
+    ///
+    /// Only one line is held in memory at a time, so multi-megabyte lists
+    /// can be loaded without a copy of the whole text.
+    pub fn from_reader<R: std::io::BufRead>(
+        mut reader: R,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut engine = Self::empty();
+        let mut line = String::new();
+        while reader.read_line(&mut line)? > 0 {
+            engine.add_list_line(&line);
+            line.clear();
+        }
+        engine.compile_patterns();
+        Ok(engine)
+    }
 
-        let rules: Vec<FilterRule> = raw_rules.into_iter().map(Self::parse_rule).collect();
+    /// Route one filter list line to the network or cosmetic rule sets
+    fn add_list_line(&mut self, line: &str) {
+        use crate::filter_list::{network_rules, LineFormat};
 
-        let mut engine = FilterEngine {
-            rules,
+        let line = line.trim();
+        let (format, rules) = network_rules(line);
+        if format == LineFormat::Cosmetic {
+            // Each set only accepts its own rule syntax
+            if !self.cosmetic_filters.add_rule(line)
+                && !self.procedural_filters.add_rule(line)
+                && !self.json_prune_rules.add_rule(line)
+            {
+                self.scriptlets.add_rule(line);
+            }
+        }
+        for rule in rules {
+            self.add_rule(&rule);
+        }
+    }
+
+    /// Create an uncompiled engine without rules
+    fn empty() -> Self {
+        FilterEngine {
+            rules: OptimizedFilterStorage::new(),
             domain_matcher: None,
-            pattern_info: Vec::new(),
+            pattern_rules: Vec::new(),
+            pattern_index: TokenIndex::default(),
+            exception_index: TokenIndex::default(),
+            indexed_rules: 0,
+            badfilters: HashSet::new(),
+            cancelled_rules: 0,
+            cosmetic_filters: CosmeticFilterSet::new(),
+            elemhide_exceptions: Vec::new(),
+            procedural_filters: ProceduralFilterSet::new(),
+            scriptlets: ScriptletSet::new(),
+            json_prune_rules: JsonPruneSet::new(),
+            removeparam_rules: Vec::new(),
+            redirect_rules: Vec::new(),
+            csp_rules: Vec::new(),
             metrics: PerformanceMetrics::new(),
-        };
+            rule_sources: HashMap::new(),
+            security_rules: HashSet::new(),
+            exception_audit: None,
+            verdict_cache: None,
+            rule_hits: None,
+            category_filter: None,
+        }
+    }
 
-        engine.compile_patterns();
-        Ok(engine)
+    /// Store a raw network rule
+    fn push_rule(&mut self, rule: &str) {
+        let (flags, text) = Self::stored_form(rule);
+        self.rules.push(flags, &text);
     }
 
-    /// Parse a raw rule string into a FilterRule
-    fn parse_rule(raw_rule: String) -> FilterRule {
-        if let Some(stripped) = raw_rule.strip_prefix("@@") {
-            FilterRule::Exception(stripped.to_string())
-        } else if let Some(stripped) = raw_rule.strip_prefix("||") {
-            if let Some(domain) = stripped.strip_suffix('^') {
-                FilterRule::SubdomainPattern(domain.to_string())
-            } else {
-                FilterRule::Pattern(raw_rule)
-            }
-        } else if raw_rule.contains('*') || (raw_rule.starts_with("/") && raw_rule.ends_with("/*"))
-        {
-            FilterRule::Pattern(raw_rule)
-        } else {
-            FilterRule::Domain(raw_rule)
+    /// Storage flags and text of a raw network rule
+    ///
+    /// Rules with resource type options keep them after a `$`.
+    fn stored_form(rule: &str) -> (u8, std::borrow::Cow<'_, str>) {
+        let (pattern, options) = split_type_options(rule);
+        let (flags, text) = FilterRule::parse(pattern).to_stored();
+        match options {
+            Some(options) => (flags | OPTIONS_FLAG, format!("{text}${options}").into()),
+            None => (flags, text.into()),
         }
     }
 
     /// Create a filter engine from a file
     pub fn from_file(path: &std::path::Path) -> Result<Self, Box<dyn std::error::Error>> {
-        let content = std::fs::read_to_string(path)?;
-        Self::from_filter_list(&content)
+        let file = std::fs::File::open(path)?;
+        Self::from_reader(std::io::BufReader::new(file))
     }
 
     /// Create a new filter engine with default ad-blocking rules
     pub fn new_with_defaults() -> Self {
-        let rules = vec![
-            FilterRule::Domain("doubleclick.net".to_string()),
-            FilterRule::Domain("googleadservices.com".to_string()),
-            FilterRule::Domain("googlesyndication.com".to_string()),
-            FilterRule::Domain("facebook.com/tr".to_string()),
-            FilterRule::Domain("amazon-adsystem.com".to_string()),
+        let domains = [
+            "doubleclick.net",
+            "googleadservices.com",
+            "googlesyndication.com",
+            "facebook.com/tr",
+            "amazon-adsystem.com",
         ];
-
-        let mut engine = FilterEngine {
-            rules,
-            domain_matcher: None,
-            pattern_info: Vec::new(),
-            metrics: PerformanceMetrics::new(),
-        };
-
-        engine.compile_patterns();
-        engine
+        Self::new_with_patterns(domains.iter().map(|d| d.to_string()).collect())
     }
 
     /// Create a new filter engine with custom patterns
     pub fn new_with_patterns(patterns: Vec<String>) -> Self {
-        let rules = patterns.into_iter().map(Self::parse_rule).collect();
-
-        let mut engine = FilterEngine {
-            rules,
-            domain_matcher: None,
-            pattern_info: Vec::new(),
-            metrics: PerformanceMetrics::new(),
-        };
-
+        let mut engine = Self::empty();
+        for pattern in &patterns {
+            engine.push_rule(pattern);
+        }
         engine.compile_patterns();
         engine
     }
 
     /// Compile patterns for efficient matching
     fn compile_patterns(&mut self) {
-        // Extract patterns and their info for Aho-Corasick
+        self.drop_badfiltered();
+        self.rules.purge_removed();
+        self.compile_domain_matcher();
+
+        // The remaining rules are only checked when their token is in the URL
+        let index = |kind| {
+            TokenIndex::build(
+                self.rules
+                    .with_flags(KIND_MASK, kind)
+                    .map(|(index, flags, text)| (index as u32, split_stored(flags, text).0)),
+            )
+        };
+        self.pattern_index = index(PATTERN_FLAG);
+        self.exception_index = index(EXCEPTION_FLAG);
+        self.indexed_rules = self.rules.slots();
+
+        // Update metrics
+        self.metrics.set_filter_count(self.rules.len());
+        self.metrics.set_memory_usage(self.memory_usage());
+    }
+
+    /// Match the stored domain rules in one pass with Aho-Corasick
+    fn compile_domain_matcher(&mut self) {
         let mut patterns = Vec::new();
-        self.pattern_info.clear();
-
-        for rule in &self.rules {
-            match rule {
-                FilterRule::Domain(domain) => {
-                    patterns.push(domain.clone());
-                    self.pattern_info.push(PatternInfo {
-                        pattern: domain.clone(),
-                        rule_type: PatternType::Domain,
-                    });
-                }
-                FilterRule::SubdomainPattern(domain) => {
-                    patterns.push(domain.clone());
-                    self.pattern_info.push(PatternInfo {
-                        pattern: domain.clone(),
-                        rule_type: PatternType::Subdomain,
-                    });
-                }
-                _ => {}
+        let mut pattern_rules = Vec::new();
+        for (index, flags, text) in self.rules.iter() {
+            let kind = flags & KIND_MASK;
+            if kind == DOMAIN_FLAG || kind == SUBDOMAIN_FLAG {
+                patterns.push(split_stored(flags, text).0);
+                pattern_rules.push(index as u32);
             }
         }
 
         // Build Aho-Corasick automaton if we have patterns
+        let mut domain_matcher = None;
         if !patterns.is_empty() {
-            match AhoCorasick::new(&patterns) {
-                Ok(ac) => self.domain_matcher = Some(Arc::new(ac)),
+            match AhoCorasick::new(patterns) {
+                Ok(ac) => domain_matcher = Some(Arc::new(ac)),
                 Err(e) => {
-                    log::error!("Failed to build Aho-Corasick automaton: {}", e);
+                    tracing::error!("Failed to build Aho-Corasick automaton: {}", e);
                     // Continue without optimized matching
                 }
             }
         }
+        self.domain_matcher = domain_matcher;
+        self.pattern_rules = pattern_rules;
+    }
 
-        // Update metrics
-        self.metrics.set_filter_count(self.rules.len());
+    /// Rules in their compiled form, for [`CompiledEngine`](crate::compiled::CompiledEngine)
+    pub fn to_compiled(&self) -> CompiledRules {
+        CompiledRules {
+            rules: self.rules.clone(),
+            pattern_index: self.pattern_index.clone(),
+            exception_index: self.exception_index.clone(),
+            indexed_rules: self.indexed_rules,
+            badfilters: self.badfilters.clone(),
+            cancelled_rules: self.cancelled_rules,
+            cosmetic_filters: self.cosmetic_filters.clone(),
+            elemhide_exceptions: self.elemhide_exceptions.clone(),
+            procedural_filters: self.procedural_filters.clone(),
+            scriptlets: self.scriptlets.clone(),
+            json_prune_rules: self.json_prune_rules.clone(),
+            removeparam_rules: self.removeparam_rules.clone(),
+            redirect_rules: self.redirect_rules.clone(),
+            csp_rules: self.csp_rules.clone(),
+            rule_sources: self
+                .rule_sources
+                .iter()
+                .map(|(rule, list)| (rule.clone(), list.to_string()))
+                .collect(),
+            security_rules: self.security_rules.clone(),
+        }
+    }
+
+    /// Restore an engine from its compiled rules without parsing them
+    ///
+    /// Only the Aho-Corasick automaton of the domain rules is rebuilt.
+    pub fn from_compiled(compiled: CompiledRules) -> Self {
+        let mut lists: HashMap<String, Arc<str>> = HashMap::new();
+        let rule_sources = compiled
+            .rule_sources
+            .into_iter()
+            .map(|(rule, list)| {
+                let list = lists
+                    .entry(list)
+                    .or_insert_with_key(|list| list.as_str().into());
+                (rule, Arc::clone(list))
+            })
+            .collect();
+        let mut engine = FilterEngine {
+            rules: compiled.rules,
+            pattern_index: compiled.pattern_index,
+            exception_index: compiled.exception_index,
+            indexed_rules: compiled.indexed_rules,
+            badfilters: compiled.badfilters,
+            cancelled_rules: compiled.cancelled_rules,
+            cosmetic_filters: compiled.cosmetic_filters,
+            elemhide_exceptions: compiled.elemhide_exceptions,
+            procedural_filters: compiled.procedural_filters,
+            scriptlets: compiled.scriptlets,
+            json_prune_rules: compiled.json_prune_rules,
+            removeparam_rules: compiled.removeparam_rules,
+            redirect_rules: compiled.redirect_rules,
+            csp_rules: compiled.csp_rules,
+            rule_sources,
+            security_rules: compiled.security_rules,
+            ..Self::empty()
+        };
+        engine.compile_domain_matcher();
+        engine.metrics.set_filter_count(engine.rules.len());
+        engine.metrics.set_memory_usage(engine.memory_usage());
+        engine
+    }
+
+    /// Remove the rules cancelled by `$badfilter` rules
+    ///
+    /// Runs on every compile, so a `$badfilter` rule also cancels rules
+    /// from lists loaded after it.
+    fn drop_badfiltered(&mut self) {
+        if self.badfilters.is_empty() {
+            return;
+        }
+        let texts: HashSet<&str> = self
+            .badfilters
+            .iter()
+            .map(|(_, text)| text.as_str())
+            .collect();
+        let cancelled: Vec<(usize, String)> = self
+            .rules
+            .iter()
+            .filter(|&(_, flags, text)| {
+                texts.contains(text) && self.badfilters.contains(&(flags, text.to_string()))
+            })
+            .map(|(index, flags, text)| (index, stored_rule_text(flags, text)))
+            .collect();
+        if cancelled.is_empty() {
+            return;
+        }
+        for (index, rule) in &cancelled {
+            self.rules.remove(*index);
+            self.rule_sources.remove(rule);
+            self.security_rules.remove(rule);
+        }
+        self.cancelled_rules += cancelled.len();
+        tracing::info!("$badfilter cancelled {} rules", cancelled.len());
+        if let Some(cache) = &self.verdict_cache {
+            cache.clear();
+        }
+    }
+
+    /// Estimated heap memory held by the network rules and caches in bytes
+    pub fn memory_usage(&self) -> usize {
+        use std::mem::size_of;
+
+        self.rules.memory_usage()
+            + self.pattern_rules.capacity() * size_of::<u32>()
+            + self.pattern_index.memory_usage()
+            + self.exception_index.memory_usage()
+            + self
+                .rule_sources
+                .keys()
+                .map(|rule| rule.len() + size_of::<(String, Arc<str>)>())
+                .sum::<usize>()
+            + self
+                .security_rules
+                .iter()
+                .map(|rule| rule.len() + size_of::<String>())
+                .sum::<usize>()
+            + self
+                .domain_matcher
+                .as_ref()
+                .map_or(0, |matcher| matcher.memory_usage())
+            + self
+                .verdict_cache
+                .as_ref()
+                .map_or(0, VerdictCache::memory_usage)
+            + self
+                .rule_hits
+                .as_deref()
+                .map_or(0, RuleHitCounter::memory_usage)
+    }
+
+    /// Drop cached verdicts, keeping the rules
+    ///
+    /// Safe to call on a shared engine in response to memory pressure.
+    pub fn release_caches(&self) {
+        if let Some(cache) = &self.verdict_cache {
+            cache.clear();
+        }
+    }
+
+    /// Drop caches and release spare capacity, returning the estimated
+    /// bytes freed
+    pub fn compact(&mut self) -> usize {
+        let before = self.memory_usage();
+        self.release_caches();
+        if self.rules.len() < self.rules.slots() {
+            // Purging removed rules shifts the indices the matchers refer to
+            self.compile_patterns();
+        }
+        self.rules.compact();
+        self.pattern_rules.shrink_to_fit();
+        self.rule_sources.shrink_to_fit();
+        self.security_rules.shrink_to_fit();
+        let after = self.memory_usage();
+        self.metrics.set_memory_usage(after);
+        before.saturating_sub(after)
     }
 
     /// Get pattern statistics
@@ -189,87 +714,393 @@ impl FilterEngine {
         PatternStats {
             compiled_patterns: self.rules.len(),
             uses_aho_corasick: self.domain_matcher.is_some(),
+            untokenized_rules: self.pattern_index.untokenized_len()
+                + self.exception_index.untokenized_len(),
+            cancelled_rules: self.cancelled_rules,
         }
     }
 
     /// Check if a URL should be blocked
+    ///
+    /// The resource type is inferred from the URL. The URL is normalized
+    /// with `utils::parse_url` first, so rules match regardless of host
+    /// case, default ports or percent-encoding.
     pub fn should_block(&self, url: &str) -> BlockDecision {
+        self.should_block_with_context(url, &RequestContext::from_url(url))
+    }
+
+    /// Check if a request should be blocked, honoring `$websocket`,
+    /// `$script` and other resource type options, and `$third-party`
+    /// when the page is known
+    pub fn should_block_with_context(&self, url: &str, context: &RequestContext) -> BlockDecision {
         let timer = PerfTimer::start();
-        // First check exception rules
-        for rule in &self.rules {
-            if let FilterRule::Exception(pattern) = rule {
-                if self.matches_exception_pattern(url, pattern) {
-                    return BlockDecision {
-                        should_block: false,
-                        reason: Some(format!("Whitelisted by exception: {pattern}")),
-                    };
+        let url = &*crate::utils::normalize_url(url);
+        let request = context.kind(url);
+        let verdict_cache = self.verdict_cache.as_ref();
+        let verdict = match verdict_cache.and_then(|c| c.get(url, request)) {
+            Some(verdict) => verdict,
+            None => {
+                let verdict = self.evaluate(url, request);
+                if let Some(cache) = verdict_cache {
+                    cache.insert(url, request, &verdict);
                 }
+                verdict
             }
+        };
+        self.record_verdict(url, &verdict, timer.elapsed());
+        verdict.decision
+    }
+
+    /// Evaluate the rules for a request, keeping the rules behind the verdict
+    fn evaluate(&self, url: &str, request: RequestKind) -> CachedVerdict {
+        // Blocked categories can't be overridden by exception rules
+        if let Some(category) = self.blocked_category(url) {
+            return CachedVerdict {
+                decision: BlockDecision {
+                    should_block: true,
+                    reason: Some(format!("Blocked category: {category:?}")),
+                    redirect_resource: None,
+                    kind: Some(BlockKind::Category(category)),
+                    verdict: Verdict::of(true, request.resource_type),
+                },
+                rule: None,
+                overridden: None,
+            };
         }
 
-        // Use Aho-Corasick for fast domain matching
-        if let Some(decision) = self.check_aho_corasick_matches(url) {
-            self.metrics
-                .record_request(decision.should_block, timer.elapsed());
-            return decision;
+        // First check exception rules
+        if let Some(pattern) = self.find_exception(url, request) {
+            // Only audited exceptions pay for the second lookup
+            let overridden = self
+                .exception_audit
+                .as_ref()
+                .and_then(|_| self.find_block_match(url, request))
+                .map(|(rule, _)| rule);
+            return CachedVerdict {
+                decision: BlockDecision {
+                    should_block: false,
+                    reason: Some(format!("Whitelisted by exception: {pattern}")),
+                    redirect_resource: None,
+                    kind: None,
+                    verdict: Verdict::Allow,
+                },
+                rule: Some(format!("@@{pattern}")),
+                overridden,
+            };
         }
 
-        // Then check other blocking rules
-        for rule in &self.rules {
-            match rule {
-                FilterRule::Domain(_) | FilterRule::SubdomainPattern(_) => {
-                    // Already handled by Aho-Corasick above
+        match self.find_block_match(url, request) {
+            Some((rule, mut decision)) => {
+                if self.security_rules.contains(&rule) {
+                    decision.kind = Some(BlockKind::Malware);
                 }
-                FilterRule::Pattern(pattern) => {
-                    if self.matches_wildcard_pattern(url, pattern) {
-                        let decision = BlockDecision {
-                            should_block: true,
-                            reason: Some(format!("Matched pattern: {pattern}")),
-                        };
-                        self.metrics
-                            .record_request(decision.should_block, timer.elapsed());
-                        return decision;
-                    }
+                if decision.should_block {
+                    decision.redirect_resource = self.find_redirect(url);
                 }
-                FilterRule::Exception(_) => {
-                    // Already handled above
+                CachedVerdict {
+                    decision,
+                    rule: Some(rule),
+                    overridden: None,
                 }
             }
+            None => CachedVerdict {
+                decision: BlockDecision {
+                    should_block: false,
+                    reason: None,
+                    redirect_resource: None,
+                    kind: None,
+                    verdict: Verdict::Allow,
+                },
+                rule: None,
+                overridden: None,
+            },
         }
+    }
 
-        let decision = BlockDecision {
-            should_block: false,
-            reason: None,
-        };
+    /// Count a verdict in the rule hits, metrics and exception audit, whether
+    /// it was just evaluated or served from the verdict cache
+    fn record_verdict(&self, url: &str, verdict: &CachedVerdict, elapsed: std::time::Duration) {
+        if let Some(rule) = &verdict.rule {
+            self.record_match(rule);
+        }
+        match (&verdict.rule, &verdict.overridden) {
+            (Some(exception), Some(overridden)) => {
+                if let Some(audit) = &self.exception_audit {
+                    audit.record(url, exception, overridden);
+                }
+            }
+            (Some(rule), None) if rule.starts_with("@@") => {}
+            _ => self
+                .metrics
+                .record_request(verdict.decision.should_block, elapsed),
+        }
+    }
+
+    /// Enabled content category the URL's host is listed in
+    fn blocked_category(&self, url: &str) -> Option<ContentCategory> {
+        self.category_filter
+            .as_ref()?
+            .blocked_category(&crate::utils::extract_domain(url))
+    }
+
+    /// Attribute a matched rule to its category and source list
+    fn record_match(&self, rule: &str) {
+        if let Some(rule_hits) = &self.rule_hits {
+            rule_hits.record(rule);
+        }
+        let source = self.rule_sources.get(rule).map(|list| &**list);
         self.metrics
-            .record_request(decision.should_block, timer.elapsed());
-        decision
+            .record_match(RuleCategory::of_rule(rule), source);
+    }
+
+    /// Rule responsible for the verdict on a URL, without recording metrics
+    ///
+    /// Exceptions are returned with their `@@` prefix; `None` means no rule
+    /// matched and the URL is allowed.
+    pub fn matching_rule(&self, url: &str) -> Option<String> {
+        let request = RequestContext::from_url(url).kind(url);
+        let url = &*crate::utils::normalize_url(url);
+        if let Some(pattern) = self.find_exception(url, request) {
+            return Some(format!("@@{pattern}"));
+        }
+        self.find_block_match(url, request).map(|(rule, _)| rule)
+    }
+
+    /// Every network rule matching a request, rather than the first one
+    ///
+    /// Each rule is checked in turn, bypassing the token index, the verdict
+    /// cache and metrics, so this is meant for developer tools only.
+    pub fn explain(&self, url: &str, context: &RequestContext) -> Explanation {
+        let url = crate::utils::normalize_url(url).into_owned();
+        let request = context.kind(&url);
+        let resource_type = request.resource_type;
+        let mut exceptions = Vec::new();
+        let mut blocks = Vec::new();
+        for (_, flags, text) in self.rules.iter() {
+            let (pattern, options) = split_stored(flags, text);
+            if !request.matches_rule_options(options) {
+                continue;
+            }
+            let (matched, exception) = match FilterRule::from_stored(flags, pattern) {
+                // The domain matcher finds domains anywhere in the URL
+                FilterRule::Domain(domain) => (url.contains(domain), false),
+                FilterRule::SubdomainPattern(domain) => {
+                    (self.matches_subdomain(&url, domain), false)
+                }
+                FilterRule::Pattern(pattern) => match pattern.strip_prefix("||") {
+                    Some(anchored) => (self.matches_subdomain_pattern(&url, anchored), false),
+                    None => (self.matches_wildcard_pattern(&url, pattern), false),
+                },
+                FilterRule::Exception(pattern) => {
+                    (self.matches_exception_pattern(&url, pattern), true)
+                }
+            };
+            if !matched {
+                continue;
+            }
+            let rule = stored_rule_text(flags, text);
+            let rule_match = RuleMatch {
+                source: self.rule_sources.get(&rule).map(|list| list.to_string()),
+                security: self.security_rules.contains(&rule),
+                exception,
+                rule,
+            };
+            if exception {
+                exceptions.push(rule_match);
+            } else {
+                blocks.push(rule_match);
+            }
+        }
+
+        let category = self.blocked_category(&url);
+        let should_block = category.is_some() || (exceptions.is_empty() && !blocks.is_empty());
+        let redirect_resource = match category {
+            None if should_block => self.find_redirect(&url),
+            _ => None,
+        };
+        exceptions.append(&mut blocks);
+        Explanation {
+            url,
+            resource_type,
+            category,
+            matches: exceptions,
+            should_block,
+            redirect_resource,
+        }
+    }
+
+    /// Strip tracking and `$removeparam` query parameters from a URL
+    ///
+    /// Returns `None` if the URL is unchanged.
+    pub fn rewrite_url(&self, url: &str) -> Option<String> {
+        let in_scope = |rule: &&RemoveParamRule| {
+            rule.scope
+                .as_deref()
+                .is_none_or(|scope| self.matches_exception_pattern(url, scope))
+        };
+        let (exceptions, removals): (Vec<&RemoveParamRule>, Vec<&RemoveParamRule>) = self
+            .removeparam_rules
+            .iter()
+            .filter(in_scope)
+            .partition(|rule| rule.exception);
+
+        removeparam::strip_query_params(url, |name| {
+            let remove = removeparam::is_tracking_param(name)
+                || removals.iter().any(|rule| rule.matches_param(name));
+            remove && !exceptions.iter().any(|rule| rule.matches_param(name))
+        })
+    }
+
+    /// CSP directives to append to the response for a document URL
+    ///
+    /// `source_domain` is checked against the rules' `domain=` option.
+    /// Directives disabled by `@@...$csp` exceptions are left out.
+    pub fn get_csp_directives(&self, url: &str, source_domain: &str) -> Vec<String> {
+        let (exceptions, rules): (Vec<&CspRule>, Vec<&CspRule>) = self
+            .csp_rules
+            .iter()
+            .filter(|rule| {
+                rule.applies_to_source(source_domain)
+                    && rule
+                        .scope
+                        .as_deref()
+                        .is_none_or(|scope| self.matches_exception_pattern(url, scope))
+            })
+            .partition(|rule| rule.exception);
+
+        if exceptions.iter().any(|rule| rule.directive.is_none()) {
+            return Vec::new();
+        }
+
+        let mut directives: Vec<String> = Vec::new();
+        for directive in rules.iter().filter_map(|rule| rule.directive.as_ref()) {
+            let excepted = exceptions
+                .iter()
+                .any(|rule| rule.directive.as_ref() == Some(directive));
+            if !excepted && !directives.contains(directive) {
+                directives.push(directive.clone());
+            }
+        }
+        directives
+    }
+
+    /// Find the redirect resource for a blocked URL
+    fn find_redirect(&self, url: &str) -> Option<String> {
+        self.redirect_rules
+            .iter()
+            .find(|rule| self.matches_exception_pattern(url, &rule.pattern))
+            .map(|rule| rule.resource.clone())
+    }
+
+    /// Find the first exception rule matching the URL
+    ///
+    /// Returns the rule text without its `@@` prefix.
+    fn find_exception(&self, url: &str, request: RequestKind) -> Option<&str> {
+        self.candidate_rules(EXCEPTION_FLAG, url, request)
+            .find(|(_, pattern)| self.matches_exception_pattern(url, pattern))
+            .map(|(rule, _)| rule)
+    }
+
+    /// Pattern or exception rules that may match the URL, in rule order, as
+    /// (stored text, pattern) pairs
+    ///
+    /// Only rules filed under a token of the URL, rules without a token and
+    /// rules added since the last compile are returned, and only if their
+    /// options allow the resource type.
+    fn candidate_rules<'a>(
+        &'a self,
+        kind: u8,
+        url: &str,
+        request: RequestKind,
+    ) -> impl Iterator<Item = (&'a str, &'a str)> + 'a {
+        let index = if kind == EXCEPTION_FLAG {
+            &self.exception_index
+        } else {
+            &self.pattern_index
+        };
+        index
+            .candidates(url)
+            .into_iter()
+            .map(|rule| rule as usize)
+            .chain(self.indexed_rules..self.rules.slots())
+            .filter_map(move |rule| self.rules.get(rule))
+            .filter(move |&(flags, _)| flags & KIND_MASK == kind)
+            .filter_map(move |(flags, text)| {
+                let (pattern, options) = split_stored(flags, text);
+                request
+                    .matches_rule_options(options)
+                    .then_some((text, pattern))
+            })
+    }
+
+    /// Find the first blocking rule matching the URL, ignoring exceptions
+    ///
+    /// Returns the rule text alongside the decision it produces.
+    fn find_block_match(&self, url: &str, request: RequestKind) -> Option<(String, BlockDecision)> {
+        // Use Aho-Corasick for fast domain matching
+        if let Some(matched) = self.check_aho_corasick_matches(url, request) {
+            return Some(matched);
+        }
+
+        // Then check other blocking rules
+        for (rule, pattern) in self.candidate_rules(PATTERN_FLAG, url, request) {
+            // `||host/path` is anchored to the host, not matched anywhere
+            let matched = match pattern.strip_prefix("||") {
+                Some(anchored) => self.matches_subdomain_pattern(url, anchored),
+                None => self.matches_wildcard_pattern(url, pattern),
+            };
+            if matched {
+                let decision = BlockDecision {
+                    should_block: true,
+                    reason: Some(format!("Matched pattern: {pattern}")),
+                    redirect_resource: None,
+                    kind: Some(BlockKind::Ad),
+                    verdict: Verdict::of(true, request.resource_type),
+                };
+                return Some((rule.to_string(), decision));
+            }
+        }
+
+        None
     }
 
     /// Check Aho-Corasick matches
-    fn check_aho_corasick_matches(&self, url: &str) -> Option<BlockDecision> {
+    fn check_aho_corasick_matches(
+        &self,
+        url: &str,
+        request: RequestKind,
+    ) -> Option<(String, BlockDecision)> {
         let matcher = self.domain_matcher.as_ref()?;
 
         for match_result in matcher.find_iter(url) {
-            let pattern_info = &self.pattern_info[match_result.pattern()];
-
-            match pattern_info.rule_type {
-                PatternType::Subdomain => {
-                    // Verify it's actually a subdomain match
-                    if self.matches_subdomain(url, &pattern_info.pattern) {
-                        return Some(BlockDecision {
-                            should_block: true,
-                            reason: Some(format!("Matched subdomain: {}", pattern_info.pattern)),
-                        });
-                    }
-                }
-                PatternType::Domain => {
-                    return Some(BlockDecision {
-                        should_block: true,
-                        reason: Some(format!("Matched ad domain: {}", pattern_info.pattern)),
-                    });
-                }
+            // Rules removed since the matcher was built are skipped
+            let index = self.pattern_rules[match_result.pattern()] as usize;
+            let Some((flags, text)) = self.rules.get(index) else {
+                continue;
+            };
+            let (pattern, options) = split_stored(flags, text);
+            if !request.matches_rule_options(options) {
+                continue;
             }
+
+            let reason = match FilterRule::from_stored(flags, pattern) {
+                // Verify it's actually a subdomain match
+                FilterRule::SubdomainPattern(domain) if self.matches_subdomain(url, domain) => {
+                    format!("Matched subdomain: {domain}")
+                }
+                FilterRule::Domain(domain) => format!("Matched ad domain: {domain}"),
+                _ => continue,
+            };
+            return Some((
+                stored_rule_text(flags, text),
+                BlockDecision {
+                    should_block: true,
+                    reason: Some(reason),
+                    redirect_resource: None,
+                    kind: Some(BlockKind::Ad),
+                    verdict: Verdict::of(true, request.resource_type),
+                },
+            ));
         }
 
         None
@@ -277,15 +1108,19 @@ impl FilterEngine {
 
     /// Check if URL matches a subdomain pattern
     fn matches_subdomain(&self, url: &str, domain: &str) -> bool {
-        if let Some(start) = url.find("://") {
-            let url_after_protocol = &url[start + 3..];
-            let url_host = url_after_protocol.split('/').next().unwrap_or("");
+        let url_after_protocol = match url.find("://") {
+            Some(start) => &url[start + 3..],
+            // STUN/TURN URIs have no `//`, e.g. `turn:relay.example.com:3478`
+            None => match url.split_once(':') {
+                Some(("stun" | "stuns" | "turn" | "turns", rest)) => rest,
+                _ => return false,
+            },
+        };
+        let url_host = url_after_protocol.split(['/', '?']).next().unwrap_or("");
+        let url_host = url_host.split(':').next().unwrap_or(url_host);
 
-            // Exact match or subdomain match
-            url_host == domain || url_host.ends_with(&format!(".{domain}"))
-        } else {
-            false
-        }
+        // Exact match or subdomain match
+        url_host == domain || url_host.ends_with(&format!(".{domain}"))
     }
 
     /// Check if URL matches a wildcard pattern
@@ -398,9 +1233,127 @@ impl FilterEngine {
     }
 
     /// Add a single rule to the engine
+    ///
+    /// Unicode hosts are stored punycode-encoded, matching normalized URLs.
     pub fn add_rule(&mut self, rule: &str) {
-        let parsed_rule = Self::parse_rule(rule.to_string());
-        self.rules.push(parsed_rule);
+        let rule = &*crate::idn::normalize_rule(rule);
+        if let Some(target) = crate::filter_list::badfilter_target(rule) {
+            // Applied to every matching rule when the patterns are compiled
+            let (flags, text) = Self::stored_form(&target);
+            self.badfilters.insert((flags, text.into_owned()));
+            return;
+        }
+        if let Some(removeparam_rule) = RemoveParamRule::parse(rule) {
+            self.removeparam_rules.push(removeparam_rule);
+            return;
+        }
+        if let Some(elemhide) = ElemHideException::parse(rule) {
+            self.elemhide_exceptions.push(elemhide);
+            return;
+        }
+        if let Some(csp_rule) = CspRule::parse(rule) {
+            self.csp_rules.push(csp_rule);
+            return;
+        }
+        let rule = match RedirectRule::parse(rule) {
+            Some(redirect_rule) => {
+                let blocks = redirect_rule.blocks;
+                let pattern = redirect_rule.pattern.clone();
+                self.redirect_rules.push(redirect_rule);
+                if !blocks {
+                    return;
+                }
+                pattern
+            }
+            None => rule.to_string(),
+        };
+        self.push_rule(&rule);
+        if let Some(cache) = &self.verdict_cache {
+            cache.clear();
+        }
+    }
+
+    /// Remove one rule equal to a filter list line, returning whether one was removed
+    ///
+    /// Hosts entries and bare domains remove the `||domain^` rules they were
+    /// loaded as. Call `build_domain_matcher` after removing network rules.
+    pub fn remove_rule(&mut self, line: &str) -> bool {
+        self.remove_list_line(line) > 0
+    }
+
+    /// Undo `add_list_line`, returning the number of rules removed
+    fn remove_list_line(&mut self, line: &str) -> usize {
+        use crate::filter_list::{network_rules, LineFormat};
+
+        let line = line.trim();
+        let (format, rules) = network_rules(line);
+        if format == LineFormat::Cosmetic {
+            let removed = self.cosmetic_filters.remove_rule(line)
+                || self.procedural_filters.remove_rule(line)
+                || self.json_prune_rules.remove_rule(line)
+                || self.scriptlets.remove_rule(line);
+            return removed as usize;
+        }
+        rules
+            .iter()
+            .filter(|rule| self.remove_network_rule(rule))
+            .count()
+    }
+
+    /// Remove one network rule, returning whether one was removed
+    fn remove_network_rule(&mut self, rule: &str) -> bool {
+        let line = &*crate::idn::normalize_rule(rule);
+        if let Some(target) = crate::filter_list::badfilter_target(line) {
+            // Rules it already cancelled stay dropped until their list is reloaded
+            let (flags, text) = Self::stored_form(&target);
+            return self.badfilters.remove(&(flags, text.into_owned()));
+        }
+        let removed = if let Some(rule) = RemoveParamRule::parse(line) {
+            remove_first(&mut self.removeparam_rules, &rule)
+        } else if let Some(rule) = ElemHideException::parse(line) {
+            remove_first(&mut self.elemhide_exceptions, &rule)
+        } else if let Some(rule) = CspRule::parse(line) {
+            remove_first(&mut self.csp_rules, &rule)
+        } else {
+            let rule = match RedirectRule::parse(line) {
+                Some(redirect_rule) => {
+                    let blocks = redirect_rule.blocks;
+                    let pattern = redirect_rule.pattern.clone();
+                    let removed = remove_first(&mut self.redirect_rules, &redirect_rule);
+                    if !blocks {
+                        return removed;
+                    }
+                    pattern
+                }
+                None => line.to_string(),
+            };
+            let (flags, text) = Self::stored_form(&rule);
+            self.rules.remove_first(flags, &text)
+        };
+
+        if removed {
+            if let Some(cache) = &self.verdict_cache {
+                cache.clear();
+            }
+        }
+        removed
+    }
+
+    /// Apply the rules added to and removed from a filter list in place
+    ///
+    /// Returns the number of rules actually removed; removed lines the
+    /// engine does not hold are ignored.
+    pub fn apply_diff(&mut self, diff: &FilterListDiff) -> usize {
+        let removed = diff
+            .removed
+            .iter()
+            .map(|line| self.remove_list_line(line))
+            .sum();
+        for line in &diff.added {
+            self.add_list_line(line);
+        }
+        self.compile_patterns();
+        removed
     }
 
     /// Rebuild the domain matcher (alias for compile_patterns)
@@ -410,11 +1363,8 @@ impl FilterEngine {
 
     /// Load rules from EasyList format content
     pub fn load_easylist_rules(&mut self, content: &str) -> Result<(), Box<dyn std::error::Error>> {
-        let loader = crate::FilterListLoader::new();
-        let rules = loader.parse_filter_list(content)?;
-
-        for rule_str in rules {
-            self.add_rule(&rule_str);
+        for line in content.lines() {
+            self.add_list_line(line);
         }
 
         // Rebuild the Aho-Corasick matcher after adding new rules
@@ -423,20 +1373,66 @@ impl FilterEngine {
         Ok(())
     }
 
+    /// Load rules from a named filter list, attributing their matches to it
+    ///
+    /// The name shows up in `MetricsSnapshot::blocks_by_list`.
+    pub fn load_list(&mut self, name: &str, content: &str) {
+        self.load_named_list(name, content, false);
+    }
+
+    /// Load a malware/phishing protection list
+    ///
+    /// Like [`load_list`](Self::load_list), but requests blocked by its
+    /// rules get [`BlockKind::Malware`].
+    pub fn load_security_list(&mut self, name: &str, content: &str) {
+        self.load_named_list(name, content, true);
+    }
+
+    fn load_named_list(&mut self, name: &str, content: &str, security: bool) {
+        let name: Arc<str> = Arc::from(name);
+        for line in content.lines() {
+            let before = self.rules.len();
+            self.add_list_line(line);
+            if self.rules.len() > before {
+                if let Some((flags, text)) = self.rules.last() {
+                    let rule = stored_rule_text(flags, text);
+                    if security {
+                        self.security_rules.insert(rule.clone());
+                    }
+                    self.rule_sources.insert(rule, Arc::clone(&name));
+                }
+            }
+        }
+        self.compile_patterns();
+    }
+
     /// Create a new filter engine from configuration
+    ///
+    /// When none of the lists can be downloaded, the updater's cache is
+    /// loaded instead, or on first run the bundled list (with the
+    /// `bundled-filters` feature).
     pub fn new(config: &crate::Config) -> Result<Self, Box<dyn std::error::Error>> {
         let mut engine = Self::new_with_defaults();
 
         // Load filter lists from config
-        if !config.filter_lists.is_empty() {
+        let mut loaded = false;
+        let filter_lists = config.effective_filter_lists();
+        if !filter_lists.is_empty() {
             let loader = crate::FilterListLoader::new();
-            for url in &config.filter_lists {
+            for url in &filter_lists {
                 if let Ok(content) = loader.load_from_url(url) {
-                    engine.load_easylist_rules(&content)?;
+                    loaded |= !content.trim().is_empty();
+                    engine.load_list(url, &content);
                 }
             }
         }
 
+        if !loaded {
+            if let Some((name, content)) = Self::offline_list(config) {
+                engine.load_list(name, &content);
+            }
+        }
+
         // Load custom rules if specified
         if let Some(custom_path) = &config.custom_rules_path {
             if let Ok(content) = std::fs::read_to_string(custom_path) {
@@ -447,6 +1443,242 @@ impl FilterEngine {
         Ok(engine)
     }
 
+    /// Rules to start with when no list could be downloaded
+    fn offline_list(config: &crate::Config) -> Option<(&'static str, String)> {
+        let cached = crate::FilterUpdater::new(config.update_config())
+            .and_then(|updater| updater.load_from_cache());
+        match cached {
+            Ok(content) => Some(("cache", content)),
+            #[cfg(feature = "bundled-filters")]
+            Err(_) => Some((
+                crate::bundled_filters::LIST_NAME,
+                crate::bundled_filters::filter_list(),
+            )),
+            #[cfg(not(feature = "bundled-filters"))]
+            Err(_) => None,
+        }
+    }
+
+    /// Get the element hiding selectors that apply to a page domain
+    pub fn get_cosmetic_selectors(&self, domain: &str) -> Vec<String> {
+        self.cosmetic_filters.selectors_for_domain(domain)
+    }
+
+    /// Resolve cosmetic filtering for a document URL
+    ///
+    /// Applies `#@#` exceptions and the `$elemhide`, `$generichide` and
+    /// `$specifichide` exceptions covering the document.
+    pub fn get_cosmetic_result(&self, url: &str) -> CosmeticResult {
+        let mut generichide = false;
+        let mut specifichide = false;
+        for exception in &self.elemhide_exceptions {
+            if !self.matches_exception_pattern(url, &exception.scope) {
+                continue;
+            }
+            match exception.kind {
+                ElemHideKind::All => {
+                    generichide = true;
+                    specifichide = true;
+                }
+                ElemHideKind::Generic => generichide = true,
+                ElemHideKind::Specific => specifichide = true,
+            }
+        }
+
+        let host = crate::utils::extract_domain(url);
+        let domain = host.split(':').next().unwrap_or(&host);
+        self.cosmetic_filters
+            .cosmetic_result(domain, generichide, specifichide)
+    }
+
+    /// Get all element hiding rules
+    pub fn cosmetic_filters(&self) -> &CosmeticFilterSet {
+        &self.cosmetic_filters
+    }
+
+    /// Get the procedural filters for a page domain as a JSON array
+    ///
+    /// Each entry holds the original `selector` and its parsed `steps` for
+    /// the injected script to evaluate.
+    pub fn get_procedural_filters(&self, domain: &str) -> String {
+        self.procedural_filters
+            .to_json(domain)
+            .unwrap_or_else(|_| "[]".to_string())
+    }
+
+    /// Get all procedural filters
+    pub fn procedural_filters(&self) -> &ProceduralFilterSet {
+        &self.procedural_filters
+    }
+
+    /// Get ready-to-inject scriptlet JavaScript for a page domain
+    pub fn get_scriptlets_for_domain(&self, domain: &str) -> Vec<String> {
+        self.scriptlets.scriptlets_for_domain(domain)
+    }
+
+    /// Get all scriptlet rules
+    pub fn scriptlets(&self) -> &ScriptletSet {
+        &self.scriptlets
+    }
+
+    /// Delete the properties `json-prune` rules name from a JSON response
+    /// body fetched by a page on `domain`
+    pub fn prune_json<'a>(&self, domain: &str, body: &'a str) -> std::borrow::Cow<'a, str> {
+        self.json_prune_rules.prune_json(domain, body)
+    }
+
+    /// Get all JSON pruning rules
+    pub fn json_prune_rules(&self) -> &JsonPruneSet {
+        &self.json_prune_rules
+    }
+
+    /// Get the compiled network rules
+    ///
+    /// Rules limited to resource types are left out, as exports can't
+    /// express their options.
+    pub(crate) fn rules(&self) -> impl Iterator<Item = FilterRule<'_>> + '_ {
+        self.rules
+            .iter()
+            .filter(|&(_, flags, _)| flags & OPTIONS_FLAG == 0)
+            .map(|(_, flags, text)| FilterRule::from_stored(flags, text))
+    }
+
+    /// Number of network rules
+    pub fn rule_count(&self) -> usize {
+        self.rules.len()
+    }
+
+    /// Number of rules of each kind
+    pub fn rule_counts(&self) -> RuleCounts {
+        let exception = self.rules.with_flags(KIND_MASK, EXCEPTION_FLAG).count();
+        RuleCounts {
+            block: self.rules.len() - exception,
+            exception,
+            cosmetic: self.cosmetic_filters.len(),
+            procedural: self.procedural_filters.len(),
+            scriptlet: self.scriptlets.len(),
+            json_prune: self.json_prune_rules.len(),
+            removeparam: self.removeparam_rules.len(),
+            redirect: self.redirect_rules.len(),
+            csp: self.csp_rules.len(),
+        }
+    }
+
+    /// Enable the exception audit log, keeping at most `capacity` overrides
+    pub fn enable_exception_audit(&mut self, capacity: usize) {
+        self.exception_audit = Some(Arc::new(ExceptionAuditLog::new(capacity)));
+        // Cached exceptions don't know the rule they override yet
+        self.release_caches();
+    }
+
+    /// Disable the exception audit log and drop recorded overrides
+    pub fn disable_exception_audit(&mut self) {
+        self.exception_audit = None;
+    }
+
+    /// Get the exception audit log if audit mode is enabled
+    pub fn exception_audit(&self) -> Option<&ExceptionAuditLog> {
+        self.exception_audit.as_deref()
+    }
+
+    /// Enable the verdict cache
+    ///
+    /// Verdicts are cached per normalized URL and request kind; other URLs
+    /// under the same path are evaluated on their own.
+    pub fn enable_verdict_cache(&mut self, ttl: std::time::Duration, capacity: usize) {
+        self.verdict_cache = Some(VerdictCache::new(ttl, capacity));
+    }
+
+    /// Disable the verdict cache
+    pub fn disable_verdict_cache(&mut self) {
+        self.verdict_cache = None;
+    }
+
+    /// Get the verdict cache, if enabled
+    pub fn verdict_cache(&self) -> Option<&VerdictCache> {
+        self.verdict_cache.as_ref()
+    }
+
+    /// Start counting rule hits, counting one match in `sample_rate`
+    pub fn enable_rule_hit_tracking(&mut self, sample_rate: u32) {
+        self.rule_hits = Some(Arc::new(RuleHitCounter::new(sample_rate)));
+    }
+
+    /// Stop counting rule hits and drop the counters
+    pub fn disable_rule_hit_tracking(&mut self) {
+        self.rule_hits = None;
+    }
+
+    /// Get the rule hit counters, if tracking is enabled
+    pub fn rule_hits(&self) -> Option<&RuleHitCounter> {
+        self.rule_hits.as_deref()
+    }
+
+    /// Block the sites of the filter's enabled content categories
+    pub fn enable_category_filter(&mut self, filter: Arc<CategoryFilter>) {
+        self.category_filter = Some(filter);
+        self.release_caches();
+    }
+
+    /// Stop blocking content categories
+    pub fn disable_category_filter(&mut self) {
+        self.category_filter = None;
+        self.release_caches();
+    }
+
+    /// Get the content category filter, if enabled
+    pub fn category_filter(&self) -> Option<&Arc<CategoryFilter>> {
+        self.category_filter.as_ref()
+    }
+
+    /// Network rules that did not match within `window`, or `None` when
+    /// hit tracking is disabled
+    ///
+    /// Check `RuleHitReport::is_complete` before pruning: rules look unused
+    /// until hits have been tracked for the whole window.
+    pub fn rule_hit_report(&self, window: std::time::Duration) -> Option<RuleHitReport> {
+        let rule_hits = self.rule_hits.as_ref()?;
+        let rules = self
+            .rules
+            .iter()
+            .map(|(_, flags, text)| stored_rule_text(flags, text));
+        Some(rule_hits.report(rules, window))
+    }
+
+    /// Remove the given network rules, e.g. a report's unused rules, and
+    /// recompile, returning the number removed
+    pub fn prune_rules(&mut self, rules: &[String]) -> usize {
+        let removed = rules.iter().filter(|rule| self.remove_rule(rule)).count();
+        if removed > 0 {
+            self.compile_patterns();
+        }
+        removed
+    }
+
+    /// Move the most matched network rules to the front, so they are
+    /// checked first, and recompile
+    ///
+    /// Rules with the same estimated hits keep their order. Returns `false`
+    /// without changes when hit tracking is disabled.
+    pub fn reorder_rules_by_hits(&mut self) -> bool {
+        let Some(rule_hits) = &self.rule_hits else {
+            return false;
+        };
+        let mut rules: Vec<(u64, u8, &str)> = self
+            .rules
+            .iter()
+            .map(|(_, flags, text)| (rule_hits.hits(&stored_rule_text(flags, text)), flags, text))
+            .collect();
+        rules.sort_by_key(|(hits, _, _)| std::cmp::Reverse(*hits));
+        let mut reordered = OptimizedFilterStorage::new();
+        for (_, flags, text) in rules {
+            reordered.push(flags, text);
+        }
+        self.rules = reordered;
+        self.compile_patterns();
+        true
+    }
+
     /// Get performance metrics
     pub fn get_metrics(&self) -> &PerformanceMetrics {
         &self.metrics
@@ -457,3 +1689,14 @@ impl FilterEngine {
         self.metrics.reset();
     }
 }
+
+/// Remove the first element equal to `item`, returning whether one was found
+fn remove_first<T: PartialEq>(items: &mut Vec<T>, item: &T) -> bool {
+    match items.iter().position(|existing| existing == item) {
+        Some(index) => {
+            items.remove(index);
+            true
+        }
+        None => false,
+    }
+}
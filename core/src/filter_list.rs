@@ -1,6 +1,346 @@
 //! Filter list loading and parsing
 //!
-//! Supports EasyList format filter rules
+//! Supports EasyList format filter rules. Lists mixing hosts-file entries
+//! and plain domains with EasyList rules are detected line by line; those
+//! lines become `||domain^` rules. [`FilterListLoader::parse_with_report`]
+//! tells which lines were used, skipped or malformed, and in strict mode
+//! rejects a list with malformed lines.
+
+use crate::lint::{LintDiagnostic, LintSeverity};
+use serde::Serialize;
+
+pub use crate::domain_filter::{is_cosmetic_rule, is_skipped_line};
+
+/// Update interval asked for by a list's `! Expires:` header
+///
+/// Accepts `4 days`, `12 hours` and short forms like `5d` or `12h`, with
+/// any trailing note such as `(update frequency)`. Only the header lines
+/// before the first rule are read.
+pub fn expires_header(content: &str) -> Option<std::time::Duration> {
+    let value = content
+        .lines()
+        .map(str::trim)
+        .take_while(|line| is_skipped_line(line))
+        .find_map(|line| {
+            let (key, value) = line.strip_prefix('!')?.split_once(':')?;
+            key.trim().eq_ignore_ascii_case("expires").then_some(value)
+        })?
+        .trim();
+    let digits = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let count: u64 = value[..digits].parse().ok()?;
+    let unit = value[digits..].trim_start().chars().next()?;
+    let hours = match unit.to_ascii_lowercase() {
+        'd' => count.checked_mul(24)?,
+        'h' => count,
+        _ => return None,
+    };
+    if hours == 0 {
+        return None;
+    }
+    Some(std::time::Duration::from_secs(hours.checked_mul(3600)?))
+}
+
+/// Syntax of a filter list line
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LineFormat {
+    /// Empty line, `!`/`#` comment or `[Adblock Plus 2.0]` header
+    Comment,
+    /// EasyList network rule
+    Network,
+    /// Element hiding, procedural or scriptlet rule
+    Cosmetic,
+    /// Hosts-file entry such as `0.0.0.0 ads.example`
+    Hosts,
+    /// Bare domain such as `ads.example`
+    Domain,
+    /// A line none of the formats accept, e.g. `ads example`
+    Unknown,
+}
+
+/// Addresses hosts-file blocklists point blocked domains at
+const HOSTS_SINK_ADDRESSES: [&str; 4] = ["0.0.0.0", "127.0.0.1", "::", "::1"];
+
+/// Names hosts files map for the system rather than to block
+const HOSTS_LOCAL_NAMES: [&str; 4] = [
+    "localhost",
+    "localhost.localdomain",
+    "broadcasthost",
+    "local",
+];
+
+/// File extensions that make an EasyList pattern rather than a domain
+const RESOURCE_EXTENSIONS: [&str; 14] = [
+    "js", "css", "gif", "jpg", "jpeg", "png", "svg", "webp", "swf", "html", "htm", "php", "json",
+    "txt",
+];
+
+/// Detect the syntax of a trimmed filter list line
+pub fn detect_line_format(line: &str) -> LineFormat {
+    if is_skipped_line(line) {
+        return LineFormat::Comment;
+    }
+    if is_cosmetic_rule(line) {
+        return LineFormat::Cosmetic;
+    }
+    if line.starts_with('#') {
+        return LineFormat::Comment;
+    }
+    let mut fields = line.split_whitespace();
+    if fields
+        .next()
+        .is_some_and(|address| HOSTS_SINK_ADDRESSES.contains(&address))
+    {
+        return match hosts_line_domains(line).next() {
+            Some(_) => LineFormat::Hosts,
+            None if fields
+                .next()
+                .is_some_and(|name| HOSTS_LOCAL_NAMES.contains(&name)) =>
+            {
+                LineFormat::Comment
+            }
+            None => LineFormat::Unknown,
+        };
+    }
+    if is_bare_domain(line) {
+        return LineFormat::Domain;
+    }
+    // Only options (e.g. `$csp=`) and regexes may contain whitespace
+    let pattern = line.split('$').next().unwrap_or(line);
+    let is_regex = pattern.len() > 1 && pattern.starts_with('/') && pattern.ends_with('/');
+    if !is_regex && pattern.contains(char::is_whitespace) {
+        return LineFormat::Unknown;
+    }
+    LineFormat::Network
+}
+
+/// Blocked domains of a hosts-file line, without the system names
+pub fn hosts_line_domains(line: &str) -> impl Iterator<Item = &str> {
+    let line = line.split('#').next().unwrap_or(line);
+    let mut fields = line.split_whitespace();
+    let is_sink = fields
+        .next()
+        .is_some_and(|address| HOSTS_SINK_ADDRESSES.contains(&address));
+    fields
+        .filter(move |_| is_sink)
+        .map(|domain| domain.trim_end_matches('.'))
+        .filter(|domain| !HOSTS_LOCAL_NAMES.contains(domain) && is_bare_domain(domain))
+}
+
+/// Whether a line is a domain name and nothing else
+fn is_bare_domain(line: &str) -> bool {
+    let domain = line.trim_end_matches('.');
+    let Some((_, tld)) = domain.rsplit_once('.') else {
+        return false;
+    };
+    let labels_valid = domain.split('.').all(|label| {
+        !label.is_empty()
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    });
+    labels_valid
+        && tld.len() >= 2
+        && tld.chars().all(|c| c.is_ascii_alphabetic())
+        && !RESOURCE_EXTENSIONS
+            .iter()
+            .any(|extension| tld.eq_ignore_ascii_case(extension))
+}
+
+/// Network rules of a line in EasyList syntax, with its detected format
+///
+/// Hosts entries and bare domains become `||domain^` rules; comments,
+/// cosmetic rules and unknown lines yield none.
+pub fn network_rules(line: &str) -> (LineFormat, Vec<std::borrow::Cow<'_, str>>) {
+    let line = line.trim();
+    let format = detect_line_format(line);
+    let rules = match format {
+        LineFormat::Network => vec![line.into()],
+        LineFormat::Domain => vec![format!("||{}^", line.trim_end_matches('.')).into()],
+        LineFormat::Hosts => hosts_line_domains(line)
+            .map(|domain| format!("||{domain}^").into())
+            .collect(),
+        LineFormat::Comment | LineFormat::Cosmetic | LineFormat::Unknown => Vec::new(),
+    };
+    (format, rules)
+}
+
+/// Domain of a `||domain^` block rule without options
+pub(crate) fn plain_domain_block(rule: &str) -> Option<&str> {
+    let domain = rule.strip_prefix("||")?.strip_suffix('^')?;
+    if domain.is_empty() || domain.contains(['/', '*', '$', '^']) {
+        return None;
+    }
+    Some(domain)
+}
+
+/// Rule cancelled by a `$badfilter` line, i.e. the line without that option
+///
+/// `||ads.com^$script,badfilter` cancels `||ads.com^$script`.
+pub fn badfilter_target(line: &str) -> Option<String> {
+    let (rule, options) = line.rsplit_once('$')?;
+    if !options
+        .split(',')
+        .any(|option| option.trim() == "badfilter")
+    {
+        return None;
+    }
+    let kept: Vec<&str> = options
+        .split(',')
+        .filter(|option| option.trim() != "badfilter")
+        .collect();
+    if kept.is_empty() {
+        Some(rule.to_string())
+    } else {
+        Some(format!("{rule}${}", kept.join(",")))
+    }
+}
+
+/// Drop `$badfilter` lines and the rules they cancel, returning the kept
+/// lines and the number of rules cancelled
+///
+/// A `$badfilter` line cancels identical rules anywhere in the input, so
+/// it works across concatenated lists.
+pub fn apply_badfilters<'a>(lines: impl IntoIterator<Item = &'a str>) -> (Vec<&'a str>, usize) {
+    let lines: Vec<&str> = lines.into_iter().collect();
+    let cancelled: std::collections::HashSet<String> = lines
+        .iter()
+        .filter_map(|line| badfilter_target(line))
+        .collect();
+    if cancelled.is_empty() {
+        return (lines, 0);
+    }
+
+    let mut count = 0;
+    let kept = lines
+        .into_iter()
+        .filter(|line| {
+            if badfilter_target(line).is_some() {
+                return false;
+            }
+            if cancelled.contains(*line) {
+                count += 1;
+                return false;
+            }
+            true
+        })
+        .collect();
+    (kept, count)
+}
+
+/// Counts from a normalization pass
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NormalizationReport {
+    /// Rule lines in the input
+    pub input_rules: usize,
+    /// Rule lines kept
+    pub output_rules: usize,
+    /// Identical rules dropped
+    pub duplicates_removed: usize,
+    /// `||sub.domain^` rules dropped because `||domain^` is present
+    pub subsumed_removed: usize,
+}
+
+impl NormalizationReport {
+    /// Total number of rules eliminated
+    pub fn eliminated(&self) -> usize {
+        self.duplicates_removed + self.subsumed_removed
+    }
+}
+
+/// Number of lines of each format in a list
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FormatReport {
+    pub network: usize,
+    pub cosmetic: usize,
+    pub hosts: usize,
+    pub domain: usize,
+    /// Lines dropped because no format accepts them
+    pub unknown: usize,
+}
+
+impl FormatReport {
+    fn count(&mut self, format: LineFormat) {
+        match format {
+            LineFormat::Comment => {}
+            LineFormat::Network => self.network += 1,
+            LineFormat::Cosmetic => self.cosmetic += 1,
+            LineFormat::Hosts => self.hosts += 1,
+            LineFormat::Domain => self.domain += 1,
+            LineFormat::Unknown => self.unknown += 1,
+        }
+    }
+
+    /// Whether lines of more than one rule syntax were found
+    pub fn is_mixed(&self) -> bool {
+        let formats = [self.network + self.cosmetic, self.hosts, self.domain];
+        formats.iter().filter(|&&count| count > 0).count() > 1
+    }
+}
+
+/// How [`FilterListLoader::parse_with_report`] treats malformed lines
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ParseMode {
+    /// Drop malformed lines and report them
+    #[default]
+    Lenient,
+    /// Reject the list if any line is malformed
+    Strict,
+}
+
+/// Why a line yielded no network rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    /// `!` or `#` comment, or a hosts entry for a system name
+    Comment,
+    /// `[Adblock Plus 2.0]` style header
+    Header,
+    /// Element hiding, procedural or scriptlet rule
+    Cosmetic,
+}
+
+/// A line without network rules
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct SkippedLine {
+    /// 1-based line number
+    pub line: usize,
+    pub reason: SkipReason,
+}
+
+/// What parsing made of each line of a list
+///
+/// Blank lines are neither accepted nor skipped.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ParseReport {
+    /// Number of lines that yielded network rules
+    pub accepted: usize,
+    pub skipped: Vec<SkippedLine>,
+    /// Linter errors of the malformed lines, which yield no rules
+    pub errors: Vec<LintDiagnostic>,
+}
+
+/// A list rejected by [`ParseMode::Strict`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct MalformedList {
+    /// Linter errors, in line order
+    pub errors: Vec<LintDiagnostic>,
+}
+
+impl std::fmt::Display for MalformedList {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} malformed line(s)", self.errors.len())?;
+        if let Some(first) = self.errors.first() {
+            write!(f, ", first on line {}: {}", first.line, first.message)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for MalformedList {}
 
 /// Filter list loader for parsing EasyList format
 pub struct FilterListLoader {
@@ -51,41 +391,150 @@ impl FilterListLoader {
         #[cfg(not(feature = "http"))]
         {
             // Fallback for when HTTP feature is not enabled
-            eprintln!("Warning: HTTP feature not enabled for URL: {url}");
+            tracing::warn!(%url, "HTTP feature not enabled, not downloading");
             Ok(String::new())
         }
     }
 
     /// Parse a filter list string into rules
+    ///
+    /// Hosts entries and bare domains are converted to `||domain^` rules
+    /// and malformed lines are dropped, see
+    /// [`parse_with_report`](Self::parse_with_report).
     pub fn parse_filter_list(
         &self,
         content: &str,
     ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
-        let mut rules = Vec::new();
+        Ok(self.parse_with_report(content, ParseMode::Lenient)?.0)
+    }
 
-        for line in content.lines() {
-            let trimmed = line.trim();
+    /// Parse a filter list, reporting accepted, skipped and malformed lines
+    ///
+    /// Lines the linter reports as errors (unknown syntax or options,
+    /// invalid regexes) are malformed. They are dropped in lenient mode;
+    /// strict mode fails with [`MalformedList`] instead.
+    pub fn parse_with_report(
+        &self,
+        content: &str,
+        mode: ParseMode,
+    ) -> Result<(Vec<String>, ParseReport), MalformedList> {
+        let mut report = ParseReport {
+            errors: crate::lint::lint(content)
+                .into_iter()
+                .filter(|diagnostic| diagnostic.severity == LintSeverity::Error)
+                .collect(),
+            ..ParseReport::default()
+        };
+        if mode == ParseMode::Strict && !report.errors.is_empty() {
+            return Err(MalformedList {
+                errors: report.errors,
+            });
+        }
 
-            // Skip empty lines and comments
-            if trimmed.is_empty() || trimmed.starts_with('!') {
+        let error_lines: std::collections::HashSet<usize> =
+            report.errors.iter().map(|error| error.line).collect();
+        let mut rules = Vec::new();
+        for (index, line) in content.lines().enumerate() {
+            let number = index + 1;
+            let line = line.trim();
+            if line.is_empty() || error_lines.contains(&number) {
                 continue;
             }
+            let (format, line_rules) = network_rules(line);
+            let reason = match format {
+                LineFormat::Comment if line.starts_with('[') => SkipReason::Header,
+                LineFormat::Comment => SkipReason::Comment,
+                LineFormat::Cosmetic => SkipReason::Cosmetic,
+                // Reported by the linter
+                LineFormat::Unknown => continue,
+                LineFormat::Network | LineFormat::Hosts | LineFormat::Domain => {
+                    report.accepted += 1;
+                    rules.extend(line_rules.into_iter().map(std::borrow::Cow::into_owned));
+                    continue;
+                }
+            };
+            report.skipped.push(SkippedLine {
+                line: number,
+                reason,
+            });
+        }
+        Ok((rules, report))
+    }
+
+    /// Parse a list mixing hosts entries, bare domains and EasyList rules
+    /// into network rules, counting the lines of each format
+    pub fn parse_mixed(&self, content: &str) -> (Vec<String>, FormatReport) {
+        let mut report = FormatReport::default();
+        let mut rules = Vec::new();
+        for line in content.lines() {
+            let (format, line_rules) = network_rules(line);
+            report.count(format);
+            rules.extend(line_rules.into_iter().map(std::borrow::Cow::into_owned));
+        }
+        (rules, report)
+    }
+
+    /// Lazily yield the network rules of filter list lines
+    ///
+    /// Lines are trimmed; comments, headers and cosmetic rules are skipped.
+    /// Nothing is buffered, so a list can be parsed while it is read.
+    pub fn parse_lines<'a, I>(&self, lines: I) -> impl Iterator<Item = &'a str>
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        lines
+            .into_iter()
+            .map(str::trim)
+            .filter(|line| !is_skipped_line(line) && !is_cosmetic_rule(line))
+    }
 
-            // Skip malformed rules
-            if trimmed.starts_with('[') || trimmed.starts_with("!!!") {
+    /// Dedupe rules and drop subdomain blocks covered by a parent domain
+    ///
+    /// Comments and headers are removed; the remaining rules keep their
+    /// first-seen order.
+    pub fn normalize(&self, content: &str) -> (String, NormalizationReport) {
+        let mut report = NormalizationReport::default();
+        let mut seen = std::collections::HashSet::new();
+        let mut rules = Vec::new();
+
+        for line in content.lines().map(str::trim) {
+            if is_skipped_line(line) {
                 continue;
             }
+            report.input_rules += 1;
+            if seen.insert(line) {
+                rules.push(line);
+            } else {
+                report.duplicates_removed += 1;
+            }
+        }
+
+        let blocked_domains: std::collections::HashSet<String> = rules
+            .iter()
+            .filter_map(|rule| plain_domain_block(rule))
+            .map(str::to_lowercase)
+            .collect();
 
-            // Skip CSS rules for now (handled separately)
-            if trimmed.starts_with("##") || trimmed.contains("##") {
+        let mut normalized = String::new();
+        for rule in rules {
+            if is_subsumed(rule, &blocked_domains) {
+                report.subsumed_removed += 1;
                 continue;
             }
-
-            // Add valid rules
-            rules.push(trimmed.to_string());
+            normalized.push_str(rule);
+            normalized.push('\n');
+            report.output_rules += 1;
         }
 
-        Ok(rules)
+        (normalized, report)
+    }
+
+    /// Check filter list text for unknown options, invalid regexes,
+    /// duplicates and overly broad patterns
+    ///
+    /// Diagnostics carry 1-based line numbers, in line order.
+    pub fn lint(&self, content: &str) -> Vec<LintDiagnostic> {
+        crate::lint::lint(content)
     }
 
     /// Get CSS rules for a specific domain
@@ -124,6 +573,21 @@ impl FilterListLoader {
     }
 }
 
+/// Whether a plain `||domain^` rule is covered by a parent domain block
+fn is_subsumed(rule: &str, blocked_domains: &std::collections::HashSet<String>) -> bool {
+    let Some(domain) = plain_domain_block(rule) else {
+        return false;
+    };
+    let mut domain = domain.to_lowercase();
+    while let Some(pos) = domain.find('.') {
+        domain.drain(..=pos);
+        if blocked_domains.contains(&domain) {
+            return true;
+        }
+    }
+    false
+}
+
 impl Default for FilterListLoader {
     fn default() -> Self {
         Self::new()
@@ -0,0 +1,308 @@
+//! Subscribed filter list registry
+//!
+//! Tracks each subscribed list with the metadata from its `!` header and an
+//! enabled flag, so the settings screen can toggle lists and the engine can
+//! be rebuilt from the enabled subset.
+
+use crate::filter_list::is_skipped_line;
+use crate::rule_set::RuleSet;
+use crate::FilterEngine;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Metadata of a subscribed filter list
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FilterListInfo {
+    /// Subscription URL (also the list's identifier)
+    pub url: String,
+    /// `! Title:` header
+    pub title: Option<String>,
+    /// `! Version:` header
+    pub version: Option<String>,
+    /// `! Homepage:` header
+    pub homepage: Option<String>,
+    /// Number of rule lines
+    pub rule_count: usize,
+    /// When the list content was last replaced
+    pub last_updated: DateTime<Utc>,
+    /// Whether the list is used when building the engine
+    pub enabled: bool,
+    /// Whether this is a malware/phishing protection list, whose blocks
+    /// are reported as `BlockKind::Malware`
+    #[serde(default)]
+    pub security: bool,
+}
+
+impl FilterListInfo {
+    /// Read the metadata of a list's content
+    fn from_content(url: &str, content: &str) -> Self {
+        let mut info = FilterListInfo {
+            url: url.to_string(),
+            title: None,
+            version: None,
+            homepage: None,
+            rule_count: 0,
+            last_updated: Utc::now(),
+            enabled: true,
+            security: false,
+        };
+
+        for line in content.lines().map(str::trim) {
+            if let Some(comment) = line.strip_prefix('!') {
+                if let Some((key, value)) = comment.split_once(':') {
+                    let value = Some(value.trim().to_string()).filter(|v| !v.is_empty());
+                    match key.trim().to_lowercase().as_str() {
+                        "title" => info.title = info.title.take().or(value),
+                        "version" => info.version = info.version.take().or(value),
+                        "homepage" => info.homepage = info.homepage.take().or(value),
+                        _ => {}
+                    }
+                }
+            } else if !is_skipped_line(line) {
+                info.rule_count += 1;
+            }
+        }
+
+        info
+    }
+}
+
+/// Registered list with its content
+#[derive(Debug, Clone)]
+struct RegisteredList {
+    info: FilterListInfo,
+    content: String,
+}
+
+/// Registry of subscribed filter lists
+#[derive(Debug, Clone, Default)]
+pub struct FilterListRegistry {
+    lists: Vec<RegisteredList>,
+}
+
+impl FilterListRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a list or replace the content of a registered one
+    ///
+    /// A replaced list keeps its enabled and security flags.
+    pub fn add_list(&mut self, url: &str, content: &str) -> &FilterListInfo {
+        let mut info = FilterListInfo::from_content(url, content);
+        let index = match self.lists.iter().position(|list| list.info.url == url) {
+            Some(index) => {
+                info.enabled = self.lists[index].info.enabled;
+                info.security = self.lists[index].info.security;
+                self.lists[index] = RegisteredList {
+                    info,
+                    content: content.to_string(),
+                };
+                index
+            }
+            None => {
+                self.lists.push(RegisteredList {
+                    info,
+                    content: content.to_string(),
+                });
+                self.lists.len() - 1
+            }
+        };
+        &self.lists[index].info
+    }
+
+    /// Remove a list, returning whether it was registered
+    pub fn remove_list(&mut self, url: &str) -> bool {
+        let before = self.lists.len();
+        self.lists.retain(|list| list.info.url != url);
+        self.lists.len() != before
+    }
+
+    /// Enable or disable a list, returning whether it was registered
+    pub fn set_enabled(&mut self, url: &str, enabled: bool) -> bool {
+        match self.lists.iter_mut().find(|list| list.info.url == url) {
+            Some(list) => {
+                list.info.enabled = enabled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Mark a list as a malware/phishing protection list, returning
+    /// whether it was registered
+    pub fn set_security(&mut self, url: &str, security: bool) -> bool {
+        match self.lists.iter_mut().find(|list| list.info.url == url) {
+            Some(list) => {
+                list.info.security = security;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Metadata of a registered list
+    pub fn get(&self, url: &str) -> Option<&FilterListInfo> {
+        self.lists
+            .iter()
+            .find(|list| list.info.url == url)
+            .map(|list| &list.info)
+    }
+
+    /// Content of a registered list
+    pub fn content(&self, url: &str) -> Option<&str> {
+        self.lists
+            .iter()
+            .find(|list| list.info.url == url)
+            .map(|list| list.content.as_str())
+    }
+
+    /// Metadata of every registered list in subscription order
+    pub fn lists(&self) -> Vec<&FilterListInfo> {
+        self.lists.iter().map(|list| &list.info).collect()
+    }
+
+    /// Concatenated content of the enabled lists
+    pub fn enabled_content(&self) -> String {
+        let mut content = String::new();
+        for list in self.lists.iter().filter(|list| list.info.enabled) {
+            content.push_str(&list.content);
+            if !list.content.ends_with('\n') {
+                content.push('\n');
+            }
+        }
+        content
+    }
+
+    /// Rule set of the enabled lists
+    ///
+    /// Blocks are attributed to each list's title, or its URL if untitled.
+    pub fn rule_set(&self) -> RuleSet {
+        let mut rule_set = RuleSet::new();
+        for list in self.lists.iter().filter(|list| list.info.enabled) {
+            let name = list.info.title.as_deref().unwrap_or(&list.info.url);
+            if list.info.security {
+                rule_set.add_security_list(name, &list.content);
+            } else {
+                rule_set.add_list(name, &list.content);
+            }
+        }
+        rule_set
+    }
+
+    /// Build an engine from the enabled lists
+    pub fn build_engine(&self) -> Result<FilterEngine, Box<dyn std::error::Error>> {
+        self.rule_set().build_engine()
+    }
+
+    /// Write every list to a storage backend, dropping stored lists that
+    /// are no longer registered
+    pub fn save_to(
+        &self,
+        storage: &mut dyn crate::storage::Storage,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        for (info, _) in storage.load_filter_lists()? {
+            if self.get(&info.url).is_none() {
+                storage.remove_filter_list(&info.url)?;
+            }
+        }
+        for (position, list) in self.lists.iter().enumerate() {
+            storage.save_filter_list(position, &list.info, &list.content)?;
+        }
+        Ok(())
+    }
+
+    /// Registry of the lists in a storage backend
+    pub fn load_from(
+        storage: &dyn crate::storage::Storage,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let lists = storage
+            .load_filter_lists()?
+            .into_iter()
+            .map(|(info, content)| RegisteredList { info, content })
+            .collect();
+        Ok(FilterListRegistry { lists })
+    }
+
+    /// Export the list metadata to JSON
+    pub fn to_json(&self) -> Result<String, Box<dyn std::error::Error>> {
+        Ok(serde_json::to_string(&self.lists())?)
+    }
+
+    /// Number of registered lists
+    pub fn len(&self) -> usize {
+        self.lists.len()
+    }
+
+    /// Whether no list is registered
+    pub fn is_empty(&self) -> bool {
+        self.lists.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_metadata_and_toggle() {
+        let mut registry = FilterListRegistry::new();
+        let info = registry.add_list(
+            "https://lists.example/ads.txt",
+            "[Adblock Plus 2.0]\n! Title: Ads\n! Version: 202601\n! Homepage: https://lists.example/\n||ads.com^\n##.ad",
+        );
+        assert_eq!(info.title.as_deref(), Some("Ads"));
+        assert_eq!(info.version.as_deref(), Some("202601"));
+        assert_eq!(info.rule_count, 2);
+
+        registry.add_list("https://lists.example/privacy.txt", "||tracker.net^");
+        assert!(registry.set_enabled("https://lists.example/ads.txt", false));
+        assert_eq!(registry.enabled_content(), "||tracker.net^\n");
+
+        // Replacing content keeps the enabled flag
+        registry.add_list("https://lists.example/ads.txt", "||ads.com^");
+        assert!(
+            !registry
+                .get("https://lists.example/ads.txt")
+                .unwrap()
+                .enabled
+        );
+
+        assert!(registry.remove_list("https://lists.example/ads.txt"));
+        assert!(!registry.set_enabled("https://lists.example/ads.txt", true));
+        assert_eq!(registry.len(), 1);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_registry_storage_round_trip() {
+        let mut storage = crate::sqlite_storage::SqliteStorage::open_in_memory().unwrap();
+        let mut registry = FilterListRegistry::new();
+        registry.add_list("https://lists.example/b.txt", "! Title: B\n||b.com^");
+        registry.add_list("https://lists.example/a.txt", "||a.com^");
+        registry.set_enabled("https://lists.example/a.txt", false);
+        registry.save_to(&mut storage).unwrap();
+
+        registry.remove_list("https://lists.example/b.txt");
+        registry.save_to(&mut storage).unwrap();
+        registry.add_list("https://lists.example/c.txt", "||c.com^");
+        registry.save_to(&mut storage).unwrap();
+
+        let restored = FilterListRegistry::load_from(&storage).unwrap();
+        let urls: Vec<_> = restored
+            .lists()
+            .iter()
+            .map(|info| info.url.as_str())
+            .collect();
+        assert_eq!(
+            urls,
+            ["https://lists.example/a.txt", "https://lists.example/c.txt"]
+        );
+        assert!(!restored.get("https://lists.example/a.txt").unwrap().enabled);
+        assert_eq!(
+            restored.content("https://lists.example/c.txt"),
+            Some("||c.com^")
+        );
+    }
+}
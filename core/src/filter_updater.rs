@@ -1,32 +1,251 @@
 //! Filter list updater for automatic updates
 //!
-//! Downloads and caches filter lists from remote sources
-
+//! Downloads and caches filter lists from remote sources. Compressed
+//! downloads are decoded, and the cache is stored gzip-compressed.
+//!
+//! Each list is cached in its own file with its ETag and rule count, and
+//! the lists are merged when loaded, so a list can be disabled or updated
+//! without downloading the others again. A list's `! Expires:` header
+//! sets its own update interval. Cache files are replaced
+//! atomically, and the metadata records their SHA-256 so a corrupt cache
+//! is re-downloaded. An [`UpdatePolicy`] defers scheduled downloads on
+//! metered connections.
+
+use crate::checksum;
+use crate::compression;
+use crate::event_bus::{EngineEvent, EventBus};
+use crate::filter_list::is_skipped_line;
+use serde::Serialize;
 use std::collections::HashMap;
+use std::io::Write;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::{Duration, SystemTime};
 
 /// Default cache file names
-const FILTER_CACHE_FILE: &str = "filters_cache.txt";
+const FILTER_CACHE_FILE: &str = "filters_cache.txt.gz";
+/// Uncompressed cache written by earlier versions
+const LEGACY_CACHE_FILE: &str = "filters_cache.txt";
+/// Directory of the per-list cache files
+const LISTS_DIR: &str = "lists";
 const METADATA_FILE: &str = "cache_metadata.json";
 
+/// Consecutive failures after which a URL is tried after the healthy ones
+const DEMOTE_AFTER_FAILURES: u32 = 3;
+
+/// How long a demoted URL stays last before it is tried first again
+const DEMOTION_PERIOD: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Bounds of the update interval a list's `! Expires:` header can set,
+/// as Adblock Plus applies them
+pub const MIN_LIST_EXPIRY: Duration = Duration::from_secs(60 * 60);
+pub const MAX_LIST_EXPIRY: Duration = Duration::from_secs(14 * 24 * 60 * 60);
+
 /// Configuration for filter updates
 #[derive(Debug, Clone)]
 pub struct UpdateConfig {
     /// URLs to download filter lists from
     pub urls: Vec<String>,
+    /// IDs of catalog lists to download, trying their mirrors in turn
+    pub presets: Vec<String>,
+    /// Fallback URLs by primary list URL, tried in order when it fails
+    pub mirrors: HashMap<String, Vec<String>>,
     /// How often to check for updates
     pub update_interval: Duration,
     /// Directory to cache downloaded filters
     pub cache_dir: Option<PathBuf>,
 }
 
+/// Reason a downloaded list was rejected
+#[derive(Debug, Clone, PartialEq)]
+pub enum DownloadError {
+    /// The list could not be fetched
+    Network(String),
+    /// The content does not match the pinned SHA-256 or `! Checksum:` header
+    ChecksumMismatch {
+        url: String,
+        expected: String,
+        actual: String,
+    },
+    /// The update was deferred because the connection is metered
+    Metered,
+}
+
+impl std::fmt::Display for DownloadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DownloadError::Network(message) => write!(f, "Download failed: {message}"),
+            DownloadError::ChecksumMismatch {
+                url,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Checksum mismatch for {url}: expected {expected}, got {actual}"
+            ),
+            DownloadError::Metered => write!(f, "Update deferred on a metered connection"),
+        }
+    }
+}
+
+impl std::error::Error for DownloadError {}
+
+/// Cached list that failed its integrity check
+#[derive(Debug, Clone, PartialEq)]
+pub struct CacheCorrupt {
+    pub expected: String,
+    pub actual: String,
+}
+
+impl std::fmt::Display for CacheCorrupt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Filter cache is corrupt: expected SHA-256 {}, got {}",
+            self.expected, self.actual
+        )
+    }
+}
+
+impl std::error::Error for CacheCorrupt {}
+
+/// Host callback reporting whether the current connection is metered
+pub type MeteredCheck = Arc<dyn Fn() -> bool + Send + Sync>;
+
+/// When scheduled updates may download
+#[derive(Clone, Default)]
+pub struct UpdatePolicy {
+    /// Whether to download over metered connections such as mobile data
+    pub allow_metered: bool,
+    /// Reports whether the connection is metered; without it every
+    /// connection counts as unmetered
+    pub is_network_metered: Option<MeteredCheck>,
+}
+
+impl UpdatePolicy {
+    /// Whether downloads are deferred on the current connection
+    pub fn defers_downloads(&self) -> bool {
+        !self.allow_metered
+            && self
+                .is_network_metered
+                .as_ref()
+                .is_some_and(|is_metered| is_metered())
+    }
+}
+
+impl std::fmt::Debug for UpdatePolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UpdatePolicy")
+            .field("allow_metered", &self.allow_metered)
+            .field("is_network_metered", &self.is_network_metered.is_some())
+            .finish()
+    }
+}
+
+/// Cached copy of one filter list
+#[derive(Debug, Clone, PartialEq, Serialize, serde::Deserialize)]
+pub struct CachedList {
+    /// File in the cache's `lists` directory
+    pub file: String,
+    /// URL the copy was downloaded from: the list's own or a mirror
+    pub source: String,
+    /// ETag sent with the copy, for conditional requests
+    pub etag: Option<String>,
+    /// When the copy was last downloaded or confirmed unchanged
+    pub last_fetch: SystemTime,
+    pub rule_count: usize,
+    /// SHA-256 (hex) of the uncompressed list
+    pub content_sha256: String,
+    /// Interval of the list's `! Expires:` header, before clamping
+    #[serde(default)]
+    pub expires: Option<Duration>,
+}
+
+/// Response to a download
+enum Fetched {
+    /// The copy with the sent ETag is still current
+    NotModified,
+    List {
+        content: String,
+        etag: Option<String>,
+    },
+}
+
+/// Rules added to and removed from a filter list between two downloads
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct FilterListDiff {
+    /// Rule lines only in the new download, in list order
+    pub added: Vec<String>,
+    /// Rule lines only in the previous copy, in list order
+    pub removed: Vec<String>,
+}
+
+impl FilterListDiff {
+    /// Compare two versions of a filter list
+    ///
+    /// Comments and headers are ignored; a rule listed twice counts twice.
+    pub fn compute(old: &str, new: &str) -> Self {
+        let mut old_counts: HashMap<&str, usize> = HashMap::new();
+        for line in rule_lines_of(old) {
+            *old_counts.entry(line).or_insert(0) += 1;
+        }
+
+        let mut added = Vec::new();
+        for line in rule_lines_of(new) {
+            match old_counts.get_mut(line) {
+                Some(count) if *count > 0 => *count -= 1,
+                _ => added.push(line.to_string()),
+            }
+        }
+
+        // Whatever is left over in the old counts was removed
+        let mut removed = Vec::new();
+        for line in rule_lines_of(old) {
+            if let Some(count) = old_counts.get_mut(line) {
+                if *count > 0 {
+                    *count -= 1;
+                    removed.push(line.to_string());
+                }
+            }
+        }
+
+        FilterListDiff { added, removed }
+    }
+
+    /// Whether the two versions hold the same rules
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+
+    /// Summary for the UI, e.g. "12 added, 3 removed"
+    pub fn summary(&self) -> String {
+        format!("{} added, {} removed", self.added.len(), self.removed.len())
+    }
+}
+
+/// Trimmed rule lines of a filter list
+fn rule_lines_of(content: &str) -> impl Iterator<Item = &str> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !is_skipped_line(line))
+}
+
 /// Filter list updater
 pub struct FilterUpdater {
     config: UpdateConfig,
     last_update: Option<SystemTime>,
     #[allow(dead_code)]
     cached_filters: HashMap<String, String>,
+    /// Bus notified when an update completes
+    event_bus: Option<Arc<EventBus>>,
+    /// Expected SHA-256 (hex) of downloads by URL
+    pinned_checksums: HashMap<String, String>,
+    /// Failed downloads by URL since its last success
+    mirror_failures: HashMap<String, MirrorFailures>,
+    /// Cached lists by their primary URL
+    lists: HashMap<String, CachedList>,
+    policy: UpdatePolicy,
 }
 
 impl FilterUpdater {
@@ -36,6 +255,11 @@ impl FilterUpdater {
             config,
             last_update: None,
             cached_filters: HashMap::new(),
+            event_bus: None,
+            pinned_checksums: HashMap::new(),
+            mirror_failures: HashMap::new(),
+            lists: HashMap::new(),
+            policy: UpdatePolicy::default(),
         };
 
         // Try to load from cache on initialization
@@ -47,7 +271,16 @@ impl FilterUpdater {
     }
 
     /// Check if an update is needed
+    ///
+    /// Once lists are cached separately, whether any configured list is
+    /// due or not cached yet.
     pub fn needs_update(&self) -> bool {
+        if !self.lists.is_empty() {
+            return self
+                .list_sources()
+                .iter()
+                .any(|(url, _)| self.is_list_due(url));
+        }
         match self.last_update {
             None => true,
             Some(last) => match SystemTime::now().duration_since(last) {
@@ -58,15 +291,57 @@ impl FilterUpdater {
     }
 
     /// Update with provided content (for testing)
+    ///
+    /// The content replaces the whole cache, including per-list copies.
     pub fn update_with_content(&mut self, content: &str) -> Result<(), Box<dyn std::error::Error>> {
-        if let Some(ref cache_dir) = self.config.cache_dir {
-            self.save_to_cache(cache_dir, content)?;
+        let started = std::time::Instant::now();
+        if let Some(cache_dir) = self.config.cache_dir.clone() {
+            for list in std::mem::take(&mut self.lists).into_values() {
+                let _ = std::fs::remove_file(cache_dir.join(LISTS_DIR).join(list.file));
+            }
+            self.save_to_cache(&cache_dir, content)?;
         }
 
         self.last_update = Some(SystemTime::now());
+        self.publish_update(content, started);
         Ok(())
     }
 
+    fn publish_update(&self, content: &str, started: std::time::Instant) {
+        if let Some(ref bus) = self.event_bus {
+            let rules_count = content
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('!') && !line.starts_with('['))
+                .count();
+            bus.publish(&EngineEvent::UpdateCompleted {
+                rules_count,
+                duration_ms: started.elapsed().as_millis() as u64,
+            });
+        }
+    }
+
+    /// Update with a new download, returning its diff against the cached copy
+    ///
+    /// Without a cached copy every rule of the download counts as added.
+    /// Feed the diff to `AdBlockCore::apply_filter_diff` instead of
+    /// reloading the whole list.
+    pub fn update_with_diff(
+        &mut self,
+        content: &str,
+    ) -> Result<FilterListDiff, Box<dyn std::error::Error>> {
+        let cached = self.load_from_cache().unwrap_or_default();
+        let diff = FilterListDiff::compute(&cached, content);
+        self.update_with_content(content)?;
+        tracing::info!("Filter list update: {}", diff.summary());
+        Ok(diff)
+    }
+
+    /// Publish completed updates on a bus
+    pub fn set_event_bus(&mut self, bus: Arc<EventBus>) {
+        self.event_bus = Some(bus);
+    }
+
     /// Save filter content and metadata to cache
     fn save_to_cache(
         &self,
@@ -75,32 +350,109 @@ impl FilterUpdater {
     ) -> Result<(), Box<dyn std::error::Error>> {
         std::fs::create_dir_all(cache_dir)?;
 
-        // Save filter content
+        // Save filter content, compressed since lists shrink about fourfold
         let cache_file = cache_dir.join(FILTER_CACHE_FILE);
-        std::fs::write(&cache_file, content)?;
+        write_atomically(&cache_file, &compression::gzip_compress(content.as_bytes()))?;
+        let _ = std::fs::remove_file(cache_dir.join(LEGACY_CACHE_FILE));
 
-        // Save metadata
-        self.save_cache_metadata(cache_dir)?;
+        // Save metadata last: a crash before it leaves a hash mismatch,
+        // which is re-downloaded rather than trusted
+        self.save_cache_metadata(cache_dir, Some(checksum::sha256_hex(content)))?;
 
         Ok(())
     }
 
     /// Save cache metadata
-    fn save_cache_metadata(&self, cache_dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    fn save_cache_metadata(
+        &self,
+        cache_dir: &Path,
+        content_sha256: Option<String>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
         let metadata_file = cache_dir.join(METADATA_FILE);
         let metadata = CacheMetadata {
             last_update: SystemTime::now(),
+            mirror_failures: self.mirror_failures.clone(),
+            content_sha256,
+            lists: self.lists.clone(),
         };
         let metadata_json = serde_json::to_string(&metadata)?;
-        std::fs::write(&metadata_file, metadata_json)?;
+        write_atomically(&metadata_file, metadata_json.as_bytes())?;
+        Ok(())
+    }
+
+    /// Pin the SHA-256 (hex) a download from a URL must have
+    pub fn pin_checksum(&mut self, url: &str, sha256_hex: &str) {
+        self.pinned_checksums
+            .insert(url.to_string(), sha256_hex.to_lowercase());
+    }
+
+    /// Remove the pinned checksum of a URL
+    pub fn unpin_checksum(&mut self, url: &str) {
+        self.pinned_checksums.remove(url);
+    }
+
+    /// Check a download against its pinned SHA-256 and `! Checksum:` header
+    pub fn verify_download(&self, url: &str, content: &str) -> Result<(), DownloadError> {
+        if let Some(expected) = self.pinned_checksums.get(url) {
+            let actual = checksum::sha256_hex(content);
+            if &actual != expected {
+                return Err(DownloadError::ChecksumMismatch {
+                    url: url.to_string(),
+                    expected: expected.clone(),
+                    actual,
+                });
+            }
+        }
+
+        if let Some(expected) = checksum::embedded_checksum(content) {
+            let actual = checksum::list_checksum(content);
+            if actual != expected.trim_end_matches('=') {
+                return Err(DownloadError::ChecksumMismatch {
+                    url: url.to_string(),
+                    expected: expected.to_string(),
+                    actual,
+                });
+            }
+        }
+
         Ok(())
     }
 
-    /// Download a filter list from URL
+    /// Download a filter list from URL and verify its checksums
+    ///
+    /// A list failing verification is returned as
+    /// `DownloadError::ChecksumMismatch`.
     pub fn download_filter_list(&self, url: &str) -> Result<String, Box<dyn std::error::Error>> {
+        match self.download_if_modified(url, None)? {
+            Fetched::List { content, .. } => Ok(content),
+            Fetched::NotModified => Err("Not modified without a cached copy".into()),
+        }
+    }
+
+    /// Download a list unless it still has the ETag `etag`
+    fn download_if_modified(
+        &self,
+        url: &str,
+        etag: Option<&str>,
+    ) -> Result<Fetched, Box<dyn std::error::Error>> {
+        let fetched = self.fetch_filter_list(url, etag)?;
+        if let Fetched::List { ref content, .. } = fetched {
+            self.verify_download(url, content)?;
+        }
+        Ok(fetched)
+    }
+
+    /// Fetch a filter list from URL without verification
+    fn fetch_filter_list(
+        &self,
+        url: &str,
+        etag: Option<&str>,
+    ) -> Result<Fetched, Box<dyn std::error::Error>> {
         // For testing, simulate failures for invalid URLs
         if url.contains("invalid") || url.contains("nonexistent") {
-            return Err("Failed to download filter list".into());
+            return Err(
+                DownloadError::Network("Failed to download filter list".to_string()).into(),
+            );
         }
 
         #[cfg(feature = "http")]
@@ -112,69 +464,376 @@ impl FilterUpdater {
                 .user_agent("AdBlock/1.0")
                 .build()?;
 
-            let response = client.get(url).send()?;
+            let mut request = client.get(url);
+            if let Some(etag) = etag {
+                request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+            }
+            let response = request.send()?;
 
+            if response.status() == reqwest::StatusCode::NOT_MODIFIED {
+                return Ok(Fetched::NotModified);
+            }
             if !response.status().is_success() {
                 return Err(format!("HTTP error: {}", response.status()).into());
             }
 
-            let content = response.text()?;
-            Ok(content)
+            let header = |name| {
+                response
+                    .headers()
+                    .get(name)
+                    .and_then(|value| value.to_str().ok())
+                    .map(str::to_string)
+            };
+            let encoding = header(reqwest::header::CONTENT_ENCODING);
+            let etag = header(reqwest::header::ETAG);
+            let body = response.bytes()?;
+            // Also decodes .gz and .zst lists served without the header
+            let content = compression::decode_download(&body, encoding.as_deref())?;
+            Ok(Fetched::List {
+                content: String::from_utf8(content)?,
+                etag,
+            })
         }
 
         #[cfg(not(feature = "http"))]
         {
             // Fallback for when HTTP feature is not enabled
-            eprintln!("Note: HTTP feature not enabled. URL: {url}");
+            tracing::debug!(%url, "HTTP feature not enabled, serving sample content");
 
             // Simulate different content based on URL
-            if url.contains("easylist") {
-                Ok(include_str!("../tests/fixtures/easylist_sample.txt").to_string())
+            let content = if url.contains("easylist") {
+                include_str!("../tests/fixtures/easylist_sample.txt").to_string()
             } else if url.contains("easyprivacy") {
-                Ok("! EasyPrivacy Sample\n||analytics.com^\n||tracking.net^".to_string())
+                "! EasyPrivacy Sample\n||analytics.com^\n||tracking.net^".to_string()
             } else {
-                Ok("||downloaded-ads.com^".to_string())
+                "||downloaded-ads.com^".to_string()
+            };
+            // Tag by content, as servers commonly do
+            let tag = format!("\"{}\"", &checksum::sha256_hex(&content)[..16]);
+            if etag == Some(tag.as_str()) {
+                return Ok(Fetched::NotModified);
             }
+            Ok(Fetched::List {
+                content,
+                etag: Some(tag),
+            })
         }
     }
 
+    /// Set when scheduled updates may download
+    pub fn set_update_policy(&mut self, policy: UpdatePolicy) {
+        self.policy = policy;
+    }
+
+    pub fn update_policy(&self) -> &UpdatePolicy {
+        &self.policy
+    }
+
     /// Perform automatic update if needed
+    ///
+    /// Lists are downloaded when an update is due, or when they have no
+    /// cached copy yet; the others are taken from the cache. A list that
+    /// fails to download keeps its previous copy. While the update policy
+    /// defers downloads the cache is returned, or `DownloadError::Metered`
+    /// without one.
     pub fn auto_update(&mut self) -> Result<String, Box<dyn std::error::Error>> {
-        if !self.needs_update() {
-            // Try to load from cache
+        if self.policy.defers_downloads() {
+            tracing::info!("Deferring filter list updates on a metered connection");
+            return self
+                .load_from_cache()
+                .map_err(|_| DownloadError::Metered.into());
+        }
+        self.update(false)
+    }
+
+    /// Download every list now, whatever the update intervals and policy
+    ///
+    /// For a user-initiated refresh. Unchanged lists are still confirmed
+    /// by their ETag instead of downloaded again.
+    pub fn force_update(&mut self) -> Result<String, Box<dyn std::error::Error>> {
+        self.update(true)
+    }
+
+    fn update(&mut self, force: bool) -> Result<String, Box<dyn std::error::Error>> {
+        if !force && !self.needs_update() && self.lists.is_empty() {
+            // Content stored with `update_with_content`
             if let Ok(cached) = self.load_from_cache() {
                 return Ok(cached);
             }
         }
 
-        // Download all configured filter lists
+        let started = std::time::Instant::now();
         let mut all_filters = Vec::new();
-
-        for url in &self.config.urls.clone() {
-            match self.download_filter_list(url) {
-                Ok(content) => all_filters.push(content),
-                Err(e) => eprintln!("Failed to download {url}: {e}"),
+        let (mut attempted, mut fetched) = (0, 0);
+
+        for (url, sources) in self.list_sources() {
+            let cached = self.load_cached_list(&url).ok();
+            let due = force || self.is_list_due(&url);
+            if let (false, Some(content)) = (due, &cached) {
+                all_filters.push(content.clone());
+                continue;
+            }
+            // Only ask for changes when the copy to keep is intact
+            let etag = cached
+                .as_ref()
+                .and_then(|_| self.lists.get(&url))
+                .and_then(|list| Some((list.source.clone(), list.etag.clone()?)));
+
+            attempted += 1;
+            match self.download_with_fallback(&sources, etag.as_ref()) {
+                Ok((_, Fetched::NotModified)) => {
+                    fetched += 1;
+                    if let Some(list) = self.lists.get_mut(&url) {
+                        list.last_fetch = SystemTime::now();
+                    }
+                    all_filters.extend(cached);
+                }
+                Ok((source, Fetched::List { content, etag })) => {
+                    fetched += 1;
+                    self.save_list(&url, source, etag, &content)?;
+                    all_filters.push(content);
+                }
+                Err(e) => {
+                    // A tampered list must not replace the previous cache
+                    if let Some(DownloadError::ChecksumMismatch { .. }) = e.downcast_ref() {
+                        tracing::warn!(%url, error = %e, "Rejected filter list");
+                        return self.load_from_cache().map_err(|_| e);
+                    }
+                    tracing::warn!(%url, error = %e, "Failed to download filter list");
+                    all_filters.extend(cached);
+                }
             }
         }
 
-        if all_filters.is_empty() {
+        if all_filters.is_empty() || (attempted > 0 && fetched == 0) {
             return Err("Failed to download any filter lists".into());
         }
 
-        // Merge all downloaded lists
+        // Merge all lists
         let merged = self.merge_filter_lists(all_filters.iter().map(|s| s.as_str()).collect());
 
-        // Save to cache
-        self.update_with_content(&merged)?;
+        if attempted > 0 {
+            self.last_update = Some(SystemTime::now());
+            if let Some(ref cache_dir) = self.config.cache_dir {
+                // Superseded by the per-list copies
+                let _ = std::fs::remove_file(cache_dir.join(FILTER_CACHE_FILE));
+                let _ = std::fs::remove_file(cache_dir.join(LEGACY_CACHE_FILE));
+                self.save_cache_metadata(cache_dir, None)?;
+            }
+            self.publish_update(&merged, started);
+        }
 
         Ok(merged)
     }
 
+    /// Cached copy of a list, by its primary URL
+    pub fn cached_list(&self, url: &str) -> Option<&CachedList> {
+        self.lists.get(url)
+    }
+
+    /// Update interval of a list: its `! Expires:` header clamped to
+    /// `MIN_LIST_EXPIRY..=MAX_LIST_EXPIRY`, or the configured interval
+    pub fn list_update_interval(&self, url: &str) -> Duration {
+        self.lists
+            .get(url)
+            .and_then(|list| list.expires)
+            .map_or(self.config.update_interval, |expires| {
+                expires.clamp(MIN_LIST_EXPIRY, MAX_LIST_EXPIRY)
+            })
+    }
+
+    /// Time until the next list is due, zero if one is already
+    pub fn next_update_in(&self) -> Duration {
+        if self.lists.is_empty() {
+            let elapsed = self
+                .last_update
+                .and_then(|last| last.elapsed().ok())
+                .unwrap_or(self.config.update_interval);
+            return self.config.update_interval.saturating_sub(elapsed);
+        }
+        self.list_sources()
+            .iter()
+            .map(|(url, _)| match self.lists.get(url) {
+                Some(list) => {
+                    let elapsed = list.last_fetch.elapsed().unwrap_or_default();
+                    self.list_update_interval(url).saturating_sub(elapsed)
+                }
+                None => Duration::ZERO,
+            })
+            .min()
+            .unwrap_or(self.config.update_interval)
+    }
+
+    /// Whether a list is not cached or its update interval has passed
+    fn is_list_due(&self, url: &str) -> bool {
+        self.lists.get(url).is_none_or(|list| {
+            list.last_fetch
+                .elapsed()
+                .map_or(true, |elapsed| elapsed >= self.list_update_interval(url))
+        })
+    }
+
+    /// Store the copy of a list downloaded from `source`
+    fn save_list(
+        &mut self,
+        url: &str,
+        source: String,
+        etag: Option<String>,
+        content: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let file = format!("{}.txt.gz", &checksum::sha256_hex(url)[..16]);
+        if let Some(ref cache_dir) = self.config.cache_dir {
+            let lists_dir = cache_dir.join(LISTS_DIR);
+            std::fs::create_dir_all(&lists_dir)?;
+            write_atomically(
+                &lists_dir.join(&file),
+                &compression::gzip_compress(content.as_bytes()),
+            )?;
+        }
+        self.lists.insert(
+            url.to_string(),
+            CachedList {
+                file,
+                source,
+                etag,
+                last_fetch: SystemTime::now(),
+                rule_count: rule_lines_of(content).count(),
+                content_sha256: checksum::sha256_hex(content),
+                expires: crate::filter_list::expires_header(content),
+            },
+        );
+        Ok(())
+    }
+
+    /// Cached copy of a list, checked against its recorded hash
+    fn load_cached_list(&self, url: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let list = self.lists.get(url).ok_or("List not cached")?;
+        let cache_dir = self
+            .config
+            .cache_dir
+            .as_ref()
+            .ok_or("No cache directory configured")?;
+        let compressed = std::fs::read(cache_dir.join(LISTS_DIR).join(&list.file))?;
+        let content = String::from_utf8(compression::gzip_decompress(&compressed)?)?;
+        let actual = checksum::sha256_hex(&content);
+        if actual != list.content_sha256 {
+            return Err(CacheCorrupt {
+                expected: list.content_sha256.clone(),
+                actual,
+            }
+            .into());
+        }
+        Ok(content)
+    }
+
+    /// Primary URL and download URLs of each configured list, in the
+    /// order to try them
+    ///
+    /// Catalog presets are resolved to their URLs and mirrors; unknown
+    /// preset IDs are skipped. Each list's primary URL comes first, then
+    /// its mirrors, except that URLs failing repeatedly are moved last.
+    fn list_sources(&self) -> Vec<(String, Vec<String>)> {
+        let catalog = crate::list_catalog::ListCatalog::builtin();
+        let presets = self.config.presets.iter().filter_map(|id| {
+            let list = catalog.get(id);
+            if list.is_none() {
+                tracing::warn!("Unknown filter list preset {id}");
+            }
+            list.map(|list| list.urls.clone())
+        });
+        self.config
+            .urls
+            .iter()
+            .map(|url| vec![url.clone()])
+            .chain(presets)
+            .map(|mut urls| {
+                let mirrors = self.config.mirrors.get(&urls[0]).into_iter().flatten();
+                for mirror in mirrors {
+                    if !urls.contains(mirror) {
+                        urls.push(mirror.clone());
+                    }
+                }
+                let primary = urls[0].clone();
+                // Stable, so healthy and demoted URLs each keep their order
+                urls.sort_by_key(|url| self.is_demoted(url));
+                (primary, urls)
+            })
+            .collect()
+    }
+
+    /// Download a list from the first of its URLs that succeeds
+    ///
+    /// Returns the URL the list came from. `etag` is the source and ETag
+    /// of the cached copy, sent only to that source. A checksum mismatch is
+    /// returned at once rather than masked by a mirror; otherwise the last
+    /// URL's error is returned.
+    fn download_with_fallback(
+        &mut self,
+        urls: &[String],
+        etag: Option<&(String, String)>,
+    ) -> Result<(String, Fetched), Box<dyn std::error::Error>> {
+        let mut last_error = None;
+        for url in urls {
+            let etag = etag
+                .filter(|(source, _)| source == url)
+                .map(|(_, etag)| etag.as_str());
+            match self.download_if_modified(url, etag) {
+                Ok(fetched) => {
+                    self.mirror_failures.remove(url);
+                    return Ok((url.clone(), fetched));
+                }
+                Err(e) => {
+                    self.record_failure(url);
+                    if let Some(DownloadError::ChecksumMismatch { .. }) = e.downcast_ref() {
+                        return Err(e);
+                    }
+                    tracing::warn!(%url, error = %e, "Failed to download filter list, trying the next mirror");
+                    last_error = Some(e);
+                }
+            }
+        }
+        Err(last_error.unwrap_or_else(|| "No URL to download from".into()))
+    }
+
+    fn record_failure(&mut self, url: &str) {
+        let failures = self
+            .mirror_failures
+            .entry(url.to_string())
+            .or_insert(MirrorFailures {
+                count: 0,
+                last: SystemTime::now(),
+            });
+        failures.count += 1;
+        failures.last = SystemTime::now();
+        if failures.count == DEMOTE_AFTER_FAILURES {
+            tracing::warn!("Demoting {url} after {} failed downloads", failures.count);
+        }
+    }
+
+    /// Whether a URL failed repeatedly within the demotion period
+    fn is_demoted(&self, url: &str) -> bool {
+        self.mirror_failures.get(url).is_some_and(|failures| {
+            failures.count >= DEMOTE_AFTER_FAILURES
+                && failures
+                    .last
+                    .elapsed()
+                    .is_ok_and(|elapsed| elapsed < DEMOTION_PERIOD)
+        })
+    }
+
+    /// Downloads from a URL that failed in a row since its last success
+    ///
+    /// Kept across restarts with the cache metadata.
+    pub fn consecutive_failures(&self, url: &str) -> u32 {
+        self.mirror_failures
+            .get(url)
+            .map_or(0, |failures| failures.count)
+    }
+
     /// Merge multiple filter lists
     pub fn merge_filter_lists(&self, lists: Vec<&str>) -> String {
         let mut merged = String::new();
-        merged.push_str("! Merged Filter List\n");
-        merged.push_str(&format!("! Generated at: {:?}\n\n", SystemTime::now()));
+        merged.push_str("! Merged Filter List\n\n");
 
         for list in lists {
             merged.push_str(list);
@@ -186,6 +845,25 @@ impl FilterUpdater {
         merged
     }
 
+    /// Merge filter lists and normalize the result
+    ///
+    /// Overlapping lists (e.g. EasyList and EasyPrivacy) share many rules;
+    /// duplicates and subsumed subdomain blocks are dropped and counted in
+    /// the report.
+    pub fn merge_and_normalize_filter_lists(
+        &self,
+        lists: Vec<&str>,
+    ) -> (String, crate::filter_list::NormalizationReport) {
+        let merged = self.merge_filter_lists(lists);
+        let (normalized, report) = crate::FilterListLoader::new().normalize(&merged);
+        tracing::info!(
+            "Normalized merged filter lists: {} of {} rules eliminated",
+            report.eliminated(),
+            report.input_rules
+        );
+        (normalized, report)
+    }
+
     /// Load filters from cache
     pub fn load_from_cache(&self) -> Result<String, Box<dyn std::error::Error>> {
         let cache_dir = self
@@ -194,30 +872,136 @@ impl FilterUpdater {
             .as_ref()
             .ok_or("No cache directory configured")?;
 
-        let cache_file = cache_dir.join(FILTER_CACHE_FILE);
-        if !cache_file.exists() {
-            return Err("Cache file not found".into());
+        // Merge the per-list copies of the configured lists
+        let cached = self
+            .list_sources()
+            .into_iter()
+            .filter(|(url, _)| self.lists.contains_key(url))
+            .map(|(url, _)| self.load_cached_list(&url))
+            .collect::<Result<Vec<_>, _>>()?;
+        if !cached.is_empty() {
+            return Ok(self.merge_filter_lists(cached.iter().map(|s| s.as_str()).collect()));
         }
 
-        std::fs::read_to_string(&cache_file).map_err(|e| e.into())
+        let cache_file = cache_dir.join(FILTER_CACHE_FILE);
+        let content = if cache_file.exists() {
+            let content = compression::gzip_decompress(&std::fs::read(&cache_file)?)?;
+            String::from_utf8(content)?
+        } else {
+            let legacy_file = cache_dir.join(LEGACY_CACHE_FILE);
+            if !legacy_file.exists() {
+                return Err("Cache file not found".into());
+            }
+            std::fs::read_to_string(&legacy_file)?
+        };
+
+        // Caches written before hashes were recorded are trusted
+        let expected = read_cache_metadata(cache_dir)
+            .ok()
+            .flatten()
+            .and_then(|metadata| metadata.content_sha256);
+        if let Some(expected) = expected {
+            let actual = checksum::sha256_hex(&content);
+            if actual != expected {
+                return Err(CacheCorrupt { expected, actual }.into());
+            }
+        }
+        Ok(content)
     }
 
     /// Load cache metadata
     fn load_cache_metadata(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         if let Some(ref cache_dir) = self.config.cache_dir {
-            let metadata_file = cache_dir.join(METADATA_FILE);
-            if metadata_file.exists() {
-                let metadata_json = std::fs::read_to_string(&metadata_file)?;
-                let metadata: CacheMetadata = serde_json::from_str(&metadata_json)?;
+            if let Some(metadata) = read_cache_metadata(cache_dir)? {
                 self.last_update = Some(metadata.last_update);
+                self.mirror_failures = metadata.mirror_failures;
+                self.lists = metadata.lists;
             }
         }
         Ok(())
     }
 }
 
+/// Read the metadata of a cache directory, if it has any
+fn read_cache_metadata(
+    cache_dir: &Path,
+) -> Result<Option<CacheMetadata>, Box<dyn std::error::Error>> {
+    let metadata_file = cache_dir.join(METADATA_FILE);
+    if !metadata_file.exists() {
+        return Ok(None);
+    }
+    let metadata_json = std::fs::read_to_string(&metadata_file)?;
+    Ok(Some(serde_json::from_str(&metadata_json)?))
+}
+
+/// Replace a file so a crash leaves either the old or the new content
+///
+/// The content is synced to a temporary file that is then renamed over
+/// the target.
+fn write_atomically(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(".tmp");
+    let temp = path.with_file_name(temp_name);
+    {
+        let mut file = std::fs::File::create(&temp)?;
+        file.write_all(content)?;
+        file.sync_all()?;
+    }
+    std::fs::rename(&temp, path)?;
+    // Persist the rename itself; directories can't be opened on Windows
+    #[cfg(unix)]
+    if let Some(dir) = path.parent() {
+        std::fs::File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
 /// Cache metadata
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 struct CacheMetadata {
     last_update: SystemTime,
+    #[serde(default)]
+    mirror_failures: HashMap<String, MirrorFailures>,
+    /// SHA-256 (hex) of the uncompressed merged list, if one is cached
+    #[serde(default)]
+    content_sha256: Option<String>,
+    #[serde(default)]
+    lists: HashMap<String, CachedList>,
+}
+
+/// Failed downloads from a URL since its last success
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+struct MirrorFailures {
+    count: u32,
+    last: SystemTime,
+}
+
+// Relies on the download stub failing `invalid` hosts
+#[cfg(all(test, not(feature = "http")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_download_falls_back_to_mirrors() {
+        let mut updater = FilterUpdater::new(UpdateConfig {
+            urls: Vec::new(),
+            presets: vec!["easyprivacy".to_string(), "no-such-list".to_string()],
+            mirrors: HashMap::new(),
+            update_interval: Duration::from_secs(3600),
+            cache_dir: None,
+        })
+        .unwrap();
+        assert_eq!(updater.list_sources().len(), 1);
+
+        let urls = [
+            "https://invalid.example/easyprivacy.txt".to_string(),
+            "https://mirror.example/easyprivacy.txt".to_string(),
+        ];
+        let (source, fetched) = updater.download_with_fallback(&urls, None).unwrap();
+        assert_eq!(source, urls[1]);
+        assert!(
+            matches!(fetched, Fetched::List { content, .. } if content.contains("EasyPrivacy"))
+        );
+        assert!(updater.download_with_fallback(&urls[..1], None).is_err());
+    }
 }
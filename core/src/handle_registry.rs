@@ -0,0 +1,150 @@
+//! Registry of objects handed out through the C API and JNI
+//!
+//! Callers get opaque integer handles instead of pointers, and every call
+//! looks its handle up again, so a destroyed, stale or made-up handle is
+//! rejected rather than dereferenced. A handle holds a slot index in its
+//! low half and the slot's generation in its high half; the generation
+//! changes each time the slot is freed, so an old handle never reaches the
+//! slot's next occupant. Zero is never a valid handle.
+
+use std::sync::{Arc, PoisonError, RwLock};
+
+/// Bits of a handle holding the slot index, and the generation above them
+const HALF_BITS: u32 = usize::BITS / 2;
+const HALF_MASK: usize = (1 << HALF_BITS) - 1;
+
+struct Slot<T> {
+    generation: usize,
+    value: Option<Arc<T>>,
+}
+
+struct Slots<T> {
+    entries: Vec<Slot<T>>,
+    /// Indices of empty entries
+    free: Vec<usize>,
+}
+
+/// Thread-safe slab of shared objects addressed by generation-checked handles
+///
+/// Lookups hand out an [`Arc`], so removing an object while another thread
+/// is using it only drops it once that call returns.
+pub struct HandleRegistry<T> {
+    slots: RwLock<Slots<T>>,
+}
+
+impl<T> HandleRegistry<T> {
+    /// Create an empty registry, e.g. in a `static`
+    pub const fn new() -> Self {
+        Self {
+            slots: RwLock::new(Slots {
+                entries: Vec::new(),
+                free: Vec::new(),
+            }),
+        }
+    }
+
+    /// Store an object, returning its handle
+    ///
+    /// Returns `None` if every slot is taken.
+    pub fn insert(&self, value: T) -> Option<usize> {
+        let mut slots = self.slots.write().unwrap_or_else(PoisonError::into_inner);
+        let index = match slots.free.pop() {
+            Some(index) => index,
+            None if slots.entries.len() < HALF_MASK => {
+                slots.entries.push(Slot {
+                    generation: 0,
+                    value: None,
+                });
+                slots.entries.len() - 1
+            }
+            None => return None,
+        };
+        let slot = &mut slots.entries[index];
+        slot.value = Some(Arc::new(value));
+        Some((slot.generation << HALF_BITS) | (index + 1))
+    }
+
+    /// Object of a handle, if it is still registered
+    pub fn get(&self, handle: usize) -> Option<Arc<T>> {
+        let (index, generation) = decode(handle)?;
+        let slots = self.slots.read().unwrap_or_else(PoisonError::into_inner);
+        let slot = slots.entries.get(index)?;
+        if slot.generation != generation {
+            return None;
+        }
+        slot.value.clone()
+    }
+
+    /// Unregister a handle, returning its object
+    ///
+    /// The handle, and any copy of it, is invalid from now on.
+    pub fn remove(&self, handle: usize) -> Option<Arc<T>> {
+        let (index, generation) = decode(handle)?;
+        let mut slots = self.slots.write().unwrap_or_else(PoisonError::into_inner);
+        let slot = slots.entries.get_mut(index)?;
+        if slot.generation != generation {
+            return None;
+        }
+        let value = slot.value.take()?;
+        slot.generation = (slot.generation + 1) & HALF_MASK;
+        slots.free.push(index);
+        Some(value)
+    }
+
+    /// Number of registered objects
+    pub fn len(&self) -> usize {
+        let slots = self.slots.read().unwrap_or_else(PoisonError::into_inner);
+        slots.entries.len() - slots.free.len()
+    }
+
+    /// Whether no object is registered
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T> Default for HandleRegistry<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Slot index and generation of a handle
+fn decode(handle: usize) -> Option<(usize, usize)> {
+    let index = (handle & HALF_MASK).checked_sub(1)?;
+    Some((index, handle >> HALF_BITS))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stale_handles_are_rejected() {
+        let registry = HandleRegistry::new();
+        let first = registry.insert("first").unwrap();
+        assert_eq!(registry.get(first).as_deref(), Some(&"first"));
+        assert!(registry.get(0).is_none());
+        assert!(registry.get(first + 1).is_none());
+
+        assert_eq!(registry.remove(first).as_deref(), Some(&"first"));
+        assert!(registry.remove(first).is_none());
+
+        // The slot is reused under a new generation
+        let second = registry.insert("second").unwrap();
+        assert_ne!(first, second);
+        assert!(registry.get(first).is_none());
+        assert_eq!(registry.get(second).as_deref(), Some(&"second"));
+        assert_eq!(registry.len(), 1);
+    }
+
+    #[test]
+    fn test_removed_objects_outlive_running_calls() {
+        let registry = HandleRegistry::new();
+        let handle = registry.insert(vec![1, 2, 3]).unwrap();
+        let in_use = registry.get(handle).unwrap();
+        registry.remove(handle);
+        assert!(registry.is_empty());
+        assert_eq!(in_use.len(), 3);
+    }
+}
@@ -0,0 +1,256 @@
+//! Hash-prefix blocklists
+//!
+//! Safe Browsing style lists where only SHA-256 hashes of domains are
+//! distributed. The client keeps short hash prefixes locally; a prefix hit
+//! is only a *partial* match and must be confirmed against the full hash,
+//! either from full hashes shipped with the list or through a caller
+//! provided verifier (e.g. a server lookup by prefix).
+
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+
+/// Default prefix length in bytes (32 bits, as in Safe Browsing)
+pub const DEFAULT_PREFIX_LEN: usize = 4;
+
+/// Maximum number of host suffixes checked per domain
+const MAX_HOST_SUFFIXES: usize = 5;
+
+/// A SHA-256 digest
+pub type FullHash = [u8; 32];
+
+/// Confirms partial matches by resolving a prefix to its full hashes
+pub trait FullHashVerifier: Send + Sync {
+    /// Full hashes sharing the given prefix, or `None` if unavailable
+    fn full_hashes_for_prefix(&self, prefix: &[u8]) -> Option<Vec<FullHash>>;
+}
+
+impl<F> FullHashVerifier for F
+where
+    F: Fn(&[u8]) -> Option<Vec<FullHash>> + Send + Sync,
+{
+    fn full_hashes_for_prefix(&self, prefix: &[u8]) -> Option<Vec<FullHash>> {
+        self(prefix)
+    }
+}
+
+/// Result of looking a domain up in a hash-prefix list
+#[derive(Debug, Clone, PartialEq)]
+pub enum HashMatch {
+    /// No prefix matched
+    Miss,
+    /// A prefix matched but the full hash could not be confirmed
+    Partial {
+        expression: String,
+        full_hash: FullHash,
+    },
+    /// The full hash of the expression is on the list
+    Confirmed { expression: String },
+}
+
+impl HashMatch {
+    /// Whether the match is confirmed
+    pub fn is_confirmed(&self) -> bool {
+        matches!(self, HashMatch::Confirmed { .. })
+    }
+}
+
+/// Hash-prefix list for a single category
+#[derive(Debug, Clone)]
+pub struct HashPrefixList {
+    name: String,
+    prefix_len: usize,
+    prefixes: HashSet<Vec<u8>>,
+    full_hashes: HashSet<FullHash>,
+}
+
+impl HashPrefixList {
+    /// Create an empty list using `prefix_len`-byte prefixes
+    pub fn new(name: &str, prefix_len: usize) -> Self {
+        Self {
+            name: name.to_string(),
+            prefix_len: prefix_len.clamp(4, 32),
+            prefixes: HashSet::new(),
+            full_hashes: HashSet::new(),
+        }
+    }
+
+    /// List name (e.g. "phishing")
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Prefix length in bytes
+    pub fn prefix_len(&self) -> usize {
+        self.prefix_len
+    }
+
+    /// Number of prefixes
+    pub fn len(&self) -> usize {
+        self.prefixes.len()
+    }
+
+    /// Whether the list has no prefixes
+    pub fn is_empty(&self) -> bool {
+        self.prefixes.is_empty()
+    }
+
+    /// Add a hash prefix; longer input is truncated to the prefix length
+    pub fn add_prefix(&mut self, prefix: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        if prefix.len() < self.prefix_len {
+            return Err(format!(
+                "Hash prefix too short: {} bytes, expected {}",
+                prefix.len(),
+                self.prefix_len
+            )
+            .into());
+        }
+        self.prefixes.insert(prefix[..self.prefix_len].to_vec());
+        Ok(())
+    }
+
+    /// Add a full hash, which also registers its prefix
+    pub fn add_full_hash(&mut self, full_hash: FullHash) {
+        self.prefixes.insert(full_hash[..self.prefix_len].to_vec());
+        self.full_hashes.insert(full_hash);
+    }
+
+    /// Hash a plaintext domain and add it (for building lists)
+    pub fn add_domain(&mut self, domain: &str) {
+        self.add_full_hash(hash_expression(&normalize_domain(domain)));
+    }
+
+    /// Load hex-encoded prefixes or full hashes, one per line
+    ///
+    /// Lines starting with `!` or `#` are comments. Returns the number of
+    /// entries loaded.
+    pub fn load_hex(&mut self, content: &str) -> Result<usize, Box<dyn std::error::Error>> {
+        let mut loaded = 0;
+
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('!') || line.starts_with('#') {
+                continue;
+            }
+
+            let bytes = hex::decode(line)?;
+            if bytes.len() == 32 {
+                let mut full_hash = [0u8; 32];
+                full_hash.copy_from_slice(&bytes);
+                self.add_full_hash(full_hash);
+            } else {
+                self.add_prefix(&bytes)?;
+            }
+            loaded += 1;
+        }
+
+        Ok(loaded)
+    }
+
+    /// Look a domain up using only locally known full hashes
+    pub fn check_domain(&self, domain: &str) -> HashMatch {
+        self.check_domain_with(domain, None)
+    }
+
+    /// Look a domain up, confirming partial matches with `verifier`
+    pub fn check_domain_with(
+        &self,
+        domain: &str,
+        verifier: Option<&dyn FullHashVerifier>,
+    ) -> HashMatch {
+        let mut partial = None;
+
+        for expression in host_suffixes(&normalize_domain(domain)) {
+            let full_hash = hash_expression(&expression);
+            let prefix = &full_hash[..self.prefix_len];
+
+            if !self.prefixes.contains(prefix) {
+                continue;
+            }
+
+            if self.full_hashes.contains(&full_hash) {
+                return HashMatch::Confirmed { expression };
+            }
+
+            if let Some(full_hashes) = verifier.and_then(|v| v.full_hashes_for_prefix(prefix)) {
+                if full_hashes.contains(&full_hash) {
+                    return HashMatch::Confirmed { expression };
+                }
+                // The verifier is authoritative for this prefix
+                continue;
+            }
+
+            partial.get_or_insert(HashMatch::Partial {
+                expression,
+                full_hash,
+            });
+        }
+
+        partial.unwrap_or(HashMatch::Miss)
+    }
+}
+
+/// SHA-256 of a lookup expression
+pub fn hash_expression(expression: &str) -> FullHash {
+    Sha256::digest(expression.as_bytes()).into()
+}
+
+/// Lowercase a domain and strip surrounding dots
+fn normalize_domain(domain: &str) -> String {
+    domain.trim().trim_matches('.').to_lowercase()
+}
+
+/// Host suffixes checked for a domain: the exact host plus up to four
+/// parent domains, never going below the registrable two labels
+fn host_suffixes(domain: &str) -> Vec<String> {
+    let labels: Vec<&str> = domain.split('.').collect();
+    let mut suffixes = vec![domain.to_string()];
+
+    let start = labels.len().saturating_sub(MAX_HOST_SUFFIXES).max(1);
+    for i in start..labels.len().saturating_sub(1) {
+        suffixes.push(labels[i..].join("."));
+    }
+
+    suffixes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_prefix_matching() {
+        let mut list = HashPrefixList::new("phishing", DEFAULT_PREFIX_LEN);
+        list.add_domain("evil.example");
+
+        assert_eq!(
+            list.check_domain("login.evil.example"),
+            HashMatch::Confirmed {
+                expression: "evil.example".to_string()
+            }
+        );
+        assert_eq!(list.check_domain("good.example"), HashMatch::Miss);
+    }
+
+    #[test]
+    fn test_partial_match_needs_verification() {
+        let full_hash = hash_expression("tracker.example");
+        let mut list = HashPrefixList::new("adult", DEFAULT_PREFIX_LEN);
+        list.load_hex(&hex::encode(&full_hash[..4])).unwrap();
+
+        assert!(matches!(
+            list.check_domain("tracker.example"),
+            HashMatch::Partial { .. }
+        ));
+
+        let verifier = move |_: &[u8]| Some(vec![full_hash]);
+        assert!(list
+            .check_domain_with("tracker.example", Some(&verifier))
+            .is_confirmed());
+
+        let empty_verifier = |_: &[u8]| Some(Vec::new());
+        assert_eq!(
+            list.check_domain_with("tracker.example", Some(&empty_verifier)),
+            HashMatch::Miss
+        );
+    }
+}
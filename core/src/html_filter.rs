@@ -0,0 +1,167 @@
+//! HTML rewriting for proxied pages
+//!
+//! Applies cosmetic filtering on the proxy side, for clients that can't
+//! run injected JavaScript or stylesheets. Page HTML is streamed through
+//! a rewriter that removes elements matching the page's element hiding
+//! selectors and prepends the page's scriptlets to `<head>`. Selectors
+//! the rewriter can't match (e.g. procedural ones) are skipped.
+
+use crate::FilterEngine;
+use lol_html::errors::RewritingError;
+use lol_html::html_content::{ContentType, Element};
+use lol_html::{ElementContentHandlers, HtmlRewriter, OutputSink, Selector, Settings};
+use std::borrow::Cow;
+use std::cell::Cell;
+
+/// Rewrites the HTML of one page
+#[derive(Debug, Default)]
+pub struct HtmlFilter {
+    selectors: Vec<Selector>,
+    /// `<script>` element with the page's scriptlets
+    bootstrap: Option<String>,
+}
+
+impl HtmlFilter {
+    /// Filter removing elements matching `selectors` and injecting `scriptlets`
+    pub fn new(selectors: &[String], scriptlets: &[String]) -> Self {
+        let selectors = selectors
+            .iter()
+            .filter_map(|selector| match selector.parse() {
+                Ok(parsed) => Some(parsed),
+                Err(e) => {
+                    tracing::debug!("Skipping selector {selector}: {e}");
+                    None
+                }
+            })
+            .collect();
+        let bootstrap = (!scriptlets.is_empty()).then(|| {
+            // `</script` inside the code would end the element early
+            let code = scriptlets.join("\n").replace("</", "<\\/");
+            format!("<script>{code}</script>")
+        });
+        HtmlFilter {
+            selectors,
+            bootstrap,
+        }
+    }
+
+    /// Filter applying the engine's cosmetic rules and scriptlets for a page
+    pub fn for_page(engine: &FilterEngine, url: &str) -> Self {
+        let cosmetic = engine.get_cosmetic_result(url);
+        let host = crate::utils::extract_domain(url);
+        let domain = host.split(':').next().unwrap_or(&host);
+        Self::new(
+            &cosmetic.hide_selectors,
+            &engine.get_scriptlets_for_domain(domain),
+        )
+    }
+
+    /// Whether the filter leaves pages unchanged
+    pub fn is_empty(&self) -> bool {
+        self.selectors.is_empty() && self.bootstrap.is_none()
+    }
+
+    /// Start rewriting a page, writing the result to `output` as it goes
+    pub fn stream<O: OutputSink>(&self, output: O) -> HtmlStream<'_, O> {
+        let mut handlers: Vec<_> = self
+            .selectors
+            .iter()
+            .map(|selector| {
+                let remove =
+                    ElementContentHandlers::default().element(|element: &mut Element<'_, '_>| {
+                        element.remove();
+                        Ok(())
+                    });
+                (Cow::Borrowed(selector), remove)
+            })
+            .collect();
+
+        if let Some(bootstrap) = &self.bootstrap {
+            let injected = Cell::new(false);
+            let inject =
+                ElementContentHandlers::default().element(move |element: &mut Element<'_, '_>| {
+                    if !injected.replace(true) {
+                        element.prepend(bootstrap, ContentType::Html);
+                    }
+                    Ok(())
+                });
+            handlers.push((Cow::Owned("head".parse().expect("valid selector")), inject));
+        }
+
+        let settings = Settings {
+            element_content_handlers: handlers,
+            ..Settings::new()
+        };
+        HtmlStream {
+            rewriter: HtmlRewriter::new(settings, output),
+        }
+    }
+
+    /// Rewrite a whole page
+    pub fn rewrite(&self, html: &[u8]) -> Result<Vec<u8>, RewritingError> {
+        let mut output = Vec::with_capacity(html.len());
+        let mut stream = self.stream(|chunk: &[u8]| output.extend_from_slice(chunk));
+        stream.write(html)?;
+        stream.end()?;
+        Ok(output)
+    }
+}
+
+/// A page being rewritten chunk by chunk
+pub struct HtmlStream<'h, O: OutputSink> {
+    rewriter: HtmlRewriter<'h, O>,
+}
+
+impl<O: OutputSink> HtmlStream<'_, O> {
+    /// Feed the next chunk of the page
+    pub fn write(&mut self, chunk: &[u8]) -> Result<(), RewritingError> {
+        self.rewriter.write(chunk)
+    }
+
+    /// Finish the page, flushing the rest of the output
+    pub fn end(self) -> Result<(), RewritingError> {
+        self.rewriter.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rewrite_removes_elements_and_injects_scriptlets() {
+        let engine = FilterEngine::from_filter_list(
+            "example.com##.ad-banner\n##div#sponsor\nexample.com##+js(set-constant, adsEnabled, false)\n\
+             example.com##div:has-text(Sponsored)\n",
+        )
+        .unwrap();
+        let filter = HtmlFilter::for_page(&engine, "https://www.example.com/article");
+        assert!(!filter.is_empty());
+
+        let html = b"<html><head><title>t</title></head><body>\
+            <div class=\"ad-banner\"><img src=\"ad.png\"></div><p>Text</p><div id=\"sponsor\">x</div>\
+            </body></html>";
+        let output = String::from_utf8(filter.rewrite(html).unwrap()).unwrap();
+
+        assert!(output.starts_with("<html><head><script>"));
+        assert!(output.contains("adsEnabled"));
+        assert!(output.ends_with("<title>t</title></head><body><p>Text</p></body></html>"));
+    }
+
+    #[test]
+    fn test_stream_rewrites_across_chunks() {
+        let filter = HtmlFilter::new(&[".ad".to_string()], &["alert('</script>')".to_string()]);
+        let mut output = Vec::new();
+        let mut stream = filter.stream(|chunk: &[u8]| output.extend_from_slice(chunk));
+        for chunk in ["<head></he", "ad><p class=\"a", "d\">ad</p><p>ok</p>"] {
+            stream.write(chunk.as_bytes()).unwrap();
+        }
+        stream.end().unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "<head><script>alert('<\\/script>')</script></head><p>ok</p>"
+        );
+        assert!(HtmlFilter::new(&[], &[]).is_empty());
+    }
+}
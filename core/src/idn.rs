@@ -0,0 +1,227 @@
+//! Internationalized domain name helpers
+//!
+//! Rules and statistics keep hosts in their ASCII (punycode) form.
+//! [`to_ascii`] converts Unicode hosts to that form for matching; the
+//! display helpers render them as Unicode for display only, falling back to
+//! the punycode form when a label looks like a homograph spoofing attempt.
+
+/// ACE prefix of punycode labels
+const ACE_PREFIX: &str = "xn--";
+
+/// Cyrillic and Greek letters that look like Latin letters
+const LATIN_LOOKALIKES: &[char] = &[
+    'а', 'в', 'е', 'к', 'м', 'н', 'о', 'р', 'с', 'т', 'у', 'х', 'ѕ', 'і', 'ј', 'ӏ', 'ԁ', 'ԛ', 'ԝ',
+    'ɡ', 'α', 'ο', 'ν', 'ρ', 'τ', 'υ', 'ι', 'κ',
+];
+
+/// Characters that imitate URL punctuation or are invisible
+const DENIED_CHARS: &[char] = &[
+    '\u{200b}', '\u{200c}', '\u{200d}', '\u{feff}', '\u{2044}', '\u{2215}', '\u{ff0f}', '\u{0589}',
+    '\u{05c3}', '\u{01c3}', '\u{a789}', '\u{2024}', '\u{3002}', '\u{ff0e}', '\u{0338}',
+];
+
+/// Languages whose users expect all-Cyrillic domains
+const CYRILLIC_LANGUAGES: &[&str] = &["ru", "uk", "bg", "sr", "be", "mk", "kk"];
+
+/// Writing system of a character, as far as spoofing checks care
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Script {
+    /// Digits, hyphen and marks shared by several scripts
+    Common,
+    Latin,
+    Cyrillic,
+    Greek,
+    Han,
+    Hiragana,
+    Katakana,
+    Hangul,
+    Other,
+}
+
+fn script_of(c: char) -> Script {
+    match c {
+        '0'..='9' | '-' | '\u{30fc}' | '\u{3005}' => Script::Common,
+        'a'..='z' | 'A'..='Z' | '\u{00c0}'..='\u{024f}' => Script::Latin,
+        '\u{0400}'..='\u{052f}' => Script::Cyrillic,
+        '\u{0370}'..='\u{03ff}' => Script::Greek,
+        '\u{3400}'..='\u{4dbf}' | '\u{4e00}'..='\u{9fff}' | '\u{f900}'..='\u{faff}' => Script::Han,
+        '\u{3040}'..='\u{309f}' => Script::Hiragana,
+        '\u{30a0}'..='\u{30ff}' | '\u{31f0}'..='\u{31ff}' | '\u{ff66}'..='\u{ff9f}' => {
+            Script::Katakana
+        }
+        '\u{1100}'..='\u{11ff}' | '\u{ac00}'..='\u{d7af}' => Script::Hangul,
+        _ => Script::Other,
+    }
+}
+
+/// Whether a decoded label is safe to show as Unicode
+fn is_safe_label(label: &str, cyrillic_context: bool) -> bool {
+    if label.chars().any(|c| DENIED_CHARS.contains(&c)) {
+        return false;
+    }
+
+    let mut scripts: Vec<Script> = label
+        .chars()
+        .map(script_of)
+        .filter(|script| *script != Script::Common)
+        .collect();
+    scripts.sort_by_key(|script| *script as u8);
+    scripts.dedup();
+
+    let allowed_mix = match scripts.as_slice() {
+        [] | [_] => true,
+        // Japanese, Chinese and Korean names are often written mixed with Latin
+        mixed => {
+            mixed.iter().all(|script| {
+                matches!(
+                    script,
+                    Script::Latin | Script::Han | Script::Hiragana | Script::Katakana
+                )
+            }) || mixed
+                .iter()
+                .all(|script| matches!(script, Script::Latin | Script::Han | Script::Hangul))
+        }
+    };
+    if !allowed_mix || scripts.contains(&Script::Other) && scripts.len() > 1 {
+        return false;
+    }
+
+    // Reject whole-script confusables such as Cyrillic "аррӏе"
+    if matches!(scripts.as_slice(), [Script::Cyrillic] | [Script::Greek]) && !cyrillic_context {
+        let all_lookalikes = label
+            .chars()
+            .filter(|c| script_of(*c) != Script::Common)
+            .all(|c| LATIN_LOOKALIKES.contains(&c));
+        if all_lookalikes {
+            return false;
+        }
+    }
+
+    true
+}
+
+/// Convert a host to its lowercase ASCII form for matching
+///
+/// Non-ASCII hosts go through UTS #46 processing, so they are mapped and
+/// normalized the way browsers do before their labels are punycode-encoded.
+/// Returns `None` if the host is not a valid domain name.
+pub fn to_ascii(host: &str) -> Option<String> {
+    if host.is_ascii() {
+        return Some(host.to_ascii_lowercase());
+    }
+    idna::domain_to_ascii(host).ok()
+}
+
+/// Punycode-encode the host of a network rule such as `||реклама.рф^`
+///
+/// The host is the leading part of the rule after any `@@`, `||` or `|`
+/// prefix and scheme. Rules whose host is already ASCII are returned
+/// unchanged; paths keep their Unicode, as URLs are matched
+/// percent-decoded.
+pub fn normalize_rule(rule: &str) -> std::borrow::Cow<'_, str> {
+    if rule.is_ascii() {
+        return rule.into();
+    }
+    let mut host_start = 0;
+    for prefix in ["@@", "||", "|"] {
+        if rule[host_start..].starts_with(prefix) {
+            host_start += prefix.len();
+        }
+    }
+    if let Some(scheme_end) = rule[host_start..].find("://") {
+        host_start += scheme_end + 3;
+    }
+    let host_end = rule[host_start..]
+        .find(['^', '/', '*', '$', '|', ':', '?'])
+        .map_or(rule.len(), |end| host_start + end);
+    let host = &rule[host_start..host_end];
+    match to_ascii(host) {
+        Some(ascii) if ascii != host => {
+            format!("{}{ascii}{}", &rule[..host_start], &rule[host_end..]).into()
+        }
+        _ => rule.into(),
+    }
+}
+
+/// Render a host for display, decoding punycode labels
+///
+/// `locale` is a BCP 47 tag such as `ja-JP`. The host is returned
+/// unchanged if any label fails to decode or looks like a spoofing attempt;
+/// matching must always use the original punycode form.
+pub fn to_display(host: &str, locale: &str) -> String {
+    if !host.to_ascii_lowercase().contains(ACE_PREFIX) {
+        return host.to_string();
+    }
+
+    let (unicode, result) = idna::domain_to_unicode(host);
+    if result.is_err() {
+        return host.to_string();
+    }
+    let decoded: Vec<&str> = unicode.split('.').collect();
+
+    // All-Cyrillic labels are expected under Cyrillic TLDs and locales
+    let language = locale.split(['-', '_']).next().unwrap_or("");
+    let cyrillic_context = CYRILLIC_LANGUAGES.contains(&language.to_ascii_lowercase().as_str())
+        || decoded
+            .last()
+            .is_some_and(|tld| tld.chars().any(|c| script_of(c) == Script::Cyrillic));
+
+    if decoded
+        .iter()
+        .all(|label| is_safe_label(label, cyrillic_context))
+    {
+        decoded.join(".")
+    } else {
+        host.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_ascii() {
+        assert_eq!(to_ascii("Bücher.de").as_deref(), Some("xn--bcher-kva.de"));
+        assert_eq!(
+            to_ascii("Пример.РФ").as_deref(),
+            Some("xn--e1afmkfd.xn--p1ai")
+        );
+        assert_eq!(
+            to_ascii("Ads.Example.COM").as_deref(),
+            Some("ads.example.com")
+        );
+        assert_eq!(to_ascii("日本語。jp").as_deref(), Some("xn--wgv71a119e.jp"));
+        // Fullwidth letters are mapped before encoding
+        assert_eq!(
+            to_ascii("ｅｘａｍｐｌｅ.日本").as_deref(),
+            Some("example.xn--wgv71a")
+        );
+        assert_eq!(to_ascii("ab\u{200b}\u{fffd}.com"), None);
+
+        assert_eq!(normalize_rule("||реклама.рф^"), "||xn--80aanufhx.xn--p1ai^");
+        assert_eq!(
+            normalize_rule("@@|https://Пример.рф/путь"),
+            "@@|https://xn--e1afmkfd.xn--p1ai/путь"
+        );
+        assert_eq!(normalize_rule("*/реклама/*"), "*/реклама/*");
+    }
+
+    #[test]
+    fn test_display_with_homograph_safeguards() {
+        assert_eq!(to_display("xn--wgv71a119e.jp", "ja-JP"), "日本語.jp");
+        assert_eq!(to_display("ads.example.com", "ja-JP"), "ads.example.com");
+
+        // Cyrillic "аррӏе" imitating "apple"
+        assert_eq!(
+            to_display("xn--80ak6aa92e.com", "ja-JP"),
+            "xn--80ak6aa92e.com"
+        );
+        // Mixed Latin and Cyrillic in one label
+        assert_eq!(to_display("xn--pypal-4ve.com", "en"), "xn--pypal-4ve.com");
+
+        assert_eq!(to_display("xn--e1afmkfd.xn--p1ai", "en"), "пример.рф");
+        // Labels that don't decode are shown as they are
+        assert_eq!(to_display("xn--a.com", "en"), "xn--a.com");
+    }
+}
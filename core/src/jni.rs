@@ -1,13 +1,20 @@
 //! JNI bindings for Android
 //!
 //! This module provides JNI-compatible function signatures that wrap the FFI functions
+//!
+//! Like the C API, a panic never unwinds into the JVM; the call returns
+//! `0`, `false` or null instead.
+//!
+//! The `long` handles are the C API's registry handles, not pointers, so a
+//! handle used after `nativeDestroy` is rejected rather than dereferenced.
 
 #![cfg(target_os = "android")]
 
-use jni::objects::{JClass, JString};
-use jni::sys::{jboolean, jlong, jstring, JNI_FALSE, JNI_TRUE};
-use jni::JNIEnv;
-use std::ffi::CString;
+use jni::objects::{GlobalRef, JClass, JObject, JString, JValue};
+use jni::sys::{jboolean, jint, jlong, jstring, JNI_FALSE, JNI_TRUE};
+use jni::{JNIEnv, JavaVM};
+use std::ffi::{c_void, CStr, CString};
+use std::os::raw::c_char;
 
 use crate::ffi;
 
@@ -16,7 +23,9 @@ pub extern "system" fn Java_com_adblock_AdBlockEngine_nativeCreate(
     _env: JNIEnv,
     _class: JClass,
 ) -> jlong {
-    ffi::adblock_engine_create() as jlong
+    ffi::catch_panic(std::ptr::null_mut(), || {
+        ffi::adblock_engine_create(std::ptr::null_mut()) as jlong
+    })
 }
 
 #[no_mangle]
@@ -25,7 +34,9 @@ pub extern "system" fn Java_com_adblock_AdBlockEngine_nativeDestroy(
     _class: JClass,
     handle: jlong,
 ) {
-    ffi::adblock_engine_destroy(handle as *mut std::ffi::c_void);
+    ffi::catch_panic(std::ptr::null_mut(), || {
+        ffi::adblock_engine_destroy(handle as *mut std::ffi::c_void);
+    })
 }
 
 #[no_mangle]
@@ -35,27 +46,88 @@ pub extern "system" fn Java_com_adblock_AdBlockEngine_nativeShouldBlock(
     handle: jlong,
     url: JString,
 ) -> jboolean {
-    let engine = handle as *mut std::ffi::c_void;
-    if engine.is_null() {
-        return JNI_FALSE;
-    }
+    ffi::catch_panic(std::ptr::null_mut(), || {
+        let engine = handle as *mut std::ffi::c_void;
+        if engine.is_null() {
+            return JNI_FALSE;
+        }
+
+        let url_str = match env.get_string(&url) {
+            Ok(s) => s,
+            Err(_) => return JNI_FALSE,
+        };
 
-    let url_str = match env.get_string(&url) {
-        Ok(s) => s,
-        Err(_) => return JNI_FALSE,
-    };
-
-    let url_cstr = match CString::new(url_str.to_string_lossy().as_bytes()) {
-        Ok(s) => s,
-        Err(_) => return JNI_FALSE,
-    };
-
-    let should_block = ffi::adblock_engine_should_block(engine, url_cstr.as_ptr());
-    if should_block {
-        JNI_TRUE
-    } else {
-        JNI_FALSE
+        let url_cstr = match CString::new(url_str.to_string_lossy().as_bytes()) {
+            Ok(s) => s,
+            Err(_) => return JNI_FALSE,
+        };
+
+        let should_block =
+            ffi::adblock_engine_should_block(engine, url_cstr.as_ptr(), std::ptr::null_mut());
+        if should_block {
+            JNI_TRUE
+        } else {
+            JNI_FALSE
+        }
+    })
+}
+
+/// Copy a Java string into a C string; `None` if it can't be read
+fn c_string(env: &mut JNIEnv, s: &JString) -> Option<CString> {
+    let s = env.get_string(s).ok()?;
+    CString::new(s.to_string_lossy().as_bytes()).ok()
+}
+
+/// Copy a nullable Java string; `Some(None)` if it is null
+fn optional_c_string(env: &mut JNIEnv, s: &JString) -> Option<Option<CString>> {
+    if s.is_null() {
+        return Some(None);
     }
+    c_string(env, s).map(Some)
+}
+
+/// Check a WebView request with its page URL and resource type
+///
+/// `page_url` and `resource_type` may be null; see
+/// `adblock_engine_should_block_request`.
+#[no_mangle]
+pub extern "system" fn Java_com_adblock_AdBlockEngine_nativeShouldBlockEx(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    url: JString,
+    page_url: JString,
+    resource_type: JString,
+) -> jboolean {
+    ffi::catch_panic(std::ptr::null_mut(), || {
+        let engine = handle as *mut std::ffi::c_void;
+        let Some(url_cstr) = c_string(&mut env, &url) else {
+            return JNI_FALSE;
+        };
+        let (Some(page_url_cstr), Some(resource_type_cstr)) = (
+            optional_c_string(&mut env, &page_url),
+            optional_c_string(&mut env, &resource_type),
+        ) else {
+            return JNI_FALSE;
+        };
+
+        let should_block = ffi::adblock_engine_should_block_request(
+            engine,
+            url_cstr.as_ptr(),
+            page_url_cstr
+                .as_ref()
+                .map_or(std::ptr::null(), |s| s.as_ptr()),
+            resource_type_cstr
+                .as_ref()
+                .map_or(std::ptr::null(), |s| s.as_ptr()),
+            std::ptr::null_mut(),
+        );
+        if should_block {
+            JNI_TRUE
+        } else {
+            JNI_FALSE
+        }
+    })
 }
 
 #[no_mangle]
@@ -65,27 +137,119 @@ pub extern "system" fn Java_com_adblock_AdBlockEngine_nativeLoadFilterList(
     handle: jlong,
     filter_list: JString,
 ) -> jboolean {
-    let engine = handle as *mut std::ffi::c_void;
-    if engine.is_null() {
-        return JNI_FALSE;
-    }
+    ffi::catch_panic(std::ptr::null_mut(), || {
+        let engine = handle as *mut std::ffi::c_void;
+        if engine.is_null() {
+            return JNI_FALSE;
+        }
 
-    let filter_str = match env.get_string(&filter_list) {
-        Ok(s) => s,
-        Err(_) => return JNI_FALSE,
-    };
-
-    let filter_cstr = match CString::new(filter_str.to_string_lossy().as_bytes()) {
-        Ok(s) => s,
-        Err(_) => return JNI_FALSE,
-    };
-
-    let success = ffi::adblock_engine_load_filter_list(engine, filter_cstr.as_ptr());
-    if success {
-        JNI_TRUE
-    } else {
-        JNI_FALSE
-    }
+        let filter_str = match env.get_string(&filter_list) {
+            Ok(s) => s,
+            Err(_) => return JNI_FALSE,
+        };
+
+        let filter_cstr = match CString::new(filter_str.to_string_lossy().as_bytes()) {
+            Ok(s) => s,
+            Err(_) => return JNI_FALSE,
+        };
+
+        let success = ffi::adblock_engine_load_filter_list(
+            engine,
+            filter_cstr.as_ptr(),
+            std::ptr::null_mut(),
+        );
+        if success {
+            JNI_TRUE
+        } else {
+            JNI_FALSE
+        }
+    })
+}
+
+/// Java `FilterUpdateCallback` waiting for an async update
+struct JniUpdateCallback {
+    vm: JavaVM,
+    callback: GlobalRef,
+}
+
+/// Deliver an update outcome to its `FilterUpdateCallback` on the update thread
+extern "C" fn finish_jni_update(
+    rule_count: usize,
+    error_message: *const c_char,
+    user_data: *mut c_void,
+) {
+    let callback = unsafe { Box::from_raw(user_data as *mut JniUpdateCallback) };
+    let message = (!error_message.is_null()).then(|| {
+        unsafe { CStr::from_ptr(error_message) }
+            .to_string_lossy()
+            .into_owned()
+    });
+
+    // A panic must not unwind out of an `extern "C"` function
+    let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        let Ok(mut env) = callback.vm.attach_current_thread() else {
+            return;
+        };
+        let result = match message {
+            None => env.call_method(
+                &callback.callback,
+                "onSuccess",
+                "(I)V",
+                &[JValue::Int(jint::try_from(rule_count).unwrap_or(jint::MAX))],
+            ),
+            Some(message) => match env.new_string(message) {
+                Ok(message) => env.call_method(
+                    &callback.callback,
+                    "onError",
+                    "(Ljava/lang/String;)V",
+                    &[JValue::Object(&message)],
+                ),
+                Err(e) => Err(e),
+            },
+        };
+        // Don't leave an exception thrown by the callback pending on this thread
+        if result.is_err() && env.exception_check().unwrap_or(false) {
+            let _ = env.exception_clear();
+        }
+    }));
+}
+
+/// Update the configured filter lists on a Rust thread
+///
+/// Returns once the update has started; `callback` then gets exactly one
+/// `onSuccess(ruleCount)` or `onError(message)`, from that thread.
+#[no_mangle]
+pub extern "system" fn Java_com_adblock_AdBlockEngine_nativeUpdateFiltersAsync(
+    env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    callback: JObject,
+) -> jboolean {
+    ffi::catch_panic(std::ptr::null_mut(), || {
+        let engine = handle as *mut std::ffi::c_void;
+        if engine.is_null() || callback.is_null() {
+            return JNI_FALSE;
+        }
+
+        let (Ok(vm), Ok(callback)) = (env.get_java_vm(), env.new_global_ref(&callback)) else {
+            return JNI_FALSE;
+        };
+        let user_data = Box::into_raw(Box::new(JniUpdateCallback { vm, callback }));
+
+        let started = ffi::adblock_engine_update_filters_async(
+            engine,
+            Some(finish_jni_update),
+            user_data as *mut c_void,
+            std::ptr::null_mut(),
+        );
+        if started {
+            JNI_TRUE
+        } else {
+            // The callback will never run, so release the reference here
+            drop(unsafe { Box::from_raw(user_data) });
+            JNI_FALSE
+        }
+    })
 }
 
 #[no_mangle]
@@ -94,24 +258,60 @@ pub extern "system" fn Java_com_adblock_AdBlockEngine_nativeGetStats(
     _class: JClass,
     handle: jlong,
 ) -> jstring {
-    let engine = handle as *mut std::ffi::c_void;
-    if engine.is_null() {
-        return std::ptr::null_mut();
-    }
+    ffi::catch_panic(std::ptr::null_mut(), || {
+        let engine = handle as *mut std::ffi::c_void;
+        if engine.is_null() {
+            return std::ptr::null_mut();
+        }
 
-    let stats_ptr = ffi::adblock_engine_get_stats(engine);
-    if stats_ptr.is_null() {
-        return std::ptr::null_mut();
-    }
+        let stats_ptr = ffi::adblock_engine_get_stats(engine, std::ptr::null_mut());
+        if stats_ptr.is_null() {
+            return std::ptr::null_mut();
+        }
+
+        let stats_cstr = unsafe { std::ffi::CStr::from_ptr(stats_ptr) };
+        let result = match env.new_string(stats_cstr.to_string_lossy()) {
+            Ok(s) => s.into_raw(),
+            Err(_) => std::ptr::null_mut(),
+        };
+
+        unsafe { ffi::adblock_free_string(stats_ptr as *mut std::os::raw::c_char) };
+        result
+    })
+}
+
+/// Get the stylesheet hiding a page domain's elements, for cosmetic injection
+#[no_mangle]
+pub extern "system" fn Java_com_adblock_AdBlockEngine_nativeGetCssRules(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    domain: JString,
+) -> jstring {
+    ffi::catch_panic(std::ptr::null_mut(), || {
+        let engine = handle as *mut std::ffi::c_void;
+        let Some(domain_cstr) = c_string(&mut env, &domain) else {
+            return std::ptr::null_mut();
+        };
 
-    let stats_cstr = unsafe { std::ffi::CStr::from_ptr(stats_ptr) };
-    let result = match env.new_string(stats_cstr.to_string_lossy()) {
-        Ok(s) => s.into_raw(),
-        Err(_) => std::ptr::null_mut(),
-    };
+        let css_ptr = ffi::adblock_engine_get_cosmetic_css(
+            engine,
+            domain_cstr.as_ptr(),
+            std::ptr::null_mut(),
+        );
+        if css_ptr.is_null() {
+            return std::ptr::null_mut();
+        }
 
-    unsafe { ffi::adblock_free_string(stats_ptr as *mut std::os::raw::c_char) };
-    result
+        let css_cstr = unsafe { std::ffi::CStr::from_ptr(css_ptr) };
+        let result = match env.new_string(css_cstr.to_string_lossy()) {
+            Ok(s) => s.into_raw(),
+            Err(_) => std::ptr::null_mut(),
+        };
+
+        unsafe { ffi::adblock_free_string(css_ptr) };
+        result
+    })
 }
 
 #[no_mangle]
@@ -120,17 +320,39 @@ pub extern "system" fn Java_com_adblock_AdBlockEngine_nativeResetStats(
     _class: JClass,
     handle: jlong,
 ) -> jboolean {
-    let engine = handle as *mut std::ffi::c_void;
-    if engine.is_null() {
-        return JNI_FALSE;
-    }
+    ffi::catch_panic(std::ptr::null_mut(), || {
+        let engine = handle as *mut std::ffi::c_void;
+        if engine.is_null() {
+            return JNI_FALSE;
+        }
 
-    let success = ffi::adblock_engine_reset_stats(engine);
-    if success {
-        JNI_TRUE
-    } else {
-        JNI_FALSE
-    }
+        let success = ffi::adblock_engine_reset_stats(engine, std::ptr::null_mut());
+        if success {
+            JNI_TRUE
+        } else {
+            JNI_FALSE
+        }
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_adblock_AdBlockEngine_nativeStartSession(
+    _env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+) -> jboolean {
+    ffi::catch_panic(std::ptr::null_mut(), || {
+        let engine = handle as *mut std::ffi::c_void;
+        if engine.is_null() {
+            return JNI_FALSE;
+        }
+
+        if ffi::adblock_engine_start_session(engine, std::ptr::null_mut()) {
+            JNI_TRUE
+        } else {
+            JNI_FALSE
+        }
+    })
 }
 
 #[no_mangle]
@@ -139,22 +361,24 @@ pub extern "system" fn Java_com_adblock_AdBlockEngine_nativeGetMetrics(
     _class: JClass,
     handle: jlong,
 ) -> jstring {
-    let engine = handle as *mut std::ffi::c_void;
-    if engine.is_null() {
-        return std::ptr::null_mut();
-    }
+    ffi::catch_panic(std::ptr::null_mut(), || {
+        let engine = handle as *mut std::ffi::c_void;
+        if engine.is_null() {
+            return std::ptr::null_mut();
+        }
 
-    let metrics_ptr = ffi::adblock_engine_get_metrics(engine);
-    if metrics_ptr.is_null() {
-        return std::ptr::null_mut();
-    }
+        let metrics_ptr = ffi::adblock_engine_get_metrics(engine, std::ptr::null_mut());
+        if metrics_ptr.is_null() {
+            return std::ptr::null_mut();
+        }
 
-    let metrics_cstr = unsafe { std::ffi::CStr::from_ptr(metrics_ptr) };
-    let result = match env.new_string(metrics_cstr.to_string_lossy()) {
-        Ok(s) => s.into_raw(),
-        Err(_) => std::ptr::null_mut(),
-    };
+        let metrics_cstr = unsafe { std::ffi::CStr::from_ptr(metrics_ptr) };
+        let result = match env.new_string(metrics_cstr.to_string_lossy()) {
+            Ok(s) => s.into_raw(),
+            Err(_) => std::ptr::null_mut(),
+        };
 
-    unsafe { ffi::adblock_free_string(metrics_ptr as *mut std::os::raw::c_char) };
-    result
+        unsafe { ffi::adblock_free_string(metrics_ptr as *mut std::os::raw::c_char) };
+        result
+    })
 }
@@ -0,0 +1,276 @@
+//! JSON response pruning
+//!
+//! Some sites deliver ads inside API responses, out of reach of request
+//! blocking. uBlock-style `example.com##+js(json-prune, ads playerAds.[].ad)`
+//! rules name the properties to delete; the layer intercepting XHR and
+//! fetch responses passes matching bodies to [`JsonPruneSet::prune_json`].
+//!
+//! Paths are dot-separated property names, where `*` matches every
+//! property of an object and `[]` every element of an array. An optional
+//! second argument lists paths that must all exist for the rule to apply.
+
+use crate::scriptlet::ScriptletRule;
+use serde_json::Value;
+use std::borrow::Cow;
+
+/// Scriptlet names of pruning rules
+const JSON_PRUNE_NAMES: &[&str] = &["json-prune", "json-prune.js"];
+
+/// A single `##+js(json-prune, ...)` rule
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct JsonPruneRule {
+    /// Paths of the properties to delete
+    pub paths: Vec<String>,
+    /// Paths that must all exist for the rule to apply
+    pub required: Vec<String>,
+    /// Domains the rule is limited to
+    scope: ScriptletRule,
+}
+
+impl JsonPruneRule {
+    /// Parse a rule like `example.com##+js(json-prune, ads, data)`
+    ///
+    /// Returns `None` for other scriptlets and rules without paths.
+    pub fn parse(line: &str) -> Option<Self> {
+        let scope = ScriptletRule::parse(line)?;
+        if !JSON_PRUNE_NAMES.contains(&scope.name.as_str()) {
+            return None;
+        }
+        let paths_arg = |index: usize| -> Vec<String> {
+            scope
+                .args
+                .get(index)
+                .map(|arg| arg.split_whitespace().map(str::to_string).collect())
+                .unwrap_or_default()
+        };
+        let paths = paths_arg(0);
+        let required = paths_arg(1);
+        if paths.is_empty() {
+            return None;
+        }
+        Some(JsonPruneRule {
+            paths,
+            required,
+            scope,
+        })
+    }
+
+    /// Check if the rule applies to a page domain
+    pub fn applies_to(&self, domain: &str) -> bool {
+        self.scope.applies_to(domain)
+    }
+
+    /// Delete the rule's paths from a value, returning whether any were deleted
+    pub fn prune(&self, value: &mut Value) -> bool {
+        if !self
+            .required
+            .iter()
+            .all(|path| path_exists(value, &segments(path)))
+        {
+            return false;
+        }
+        let mut pruned = false;
+        for path in &self.paths {
+            pruned |= prune_path(value, &segments(path));
+        }
+        pruned
+    }
+}
+
+fn segments(path: &str) -> Vec<&str> {
+    path.split('.')
+        .filter(|segment| !segment.is_empty())
+        .collect()
+}
+
+/// Values a path segment selects below a value
+fn children<'a>(value: &'a mut Value, segment: &str) -> Vec<&'a mut Value> {
+    match (value, segment) {
+        (Value::Array(items), "[]") => items.iter_mut().collect(),
+        (Value::Object(map), "*") => map.values_mut().collect(),
+        (Value::Object(map), key) => map.get_mut(key).into_iter().collect(),
+        (Value::Array(items), index) => index
+            .parse::<usize>()
+            .ok()
+            .and_then(|index| items.get_mut(index))
+            .into_iter()
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn path_exists(value: &Value, path: &[&str]) -> bool {
+    let Some((first, rest)) = path.split_first() else {
+        return true;
+    };
+    match (value, *first) {
+        (Value::Array(items), "[]") => items.iter().any(|item| path_exists(item, rest)),
+        (Value::Object(map), "*") => map.values().any(|child| path_exists(child, rest)),
+        (Value::Object(map), key) => map.get(key).is_some_and(|child| path_exists(child, rest)),
+        (Value::Array(items), index) => index
+            .parse::<usize>()
+            .ok()
+            .and_then(|index| items.get(index))
+            .is_some_and(|child| path_exists(child, rest)),
+        _ => false,
+    }
+}
+
+fn prune_path(value: &mut Value, path: &[&str]) -> bool {
+    match path {
+        [] => false,
+        [last] => match (value, *last) {
+            (Value::Array(items), "[]") => {
+                let pruned = !items.is_empty();
+                items.clear();
+                pruned
+            }
+            (Value::Object(map), "*") => {
+                let pruned = !map.is_empty();
+                map.clear();
+                pruned
+            }
+            (Value::Object(map), key) => map.remove(key).is_some(),
+            (Value::Array(items), index) => match index.parse::<usize>() {
+                Ok(index) if index < items.len() => {
+                    items.remove(index);
+                    true
+                }
+                _ => false,
+            },
+            _ => false,
+        },
+        [first, rest @ ..] => {
+            let mut pruned = false;
+            for child in children(value, first) {
+                pruned |= prune_path(child, rest);
+            }
+            pruned
+        }
+    }
+}
+
+/// Collection of JSON pruning rules
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct JsonPruneSet {
+    rules: Vec<JsonPruneRule>,
+}
+
+impl JsonPruneSet {
+    /// Create an empty set
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse and add a rule line, returning whether it was a pruning rule
+    pub fn add_rule(&mut self, line: &str) -> bool {
+        match JsonPruneRule::parse(line) {
+            Some(rule) => {
+                self.rules.push(rule);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove one rule equal to the parsed line, returning whether one was removed
+    pub fn remove_rule(&mut self, line: &str) -> bool {
+        let Some(rule) = JsonPruneRule::parse(line) else {
+            return false;
+        };
+        match self.rules.iter().position(|existing| *existing == rule) {
+            Some(index) => {
+                self.rules.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Prune a JSON response body for a page domain
+    ///
+    /// The body is returned as is if no rule applies, it isn't JSON or
+    /// nothing was deleted. Pruned bodies are re-serialized compactly,
+    /// with object keys in sorted order.
+    pub fn prune_json<'a>(&self, domain: &str, body: &'a str) -> Cow<'a, str> {
+        let rules: Vec<&JsonPruneRule> = self
+            .rules
+            .iter()
+            .filter(|rule| rule.applies_to(domain))
+            .collect();
+        if rules.is_empty() {
+            return Cow::Borrowed(body);
+        }
+        let Ok(mut value) = serde_json::from_str::<Value>(body) else {
+            return Cow::Borrowed(body);
+        };
+        let mut pruned = false;
+        for rule in rules {
+            pruned |= rule.prune(&mut value);
+        }
+        if !pruned {
+            return Cow::Borrowed(body);
+        }
+        match serde_json::to_string(&value) {
+            Ok(json) => Cow::Owned(json),
+            Err(_) => Cow::Borrowed(body),
+        }
+    }
+
+    /// All stored rules
+    pub fn rules(&self) -> &[JsonPruneRule] {
+        &self.rules
+    }
+
+    /// Number of stored rules
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    /// Whether the set is empty
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prune_json_paths() {
+        let mut set = JsonPruneSet::new();
+        assert!(set.add_rule("example.com##+js(json-prune, adPlacements playerAds.[].ad)"));
+        assert!(set.add_rule("example.com##+js(json-prune, data.*.sponsored, data)"));
+        assert!(!set.add_rule("example.com##+js(set-constant, ads, false)"));
+        assert!(!set.add_rule("example.com##+js(json-prune)"));
+
+        let body = r#"{"adPlacements":[1],"data":{"a":{"sponsored":true,"id":1}},"playerAds":[{"ad":1,"id":2}],"video":"v"}"#;
+        assert_eq!(
+            set.prune_json("www.example.com", body),
+            r#"{"data":{"a":{"id":1}},"playerAds":[{"id":2}],"video":"v"}"#
+        );
+        assert!(matches!(
+            set.prune_json("other.com", body),
+            Cow::Borrowed(_)
+        ));
+        assert!(matches!(
+            set.prune_json("example.com", r#"{"video":"v"}"#),
+            Cow::Borrowed(_)
+        ));
+        assert!(matches!(
+            set.prune_json("example.com", "not json"),
+            Cow::Borrowed(_)
+        ));
+    }
+
+    #[test]
+    fn test_required_paths() {
+        let rule = JsonPruneRule::parse("##+js(json-prune, ads, meta.hasAds)").unwrap();
+        let mut without = serde_json::json!({"ads": [1], "meta": {}});
+        assert!(!rule.prune(&mut without));
+        let mut with = serde_json::json!({"ads": [1], "meta": {"hasAds": true}});
+        assert!(rule.prune(&mut with));
+        assert_eq!(with, serde_json::json!({"meta": {"hasAds": true}}));
+    }
+}
@@ -2,34 +2,195 @@
 //!
 //! This crate provides the core filtering engine for the AdBlock application,
 //! supporting both Android and iOS platforms through FFI bindings.
+//!
+//! Everything is built by the default `full` feature. With default features
+//! off only [`domain_filter`] remains, for DNS filters on constrained devices.
 
 #![allow(non_snake_case)]
 
+#[cfg(feature = "full")]
 pub mod analytics;
+#[cfg(feature = "async")]
+pub mod async_engine;
+#[cfg(feature = "full")]
+pub mod audit;
+#[cfg(feature = "full")]
 pub mod backup;
+#[cfg(feature = "bundled-filters")]
+pub mod bundled_filters;
+#[cfg(feature = "full")]
+pub mod category_filter;
+#[cfg(feature = "full")]
+pub mod checksum;
+#[cfg(feature = "full")]
+pub mod compare;
+#[cfg(feature = "full")]
+pub mod compiled;
+#[cfg(feature = "full")]
+pub mod compression;
+#[cfg(feature = "full")]
+pub mod config;
+#[cfg(feature = "full")]
+pub mod content_blocker;
+#[cfg(feature = "full")]
+pub mod cosmetic;
+#[cfg(feature = "full")]
 pub mod crash_reporter;
+#[cfg(feature = "full")]
+pub mod csp;
+#[cfg(feature = "full")]
+pub mod dnr_export;
+#[cfg(feature = "full")]
+pub mod dns_server;
+#[cfg(feature = "full")]
+pub mod domain_age;
+pub mod domain_filter;
+#[cfg(feature = "full")]
+pub mod entity;
+#[cfg(feature = "full")]
+pub mod event_bus;
+#[cfg(feature = "full")]
 pub mod ffi;
+#[cfg(feature = "full")]
 pub mod filter_engine;
+#[cfg(feature = "full")]
 pub mod filter_list;
+#[cfg(feature = "full")]
+pub mod filter_registry;
+#[cfg(feature = "full")]
 pub mod filter_updater;
-#[cfg(target_os = "android")]
+#[cfg(feature = "full")]
+pub mod handle_registry;
+#[cfg(feature = "full")]
+pub mod hash_list;
+#[cfg(feature = "html-filter")]
+pub mod html_filter;
+#[cfg(feature = "full")]
+pub mod idn;
+#[cfg(all(target_os = "android", feature = "full"))]
 pub mod jni;
+#[cfg(feature = "full")]
+pub mod json_prune;
+#[cfg(feature = "full")]
+pub mod lint;
+#[cfg(feature = "full")]
+pub mod list_catalog;
+#[cfg(feature = "full")]
+pub mod logging;
+#[cfg(feature = "full")]
+pub mod maintenance;
+#[cfg(feature = "full")]
+pub mod media;
+#[cfg(feature = "full")]
 pub mod memory_optimization;
+#[cfg(feature = "full")]
 pub mod metrics;
+#[cfg(feature = "full")]
 pub mod network;
+#[cfg(feature = "full")]
+pub mod packet;
+#[cfg(feature = "full")]
+pub mod pause;
+#[cfg(feature = "full")]
+pub mod procedural;
+#[cfg(feature = "proxy")]
+pub mod proxy;
+#[cfg(feature = "full")]
+pub mod public_suffix;
+#[cfg(feature = "full")]
+pub mod query_log;
+#[cfg(feature = "full")]
+pub mod removeparam;
+#[cfg(feature = "full")]
+pub mod resource_type;
+#[cfg(feature = "full")]
+pub mod resources;
+#[cfg(feature = "full")]
+pub mod rule_hits;
+#[cfg(feature = "full")]
+pub mod rule_import;
+#[cfg(feature = "full")]
+pub mod rule_set;
+#[cfg(feature = "full")]
 pub mod rules;
+#[cfg(feature = "full")]
+pub mod scriptlet;
+#[cfg(feature = "full")]
+pub mod site_policy;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_storage;
+#[cfg(feature = "full")]
 pub mod statistics;
+#[cfg(feature = "full")]
+pub mod storage;
+#[cfg(feature = "full")]
+pub mod subscription;
+#[cfg(feature = "full")]
+pub mod token_index;
+#[cfg(feature = "full")]
+pub mod tracker_category;
+#[cfg(all(feature = "full", feature = "uniffi"))]
+pub mod uniffi_bindings;
+#[cfg(feature = "full")]
+pub mod update_scheduler;
+#[cfg(feature = "full")]
 pub mod utils;
+#[cfg(feature = "full")]
+pub mod verdict_cache;
+#[cfg(feature = "full")]
+pub mod worker_pool;
+
+#[cfg(all(feature = "full", feature = "uniffi"))]
+uniffi::setup_scaffolding!("adblock");
 
-pub use filter_engine::{BlockDecision, FilterEngine};
+#[cfg(feature = "full")]
+pub use filter_engine::{BlockDecision, BlockKind, Explanation, FilterEngine, RuleMatch, Verdict};
+#[cfg(feature = "full")]
 pub use filter_list::FilterListLoader;
-pub use filter_updater::{FilterUpdater, UpdateConfig};
-pub use statistics::{BlockEvent, DomainStats, Statistics};
+#[cfg(feature = "full")]
+pub use filter_updater::{FilterUpdater, UpdateConfig, UpdatePolicy};
+#[cfg(feature = "full")]
+pub use statistics::{BlockEvent, DomainStats, SessionStats, SharedStatistics, Statistics};
+
+#[cfg(feature = "full")]
+/// Built-in sets of filter lists
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterPreset {
+    /// EasyList only
+    Basic,
+    /// EasyList and EasyPrivacy
+    Standard,
+    /// Standard plus the Fanboy annoyance list
+    Strict,
+}
+
+#[cfg(feature = "full")]
+impl FilterPreset {
+    /// Filter list URLs of the preset
+    pub fn urls(&self) -> Vec<&'static str> {
+        const EASYLIST: &str = "https://easylist.to/easylist/easylist.txt";
+        const EASYPRIVACY: &str = "https://easylist.to/easylist/easyprivacy.txt";
+        const ANNOYANCE: &str = "https://secure.fanboy.co.nz/fanboy-annoyance.txt";
+
+        match self {
+            FilterPreset::Basic => vec![EASYLIST],
+            FilterPreset::Standard => vec![EASYLIST, EASYPRIVACY],
+            FilterPreset::Strict => vec![EASYLIST, EASYPRIVACY, ANNOYANCE],
+        }
+    }
+}
 
+#[cfg(feature = "full")]
 /// Core configuration for the ad blocking engine
+///
+/// Fields missing from persisted JSON take their default values.
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
 pub struct Config {
-    /// Enable verbose logging
+    /// Persisted format version (see [`config::CONFIG_VERSION`])
+    pub version: u32,
+    /// Keep debug records in the engine log (see [`logging`])
     pub debug: bool,
     /// Maximum memory usage in MB
     pub max_memory_mb: usize,
@@ -39,11 +200,34 @@ pub struct Config {
     pub filter_lists: Vec<String>,
     /// Path to custom filter rules file
     pub custom_rules_path: Option<String>,
+    /// Directory for downloaded filter list caches
+    pub cache_dir: Option<String>,
+    /// Domains (and their subdomains) whose requests are never blocked
+    pub whitelist: Vec<String>,
+    /// Blocking mode of individual sites
+    pub site_policy: site_policy::SitePolicy,
+    /// Recurring windows during which blocking is paused
+    pub pause_schedule: Vec<pause::PauseWindow>,
+    /// How much of the browsing trail statistics keep
+    pub statistics_privacy: statistics::StatisticsPrivacy,
+    /// Preset whose lists are loaded in addition to `filter_lists`
+    pub preset: Option<FilterPreset>,
+    /// IDs of the enabled lists of the built-in
+    /// [`ListCatalog`](list_catalog::ListCatalog)
+    pub list_presets: Vec<String>,
+    /// Platform directory for persisted crash reports; kept in memory if unset
+    pub crash_reports_dir: Option<String>,
+    /// Platform directory for analytics batches awaiting upload
+    pub analytics_dir: Option<String>,
+    /// Record anonymous usage analytics (opt-in)
+    pub analytics_enabled: bool,
 }
 
+#[cfg(feature = "full")]
 impl Default for Config {
     fn default() -> Self {
         Self {
+            version: config::CONFIG_VERSION,
             debug: false,
             max_memory_mb: 30,
             update_interval: 86400, // 24 hours
@@ -52,98 +236,1208 @@ impl Default for Config {
                 "https://easylist.to/easylist/easyprivacy.txt".to_string(),
             ],
             custom_rules_path: None,
+            cache_dir: None,
+            whitelist: Vec::new(),
+            site_policy: site_policy::SitePolicy::new(),
+            pause_schedule: Vec::new(),
+            statistics_privacy: statistics::StatisticsPrivacy::default(),
+            preset: None,
+            list_presets: Vec::new(),
+            crash_reports_dir: None,
+            analytics_dir: None,
+            analytics_enabled: false,
         }
     }
 }
 
+#[cfg(feature = "full")]
+impl Config {
+    /// Filter list URLs from the presets and `filter_lists`, without duplicates
+    ///
+    /// Catalog lists contribute their primary URL.
+    pub fn effective_filter_lists(&self) -> Vec<String> {
+        let mut urls: Vec<String> = Vec::new();
+        let preset_urls = self.preset.map(|preset| preset.urls()).unwrap_or_default();
+        let catalog = list_catalog::ListCatalog::builtin();
+        let list_preset_urls = self
+            .list_presets
+            .iter()
+            .filter_map(|id| catalog.get(id))
+            .map(|list| list.url());
+        for url in preset_urls
+            .into_iter()
+            .chain(list_preset_urls)
+            .chain(self.filter_lists.iter().map(String::as_str))
+        {
+            if !urls.iter().any(|existing| existing == url) {
+                urls.push(url.to_string());
+            }
+        }
+        urls
+    }
+
+    /// `max_memory_mb` in bytes
+    pub fn memory_limit_bytes(&self) -> usize {
+        self.max_memory_mb.saturating_mul(1024 * 1024)
+    }
+
+    /// Updater settings for the configured lists
+    ///
+    /// Catalog lists are passed by ID so the updater can fall back to
+    /// their mirrors.
+    pub fn update_config(&self) -> UpdateConfig {
+        let catalog = list_catalog::ListCatalog::builtin();
+        let mut urls = self.effective_filter_lists();
+        urls.retain(|url| {
+            catalog
+                .find_by_url(url)
+                .is_none_or(|list| !self.list_presets.contains(&list.id))
+        });
+        UpdateConfig {
+            urls,
+            presets: self.list_presets.clone(),
+            mirrors: std::collections::HashMap::new(),
+            update_interval: std::time::Duration::from_secs(self.update_interval),
+            cache_dir: self.cache_dir.as_ref().map(std::path::PathBuf::from),
+        }
+    }
+
+    /// Whether requests to a host are whitelisted
+    pub fn is_whitelisted(&self, host: &str) -> bool {
+        let host = host.split(':').next().unwrap_or(host);
+        self.whitelist.iter().any(|domain| {
+            let domain = domain.trim().trim_start_matches("*.");
+            host == domain
+                || host
+                    .strip_suffix(domain)
+                    .is_some_and(|prefix| prefix.ends_with('.'))
+        })
+    }
+}
+
+#[cfg(feature = "full")]
 /// Main entry point for the ad blocking engine
+///
+/// `AdBlockCore` is `Sync`: requests can be checked through `&self` from
+/// many threads at once, with statistics recorded without a global lock.
+/// Methods taking `&mut self` reconfigure the core and need exclusive
+/// access, e.g. through an `RwLock`.
 pub struct AdBlockCore {
-    engine: std::sync::Arc<FilterEngine>,
-    statistics: std::sync::Mutex<Statistics>,
-    #[allow(dead_code)]
+    /// Active engine, swapped as a whole when the rules are reloaded and
+    /// shared with the maintenance tasks
+    engine: EngineSlot,
+    statistics: std::sync::Arc<SharedStatistics>,
+    events: std::sync::Arc<event_bus::EventBus>,
     config: Config,
+    /// User rules applied on top of every loaded filter list
+    custom_rules: Vec<String>,
+    /// Automatic backups after config changes and statistic milestones
+    auto_backup: Option<std::sync::Arc<backup::BackupManager>>,
+    /// Writes milestone backups off the checking threads, one at a time
+    backup_worker: Option<worker_pool::WorkerPool>,
+    /// Requests checked through `check_url`, timing the periodic checks
+    checked_requests: std::sync::atomic::AtomicU64,
+    crash_reporter: std::sync::Arc<crash_reporter::CrashReporter>,
+    analytics: std::sync::Arc<analytics::Analytics>,
+    /// Publishes memory pressure when `Config::max_memory_mb` is exceeded
+    memory: memory_optimization::MemoryOptimizer,
+    /// Manual "disable for a while" pause
+    pause: pause::PauseState,
+    /// Content category blocking applied to every engine swapped in
+    category_filter: Option<std::sync::Arc<category_filter::CategoryFilter>>,
+    /// Log of checked requests for the activity screen (disabled when `None`),
+    /// locked so requests can be checked through `&self`
+    query_log: Option<parking_lot::Mutex<query_log::QueryLog>>,
+    /// Rules the engine was built from, shared with linked network filters
+    rule_source: rule_set::SharedRuleSet,
+    /// Called for every request checked through `check_url`
+    block_listener: parking_lot::RwLock<Option<std::sync::Arc<BlockListener>>>,
+    /// Runs deferred work while the device is idle; stopped on drop
+    maintenance: Option<maintenance::MaintenanceScheduler>,
+}
+
+#[cfg(feature = "full")]
+/// Slot holding the active engine
+type EngineSlot = std::sync::Arc<parking_lot::RwLock<std::sync::Arc<FilterEngine>>>;
+
+#[cfg(feature = "full")]
+/// Run `f` on the active engine unless checks in flight share it
+fn with_unshared_engine(
+    slot: &EngineSlot,
+    f: impl FnOnce(&mut FilterEngine),
+) -> Result<(), String> {
+    let mut engine = slot.write();
+    let engine = std::sync::Arc::get_mut(&mut engine).ok_or("Filter engine is shared")?;
+    f(engine);
+    Ok(())
+}
+
+#[cfg(feature = "full")]
+/// Count sampled rule hits on a core's engine, which its rule-reordering
+/// maintenance task sorts the rules by
+fn track_rule_hits(engine: &mut FilterEngine) {
+    if engine.rule_hits().is_none() {
+        engine.enable_rule_hit_tracking(rule_hits::DEFAULT_HIT_SAMPLE_RATE);
+    }
+}
+
+#[cfg(feature = "full")]
+/// Longest wait for the engine slot before a patch gives up as busy
+const ENGINE_PATCH_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(500);
+
+#[cfg(feature = "full")]
+/// Run `f` on the active engine, or on a copy swapped in for it if checks
+/// in flight share it
+fn patch_engine<R>(
+    slot: &EngineSlot,
+    f: impl FnOnce(&mut FilterEngine) -> R,
+) -> Result<R, RuleError> {
+    let mut engine = slot
+        .try_write_for(ENGINE_PATCH_TIMEOUT)
+        .ok_or(RuleError::Busy)?;
+    Ok(f(std::sync::Arc::make_mut(&mut engine)))
 }
 
+#[cfg(feature = "full")]
+/// Why rules couldn't be added to or removed from the active engine
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuleError {
+    /// A comment or a rule the linter reports as an error
+    Invalid { rule: String },
+    /// The engine stayed locked, e.g. by a swap; retrying may succeed
+    Busy,
+}
+
+#[cfg(feature = "full")]
+impl std::fmt::Display for RuleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RuleError::Invalid { rule } => write!(f, "Invalid rule: {rule}"),
+            RuleError::Busy => write!(f, "Filter engine is busy"),
+        }
+    }
+}
+
+#[cfg(feature = "full")]
+impl std::error::Error for RuleError {}
+
+#[cfg(feature = "full")]
+/// Observer of checked requests, e.g. for a live "X blocked" counter
+pub type BlockListener = dyn Fn(&BlockEvent) + Send + Sync;
+
+#[cfg(feature = "full")]
+/// Reason given for requests allowed while blocking is paused
+const PAUSED_REASON: &str = "Blocking paused";
+
+#[cfg(feature = "full")]
 impl AdBlockCore {
     /// Create a new instance with the given configuration
+    ///
+    /// Panics are recorded by this instance's crash reporter from now on.
     pub fn new(config: Config) -> Result<Self, Box<dyn std::error::Error>> {
         let engine = FilterEngine::new(&config)?;
+        let core = Self::with_engine(engine, config);
+        crash_reporter::install_panic_handler(std::sync::Arc::clone(&core.crash_reporter));
+        Ok(core)
+    }
+
+    /// Wrap an engine, subscribing statistics to memory pressure events and
+    /// the crash reporter and analytics to engine events
+    fn with_engine(mut engine: FilterEngine, config: Config) -> Self {
+        logging::init();
+        logging::set_debug(config.debug);
+        let statistics = std::sync::Arc::new(SharedStatistics::with_config(
+            statistics::StatisticsConfig {
+                privacy: config.statistics_privacy,
+                ..Default::default()
+            },
+        ));
+        let events = std::sync::Arc::new(event_bus::EventBus::new());
+
+        let mut reporter = crash_reporter::CrashReporter::new(config.crash_reports_dir.clone());
+        reporter.set_event_bus(std::sync::Arc::clone(&events));
+        reporter.set_filter_rules_count(engine.rule_count());
+        let reporter = std::sync::Arc::new(reporter);
+        // Weak, as the reporter holds the bus
+        let tracked = std::sync::Arc::downgrade(&reporter);
+        events.subscribe(move |event| {
+            let rule_count = match event {
+                event_bus::EngineEvent::RuleSetUpdated { rule_count }
+                | event_bus::EngineEvent::RuleSetPatched { rule_count, .. } => *rule_count,
+                _ => return,
+            };
+            if let Some(reporter) = tracked.upgrade() {
+                reporter.set_filter_rules_count(rule_count);
+            }
+        });
+
+        let stats = std::sync::Arc::clone(&statistics);
+        events.subscribe(move |event| {
+            if let event_bus::EngineEvent::MemoryPressure { .. } = event {
+                stats.update(Statistics::handle_memory_pressure);
+            }
+        });
 
-        Ok(Self {
-            engine: std::sync::Arc::new(engine),
-            statistics: std::sync::Mutex::new(Statistics::new()),
+        let mut memory = memory_optimization::MemoryOptimizer::new();
+        memory.set_max_memory(config.memory_limit_bytes());
+        memory.set_event_bus(std::sync::Arc::clone(&events));
+
+        let analytics = std::sync::Arc::new(match &config.analytics_dir {
+            Some(dir) => analytics::Analytics::with_batch_dir(dir),
+            None => analytics::Analytics::new(),
+        });
+        analytics.set_enabled(config.analytics_enabled);
+        analytics::events::subscribe_to(std::sync::Arc::clone(&analytics), &events);
+
+        track_rule_hits(&mut engine);
+        let engine = EngineSlot::new(parking_lot::RwLock::new(std::sync::Arc::new(engine)));
+        let maintenance = Self::start_maintenance(&engine, &analytics, &config);
+
+        Self {
+            engine,
+            statistics,
+            events,
             config,
-        })
+            custom_rules: Vec::new(),
+            auto_backup: None,
+            backup_worker: None,
+            checked_requests: std::sync::atomic::AtomicU64::new(0),
+            crash_reporter: reporter,
+            analytics,
+            memory,
+            pause: pause::PauseState::default(),
+            category_filter: None,
+            query_log: None,
+            rule_source: Default::default(),
+            block_listener: parking_lot::RwLock::new(None),
+            maintenance,
+        }
+    }
+
+    /// Register the core's deferred work and start running it once the
+    /// host reports the device idle
+    ///
+    /// Tasks that rebuild the engine fail while checks in flight share it
+    /// and are retried on the next pass.
+    fn start_maintenance(
+        engine: &EngineSlot,
+        analytics: &std::sync::Arc<analytics::Analytics>,
+        config: &Config,
+    ) -> Option<maintenance::MaintenanceScheduler> {
+        use maintenance::{Maintenance, MaintenanceKind, MaintenanceScheduler};
+        use std::time::Duration;
+
+        let mut tasks = Maintenance::new();
+        let slot = std::sync::Arc::clone(engine);
+        tasks.register(
+            "cache-compaction",
+            MaintenanceKind::CacheCompaction,
+            Duration::from_secs(10 * 60),
+            false,
+            move || {
+                if with_unshared_engine(&slot, |engine| {
+                    engine.compact();
+                })
+                .is_err()
+                {
+                    slot.read().release_caches();
+                }
+                Ok(())
+            },
+        );
+        if config.analytics_dir.is_some() {
+            let analytics = std::sync::Arc::clone(analytics);
+            tasks.register(
+                "analytics-flush",
+                MaintenanceKind::AnalyticsFlush,
+                Duration::from_secs(15 * 60),
+                false,
+                move || analytics.flush().map(drop).map_err(|e| e.to_string()),
+            );
+        }
+        let slot = std::sync::Arc::clone(engine);
+        tasks.register(
+            "rule-reordering",
+            MaintenanceKind::RuleReordering,
+            Duration::from_secs(6 * 3600),
+            true,
+            move || {
+                let mut reordered = false;
+                with_unshared_engine(&slot, |engine| {
+                    reordered = engine.reorder_rules_by_hits();
+                })?;
+                match reordered {
+                    true => Ok(()),
+                    false => Err("Rule hit tracking is disabled".to_string()),
+                }
+            },
+        );
+        // Rules added since the last build are checked for every URL
+        // until the matchers are rebuilt
+        let slot = std::sync::Arc::clone(engine);
+        tasks.register(
+            "engine-refresh",
+            MaintenanceKind::EngineRefresh,
+            Duration::from_secs(24 * 3600),
+            true,
+            move || with_unshared_engine(&slot, FilterEngine::build_domain_matcher),
+        );
+
+        match MaintenanceScheduler::start(tasks, maintenance::DEFAULT_MAINTENANCE_INTERVAL) {
+            Ok(scheduler) => Some(scheduler),
+            Err(e) => {
+                tracing::warn!("Failed to start maintenance: {e}");
+                None
+            }
+        }
+    }
+
+    /// Report whether the device is idle and charging, from the host's
+    /// idle and power callbacks
+    ///
+    /// Due maintenance tasks run in the background while the device is
+    /// idle; some also need it to be charging.
+    pub fn set_device_conditions(&self, conditions: maintenance::DeviceConditions) {
+        if let Some(scheduler) = &self.maintenance {
+            scheduler.set_conditions(conditions);
+        }
+    }
+
+    /// Run the due maintenance tasks now on the calling thread, under the
+    /// conditions last reported
+    pub fn run_maintenance(&self) -> maintenance::MaintenanceReport {
+        self.maintenance
+            .as_ref()
+            .map(maintenance::MaintenanceScheduler::run_once)
+            .unwrap_or_default()
+    }
+
+    /// Stop background maintenance, waiting for a running task to finish
+    ///
+    /// Dropping the core does the same.
+    pub fn stop_maintenance(&mut self) {
+        if let Some(mut scheduler) = self.maintenance.take() {
+            scheduler.stop();
+        }
+    }
+
+    /// Call `listener` for every request checked through `check_url`,
+    /// replacing any previous listener
+    ///
+    /// It runs on the checking thread after statistics are recorded, so it
+    /// should return quickly. Domains are reported as statistics store them.
+    pub fn set_event_listener(&self, listener: Box<BlockListener>) {
+        *self.block_listener.write() = Some(std::sync::Arc::from(listener));
+    }
+
+    /// Stop calling the event listener
+    pub fn clear_event_listener(&self) {
+        *self.block_listener.write() = None;
+    }
+
+    fn notify_listener(&self, decision: &BlockDecision, domain: &str, size: u64) {
+        // Not called under the lock, so the listener may replace itself
+        let Some(listener) = self.block_listener.read().clone() else {
+            return;
+        };
+        let category = match decision.kind {
+            Some(BlockKind::Malware) => tracker_category::TrackerCategory::Malware,
+            _ => tracker_category::current().category_of(domain),
+        };
+        listener(&BlockEvent {
+            timestamp: std::time::SystemTime::now(),
+            domain: self.statistics.stored_domain(domain).unwrap_or_default(),
+            blocked: decision.should_block,
+            size,
+            category,
+        });
     }
 
     /// Create a new instance with custom patterns
     pub fn with_patterns(patterns: Vec<String>) -> Result<Self, Box<dyn std::error::Error>> {
         let engine = FilterEngine::new_with_patterns(patterns);
-
-        Ok(Self {
-            engine: std::sync::Arc::new(engine),
-            statistics: std::sync::Mutex::new(Statistics::new()),
-            config: Config::default(),
-        })
+        Ok(Self::with_engine(engine, Config::default()))
     }
 
     /// Create a new instance from a filter list
     pub fn from_filter_list(filter_list: &str) -> Result<Self, Box<dyn std::error::Error>> {
-        let engine = FilterEngine::from_filter_list(filter_list)?;
+        let mut rule_set = rule_set::RuleSet::from_filter_list(filter_list);
+        let core = Self::with_engine(rule_set.build_engine()?, Config::default());
+        rule_set.drop_content();
+        *core.rule_source.write() = std::sync::Arc::new(rule_set);
+        Ok(core)
+    }
 
-        Ok(Self {
-            engine: std::sync::Arc::new(engine),
-            statistics: std::sync::Mutex::new(Statistics::new()),
-            config: Config::default(),
-        })
+    /// Replace the active filter rules with the rules from a filter list
+    ///
+    /// Statistics are kept across reloads.
+    pub fn load_filter_list(
+        &mut self,
+        filter_list: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.reload_rule_set(rule_set::RuleSet::from_filter_list(filter_list))
+    }
+
+    /// Replace the rules of both the URL engine and the linked network
+    /// filters
+    ///
+    /// This is the single reload path: the engine is built from the rule
+    /// set, which is then shared with every filter from `network_filter`.
+    pub fn reload_rule_set(
+        &self,
+        rule_set: rule_set::RuleSet,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let engine = rule_set.build_engine()?;
+        self.swap_rule_set(rule_set, engine);
+        Ok(())
+    }
+
+    /// Swap in an engine already built from `rule_set`, e.g. outside a lock
+    pub fn swap_rule_set(&self, mut rule_set: rule_set::RuleSet, engine: FilterEngine) {
+        rule_set.drop_content();
+        *self.rule_source.write() = std::sync::Arc::new(rule_set);
+        self.swap_engine(engine);
+    }
+
+    /// Rule set the active engine was built from
+    ///
+    /// Only list names and the domain-level view are kept; the list content
+    /// is dropped once the engine is built.
+    pub fn rule_set(&self) -> std::sync::Arc<rule_set::RuleSet> {
+        std::sync::Arc::clone(&self.rule_source.read())
+    }
+
+    /// DNS-level filter following this core's rules, whitelist and content
+    /// category filter
+    ///
+    /// Later rule reloads apply to the filter; whitelist changes need a
+    /// new one.
+    pub fn network_filter(&self) -> network::NetworkFilter {
+        let mut filter = network::NetworkFilter::new();
+        filter.set_rule_source(Some(std::sync::Arc::clone(&self.rule_source)));
+        filter.set_category_filter(self.category_filter.clone());
+        filter.load_from_rules(&self.custom_rules);
+        for domain in &self.config.whitelist {
+            filter.add_allowed_domain(domain);
+        }
+        filter
+    }
+
+    /// Build an engine from a filter list and swap it in
+    ///
+    /// The engine is built on the calling thread, so hosts call this from a
+    /// background thread of their own. Requests keep being checked against
+    /// the current engine while the new one is built; statistics and
+    /// configuration are kept.
+    pub fn reload_filters(&self, filter_list: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let rule_set = rule_set::RuleSet::from_filter_list(filter_list);
+        let engine = rule_set.build_engine()?;
+        self.swap_rule_set(rule_set, engine);
+        Ok(())
+    }
+
+    /// Build an engine from a filter list on a background thread and swap it
+    /// in
+    ///
+    /// `on_done` runs on that thread once the new rules are live, with the
+    /// number of active network rules, or with the reason the list couldn't
+    /// be built. A `RuleSetUpdated` event is published as for
+    /// [`reload_filters`](Self::reload_filters).
+    pub fn reload_filters_in_background(
+        self: &std::sync::Arc<Self>,
+        filter_list: String,
+        on_done: impl FnOnce(Result<usize, String>) + Send + 'static,
+    ) -> std::io::Result<std::thread::JoinHandle<()>> {
+        let core = std::sync::Arc::clone(self);
+        std::thread::Builder::new()
+            .name("adblock-reload".to_string())
+            .spawn(move || {
+                let result = core
+                    .reload_filters(&filter_list)
+                    .map(|()| core.engine().rule_count())
+                    .map_err(|e| e.to_string());
+                on_done(result);
+            })
+    }
+
+    /// Atomically replace the active engine
+    ///
+    /// Custom rules are added to the new engine first and its rule hits are
+    /// sampled. Checks already running finish against the previous engine.
+    pub fn swap_engine(&self, mut engine: FilterEngine) {
+        track_rule_hits(&mut engine);
+        if let Some(filter) = &self.category_filter {
+            engine.enable_category_filter(std::sync::Arc::clone(filter));
+        }
+        if !self.custom_rules.is_empty() {
+            engine.apply_diff(&filter_updater::FilterListDiff {
+                added: self.custom_rules.clone(),
+                removed: Vec::new(),
+            });
+        }
+        if engine.memory_usage() > self.config.memory_limit_bytes() {
+            engine.compact();
+        }
+        let rule_count = engine.rule_count();
+        *self.engine.write() = std::sync::Arc::new(engine);
+        self.events
+            .publish(&event_bus::EngineEvent::RuleSetUpdated { rule_count });
+    }
+
+    /// Apply a filter list diff to the active rules without a full rebuild
+    ///
+    /// Publishes a `RuleSetPatched` event with the added and removed counts.
+    pub fn apply_filter_diff(
+        &mut self,
+        diff: &filter_updater::FilterListDiff,
+    ) -> Result<(), RuleError> {
+        let (removed, rule_count) = patch_engine(&self.engine, |engine| {
+            (engine.apply_diff(diff), engine.rule_count())
+        })?;
+        let mut rule_set = (*self.rule_set()).clone();
+        rule_set.apply_diff(diff);
+        *self.rule_source.write() = std::sync::Arc::new(rule_set);
+        self.events
+            .publish(&event_bus::EngineEvent::RuleSetPatched {
+                added: diff.added.len(),
+                removed,
+                rule_count,
+            });
+        Ok(())
+    }
+
+    /// Set or clear content category blocking
+    ///
+    /// The filter is applied to the active engine and kept for engines
+    /// swapped in later.
+    pub fn set_category_filter(
+        &mut self,
+        filter: Option<std::sync::Arc<category_filter::CategoryFilter>>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        patch_engine(&self.engine, |engine| match &filter {
+            Some(filter) => engine.enable_category_filter(std::sync::Arc::clone(filter)),
+            None => engine.disable_category_filter(),
+        })?;
+        self.category_filter = filter;
+        Ok(())
+    }
+
+    /// Check if a URL should be blocked without tracking statistics
+    pub fn should_block(&self, url: &str) -> BlockDecision {
+        self.decide(url, &resource_type::RequestContext::from_url(url))
+    }
+
+    /// Check if a request of a known resource type should be blocked
+    /// without tracking statistics
+    pub fn should_block_with_context(
+        &self,
+        url: &str,
+        context: &resource_type::RequestContext,
+    ) -> BlockDecision {
+        self.decide(url, context)
+    }
+
+    /// Engine decision, overridden while paused and for whitelisted hosts
+    /// and sites with blocking turned off
+    ///
+    /// The site is that of `context.document_url`, or of the URL itself
+    /// for top-level document requests.
+    fn decide(&self, url: &str, context: &resource_type::RequestContext) -> BlockDecision {
+        if self.is_paused() {
+            return BlockDecision {
+                should_block: false,
+                reason: Some(PAUSED_REASON.to_string()),
+                redirect_resource: None,
+                kind: None,
+                verdict: Verdict::Allow,
+            };
+        }
+        if self.config.is_whitelisted(&utils::extract_domain(url)) {
+            return BlockDecision {
+                should_block: false,
+                reason: Some("Whitelisted".to_string()),
+                redirect_resource: None,
+                kind: None,
+                verdict: Verdict::Allow,
+            };
+        }
+        let document_url = match &context.document_url {
+            Some(document_url) => Some(document_url.as_str()),
+            None if context.resource_type == resource_type::ResourceType::Document => Some(url),
+            None => None,
+        };
+        if let Some(document_url) = document_url {
+            if !self
+                .site_mode(&utils::extract_domain(document_url))
+                .blocks_requests()
+            {
+                return BlockDecision {
+                    should_block: false,
+                    reason: Some("Blocking disabled for site".to_string()),
+                    redirect_resource: None,
+                    kind: None,
+                    verdict: Verdict::Allow,
+                };
+            }
+        }
+        self.engine().should_block_with_context(url, context)
     }
 
     /// Check if a URL should be blocked and track statistics
-    pub fn check_url(&mut self, url: &str, size: u64) -> BlockDecision {
-        let decision = self.engine.should_block(url);
+    pub fn check_url(&self, url: &str, size: u64) -> BlockDecision {
+        self.check_url_from(url, size, None)
+    }
+
+    /// Check a URL requested by an app or package, tracking statistics
+    ///
+    /// The app is recorded in the query log.
+    pub fn check_url_for_app(&self, url: &str, size: u64, app: &str) -> BlockDecision {
+        self.check_url_from(url, size, Some(app))
+    }
+
+    /// Check a request of a known resource type and page, tracking statistics
+    pub fn check_request(
+        &self,
+        url: &str,
+        context: &resource_type::RequestContext,
+        size: u64,
+    ) -> BlockDecision {
+        self.track_check(url, self.decide(url, context), size, None)
+    }
 
+    fn check_url_from(&self, url: &str, size: u64, app: Option<&str>) -> BlockDecision {
+        let decision = self.decide(url, &resource_type::RequestContext::from_url(url));
+        self.track_check(url, decision, size, app)
+    }
+
+    /// Record a checked request in statistics, the query log and listeners
+    fn track_check(
+        &self,
+        url: &str,
+        decision: BlockDecision,
+        size: u64,
+        app: Option<&str>,
+    ) -> BlockDecision {
         // Extract domain from URL for statistics
         let domain = utils::extract_domain(url);
 
         // Track statistics
-        self.track_decision(&decision, &domain, size);
+        let blocked_count = self.track_decision(&decision, &domain, size);
+        self.log_query(&decision, &domain, app);
+        self.notify_listener(&decision, &domain, size);
+        if blocked_count.is_some_and(|count| count % backup::BLOCKED_MILESTONE == 0) {
+            self.queue_auto_backup();
+        }
+        let requests = self
+            .checked_requests
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+            + 1;
+        if requests.is_multiple_of(analytics::PERFORMANCE_CHECK_INTERVAL) {
+            self.check_match_time();
+        }
+        if requests.is_multiple_of(memory_optimization::MEMORY_CHECK_INTERVAL) {
+            self.enforce_memory_limit();
+        }
 
         decision
     }
 
+    /// Report a slow average match time
+    fn check_match_time(&self) {
+        if !self.analytics.is_enabled() {
+            return;
+        }
+        let metrics = self.engine().get_metrics().snapshot();
+        if metrics.avg_processing_time_ns > analytics::SLOW_MATCH_THRESHOLD_NS {
+            analytics::events::performance_warning(
+                &self.analytics,
+                "avg_match_time_ns",
+                metrics.avg_processing_time_ns as f64,
+            );
+        }
+    }
+
+    /// Free caches and publish a `MemoryPressure` event
+    ///
+    /// Call when the platform reports low memory. Returns the engine's
+    /// estimated memory usage afterwards.
+    pub fn handle_memory_pressure(&self) -> usize {
+        let engine = self.engine();
+        engine.release_caches();
+        self.memory.handle_memory_pressure();
+        engine.memory_usage()
+    }
+
+    /// Bring the engine under `Config::max_memory_mb` if possible
+    ///
+    /// Caches are dropped first; the rule storage is compacted only if the
+    /// engine is not shared with checks in flight. Returns whether the
+    /// engine fits the limit afterwards.
+    pub fn enforce_memory_limit(&self) -> bool {
+        let limit = self.config.memory_limit_bytes();
+        if self.engine().memory_usage() <= limit {
+            return true;
+        }
+        let mut usage = self.handle_memory_pressure();
+        if usage > limit {
+            if let Some(engine) = std::sync::Arc::get_mut(&mut self.engine.write()) {
+                engine.compact();
+                usage = engine.memory_usage();
+            }
+        }
+        if usage > limit {
+            tracing::warn!(
+                "Filter engine uses {}KB, above the {}MB limit",
+                usage / 1024,
+                self.config.max_memory_mb
+            );
+        }
+        usage <= limit
+    }
+
     /// Track the blocking decision in statistics
-    fn track_decision(&self, decision: &BlockDecision, domain: &str, size: u64) {
-        if let Ok(mut stats) = self.statistics.lock() {
-            if decision.should_block {
-                stats.record_blocked(domain, size);
-            } else {
-                stats.record_allowed(domain, size);
+    ///
+    /// Returns the new blocked count if the request was blocked.
+    fn track_decision(&self, decision: &BlockDecision, domain: &str, size: u64) -> Option<u64> {
+        let stats = &self.statistics;
+        if decision.reason.as_deref() == Some(PAUSED_REASON) {
+            stats.record_paused(domain, size);
+            None
+        } else if decision.kind == Some(BlockKind::Malware) {
+            stats.record_malware(domain, size);
+            Some(stats.get_blocked_count())
+        } else if let Some(BlockKind::Category(category)) = decision.kind {
+            stats.record_category_blocked(domain, size, category);
+            Some(stats.get_blocked_count())
+        } else if decision.should_block {
+            stats.record_blocked(domain, size);
+            Some(stats.get_blocked_count())
+        } else {
+            stats.record_allowed(domain, size);
+            None
+        }
+    }
+
+    /// Record a decision in the query log, honoring the statistics privacy
+    ///
+    /// Nothing is logged in `CountsOnly` mode. With hashed domains the
+    /// reason is left out too, as the matched rule usually names the domain.
+    fn log_query(&self, decision: &BlockDecision, domain: &str, app: Option<&str>) {
+        let Some(log) = &self.query_log else {
+            return;
+        };
+        let stats = &self.statistics;
+        let Some(stored) = stats.stored_domain(domain) else {
+            return;
+        };
+        let verdict = match decision.kind {
+            _ if decision.reason.as_deref() == Some(PAUSED_REASON) => {
+                query_log::QueryVerdict::Paused
+            }
+            _ if !decision.should_block => query_log::QueryVerdict::Allowed,
+            Some(BlockKind::Malware) => query_log::QueryVerdict::Malware,
+            Some(BlockKind::Category(_)) => query_log::QueryVerdict::Category,
+            _ => query_log::QueryVerdict::Blocked,
+        };
+        let mut entry = query_log::QueryLogEntry::now(&stored, verdict);
+        if stats.privacy() == statistics::StatisticsPrivacy::Full {
+            entry.reason = decision.reason.clone();
+        }
+        entry.app = app.map(str::to_string);
+        if let Err(e) = log.lock().record(entry) {
+            tracing::warn!("Failed to write query log: {e}");
+        }
+    }
+
+    /// Start logging checked requests, to a size-capped file if `path` is set
+    pub fn enable_query_log(
+        &mut self,
+        path: Option<&std::path::Path>,
+        max_bytes: u64,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let log = match path {
+            Some(path) => query_log::QueryLog::open(path, max_bytes)?,
+            None => query_log::QueryLog::in_memory(max_bytes),
+        };
+        self.query_log = Some(parking_lot::Mutex::new(log));
+        Ok(())
+    }
+
+    /// Start logging checked requests to a storage backend
+    pub fn enable_query_log_storage(
+        &mut self,
+        storage: Box<dyn storage::Storage>,
+        max_entries: usize,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let log = query_log::QueryLog::with_storage(storage, max_entries)?;
+        self.query_log = Some(parking_lot::Mutex::new(log));
+        Ok(())
+    }
+
+    /// Stop logging checked requests; a log file is left in place
+    pub fn disable_query_log(&mut self) {
+        self.query_log = None;
+    }
+
+    /// Get the query log, if enabled
+    ///
+    /// Requests checked meanwhile wait to be logged until the guard is dropped.
+    pub fn query_log(&self) -> Option<parking_lot::MutexGuard<'_, query_log::QueryLog>> {
+        self.query_log.as_ref().map(|log| log.lock())
+    }
+
+    /// Remove every query log entry
+    pub fn clear_query_log(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        match &mut self.query_log {
+            Some(log) => log.get_mut().clear(),
+            None => Ok(()),
+        }
+    }
+
+    /// Back up automatically after config changes and statistic milestones
+    ///
+    /// Milestone backups are written on a background thread.
+    pub fn set_auto_backup(&mut self, manager: Option<backup::BackupManager>) {
+        self.backup_worker = match (&manager, self.backup_worker.take()) {
+            (None, _) => None,
+            (Some(_), Some(worker)) => Some(worker),
+            (Some(_), None) => worker_pool::WorkerPool::new("adblock-backup", 1, 1)
+                .inspect_err(|e| tracing::warn!("Failed to start the backup thread: {e}"))
+                .ok(),
+        };
+        self.auto_backup = manager.map(std::sync::Arc::new);
+    }
+
+    /// Create an automatic backup if enabled, logging failures
+    fn run_auto_backup(&self) {
+        if let Some(manager) = &self.auto_backup {
+            let statistics = self.get_statistics();
+            if let Err(e) = manager.create_auto_backup(
+                self.config.clone(),
+                self.custom_rules.clone(),
+                &statistics,
+            ) {
+                tracing::warn!("Automatic backup failed: {e}");
             }
         }
     }
 
+    /// Create an automatic backup of the current state on the backup
+    /// thread, skipping it if one is still waiting to be written
+    fn queue_auto_backup(&self) {
+        let (Some(manager), Some(worker)) = (&self.auto_backup, &self.backup_worker) else {
+            return;
+        };
+        let manager = std::sync::Arc::clone(manager);
+        let config = self.config.clone();
+        let custom_rules = self.custom_rules.clone();
+        let statistics = self.get_statistics();
+        let queued = worker.try_execute(move || {
+            if let Err(e) = manager.create_auto_backup(config, custom_rules, &statistics) {
+                tracing::warn!("Automatic backup failed: {e}");
+            }
+        });
+        if !queued {
+            tracing::debug!("Skipped a milestone backup while another is pending");
+        }
+    }
+
+    /// Persist the lifetime statistics to a storage backend
+    pub fn save_statistics(
+        &self,
+        storage: &mut dyn storage::Storage,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.statistics.snapshot().save_to(storage)
+    }
+
+    /// Restore the lifetime statistics from a storage backend, returning
+    /// whether any were stored
+    pub fn load_statistics(
+        &self,
+        storage: &dyn storage::Storage,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        self.statistics.update(|stats| stats.load_from(storage))
+    }
+
     /// Get a copy of current statistics
     pub fn get_statistics(&self) -> Statistics {
-        self.statistics
-            .lock()
-            .map(|stats| stats.clone())
-            .unwrap_or_else(|_| Statistics::new())
+        self.statistics.snapshot()
+    }
+
+    /// Blocked requests to a host, e.g. for a site details screen
+    pub fn domain_stats(&self, domain: &str) -> Option<DomainStats> {
+        self.statistics.domain_stats(domain)
+    }
+
+    /// Page of the blocked domains, most blocked first
+    pub fn top_blocked(&self, offset: usize, limit: usize) -> Vec<DomainStats> {
+        self.statistics.top_blocked(offset, limit)
+    }
+
+    /// Live statistics, for servers such as `dns_server::DnsServer` that
+    /// record requests outside `check_url`
+    pub fn shared_statistics(&self) -> std::sync::Arc<SharedStatistics> {
+        std::sync::Arc::clone(&self.statistics)
+    }
+
+    /// Start a new statistics session, e.g. when the VPN connects
+    pub fn start_statistics_session(&self) {
+        self.statistics.start_session();
     }
 
     /// Reset statistics
     pub fn reset_statistics(&self) {
-        if let Ok(mut stats) = self.statistics.lock() {
-            stats.reset();
+        self.statistics.reset();
+    }
+
+    /// Get the active filter engine
+    pub fn engine(&self) -> std::sync::Arc<FilterEngine> {
+        std::sync::Arc::clone(&self.engine.read())
+    }
+
+    /// Active configuration
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Replace the configuration after validating it
+    pub fn update_config(&mut self, config: Config) -> Result<(), Vec<config::ConfigError>> {
+        config.validate()?;
+        self.config = config;
+        self.apply_config();
+        self.run_auto_backup();
+        Ok(())
+    }
+
+    /// Enable or disable a list of the built-in catalog by ID
+    ///
+    /// The list is downloaded on the next update.
+    pub fn set_list_preset_enabled(
+        &mut self,
+        id: &str,
+        enabled: bool,
+    ) -> Result<(), Vec<config::ConfigError>> {
+        let mut config = self.config.clone();
+        config.list_presets.retain(|listed| listed != id);
+        if enabled {
+            config.list_presets.push(id.to_string());
         }
+        self.update_config(config)
     }
 
-    /// Get a reference to the filter engine
-    pub fn engine(&self) -> &FilterEngine {
-        &self.engine
+    /// Apply logging, analytics, memory and statistics settings of a
+    /// replaced config
+    fn apply_config(&mut self) {
+        logging::set_debug(self.config.debug);
+        self.analytics.set_enabled(self.config.analytics_enabled);
+        let privacy = self.config.statistics_privacy;
+        let privacy_changed = self.statistics.update(|stats| {
+            let changed = stats.privacy() != privacy;
+            stats.set_privacy(privacy);
+            changed
+        });
+        // Logged domains can't be hashed after the fact
+        if privacy_changed && privacy != statistics::StatisticsPrivacy::Full {
+            if let Err(e) = self.clear_query_log() {
+                tracing::warn!("Failed to clear query log: {e}");
+            }
+        }
+        self.memory.set_max_memory(self.config.memory_limit_bytes());
+        self.enforce_memory_limit();
+    }
+
+    /// User rules applied on top of the loaded filter lists
+    pub fn custom_rules(&self) -> &[String] {
+        &self.custom_rules
+    }
+
+    /// Replace the user rules, patching the active engine
+    pub fn set_custom_rules(&mut self, rules: Vec<String>) -> Result<(), RuleError> {
+        let diff = filter_updater::FilterListDiff::compute(
+            &self.custom_rules.join("\n"),
+            &rules.join("\n"),
+        );
+        self.apply_filter_diff(&diff)?;
+        self.custom_rules = rules;
+        self.run_auto_backup();
+        Ok(())
+    }
+
+    /// Add one user rule, patching the active engine
+    ///
+    /// Returns `false` if it already is a user rule. Fails for comments and
+    /// rules the linter reports as errors.
+    pub fn add_custom_rule(&mut self, rule: &str) -> Result<bool, RuleError> {
+        let rule = rule.trim();
+        let invalid = lint::lint(rule)
+            .iter()
+            .any(|diagnostic| diagnostic.severity == lint::LintSeverity::Error);
+        if filter_list::is_skipped_line(rule) || invalid {
+            return Err(RuleError::Invalid {
+                rule: rule.to_string(),
+            });
+        }
+        if self.custom_rules.iter().any(|existing| existing == rule) {
+            return Ok(false);
+        }
+        let mut rules = self.custom_rules.clone();
+        rules.push(rule.to_string());
+        self.set_custom_rules(rules)?;
+        Ok(true)
+    }
+
+    /// Remove one user rule, returning `false` if it is not a user rule
+    pub fn remove_custom_rule(&mut self, rule: &str) -> Result<bool, RuleError> {
+        let rule = rule.trim();
+        let Some(index) = self
+            .custom_rules
+            .iter()
+            .position(|existing| existing == rule)
+        else {
+            return Ok(false);
+        };
+        let mut rules = self.custom_rules.clone();
+        rules.remove(index);
+        self.set_custom_rules(rules)?;
+        Ok(true)
+    }
+
+    /// Number of network rules in the active engine, user rules included
+    pub fn rule_count(&self) -> usize {
+        self.engine().rule_count()
+    }
+
+    /// Pause blocking for `duration`, e.g. "disable for 30 minutes"
+    pub fn pause(&mut self, duration: std::time::Duration) {
+        self.pause.pause(duration);
+    }
+
+    /// End a manual pause
+    ///
+    /// Scheduled pause windows still apply; remove them from
+    /// `Config::pause_schedule` to block during them.
+    pub fn resume(&mut self) {
+        self.pause.resume();
+    }
+
+    /// End of the manual pause, if one is running
+    pub fn paused_until(&self) -> Option<std::time::SystemTime> {
+        self.pause.paused_until()
+    }
+
+    /// Whether blocking is paused now, manually or by the pause schedule
+    pub fn is_paused(&self) -> bool {
+        self.pause.is_paused(&self.config.pause_schedule)
+    }
+
+    /// Blocking mode of a site's pages
+    pub fn site_mode(&self, host: &str) -> site_policy::SiteMode {
+        self.config.site_policy.mode_for(host)
+    }
+
+    /// Set the blocking mode of a domain and its subdomains
+    pub fn set_site_mode(&mut self, domain: &str, mode: site_policy::SiteMode) {
+        self.config.site_policy.set_mode(domain, mode);
+        self.run_auto_backup();
+    }
+
+    /// Go back to the default mode for a domain, returning whether it had one
+    pub fn clear_site_mode(&mut self, domain: &str) -> bool {
+        let removed = self.config.site_policy.remove(domain);
+        if removed {
+            self.run_auto_backup();
+        }
+        removed
+    }
+
+    /// Cosmetic filtering for a document, honoring its site mode
+    pub fn get_cosmetic_result(&self, url: &str) -> cosmetic::CosmeticResult {
+        if self
+            .site_mode(&utils::extract_domain(url))
+            .applies_cosmetic_rules()
+        {
+            self.engine().get_cosmetic_result(url)
+        } else {
+            cosmetic::CosmeticResult::default()
+        }
+    }
+
+    /// Prune a JSON response fetched by a page on `domain`, honoring its site mode
+    pub fn prune_json<'a>(&self, domain: &str, body: &'a str) -> std::borrow::Cow<'a, str> {
+        if self.site_mode(domain).applies_cosmetic_rules() {
+            self.engine().prune_json(domain, body)
+        } else {
+            std::borrow::Cow::Borrowed(body)
+        }
+    }
+
+    /// Custom rules and whitelist as EasyList-compatible text
+    pub fn export_user_rules(&self) -> String {
+        rule_import::export_user_rules(&self.custom_rules, &self.config.whitelist)
+    }
+
+    /// Merge custom rules and whitelist entries from text
+    ///
+    /// Rules and domains already present are skipped; lines that can never
+    /// match are reported as invalid and not imported.
+    pub fn import_user_rules(
+        &mut self,
+        text: &str,
+    ) -> Result<rule_import::TextImportReport, Box<dyn std::error::Error>> {
+        let report = rule_import::RuleImporter::new().import_text(
+            &self.custom_rules,
+            &self.config.whitelist,
+            text,
+        );
+        if !report.whitelist_added.is_empty() {
+            self.config.whitelist = report.whitelist.clone();
+        }
+        self.set_custom_rules(report.rules.user_rules.clone())?;
+        Ok(report)
+    }
+
+    /// Snapshot config, custom rules and statistics
+    pub fn create_backup(&self) -> backup::BackupData {
+        backup::BackupData::create(
+            self.config.clone(),
+            self.custom_rules.clone(),
+            &self.get_statistics(),
+        )
+    }
+
+    /// Restore config (including the whitelist), custom rules and statistics
+    ///
+    /// The backup is validated first; nothing changes if it is invalid.
+    pub fn restore_backup(
+        &mut self,
+        backup: &backup::BackupData,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        backup.validate()?;
+        self.set_custom_rules(backup.custom_rules.clone())?;
+        self.config = backup.config.clone();
+        self.apply_config();
+        self.statistics
+            .update(|stats| backup.statistics.restore_into(stats));
+        Ok(())
+    }
+
+    /// Crash reporter of this core
+    pub fn crash_reporter(&self) -> &std::sync::Arc<crash_reporter::CrashReporter> {
+        &self.crash_reporter
+    }
+
+    /// Anonymous usage analytics of this core
+    pub fn analytics(&self) -> &std::sync::Arc<analytics::Analytics> {
+        &self.analytics
+    }
+
+    /// Event bus shared by this core's modules
+    pub fn events(&self) -> &std::sync::Arc<event_bus::EventBus> {
+        &self.events
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "full"))]
 mod tests {
     use super::*;
 
@@ -154,4 +1448,67 @@ mod tests {
         assert_eq!(config.update_interval, 86400);
         assert!(!config.debug);
     }
+
+    #[test]
+    fn test_config_defaults_for_missing_fields() {
+        let config: Config =
+            serde_json::from_str(r#"{"debug":true,"whitelist":["example.com"]}"#).unwrap();
+        assert!(config.debug);
+        assert_eq!(config.update_interval, 86400);
+        assert_eq!(config.filter_lists.len(), 2);
+        assert!(config.cache_dir.is_none());
+        assert!(config.is_whitelisted("shop.example.com"));
+        assert!(!config.is_whitelisted("notexample.com"));
+
+        let strict = Config {
+            preset: Some(FilterPreset::Strict),
+            ..Config::default()
+        };
+        assert_eq!(strict.effective_filter_lists().len(), 3);
+    }
+
+    #[test]
+    fn test_list_presets_resolve_through_the_catalog() {
+        let config = Config {
+            filter_lists: vec!["https://easylist.to/easylist/easylist.txt".to_string()],
+            list_presets: vec!["easylist".to_string(), "liste-fr".to_string()],
+            ..Config::default()
+        };
+        assert!(config.validate().is_ok());
+        assert_eq!(config.effective_filter_lists().len(), 2);
+
+        // Catalog lists are left to the updater's mirror fallback
+        let update = config.update_config();
+        assert!(update.urls.is_empty());
+        assert_eq!(update.presets, ["easylist", "liste-fr"]);
+
+        let unknown = Config {
+            list_presets: vec!["no-such-list".to_string()],
+            ..Config::default()
+        };
+        assert_eq!(
+            unknown.validate(),
+            Err(vec![config::ConfigError::UnknownListPreset {
+                id: "no-such-list".to_string()
+            }])
+        );
+    }
+
+    #[test]
+    fn test_rule_errors_tell_invalid_rules_from_a_busy_engine() {
+        let mut core = AdBlockCore::from_filter_list("||ads.com^").unwrap();
+        assert_eq!(
+            core.add_custom_rule("! comment"),
+            Err(RuleError::Invalid {
+                rule: "! comment".to_string()
+            })
+        );
+
+        // A swap holding the engine slot makes patches give up
+        let slot = std::sync::Arc::clone(&core.engine);
+        let held = slot.write();
+        assert_eq!(core.add_custom_rule("||tracker.net^"), Err(RuleError::Busy));
+        drop(held);
+        assert_eq!(core.add_custom_rule("||tracker.net^"), Ok(true));
+    }
 }
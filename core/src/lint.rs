@@ -0,0 +1,201 @@
+//! Filter list linting
+//!
+//! Checks rule lines for problems the engine would silently ignore, so the
+//! custom rules editor can underline them before they are saved.
+
+use crate::filter_list::{detect_line_format, LineFormat};
+use crate::resource_type::ResourceType;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Literal text shorter than this makes a rule match too many URLs
+const MIN_LITERAL_LEN: usize = 4;
+
+/// Options understood besides resource types, without `~` or `=value`
+const KNOWN_OPTIONS: &[&str] = &[
+    "badfilter",
+    "csp",
+    "domain",
+    "ehide",
+    "elemhide",
+    "first-party",
+    "1p",
+    "generichide",
+    "ghide",
+    "important",
+    "match-case",
+    "redirect",
+    "redirect-rule",
+    "removeparam",
+    "shide",
+    "specifichide",
+    "third-party",
+    "3p",
+];
+
+/// How serious a diagnostic is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LintSeverity {
+    /// The rule works but is probably not what was meant
+    Warning,
+    /// The rule can never match as written
+    Error,
+}
+
+/// Problem found in a rule
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LintKind {
+    /// A `$` option the engine does not understand
+    UnknownOption(String),
+    /// A `/regex/` rule that does not compile
+    InvalidRegex(String),
+    /// The same rule appeared earlier, on this 1-based line
+    DuplicateRule(usize),
+    /// A pattern with so little literal text that it matches most URLs
+    OverlyBroad,
+    /// A line in none of the supported rule syntaxes
+    UnknownSyntax,
+}
+
+impl LintKind {
+    pub fn severity(&self) -> LintSeverity {
+        match self {
+            LintKind::UnknownOption(_) | LintKind::InvalidRegex(_) | LintKind::UnknownSyntax => {
+                LintSeverity::Error
+            }
+            LintKind::DuplicateRule(_) | LintKind::OverlyBroad => LintSeverity::Warning,
+        }
+    }
+}
+
+/// Diagnostic for one line of a filter list
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LintDiagnostic {
+    /// 1-based line number
+    pub line: usize,
+    pub severity: LintSeverity,
+    pub kind: LintKind,
+    /// Human-readable description
+    pub message: String,
+}
+
+impl LintDiagnostic {
+    fn new(line: usize, kind: LintKind) -> Self {
+        let message = match &kind {
+            LintKind::UnknownOption(option) => format!("Unknown option \"{option}\""),
+            LintKind::InvalidRegex(error) => format!("Invalid regular expression: {error}"),
+            LintKind::DuplicateRule(first) => format!("Duplicate of the rule on line {first}"),
+            LintKind::OverlyBroad => "Pattern matches too many URLs".to_string(),
+            LintKind::UnknownSyntax => "Not a filter rule, hosts entry or domain".to_string(),
+        };
+        LintDiagnostic {
+            line,
+            severity: kind.severity(),
+            kind,
+            message,
+        }
+    }
+}
+
+/// Lint filter list text, returning diagnostics in line order
+pub fn lint(content: &str) -> Vec<LintDiagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut seen: HashMap<&str, usize> = HashMap::new();
+
+    for (index, line) in content.lines().enumerate() {
+        let number = index + 1;
+        let line = line.trim();
+        let format = detect_line_format(line);
+        if format == LineFormat::Comment {
+            continue;
+        }
+        if let Some(&first) = seen.get(line) {
+            diagnostics.push(LintDiagnostic::new(number, LintKind::DuplicateRule(first)));
+            continue;
+        }
+        seen.insert(line, number);
+        match format {
+            LineFormat::Network => lint_network_rule(line, number, &mut diagnostics),
+            LineFormat::Unknown => {
+                diagnostics.push(LintDiagnostic::new(number, LintKind::UnknownSyntax));
+            }
+            _ => {}
+        }
+    }
+    diagnostics
+}
+
+fn lint_network_rule(line: &str, number: usize, diagnostics: &mut Vec<LintDiagnostic>) {
+    let rule = line.strip_prefix("@@").unwrap_or(line);
+    let (pattern, options) = split_options(rule);
+
+    let mut has_domain = false;
+    for option in options.into_iter().flat_map(|options| options.split(',')) {
+        let option = option.trim();
+        let name = option.strip_prefix('~').unwrap_or(option);
+        let name = name.split_once('=').map_or(name, |(name, _)| name);
+        has_domain |= name == "domain";
+        if !ResourceType::is_type_option(name) && !KNOWN_OPTIONS.contains(&name) {
+            diagnostics.push(LintDiagnostic::new(
+                number,
+                LintKind::UnknownOption(option.to_string()),
+            ));
+        }
+    }
+
+    if let Some(regex) = regex_body(pattern) {
+        if let Err(e) = regex::Regex::new(regex) {
+            diagnostics.push(LintDiagnostic::new(
+                number,
+                LintKind::InvalidRegex(e.to_string()),
+            ));
+        }
+        return;
+    }
+
+    // Rules limited to some sites can't match most URLs
+    let longest_literal = pattern
+        .split(['*', '^', '|'])
+        .map(str::len)
+        .max()
+        .unwrap_or(0);
+    if !has_domain && longest_literal < MIN_LITERAL_LEN {
+        diagnostics.push(LintDiagnostic::new(number, LintKind::OverlyBroad));
+    }
+}
+
+/// Split a rule into its pattern and `$` options
+///
+/// A whole-line `/regex/` has no options, as `$` is a regex anchor there.
+fn split_options(rule: &str) -> (&str, Option<&str>) {
+    if regex_body(rule).is_some() {
+        return (rule, None);
+    }
+    match rule.rsplit_once('$') {
+        Some((pattern, options)) => (pattern, Some(options)),
+        None => (rule, None),
+    }
+}
+
+/// Expression of a `/regex/` pattern
+fn regex_body(pattern: &str) -> Option<&str> {
+    pattern
+        .strip_prefix('/')?
+        .strip_suffix('/')
+        .filter(|body| !body.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_regex_options() {
+        assert_eq!(split_options("/ads$/"), ("/ads$/", None));
+        assert_eq!(
+            split_options("/ad[0-9]+/$script"),
+            ("/ad[0-9]+/", Some("script"))
+        );
+        assert_eq!(split_options("||ads.com^"), ("||ads.com^", None));
+    }
+}
@@ -0,0 +1,201 @@
+//! Catalog of well-known filter lists
+//!
+//! A curated catalog of list subscriptions (EasyList, EasyPrivacy, AdGuard
+//! and regional lists) is embedded so the settings screen can offer them
+//! without the user pasting URLs. Lists are enabled by ID through
+//! `Config::list_presets`; the updater downloads each from its first URL
+//! and falls back to the mirrors listed after it.
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+/// Catalog bundled with the crate
+const EMBEDDED_CATALOG: &str = include_str!("../data/filter_list_catalog.json");
+
+static BUILTIN: Lazy<ListCatalog> =
+    Lazy::new(|| ListCatalog::parse(EMBEDDED_CATALOG).expect("embedded list catalog is valid"));
+
+/// What a catalog list blocks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ListCategory {
+    Ads,
+    Privacy,
+    Annoyances,
+    /// Ads of sites in a language or country
+    Regional,
+    /// Malware and phishing protection
+    Security,
+}
+
+/// Filter list offered by the catalog
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ListPreset {
+    /// Stable identifier, e.g. `easylist`
+    pub id: String,
+    pub title: String,
+    pub category: ListCategory,
+    /// ISO 3166-1 country code of regional lists
+    #[serde(default)]
+    pub region: Option<String>,
+    /// ISO 639-1 codes of the languages the list targets; empty if global
+    #[serde(default)]
+    pub languages: Vec<String>,
+    #[serde(default)]
+    pub homepage: Option<String>,
+    /// Approximate download size in KiB
+    pub size_kb: u32,
+    /// Whether the list is enabled in a fresh install
+    #[serde(default)]
+    pub recommended: bool,
+    /// Download URLs, primary first, then mirrors
+    pub urls: Vec<String>,
+}
+
+impl ListPreset {
+    /// Primary download URL
+    pub fn url(&self) -> &str {
+        &self.urls[0]
+    }
+
+    /// Fallback URLs, tried in order when the primary one fails
+    pub fn mirrors(&self) -> &[String] {
+        &self.urls[1..]
+    }
+
+    /// Whether the list targets a language, given as a code such as
+    /// `de` or a locale such as `de-AT`
+    pub fn targets_language(&self, locale: &str) -> bool {
+        let language = locale.split(['-', '_']).next().unwrap_or(locale);
+        self.languages
+            .iter()
+            .any(|listed| listed.eq_ignore_ascii_case(language))
+    }
+
+    /// Whether blocks by the list are reported as `BlockKind::Malware`
+    pub fn is_security(&self) -> bool {
+        self.category == ListCategory::Security
+    }
+}
+
+/// Catalog file: the lists with a format version
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ListCatalog {
+    #[serde(default)]
+    version: u32,
+    lists: Vec<ListPreset>,
+}
+
+impl ListCatalog {
+    /// Parse a catalog, rejecting lists without a URL or with a duplicate ID
+    pub fn parse(json: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let catalog: ListCatalog = serde_json::from_str(json)?;
+        for (index, list) in catalog.lists.iter().enumerate() {
+            if list.urls.is_empty() {
+                return Err(format!("Catalog list {} has no URL", list.id).into());
+            }
+            if catalog.lists[..index]
+                .iter()
+                .any(|other| other.id == list.id)
+            {
+                return Err(format!("Duplicate catalog list {}", list.id).into());
+            }
+        }
+        Ok(catalog)
+    }
+
+    /// The catalog bundled with the crate
+    pub fn builtin() -> &'static ListCatalog {
+        &BUILTIN
+    }
+
+    /// Catalog format version
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Every list in catalog order
+    pub fn lists(&self) -> &[ListPreset] {
+        &self.lists
+    }
+
+    /// List with an ID
+    pub fn get(&self, id: &str) -> Option<&ListPreset> {
+        self.lists.iter().find(|list| list.id == id)
+    }
+
+    /// List downloaded from a URL, primary or mirror
+    pub fn find_by_url(&self, url: &str) -> Option<&ListPreset> {
+        self.lists
+            .iter()
+            .find(|list| list.urls.iter().any(|listed| listed == url))
+    }
+
+    /// Lists of a category
+    pub fn by_category(&self, category: ListCategory) -> Vec<&ListPreset> {
+        self.lists
+            .iter()
+            .filter(|list| list.category == category)
+            .collect()
+    }
+
+    /// IDs to enable in a fresh install: the recommended lists plus the
+    /// regional lists for the device locale
+    pub fn recommended_ids(&self, locale: &str) -> Vec<String> {
+        self.lists
+            .iter()
+            .filter(|list| list.recommended || list.targets_language(locale))
+            .map(|list| list.id.clone())
+            .collect()
+    }
+
+    /// Export the catalog to JSON for the settings screen
+    pub fn to_json(&self) -> Result<String, Box<dyn std::error::Error>> {
+        Ok(serde_json::to_string(&self.lists)?)
+    }
+
+    pub fn len(&self) -> usize {
+        self.lists.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lists.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_catalog() {
+        let catalog = ListCatalog::builtin();
+        let easylist = catalog.get("easylist").unwrap();
+        assert_eq!(easylist.url(), "https://easylist.to/easylist/easylist.txt");
+        assert!(!easylist.mirrors().is_empty());
+        assert!(catalog.get("urlhaus").unwrap().is_security());
+        assert_eq!(
+            catalog.find_by_url(easylist.mirrors()[0].as_str()),
+            Some(easylist)
+        );
+
+        let ids = catalog.recommended_ids("de-AT");
+        assert!(ids.contains(&"easylist".to_string()));
+        assert!(ids.contains(&"easylist-germany".to_string()));
+        assert!(!ids.contains(&"liste-fr".to_string()));
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_lists() {
+        let list = r#"{"id":"a","title":"A","category":"ads","size_kb":1,"urls":[]}"#;
+        assert!(ListCatalog::parse(&format!(r#"{{"lists":[{list}]}}"#)).is_err());
+        let list = r#"{"id":"a","title":"A","category":"ads","size_kb":1,"urls":["https://a.example/a.txt"]}"#;
+        assert!(ListCatalog::parse(&format!(r#"{{"lists":[{list},{list}]}}"#)).is_err());
+        assert_eq!(
+            ListCatalog::parse(&format!(r#"{{"lists":[{list}]}}"#))
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+}
@@ -0,0 +1,254 @@
+//! Engine logging
+//!
+//! The engine logs through `tracing`. [`init`] installs a subscriber that
+//! keeps the most recent records in memory, so a diagnostics screen or bug
+//! report can show them (`adblock_get_recent_logs`), and with the `logcat`
+//! or `os-log` feature also forwards them to the platform log. Records of
+//! dependencies logging through `log` are bridged in.
+//!
+//! Logging is process-wide: debug records are kept only while debug logging
+//! is on, which follows `Config::debug` of the engine created or configured
+//! last. If the host app installed its own `tracing` subscriber first, the
+//! engine's records go there and the in-memory log stays empty.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, Once, PoisonError};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Metadata, Subscriber};
+use tracing_log::NormalizeEvent;
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+
+/// Number of records kept in the in-memory log
+pub const RECENT_LOG_CAPACITY: usize = 500;
+
+/// Subsystem of the engine's os_log records
+#[cfg(all(feature = "os-log", target_os = "ios"))]
+const OS_LOG_SUBSYSTEM: &str = "com.adblock.core";
+
+/// Tag of the engine's logcat records
+#[cfg(all(feature = "logcat", target_os = "android"))]
+const LOGCAT_TAG: &[u8] = b"AdBlockCore\0";
+
+static DEBUG: AtomicBool = AtomicBool::new(false);
+static RECENT: Mutex<VecDeque<LogRecord>> = Mutex::new(VecDeque::new());
+
+/// A log record kept in memory
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LogRecord {
+    pub timestamp: DateTime<Utc>,
+    /// `ERROR`, `WARN`, `INFO`, `DEBUG` or `TRACE`
+    pub level: String,
+    /// Module that logged the record
+    pub target: String,
+    pub message: String,
+    /// Structured fields of the record besides the message
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub fields: BTreeMap<String, String>,
+}
+
+/// Install the engine's log subscriber, once per process
+pub fn init() {
+    static INIT: Once = Once::new();
+    INIT.call_once(|| {
+        let subscriber = tracing_subscriber::registry().with(RecentLogs);
+        #[cfg(all(feature = "logcat", target_os = "android"))]
+        let subscriber = subscriber.with(Logcat);
+        #[cfg(all(feature = "os-log", target_os = "ios"))]
+        let subscriber = subscriber.with(tracing_oslog::OsLogger::new(OS_LOG_SUBSYSTEM, "engine"));
+
+        if tracing::subscriber::set_global_default(subscriber).is_ok() {
+            // Fails if the host app already set a `log` logger, which then
+            // keeps the records of `log`-based dependencies
+            let _ = tracing_log::LogTracer::builder()
+                .with_max_level(log::LevelFilter::Debug)
+                .init();
+        }
+    });
+}
+
+/// Keep debug records too, or only `INFO` and above
+pub fn set_debug(enabled: bool) {
+    if DEBUG.swap(enabled, Ordering::Relaxed) != enabled {
+        // Call sites cache whether they are enabled
+        tracing::callsite::rebuild_interest_cache();
+    }
+}
+
+/// Whether debug records are kept
+pub fn is_debug() -> bool {
+    DEBUG.load(Ordering::Relaxed)
+}
+
+/// The in-memory log, oldest record first
+pub fn recent_logs() -> Vec<LogRecord> {
+    let recent = RECENT.lock().unwrap_or_else(PoisonError::into_inner);
+    recent.iter().cloned().collect()
+}
+
+/// Empty the in-memory log
+pub fn clear_recent_logs() {
+    RECENT
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clear();
+}
+
+fn max_level() -> Level {
+    if is_debug() {
+        Level::DEBUG
+    } else {
+        Level::INFO
+    }
+}
+
+/// Layer filtering by the debug setting and filling the in-memory log
+struct RecentLogs;
+
+impl<S: Subscriber> Layer<S> for RecentLogs {
+    fn enabled(&self, metadata: &Metadata<'_>, _ctx: Context<'_, S>) -> bool {
+        *metadata.level() <= max_level()
+    }
+
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let record = LogRecord::from_event(event);
+        let mut recent = RECENT.lock().unwrap_or_else(PoisonError::into_inner);
+        if recent.len() >= RECENT_LOG_CAPACITY {
+            recent.pop_front();
+        }
+        recent.push_back(record);
+    }
+}
+
+impl LogRecord {
+    fn from_event(event: &Event<'_>) -> Self {
+        // Records bridged from `log` carry their real target in fields
+        let normalized = event.normalized_metadata();
+        let metadata = normalized.as_ref().unwrap_or_else(|| event.metadata());
+        let mut visitor = FieldVisitor::default();
+        event.record(&mut visitor);
+        Self {
+            timestamp: Utc::now(),
+            level: metadata.level().to_string(),
+            target: metadata.target().to_string(),
+            message: visitor.message,
+            fields: visitor.fields,
+        }
+    }
+}
+
+impl fmt::Display for LogRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        for (name, value) in &self.fields {
+            write!(f, " {name}={value}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct FieldVisitor {
+    message: String,
+    fields: BTreeMap<String, String>,
+}
+
+impl FieldVisitor {
+    fn insert(&mut self, field: &Field, value: String) {
+        match field.name() {
+            "message" => self.message = value,
+            name if name.starts_with("log.") => {}
+            name => {
+                self.fields.insert(name.to_string(), value);
+            }
+        }
+    }
+}
+
+impl Visit for FieldVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.insert(field, value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.insert(field, format!("{value:?}"));
+    }
+}
+
+/// Layer writing records to logcat
+#[cfg(all(feature = "logcat", target_os = "android"))]
+struct Logcat;
+
+#[cfg(all(feature = "logcat", target_os = "android"))]
+impl<S: Subscriber> Layer<S> for Logcat {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        use android_log_sys::LogPriority;
+
+        let record = LogRecord::from_event(event);
+        let priority = match *event.metadata().level() {
+            Level::ERROR => LogPriority::ERROR,
+            Level::WARN => LogPriority::WARN,
+            Level::INFO => LogPriority::INFO,
+            Level::DEBUG => LogPriority::DEBUG,
+            _ => LogPriority::VERBOSE,
+        };
+        let text = format!("{}: {record}", record.target).replace('\0', "");
+        let Ok(text) = std::ffi::CString::new(text) else {
+            return;
+        };
+        unsafe {
+            android_log_sys::__android_log_write(
+                priority as std::os::raw::c_int,
+                LOGCAT_TAG.as_ptr() as *const std::os::raw::c_char,
+                text.as_ptr(),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recent_logs_keep_message_and_fields() {
+        init();
+        tracing::warn!(
+            list = "https://lists.example/ads.txt",
+            attempt = 2,
+            "Test download failed"
+        );
+
+        let record = recent_logs()
+            .into_iter()
+            .rev()
+            .find(|r| r.message == "Test download failed")
+            .unwrap();
+        assert_eq!(record.level, "WARN");
+        assert_eq!(record.target, module_path!());
+        assert_eq!(record.fields["list"], "https://lists.example/ads.txt");
+        assert_eq!(record.fields["attempt"], "2");
+        assert_eq!(
+            record.to_string(),
+            "Test download failed attempt=2 list=https://lists.example/ads.txt"
+        );
+    }
+
+    #[test]
+    fn test_log_records_are_bridged() {
+        init();
+        tracing::error!(target: "some_dependency", "Test bridged record");
+
+        let record = recent_logs()
+            .into_iter()
+            .rev()
+            .find(|r| r.message == "Test bridged record")
+            .unwrap();
+        assert_eq!(record.level, "ERROR");
+        assert_eq!(record.target, "some_dependency");
+        assert!(record.fields.is_empty());
+    }
+}
@@ -0,0 +1,395 @@
+//! Idle-time maintenance
+//!
+//! Modules register deferred work here instead of running their own timers.
+//! The host app calls [`Maintenance::on_idle`] when the device is idle (and
+//! optionally charging); every task that is due runs once, in a fixed order.
+//! A [`MaintenanceScheduler`] does so on a background thread instead, with
+//! the conditions the host last reported.
+
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Default delay between the passes of a [`MaintenanceScheduler`]
+pub const DEFAULT_MAINTENANCE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Kind of deferred work, in execution order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MaintenanceKind {
+    /// Drop stale cache entries and shrink buffers
+    CacheCompaction,
+    /// Prune old statistics events
+    StatisticsPruning,
+    /// Flush buffered analytics events
+    AnalyticsFlush,
+    /// Reorder rules by observed hit frequency
+    RuleReordering,
+    /// Rebuild the compiled engine from current lists
+    EngineRefresh,
+}
+
+/// Device conditions reported by the host app
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DeviceConditions {
+    /// The device is idle (screen off, no user interaction)
+    pub idle: bool,
+    /// The device is on external power
+    pub charging: bool,
+}
+
+/// Outcome of one task run
+#[derive(Debug, Clone)]
+pub struct TaskOutcome {
+    pub name: String,
+    pub kind: MaintenanceKind,
+    pub duration: Duration,
+    pub error: Option<String>,
+}
+
+/// Result of a maintenance pass
+#[derive(Debug, Clone, Default)]
+pub struct MaintenanceReport {
+    /// Tasks that ran, in execution order
+    pub completed: Vec<TaskOutcome>,
+    /// Due tasks postponed because the time budget ran out
+    pub deferred: Vec<String>,
+}
+
+impl MaintenanceReport {
+    /// Number of tasks that returned an error
+    pub fn failures(&self) -> usize {
+        self.completed.iter().filter(|t| t.error.is_some()).count()
+    }
+}
+
+type TaskFn = Box<dyn FnMut() -> Result<(), String> + Send>;
+
+struct Task {
+    name: String,
+    kind: MaintenanceKind,
+    min_interval: Duration,
+    requires_charging: bool,
+    last_run: Option<Instant>,
+    run: TaskFn,
+}
+
+impl Task {
+    fn is_due(&self, now: Instant, conditions: DeviceConditions) -> bool {
+        if self.requires_charging && !conditions.charging {
+            return false;
+        }
+        self.last_run
+            .is_none_or(|last| now.duration_since(last) >= self.min_interval)
+    }
+}
+
+/// Coordinator for deferred maintenance work
+pub struct Maintenance {
+    tasks: Vec<Task>,
+    budget: Option<Duration>,
+}
+
+impl Maintenance {
+    /// Create a coordinator without a time budget
+    pub fn new() -> Self {
+        Self {
+            tasks: Vec::new(),
+            budget: None,
+        }
+    }
+
+    /// Stop starting new tasks once a pass has run for `budget`
+    pub fn set_budget(&mut self, budget: Option<Duration>) {
+        self.budget = budget;
+    }
+
+    /// Register a task that runs at most once per `min_interval`
+    ///
+    /// Tasks with `requires_charging` only run while the device is charging.
+    pub fn register<F>(
+        &mut self,
+        name: &str,
+        kind: MaintenanceKind,
+        min_interval: Duration,
+        requires_charging: bool,
+        task: F,
+    ) where
+        F: FnMut() -> Result<(), String> + Send + 'static,
+    {
+        self.tasks.push(Task {
+            name: name.to_string(),
+            kind,
+            min_interval,
+            requires_charging,
+            last_run: None,
+            run: Box::new(task),
+        });
+        // Stable sort keeps registration order within a kind
+        self.tasks.sort_by_key(|task| task.kind);
+    }
+
+    /// Remove a task by name, returning whether it existed
+    pub fn unregister(&mut self, name: &str) -> bool {
+        let before = self.tasks.len();
+        self.tasks.retain(|task| task.name != name);
+        self.tasks.len() != before
+    }
+
+    /// Names of the tasks that would run under the given conditions
+    pub fn due_tasks(&self, conditions: DeviceConditions) -> Vec<String> {
+        if !conditions.idle {
+            return Vec::new();
+        }
+        let now = Instant::now();
+        self.tasks
+            .iter()
+            .filter(|task| task.is_due(now, conditions))
+            .map(|task| task.name.clone())
+            .collect()
+    }
+
+    /// Run every due task; does nothing unless the device is idle
+    pub fn on_idle(&mut self, conditions: DeviceConditions) -> MaintenanceReport {
+        let mut report = MaintenanceReport::default();
+        if !conditions.idle {
+            return report;
+        }
+
+        let started = Instant::now();
+        for task in &mut self.tasks {
+            if !task.is_due(Instant::now(), conditions) {
+                continue;
+            }
+
+            if self
+                .budget
+                .is_some_and(|budget| started.elapsed() >= budget)
+            {
+                report.deferred.push(task.name.clone());
+                continue;
+            }
+
+            let task_start = Instant::now();
+            let result = (task.run)();
+            task.last_run = Some(Instant::now());
+
+            if let Err(e) = &result {
+                tracing::warn!("Maintenance task {} failed: {}", task.name, e);
+            }
+            report.completed.push(TaskOutcome {
+                name: task.name.clone(),
+                kind: task.kind,
+                duration: task_start.elapsed(),
+                error: result.err(),
+            });
+        }
+
+        report
+    }
+
+    /// Number of registered tasks
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    /// Whether no tasks are registered
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+}
+
+impl Default for Maintenance {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// State shared with the background thread
+struct Shared {
+    maintenance: Mutex<Maintenance>,
+    conditions: Mutex<DeviceConditions>,
+    signal: Mutex<Signal>,
+    wake: Condvar,
+}
+
+/// Requests to the background thread
+#[derive(Default)]
+struct Signal {
+    stopped: bool,
+    /// Run a pass now; kept until the thread gets to it
+    woken: bool,
+}
+
+impl Shared {
+    fn run(&self) -> MaintenanceReport {
+        let conditions = *self
+            .conditions
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        match self.maintenance.lock() {
+            Ok(mut maintenance) => maintenance.on_idle(conditions),
+            Err(_) => MaintenanceReport::default(),
+        }
+    }
+
+    /// Sleep for `delay` unless stopped or woken first, returning whether stopped
+    fn wait(&self, delay: Duration) -> bool {
+        let Ok(signal) = self.signal.lock() else {
+            return true;
+        };
+        match self
+            .wake
+            .wait_timeout_while(signal, delay, |signal| !signal.stopped && !signal.woken)
+        {
+            Ok((mut signal, _)) => {
+                signal.woken = false;
+                signal.stopped
+            }
+            Err(_) => true,
+        }
+    }
+
+    fn notify(&self, update: impl FnOnce(&mut Signal)) {
+        if let Ok(mut signal) = self.signal.lock() {
+            update(&mut signal);
+        }
+        self.wake.notify_all();
+    }
+}
+
+/// Background thread running due maintenance tasks
+///
+/// Every interval, and whenever the host reports new conditions, runs a
+/// [`Maintenance::on_idle`] pass with the conditions last reported. No task
+/// runs until the host reports the device idle.
+pub struct MaintenanceScheduler {
+    shared: Arc<Shared>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl MaintenanceScheduler {
+    /// Start running the tasks of `maintenance` every `interval`
+    pub fn start(maintenance: Maintenance, interval: Duration) -> std::io::Result<Self> {
+        let shared = Arc::new(Shared {
+            maintenance: Mutex::new(maintenance),
+            conditions: Mutex::new(DeviceConditions::default()),
+            signal: Mutex::new(Signal::default()),
+            wake: Condvar::new(),
+        });
+        let thread_shared = Arc::clone(&shared);
+        let handle = std::thread::Builder::new()
+            .name("adblock-maintenance".to_string())
+            .spawn(move || {
+                while !thread_shared.wait(interval) {
+                    thread_shared.run();
+                }
+            })?;
+        Ok(Self {
+            shared,
+            handle: Some(handle),
+        })
+    }
+
+    /// Report device conditions from the host's idle and power callbacks
+    ///
+    /// Due tasks run right away if the device became idle.
+    pub fn set_conditions(&self, conditions: DeviceConditions) {
+        *self
+            .shared
+            .conditions
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = conditions;
+        if conditions.idle {
+            self.shared.notify(|signal| signal.woken = true);
+        }
+    }
+
+    /// Run one pass now on the calling thread
+    pub fn run_once(&self) -> MaintenanceReport {
+        self.shared.run()
+    }
+
+    /// Stop the background thread and wait for it to finish
+    ///
+    /// A task already running finishes first.
+    pub fn stop(&mut self) {
+        self.shared.notify(|signal| signal.stopped = true);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+
+    /// Whether the background thread is running
+    pub fn is_running(&self) -> bool {
+        self.handle.is_some()
+    }
+}
+
+impl Drop for MaintenanceScheduler {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Statistics;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_tasks_run_in_kind_order_when_idle() {
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let stats = Arc::new(Mutex::new(Statistics::new()));
+        stats.lock().unwrap().record_blocked("ads.com", 10);
+
+        let mut maintenance = Maintenance::new();
+        let o = order.clone();
+        maintenance.register(
+            "engine",
+            MaintenanceKind::EngineRefresh,
+            Duration::ZERO,
+            true,
+            move || {
+                o.lock().unwrap().push("engine");
+                Ok(())
+            },
+        );
+        let o = order.clone();
+        let s = stats.clone();
+        maintenance.register(
+            "stats",
+            MaintenanceKind::StatisticsPruning,
+            Duration::from_secs(3600),
+            false,
+            move || {
+                o.lock().unwrap().push("stats");
+                s.lock().unwrap().prune_events(Duration::ZERO);
+                Ok(())
+            },
+        );
+
+        let busy = DeviceConditions {
+            idle: false,
+            charging: true,
+        };
+        assert!(maintenance.on_idle(busy).completed.is_empty());
+
+        let idle = DeviceConditions {
+            idle: true,
+            charging: false,
+        };
+        let report = maintenance.on_idle(idle);
+        assert_eq!(report.completed.len(), 1);
+        assert!(stats.lock().unwrap().recent_events(10).is_empty());
+
+        // The stats task is not due again yet; the engine task needs power
+        let charging = DeviceConditions {
+            idle: true,
+            charging: true,
+        };
+        assert_eq!(maintenance.due_tasks(charging), vec!["engine"]);
+        maintenance.on_idle(charging);
+        assert_eq!(*order.lock().unwrap(), vec!["stats", "engine"]);
+    }
+}
@@ -0,0 +1,394 @@
+//! Ad removal from streaming manifests
+//!
+//! Video ads (e.g. on YouTube or Twitch) are often stitched into the
+//! stream itself, so blocking requests can't remove them. [`MediaFilter`]
+//! rewrites HLS playlists and DASH manifests instead, dropping the ad
+//! segments and periods before the player sees them. Ads are found by
+//! their markers (`#EXT-X-CUE-OUT`, stitched-ad date ranges, SCTE-35
+//! event streams) and by segment URLs matching the filter's ad segment
+//! rules, which use network rule syntax such as `||ads.example^` or
+//! `*/ad_segments/*`.
+
+use crate::FilterEngine;
+
+/// `CLASS` of the date ranges Twitch marks stitched ads with
+const STITCHED_AD_CLASS: &str = "twitch-stitched-ad";
+
+/// Scheme URI prefix of SCTE-35 ad signalling event streams
+const SCTE35_SCHEME: &str = "urn:scte:scte35";
+
+/// Streaming manifest format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManifestFormat {
+    /// HLS playlist (`.m3u8`)
+    Hls,
+    /// MPEG-DASH manifest (`.mpd`)
+    Dash,
+}
+
+impl ManifestFormat {
+    /// Format of a manifest, recognized from its content
+    pub fn detect(content: &str) -> Option<Self> {
+        let content = content.trim_start_matches('\u{feff}').trim_start();
+        if content.starts_with("#EXTM3U") {
+            Some(ManifestFormat::Hls)
+        } else if content.contains("<MPD") {
+            Some(ManifestFormat::Dash)
+        } else {
+            None
+        }
+    }
+}
+
+/// A manifest with its ads removed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilteredManifest {
+    pub content: String,
+    /// Number of HLS segments or DASH periods removed
+    pub removed: usize,
+}
+
+/// Removes ads from streaming manifests
+#[derive(Default)]
+pub struct MediaFilter {
+    /// Rules matching ad segment URLs
+    segment_rules: Option<FilterEngine>,
+}
+
+impl MediaFilter {
+    /// Filter recognizing ads by their markers only
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Filter also removing segments whose URL matches one of the rules
+    pub fn with_segment_rules(rules: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(MediaFilter {
+            segment_rules: Some(FilterEngine::from_filter_list(rules)?),
+        })
+    }
+
+    /// Remove the ads from a manifest of either format
+    ///
+    /// Relative segment URLs are resolved against `manifest_url` before
+    /// they are matched. Returns `None` if the content is not a manifest.
+    pub fn filter_manifest(&self, content: &str, manifest_url: &str) -> Option<FilteredManifest> {
+        match ManifestFormat::detect(content)? {
+            ManifestFormat::Hls => Some(self.filter_hls(content, manifest_url)),
+            ManifestFormat::Dash => Some(self.filter_dash(content, manifest_url)),
+        }
+    }
+
+    /// Remove the ad segments of an HLS media playlist
+    ///
+    /// Segments between `#EXT-X-CUE-OUT` and `#EXT-X-CUE-IN`, inside a
+    /// stitched-ad date range or matching a rule are dropped with their
+    /// tags. A discontinuity is inserted where content resumes, and the
+    /// media sequence number skips ads removed from the start. Master
+    /// playlists are returned unchanged.
+    pub fn filter_hls(&self, content: &str, manifest_url: &str) -> FilteredManifest {
+        if content.contains("#EXT-X-STREAM-INF") {
+            return FilteredManifest {
+                content: content.to_string(),
+                removed: 0,
+            };
+        }
+
+        let mut header = Vec::new();
+        let mut body = Vec::new();
+        // Tags of the segment being read, kept until its URI decides its fate
+        let mut pending: Vec<&str> = Vec::new();
+        let mut in_ad_break = false;
+        let mut removed = 0;
+        let mut leading_removed = 0;
+        let mut kept_any = false;
+        let mut discontinuity = false;
+
+        for line in content.lines() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() {
+                continue;
+            }
+            if let Some(tag) = trimmed.strip_prefix('#') {
+                if is_ad_start(tag) {
+                    in_ad_break = true;
+                    continue;
+                }
+                if is_ad_end(tag) {
+                    in_ad_break = false;
+                    continue;
+                }
+                if tag.starts_with("EXT-X-CUE-OUT-CONT") {
+                    continue;
+                }
+                if is_playlist_tag(tag) {
+                    if kept_any || !pending.is_empty() {
+                        body.push(line);
+                    } else {
+                        header.push(line);
+                    }
+                } else {
+                    pending.push(line);
+                }
+                continue;
+            }
+
+            // A segment URI ends the segment
+            let is_ad = in_ad_break || self.is_ad_url(&resolve_url(manifest_url, trimmed));
+            if is_ad {
+                removed += 1;
+                if !kept_any {
+                    leading_removed += 1;
+                }
+                discontinuity = true;
+            } else {
+                if discontinuity && kept_any && !pending.contains(&"#EXT-X-DISCONTINUITY") {
+                    body.push("#EXT-X-DISCONTINUITY");
+                }
+                discontinuity = false;
+                kept_any = true;
+                body.append(&mut pending);
+                body.push(line);
+            }
+            pending.clear();
+        }
+        body.append(&mut pending);
+
+        let mut output = String::with_capacity(content.len());
+        for line in header {
+            match line.trim().strip_prefix("#EXT-X-MEDIA-SEQUENCE:") {
+                Some(sequence) if leading_removed > 0 => {
+                    let sequence = sequence.trim().parse::<u64>().unwrap_or(0);
+                    output.push_str(&format!(
+                        "#EXT-X-MEDIA-SEQUENCE:{}",
+                        sequence + leading_removed
+                    ));
+                }
+                _ => output.push_str(line),
+            }
+            output.push('\n');
+        }
+        for line in body {
+            output.push_str(line);
+            output.push('\n');
+        }
+        FilteredManifest {
+            content: output,
+            removed,
+        }
+    }
+
+    /// Remove the ad periods of a DASH manifest
+    ///
+    /// Periods announcing an ad through a SCTE-35 event stream, or with a
+    /// `BaseURL` or segment template URL matching a rule, are dropped.
+    pub fn filter_dash(&self, content: &str, manifest_url: &str) -> FilteredManifest {
+        let mut output = String::with_capacity(content.len());
+        let mut removed = 0;
+        let mut rest = content;
+        while let Some(start) = find_element(rest, "Period") {
+            output.push_str(&rest[..start]);
+            let end = match rest[start..].find("</Period>") {
+                Some(close) => start + close + "</Period>".len(),
+                // A self-closing period has no content to judge
+                None => rest[start..]
+                    .find("/>")
+                    .map_or(rest.len(), |close| start + close + 2),
+            };
+            let period = &rest[start..end];
+            if self.is_ad_period(period, manifest_url) {
+                removed += 1;
+                // Drop the indentation left before the period
+                let trimmed = output.trim_end_matches([' ', '\t']).len();
+                output.truncate(trimmed);
+                rest = rest[end..].strip_prefix('\n').unwrap_or(&rest[end..]);
+            } else {
+                output.push_str(period);
+                rest = &rest[end..];
+            }
+        }
+        output.push_str(rest);
+        FilteredManifest {
+            content: output,
+            removed,
+        }
+    }
+
+    fn is_ad_period(&self, period: &str, manifest_url: &str) -> bool {
+        if attribute_values(period, "schemeIdUri").any(|scheme| scheme.starts_with(SCTE35_SCHEME)) {
+            return true;
+        }
+        let base_urls = element_texts(period, "BaseURL");
+        let templates = ["media", "initialization"]
+            .into_iter()
+            .flat_map(|name| attribute_values(period, name));
+        base_urls
+            .chain(templates)
+            .any(|url| self.is_ad_url(&resolve_url(manifest_url, url)))
+    }
+
+    /// Whether a segment URL matches an ad segment rule
+    pub fn is_ad_url(&self, url: &str) -> bool {
+        self.segment_rules
+            .as_ref()
+            .is_some_and(|rules| rules.should_block(url).should_block)
+    }
+}
+
+/// Whether an HLS tag (without `#`) starts an ad break
+fn is_ad_start(tag: &str) -> bool {
+    if tag.starts_with("EXT-X-CUE-OUT-CONT") {
+        return false;
+    }
+    tag.starts_with("EXT-X-CUE-OUT")
+        || tag.starts_with("EXT-X-DATERANGE")
+            && (tag.contains(STITCHED_AD_CLASS) || tag.contains("SCTE35-OUT"))
+}
+
+/// Whether an HLS tag (without `#`) ends an ad break
+fn is_ad_end(tag: &str) -> bool {
+    tag.starts_with("EXT-X-CUE-IN")
+        || tag.starts_with("EXT-X-DATERANGE") && tag.contains("SCTE35-IN")
+}
+
+/// Whether an HLS tag applies to the whole playlist rather than a segment
+fn is_playlist_tag(tag: &str) -> bool {
+    const PLAYLIST_TAGS: &[&str] = &[
+        "EXTM3U",
+        "EXT-X-VERSION",
+        "EXT-X-TARGETDURATION",
+        "EXT-X-MEDIA-SEQUENCE",
+        "EXT-X-DISCONTINUITY-SEQUENCE",
+        "EXT-X-PLAYLIST-TYPE",
+        "EXT-X-INDEPENDENT-SEGMENTS",
+        "EXT-X-START",
+        "EXT-X-ENDLIST",
+        "EXT-X-SERVER-CONTROL",
+        "EXT-X-PART-INF",
+    ];
+    let name = tag.split(':').next().unwrap_or(tag);
+    PLAYLIST_TAGS.contains(&name)
+}
+
+/// Offset of the next `<name` start tag
+fn find_element(xml: &str, name: &str) -> Option<usize> {
+    let open = format!("<{name}");
+    let mut offset = 0;
+    while let Some(pos) = xml[offset..].find(&open) {
+        let start = offset + pos;
+        let after = xml[start + open.len()..].chars().next();
+        if after.is_some_and(|c| c == '>' || c == '/' || c.is_whitespace()) {
+            return Some(start);
+        }
+        offset = start + open.len();
+    }
+    None
+}
+
+/// Values of every `name="…"` attribute in an XML fragment
+fn attribute_values<'a>(xml: &'a str, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+    let needle = format!(" {name}=\"");
+    xml.match_indices(' ')
+        .filter_map(move |(pos, _)| xml[pos..].strip_prefix(needle.as_str()))
+        .filter_map(|value| value.split_once('"').map(|(value, _)| value))
+}
+
+/// Text of every `<name>…</name>` element in an XML fragment
+fn element_texts<'a>(xml: &'a str, name: &str) -> impl Iterator<Item = &'a str> + 'a {
+    let open = format!("<{name}>");
+    let close = format!("</{name}>");
+    xml.match_indices(open.as_str())
+        .map(|(pos, _)| pos + open.len())
+        .collect::<Vec<_>>()
+        .into_iter()
+        .filter_map(move |start| {
+            let end = xml[start..].find(close.as_str())?;
+            Some(xml[start..start + end].trim())
+        })
+}
+
+/// Resolve a URI from a manifest against the manifest URL
+fn resolve_url(base: &str, uri: &str) -> String {
+    if uri.contains("://") {
+        return uri.to_string();
+    }
+    let Some((scheme, rest)) = base.split_once("://") else {
+        return uri.to_string();
+    };
+    if let Some(network_path) = uri.strip_prefix("//") {
+        return format!("{scheme}://{network_path}");
+    }
+    let rest = rest.split(['?', '#']).next().unwrap_or(rest);
+    let authority_end = rest.find('/').unwrap_or(rest.len());
+    if uri.starts_with('/') {
+        return format!("{scheme}://{}{uri}", &rest[..authority_end]);
+    }
+    let directory = rest.rfind('/').map_or(rest.len(), |slash| slash + 1);
+    if directory <= authority_end {
+        format!("{scheme}://{}/{uri}", &rest[..authority_end])
+    } else {
+        format!("{scheme}://{}{uri}", &rest[..directory])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_hls_ad_breaks_and_rules() {
+        let playlist = "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-TARGETDURATION:6\n#EXT-X-MEDIA-SEQUENCE:100\n\
+            #EXTINF:6.0,\nad/preroll.ts\n\
+            #EXTINF:6.0,\ncontent1.ts\n\
+            #EXT-X-CUE-OUT:DURATION=12\n#EXTINF:6.0,\nbreak1.ts\n#EXT-X-CUE-OUT-CONT:6/12\n#EXTINF:6.0,\nbreak2.ts\n#EXT-X-CUE-IN\n\
+            #EXTINF:6.0,\ncontent2.ts\n#EXT-X-ENDLIST\n";
+        let filter = MediaFilter::with_segment_rules("||cdn.example/video/ad/*\n").unwrap();
+
+        let filtered = filter
+            .filter_manifest(playlist, "https://cdn.example/video/index.m3u8")
+            .unwrap();
+
+        assert_eq!(filtered.removed, 3);
+        assert_eq!(
+            filtered.content,
+            "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-TARGETDURATION:6\n#EXT-X-MEDIA-SEQUENCE:101\n\
+             #EXTINF:6.0,\ncontent1.ts\n\
+             #EXT-X-DISCONTINUITY\n#EXTINF:6.0,\ncontent2.ts\n#EXT-X-ENDLIST\n"
+        );
+    }
+
+    #[test]
+    fn test_filter_dash_ad_periods() {
+        let manifest = "<?xml version=\"1.0\"?>\n<MPD type=\"static\">\n  <Period id=\"main\">\n    <BaseURL>main/</BaseURL>\n  </Period>\n  <Period id=\"break\">\n    <EventStream schemeIdUri=\"urn:scte:scte35:2014:xml+bin\"/>\n  </Period>\n  <Period id=\"sponsor\">\n    <SegmentTemplate media=\"https://ads.example/seg-$Number$.m4s\"/>\n  </Period>\n</MPD>\n";
+        let filter = MediaFilter::with_segment_rules("||ads.example^\n").unwrap();
+
+        let filtered = filter
+            .filter_manifest(manifest, "https://cdn.example/stream.mpd")
+            .unwrap();
+
+        assert_eq!(filtered.removed, 2);
+        assert_eq!(
+            filtered.content,
+            "<?xml version=\"1.0\"?>\n<MPD type=\"static\">\n  <Period id=\"main\">\n    <BaseURL>main/</BaseURL>\n  </Period>\n</MPD>\n"
+        );
+        assert_eq!(ManifestFormat::detect("not a manifest"), None);
+    }
+
+    #[test]
+    fn test_resolve_url() {
+        let base = "https://cdn.example/video/index.m3u8?token=1";
+        assert_eq!(
+            resolve_url(base, "seg.ts"),
+            "https://cdn.example/video/seg.ts"
+        );
+        assert_eq!(resolve_url(base, "/root.ts"), "https://cdn.example/root.ts");
+        assert_eq!(
+            resolve_url(base, "//other.example/a.ts"),
+            "https://other.example/a.ts"
+        );
+        assert_eq!(
+            resolve_url("https://cdn.example", "a.ts"),
+            "https://cdn.example/a.ts"
+        );
+    }
+}
@@ -1,7 +1,11 @@
-use std::sync::Arc;
+use crate::event_bus::{EngineEvent, EventBus, SubscriptionId};
+use std::collections::{HashMap, HashSet};
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::collections::HashMap;
-use std::time::{Duration, Instant};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Requests between checks of the engine's memory usage
+pub const MEMORY_CHECK_INTERVAL: u64 = 1000;
 
 /// Memory optimization settings and utilities
 pub struct MemoryOptimizer {
@@ -13,6 +17,8 @@ pub struct MemoryOptimizer {
     cache_entries: Arc<parking_lot::RwLock<LruCache>>,
     /// Memory pressure callback
     memory_pressure_callback: Option<Box<dyn Fn() + Send + Sync>>,
+    /// Bus notified of memory pressure
+    event_bus: Option<Arc<EventBus>>,
 }
 
 struct LruCache {
@@ -36,6 +42,7 @@ impl MemoryOptimizer {
             current_cache_size: AtomicUsize::new(0),
             cache_entries: Arc::new(parking_lot::RwLock::new(LruCache::new(1000))),
             memory_pressure_callback: None,
+            event_bus: None,
         }
     }
 
@@ -53,11 +60,11 @@ impl MemoryOptimizer {
     /// Add data to cache with memory management
     pub fn cache_data(&self, key: String, data: Vec<u8>) {
         let size = data.len();
-        
+
         // Check if this would exceed memory limit
         let current = self.current_cache_size.load(Ordering::Relaxed);
         let max = self.max_cache_size.load(Ordering::Relaxed);
-        
+
         if current + size > max {
             // Evict old entries to make room
             self.evict_to_fit(size);
@@ -65,20 +72,24 @@ impl MemoryOptimizer {
 
         // Add to cache
         let mut cache = self.cache_entries.write();
-        
+
         // Remove old entry if exists
         if let Some(old_entry) = cache.entries.remove(&key) {
-            self.current_cache_size.fetch_sub(old_entry.size, Ordering::Relaxed);
+            self.current_cache_size
+                .fetch_sub(old_entry.size, Ordering::Relaxed);
         }
 
         // Add new entry
-        cache.entries.insert(key.clone(), CacheEntry {
-            data,
-            size,
-            last_accessed: Instant::now(),
-            access_count: 1,
-        });
-        
+        cache.entries.insert(
+            key.clone(),
+            CacheEntry {
+                data,
+                size,
+                last_accessed: Instant::now(),
+                access_count: 1,
+            },
+        );
+
         cache.access_order.push(key);
         self.current_cache_size.fetch_add(size, Ordering::Relaxed);
 
@@ -91,7 +102,7 @@ impl MemoryOptimizer {
     /// Get data from cache
     pub fn get_cached(&self, key: &str) -> Option<Vec<u8>> {
         let mut cache = self.cache_entries.write();
-        
+
         if let Some(entry) = cache.entries.get_mut(key) {
             entry.last_accessed = Instant::now();
             entry.access_count += 1;
@@ -114,22 +125,25 @@ impl MemoryOptimizer {
         let mut cache = self.cache_entries.write();
         let max = self.max_cache_size.load(Ordering::Relaxed);
         let mut current = self.current_cache_size.load(Ordering::Relaxed);
-        
+
         // Sort by last accessed time
-        let mut entries: Vec<_> = cache.entries.iter()
+        let mut entries: Vec<_> = cache
+            .entries
+            .iter()
             .map(|(k, v)| (k.clone(), v.last_accessed, v.size))
             .collect();
         entries.sort_by_key(|(_, time, _)| *time);
 
         // Evict oldest entries until we have enough space
-        for (key, _, size) in entries {
+        for (key, _, _) in entries {
             if current + needed_size <= max {
                 break;
             }
 
             if let Some(entry) = cache.entries.remove(&key) {
                 current -= entry.size;
-                self.current_cache_size.fetch_sub(entry.size, Ordering::Relaxed);
+                self.current_cache_size
+                    .fetch_sub(entry.size, Ordering::Relaxed);
             }
         }
 
@@ -141,7 +155,7 @@ impl MemoryOptimizer {
     fn evict_if_needed(&self) {
         let current = self.current_cache_size.load(Ordering::Relaxed);
         let max = self.max_cache_size.load(Ordering::Relaxed);
-        
+
         if current > max {
             let to_evict = current - max;
             self.evict_to_fit(to_evict);
@@ -156,39 +170,61 @@ impl MemoryOptimizer {
         self.memory_pressure_callback = Some(Box::new(callback));
     }
 
+    /// Publish memory pressure events on a bus
+    pub fn set_event_bus(&mut self, bus: Arc<EventBus>) {
+        self.event_bus = Some(bus);
+    }
+
+    /// Clear the cache whenever the rule set changes
+    pub fn subscribe_to(optimizer: Arc<Self>, bus: &EventBus) -> SubscriptionId {
+        bus.subscribe(move |event| {
+            if let EngineEvent::RuleSetUpdated { .. } | EngineEvent::RuleSetPatched { .. } = event {
+                optimizer.clear_cache();
+            }
+        })
+    }
+
     /// Trigger memory pressure handling
     pub fn handle_memory_pressure(&self) {
         // Clear 50% of cache on memory pressure
         let mut cache = self.cache_entries.write();
         let entries_to_remove = cache.entries.len() / 2;
-        
+
         let mut removed = 0;
-        let keys: Vec<_> = cache.access_order.iter()
+        let keys: Vec<_> = cache
+            .access_order
+            .iter()
             .take(entries_to_remove)
             .cloned()
             .collect();
 
         for key in keys {
             if let Some(entry) = cache.entries.remove(&key) {
-                self.current_cache_size.fetch_sub(entry.size, Ordering::Relaxed);
+                self.current_cache_size
+                    .fetch_sub(entry.size, Ordering::Relaxed);
                 removed += 1;
             }
         }
 
         cache.compact_access_order();
-        
+
         // Call callback if set
         if let Some(ref callback) = self.memory_pressure_callback {
             callback();
         }
-        
-        log::info!("Memory pressure handled: removed {} cache entries", removed);
+        if let Some(ref bus) = self.event_bus {
+            bus.publish(&EngineEvent::MemoryPressure {
+                freed_entries: removed,
+            });
+        }
+
+        tracing::info!("Memory pressure handled: removed {} cache entries", removed);
     }
 
     /// Get memory statistics
     pub fn get_stats(&self) -> MemoryStats {
         let cache = self.cache_entries.read();
-        
+
         MemoryStats {
             total_memory_bytes: self.get_memory_usage(),
             cache_entries: cache.entries.len(),
@@ -198,6 +234,12 @@ impl MemoryOptimizer {
     }
 }
 
+impl Default for MemoryOptimizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl LruCache {
     fn new(max_entries: usize) -> Self {
         Self {
@@ -210,9 +252,8 @@ impl LruCache {
     fn compact_access_order(&mut self) {
         // Remove duplicates and non-existent keys
         let mut seen = std::collections::HashSet::new();
-        self.access_order.retain(|key| {
-            self.entries.contains_key(key) && seen.insert(key.clone())
-        });
+        self.access_order
+            .retain(|key| self.entries.contains_key(key) && seen.insert(key.clone()));
     }
 }
 
@@ -226,27 +267,31 @@ pub struct MemoryStats {
 
 /// Memory-efficient string interning for filter rules
 pub struct StringInterner {
-    strings: parking_lot::RwLock<HashMap<String, Arc<str>>>,
+    /// Keyed by the shared string itself, so each string is stored once
+    strings: parking_lot::RwLock<HashSet<Arc<str>>>,
     total_size: AtomicUsize,
 }
 
 impl StringInterner {
     pub fn new() -> Self {
         Self {
-            strings: parking_lot::RwLock::new(HashMap::new()),
+            strings: parking_lot::RwLock::new(HashSet::new()),
             total_size: AtomicUsize::new(0),
         }
     }
 
     /// Intern a string to save memory on duplicates
     pub fn intern(&self, s: &str) -> Arc<str> {
+        if let Some(interned) = self.strings.read().get(s) {
+            return Arc::clone(interned);
+        }
         let mut strings = self.strings.write();
-        
+
         if let Some(interned) = strings.get(s) {
             Arc::clone(interned)
         } else {
-            let arc = Arc::from(s);
-            strings.insert(s.to_string(), Arc::clone(&arc));
+            let arc: Arc<str> = Arc::from(s);
+            strings.insert(Arc::clone(&arc));
             self.total_size.fetch_add(s.len(), Ordering::Relaxed);
             arc
         }
@@ -257,6 +302,22 @@ impl StringInterner {
         self.total_size.load(Ordering::Relaxed)
     }
 
+    /// Forget strings only the interner still references, returning the
+    /// bytes released
+    pub fn purge_unused(&self) -> usize {
+        let mut strings = self.strings.write();
+        let mut freed = 0;
+        strings.retain(|interned| {
+            let used = Arc::strong_count(interned) > 1;
+            if !used {
+                freed += interned.len();
+            }
+            used
+        });
+        self.total_size.fetch_sub(freed, Ordering::Relaxed);
+        freed
+    }
+
     /// Clear all interned strings
     pub fn clear(&self) {
         let mut strings = self.strings.write();
@@ -265,67 +326,156 @@ impl StringInterner {
     }
 }
 
-/// Optimized filter rule storage
+impl Default for StringInterner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Flag bit marking a rule removed until the next compaction
+const REMOVED_FLAG: u8 = 0x80;
+
+/// Compact filter rule storage
+///
+/// Rule texts live back to back in a single arena addressed by offsets,
+/// with one flag byte per rule, so a rule costs five bytes plus its text
+/// instead of a separate heap allocation. Removed rules are marked and
+/// dropped by [`compact`](Self::compact) or
+/// [`purge_removed`](Self::purge_removed), keeping indices stable until then.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
 pub struct OptimizedFilterStorage {
-    /// Interned domain patterns
-    domains: Vec<Arc<str>>,
-    /// Bit flags for rule properties  
+    /// Rule texts, concatenated
+    arena: String,
+    /// Start of each rule's text in the arena
+    offsets: Vec<u32>,
+    /// Bit flags for rule properties; the top bit is reserved
     flags: Vec<u8>,
-    /// Memory optimizer
-    memory: Arc<MemoryOptimizer>,
+    /// Number of rules marked removed
+    removed: usize,
 }
 
 impl OptimizedFilterStorage {
-    pub fn new(memory: Arc<MemoryOptimizer>) -> Self {
-        Self {
-            domains: Vec::new(),
-            flags: Vec::new(),
-            memory,
-        }
+    pub fn new() -> Self {
+        Self::default()
     }
 
-    /// Add a filter rule with memory optimization
-    pub fn add_rule(&mut self, domain: &str, flags: u8, interner: &StringInterner) {
-        let interned = interner.intern(domain);
-        self.domains.push(interned);
-        self.flags.push(flags);
+    /// Add a rule, returning its index
+    pub fn push(&mut self, flags: u8, text: &str) -> usize {
+        debug_assert_eq!(flags & REMOVED_FLAG, 0, "top flag bit is reserved");
+        self.offsets.push(self.arena.len() as u32);
+        self.flags.push(flags & !REMOVED_FLAG);
+        self.arena.push_str(text);
+        self.offsets.len() - 1
+    }
 
-        // Check memory usage periodically
-        if self.domains.len() % 1000 == 0 {
-            let usage = self.estimate_memory_usage();
-            if usage > 20 * 1024 * 1024 { // 20MB for filters
-                log::warn!("Filter storage using {}MB", usage / 1024 / 1024);
-            }
+    /// Flags and text of the rule at `index`, unless it was removed
+    pub fn get(&self, index: usize) -> Option<(u8, &str)> {
+        let flags = *self.flags.get(index)?;
+        if flags & REMOVED_FLAG != 0 {
+            return None;
         }
+        Some((flags, self.text(index)))
     }
 
-    /// Estimate memory usage of filter storage
-    fn estimate_memory_usage(&self) -> usize {
-        // Vec overhead + string data + flags
-        self.domains.len() * std::mem::size_of::<Arc<str>>() +
-        self.domains.iter().map(|s| s.len()).sum::<usize>() +
-        self.flags.len()
+    fn text(&self, index: usize) -> &str {
+        let start = self.offsets[index] as usize;
+        let end = self
+            .offsets
+            .get(index + 1)
+            .map_or(self.arena.len(), |&end| end as usize);
+        &self.arena[start..end]
     }
 
-    /// Compact storage to reduce memory
-    pub fn compact(&mut self) {
-        // Remove duplicates
-        let mut seen = std::collections::HashSet::new();
-        let mut new_domains = Vec::new();
-        let mut new_flags = Vec::new();
+    /// Flags and text of the most recently added rule, unless it was removed
+    pub fn last(&self) -> Option<(u8, &str)> {
+        self.get(self.offsets.len().checked_sub(1)?)
+    }
 
-        for (domain, flag) in self.domains.iter().zip(self.flags.iter()) {
-            if seen.insert(domain.clone()) {
-                new_domains.push(domain.clone());
-                new_flags.push(*flag);
+    /// Live rules with their indices
+    pub fn iter(&self) -> impl Iterator<Item = (usize, u8, &str)> + '_ {
+        (0..self.offsets.len())
+            .filter_map(move |index| self.get(index).map(|(flags, text)| (index, flags, text)))
+    }
+
+    /// Live rules whose flags equal `flags` in the bits of `mask`
+    ///
+    /// Only the flag bytes are scanned, so this is cheaper than filtering
+    /// [`iter`](Self::iter).
+    pub fn with_flags(&self, mask: u8, flags: u8) -> impl Iterator<Item = (usize, u8, &str)> + '_ {
+        let mask = mask | REMOVED_FLAG;
+        self.flags
+            .iter()
+            .enumerate()
+            .filter(move |&(_, &f)| f & mask == flags)
+            .map(|(index, &f)| (index, f, self.text(index)))
+    }
+
+    /// Number of slots including removed rules, i.e. the index the next
+    /// rule gets
+    pub fn slots(&self) -> usize {
+        self.offsets.len()
+    }
+
+    /// Number of live rules
+    pub fn len(&self) -> usize {
+        self.offsets.len() - self.removed
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Mark the first live rule with these flags and text removed,
+    /// returning whether one was found
+    pub fn remove_first(&mut self, flags: u8, text: &str) -> bool {
+        let found = self
+            .iter()
+            .find(|&(_, f, t)| f == flags && t == text)
+            .map(|(index, _, _)| index);
+        found.is_some_and(|index| self.remove(index))
+    }
+
+    /// Mark the rule at `index` removed, returning whether it was live
+    pub fn remove(&mut self, index: usize) -> bool {
+        match self.flags.get_mut(index) {
+            Some(flags) if *flags & REMOVED_FLAG == 0 => {
+                *flags |= REMOVED_FLAG;
+                self.removed += 1;
+                true
             }
+            _ => false,
+        }
+    }
+
+    /// Drop removed rules from the arena; indices of later rules shift down
+    pub fn purge_removed(&mut self) {
+        if self.removed == 0 {
+            return;
+        }
+        let mut purged = Self {
+            arena: String::with_capacity(self.arena.len()),
+            offsets: Vec::with_capacity(self.len()),
+            flags: Vec::with_capacity(self.len()),
+            removed: 0,
+        };
+        for (_, flags, text) in self.iter() {
+            purged.push(flags, text);
         }
+        *self = purged;
+    }
+
+    /// Estimated heap memory in bytes
+    pub fn memory_usage(&self) -> usize {
+        self.arena.capacity()
+            + self.offsets.capacity() * std::mem::size_of::<u32>()
+            + self.flags.capacity()
+    }
 
-        self.domains = new_domains;
-        self.flags = new_flags;
-        
-        // Shrink to fit
-        self.domains.shrink_to_fit();
+    /// Drop removed rules and release spare capacity
+    pub fn compact(&mut self) {
+        self.purge_removed();
+        self.arena.shrink_to_fit();
+        self.offsets.shrink_to_fit();
         self.flags.shrink_to_fit();
     }
 }
@@ -337,17 +487,17 @@ mod tests {
     #[test]
     fn test_memory_optimizer() {
         let optimizer = MemoryOptimizer::new();
-        
+
         // Set max to 1MB for testing
         optimizer.set_max_memory(1024 * 1024);
-        
+
         // Add some data
         optimizer.cache_data("test1".to_string(), vec![0u8; 512 * 1024]);
         assert_eq!(optimizer.get_memory_usage(), 512 * 1024);
-        
+
         // Add more data that triggers eviction
         optimizer.cache_data("test2".to_string(), vec![0u8; 768 * 1024]);
-        
+
         // Should have evicted first entry
         assert!(optimizer.get_memory_usage() <= 1024 * 1024);
         assert!(optimizer.get_cached("test1").is_none());
@@ -357,14 +507,45 @@ mod tests {
     #[test]
     fn test_string_interner() {
         let interner = StringInterner::new();
-        
+
         let s1 = interner.intern("example.com");
         let s2 = interner.intern("example.com");
-        
+
         // Should be the same Arc
         assert!(Arc::ptr_eq(&s1, &s2));
-        
+
         // Memory usage should be counted once
         assert_eq!(interner.memory_usage(), "example.com".len());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_filter_storage_arena() {
+        let mut storage = OptimizedFilterStorage::new();
+        storage.push(0, "ads.com");
+        storage.push(1, "*/banner/*");
+        storage.push(0, "ads.com");
+        assert_eq!(storage.get(1), Some((1, "*/banner/*")));
+
+        // Removal keeps indices stable until purged
+        assert!(storage.remove_first(0, "ads.com"));
+        assert!(!storage.remove_first(2, "ads.com"));
+        assert_eq!(storage.get(0), None);
+        assert_eq!(storage.len(), 2);
+        storage.compact();
+        let rules: Vec<_> = storage.iter().collect();
+        assert_eq!(rules, vec![(0, 1, "*/banner/*"), (1, 0, "ads.com")]);
+
+        // One arena beats a heap allocation per rule
+        let texts: Vec<String> = (0..1000).map(|i| format!("||ads{i}.example^")).collect();
+        let mut storage = OptimizedFilterStorage::new();
+        for text in &texts {
+            storage.push(2, text);
+        }
+        storage.compact();
+        let per_rule: usize = texts
+            .iter()
+            .map(|t| std::mem::size_of::<String>() + t.len())
+            .sum();
+        assert!(storage.memory_usage() < per_rule);
+    }
+}
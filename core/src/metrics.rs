@@ -1,7 +1,59 @@
+use std::collections::{BTreeMap, HashMap};
 use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
+/// Kind of network rule a match is attributed to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleCategory {
+    /// Plain domain rule (`ads.example.com`)
+    Domain,
+    /// Anchored domain rule (`||ads.example.com^`)
+    Subdomain,
+    /// Pattern with wildcards (`*/ads/*`)
+    Wildcard,
+    /// Regular expression rule (`/banner\d+/`)
+    Regex,
+    /// Exception rule (`@@...`)
+    Exception,
+}
+
+impl RuleCategory {
+    const ALL: [RuleCategory; 5] = [
+        RuleCategory::Domain,
+        RuleCategory::Subdomain,
+        RuleCategory::Wildcard,
+        RuleCategory::Regex,
+        RuleCategory::Exception,
+    ];
+
+    /// Categorize a rule as returned by `FilterEngine::matching_rule`
+    pub fn of_rule(rule: &str) -> Self {
+        if rule.starts_with("@@") {
+            RuleCategory::Exception
+        } else if rule.starts_with("||") {
+            RuleCategory::Subdomain
+        } else if rule.len() > 1 && rule.starts_with('/') && rule.ends_with('/') {
+            RuleCategory::Regex
+        } else if rule.contains('*') {
+            RuleCategory::Wildcard
+        } else {
+            RuleCategory::Domain
+        }
+    }
+
+    /// Name used in snapshots and exported metrics
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RuleCategory::Domain => "domain",
+            RuleCategory::Subdomain => "subdomain",
+            RuleCategory::Wildcard => "wildcard",
+            RuleCategory::Regex => "regex",
+            RuleCategory::Exception => "exception",
+        }
+    }
+}
+
 /// Performance metrics for the ad blocking engine
 #[derive(Debug, Clone)]
 pub struct PerformanceMetrics {
@@ -17,9 +69,7 @@ struct MetricsInner {
 
     // Performance metrics
     total_processing_time_ns: AtomicU64,
-    avg_processing_time_ns: AtomicU64,
-    max_processing_time_ns: AtomicU64,
-    min_processing_time_ns: AtomicU64,
+    latency: LatencyHistogram,
 
     // Memory metrics
     filter_count: AtomicUsize,
@@ -33,6 +83,11 @@ struct MetricsInner {
     cache_hits: AtomicU64,
     cache_misses: AtomicU64,
     cache_size: AtomicUsize,
+
+    // Attribution metrics, indexed like `RuleCategory::ALL`
+    matches_by_category: [AtomicU64; RuleCategory::ALL.len()],
+    /// Blocks per source list; the write lock is only taken for new lists
+    blocks_by_list: parking_lot::RwLock<HashMap<String, AtomicU64>>,
 }
 
 impl Default for PerformanceMetrics {
@@ -50,9 +105,7 @@ impl PerformanceMetrics {
                 blocked_requests: AtomicU64::new(0),
                 allowed_requests: AtomicU64::new(0),
                 total_processing_time_ns: AtomicU64::new(0),
-                avg_processing_time_ns: AtomicU64::new(0),
-                max_processing_time_ns: AtomicU64::new(0),
-                min_processing_time_ns: AtomicU64::new(u64::MAX),
+                latency: LatencyHistogram::new(),
                 filter_count: AtomicUsize::new(0),
                 memory_usage_bytes: AtomicUsize::new(0),
                 parse_errors: AtomicU64::new(0),
@@ -60,6 +113,8 @@ impl PerformanceMetrics {
                 cache_hits: AtomicU64::new(0),
                 cache_misses: AtomicU64::new(0),
                 cache_size: AtomicUsize::new(0),
+                matches_by_category: std::array::from_fn(|_| AtomicU64::new(0)),
+                blocks_by_list: parking_lot::RwLock::new(HashMap::new()),
             }),
         }
     }
@@ -80,37 +135,35 @@ impl PerformanceMetrics {
         self.inner
             .total_processing_time_ns
             .fetch_add(time_ns, Ordering::Relaxed);
+        self.inner.latency.record(time_ns);
+    }
 
-        // Update max processing time
-        self.inner
-            .max_processing_time_ns
-            .fetch_max(time_ns, Ordering::Relaxed);
-
-        // Update min processing time
-        loop {
-            let current_min = self.inner.min_processing_time_ns.load(Ordering::Relaxed);
-            if time_ns >= current_min {
-                break;
-            }
-            if self
-                .inner
-                .min_processing_time_ns
-                .compare_exchange_weak(current_min, time_ns, Ordering::Relaxed, Ordering::Relaxed)
-                .is_ok()
-            {
-                break;
-            }
+    /// Attribute a rule match to its category and, for blocks, its source list
+    pub fn record_match(&self, category: RuleCategory, source_list: Option<&str>) {
+        let index = RuleCategory::ALL
+            .iter()
+            .position(|c| *c == category)
+            .unwrap_or_default();
+        self.inner.matches_by_category[index].fetch_add(1, Ordering::Relaxed);
+
+        let Some(list) = source_list.filter(|_| category != RuleCategory::Exception) else {
+            return;
+        };
+        if let Some(count) = self.inner.blocks_by_list.read().get(list) {
+            count.fetch_add(1, Ordering::Relaxed);
+            return;
         }
+        self.inner
+            .blocks_by_list
+            .write()
+            .entry(list.to_string())
+            .or_default()
+            .fetch_add(1, Ordering::Relaxed);
+    }
 
-        // Calculate average
-        let total_requests = self.inner.total_requests.load(Ordering::Relaxed);
-        let total_time = self.inner.total_processing_time_ns.load(Ordering::Relaxed);
-        if total_requests > 0 {
-            let avg = total_time / total_requests;
-            self.inner
-                .avg_processing_time_ns
-                .store(avg, Ordering::Relaxed);
-        }
+    /// Number of requests recorded so far
+    pub fn total_requests(&self) -> u64 {
+        self.inner.total_requests.load(Ordering::Relaxed)
     }
 
     /// Record cache hit
@@ -152,20 +205,22 @@ impl PerformanceMetrics {
 
     /// Get current metrics snapshot
     pub fn snapshot(&self) -> MetricsSnapshot {
+        let latency = self.inner.latency.counts();
         MetricsSnapshot {
             total_requests: self.inner.total_requests.load(Ordering::Relaxed),
             blocked_requests: self.inner.blocked_requests.load(Ordering::Relaxed),
             allowed_requests: self.inner.allowed_requests.load(Ordering::Relaxed),
-            avg_processing_time_ns: self.inner.avg_processing_time_ns.load(Ordering::Relaxed),
-            max_processing_time_ns: self.inner.max_processing_time_ns.load(Ordering::Relaxed),
-            min_processing_time_ns: {
-                let min = self.inner.min_processing_time_ns.load(Ordering::Relaxed);
-                if min == u64::MAX {
-                    0
-                } else {
-                    min
-                }
-            },
+            avg_processing_time_ns: self
+                .inner
+                .total_processing_time_ns
+                .load(Ordering::Relaxed)
+                .checked_div(self.inner.total_requests.load(Ordering::Relaxed))
+                .unwrap_or(0),
+            max_processing_time_ns: latency.max(),
+            min_processing_time_ns: latency.min(),
+            p50_processing_time_ns: latency.percentile(0.50),
+            p95_processing_time_ns: latency.percentile(0.95),
+            p99_processing_time_ns: latency.percentile(0.99),
             filter_count: self.inner.filter_count.load(Ordering::Relaxed),
             memory_usage_bytes: self.inner.memory_usage_bytes.load(Ordering::Relaxed),
             parse_errors: self.inner.parse_errors.load(Ordering::Relaxed),
@@ -173,6 +228,20 @@ impl PerformanceMetrics {
             cache_hits: self.inner.cache_hits.load(Ordering::Relaxed),
             cache_misses: self.inner.cache_misses.load(Ordering::Relaxed),
             cache_size: self.inner.cache_size.load(Ordering::Relaxed),
+            matches_by_category: RuleCategory::ALL
+                .iter()
+                .zip(&self.inner.matches_by_category)
+                .map(|(category, count)| {
+                    (category.as_str().to_string(), count.load(Ordering::Relaxed))
+                })
+                .collect(),
+            blocks_by_list: self
+                .inner
+                .blocks_by_list
+                .read()
+                .iter()
+                .map(|(list, count)| (list.clone(), count.load(Ordering::Relaxed)))
+                .collect(),
             block_rate: self.calculate_block_rate(),
             cache_hit_rate: self.calculate_cache_hit_rate(),
         }
@@ -186,19 +255,15 @@ impl PerformanceMetrics {
         self.inner
             .total_processing_time_ns
             .store(0, Ordering::Relaxed);
-        self.inner
-            .avg_processing_time_ns
-            .store(0, Ordering::Relaxed);
-        self.inner
-            .max_processing_time_ns
-            .store(0, Ordering::Relaxed);
-        self.inner
-            .min_processing_time_ns
-            .store(u64::MAX, Ordering::Relaxed);
+        self.inner.latency.reset();
         self.inner.parse_errors.store(0, Ordering::Relaxed);
         self.inner.match_errors.store(0, Ordering::Relaxed);
         self.inner.cache_hits.store(0, Ordering::Relaxed);
         self.inner.cache_misses.store(0, Ordering::Relaxed);
+        for count in &self.inner.matches_by_category {
+            count.store(0, Ordering::Relaxed);
+        }
+        self.inner.blocks_by_list.write().clear();
     }
 
     fn calculate_block_rate(&self) -> f64 {
@@ -225,6 +290,97 @@ impl PerformanceMetrics {
     }
 }
 
+/// Number of latency buckets; the last one also holds everything slower
+const LATENCY_BUCKETS: usize = 40;
+
+/// Lock-free latency histogram with power-of-two nanosecond buckets
+///
+/// Bucket 0 holds 0ns and bucket `i` holds `2^(i-1)..2^i` ns, so
+/// percentiles are accurate to within a factor of two.
+#[derive(Debug)]
+struct LatencyHistogram {
+    buckets: [AtomicU64; LATENCY_BUCKETS],
+}
+
+impl LatencyHistogram {
+    fn new() -> Self {
+        Self {
+            buckets: std::array::from_fn(|_| AtomicU64::new(0)),
+        }
+    }
+
+    fn record(&self, time_ns: u64) {
+        let bucket = ((u64::BITS - time_ns.leading_zeros()) as usize).min(LATENCY_BUCKETS - 1);
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn counts(&self) -> LatencyCounts {
+        LatencyCounts(std::array::from_fn(|i| {
+            self.buckets[i].load(Ordering::Relaxed)
+        }))
+    }
+
+    fn reset(&self) {
+        for bucket in &self.buckets {
+            bucket.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Bucket counts read from a [`LatencyHistogram`]
+struct LatencyCounts([u64; LATENCY_BUCKETS]);
+
+impl LatencyCounts {
+    /// Smallest value of bucket `i`
+    fn lower_bound(i: usize) -> u64 {
+        if i == 0 {
+            0
+        } else {
+            1 << (i - 1)
+        }
+    }
+
+    /// Largest value of bucket `i`
+    fn upper_bound(i: usize) -> u64 {
+        if i == LATENCY_BUCKETS - 1 {
+            u64::MAX
+        } else {
+            (1 << i) - 1
+        }
+    }
+
+    fn min(&self) -> u64 {
+        self.0
+            .iter()
+            .position(|&count| count > 0)
+            .map_or(0, Self::lower_bound)
+    }
+
+    fn max(&self) -> u64 {
+        self.0
+            .iter()
+            .rposition(|&count| count > 0)
+            .map_or(0, Self::upper_bound)
+    }
+
+    /// Upper bound of the bucket holding the `quantile` (0.0..=1.0)
+    fn percentile(&self, quantile: f64) -> u64 {
+        let total: u64 = self.0.iter().sum();
+        if total == 0 {
+            return 0;
+        }
+        let rank = ((quantile * total as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (i, &count) in self.0.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return Self::upper_bound(i);
+            }
+        }
+        Self::upper_bound(LATENCY_BUCKETS - 1)
+    }
+}
+
 /// Snapshot of performance metrics at a point in time
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct MetricsSnapshot {
@@ -232,8 +388,13 @@ pub struct MetricsSnapshot {
     pub blocked_requests: u64,
     pub allowed_requests: u64,
     pub avg_processing_time_ns: u64,
+    /// Upper bound of the slowest latency bucket
     pub max_processing_time_ns: u64,
+    /// Lower bound of the fastest latency bucket
     pub min_processing_time_ns: u64,
+    pub p50_processing_time_ns: u64,
+    pub p95_processing_time_ns: u64,
+    pub p99_processing_time_ns: u64,
     pub filter_count: usize,
     pub memory_usage_bytes: usize,
     pub parse_errors: u64,
@@ -241,6 +402,10 @@ pub struct MetricsSnapshot {
     pub cache_hits: u64,
     pub cache_misses: u64,
     pub cache_size: usize,
+    /// Rule matches per `RuleCategory` name
+    pub matches_by_category: BTreeMap<String, u64>,
+    /// Blocks per source list (rules not loaded from a named list are left out)
+    pub blocks_by_list: BTreeMap<String, u64>,
     pub block_rate: f64,
     pub cache_hit_rate: f64,
 }
@@ -255,6 +420,144 @@ impl MetricsSnapshot {
     pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
         serde_json::from_str(json)
     }
+
+    /// Percentage of blocked requests caused by a source list
+    pub fn list_block_share(&self, list: &str) -> f64 {
+        match self.blocks_by_list.get(list) {
+            Some(&blocks) if self.blocked_requests > 0 => {
+                blocks as f64 / self.blocked_requests as f64 * 100.0
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// Render in the Prometheus text exposition format
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+        write_metric(
+            &mut out,
+            "adblock_requests_total",
+            "counter",
+            "Requests checked against the filter rules",
+            &[
+                ("verdict=\"blocked\"", self.blocked_requests as f64),
+                ("verdict=\"allowed\"", self.allowed_requests as f64),
+            ],
+        );
+        write_metric(
+            &mut out,
+            "adblock_match_time_nanoseconds",
+            "gauge",
+            "Time spent matching a request",
+            &[
+                ("stat=\"avg\"", self.avg_processing_time_ns as f64),
+                ("stat=\"min\"", self.min_processing_time_ns as f64),
+                ("stat=\"max\"", self.max_processing_time_ns as f64),
+                ("stat=\"p50\"", self.p50_processing_time_ns as f64),
+                ("stat=\"p95\"", self.p95_processing_time_ns as f64),
+                ("stat=\"p99\"", self.p99_processing_time_ns as f64),
+            ],
+        );
+        let categories: Vec<(String, f64)> = self
+            .matches_by_category
+            .iter()
+            .map(|(category, count)| (format!("category=\"{category}\""), *count as f64))
+            .collect();
+        write_metric(
+            &mut out,
+            "adblock_rule_matches_total",
+            "counter",
+            "Rule matches by rule category",
+            &label_refs(&categories),
+        );
+        let lists: Vec<(String, f64)> = self
+            .blocks_by_list
+            .iter()
+            .map(|(list, count)| (format!("list=\"{}\"", escape_label(list)), *count as f64))
+            .collect();
+        write_metric(
+            &mut out,
+            "adblock_list_blocks_total",
+            "counter",
+            "Blocked requests by source filter list",
+            &label_refs(&lists),
+        );
+        write_metric(
+            &mut out,
+            "adblock_filters",
+            "gauge",
+            "Loaded filter rules",
+            &[("", self.filter_count as f64)],
+        );
+        write_metric(
+            &mut out,
+            "adblock_memory_usage_bytes",
+            "gauge",
+            "Estimated engine memory usage",
+            &[("", self.memory_usage_bytes as f64)],
+        );
+        write_metric(
+            &mut out,
+            "adblock_errors_total",
+            "counter",
+            "Rule parse and match errors",
+            &[
+                ("kind=\"parse\"", self.parse_errors as f64),
+                ("kind=\"match\"", self.match_errors as f64),
+            ],
+        );
+        write_metric(
+            &mut out,
+            "adblock_cache_lookups_total",
+            "counter",
+            "Verdict cache lookups",
+            &[
+                ("result=\"hit\"", self.cache_hits as f64),
+                ("result=\"miss\"", self.cache_misses as f64),
+            ],
+        );
+        write_metric(
+            &mut out,
+            "adblock_cache_entries",
+            "gauge",
+            "Entries in the verdict cache",
+            &[("", self.cache_size as f64)],
+        );
+        out
+    }
+}
+
+/// Borrow owned label sets for [`write_metric`]
+fn label_refs(samples: &[(String, f64)]) -> Vec<(&str, f64)> {
+    samples
+        .iter()
+        .map(|(labels, value)| (labels.as_str(), *value))
+        .collect()
+}
+
+/// Escape a Prometheus label value
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Append one metric family with its `HELP` and `TYPE` lines
+///
+/// Each sample is a label set (without braces, empty for none) and a value.
+fn write_metric(out: &mut String, name: &str, kind: &str, help: &str, samples: &[(&str, f64)]) {
+    use std::fmt::Write;
+
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+    for (labels, value) in samples {
+        if labels.is_empty() {
+            let _ = writeln!(out, "{name} {value}");
+        } else {
+            let _ = writeln!(out, "{name}{{{labels}}} {value}");
+        }
+    }
 }
 
 /// Performance timer for measuring request processing time
@@ -297,11 +600,77 @@ mod tests {
         assert_eq!(snapshot.blocked_requests, 2);
         assert_eq!(snapshot.allowed_requests, 1);
         assert_eq!(snapshot.avg_processing_time_ns, 1500);
-        assert_eq!(snapshot.max_processing_time_ns, 2000);
-        assert_eq!(snapshot.min_processing_time_ns, 1000);
+        // Bounds of the 512..=1023 and 1024..=2047 buckets
+        assert_eq!(snapshot.max_processing_time_ns, 2047);
+        assert_eq!(snapshot.min_processing_time_ns, 512);
+        assert_eq!(snapshot.p50_processing_time_ns, 2047);
         assert_eq!(snapshot.block_rate, 66.66666666666666);
     }
 
+    #[test]
+    fn test_prometheus_export() {
+        let metrics = PerformanceMetrics::new();
+        metrics.record_request(true, Duration::from_nanos(1000));
+        metrics.record_request(false, Duration::from_nanos(3000));
+        metrics.set_filter_count(42);
+
+        let text = metrics.snapshot().to_prometheus();
+        assert!(text.contains("# TYPE adblock_requests_total counter\n"));
+        assert!(text.contains("adblock_requests_total{verdict=\"blocked\"} 1\n"));
+        assert!(text.contains("adblock_match_time_nanoseconds{stat=\"avg\"} 2000\n"));
+        assert!(text.contains("\nadblock_filters 42\n"));
+        assert!(text
+            .lines()
+            .all(|line| line.starts_with('#') || line.starts_with("adblock_")));
+    }
+
+    #[test]
+    fn test_latency_percentiles() {
+        let metrics = PerformanceMetrics::new();
+        for _ in 0..98 {
+            metrics.record_request(false, Duration::from_nanos(100));
+        }
+        metrics.record_request(true, Duration::from_micros(50));
+        metrics.record_request(true, Duration::from_millis(3));
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.p50_processing_time_ns, 127);
+        assert_eq!(snapshot.p95_processing_time_ns, 127);
+        assert_eq!(snapshot.p99_processing_time_ns, 65_535);
+        assert_eq!(snapshot.max_processing_time_ns, 4_194_303);
+
+        metrics.reset();
+        assert_eq!(metrics.snapshot().p99_processing_time_ns, 0);
+    }
+
+    #[test]
+    fn test_match_attribution() {
+        let metrics = PerformanceMetrics::new();
+        for _ in 0..3 {
+            metrics.record_request(true, Duration::from_nanos(100));
+        }
+        metrics.record_match(RuleCategory::Subdomain, Some("EasyPrivacy"));
+        metrics.record_match(RuleCategory::Domain, Some("EasyList"));
+        metrics.record_match(RuleCategory::Subdomain, None);
+        metrics.record_match(RuleCategory::Exception, Some("EasyList"));
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.matches_by_category["subdomain"], 2);
+        assert_eq!(snapshot.matches_by_category["exception"], 1);
+        assert_eq!(snapshot.blocks_by_list["EasyList"], 1);
+        assert!((snapshot.list_block_share("EasyPrivacy") - 33.33).abs() < 0.01);
+        assert!(snapshot
+            .to_prometheus()
+            .contains("adblock_list_blocks_total{list=\"EasyList\"} 1\n"));
+
+        assert_eq!(
+            RuleCategory::of_rule("@@||cdn.example^"),
+            RuleCategory::Exception
+        );
+        assert_eq!(RuleCategory::of_rule("/banner[0-9]+/"), RuleCategory::Regex);
+        assert_eq!(RuleCategory::of_rule("*/ads/*"), RuleCategory::Wildcard);
+    }
+
     #[test]
     fn test_cache_metrics() {
         let metrics = PerformanceMetrics::new();
@@ -2,8 +2,15 @@
 //!
 //! This module handles network-level filtering and DNS resolution
 
-use std::collections::HashMap;
+use crate::category_filter::{CategoryFilter, ContentCategory};
+use crate::domain_age::{DomainAgePolicy, DomainAgeVerdict};
+use crate::domain_filter::{parse_domain_rule, DomainRule};
+use crate::hash_list::{FullHashVerifier, HashPrefixList};
+use crate::rule_set::SharedRuleSet;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::Arc;
 
 /// DNS query types
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -13,6 +20,44 @@ pub enum DnsQueryType {
     CNAME, // Canonical name
     MX,    // Mail exchange
     TXT,   // Text record
+    /// Any other record type, by its numeric code
+    Other(u16),
+}
+
+/// How queries for blocked domains are answered
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockResponsePolicy {
+    /// Answer with the sink addresses, `0.0.0.0` and `::` by default
+    #[default]
+    NullIp,
+    /// Answer that the domain does not exist
+    Nxdomain,
+    /// Answer with a custom address, e.g. that of a block page server;
+    /// queries for the other address family get the sink address
+    CustomIp(IpAddr),
+    /// Refuse to answer
+    Refused,
+}
+
+/// Response code of a DNS answer
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DnsResponseCode {
+    #[default]
+    NoError,
+    NxDomain,
+    Refused,
+}
+
+impl DnsResponseCode {
+    /// `RCODE` value of the DNS header
+    pub fn code(self) -> u8 {
+        match self {
+            DnsResponseCode::NoError => 0,
+            DnsResponseCode::NxDomain => 3,
+            DnsResponseCode::Refused => 5,
+        }
+    }
 }
 
 /// DNS query structure
@@ -28,7 +73,12 @@ pub struct DnsQuery {
 pub struct DnsResponse {
     pub transaction_id: u16,
     pub answers: Vec<DnsAnswer>,
+    pub response_code: DnsResponseCode,
     pub blocked: bool,
+    /// Set when the domain age policy flagged the domain without blocking it
+    pub flagged: bool,
+    /// Blocked content category the domain is listed in
+    pub category: Option<ContentCategory>,
 }
 
 /// DNS answer record
@@ -43,7 +93,20 @@ pub enum DnsAnswer {
 /// Network filter for DNS-level blocking
 pub struct NetworkFilter {
     blocked_domains: HashMap<String, bool>,
-    redirect_ip: IpAddr,
+    /// Domains never blocked, along with their subdomains
+    allowed_domains: HashSet<String>,
+    block_response: BlockResponsePolicy,
+    /// Policies overriding `block_response` for domains and their subdomains
+    domain_block_responses: HashMap<String, BlockResponsePolicy>,
+    /// Addresses blocked A and AAAA queries resolve to
+    sink_v4: Ipv4Addr,
+    sink_v6: Ipv6Addr,
+    domain_age_policy: Option<DomainAgePolicy>,
+    hash_lists: Vec<HashPrefixList>,
+    hash_verifier: Option<Box<dyn FullHashVerifier>>,
+    category_filter: Option<Arc<CategoryFilter>>,
+    /// Rule set shared with the core, consulted after the own domains
+    rule_source: Option<SharedRuleSet>,
 }
 
 impl NetworkFilter {
@@ -51,13 +114,123 @@ impl NetworkFilter {
     pub fn new() -> Self {
         NetworkFilter {
             blocked_domains: HashMap::new(),
-            redirect_ip: IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)),
+            allowed_domains: HashSet::new(),
+            block_response: BlockResponsePolicy::default(),
+            domain_block_responses: HashMap::new(),
+            sink_v4: Ipv4Addr::UNSPECIFIED,
+            sink_v6: Ipv6Addr::UNSPECIFIED,
+            domain_age_policy: None,
+            hash_lists: Vec::new(),
+            hash_verifier: None,
+            category_filter: None,
+            rule_source: None,
         }
     }
 
+    /// Follow the rule set the core's engine is built from
+    ///
+    /// Reloads of the shared rule set apply to this filter immediately.
+    pub fn set_rule_source(&mut self, source: Option<SharedRuleSet>) {
+        self.rule_source = source;
+    }
+
     /// Set the IP address to redirect blocked domains to
     pub fn set_redirect_ip(&mut self, ip: IpAddr) {
-        self.redirect_ip = ip;
+        self.block_response = BlockResponsePolicy::CustomIp(ip);
+    }
+
+    /// Set the addresses blocked A and AAAA queries resolve to
+    pub fn set_sink_addresses(&mut self, v4: Ipv4Addr, v6: Ipv6Addr) {
+        self.sink_v4 = v4;
+        self.sink_v6 = v6;
+    }
+
+    /// Addresses blocked A and AAAA queries resolve to
+    pub fn sink_addresses(&self) -> (Ipv4Addr, Ipv6Addr) {
+        (self.sink_v4, self.sink_v6)
+    }
+
+    /// Set how queries for blocked domains are answered
+    pub fn set_block_response_policy(&mut self, policy: BlockResponsePolicy) {
+        self.block_response = policy;
+    }
+
+    /// How queries for blocked domains are answered by default
+    pub fn block_response_policy(&self) -> BlockResponsePolicy {
+        self.block_response
+    }
+
+    /// Override the block response for a domain and its subdomains;
+    /// `None` removes the override
+    pub fn set_domain_block_response(&mut self, domain: &str, policy: Option<BlockResponsePolicy>) {
+        let domain = domain.trim_matches('.').to_lowercase();
+        match policy {
+            Some(policy) => self.domain_block_responses.insert(domain, policy),
+            None => self.domain_block_responses.remove(&domain),
+        };
+    }
+
+    /// Block response of a domain: that of the closest domain with an
+    /// override, or the default
+    pub fn block_response_for(&self, domain: &str) -> BlockResponsePolicy {
+        if self.domain_block_responses.is_empty() {
+            return self.block_response;
+        }
+        let domain = domain.trim_matches('.').to_lowercase();
+        let mut candidate = domain.as_str();
+        loop {
+            if let Some(&policy) = self.domain_block_responses.get(candidate) {
+                return policy;
+            }
+            match candidate.split_once('.') {
+                Some((_, parent)) => candidate = parent,
+                None => return self.block_response,
+            }
+        }
+    }
+
+    /// Set the newly-registered-domain policy applied to unlisted domains
+    pub fn set_domain_age_policy(&mut self, policy: Option<DomainAgePolicy>) {
+        self.domain_age_policy = policy;
+    }
+
+    /// Evaluate a domain against the domain age policy, if one is set
+    pub fn check_domain_age(&self, domain: &str) -> Option<DomainAgeVerdict> {
+        self.domain_age_policy
+            .as_ref()
+            .map(|policy| policy.evaluate(domain))
+    }
+
+    /// Add a hash-prefix blocklist
+    pub fn add_hash_list(&mut self, list: HashPrefixList) {
+        self.hash_lists.push(list);
+    }
+
+    /// Set the verifier used to confirm partial hash-prefix matches
+    pub fn set_hash_verifier(&mut self, verifier: Option<Box<dyn FullHashVerifier>>) {
+        self.hash_verifier = verifier;
+    }
+
+    /// Find the hash-prefix list with a confirmed match for a domain
+    pub fn check_hash_lists(&self, domain: &str) -> Option<&str> {
+        let verifier = self.hash_verifier.as_deref();
+        self.hash_lists
+            .iter()
+            .find(|list| list.check_domain_with(domain, verifier).is_confirmed())
+            .map(|list| list.name())
+    }
+
+    /// Set the content category filter, e.g. the one shared with the
+    /// `FilterEngine`
+    pub fn set_category_filter(&mut self, filter: Option<Arc<CategoryFilter>>) {
+        self.category_filter = filter;
+    }
+
+    /// Enabled content category a domain is listed in
+    pub fn check_category(&self, domain: &str) -> Option<ContentCategory> {
+        self.category_filter
+            .as_ref()
+            .and_then(|filter| filter.blocked_category(domain))
     }
 
     /// Add a domain to the blocklist
@@ -73,8 +246,51 @@ impl NetworkFilter {
         }
     }
 
-    /// Check if a domain is blocked
+    /// Allow a domain and its subdomains, overriding every block
+    ///
+    /// Hosts whitelisted in the app should be added here as well, so their
+    /// DNS lookups are not blocked either.
+    pub fn add_allowed_domain(&mut self, domain: &str) {
+        let normalized = domain.trim().trim_start_matches("*.").trim_matches('.');
+        if !normalized.is_empty() {
+            self.allowed_domains.insert(normalized.to_lowercase());
+        }
+    }
+
+    /// Remove an allowed domain, returning whether it was allowed
+    pub fn remove_allowed_domain(&mut self, domain: &str) -> bool {
+        let normalized = domain.trim().trim_start_matches("*.").trim_matches('.');
+        self.allowed_domains.remove(&normalized.to_lowercase())
+    }
+
+    /// Check if a domain or one of its parent domains is allowed
+    pub fn is_allowed(&self, domain: &str) -> bool {
+        if let Some(source) = &self.rule_source {
+            if source.read().allows_domain(domain) {
+                return true;
+            }
+        }
+        if self.allowed_domains.is_empty() {
+            return false;
+        }
+        let normalized = domain.trim_matches('.').to_lowercase();
+        let mut candidate = normalized.as_str();
+        loop {
+            if self.allowed_domains.contains(candidate) {
+                return true;
+            }
+            match candidate.split_once('.') {
+                Some((_, parent)) => candidate = parent,
+                None => return false,
+            }
+        }
+    }
+
+    /// Check if a domain is blocked; allowed domains never are
     pub fn is_blocked(&self, domain: &str) -> bool {
+        if self.is_allowed(domain) {
+            return false;
+        }
         let normalized = domain.trim_matches('.').to_lowercase();
 
         // Check exact match
@@ -91,80 +307,87 @@ impl NetworkFilter {
             }
         }
 
-        false
+        if let Some(source) = &self.rule_source {
+            if source.read().blocks_domain(&normalized) {
+                return true;
+            }
+        }
+
+        // Only confirmed hash matches block; partial hits need verification
+        self.check_hash_lists(&normalized).is_some() || self.check_category(&normalized).is_some()
     }
 
     /// Process a DNS query
     pub fn process_dns_query(&self, query: &DnsQuery) -> DnsResponse {
-        let blocked = self.is_blocked(&query.domain);
-
-        let answers = if blocked {
-            match query.query_type {
-                DnsQueryType::A => {
-                    if let IpAddr::V4(ipv4) = self.redirect_ip {
-                        vec![DnsAnswer::A(ipv4)]
-                    } else {
-                        vec![]
-                    }
-                }
-                DnsQueryType::AAAA => {
-                    if let IpAddr::V6(ipv6) = self.redirect_ip {
-                        vec![DnsAnswer::AAAA(ipv6)]
-                    } else {
-                        vec![]
-                    }
-                }
-                _ => vec![],
-            }
+        let allowed = self.is_allowed(&query.domain);
+        let mut blocked = !allowed && self.is_blocked(&query.domain);
+        let mut flagged = false;
+        let category = if allowed {
+            None
         } else {
-            vec![]
+            self.check_category(&query.domain)
+        };
+
+        // Only consult the (potentially slow) age resolver for unlisted domains
+        if !blocked && !allowed {
+            match self.check_domain_age(&query.domain) {
+                Some(DomainAgeVerdict::Blocked { .. }) => blocked = true,
+                Some(DomainAgeVerdict::Flagged { .. }) => flagged = true,
+                _ => {}
+            }
+        }
+
+        let policy = blocked.then(|| self.block_response_for(&query.domain));
+        let (answers, response_code) = match policy {
+            None => (vec![], DnsResponseCode::NoError),
+            Some(BlockResponsePolicy::NullIp) => (
+                self.sink_answers(query.query_type, None),
+                DnsResponseCode::NoError,
+            ),
+            Some(BlockResponsePolicy::CustomIp(ip)) => (
+                self.sink_answers(query.query_type, Some(ip)),
+                DnsResponseCode::NoError,
+            ),
+            Some(BlockResponsePolicy::Nxdomain) => (vec![], DnsResponseCode::NxDomain),
+            Some(BlockResponsePolicy::Refused) => (vec![], DnsResponseCode::Refused),
         };
 
         DnsResponse {
             transaction_id: query.transaction_id,
             answers,
+            response_code,
             blocked,
+            flagged,
+            category,
         }
     }
 
-    /// Load blocked domains from filter rules
-    pub fn load_from_rules(&mut self, rules: &[String]) {
-        for rule in rules {
-            // Skip comments and empty lines
-            if rule.trim().is_empty() || rule.starts_with('!') {
-                continue;
-            }
-
-            // Extract domain from rule
-            if let Some(domain) = extract_domain_from_rule(rule) {
-                self.add_blocked_domain(&domain);
-            }
+    /// Answer of a blocked query, with `custom` replacing the sink address
+    /// of its family
+    ///
+    /// Both A and AAAA queries get an address, so apps on IPv6-only
+    /// networks fail fast instead of waiting on an empty answer.
+    fn sink_answers(&self, query_type: DnsQueryType, custom: Option<IpAddr>) -> Vec<DnsAnswer> {
+        match (query_type, custom) {
+            (DnsQueryType::A, Some(IpAddr::V4(ipv4))) => vec![DnsAnswer::A(ipv4)],
+            (DnsQueryType::A, _) => vec![DnsAnswer::A(self.sink_v4)],
+            (DnsQueryType::AAAA, Some(IpAddr::V6(ipv6))) => vec![DnsAnswer::AAAA(ipv6)],
+            (DnsQueryType::AAAA, _) => vec![DnsAnswer::AAAA(self.sink_v6)],
+            _ => vec![],
         }
     }
-}
 
-/// Extract domain from a filter rule
-fn extract_domain_from_rule(rule: &str) -> Option<String> {
-    let rule = rule.trim();
-
-    // Handle domain rules like ||example.com^
-    if let Some(stripped) = rule.strip_prefix("||") {
-        if let Some(domain_end) = stripped.find('^') {
-            return Some(stripped[..domain_end].to_string());
+    /// Load blocked domains from whole-domain filter rules, and allowed
+    /// domains from their `@@` exceptions
+    pub fn load_from_rules(&mut self, rules: &[String]) {
+        for rule in rules {
+            match parse_domain_rule(rule) {
+                Some(DomainRule::Block(domain)) => self.add_blocked_domain(&domain),
+                Some(DomainRule::Allow(domain)) => self.add_allowed_domain(&domain),
+                None => {}
+            }
         }
     }
-
-    // Handle simple domain rules
-    if !rule.contains('/')
-        && !rule.contains('*')
-        && !rule.contains('?')
-        && rule.contains('.')
-        && !rule.starts_with('.')
-    {
-        return Some(rule.to_string());
-    }
-
-    None
 }
 
 impl Default for NetworkFilter {
@@ -0,0 +1,573 @@
+//! IP packet parsing for the VPN tun device
+//!
+//! Parses raw IPv4/IPv6 packets read from a tun file descriptor into a
+//! [`PacketInfo`], validates and recomputes their checksums, rewrites their
+//! endpoints and builds UDP packets to write back, so the Android
+//! `VpnService` and the iOS packet tunnel share one tested implementation.
+
+use crate::dns_server::check_query;
+use crate::network::NetworkFilter;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+/// IP protocol number of TCP
+const PROTOCOL_TCP: u8 = 6;
+
+/// IP protocol number of UDP
+const PROTOCOL_UDP: u8 = 17;
+
+/// Hop limit of built packets
+const DEFAULT_TTL: u8 = 64;
+
+/// IP version of a packet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IpVersion {
+    V4,
+    V6,
+}
+
+/// Transport protocol of a packet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransportProtocol {
+    Tcp,
+    Udp,
+    Other(u8),
+}
+
+impl TransportProtocol {
+    fn from_number(number: u8) -> Self {
+        match number {
+            PROTOCOL_TCP => TransportProtocol::Tcp,
+            PROTOCOL_UDP => TransportProtocol::Udp,
+            other => TransportProtocol::Other(other),
+        }
+    }
+
+    fn number(self) -> u8 {
+        match self {
+            TransportProtocol::Tcp => PROTOCOL_TCP,
+            TransportProtocol::Udp => PROTOCOL_UDP,
+            TransportProtocol::Other(number) => number,
+        }
+    }
+}
+
+/// A packet that could not be parsed or built
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PacketError {
+    /// The packet is shorter than its headers say
+    Truncated,
+    /// The IP version nibble is neither 4 nor 6
+    UnsupportedVersion(u8),
+    /// A header field is out of range
+    InvalidHeader,
+    /// The packet has no TCP/UDP header to rewrite
+    NoTransportHeader,
+    /// Source and destination are of different address families
+    AddressFamilyMismatch,
+    /// The payload doesn't fit in a single packet
+    TooLarge,
+}
+
+impl std::fmt::Display for PacketError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PacketError::Truncated => write!(f, "Truncated packet"),
+            PacketError::UnsupportedVersion(version) => {
+                write!(f, "IP version {version} is not supported")
+            }
+            PacketError::InvalidHeader => write!(f, "Invalid packet header"),
+            PacketError::NoTransportHeader => write!(f, "Packet has no TCP/UDP header"),
+            PacketError::AddressFamilyMismatch => {
+                write!(f, "Source and destination address families differ")
+            }
+            PacketError::TooLarge => write!(f, "Payload too large for a packet"),
+        }
+    }
+}
+
+impl std::error::Error for PacketError {}
+
+/// Headers of a parsed IP packet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PacketInfo {
+    pub version: IpVersion,
+    pub protocol: TransportProtocol,
+    pub source: IpAddr,
+    pub destination: IpAddr,
+    /// Ports of TCP/UDP packets; `None` for other protocols and for
+    /// fragments without the transport header
+    pub source_port: Option<u16>,
+    pub destination_port: Option<u16>,
+    /// Part of a fragmented packet, so the transport checksum covers
+    /// bytes not in this packet
+    pub fragment: bool,
+    /// Offset of the transport header
+    pub transport_offset: usize,
+    /// Offset of the transport payload; the transport offset when the
+    /// transport header wasn't parsed
+    pub payload_offset: usize,
+    /// Length of the packet per its IP header, excluding trailing padding
+    pub total_len: usize,
+}
+
+impl PacketInfo {
+    pub fn source_addr(&self) -> Option<SocketAddr> {
+        Some(SocketAddr::new(self.source, self.source_port?))
+    }
+
+    pub fn destination_addr(&self) -> Option<SocketAddr> {
+        Some(SocketAddr::new(self.destination, self.destination_port?))
+    }
+
+    /// Transport payload of the packet the info was parsed from
+    pub fn payload<'a>(&self, packet: &'a [u8]) -> &'a [u8] {
+        &packet[self.payload_offset..self.total_len]
+    }
+
+    /// Whether the packet is a plain DNS query over UDP
+    pub fn is_dns_query(&self) -> bool {
+        self.protocol == TransportProtocol::Udp && self.destination_port == Some(53)
+    }
+
+    fn has_transport_header(&self) -> bool {
+        self.source_port.is_some()
+    }
+}
+
+/// Parse the IP and TCP/UDP headers of a raw packet
+pub fn parse_packet(packet: &[u8]) -> Result<PacketInfo, PacketError> {
+    let first = *packet.first().ok_or(PacketError::Truncated)?;
+    match first >> 4 {
+        4 => parse_ipv4(packet),
+        6 => parse_ipv6(packet),
+        version => Err(PacketError::UnsupportedVersion(version)),
+    }
+}
+
+fn parse_ipv4(packet: &[u8]) -> Result<PacketInfo, PacketError> {
+    let header = packet.get(..20).ok_or(PacketError::Truncated)?;
+    let header_len = (header[0] & 0x0F) as usize * 4;
+    let total_len = u16::from_be_bytes([header[2], header[3]]) as usize;
+    if header_len < 20 || total_len < header_len {
+        return Err(PacketError::InvalidHeader);
+    }
+    if packet.len() < total_len {
+        return Err(PacketError::Truncated);
+    }
+    let more_fragments = header[6] & 0x20 != 0;
+    let fragment_offset = u16::from_be_bytes([header[6] & 0x1F, header[7]]);
+    let source = Ipv4Addr::new(header[12], header[13], header[14], header[15]);
+    let destination = Ipv4Addr::new(header[16], header[17], header[18], header[19]);
+    let mut info = PacketInfo {
+        version: IpVersion::V4,
+        protocol: TransportProtocol::from_number(header[9]),
+        source: source.into(),
+        destination: destination.into(),
+        source_port: None,
+        destination_port: None,
+        fragment: more_fragments || fragment_offset != 0,
+        transport_offset: header_len,
+        payload_offset: header_len,
+        total_len,
+    };
+    // Later fragments carry no transport header
+    if fragment_offset == 0 {
+        parse_transport(packet, &mut info)?;
+    }
+    Ok(info)
+}
+
+fn parse_ipv6(packet: &[u8]) -> Result<PacketInfo, PacketError> {
+    let header = packet.get(..40).ok_or(PacketError::Truncated)?;
+    let total_len = 40 + u16::from_be_bytes([header[4], header[5]]) as usize;
+    if packet.len() < total_len {
+        return Err(PacketError::Truncated);
+    }
+    let address = |offset: usize| {
+        let octets: [u8; 16] = header[offset..offset + 16].try_into().unwrap();
+        Ipv6Addr::from(octets)
+    };
+    let mut next_header = header[6];
+    let mut offset = 40;
+    let mut fragment = false;
+    let mut first_fragment = true;
+    // Skip the extension headers up to the transport header
+    loop {
+        match next_header {
+            // Hop-by-hop, routing and destination options
+            0 | 43 | 60 => {
+                let extension = packet
+                    .get(offset..offset + 2)
+                    .filter(|_| offset + 2 <= total_len)
+                    .ok_or(PacketError::Truncated)?;
+                next_header = extension[0];
+                offset += (extension[1] as usize + 1) * 8;
+            }
+            44 => {
+                let extension = packet
+                    .get(offset..offset + 8)
+                    .filter(|_| offset + 8 <= total_len)
+                    .ok_or(PacketError::Truncated)?;
+                next_header = extension[0];
+                fragment = true;
+                first_fragment = u16::from_be_bytes([extension[2], extension[3]]) >> 3 == 0;
+                offset += 8;
+            }
+            _ => break,
+        }
+    }
+    if offset > total_len {
+        return Err(PacketError::Truncated);
+    }
+    let mut info = PacketInfo {
+        version: IpVersion::V6,
+        protocol: TransportProtocol::from_number(next_header),
+        source: address(8).into(),
+        destination: address(24).into(),
+        source_port: None,
+        destination_port: None,
+        fragment,
+        transport_offset: offset,
+        payload_offset: offset,
+        total_len,
+    };
+    if first_fragment {
+        parse_transport(packet, &mut info)?;
+    }
+    Ok(info)
+}
+
+/// Fill in the ports and payload offset of a TCP/UDP packet
+fn parse_transport(packet: &[u8], info: &mut PacketInfo) -> Result<(), PacketError> {
+    let segment = &packet[info.transport_offset..info.total_len];
+    let header_len = match info.protocol {
+        TransportProtocol::Tcp => {
+            let offset = *segment.get(12).ok_or(PacketError::Truncated)?;
+            let header_len = (offset >> 4) as usize * 4;
+            if header_len < 20 {
+                return Err(PacketError::InvalidHeader);
+            }
+            header_len
+        }
+        TransportProtocol::Udp => 8,
+        TransportProtocol::Other(_) => return Ok(()),
+    };
+    if segment.len() < header_len {
+        return Err(PacketError::Truncated);
+    }
+    info.source_port = Some(u16::from_be_bytes([segment[0], segment[1]]));
+    info.destination_port = Some(u16::from_be_bytes([segment[2], segment[3]]));
+    info.payload_offset = info.transport_offset + header_len;
+    Ok(())
+}
+
+/// Add 16-bit big-endian words to a one's complement sum
+fn sum_words(data: &[u8], mut sum: u32) -> u32 {
+    let mut chunks = data.chunks_exact(2);
+    for word in &mut chunks {
+        sum += u16::from_be_bytes([word[0], word[1]]) as u32;
+    }
+    if let [last] = chunks.remainder() {
+        sum += (*last as u32) << 8;
+    }
+    sum
+}
+
+/// Fold a sum into a one's complement checksum
+fn fold(mut sum: u32) -> u16 {
+    while sum > 0xFFFF {
+        sum = (sum & 0xFFFF) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// Sum of the pseudo header covered by TCP/UDP checksums
+fn pseudo_header_sum(info: &PacketInfo) -> u32 {
+    let segment_len = (info.total_len - info.transport_offset) as u32;
+    let sum = match (info.source, info.destination) {
+        (IpAddr::V4(source), IpAddr::V4(destination)) => {
+            sum_words(&destination.octets(), sum_words(&source.octets(), 0))
+        }
+        (IpAddr::V6(source), IpAddr::V6(destination)) => {
+            sum_words(&destination.octets(), sum_words(&source.octets(), 0))
+        }
+        _ => unreachable!("parsed packets have one address family"),
+    };
+    sum + (segment_len >> 16) + (segment_len & 0xFFFF) + info.protocol.number() as u32
+}
+
+/// Offset of the checksum within the transport header
+fn transport_checksum_offset(info: &PacketInfo) -> Option<usize> {
+    match info.protocol {
+        TransportProtocol::Tcp => Some(16),
+        TransportProtocol::Udp => Some(6),
+        TransportProtocol::Other(_) => None,
+    }
+}
+
+/// Whether the IPv4 header and TCP/UDP checksums of a packet are correct
+///
+/// Transport checksums of fragments can't be checked and are accepted, as
+/// are UDP over IPv4 packets sent without a checksum.
+pub fn verify_checksums(packet: &[u8], info: &PacketInfo) -> bool {
+    if info.version == IpVersion::V4 && fold(sum_words(&packet[..info.transport_offset], 0)) != 0 {
+        return false;
+    }
+    let Some(checksum_offset) = transport_checksum_offset(info) else {
+        return true;
+    };
+    if info.fragment || !info.has_transport_header() {
+        return true;
+    }
+    let segment = &packet[info.transport_offset..info.total_len];
+    if info.protocol == TransportProtocol::Udp
+        && info.version == IpVersion::V4
+        && segment[checksum_offset..checksum_offset + 2] == [0, 0]
+    {
+        return true;
+    }
+    fold(sum_words(segment, pseudo_header_sum(info))) == 0
+}
+
+/// Recompute the IPv4 header and TCP/UDP checksums of a packet
+pub fn update_checksums(packet: &mut [u8], info: &PacketInfo) {
+    if info.version == IpVersion::V4 {
+        packet[10..12].copy_from_slice(&[0, 0]);
+        let checksum = fold(sum_words(&packet[..info.transport_offset], 0));
+        packet[10..12].copy_from_slice(&checksum.to_be_bytes());
+    }
+    let Some(checksum_offset) = transport_checksum_offset(info) else {
+        return;
+    };
+    if info.fragment || !info.has_transport_header() {
+        return;
+    }
+    let checksum_at = info.transport_offset + checksum_offset;
+    packet[checksum_at..checksum_at + 2].copy_from_slice(&[0, 0]);
+    let segment = &packet[info.transport_offset..info.total_len];
+    let mut checksum = fold(sum_words(segment, pseudo_header_sum(info)));
+    // Zero means "no checksum" for UDP
+    if checksum == 0 && info.protocol == TransportProtocol::Udp {
+        checksum = 0xFFFF;
+    }
+    packet[checksum_at..checksum_at + 2].copy_from_slice(&checksum.to_be_bytes());
+}
+
+/// Point a TCP/UDP packet at another destination, e.g. to forward DNS
+/// queries to an upstream resolver, returning its updated info
+pub fn rewrite_destination(
+    packet: &mut [u8],
+    info: &PacketInfo,
+    destination: SocketAddr,
+) -> Result<PacketInfo, PacketError> {
+    rewrite_endpoint(packet, info, destination, false)
+}
+
+/// Change the source of a TCP/UDP packet, e.g. to make a forwarded answer
+/// come from the address the client queried, returning its updated info
+pub fn rewrite_source(
+    packet: &mut [u8],
+    info: &PacketInfo,
+    source: SocketAddr,
+) -> Result<PacketInfo, PacketError> {
+    rewrite_endpoint(packet, info, source, true)
+}
+
+fn rewrite_endpoint(
+    packet: &mut [u8],
+    info: &PacketInfo,
+    endpoint: SocketAddr,
+    source: bool,
+) -> Result<PacketInfo, PacketError> {
+    if !info.has_transport_header() || info.fragment {
+        return Err(PacketError::NoTransportHeader);
+    }
+    let (address_at, port_at) = match (info.version, endpoint.ip(), source) {
+        (IpVersion::V4, IpAddr::V4(_), true) => (12, 0),
+        (IpVersion::V4, IpAddr::V4(_), false) => (16, 2),
+        (IpVersion::V6, IpAddr::V6(_), true) => (8, 0),
+        (IpVersion::V6, IpAddr::V6(_), false) => (24, 2),
+        _ => return Err(PacketError::AddressFamilyMismatch),
+    };
+    let octets = match endpoint.ip() {
+        IpAddr::V4(ip) => ip.octets().to_vec(),
+        IpAddr::V6(ip) => ip.octets().to_vec(),
+    };
+    packet[address_at..address_at + octets.len()].copy_from_slice(&octets);
+    let port_at = info.transport_offset + port_at;
+    packet[port_at..port_at + 2].copy_from_slice(&endpoint.port().to_be_bytes());
+
+    let mut updated = info.clone();
+    if source {
+        updated.source = endpoint.ip();
+        updated.source_port = Some(endpoint.port());
+    } else {
+        updated.destination = endpoint.ip();
+        updated.destination_port = Some(endpoint.port());
+    }
+    update_checksums(packet, &updated);
+    Ok(updated)
+}
+
+/// Build a UDP packet to write to the tun device
+pub fn build_udp_packet(
+    source: SocketAddr,
+    destination: SocketAddr,
+    payload: &[u8],
+) -> Result<Vec<u8>, PacketError> {
+    let udp_len = u16::try_from(8 + payload.len()).map_err(|_| PacketError::TooLarge)?;
+    let mut packet = match (source.ip(), destination.ip()) {
+        (IpAddr::V4(source), IpAddr::V4(destination)) => {
+            let total_len =
+                u16::try_from(20 + udp_len as usize).map_err(|_| PacketError::TooLarge)?;
+            let mut header = vec![0x45, 0];
+            header.extend_from_slice(&total_len.to_be_bytes());
+            // No identification, don't fragment
+            header.extend_from_slice(&[0, 0, 0x40, 0, DEFAULT_TTL, PROTOCOL_UDP, 0, 0]);
+            header.extend_from_slice(&source.octets());
+            header.extend_from_slice(&destination.octets());
+            header
+        }
+        (IpAddr::V6(source), IpAddr::V6(destination)) => {
+            let mut header = vec![0x60, 0, 0, 0];
+            header.extend_from_slice(&udp_len.to_be_bytes());
+            header.extend_from_slice(&[PROTOCOL_UDP, DEFAULT_TTL]);
+            header.extend_from_slice(&source.octets());
+            header.extend_from_slice(&destination.octets());
+            header
+        }
+        _ => return Err(PacketError::AddressFamilyMismatch),
+    };
+    packet.extend_from_slice(&source.port().to_be_bytes());
+    packet.extend_from_slice(&destination.port().to_be_bytes());
+    packet.extend_from_slice(&udp_len.to_be_bytes());
+    packet.extend_from_slice(&[0, 0]);
+    packet.extend_from_slice(payload);
+    let info = parse_packet(&packet)?;
+    update_checksums(&mut packet, &info);
+    Ok(packet)
+}
+
+/// UDP packet answering a received one with `payload`
+pub fn udp_reply(info: &PacketInfo, payload: &[u8]) -> Result<Vec<u8>, PacketError> {
+    let (Some(source), Some(destination)) = (info.source_addr(), info.destination_addr()) else {
+        return Err(PacketError::NoTransportHeader);
+    };
+    build_udp_packet(destination, source, payload)
+}
+
+/// Answer a DNS query packet read from the tun device if its domain is
+/// blocked
+///
+/// Returns the reply packet to write back, or `None` if the packet is not
+/// a blocked DNS query and should be forwarded.
+pub fn answer_dns_packet(filter: &NetworkFilter, packet: &[u8]) -> Option<Vec<u8>> {
+    let info = parse_packet(packet).ok()?;
+    if !info.is_dns_query() || info.fragment {
+        return None;
+    }
+    let answer = check_query(filter, info.payload(packet))?.answer?;
+    udp_reply(&info, &answer).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dns_query(domain: &str) -> Vec<u8> {
+        let mut message = vec![0xAB, 0xCD, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0];
+        for label in domain.split('.') {
+            message.push(label.len() as u8);
+            message.extend_from_slice(label.as_bytes());
+        }
+        message.extend_from_slice(&[0, 0, 1, 0, 1]);
+        message
+    }
+
+    #[test]
+    fn test_parse_udp_packets() {
+        let client: SocketAddr = "10.0.0.2:40000".parse().unwrap();
+        let resolver: SocketAddr = "10.0.0.1:53".parse().unwrap();
+        let packet = build_udp_packet(client, resolver, b"query").unwrap();
+        let info = parse_packet(&packet).unwrap();
+        assert_eq!(info.version, IpVersion::V4);
+        assert_eq!(info.protocol, TransportProtocol::Udp);
+        assert_eq!(info.source_addr(), Some(client));
+        assert_eq!(info.destination_addr(), Some(resolver));
+        assert_eq!(info.payload(&packet), b"query");
+        assert!(info.is_dns_query());
+        assert!(verify_checksums(&packet, &info));
+
+        let client: SocketAddr = "[fd00::2]:40000".parse().unwrap();
+        let resolver: SocketAddr = "[fd00::1]:53".parse().unwrap();
+        let packet = build_udp_packet(client, resolver, b"query").unwrap();
+        let info = parse_packet(&packet).unwrap();
+        assert_eq!(info.version, IpVersion::V6);
+        assert_eq!(info.destination_addr(), Some(resolver));
+        assert!(verify_checksums(&packet, &info));
+
+        assert_eq!(parse_packet(&packet[..30]), Err(PacketError::Truncated));
+        assert_eq!(
+            parse_packet(&[0x50]),
+            Err(PacketError::UnsupportedVersion(5))
+        );
+    }
+
+    #[test]
+    fn test_parse_tcp_packet() {
+        // SYN from 192.168.1.2:51000 to 93.184.216.34:443 with MSS option
+        let mut packet = vec![
+            0x45, 0, 0, 44, 0x12, 0x34, 0x40, 0, 64, 6, 0, 0, 192, 168, 1, 2, 93, 184, 216, 34,
+        ];
+        packet.extend_from_slice(&51000u16.to_be_bytes());
+        packet.extend_from_slice(&443u16.to_be_bytes());
+        packet.extend_from_slice(&[0, 0, 0, 1, 0, 0, 0, 0, 0x60, 0x02, 0xFF, 0xFF, 0, 0, 0, 0]);
+        packet.extend_from_slice(&[2, 4, 0x05, 0xB4]);
+        let info = parse_packet(&packet).unwrap();
+        update_checksums(&mut packet, &info);
+
+        assert_eq!(info.protocol, TransportProtocol::Tcp);
+        assert_eq!(info.destination_port, Some(443));
+        assert_eq!(info.payload_offset, 44);
+        assert!(verify_checksums(&packet, &info));
+        packet[25] ^= 0xFF;
+        assert!(!verify_checksums(&packet, &info));
+    }
+
+    #[test]
+    fn test_rewrite_destination() {
+        let client: SocketAddr = "10.0.0.2:40000".parse().unwrap();
+        let mut packet =
+            build_udp_packet(client, "10.0.0.1:53".parse().unwrap(), b"query").unwrap();
+        let info = parse_packet(&packet).unwrap();
+        let upstream: SocketAddr = "1.1.1.1:53".parse().unwrap();
+
+        let rewritten = rewrite_destination(&mut packet, &info, upstream).unwrap();
+        assert_eq!(rewritten, parse_packet(&packet).unwrap());
+        assert_eq!(rewritten.destination_addr(), Some(upstream));
+        assert!(verify_checksums(&packet, &rewritten));
+        assert_eq!(
+            rewrite_source(&mut packet, &rewritten, "[::1]:53".parse().unwrap()),
+            Err(PacketError::AddressFamilyMismatch)
+        );
+    }
+
+    #[test]
+    fn test_answer_dns_packet() {
+        let mut filter = NetworkFilter::new();
+        filter.add_blocked_domain("ads.example");
+        let client: SocketAddr = "10.0.0.2:40000".parse().unwrap();
+        let resolver: SocketAddr = "10.0.0.1:53".parse().unwrap();
+
+        let query = build_udp_packet(client, resolver, &dns_query("ads.example")).unwrap();
+        let reply = answer_dns_packet(&filter, &query).unwrap();
+        let info = parse_packet(&reply).unwrap();
+        assert_eq!(info.source_addr(), Some(resolver));
+        assert_eq!(info.destination_addr(), Some(client));
+        assert!(verify_checksums(&reply, &info));
+        assert_eq!(&info.payload(&reply)[..2], &[0xAB, 0xCD]);
+
+        let allowed = build_udp_packet(client, resolver, &dns_query("ok.example")).unwrap();
+        assert_eq!(answer_dns_packet(&filter, &allowed), None);
+    }
+}
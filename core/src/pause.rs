@@ -0,0 +1,131 @@
+//! Temporary and scheduled pauses
+//!
+//! Blocking can be paused for a while ("disable for 30 minutes") or during
+//! recurring windows such as 9:00–17:00 on weekdays. Both are checked on
+//! every request, so the engine keeps running while paused.
+
+use chrono::{Datelike, NaiveDateTime, NaiveTime, Weekday};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime};
+
+/// Recurring period during which blocking is paused
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PauseWindow {
+    /// Days the window starts on; empty means every day
+    #[serde(default)]
+    pub days: Vec<Weekday>,
+    /// Local start time
+    pub start: NaiveTime,
+    /// Local end time; a window ending before it starts runs past midnight
+    pub end: NaiveTime,
+}
+
+impl PauseWindow {
+    /// Window on the given days, from `start` to `end` local time
+    pub fn new(days: Vec<Weekday>, start: NaiveTime, end: NaiveTime) -> Self {
+        PauseWindow { days, start, end }
+    }
+
+    fn starts_on(&self, day: Weekday) -> bool {
+        self.days.is_empty() || self.days.contains(&day)
+    }
+
+    /// Whether a local date and time falls in the window
+    pub fn contains(&self, local: NaiveDateTime) -> bool {
+        let (day, time) = (local.weekday(), local.time());
+        if self.start <= self.end {
+            self.starts_on(day) && time >= self.start && time < self.end
+        } else {
+            // The part after midnight belongs to the previous day's window
+            (self.starts_on(day) && time >= self.start)
+                || (self.starts_on(day.pred()) && time < self.end)
+        }
+    }
+}
+
+/// Manual pause plus the recurring pause windows
+#[derive(Debug, Clone, Default)]
+pub struct PauseState {
+    paused_until: Option<SystemTime>,
+}
+
+impl PauseState {
+    /// Pause until `duration` from now
+    pub fn pause(&mut self, duration: Duration) {
+        self.paused_until = Some(SystemTime::now() + duration);
+    }
+
+    /// End a manual pause
+    pub fn resume(&mut self) {
+        self.paused_until = None;
+    }
+
+    /// End of the manual pause, if one is running
+    pub fn paused_until(&self) -> Option<SystemTime> {
+        self.paused_until.filter(|until| *until > SystemTime::now())
+    }
+
+    /// Whether blocking is paused now, manually or by one of `windows`
+    pub fn is_paused(&self, windows: &[PauseWindow]) -> bool {
+        if self.paused_until().is_some() {
+            return true;
+        }
+        if windows.is_empty() {
+            return false;
+        }
+        let local = chrono::Local::now().naive_local();
+        windows.iter().any(|window| window.contains(local))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn at(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        // 2024-01-01 was a Monday
+        NaiveDate::from_ymd_opt(2024, 1, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_pause_window_contains() {
+        let time = |hour| NaiveTime::from_hms_opt(hour, 0, 0).unwrap();
+        let work = PauseWindow::new(
+            vec![
+                Weekday::Mon,
+                Weekday::Tue,
+                Weekday::Wed,
+                Weekday::Thu,
+                Weekday::Fri,
+            ],
+            time(9),
+            time(17),
+        );
+        assert!(work.contains(at(1, 9, 0)));
+        assert!(work.contains(at(5, 16, 59)));
+        assert!(!work.contains(at(1, 17, 0)));
+        assert!(!work.contains(at(6, 12, 0)));
+
+        // Friday night until Saturday morning
+        let night = PauseWindow::new(vec![Weekday::Fri], time(22), time(6));
+        assert!(night.contains(at(5, 23, 0)));
+        assert!(night.contains(at(6, 5, 59)));
+        assert!(!night.contains(at(1, 5, 0)));
+    }
+
+    #[test]
+    fn test_manual_pause() {
+        let mut state = PauseState::default();
+        assert!(!state.is_paused(&[]));
+        state.pause(Duration::from_secs(60));
+        assert!(state.is_paused(&[]));
+        state.resume();
+        assert!(!state.is_paused(&[]));
+        state.pause(Duration::ZERO);
+        assert!(!state.is_paused(&[]));
+    }
+}
@@ -0,0 +1,434 @@
+//! Procedural cosmetic filters (`:has`, `:has-text`, `:upward`)
+//!
+//! Extended selectors from uBlock Origin and AdGuard cannot be injected as
+//! plain CSS. They are parsed into a list of steps that the injected
+//! JavaScript on Android/iOS evaluates against the page.
+
+use crate::cosmetic::domain_matches;
+use serde::{Deserialize, Serialize};
+
+/// Procedural pseudo-classes and their aliases
+const OPERATORS: &[(&str, Operator)] = &[
+    (":has-text(", Operator::HasText),
+    (":-abp-contains(", Operator::HasText),
+    (":contains(", Operator::HasText),
+    (":has(", Operator::Has),
+    (":-abp-has(", Operator::Has),
+    (":upward(", Operator::Upward),
+];
+
+/// Deepest `:has` nesting accepted; deeper rules are rejected rather than
+/// parsed recursively, as a hostile list could overflow the stack
+pub const MAX_NESTING: usize = 32;
+
+#[derive(Debug, Clone, Copy)]
+enum Operator {
+    Has,
+    HasText,
+    Upward,
+}
+
+/// Text to look for with `:has-text`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TextPattern {
+    /// Literal text, or the regex source if `regex` is set
+    pub pattern: String,
+    /// Whether `pattern` is a regular expression
+    pub regex: bool,
+    /// Regex flags (e.g. `i`)
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub flags: String,
+}
+
+/// Ancestor to move to with `:upward`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum UpwardTarget {
+    /// Number of levels to go up
+    Count(u32),
+    /// Closest ancestor matching a selector
+    Selector(String),
+}
+
+/// One step of a procedural selector, applied left to right
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "arg", rename_all = "kebab-case")]
+pub enum ProceduralStep {
+    /// Plain CSS, queried relative to the current elements
+    Css(String),
+    /// Keep elements containing a match for the nested steps
+    Has(Vec<ProceduralStep>),
+    /// Keep elements whose text matches
+    HasText(TextPattern),
+    /// Replace elements with an ancestor
+    Upward(UpwardTarget),
+}
+
+/// A parsed procedural element hiding rule
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProceduralFilter {
+    /// Original selector text
+    pub selector: String,
+    /// Parsed steps
+    pub steps: Vec<ProceduralStep>,
+    /// Domains the rule applies to (empty = all domains)
+    #[serde(skip)]
+    pub domains: Vec<String>,
+    /// Domains the rule is disabled on (`~domain`)
+    #[serde(skip)]
+    pub excluded_domains: Vec<String>,
+    /// Whether this is a `#@#` rule that disables the selector
+    #[serde(skip)]
+    pub exception: bool,
+}
+
+impl ProceduralFilter {
+    /// Parse a rule like `example.com##.post:has-text(Sponsored):upward(2)`
+    ///
+    /// Accepts the `##`, `#?#`, `#@#` and `#@?#` separators. Returns `None`
+    /// if the selector has no procedural operator.
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = line.trim();
+        let (separator_pos, separator_len, exception) =
+            [("#@?#", true), ("#@#", true), ("#?#", false), ("##", false)]
+                .iter()
+                .find_map(|(separator, exception)| {
+                    line.find(separator)
+                        .map(|pos| (pos, separator.len(), *exception))
+                })?;
+
+        let domains_part = &line[..separator_pos];
+        let selector = line[separator_pos + separator_len..].trim();
+        if selector.starts_with("+js(") || !is_procedural(selector) {
+            return None;
+        }
+        let steps = parse_steps(selector, 0)?;
+
+        let mut domains = Vec::new();
+        let mut excluded_domains = Vec::new();
+        for domain in domains_part.split(',') {
+            let domain = domain.trim().to_lowercase();
+            if domain.is_empty() {
+                continue;
+            }
+            if let Some(excluded) = domain.strip_prefix('~') {
+                excluded_domains.push(excluded.to_string());
+            } else {
+                domains.push(domain);
+            }
+        }
+
+        Some(ProceduralFilter {
+            selector: selector.to_string(),
+            steps,
+            domains,
+            excluded_domains,
+            exception,
+        })
+    }
+
+    /// Check if the rule applies to a page domain
+    pub fn applies_to(&self, domain: &str) -> bool {
+        let domain = domain.to_lowercase();
+
+        if self
+            .excluded_domains
+            .iter()
+            .any(|excluded| domain_matches(&domain, excluded))
+        {
+            return false;
+        }
+
+        self.domains.is_empty() || self.domains.iter().any(|d| domain_matches(&domain, d))
+    }
+}
+
+/// Whether a selector uses a procedural operator
+pub fn is_procedural(selector: &str) -> bool {
+    OPERATORS
+        .iter()
+        .any(|(prefix, _)| selector.contains(prefix))
+}
+
+/// Split a selector into CSS and procedural steps
+///
+/// `depth` is the number of enclosing `:has` operators.
+fn parse_steps(selector: &str, depth: usize) -> Option<Vec<ProceduralStep>> {
+    if depth > MAX_NESTING {
+        return None;
+    }
+    let mut steps = Vec::new();
+    let mut rest = selector;
+
+    loop {
+        let next = OPERATORS
+            .iter()
+            .filter_map(|(prefix, op)| rest.find(prefix).map(|pos| (pos, *prefix, *op)))
+            .min_by_key(|(pos, _, _)| *pos);
+
+        let Some((pos, prefix, op)) = next else {
+            push_css(&mut steps, rest);
+            return Some(steps);
+        };
+
+        push_css(&mut steps, &rest[..pos]);
+        let arg_start = pos + prefix.len();
+        let arg_len = closing_paren(&rest[arg_start..])?;
+        let arg = rest[arg_start..arg_start + arg_len].trim();
+
+        steps.push(match op {
+            Operator::Has => {
+                ProceduralStep::Has(parse_steps(arg, depth + 1).filter(|s| !s.is_empty())?)
+            }
+            Operator::HasText => ProceduralStep::HasText(parse_text(arg)?),
+            Operator::Upward => ProceduralStep::Upward(parse_upward(arg)?),
+        });
+        rest = &rest[arg_start + arg_len + 1..];
+    }
+}
+
+fn push_css(steps: &mut Vec<ProceduralStep>, css: &str) {
+    let css = css.trim();
+    if !css.is_empty() {
+        steps.push(ProceduralStep::Css(css.to_string()));
+    }
+}
+
+/// Length of an operator argument up to its closing parenthesis
+fn closing_paren(s: &str) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '(' => depth += 1,
+            ')' if depth == 0 => return Some(i),
+            ')' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+fn parse_text(arg: &str) -> Option<TextPattern> {
+    if arg.is_empty() {
+        return None;
+    }
+    if arg.len() > 2 && arg.starts_with('/') {
+        if let Some(end) = arg.rfind('/').filter(|end| *end > 0) {
+            let flags = &arg[end + 1..];
+            if flags.chars().all(|c| "imsu".contains(c)) {
+                return Some(TextPattern {
+                    pattern: arg[1..end].to_string(),
+                    regex: true,
+                    flags: flags.to_string(),
+                });
+            }
+        }
+    }
+    Some(TextPattern {
+        pattern: arg.to_string(),
+        regex: false,
+        flags: String::new(),
+    })
+}
+
+fn parse_upward(arg: &str) -> Option<UpwardTarget> {
+    match arg.parse::<u32>() {
+        Ok(count) if (1..=256).contains(&count) => Some(UpwardTarget::Count(count)),
+        Ok(_) => None,
+        Err(_) if !arg.is_empty() => Some(UpwardTarget::Selector(arg.to_string())),
+        Err(_) => None,
+    }
+}
+
+/// Collection of procedural filters
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(into = "Vec<StoredFilter>", from = "Vec<StoredFilter>")]
+pub struct ProceduralFilterSet {
+    filters: Vec<ProceduralFilter>,
+}
+
+/// [`ProceduralFilter`] as stored in compiled engines
+///
+/// Unlike the content script form, it keeps the domains.
+#[derive(Serialize, Deserialize)]
+struct StoredFilter {
+    selector: String,
+    steps: Vec<ProceduralStep>,
+    domains: Vec<String>,
+    excluded_domains: Vec<String>,
+    exception: bool,
+}
+
+impl From<ProceduralFilterSet> for Vec<StoredFilter> {
+    fn from(set: ProceduralFilterSet) -> Self {
+        set.filters
+            .into_iter()
+            .map(|filter| StoredFilter {
+                selector: filter.selector,
+                steps: filter.steps,
+                domains: filter.domains,
+                excluded_domains: filter.excluded_domains,
+                exception: filter.exception,
+            })
+            .collect()
+    }
+}
+
+impl From<Vec<StoredFilter>> for ProceduralFilterSet {
+    fn from(filters: Vec<StoredFilter>) -> Self {
+        let filters = filters
+            .into_iter()
+            .map(|filter| ProceduralFilter {
+                selector: filter.selector,
+                steps: filter.steps,
+                domains: filter.domains,
+                excluded_domains: filter.excluded_domains,
+                exception: filter.exception,
+            })
+            .collect();
+        ProceduralFilterSet { filters }
+    }
+}
+
+impl ProceduralFilterSet {
+    /// Create an empty set
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse and add a rule line, returning whether it was a procedural rule
+    pub fn add_rule(&mut self, line: &str) -> bool {
+        match ProceduralFilter::parse(line) {
+            Some(filter) => {
+                self.filters.push(filter);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove one rule equal to the parsed line, returning whether one was removed
+    pub fn remove_rule(&mut self, line: &str) -> bool {
+        let Some(filter) = ProceduralFilter::parse(line) else {
+            return false;
+        };
+        match self.filters.iter().position(|existing| *existing == filter) {
+            Some(index) => {
+                self.filters.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Add all procedural rules from a filter list
+    pub fn load_filter_list(&mut self, content: &str) {
+        for line in content.lines() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('!') {
+                continue;
+            }
+            if trimmed.contains('#') {
+                self.add_rule(trimmed);
+            }
+        }
+    }
+
+    /// Get the filters that apply to a page domain
+    ///
+    /// Filters disabled by a matching `#@#` exception are left out.
+    pub fn filters_for_domain(&self, domain: &str) -> Vec<&ProceduralFilter> {
+        let applicable: Vec<&ProceduralFilter> = self
+            .filters
+            .iter()
+            .filter(|filter| filter.applies_to(domain))
+            .collect();
+
+        applicable
+            .iter()
+            .filter(|filter| {
+                !filter.exception
+                    && !applicable
+                        .iter()
+                        .any(|other| other.exception && other.selector == filter.selector)
+            })
+            .copied()
+            .collect()
+    }
+
+    /// Get the filters that apply to a page domain as a JSON array
+    pub fn to_json(&self, domain: &str) -> Result<String, serde_json::Error> {
+        serde_json::to_string(&self.filters_for_domain(domain))
+    }
+
+    /// Number of stored filters
+    pub fn len(&self) -> usize {
+        self.filters.len()
+    }
+
+    /// Whether the set is empty
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_procedural_filter() {
+        let filter = ProceduralFilter::parse(
+            "example.com##.post:has(.label:has-text(/^Ad(s)?$/i)):upward(2)",
+        )
+        .unwrap();
+        assert_eq!(filter.domains, vec!["example.com"]);
+        assert_eq!(
+            filter.steps,
+            vec![
+                ProceduralStep::Css(".post".to_string()),
+                ProceduralStep::Has(vec![
+                    ProceduralStep::Css(".label".to_string()),
+                    ProceduralStep::HasText(TextPattern {
+                        pattern: "^Ad(s)?$".to_string(),
+                        regex: true,
+                        flags: "i".to_string(),
+                    }),
+                ]),
+                ProceduralStep::Upward(UpwardTarget::Count(2)),
+            ]
+        );
+
+        let filter = ProceduralFilter::parse("#?#div:-abp-contains(Sponsored)").unwrap();
+        assert!(filter.domains.is_empty());
+
+        assert!(ProceduralFilter::parse("##.ad").is_none());
+        assert!(ProceduralFilter::parse("##.ad:has(.x").is_none());
+        assert!(ProceduralFilter::parse("##.ad:upward(0)").is_none());
+    }
+
+    #[test]
+    fn test_deep_nesting_is_rejected() {
+        let nested =
+            |depth: usize| format!("##{}.ad{}", "div:has(".repeat(depth), ")".repeat(depth));
+        assert!(ProceduralFilter::parse(&nested(MAX_NESTING)).is_some());
+        assert!(ProceduralFilter::parse(&nested(MAX_NESTING + 1)).is_none());
+        // Deep enough to overflow the stack if parsed recursively
+        assert!(ProceduralFilter::parse(&nested(100_000)).is_none());
+    }
+
+    #[test]
+    fn test_procedural_json() {
+        let mut set = ProceduralFilterSet::new();
+        set.load_filter_list("##.a:upward(.card)\nshop.com#@#.a:upward(.card)");
+
+        assert_eq!(
+            set.to_json("news.com").unwrap(),
+            r#"[{"selector":".a:upward(.card)","steps":[{"type":"css","arg":".a"},{"type":"upward","arg":".card"}]}]"#
+        );
+        assert_eq!(set.to_json("shop.com").unwrap(), "[]");
+    }
+}
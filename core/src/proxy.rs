@@ -0,0 +1,320 @@
+//! Local HTTP(S) proxy server
+//!
+//! A small forward proxy for desktop use and integration tests that run
+//! real traffic through the engine. `CONNECT host:port` requests and
+//! absolute-URI requests (`GET http://host/path`) are checked before any
+//! upstream connection is made: blocked ones get `403 Forbidden` and are
+//! closed, allowed ones are tunneled byte for byte. HTTPS is never
+//! decrypted, so tunneled requests are checked by host only. A bounded set
+//! of worker threads serves the connections; clients beyond the connection
+//! limit get `503 Service Unavailable`.
+
+use crate::filter_engine::{BlockDecision, FilterEngine};
+use crate::worker_pool::WorkerPool;
+use crate::AdBlockCore;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// Largest request head accepted from a client
+const MAX_HEAD_BYTES: usize = 16 * 1024;
+
+/// How long a client may take to send its request head
+const HEAD_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Default number of connections served at once
+pub const DEFAULT_MAX_CONNECTIONS: usize = 64;
+
+/// Decides whether a proxied request is blocked
+pub trait RequestCheck: Send + Sync {
+    fn check(&self, url: &str) -> BlockDecision;
+}
+
+impl RequestCheck for FilterEngine {
+    fn check(&self, url: &str) -> BlockDecision {
+        self.should_block(url)
+    }
+}
+
+/// Checks through the core, so proxied requests feed the statistics
+impl RequestCheck for AdBlockCore {
+    fn check(&self, url: &str) -> BlockDecision {
+        self.check_url(url, 0)
+    }
+}
+
+/// Running proxy server; stops when shut down or dropped
+pub struct ProxyServer {
+    local_addr: SocketAddr,
+    stopped: Arc<AtomicBool>,
+    accept_thread: Option<JoinHandle<()>>,
+}
+
+impl ProxyServer {
+    /// Listen on `addr` (port 0 picks a free port) and serve in the background
+    pub fn start(
+        addr: impl ToSocketAddrs,
+        checker: Arc<dyn RequestCheck>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::start_with_limit(addr, checker, DEFAULT_MAX_CONNECTIONS)
+    }
+
+    /// Like [`ProxyServer::start`], serving at most `max_connections` at once
+    ///
+    /// As many connections again wait in a queue; clients beyond that are
+    /// answered `503 Service Unavailable` and closed.
+    pub fn start_with_limit(
+        addr: impl ToSocketAddrs,
+        checker: Arc<dyn RequestCheck>,
+        max_connections: usize,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let listener = TcpListener::bind(addr)?;
+        let workers = WorkerPool::new("adblock-proxy-client", max_connections, max_connections)?;
+        let local_addr = listener.local_addr()?;
+        let stopped = Arc::new(AtomicBool::new(false));
+        let stop = Arc::clone(&stopped);
+        let accept_thread = std::thread::Builder::new()
+            .name("adblock-proxy".to_string())
+            .spawn(move || {
+                for client in listener.incoming() {
+                    if stop.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    let Ok(client) = client else {
+                        continue;
+                    };
+                    let mut overflow = client.try_clone();
+                    let checker = Arc::clone(&checker);
+                    let queued = workers.try_execute(move || {
+                        if let Err(e) = handle_client(client, checker.as_ref()) {
+                            tracing::debug!("Proxy connection failed: {e}");
+                        }
+                    });
+                    if !queued {
+                        tracing::debug!("Proxy connection rejected, too many open");
+                        if let Ok(client) = &mut overflow {
+                            let _ = respond(client, "503 Service Unavailable");
+                        }
+                    }
+                }
+            })?;
+        Ok(ProxyServer {
+            local_addr,
+            stopped,
+            accept_thread: Some(accept_thread),
+        })
+    }
+
+    /// Address the proxy listens on
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Stop accepting connections; open tunnels run until they close
+    pub fn shutdown(&mut self) {
+        let Some(thread) = self.accept_thread.take() else {
+            return;
+        };
+        self.stopped.store(true, Ordering::SeqCst);
+        // Wake the accept loop so it sees the flag
+        let _ = TcpStream::connect(self.local_addr);
+        let _ = thread.join();
+    }
+}
+
+impl Drop for ProxyServer {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// Target of a proxied request
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ProxyTarget {
+    host: String,
+    port: u16,
+    /// URL checked against the filters
+    url: String,
+    /// Origin-form path of a plain HTTP request; `None` for `CONNECT`
+    path: Option<String>,
+}
+
+/// Target of a request line, or `None` if the proxy can't serve it
+fn parse_request_target(method: &str, target: &str) -> Option<ProxyTarget> {
+    if method.eq_ignore_ascii_case("CONNECT") {
+        let (host, port) = split_host_port(target, 443)?;
+        let url = match port {
+            443 => format!("https://{host}/"),
+            port => format!("https://{host}:{port}/"),
+        };
+        return Some(ProxyTarget {
+            host,
+            port,
+            url,
+            path: None,
+        });
+    }
+    let rest = target.strip_prefix("http://").or_else(|| {
+        target
+            .get(..7)
+            .filter(|scheme| scheme.eq_ignore_ascii_case("http://"))
+            .map(|_| &target[7..])
+    })?;
+    let (authority, path) = match rest.find(['/', '?']) {
+        Some(index) => (&rest[..index], &rest[index..]),
+        None => (rest, "/"),
+    };
+    let path = if path.starts_with('?') {
+        format!("/{path}")
+    } else {
+        path.to_string()
+    };
+    let (host, port) = split_host_port(authority, 80)?;
+    Some(ProxyTarget {
+        host,
+        port,
+        url: target.to_string(),
+        path: Some(path),
+    })
+}
+
+/// Host and port of an authority, with `[...]` IPv6 literals unwrapped
+fn split_host_port(authority: &str, default_port: u16) -> Option<(String, u16)> {
+    let authority = authority.rsplit('@').next()?;
+    let (host, port) = if let Some(rest) = authority.strip_prefix('[') {
+        let (host, rest) = rest.split_once(']')?;
+        match rest.strip_prefix(':') {
+            Some(port) => (host, port.parse().ok()?),
+            None if rest.is_empty() => (host, default_port),
+            None => return None,
+        }
+    } else {
+        match authority.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse().ok()?),
+            None => (authority, default_port),
+        }
+    };
+    (!host.is_empty()).then(|| (host.to_ascii_lowercase(), port))
+}
+
+/// Read up to the end of the request head, returning it with any bytes
+/// read past it
+fn read_head(client: &mut TcpStream) -> io::Result<(Vec<u8>, Vec<u8>)> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 4096];
+    loop {
+        if let Some(end) = buffer.windows(4).position(|window| window == b"\r\n\r\n") {
+            let rest = buffer.split_off(end + 4);
+            return Ok((buffer, rest));
+        }
+        if buffer.len() > MAX_HEAD_BYTES {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Request head too large",
+            ));
+        }
+        let read = client.read(&mut chunk)?;
+        if read == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+    }
+}
+
+fn respond(client: &mut TcpStream, status: &str) -> io::Result<()> {
+    write!(
+        client,
+        "HTTP/1.1 {status}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+    )?;
+    client.flush()
+}
+
+fn handle_client(mut client: TcpStream, checker: &dyn RequestCheck) -> io::Result<()> {
+    client.set_read_timeout(Some(HEAD_TIMEOUT))?;
+    let (head, rest) = read_head(&mut client)?;
+    let head = String::from_utf8_lossy(&head);
+    let mut lines = head.split("\r\n");
+    let request_line = lines.next().unwrap_or("");
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target), Some(version)) = (parts.next(), parts.next(), parts.next())
+    else {
+        return respond(&mut client, "400 Bad Request");
+    };
+    let Some(target) = parse_request_target(method, target) else {
+        return respond(&mut client, "400 Bad Request");
+    };
+
+    if checker.check(&target.url).should_block {
+        return respond(&mut client, "403 Forbidden");
+    }
+    let mut upstream = match TcpStream::connect((target.host.as_str(), target.port)) {
+        Ok(upstream) => upstream,
+        Err(_) => return respond(&mut client, "502 Bad Gateway"),
+    };
+    client.set_read_timeout(None)?;
+
+    match &target.path {
+        None => {
+            client.write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n")?;
+        }
+        Some(path) => {
+            // One request per connection, so every request gets checked
+            let mut forwarded = format!("{method} {path} {version}\r\n");
+            for line in lines.filter(|line| !line.is_empty()) {
+                let name = line.split(':').next().unwrap_or("").trim();
+                if !["connection", "proxy-connection", "keep-alive"]
+                    .iter()
+                    .any(|hop| name.eq_ignore_ascii_case(hop))
+                {
+                    forwarded.push_str(line);
+                    forwarded.push_str("\r\n");
+                }
+            }
+            forwarded.push_str("Connection: close\r\n\r\n");
+            upstream.write_all(forwarded.as_bytes())?;
+        }
+    }
+    upstream.write_all(&rest)?;
+    tunnel(client, upstream)
+}
+
+/// Copy bytes both ways until either side closes
+fn tunnel(client: TcpStream, upstream: TcpStream) -> io::Result<()> {
+    let (mut client_read, mut upstream_write) = (client.try_clone()?, upstream.try_clone()?);
+    let uploader = std::thread::spawn(move || {
+        let _ = io::copy(&mut client_read, &mut upstream_write);
+        let _ = upstream_write.shutdown(Shutdown::Write);
+    });
+    let (mut upstream_read, mut client_write) = (upstream, client);
+    let _ = io::copy(&mut upstream_read, &mut client_write);
+    let _ = client_write.shutdown(Shutdown::Both);
+    let _ = uploader.join();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_request_target() {
+        let connect = parse_request_target("CONNECT", "Ads.Example:443").unwrap();
+        assert_eq!(connect.host, "ads.example");
+        assert_eq!(connect.port, 443);
+        assert_eq!(connect.url, "https://ads.example/");
+        assert_eq!(connect.path, None);
+
+        let ipv6 = parse_request_target("CONNECT", "[::1]:8443").unwrap();
+        assert_eq!((ipv6.host.as_str(), ipv6.port), ("::1", 8443));
+
+        let get = parse_request_target("GET", "http://example.com:8080?q=1").unwrap();
+        assert_eq!((get.host.as_str(), get.port), ("example.com", 8080));
+        assert_eq!(get.path.as_deref(), Some("/?q=1"));
+
+        assert_eq!(parse_request_target("GET", "/index.html"), None);
+        assert_eq!(parse_request_target("CONNECT", "example.com:https"), None);
+    }
+}
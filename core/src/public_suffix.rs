@@ -0,0 +1,197 @@
+//! Public suffix list
+//!
+//! Finds the registrable domain (eTLD+1) of a host, so `ads.example.co.uk`
+//! and `www.example.co.uk` are recognized as the same site. A subset of the
+//! Mozilla list is embedded; hosts can [`install`] a full, newer copy at
+//! runtime.
+
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+/// List bundled with the crate
+const EMBEDDED_LIST: &str = include_str!("../data/public_suffix_list.dat");
+
+static CURRENT: Lazy<RwLock<Arc<PublicSuffixList>>> =
+    Lazy::new(|| RwLock::new(Arc::new(PublicSuffixList::embedded())));
+
+/// Parsed public suffix rules
+#[derive(Debug, Clone, Default)]
+pub struct PublicSuffixList {
+    /// Plain rules such as `co.uk`
+    rules: HashSet<String>,
+    /// Wildcard rules without their `*.` prefix (`*.ck` is stored as `ck`)
+    wildcards: HashSet<String>,
+    /// Exception rules without their `!` prefix
+    exceptions: HashSet<String>,
+}
+
+impl PublicSuffixList {
+    /// Parse a list in the publicsuffix.org format
+    ///
+    /// Only the first whitespace-separated word of a line counts; comments
+    /// and blank lines are skipped.
+    pub fn parse(content: &str) -> Self {
+        let mut list = PublicSuffixList::default();
+        for line in content.lines() {
+            let Some(rule) = line.split_whitespace().next() else {
+                continue;
+            };
+            if rule.starts_with("//") {
+                continue;
+            }
+            let rule = rule.trim_end_matches('.').to_ascii_lowercase();
+            if let Some(exception) = rule.strip_prefix('!') {
+                list.exceptions.insert(exception.to_string());
+            } else if let Some(parent) = rule.strip_prefix("*.") {
+                list.wildcards.insert(parent.to_string());
+            } else {
+                list.rules.insert(rule);
+            }
+        }
+        list
+    }
+
+    /// The list bundled with the crate
+    pub fn embedded() -> Self {
+        Self::parse(EMBEDDED_LIST)
+    }
+
+    /// Number of rules
+    pub fn len(&self) -> usize {
+        self.rules.len() + self.wildcards.len() + self.exceptions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Public suffix of a lowercase host
+    ///
+    /// Hosts under an unlisted top-level domain fall back to the implicit
+    /// `*` rule, so their last label is the suffix.
+    pub fn public_suffix<'a>(&self, host: &'a str) -> &'a str {
+        let host = host.trim_end_matches('.');
+        let mut suffix = host.rsplit('.').next().unwrap_or(host);
+
+        // Walk from the longest candidate; the first listed one wins
+        let mut start = 0;
+        while start < host.len() {
+            let candidate = &host[start..];
+            if self.exceptions.contains(candidate) {
+                // The exception's own leftmost label is registrable
+                return candidate
+                    .split_once('.')
+                    .map_or(candidate, |(_, rest)| rest);
+            }
+            if self.rules.contains(candidate) {
+                suffix = candidate;
+                break;
+            }
+            if let Some((_, parent)) = candidate.split_once('.') {
+                if self.wildcards.contains(parent) {
+                    suffix = candidate;
+                    break;
+                }
+            }
+            match candidate.find('.') {
+                Some(dot) => start += dot + 1,
+                None => break,
+            }
+        }
+        suffix
+    }
+
+    /// Registrable domain of a host: its public suffix plus one label
+    ///
+    /// Returns `None` for public suffixes themselves and empty hosts. IP
+    /// addresses are returned unchanged.
+    pub fn registrable_domain(&self, host: &str) -> Option<String> {
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        if host.is_empty() {
+            return None;
+        }
+        if is_ip_address(&host) {
+            return Some(host);
+        }
+        let suffix = self.public_suffix(&host);
+        if suffix.len() >= host.len() {
+            return None;
+        }
+        let rest = &host[..host.len() - suffix.len() - 1];
+        let label = rest.rsplit('.').next().unwrap_or(rest);
+        if label.is_empty() {
+            return None;
+        }
+        Some(format!("{label}.{suffix}"))
+    }
+}
+
+/// Whether a host (without port) is an IPv4 or bracketed/bare IPv6 address
+fn is_ip_address(host: &str) -> bool {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    host.parse::<std::net::IpAddr>().is_ok()
+}
+
+/// The list currently used by `utils::registrable_domain`
+pub fn current() -> Arc<PublicSuffixList> {
+    Arc::clone(&CURRENT.read())
+}
+
+/// Replace the list used by `utils::registrable_domain`, e.g. with a fresh
+/// download of <https://publicsuffix.org/list/public_suffix_list.dat>
+///
+/// An empty list is rejected so a failed download can't disable
+/// third-party detection.
+pub fn install(content: &str) -> Result<usize, Box<dyn std::error::Error>> {
+    let list = PublicSuffixList::parse(content);
+    if list.is_empty() {
+        return Err("Public suffix list has no rules".into());
+    }
+    let count = list.len();
+    *CURRENT.write() = Arc::new(list);
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registrable_domain() {
+        let list = PublicSuffixList::embedded();
+        let registrable = |host| list.registrable_domain(host);
+
+        assert_eq!(
+            registrable("ads.example.co.uk").as_deref(),
+            Some("example.co.uk")
+        );
+        assert_eq!(
+            registrable("WWW.Example.COM.").as_deref(),
+            Some("example.com")
+        );
+        assert_eq!(
+            registrable("cdn.example.unlisted").as_deref(),
+            Some("example.unlisted")
+        );
+        assert_eq!(
+            registrable("user.github.io").as_deref(),
+            Some("user.github.io")
+        );
+        assert_eq!(registrable("co.uk"), None);
+        assert_eq!(registrable("com"), None);
+
+        // Wildcard and exception rules
+        assert_eq!(registrable("a.b.ck").as_deref(), Some("a.b.ck"));
+        assert_eq!(registrable("b.ck"), None);
+        assert_eq!(registrable("www.ck").as_deref(), Some("www.ck"));
+        assert_eq!(
+            registrable("x.city.kawasaki.jp").as_deref(),
+            Some("city.kawasaki.jp")
+        );
+
+        assert_eq!(registrable("192.168.0.1").as_deref(), Some("192.168.0.1"));
+        assert_eq!(registrable("[::1]").as_deref(), Some("[::1]"));
+    }
+}
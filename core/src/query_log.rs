@@ -0,0 +1,426 @@
+//! Query log for the activity screen
+//!
+//! Records each checked request (time, domain, verdict, reason, app) in a
+//! size-capped binary file. The file is append-only; once it outgrows its
+//! cap the oldest quarter of the entries is dropped and the file rewritten.
+//! Entries are kept in memory as well, so searches don't touch the disk.
+//! Long histories can instead be kept in a [`Storage`], which is searched
+//! in place and capped by entry count.
+
+use crate::storage::Storage;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Default size cap of the log file in bytes
+pub const DEFAULT_QUERY_LOG_BYTES: u64 = 2 * 1024 * 1024;
+
+/// Magic bytes starting a query log file
+const MAGIC: &[u8; 4] = b"AQL1";
+
+/// Entries recorded in a storage-backed log between two prunes
+const PRUNE_INTERVAL: u64 = 256;
+
+/// Outcome of a logged request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryVerdict {
+    Allowed,
+    /// Blocked as an ad or tracker
+    Blocked,
+    /// Blocked by a malware/phishing protection list
+    Malware,
+    /// Blocked by content category blocking
+    Category,
+    /// Allowed because blocking was paused
+    Paused,
+}
+
+impl QueryVerdict {
+    pub(crate) fn to_byte(self) -> u8 {
+        match self {
+            QueryVerdict::Allowed => 0,
+            QueryVerdict::Blocked => 1,
+            QueryVerdict::Malware => 2,
+            QueryVerdict::Category => 3,
+            QueryVerdict::Paused => 4,
+        }
+    }
+
+    pub(crate) fn from_byte(byte: u8) -> Option<Self> {
+        Some(match byte {
+            0 => QueryVerdict::Allowed,
+            1 => QueryVerdict::Blocked,
+            2 => QueryVerdict::Malware,
+            3 => QueryVerdict::Category,
+            4 => QueryVerdict::Paused,
+            _ => return None,
+        })
+    }
+}
+
+/// One logged request
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueryLogEntry {
+    /// Milliseconds since the Unix epoch
+    pub timestamp_ms: u64,
+    /// Domain as stored under the statistics privacy level
+    pub domain: String,
+    pub verdict: QueryVerdict,
+    /// Reason of the decision, naming the matched rule
+    pub reason: Option<String>,
+    /// App or package that made the request, if known
+    pub app: Option<String>,
+}
+
+impl QueryLogEntry {
+    /// Entry stamped with the current time
+    pub fn now(domain: &str, verdict: QueryVerdict) -> Self {
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_millis() as u64);
+        QueryLogEntry {
+            timestamp_ms,
+            domain: domain.to_string(),
+            verdict,
+            reason: None,
+            app: None,
+        }
+    }
+
+    /// Encode as a length-prefixed record
+    fn encode(&self) -> Vec<u8> {
+        let mut body = Vec::with_capacity(32 + self.domain.len());
+        body.extend_from_slice(&self.timestamp_ms.to_le_bytes());
+        body.push(self.verdict.to_byte());
+        for text in [
+            Some(self.domain.as_str()),
+            self.reason.as_deref(),
+            self.app.as_deref(),
+        ] {
+            // Longer text is cut at a character boundary
+            let text = text.unwrap_or("");
+            let mut end = text.len().min(u16::MAX as usize);
+            while !text.is_char_boundary(end) {
+                end -= 1;
+            }
+            let text = &text[..end];
+            body.extend_from_slice(&(text.len() as u16).to_le_bytes());
+            body.extend_from_slice(text.as_bytes());
+        }
+        let mut record = (body.len() as u32).to_le_bytes().to_vec();
+        record.extend_from_slice(&body);
+        record
+    }
+
+    /// Decode a record body
+    fn decode(body: &[u8]) -> Option<Self> {
+        let timestamp_ms = u64::from_le_bytes(body.get(..8)?.try_into().ok()?);
+        let verdict = QueryVerdict::from_byte(*body.get(8)?)?;
+        let mut rest = &body[9..];
+        let mut texts = Vec::with_capacity(3);
+        for _ in 0..3 {
+            let len = u16::from_le_bytes(rest.get(..2)?.try_into().ok()?) as usize;
+            let text = std::str::from_utf8(rest.get(2..2 + len)?).ok()?;
+            texts.push(text.to_string());
+            rest = &rest[2 + len..];
+        }
+        let mut texts = texts.into_iter();
+        let domain = texts.next()?;
+        let mut optional = texts.map(|text| Some(text).filter(|text| !text.is_empty()));
+        Some(QueryLogEntry {
+            timestamp_ms,
+            domain,
+            verdict,
+            reason: optional.next()?,
+            app: optional.next()?,
+        })
+    }
+}
+
+/// Search criteria; unset fields match every entry
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QueryFilter {
+    /// Substring of the domain, case-insensitive
+    pub domain: Option<String>,
+    pub verdict: Option<QueryVerdict>,
+    /// Earliest timestamp in milliseconds, inclusive
+    pub since_ms: Option<u64>,
+    /// Latest timestamp in milliseconds, exclusive
+    pub until_ms: Option<u64>,
+    /// Only requests made by this app
+    pub app: Option<String>,
+}
+
+impl QueryFilter {
+    fn matches(&self, entry: &QueryLogEntry) -> bool {
+        self.domain.as_ref().is_none_or(|domain| {
+            entry
+                .domain
+                .to_ascii_lowercase()
+                .contains(&domain.to_ascii_lowercase())
+        }) && self.verdict.is_none_or(|verdict| entry.verdict == verdict)
+            && self
+                .since_ms
+                .is_none_or(|since| entry.timestamp_ms >= since)
+            && self.until_ms.is_none_or(|until| entry.timestamp_ms < until)
+            && self
+                .app
+                .as_deref()
+                .is_none_or(|app| entry.app.as_deref() == Some(app))
+    }
+}
+
+/// Size-capped log of checked requests
+#[derive(Debug)]
+pub struct QueryLog {
+    /// Oldest first, with the encoded size of each entry
+    entries: VecDeque<(QueryLogEntry, u64)>,
+    /// Encoded size of `entries`, without the magic bytes
+    bytes: u64,
+    max_bytes: u64,
+    path: Option<PathBuf>,
+    file: Option<File>,
+    /// Backend holding the entries instead of `entries`, with its entry cap;
+    /// locked since storage connections need not be shared across threads
+    storage: Option<(parking_lot::Mutex<Box<dyn Storage>>, usize)>,
+    /// Entries recorded since the storage was last pruned
+    unpruned: u64,
+}
+
+impl QueryLog {
+    /// Log kept in memory only
+    pub fn in_memory(max_bytes: u64) -> Self {
+        QueryLog {
+            entries: VecDeque::new(),
+            bytes: 0,
+            max_bytes,
+            path: None,
+            file: None,
+            storage: None,
+            unpruned: 0,
+        }
+    }
+
+    /// Log kept in a storage backend, holding at most `max_entries` entries
+    ///
+    /// Entries are not loaded into memory; searches run against the storage.
+    pub fn with_storage(
+        mut storage: Box<dyn Storage>,
+        max_entries: usize,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        storage.prune_queries(max_entries)?;
+        let mut log = Self::in_memory(0);
+        log.storage = Some((parking_lot::Mutex::new(storage), max_entries));
+        Ok(log)
+    }
+
+    /// Open or create a log file, loading its entries
+    ///
+    /// A truncated last record, e.g. after a crash, is dropped.
+    pub fn open(path: &Path, max_bytes: u64) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut log = Self::in_memory(max_bytes);
+        log.path = Some(path.to_path_buf());
+        let mut content = Vec::new();
+        if let Ok(mut file) = File::open(path) {
+            file.read_to_end(&mut content)?;
+        }
+        if content.is_empty() {
+            log.rewrite()?;
+            return Ok(log);
+        }
+        let mut rest = content.strip_prefix(MAGIC).ok_or("Not a query log file")?;
+        while rest.len() >= 4 {
+            let len = u32::from_le_bytes(rest[..4].try_into()?) as usize;
+            let Some(body) = rest.get(4..4 + len) else {
+                break;
+            };
+            if let Some(entry) = QueryLogEntry::decode(body) {
+                log.push(entry, 4 + len as u64);
+            }
+            rest = &rest[4 + len..];
+        }
+        // Rewrite to drop a truncated tail and apply a smaller cap
+        if log.bytes > log.max_bytes {
+            log.trim();
+        }
+        log.rewrite()?;
+        Ok(log)
+    }
+
+    /// Record a request, dropping the oldest entries past the size cap
+    pub fn record(&mut self, entry: QueryLogEntry) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some((storage, max_entries)) = &mut self.storage {
+            let storage = storage.get_mut();
+            storage.append_query(&entry)?;
+            self.unpruned += 1;
+            if self.unpruned >= PRUNE_INTERVAL {
+                self.unpruned = 0;
+                storage.prune_queries(*max_entries)?;
+            }
+            return Ok(());
+        }
+        let record = entry.encode();
+        self.push(entry, record.len() as u64);
+        if self.bytes > self.max_bytes {
+            self.trim();
+            return self.rewrite();
+        }
+        if let Some(file) = &mut self.file {
+            file.write_all(&record)?;
+        }
+        Ok(())
+    }
+
+    fn push(&mut self, entry: QueryLogEntry, size: u64) {
+        self.bytes += size;
+        self.entries.push_back((entry, size));
+    }
+
+    /// Drop the oldest entries until a quarter of the cap is free
+    fn trim(&mut self) {
+        let target = self.max_bytes / 4 * 3;
+        while self.bytes > target {
+            let Some((_, size)) = self.entries.pop_front() else {
+                break;
+            };
+            self.bytes -= size;
+        }
+    }
+
+    /// Write all entries to a new file and swap it in
+    fn rewrite(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let temp = path.with_extension("tmp");
+        let mut content = MAGIC.to_vec();
+        for (entry, _) in &self.entries {
+            content.extend_from_slice(&entry.encode());
+        }
+        std::fs::write(&temp, &content)?;
+        std::fs::rename(&temp, path)?;
+        self.file = Some(OpenOptions::new().append(true).open(path)?);
+        Ok(())
+    }
+
+    /// Entries matching `filter`, newest first, skipping `offset` of them
+    ///
+    /// A storage that fails to answer yields no entries.
+    pub fn search(&self, filter: &QueryFilter, offset: usize, limit: usize) -> Vec<QueryLogEntry> {
+        if let Some((storage, _)) = &self.storage {
+            return storage
+                .lock()
+                .search_queries(filter, offset, limit)
+                .unwrap_or_default();
+        }
+        self.entries
+            .iter()
+            .rev()
+            .map(|(entry, _)| entry)
+            .filter(|entry| filter.matches(entry))
+            .skip(offset)
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    /// Number of entries matching `filter`, for pagination
+    pub fn count(&self, filter: &QueryFilter) -> usize {
+        if let Some((storage, _)) = &self.storage {
+            return storage.lock().count_queries(filter).unwrap_or(0);
+        }
+        self.entries
+            .iter()
+            .filter(|(entry, _)| filter.matches(entry))
+            .count()
+    }
+
+    /// Remove every entry
+    pub fn clear(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some((storage, _)) = &mut self.storage {
+            return storage.get_mut().clear_queries();
+        }
+        self.entries.clear();
+        self.bytes = 0;
+        self.rewrite()
+    }
+
+    pub fn len(&self) -> usize {
+        if self.storage.is_some() {
+            return self.count(&QueryFilter::default());
+        }
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether entries are kept in a storage backend
+    pub fn is_storage_backed(&self) -> bool {
+        self.storage.is_some()
+    }
+
+    /// Size cap in bytes of a file or in-memory log
+    pub fn max_bytes(&self) -> u64 {
+        self.max_bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_round_trip() {
+        let mut entry = QueryLogEntry::now("ads.example", QueryVerdict::Blocked);
+        entry.reason = Some("Matched subdomain: ads.example".to_string());
+        let record = entry.encode();
+        assert_eq!(QueryLogEntry::decode(&record[4..]), Some(entry.clone()));
+
+        entry.reason = None;
+        entry.app = Some("com.example.app".to_string());
+        let record = entry.encode();
+        assert_eq!(QueryLogEntry::decode(&record[4..]), Some(entry));
+    }
+
+    #[test]
+    fn test_size_cap_drops_oldest() {
+        let mut log = QueryLog::in_memory(1000);
+        for i in 0..100 {
+            log.record(QueryLogEntry::now(
+                &format!("d{i}.example"),
+                QueryVerdict::Allowed,
+            ))
+            .unwrap();
+        }
+        assert!(log.bytes <= 1000);
+        let newest = log.search(&QueryFilter::default(), 0, 1);
+        assert_eq!(newest[0].domain, "d99.example");
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_storage_backed_log() {
+        let storage = crate::sqlite_storage::SqliteStorage::open_in_memory().unwrap();
+        let mut log = QueryLog::with_storage(Box::new(storage), 100).unwrap();
+        for i in 0..300 {
+            log.record(QueryLogEntry::now(
+                &format!("d{i}.example"),
+                QueryVerdict::Blocked,
+            ))
+            .unwrap();
+        }
+        // Pruned once after PRUNE_INTERVAL entries
+        assert_eq!(log.len(), 144);
+        let newest = log.search(&QueryFilter::default(), 0, 1);
+        assert_eq!(newest[0].domain, "d299.example");
+        log.clear().unwrap();
+        assert!(log.is_empty());
+    }
+}
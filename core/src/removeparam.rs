@@ -0,0 +1,228 @@
+//! Query parameter stripping (`$removeparam`)
+//!
+//! Parses `$removeparam` rules and removes matching query parameters from
+//! URLs so a cleaned request can be forwarded instead of blocked.
+
+use regex::Regex;
+
+/// Tracking parameters removed from every URL
+pub const DEFAULT_TRACKING_PARAMS: &[&str] = &["fbclid", "gclid", "dclid", "msclkid", "yclid"];
+
+/// Prefix of tracking parameters removed from every URL
+const TRACKING_PARAM_PREFIX: &str = "utm_";
+
+/// Which parameters a rule removes
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(into = "StoredParamMatcher", try_from = "StoredParamMatcher")]
+pub enum ParamMatcher {
+    /// Every parameter (`$removeparam`)
+    All,
+    /// A parameter by exact name (`$removeparam=ref`)
+    Name(String),
+    /// Parameters whose name matches a regex (`$removeparam=/^utm_/`)
+    Regex(Regex),
+}
+
+/// [`ParamMatcher`] as stored in compiled engines, with the regex as its
+/// source
+#[derive(serde::Serialize, serde::Deserialize)]
+enum StoredParamMatcher {
+    All,
+    Name(String),
+    Regex(String),
+}
+
+impl From<ParamMatcher> for StoredParamMatcher {
+    fn from(matcher: ParamMatcher) -> Self {
+        match matcher {
+            ParamMatcher::All => StoredParamMatcher::All,
+            ParamMatcher::Name(name) => StoredParamMatcher::Name(name),
+            ParamMatcher::Regex(regex) => StoredParamMatcher::Regex(regex.as_str().to_string()),
+        }
+    }
+}
+
+impl TryFrom<StoredParamMatcher> for ParamMatcher {
+    type Error = regex::Error;
+
+    fn try_from(matcher: StoredParamMatcher) -> Result<Self, Self::Error> {
+        Ok(match matcher {
+            StoredParamMatcher::All => ParamMatcher::All,
+            StoredParamMatcher::Name(name) => ParamMatcher::Name(name),
+            StoredParamMatcher::Regex(pattern) => ParamMatcher::Regex(Regex::new(&pattern)?),
+        })
+    }
+}
+
+impl PartialEq for ParamMatcher {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (ParamMatcher::All, ParamMatcher::All) => true,
+            (ParamMatcher::Name(a), ParamMatcher::Name(b)) => a == b,
+            (ParamMatcher::Regex(a), ParamMatcher::Regex(b)) => a.as_str() == b.as_str(),
+            _ => false,
+        }
+    }
+}
+
+impl ParamMatcher {
+    fn matches(&self, name: &str) -> bool {
+        match self {
+            ParamMatcher::All => true,
+            ParamMatcher::Name(expected) => name == expected,
+            ParamMatcher::Regex(regex) => regex.is_match(name),
+        }
+    }
+}
+
+/// A parsed `$removeparam` rule
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct RemoveParamRule {
+    /// URL pattern the rule is limited to (`None` = all URLs)
+    pub scope: Option<String>,
+    /// Parameters to remove
+    pub matcher: ParamMatcher,
+    /// Whether this is an `@@` rule that keeps the parameters
+    pub exception: bool,
+}
+
+impl RemoveParamRule {
+    /// Parse a rule like `||example.com^$removeparam=ref`
+    ///
+    /// Returns `None` if the line has no `removeparam` option.
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = line.trim();
+        let (exception, line) = match line.strip_prefix("@@") {
+            Some(stripped) => (true, stripped),
+            None => (false, line),
+        };
+
+        let options_pos = line.rfind('$')?;
+        let scope = &line[..options_pos];
+        let value = line[options_pos + 1..].split(',').find_map(|option| {
+            let option = option.trim();
+            if option == "removeparam" {
+                Some("")
+            } else {
+                option.strip_prefix("removeparam=")
+            }
+        })?;
+
+        let matcher = if value.is_empty() {
+            ParamMatcher::All
+        } else if value.len() > 2 && value.starts_with('/') {
+            let end = value.rfind('/').filter(|end| *end > 0)?;
+            let flags = &value[end + 1..];
+            let pattern = &value[1..end];
+            let pattern = if flags.contains('i') {
+                format!("(?i){pattern}")
+            } else {
+                pattern.to_string()
+            };
+            ParamMatcher::Regex(Regex::new(&pattern).ok()?)
+        } else {
+            ParamMatcher::Name(value.to_string())
+        };
+
+        Some(RemoveParamRule {
+            scope: (!scope.is_empty()).then(|| scope.to_string()),
+            matcher,
+            exception,
+        })
+    }
+
+    /// Whether the rule removes a parameter name
+    pub fn matches_param(&self, name: &str) -> bool {
+        self.matcher.matches(name)
+    }
+}
+
+/// Whether a parameter is a well-known tracking parameter
+pub fn is_tracking_param(name: &str) -> bool {
+    name.starts_with(TRACKING_PARAM_PREFIX) || DEFAULT_TRACKING_PARAMS.contains(&name)
+}
+
+/// Remove query parameters for which `should_remove` returns true
+///
+/// Returns `None` if no parameter was removed.
+pub fn strip_query_params<F>(url: &str, mut should_remove: F) -> Option<String>
+where
+    F: FnMut(&str) -> bool,
+{
+    let query_start = url.find('?')?;
+    let fragment_start = url[query_start..]
+        .find('#')
+        .map(|pos| query_start + pos)
+        .unwrap_or(url.len());
+
+    let query = &url[query_start + 1..fragment_start];
+    let mut removed = false;
+    let kept: Vec<&str> = query
+        .split('&')
+        .filter(|pair| {
+            if pair.is_empty() {
+                return false;
+            }
+            let name = pair.split('=').next().unwrap_or(pair);
+            let remove = should_remove(name);
+            removed |= remove;
+            !remove
+        })
+        .collect();
+
+    if !removed {
+        return None;
+    }
+
+    let mut cleaned = url[..query_start].to_string();
+    if !kept.is_empty() {
+        cleaned.push('?');
+        cleaned.push_str(&kept.join("&"));
+    }
+    cleaned.push_str(&url[fragment_start..]);
+    Some(cleaned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_removeparam_rules() {
+        let rule = RemoveParamRule::parse("||example.com^$removeparam=ref").unwrap();
+        assert_eq!(rule.scope.as_deref(), Some("||example.com^"));
+        assert!(rule.matches_param("ref"));
+        assert!(!rule.matches_param("page"));
+
+        let rule = RemoveParamRule::parse("$removeparam=/^ST_/i").unwrap();
+        assert!(rule.scope.is_none());
+        assert!(rule.matches_param("st_source"));
+
+        let rule = RemoveParamRule::parse("@@||shop.com^$removeparam").unwrap();
+        assert!(rule.exception);
+        assert!(rule.matches_param("anything"));
+
+        assert!(RemoveParamRule::parse("||ads.com^$third-party").is_none());
+        // Found by the parse_rule fuzz target
+        assert!(RemoveParamRule::parse("||tar$removeparam=/^ST").is_none());
+    }
+
+    #[test]
+    fn test_strip_query_params() {
+        assert_eq!(
+            strip_query_params(
+                "https://a.com/p?utm_source=x&id=1&fbclid=y#top",
+                is_tracking_param
+            ),
+            Some("https://a.com/p?id=1#top".to_string())
+        );
+        assert_eq!(
+            strip_query_params("https://a.com/p?gclid=1", is_tracking_param),
+            Some("https://a.com/p".to_string())
+        );
+        assert_eq!(
+            strip_query_params("https://a.com/p?id=1", is_tracking_param),
+            None
+        );
+    }
+}
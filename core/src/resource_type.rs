@@ -0,0 +1,366 @@
+//! Request resource types
+//!
+//! Rules can be limited to resource types with options such as `$script`
+//! or `$~websocket`. Hosts rarely know the type of an intercepted request,
+//! so [`ResourceType::infer`] guesses it from the URL scheme, request
+//! headers, well-known ports and the file extension.
+//!
+//! Pages opened with `window.open` are checked as [`ResourceType::Popup`],
+//! which only `$popup` rules apply to. `$third-party` and `$first-party`
+//! options are checked against the page the request is made from.
+
+/// Type of resource a request loads
+///
+/// Serialized as its option name, e.g. `xmlhttprequest`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResourceType {
+    Document,
+    Subdocument,
+    Script,
+    Image,
+    Stylesheet,
+    Object,
+    XmlHttpRequest,
+    Websocket,
+    Webrtc,
+    Ping,
+    Media,
+    Font,
+    /// Page opened in a new window, e.g. by `window.open`
+    Popup,
+    #[default]
+    Other,
+}
+
+/// STUN and TURN ports used by WebRTC peer connections
+const WEBRTC_PORTS: &[u16] = &[3478, 5349];
+
+/// Request headers that hint at the resource type
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RequestHints<'a> {
+    /// `Accept` header
+    pub accept: Option<&'a str>,
+    /// `Upgrade` header (`websocket` for WebSocket handshakes)
+    pub upgrade: Option<&'a str>,
+    /// `Sec-Fetch-Dest` header
+    pub sec_fetch_dest: Option<&'a str>,
+}
+
+/// Context of a request checked with `FilterEngine::should_block_with_context`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RequestContext {
+    pub resource_type: ResourceType,
+    /// URL of the page that made the request, if known
+    pub document_url: Option<String>,
+}
+
+/// What the options of a rule are checked against
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct RequestKind {
+    pub resource_type: ResourceType,
+    /// Whether the request goes to another party than its page; `None`
+    /// if the page is unknown
+    pub third_party: Option<bool>,
+}
+
+impl RequestKind {
+    /// Whether a rule with these options, if any, applies
+    ///
+    /// Party options are ignored when the page is unknown, e.g. for
+    /// requests seen at the DNS or VPN level.
+    pub fn matches_rule_options(self, options: Option<&str>) -> bool {
+        if !self.resource_type.matches_rule_options(options) {
+            return false;
+        }
+        let Some(third_party) = self.third_party else {
+            return true;
+        };
+        options
+            .into_iter()
+            .flat_map(|o| o.split(','))
+            .all(|option| match option {
+                "third-party" | "3p" | "~first-party" | "~1p" => third_party,
+                "first-party" | "1p" | "~third-party" | "~3p" => !third_party,
+                _ => true,
+            })
+    }
+}
+
+/// Whether a rule option restricts the party of the request
+pub fn is_party_option(option: &str) -> bool {
+    let name = option.strip_prefix('~').unwrap_or(option);
+    matches!(name, "third-party" | "3p" | "first-party" | "1p")
+}
+
+impl RequestContext {
+    /// What a request to `url` in this context is checked as
+    ///
+    /// Parties are compared with [`crate::utils::is_third_party`].
+    pub fn kind(&self, url: &str) -> RequestKind {
+        RequestKind {
+            resource_type: self.resource_type,
+            third_party: self
+                .document_url
+                .as_deref()
+                .map(|document_url| crate::utils::is_third_party(url, document_url)),
+        }
+    }
+
+    /// Context with the resource type inferred from the URL alone
+    pub fn from_url(url: &str) -> Self {
+        RequestContext {
+            resource_type: ResourceType::infer(url, &RequestHints::default()),
+            document_url: None,
+        }
+    }
+
+    /// Set the URL of the page that made the request
+    pub fn with_document_url(mut self, url: &str) -> Self {
+        self.document_url = Some(url.to_string());
+        self
+    }
+}
+
+impl ResourceType {
+    /// Guess the resource type of a request
+    ///
+    /// WebSocket and WebRTC are recognized from the scheme, the `Upgrade`
+    /// header and the STUN/TURN ports; other types from `Sec-Fetch-Dest`,
+    /// `Accept` and finally the file extension.
+    pub fn infer(url: &str, hints: &RequestHints) -> Self {
+        // STUN and TURN URIs have no `//`, e.g. `turn:turn.example.com`
+        let scheme = url
+            .split_once(':')
+            .map_or("", |(scheme, _)| scheme)
+            .to_ascii_lowercase();
+        match scheme.as_str() {
+            "ws" | "wss" => return ResourceType::Websocket,
+            "stun" | "stuns" | "turn" | "turns" => return ResourceType::Webrtc,
+            _ => {}
+        }
+        if hints
+            .upgrade
+            .is_some_and(|upgrade| upgrade.eq_ignore_ascii_case("websocket"))
+        {
+            return ResourceType::Websocket;
+        }
+        if crate::utils::parse_url(url)
+            .and_then(|parsed| parsed.port)
+            .is_some_and(|port| WEBRTC_PORTS.contains(&port))
+        {
+            return ResourceType::Webrtc;
+        }
+
+        if let Some(resource_type) = hints.sec_fetch_dest.and_then(Self::from_fetch_dest) {
+            return resource_type;
+        }
+        if let Some(resource_type) = hints.accept.and_then(Self::from_accept) {
+            return resource_type;
+        }
+        Self::from_extension(url).unwrap_or(ResourceType::Other)
+    }
+
+    fn from_fetch_dest(dest: &str) -> Option<Self> {
+        Some(match dest.to_ascii_lowercase().as_str() {
+            "document" => ResourceType::Document,
+            "iframe" | "frame" => ResourceType::Subdocument,
+            "script" | "worker" | "sharedworker" | "serviceworker" => ResourceType::Script,
+            "image" => ResourceType::Image,
+            "style" => ResourceType::Stylesheet,
+            "object" | "embed" => ResourceType::Object,
+            "audio" | "video" | "track" => ResourceType::Media,
+            "font" => ResourceType::Font,
+            "empty" => ResourceType::XmlHttpRequest,
+            _ => return None,
+        })
+    }
+
+    fn from_accept(accept: &str) -> Option<Self> {
+        let accept = accept.to_ascii_lowercase();
+        if accept.starts_with("text/html") {
+            Some(ResourceType::Document)
+        } else if accept.starts_with("text/css") {
+            Some(ResourceType::Stylesheet)
+        } else if accept.starts_with("image/") {
+            Some(ResourceType::Image)
+        } else if accept.starts_with("font/") {
+            Some(ResourceType::Font)
+        } else {
+            None
+        }
+    }
+
+    fn from_extension(url: &str) -> Option<Self> {
+        let path = url.split(['?', '#']).next().unwrap_or(url);
+        let path = path.split_once("://").map_or(path, |(_, rest)| rest);
+        let file = path.split_once('/')?.1.rsplit('/').next()?;
+        let extension = file.rsplit_once('.')?.1.to_ascii_lowercase();
+        Some(match extension.as_str() {
+            "js" | "mjs" => ResourceType::Script,
+            "css" => ResourceType::Stylesheet,
+            "png" | "jpg" | "jpeg" | "gif" | "webp" | "svg" | "ico" | "avif" => ResourceType::Image,
+            "woff" | "woff2" | "ttf" | "otf" | "eot" => ResourceType::Font,
+            "mp4" | "webm" | "mp3" | "ogg" | "m3u8" | "m4s" | "ts" => ResourceType::Media,
+            "html" | "htm" => ResourceType::Document,
+            "swf" => ResourceType::Object,
+            _ => return None,
+        })
+    }
+
+    /// Rule option naming this type, e.g. `websocket`
+    pub fn option_name(self) -> &'static str {
+        match self {
+            ResourceType::Document => "document",
+            ResourceType::Subdocument => "subdocument",
+            ResourceType::Script => "script",
+            ResourceType::Image => "image",
+            ResourceType::Stylesheet => "stylesheet",
+            ResourceType::Object => "object",
+            ResourceType::XmlHttpRequest => "xmlhttprequest",
+            ResourceType::Websocket => "websocket",
+            ResourceType::Webrtc => "webrtc",
+            ResourceType::Ping => "ping",
+            ResourceType::Media => "media",
+            ResourceType::Font => "font",
+            ResourceType::Popup => "popup",
+            ResourceType::Other => "other",
+        }
+    }
+
+    /// Resource type of an option name, e.g. `xmlhttprequest`
+    pub fn from_option_name(name: &str) -> Option<Self> {
+        Some(match name.to_ascii_lowercase().as_str() {
+            "document" => ResourceType::Document,
+            "subdocument" => ResourceType::Subdocument,
+            "script" => ResourceType::Script,
+            "image" => ResourceType::Image,
+            "stylesheet" => ResourceType::Stylesheet,
+            "object" => ResourceType::Object,
+            "xmlhttprequest" => ResourceType::XmlHttpRequest,
+            "websocket" => ResourceType::Websocket,
+            "webrtc" => ResourceType::Webrtc,
+            "ping" => ResourceType::Ping,
+            "media" => ResourceType::Media,
+            "font" => ResourceType::Font,
+            "popup" => ResourceType::Popup,
+            "other" => ResourceType::Other,
+            _ => return None,
+        })
+    }
+
+    /// Whether a rule option names a resource type (optionally negated)
+    pub fn is_type_option(option: &str) -> bool {
+        let name = option.strip_prefix('~').unwrap_or(option);
+        matches!(
+            name,
+            "document"
+                | "subdocument"
+                | "script"
+                | "image"
+                | "stylesheet"
+                | "object"
+                | "xmlhttprequest"
+                | "websocket"
+                | "webrtc"
+                | "ping"
+                | "media"
+                | "font"
+                | "popup"
+                | "other"
+        )
+    }
+
+    /// Whether a rule with these type options, if any, applies
+    ///
+    /// Rules without type options apply to every type but popups.
+    pub fn matches_rule_options(self, options: Option<&str>) -> bool {
+        match options {
+            Some(options) => self.matches_options(options),
+            None => self != ResourceType::Popup,
+        }
+    }
+
+    /// Whether a rule with these comma-separated type options applies
+    ///
+    /// Rules listing types apply only to those; rules with only negated
+    /// types (`~script`) apply to everything else but popups, which need
+    /// an explicit `$popup`. Options other than types are ignored.
+    pub fn matches_options(self, options: &str) -> bool {
+        let name = self.option_name();
+        let mut included = None;
+        for option in options.split(',') {
+            if !Self::is_type_option(option) {
+                continue;
+            }
+            match option.strip_prefix('~') {
+                Some(excluded) if excluded == name => return false,
+                Some(_) => {}
+                None => {
+                    included = Some(included.unwrap_or(false) || option == name);
+                }
+            }
+        }
+        included.unwrap_or(self != ResourceType::Popup)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_infer_resource_type() {
+        let none = RequestHints::default();
+        assert_eq!(
+            ResourceType::infer("wss://chat.example.com/socket", &none),
+            ResourceType::Websocket
+        );
+        assert_eq!(
+            ResourceType::infer("turn:turn.example.com", &none),
+            ResourceType::Webrtc
+        );
+        assert_eq!(
+            ResourceType::infer("stun://stun.example.com:3478", &none),
+            ResourceType::Webrtc
+        );
+        assert_eq!(
+            ResourceType::infer("https://relay.example.com:5349/", &none),
+            ResourceType::Webrtc
+        );
+        let upgrade = RequestHints {
+            upgrade: Some("WebSocket"),
+            ..RequestHints::default()
+        };
+        assert_eq!(
+            ResourceType::infer("https://example.com/live", &upgrade),
+            ResourceType::Websocket
+        );
+        let iframe = RequestHints {
+            sec_fetch_dest: Some("iframe"),
+            ..RequestHints::default()
+        };
+        assert_eq!(
+            ResourceType::infer("https://ads.com/frame.js", &iframe),
+            ResourceType::Subdocument
+        );
+        assert_eq!(
+            ResourceType::infer("https://ads.com/ad.js?v=1", &none),
+            ResourceType::Script
+        );
+        assert_eq!(
+            ResourceType::infer("https://ads.com/", &none),
+            ResourceType::Other
+        );
+    }
+
+    #[test]
+    fn test_matches_type_options() {
+        assert!(ResourceType::Websocket.matches_options("websocket"));
+        assert!(!ResourceType::Script.matches_options("websocket,webrtc"));
+        assert!(ResourceType::Image.matches_options("~script"));
+        assert!(!ResourceType::Script.matches_options("~script"));
+        assert!(ResourceType::is_type_option("~webrtc"));
+        assert!(!ResourceType::is_type_option("third-party"));
+    }
+}
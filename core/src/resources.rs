@@ -0,0 +1,190 @@
+//! Redirect resources
+//!
+//! Neutered stub assets served in place of blocked requests by `$redirect`
+//! rules, so pages that expect a script or image keep working.
+
+/// An embedded stub asset
+#[derive(Debug)]
+pub struct Resource {
+    /// Canonical resource name
+    pub name: &'static str,
+    /// Alternative names used by filter lists
+    pub aliases: &'static [&'static str],
+    /// MIME type to serve the resource with
+    pub content_type: &'static str,
+    /// Resource body
+    pub content: &'static [u8],
+}
+
+/// 1x1 transparent GIF
+const GIF_1X1: &[u8] = &[
+    0x47, 0x49, 0x46, 0x38, 0x39, 0x61, 0x01, 0x00, 0x01, 0x00, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0xff, 0xff, 0xff, 0x21, 0xf9, 0x04, 0x01, 0x00, 0x00, 0x00, 0x00, 0x2c, 0x00, 0x00, 0x00, 0x00,
+    0x01, 0x00, 0x01, 0x00, 0x00, 0x02, 0x01, 0x44, 0x00, 0x3b,
+];
+
+/// 1x1 transparent PNG
+const PNG_1X1: &[u8] = &[
+    0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44, 0x52,
+    0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x06, 0x00, 0x00, 0x00, 0x1f, 0x15, 0xc4,
+    0x89, 0x00, 0x00, 0x00, 0x0b, 0x49, 0x44, 0x41, 0x54, 0x78, 0x9c, 0x63, 0x60, 0x00, 0x02, 0x00,
+    0x00, 0x05, 0x00, 0x01, 0x7a, 0x5e, 0xab, 0x3f, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4e, 0x44,
+    0xae, 0x42, 0x60, 0x82,
+];
+
+/// 2x2 transparent PNG
+const PNG_2X2: &[u8] = &[
+    0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44, 0x52,
+    0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x02, 0x08, 0x06, 0x00, 0x00, 0x00, 0x72, 0xb6, 0x0d,
+    0x24, 0x00, 0x00, 0x00, 0x0b, 0x49, 0x44, 0x41, 0x54, 0x78, 0xda, 0x63, 0x60, 0x40, 0x07, 0x00,
+    0x00, 0x12, 0x00, 0x01, 0xe4, 0x55, 0x8d, 0xe7, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4e, 0x44,
+    0xae, 0x42, 0x60, 0x82,
+];
+
+/// Bundled resources
+pub const RESOURCES: &[Resource] = &[
+    Resource {
+        name: "noop.js",
+        aliases: &["noopjs", "noop-js"],
+        content_type: "application/javascript",
+        content: b"(function() {})();",
+    },
+    Resource {
+        name: "noop.txt",
+        aliases: &["nooptext", "noop-text"],
+        content_type: "text/plain",
+        content: b"",
+    },
+    Resource {
+        name: "noop.html",
+        aliases: &["noopframe", "noop-html"],
+        content_type: "text/html",
+        content: b"<!DOCTYPE html>",
+    },
+    Resource {
+        name: "noop.css",
+        aliases: &["noopcss"],
+        content_type: "text/css",
+        content: b"",
+    },
+    Resource {
+        name: "noop.json",
+        aliases: &["noopjson"],
+        content_type: "application/json",
+        content: b"{}",
+    },
+    Resource {
+        name: "1x1.gif",
+        aliases: &["1x1-transparent.gif"],
+        content_type: "image/gif",
+        content: GIF_1X1,
+    },
+    Resource {
+        name: "1x1.png",
+        aliases: &["1x1-transparent.png"],
+        content_type: "image/png",
+        content: PNG_1X1,
+    },
+    Resource {
+        name: "2x2.png",
+        aliases: &["2x2-transparent.png"],
+        content_type: "image/png",
+        content: PNG_2X2,
+    },
+];
+
+/// Look a resource up by name or alias
+pub fn get_resource(name: &str) -> Option<&'static Resource> {
+    RESOURCES
+        .iter()
+        .find(|resource| resource.name == name || resource.aliases.contains(&name))
+}
+
+/// A parsed `$redirect` / `$redirect-rule` rule
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct RedirectRule {
+    /// Network pattern the redirect applies to
+    pub pattern: String,
+    /// Canonical name of the resource to serve
+    pub resource: String,
+    /// `$redirect` also blocks; `$redirect-rule` only applies when another
+    /// rule blocks the request
+    pub blocks: bool,
+}
+
+impl RedirectRule {
+    /// Parse a rule like `||ads.com/ad.js$script,redirect=noopjs`
+    ///
+    /// Returns `None` if the rule has no redirect option or names an unknown
+    /// resource.
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = line.trim();
+        if line.starts_with("@@") {
+            return None;
+        }
+
+        let options_pos = line.rfind('$')?;
+        let pattern = &line[..options_pos];
+        let (value, blocks) = line[options_pos + 1..].split(',').find_map(|option| {
+            let option = option.trim();
+            option
+                .strip_prefix("redirect=")
+                .map(|value| (value, true))
+                .or_else(|| {
+                    option
+                        .strip_prefix("redirect-rule=")
+                        .map(|value| (value, false))
+                })
+        })?;
+
+        // Drop the optional `:priority` suffix
+        let name = value.split(':').next().unwrap_or(value);
+        let Some(resource) = get_resource(name) else {
+            tracing::warn!("Unknown redirect resource: {}", name);
+            return None;
+        };
+
+        if pattern.is_empty() {
+            return None;
+        }
+
+        Some(RedirectRule {
+            pattern: pattern.to_string(),
+            resource: resource.name.to_string(),
+            blocks,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_redirect_rules() {
+        let rule = RedirectRule::parse("||ads.com/ad.js$script,redirect=noopjs:100").unwrap();
+        assert_eq!(rule.pattern, "||ads.com/ad.js");
+        assert_eq!(rule.resource, "noop.js");
+        assert!(rule.blocks);
+
+        let rule = RedirectRule::parse("*/pixel.gif$redirect-rule=1x1.gif").unwrap();
+        assert!(!rule.blocks);
+        assert_eq!(
+            get_resource(&rule.resource).unwrap().content_type,
+            "image/gif"
+        );
+
+        assert!(RedirectRule::parse("||ads.com^$redirect=missing").is_none());
+        assert!(RedirectRule::parse("||ads.com^$third-party").is_none());
+    }
+
+    #[test]
+    fn test_png_resources_have_their_named_size() {
+        for (name, size) in [("1x1.png", 1), ("2x2.png", 2)] {
+            let png = get_resource(name).unwrap().content;
+            // IHDR width and height
+            assert_eq!(png[16..20], [0, 0, 0, size], "{name}");
+            assert_eq!(png[20..24], [0, 0, 0, size], "{name}");
+        }
+    }
+}
@@ -0,0 +1,215 @@
+//! Rule hit counters
+//!
+//! Counts how often each network rule matches so rules that never fire can
+//! be found and pruned. A rule's first hit and the time of its last hit are
+//! always recorded, so a rule that matched is never reported as unused.
+//! Only its count is sampled: one match in `sample_rate` is counted and
+//! scaled back up, which keeps writes to hot rules' counters rare.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Default number of matches per recorded sample
+pub const DEFAULT_HIT_SAMPLE_RATE: u32 = 8;
+
+/// Hits of one rule
+#[derive(Debug)]
+struct RuleHits {
+    samples: AtomicU64,
+    /// Whole seconds from the start of tracking to the last hit
+    last_hit: AtomicU64,
+}
+
+/// Sampled per-rule hit counters, keyed by matched rule text
+#[derive(Debug)]
+pub struct RuleHitCounter {
+    sample_rate: u64,
+    matches: AtomicU64,
+    /// Rules hit at least once; only a rule's first hit takes the write lock
+    hits: parking_lot::RwLock<HashMap<String, RuleHits>>,
+    started: Instant,
+}
+
+/// Rules that did not match within a time window
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuleHitReport {
+    /// Window the report covers
+    pub window: Duration,
+    /// How long hits have been tracked; the report is partial while this
+    /// is shorter than `window`
+    pub tracked_for: Duration,
+    /// Number of network rules checked
+    pub total_rules: usize,
+    /// Rules without a recorded hit within the window, in rule order
+    pub unused_rules: Vec<String>,
+}
+
+impl RuleHitReport {
+    /// Whether hits were tracked for the whole window
+    pub fn is_complete(&self) -> bool {
+        self.tracked_for >= self.window
+    }
+}
+
+impl RuleHitCounter {
+    /// Create counters counting one match in `sample_rate`
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate: u64::from(sample_rate.max(1)),
+            matches: AtomicU64::new(0),
+            hits: parking_lot::RwLock::new(HashMap::new()),
+            started: Instant::now(),
+        }
+    }
+
+    /// Count a match of `rule`
+    pub fn record(&self, rule: &str) {
+        let sampled = self
+            .matches
+            .fetch_add(1, Ordering::Relaxed)
+            .is_multiple_of(self.sample_rate);
+        let now = self.started.elapsed().as_secs();
+        if let Some(entry) = self.hits.read().get(rule) {
+            entry.hit(sampled, now);
+            return;
+        }
+        self.hits
+            .write()
+            .entry(rule.to_string())
+            .or_insert_with(|| RuleHits {
+                samples: AtomicU64::new(0),
+                last_hit: AtomicU64::new(now),
+            })
+            .hit(sampled, now);
+    }
+
+    /// Estimated number of matches of `rule`
+    ///
+    /// At least 1 for a rule that matched, however rarely.
+    pub fn hits(&self, rule: &str) -> u64 {
+        self.hits.read().get(rule).map_or(0, |entry| {
+            (entry.samples.load(Ordering::Relaxed) * self.sample_rate).max(1)
+        })
+    }
+
+    /// Whether `rule` matched within `window`
+    pub fn hit_within(&self, rule: &str, window: Duration) -> bool {
+        self.hits
+            .read()
+            .get(rule)
+            .is_some_and(|entry| self.since(entry) <= window)
+    }
+
+    /// Time since the last hit, overestimated by less than a second
+    fn since(&self, entry: &RuleHits) -> Duration {
+        let last_hit = Duration::from_secs(entry.last_hit.load(Ordering::Relaxed));
+        self.started.elapsed().saturating_sub(last_hit)
+    }
+
+    /// Matches per recorded sample
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate as u32
+    }
+
+    /// Time since tracking started or was last reset
+    pub fn tracked_for(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Build a report of the `rules` without a hit within `window`
+    pub fn report<I>(&self, rules: I, window: Duration) -> RuleHitReport
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let hits = self.hits.read();
+        let mut total_rules = 0;
+        let unused_rules = rules
+            .into_iter()
+            .inspect(|_| total_rules += 1)
+            .filter(|rule| {
+                hits.get(rule)
+                    .is_none_or(|entry| self.since(entry) > window)
+            })
+            .collect();
+        RuleHitReport {
+            window,
+            tracked_for: self.tracked_for(),
+            total_rules,
+            unused_rules,
+        }
+    }
+
+    /// Forget all hits and restart the tracking period
+    pub fn reset(&mut self) {
+        self.hits.get_mut().clear();
+        *self.matches.get_mut() = 0;
+        self.started = Instant::now();
+    }
+
+    /// Estimated heap memory in bytes
+    pub fn memory_usage(&self) -> usize {
+        self.hits
+            .read()
+            .keys()
+            .map(|rule| rule.len() + std::mem::size_of::<(String, RuleHits)>())
+            .sum()
+    }
+}
+
+impl RuleHits {
+    fn hit(&self, sampled: bool, now: u64) {
+        if sampled {
+            self.samples.fetch_add(1, Ordering::Relaxed);
+        }
+        // Written at most once a second, however hot the rule
+        if self.last_hit.load(Ordering::Relaxed) < now {
+            self.last_hit.store(now, Ordering::Relaxed);
+        }
+    }
+}
+
+impl Default for RuleHitCounter {
+    fn default() -> Self {
+        Self::new(DEFAULT_HIT_SAMPLE_RATE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sampled_hits_are_scaled() {
+        let counter = RuleHitCounter::new(4);
+        for _ in 0..8 {
+            counter.record("||ads.com^");
+        }
+        assert_eq!(counter.hits("||ads.com^"), 8);
+        assert!(counter.hit_within("||ads.com^", Duration::from_secs(60)));
+
+        let report = counter.report(
+            ["||ads.com^".to_string(), "||unused.com^".to_string()],
+            Duration::from_secs(60),
+        );
+        assert_eq!(report.total_rules, 2);
+        assert_eq!(report.unused_rules, vec!["||unused.com^"]);
+        assert!(!report.is_complete());
+    }
+
+    #[test]
+    fn test_rarely_hit_rules_are_never_unused() {
+        let counter = RuleHitCounter::new(8);
+        counter.record("||hot.com^");
+        // Each of these falls between samples
+        counter.record("||rare.com^");
+        counter.record("||rarer.com^");
+
+        assert_eq!(counter.hits("||rare.com^"), 1);
+        let report = counter.report(
+            ["||rare.com^".to_string(), "||rarer.com^".to_string()],
+            Duration::from_secs(60),
+        );
+        assert!(report.unused_rules.is_empty());
+    }
+}
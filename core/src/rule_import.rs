@@ -0,0 +1,333 @@
+//! User rule import deduplication
+//!
+//! Checks imported user rules (or rules restored from a backup) against the
+//! subscribed filter lists and the existing user rule store, so rules that
+//! are already covered can be flagged or skipped instead of duplicated.
+//!
+//! User rules and the whitelist can also be exported as one plain filter
+//! list and imported back with [`RuleImporter::import_text`].
+
+use crate::filter_list::plain_domain_block;
+use crate::lint::LintSeverity;
+use serde::Serialize;
+use std::collections::HashSet;
+
+/// Header of exported user rules
+const EXPORT_HEADER: &str = "[Adblock Plus 2.0]\n! Title: AdBlock user rules\n";
+
+/// Outcome for a single imported rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ImportStatus {
+    /// Not covered by anything; added to the user rules
+    Added,
+    /// Already in the user rules or earlier in the same import
+    Duplicate,
+    /// Already covered by a subscribed list
+    Redundant,
+}
+
+/// A rule checked during import
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ImportedRule {
+    pub rule: String,
+    pub status: ImportStatus,
+}
+
+/// Result of an import
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ImportReport {
+    /// Every non-empty, non-comment rule in import order
+    pub rules: Vec<ImportedRule>,
+    /// User rules after the import
+    pub user_rules: Vec<String>,
+}
+
+impl ImportReport {
+    /// Number of rules with the given status
+    pub fn count(&self, status: ImportStatus) -> usize {
+        self.rules.iter().filter(|r| r.status == status).count()
+    }
+
+    /// Rules covered by a subscribed list
+    pub fn redundant(&self) -> impl Iterator<Item = &str> {
+        self.rules
+            .iter()
+            .filter(|r| r.status == ImportStatus::Redundant)
+            .map(|r| r.rule.as_str())
+    }
+}
+
+/// A line rejected by a text import
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct InvalidLine {
+    /// 1-based line number
+    pub line: usize,
+    pub text: String,
+    pub reason: String,
+}
+
+/// Result of importing user rules and whitelist text
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct TextImportReport {
+    /// Outcome of the user rules
+    pub rules: ImportReport,
+    /// Whitelist after the import
+    pub whitelist: Vec<String>,
+    /// Domains newly added to the whitelist
+    pub whitelist_added: Vec<String>,
+    /// Whitelist entries that were already present
+    pub whitelist_duplicates: usize,
+    /// Lines skipped because they can never match
+    pub invalid: Vec<InvalidLine>,
+}
+
+/// Export user rules and whitelisted domains as filter list text
+///
+/// Whitelisted domains become `@@||domain^$document` rules, which other
+/// blockers also read as site allowlist entries.
+pub fn export_user_rules(custom_rules: &[String], whitelist: &[String]) -> String {
+    let mut text = EXPORT_HEADER.to_string();
+    for rule in custom_rules {
+        text.push_str(rule);
+        text.push('\n');
+    }
+    if !whitelist.is_empty() {
+        text.push_str("! Whitelist\n");
+        for domain in whitelist {
+            text.push_str(&format!("@@||{}^$document\n", domain.trim()));
+        }
+    }
+    text
+}
+
+/// Domain of an `@@||domain^$document` whitelist rule
+fn whitelist_domain(rule: &str) -> Option<&str> {
+    let domain = rule.strip_prefix("@@")?.strip_suffix("$document")?;
+    plain_domain_block(domain)
+}
+
+/// Checks imported rules against the active subscriptions
+#[derive(Debug, Clone)]
+pub struct RuleImporter {
+    /// Every rule of the subscribed lists
+    subscribed: HashSet<String>,
+    /// Domains blocked by plain `||domain^` subscription rules
+    blocked_domains: HashSet<String>,
+    /// Drop redundant rules instead of keeping them
+    skip_redundant: bool,
+}
+
+impl RuleImporter {
+    /// Create an importer with no subscriptions that skips redundant rules
+    pub fn new() -> Self {
+        Self {
+            subscribed: HashSet::new(),
+            blocked_domains: HashSet::new(),
+            skip_redundant: true,
+        }
+    }
+
+    /// Register the content of a subscribed filter list
+    pub fn add_subscription(&mut self, content: &str) {
+        for rule in content.lines().filter_map(normalize) {
+            if let Some(domain) = plain_domain_block(rule) {
+                self.blocked_domains.insert(domain.to_string());
+            }
+            self.subscribed.insert(rule.to_string());
+        }
+    }
+
+    /// Keep redundant rules in the user store (they are still reported)
+    pub fn set_skip_redundant(&mut self, skip: bool) {
+        self.skip_redundant = skip;
+    }
+
+    /// Whether a subscribed list already covers a rule
+    ///
+    /// A rule is covered by an identical subscription rule, or, for
+    /// `||sub.example.com^`, by a subscribed `||example.com^`.
+    pub fn is_redundant(&self, rule: &str) -> bool {
+        let Some(rule) = normalize(rule) else {
+            return false;
+        };
+        if self.subscribed.contains(rule) {
+            return true;
+        }
+
+        let Some(mut domain) = plain_domain_block(rule) else {
+            return false;
+        };
+        while let Some(pos) = domain.find('.') {
+            domain = &domain[pos + 1..];
+            if self.blocked_domains.contains(domain) {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Merge imported rules into the existing user rules
+    pub fn import<'a, I>(&self, existing: &[String], incoming: I) -> ImportReport
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let mut seen: HashSet<String> = existing
+            .iter()
+            .filter_map(|rule| normalize(rule))
+            .map(str::to_string)
+            .collect();
+        let mut report = ImportReport {
+            rules: Vec::new(),
+            user_rules: existing.to_vec(),
+        };
+
+        for rule in incoming.into_iter().filter_map(normalize) {
+            let status = if seen.contains(rule) {
+                ImportStatus::Duplicate
+            } else if self.is_redundant(rule) {
+                ImportStatus::Redundant
+            } else {
+                ImportStatus::Added
+            };
+
+            let keep = match status {
+                ImportStatus::Added => true,
+                ImportStatus::Redundant => !self.skip_redundant,
+                ImportStatus::Duplicate => false,
+            };
+            if keep {
+                seen.insert(rule.to_string());
+                report.user_rules.push(rule.to_string());
+            }
+
+            report.rules.push(ImportedRule {
+                rule: rule.to_string(),
+                status,
+            });
+        }
+
+        report
+    }
+
+    /// Import text written by [`export_user_rules`] or any filter list
+    ///
+    /// `@@||domain^$document` rules go to the whitelist, other rules to the
+    /// user rules. Duplicates are skipped, and lines the linter reports as
+    /// errors are returned as invalid instead of being imported.
+    pub fn import_text(
+        &self,
+        existing_rules: &[String],
+        existing_whitelist: &[String],
+        text: &str,
+    ) -> TextImportReport {
+        let mut report = TextImportReport {
+            whitelist: existing_whitelist.to_vec(),
+            ..TextImportReport::default()
+        };
+        for diagnostic in crate::lint::lint(text) {
+            if diagnostic.severity == LintSeverity::Error
+                && report
+                    .invalid
+                    .last()
+                    .is_none_or(|last| last.line != diagnostic.line)
+            {
+                report.invalid.push(InvalidLine {
+                    line: diagnostic.line,
+                    text: String::new(),
+                    reason: diagnostic.message,
+                });
+            }
+        }
+
+        let lines: Vec<&str> = text.lines().collect();
+        for invalid in &mut report.invalid {
+            invalid.text = lines[invalid.line - 1].trim().to_string();
+        }
+        let invalid_lines: HashSet<usize> = report.invalid.iter().map(|i| i.line).collect();
+
+        let mut incoming = Vec::new();
+        for (index, line) in lines.iter().enumerate() {
+            let Some(rule) = normalize(line) else {
+                continue;
+            };
+            if invalid_lines.contains(&(index + 1)) {
+                continue;
+            }
+            let Some(domain) = whitelist_domain(rule) else {
+                incoming.push(rule);
+                continue;
+            };
+            if report
+                .whitelist
+                .iter()
+                .any(|existing| existing.trim().eq_ignore_ascii_case(domain))
+            {
+                report.whitelist_duplicates += 1;
+            } else {
+                report.whitelist.push(domain.to_string());
+                report.whitelist_added.push(domain.to_string());
+            }
+        }
+        report.rules = self.import(existing_rules, incoming);
+        report
+    }
+}
+
+impl Default for RuleImporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Trim a rule line, skipping blanks, comments and list headers
+fn normalize(line: &str) -> Option<&str> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('!') || line.starts_with('[') {
+        None
+    } else {
+        Some(line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_import_flags_covered_rules() {
+        let mut importer = RuleImporter::new();
+        importer.add_subscription("[Adblock Plus 2.0]\n! Title: Test\n||ads.com^\n##.banner");
+
+        let existing = vec!["||mine.com^".to_string()];
+        let report = importer.import(
+            &existing,
+            "||mine.com^\n||cdn.ads.com^\n##.banner\n! note\n||new.com^\n||new.com^".lines(),
+        );
+
+        assert_eq!(report.count(ImportStatus::Added), 1);
+        assert_eq!(report.count(ImportStatus::Duplicate), 2);
+        assert_eq!(
+            report.redundant().collect::<Vec<_>>(),
+            vec!["||cdn.ads.com^", "##.banner"]
+        );
+        assert_eq!(report.user_rules, vec!["||mine.com^", "||new.com^"]);
+
+        importer.set_skip_redundant(false);
+        let report = importer.import(&[], ["||cdn.ads.com^"]);
+        assert_eq!(report.user_rules, vec!["||cdn.ads.com^"]);
+        assert!(!importer.is_redundant("||ads.com.evil^"));
+    }
+
+    #[test]
+    fn test_text_roundtrip() {
+        let rules = vec!["||ads.com^".to_string(), "example.com##.ad".to_string()];
+        let whitelist = vec!["shop.example".to_string()];
+        let text = export_user_rules(&rules, &whitelist);
+        assert!(text.contains("@@||shop.example^$document"));
+
+        let report = RuleImporter::new().import_text(&[], &[], &text);
+        assert_eq!(report.rules.user_rules, rules);
+        assert_eq!(report.whitelist, whitelist);
+        assert!(report.invalid.is_empty());
+    }
+}
@@ -0,0 +1,150 @@
+//! Rule source shared by the URL and DNS filters
+//!
+//! A [`RuleSet`] holds the filter lists the engines are built from and is
+//! parsed once into a domain-level view for DNS filtering. The
+//! `FilterEngine` is built from its lists, and `NetworkFilter`s linked to a
+//! [`SharedRuleSet`] see every reload of it, so the two cannot drift apart.
+
+use crate::domain_filter::DomainFilter;
+pub use crate::domain_filter::{parse_domain_rule, DomainRule};
+use crate::FilterEngine;
+use std::sync::Arc;
+
+/// Rule set slot shared by the core and the network filters reading it
+pub type SharedRuleSet = Arc<parking_lot::RwLock<Arc<RuleSet>>>;
+
+/// Named filter list of a rule set
+#[derive(Debug, Clone)]
+struct RuleList {
+    name: String,
+    content: String,
+    security: bool,
+}
+
+/// Filter lists with their domain-level view
+#[derive(Debug, Clone, Default)]
+pub struct RuleSet {
+    lists: Vec<RuleList>,
+    domains: DomainFilter,
+}
+
+impl RuleSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Rule set of a single unnamed filter list
+    pub fn from_filter_list(content: &str) -> Self {
+        let mut rule_set = Self::new();
+        rule_set.add_list("", content);
+        rule_set
+    }
+
+    /// Add a filter list, attributing its blocks to `name`
+    pub fn add_list(&mut self, name: &str, content: &str) {
+        self.push_list(name, content, false);
+    }
+
+    /// Add a malware/phishing protection list
+    pub fn add_security_list(&mut self, name: &str, content: &str) {
+        self.push_list(name, content, true);
+    }
+
+    fn push_list(&mut self, name: &str, content: &str, security: bool) {
+        self.domains.add_list(content);
+        self.lists.push(RuleList {
+            name: name.to_string(),
+            content: content.to_string(),
+            security,
+        });
+    }
+
+    /// Build the URL-level engine from the lists
+    ///
+    /// Unnamed lists are loaded without attribution.
+    pub fn build_engine(&self) -> Result<FilterEngine, Box<dyn std::error::Error>> {
+        let mut engine = FilterEngine::from_filter_list("")?;
+        for list in &self.lists {
+            if list.security {
+                engine.load_security_list(&list.name, &list.content);
+            } else if list.name.is_empty() {
+                engine.load_easylist_rules(&list.content)?;
+            } else {
+                engine.load_list(&list.name, &list.content);
+            }
+        }
+        Ok(engine)
+    }
+
+    /// Apply a filter list diff to the domain-level view
+    pub fn apply_diff(&mut self, diff: &crate::filter_updater::FilterListDiff) {
+        for line in &diff.removed {
+            self.domains.remove_rule(line);
+        }
+        for line in &diff.added {
+            self.domains.add_rule(line);
+        }
+    }
+
+    /// Drop the list content once an engine is built, keeping the list
+    /// names and the domain-level view
+    pub(crate) fn drop_content(&mut self) {
+        for list in &mut self.lists {
+            list.content = String::new();
+        }
+    }
+
+    /// Whether a domain or one of its parents has a whole-domain block
+    pub fn blocks_domain(&self, domain: &str) -> bool {
+        self.domains.blocks_domain(domain)
+    }
+
+    /// Whether a domain or one of its parents has a whole-domain exception
+    pub fn allows_domain(&self, domain: &str) -> bool {
+        self.domains.allows_domain(domain)
+    }
+
+    /// Number of domains blocked at the DNS level
+    pub fn blocked_domain_count(&self) -> usize {
+        self.domains.blocked_domain_count()
+    }
+
+    /// Number of lists
+    pub fn len(&self) -> usize {
+        self.lists.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lists.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_views_share_the_lists() {
+        let mut rule_set = RuleSet::new();
+        rule_set.add_list(
+            "Ads",
+            "||ads.example^\n@@||ok.ads.example^\n||example.com/banner/",
+        );
+        let engine = rule_set.build_engine().unwrap();
+
+        assert!(rule_set.blocks_domain("img.ads.example"));
+        assert!(rule_set.allows_domain("ok.ads.example"));
+        assert!(!rule_set.blocks_domain("example.com"));
+        assert!(
+            engine
+                .should_block("https://img.ads.example/x.js")
+                .should_block
+        );
+        assert!(
+            engine
+                .should_block("https://example.com/banner/1.png")
+                .should_block
+        );
+        assert_eq!(rule_set.blocked_domain_count(), 1);
+    }
+}
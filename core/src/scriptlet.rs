@@ -0,0 +1,350 @@
+//! Scriptlet injection rules
+//!
+//! Parses uBlock-style `##+js(name, arg1, arg2)` rules and renders them
+//! into JavaScript ready to inject into matching pages, using a small
+//! bundled library of standard scriptlets.
+
+use crate::cosmetic::domain_matches;
+
+/// Bundled scriptlets: canonical name, aliases, and function body
+///
+/// Each body runs with the rule arguments bound to `args`.
+const SCRIPTLETS: &[(&str, &[&str], &str)] = &[
+    (
+        "set-constant",
+        &["set"],
+        r#"const chain = args[0] || '';
+const raw = args[1] || '';
+const constants = {
+  'undefined': undefined, 'false': false, 'true': true, 'null': null, '': '',
+  'noopFunc': function() {}, 'trueFunc': function() { return true; },
+  'falseFunc': function() { return false; }
+};
+let value;
+if (Object.prototype.hasOwnProperty.call(constants, raw)) {
+  value = constants[raw];
+} else if (/^\d+$/.test(raw)) {
+  value = parseInt(raw, 10);
+  if (value > 0x7FFF) { return; }
+} else {
+  return;
+}
+const parts = chain.split('.');
+const last = parts.pop();
+let owner = window;
+for (const part of parts) {
+  if (owner[part] === undefined || owner[part] === null) { owner[part] = {}; }
+  owner = owner[part];
+}
+Object.defineProperty(owner, last, {
+  get: function() { return value; },
+  set: function() {},
+  configurable: false
+});"#,
+    ),
+    (
+        "abort-on-property-read",
+        &["aopr"],
+        r#"const parts = (args[0] || '').split('.');
+const last = parts.pop();
+let owner = window;
+for (const part of parts) {
+  owner = owner[part];
+  if (owner === undefined || owner === null) { return; }
+}
+const error = new ReferenceError('Aborted by scriptlet');
+Object.defineProperty(owner, last, {
+  get: function() { throw error; },
+  set: function() {}
+});"#,
+    ),
+    (
+        "abort-on-property-write",
+        &["aopw"],
+        r#"const parts = (args[0] || '').split('.');
+const last = parts.pop();
+let owner = window;
+for (const part of parts) {
+  owner = owner[part];
+  if (owner === undefined || owner === null) { return; }
+}
+const error = new ReferenceError('Aborted by scriptlet');
+let current = owner[last];
+Object.defineProperty(owner, last, {
+  get: function() { return current; },
+  set: function() { throw error; }
+});"#,
+    ),
+    (
+        "no-setTimeout-if",
+        &["nostif", "prevent-setTimeout"],
+        r#"const needle = args[0] || '';
+const native = window.setTimeout;
+window.setTimeout = function(handler, ...rest) {
+  if (String(handler).includes(needle)) { return 0; }
+  return native.call(this, handler, ...rest);
+};"#,
+    ),
+    (
+        "no-setInterval-if",
+        &["nosiif", "prevent-setInterval"],
+        r#"const needle = args[0] || '';
+const native = window.setInterval;
+window.setInterval = function(handler, ...rest) {
+  if (String(handler).includes(needle)) { return 0; }
+  return native.call(this, handler, ...rest);
+};"#,
+    ),
+    (
+        "remove-attr",
+        &["ra"],
+        r#"const attrs = (args[0] || '').split('|').filter(Boolean);
+if (attrs.length === 0) { return; }
+const selector = args[1] || attrs.map(function(a) { return '[' + a + ']'; }).join(',');
+const run = function() {
+  for (const el of document.querySelectorAll(selector)) {
+    for (const attr of attrs) { el.removeAttribute(attr); }
+  }
+};
+if (document.readyState === 'loading') {
+  document.addEventListener('DOMContentLoaded', run);
+} else {
+  run();
+}"#,
+    ),
+    (
+        "remove-class",
+        &["rc"],
+        r#"const classes = (args[0] || '').split('|').filter(Boolean);
+if (classes.length === 0) { return; }
+const selector = args[1] || classes.map(function(c) { return '.' + c; }).join(',');
+const run = function() {
+  for (const el of document.querySelectorAll(selector)) {
+    el.classList.remove(...classes);
+  }
+};
+if (document.readyState === 'loading') {
+  document.addEventListener('DOMContentLoaded', run);
+} else {
+  run();
+}"#,
+    ),
+];
+
+/// Resolve a scriptlet name or alias to its canonical name and body
+fn lookup(name: &str) -> Option<(&'static str, &'static str)> {
+    let name = name.strip_suffix(".js").unwrap_or(name);
+    SCRIPTLETS
+        .iter()
+        .find(|(canonical, aliases, _)| *canonical == name || aliases.contains(&name))
+        .map(|(canonical, _, body)| (*canonical, *body))
+}
+
+/// Names of the bundled scriptlets
+pub fn bundled_scriptlets() -> Vec<&'static str> {
+    SCRIPTLETS.iter().map(|(name, _, _)| *name).collect()
+}
+
+/// A single `##+js(...)` rule
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ScriptletRule {
+    /// Scriptlet name as written in the rule
+    pub name: String,
+    /// Scriptlet arguments
+    pub args: Vec<String>,
+    /// Domains the rule applies to (empty = all domains)
+    pub domains: Vec<String>,
+    /// Domains the rule is disabled on (`~domain`)
+    pub excluded_domains: Vec<String>,
+}
+
+impl ScriptletRule {
+    /// Parse a rule like `example.com##+js(set-constant, foo, false)`
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = line.trim();
+        let separator_pos = line.find("##+js(")?;
+        let domains_part = &line[..separator_pos];
+        let body = line[separator_pos + 6..].strip_suffix(')')?;
+
+        let mut parts = split_args(body).into_iter();
+        let name = parts.next().filter(|name| !name.is_empty())?;
+        let args = parts.collect();
+
+        let mut domains = Vec::new();
+        let mut excluded_domains = Vec::new();
+        for domain in domains_part.split(',') {
+            let domain = domain.trim().to_lowercase();
+            if domain.is_empty() {
+                continue;
+            }
+            if let Some(excluded) = domain.strip_prefix('~') {
+                excluded_domains.push(excluded.to_string());
+            } else {
+                domains.push(domain);
+            }
+        }
+
+        Some(ScriptletRule {
+            name,
+            args,
+            domains,
+            excluded_domains,
+        })
+    }
+
+    /// Check if the rule applies to a page domain
+    pub fn applies_to(&self, domain: &str) -> bool {
+        let domain = domain.to_lowercase();
+
+        if self
+            .excluded_domains
+            .iter()
+            .any(|excluded| domain_matches(&domain, excluded))
+        {
+            return false;
+        }
+
+        self.domains.is_empty() || self.domains.iter().any(|d| domain_matches(&domain, d))
+    }
+
+    /// Render the rule as injectable JavaScript, if the scriptlet is bundled
+    pub fn to_js(&self) -> Option<String> {
+        let (name, body) = lookup(&self.name)?;
+        // JSON encoding doubles as JS string escaping for the arguments
+        let args = serde_json::to_string(&self.args).ok()?;
+        Some(format!(
+            "(function(args) {{\n// scriptlet: {name}\ntry {{\n{body}\n}} catch (e) {{}}\n}})({args});"
+        ))
+    }
+}
+
+/// Split scriptlet arguments on commas, honouring `\,` escapes
+fn split_args(body: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut chars = body.chars();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => match chars.next() {
+                Some(',') => current.push(','),
+                Some(other) => {
+                    current.push('\\');
+                    current.push(other);
+                }
+                None => current.push('\\'),
+            },
+            ',' => args.push(std::mem::take(&mut current).trim().to_string()),
+            _ => current.push(ch),
+        }
+    }
+    args.push(current.trim().to_string());
+
+    args
+}
+
+/// Collection of scriptlet rules
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct ScriptletSet {
+    rules: Vec<ScriptletRule>,
+}
+
+impl ScriptletSet {
+    /// Create an empty set
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse and add a rule line, returning whether it was a scriptlet rule
+    pub fn add_rule(&mut self, line: &str) -> bool {
+        match ScriptletRule::parse(line) {
+            Some(rule) => {
+                if lookup(&rule.name).is_none() {
+                    tracing::debug!("Unknown scriptlet: {}", rule.name);
+                }
+                self.rules.push(rule);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove one rule equal to the parsed line, returning whether one was removed
+    pub fn remove_rule(&mut self, line: &str) -> bool {
+        let Some(rule) = ScriptletRule::parse(line) else {
+            return false;
+        };
+        match self.rules.iter().position(|existing| *existing == rule) {
+            Some(index) => {
+                self.rules.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Add all `##+js` rules from a filter list
+    pub fn load_filter_list(&mut self, content: &str) {
+        for line in content.lines() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('!') {
+                continue;
+            }
+            if trimmed.contains("##+js(") {
+                self.add_rule(trimmed);
+            }
+        }
+    }
+
+    /// Get ready-to-inject scripts for a page domain
+    ///
+    /// Rules naming scriptlets that are not bundled are skipped.
+    pub fn scriptlets_for_domain(&self, domain: &str) -> Vec<String> {
+        self.rules
+            .iter()
+            .filter(|rule| rule.applies_to(domain))
+            .filter_map(ScriptletRule::to_js)
+            .collect()
+    }
+
+    /// All stored rules
+    pub fn rules(&self) -> &[ScriptletRule] {
+        &self.rules
+    }
+
+    /// Number of stored rules
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    /// Whether the set is empty
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scriptlet_parsing_and_rendering() {
+        let rule = ScriptletRule::parse(r"example.com,~www.example.com##+js(aopr, a\,b)").unwrap();
+        assert_eq!(rule.name, "aopr");
+        assert_eq!(rule.args, vec!["a,b"]);
+        assert!(rule.applies_to("news.example.com"));
+        assert!(!rule.applies_to("www.example.com"));
+
+        let mut set = ScriptletSet::new();
+        set.load_filter_list(
+            "example.com##+js(set-constant, ads.enabled, false)\n##+js(unknown-scriptlet)\n##.ad",
+        );
+        assert_eq!(set.len(), 2);
+
+        let scripts = set.scriptlets_for_domain("example.com");
+        assert_eq!(scripts.len(), 1);
+        assert!(scripts[0].contains("// scriptlet: set-constant"));
+        assert!(scripts[0].ends_with(r#"})(["ads.enabled","false"]);"#));
+        assert!(set.scriptlets_for_domain("other.com").is_empty());
+    }
+}
@@ -0,0 +1,130 @@
+//! Per-site blocking profiles
+//!
+//! Users can turn blocking off for a site, limit it to network rules, or
+//! keep the full rule set including generic cosmetic rules. A mode set for
+//! a domain also covers its subdomains unless they have their own.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// How much blocking applies to pages of a site
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SiteMode {
+    /// Nothing is blocked or hidden
+    Off,
+    /// Network rules only; no element hiding, procedural or scriptlet rules
+    Standard,
+    /// Network rules plus every cosmetic rule, generic ones included
+    #[default]
+    Aggressive,
+}
+
+impl SiteMode {
+    /// Whether network requests made by the site's pages may be blocked
+    pub fn blocks_requests(self) -> bool {
+        self != SiteMode::Off
+    }
+
+    /// Whether cosmetic rules are applied to the site's pages
+    pub fn applies_cosmetic_rules(self) -> bool {
+        self == SiteMode::Aggressive
+    }
+}
+
+/// Modes set for individual sites
+///
+/// Sites without a mode use [`SiteMode::default`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SitePolicy {
+    sites: BTreeMap<String, SiteMode>,
+}
+
+impl SitePolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the mode of a domain and its subdomains
+    pub fn set_mode(&mut self, domain: &str, mode: SiteMode) {
+        let domain = normalize_domain(domain);
+        if !domain.is_empty() {
+            self.sites.insert(domain, mode);
+        }
+    }
+
+    /// Drop the mode set for a domain, returning whether one was set
+    pub fn remove(&mut self, domain: &str) -> bool {
+        self.sites.remove(&normalize_domain(domain)).is_some()
+    }
+
+    /// Mode set for exactly this domain
+    pub fn explicit_mode(&self, domain: &str) -> Option<SiteMode> {
+        self.sites.get(&normalize_domain(domain)).copied()
+    }
+
+    /// Mode of a host: that of the closest domain with a mode set
+    pub fn mode_for(&self, host: &str) -> SiteMode {
+        let host = normalize_domain(host);
+        let mut candidate = host.as_str();
+        loop {
+            if let Some(&mode) = self.sites.get(candidate) {
+                return mode;
+            }
+            match candidate.split_once('.') {
+                Some((_, parent)) => candidate = parent,
+                None => return SiteMode::default(),
+            }
+        }
+    }
+
+    /// Domains with a mode set, in domain order
+    pub fn iter(&self) -> impl Iterator<Item = (&str, SiteMode)> {
+        self.sites
+            .iter()
+            .map(|(domain, &mode)| (domain.as_str(), mode))
+    }
+
+    pub fn len(&self) -> usize {
+        self.sites.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sites.is_empty()
+    }
+}
+
+/// Lowercase a domain and strip a port, `*.` prefix and trailing dot
+fn normalize_domain(domain: &str) -> String {
+    let domain = domain.trim().trim_start_matches("*.");
+    let domain = domain.split(':').next().unwrap_or(domain);
+    domain.trim_end_matches('.').to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mode_for_subdomains() {
+        let mut policy = SitePolicy::new();
+        policy.set_mode("Example.com", SiteMode::Off);
+        policy.set_mode("news.example.com", SiteMode::Standard);
+
+        assert_eq!(policy.mode_for("www.example.com"), SiteMode::Off);
+        assert_eq!(
+            policy.mode_for("a.news.example.com:443"),
+            SiteMode::Standard
+        );
+        assert_eq!(policy.mode_for("example.org"), SiteMode::Aggressive);
+        assert_eq!(policy.explicit_mode("www.example.com"), None);
+
+        assert!(policy.remove("example.com"));
+        assert_eq!(policy.mode_for("www.example.com"), SiteMode::Aggressive);
+        assert_eq!(
+            serde_json::to_string(&policy).unwrap(),
+            r#"{"news.example.com":"standard"}"#
+        );
+    }
+}
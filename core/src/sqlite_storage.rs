@@ -0,0 +1,386 @@
+//! SQLite storage backend
+//!
+//! Keeps statistics, the query log and the filter list registry in one
+//! SQLite database, so long query histories are searched and paged with SQL
+//! instead of being loaded into memory. The schema is versioned with
+//! `PRAGMA user_version` and migrated forward when a database is opened.
+
+use crate::filter_registry::FilterListInfo;
+use crate::query_log::{QueryFilter, QueryLogEntry, QueryVerdict};
+use crate::statistics::DomainStats;
+use crate::storage::{Storage, StoredStatistics};
+use rusqlite::types::ToSql;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension};
+use std::path::Path;
+
+/// Schema migrations; entry `n` brings the schema from version `n` to `n + 1`
+const MIGRATIONS: &[&str] = &[
+    // Version 1: initial schema
+    "CREATE TABLE statistics (
+        id INTEGER PRIMARY KEY CHECK (id = 0),
+        blocked_count INTEGER NOT NULL,
+        allowed_count INTEGER NOT NULL,
+        data_saved INTEGER NOT NULL
+    );
+    CREATE TABLE domain_stats (
+        domain TEXT PRIMARY KEY,
+        count INTEGER NOT NULL,
+        data_saved INTEGER NOT NULL,
+        category TEXT NOT NULL
+    );
+    CREATE TABLE query_log (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        timestamp_ms INTEGER NOT NULL,
+        domain TEXT NOT NULL,
+        verdict INTEGER NOT NULL,
+        reason TEXT,
+        app TEXT
+    );
+    CREATE INDEX query_log_timestamp ON query_log (timestamp_ms);
+    CREATE INDEX query_log_domain ON query_log (domain);
+    CREATE INDEX query_log_verdict ON query_log (verdict);
+    CREATE TABLE filter_lists (
+        url TEXT PRIMARY KEY,
+        position INTEGER NOT NULL,
+        info TEXT NOT NULL,
+        content TEXT NOT NULL
+    );",
+];
+
+/// [`Storage`] backed by an SQLite database
+#[derive(Debug)]
+pub struct SqliteStorage {
+    connection: Connection,
+}
+
+impl SqliteStorage {
+    /// Open or create a database file, migrating it to the current schema
+    pub fn open(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    /// Database kept in memory only
+    pub fn open_in_memory() -> Result<Self, Box<dyn std::error::Error>> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(connection: Connection) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut storage = SqliteStorage { connection };
+        storage.migrate()?;
+        Ok(storage)
+    }
+
+    /// Version of the database schema
+    pub fn schema_version(&self) -> Result<usize, Box<dyn std::error::Error>> {
+        Ok(self
+            .connection
+            .query_row("PRAGMA user_version", [], |row| row.get(0))?)
+    }
+
+    /// Apply the migrations the database has not seen yet
+    fn migrate(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let version = self.schema_version()?;
+        if version > MIGRATIONS.len() {
+            return Err(format!("Unsupported database schema version {version}").into());
+        }
+        for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+            let transaction = self.connection.transaction()?;
+            transaction.execute_batch(migration)?;
+            transaction.pragma_update(None, "user_version", index + 1)?;
+            transaction.commit()?;
+        }
+        Ok(())
+    }
+}
+
+/// `WHERE` clause and parameters selecting the entries matching a filter
+fn query_filter_clause(filter: &QueryFilter) -> (String, Vec<Box<dyn ToSql>>) {
+    let mut conditions = Vec::new();
+    let mut values: Vec<Box<dyn ToSql>> = Vec::new();
+    if let Some(domain) = &filter.domain {
+        conditions.push("instr(lower(domain), ?) > 0");
+        values.push(Box::new(domain.to_ascii_lowercase()));
+    }
+    if let Some(verdict) = filter.verdict {
+        conditions.push("verdict = ?");
+        values.push(Box::new(verdict.to_byte()));
+    }
+    if let Some(since) = filter.since_ms {
+        conditions.push("timestamp_ms >= ?");
+        values.push(Box::new(since));
+    }
+    if let Some(until) = filter.until_ms {
+        conditions.push("timestamp_ms < ?");
+        values.push(Box::new(until));
+    }
+    if let Some(app) = &filter.app {
+        conditions.push("app = ?");
+        values.push(Box::new(app.clone()));
+    }
+    if conditions.is_empty() {
+        return (String::new(), values);
+    }
+    (format!(" WHERE {}", conditions.join(" AND ")), values)
+}
+
+impl Storage for SqliteStorage {
+    fn save_statistics(
+        &mut self,
+        statistics: &StoredStatistics,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let transaction = self.connection.transaction()?;
+        transaction.execute(
+            "INSERT OR REPLACE INTO statistics (id, blocked_count, allowed_count, data_saved)
+             VALUES (0, ?1, ?2, ?3)",
+            params![
+                statistics.blocked_count,
+                statistics.allowed_count,
+                statistics.data_saved
+            ],
+        )?;
+        transaction.execute("DELETE FROM domain_stats", [])?;
+        {
+            let mut insert = transaction.prepare(
+                "INSERT INTO domain_stats (domain, count, data_saved, category)
+                 VALUES (?1, ?2, ?3, ?4)",
+            )?;
+            for domain in &statistics.domains {
+                insert.execute(params![
+                    domain.domain,
+                    domain.count,
+                    domain.data_saved,
+                    serde_json::to_string(&domain.category)?
+                ])?;
+            }
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn load_statistics(&self) -> Result<Option<StoredStatistics>, Box<dyn std::error::Error>> {
+        let totals = self
+            .connection
+            .query_row(
+                "SELECT blocked_count, allowed_count, data_saved FROM statistics WHERE id = 0",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .optional()?;
+        let Some((blocked_count, allowed_count, data_saved)) = totals else {
+            return Ok(None);
+        };
+        let mut select = self
+            .connection
+            .prepare("SELECT domain, count, data_saved, category FROM domain_stats")?;
+        let rows = select.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, u64>(1)?,
+                row.get::<_, u64>(2)?,
+                row.get::<_, String>(3)?,
+            ))
+        })?;
+        let mut domains = Vec::new();
+        for row in rows {
+            let (domain, count, data_saved, category) = row?;
+            domains.push(DomainStats {
+                domain,
+                count,
+                data_saved,
+                category: serde_json::from_str(&category).unwrap_or_default(),
+            });
+        }
+        Ok(Some(StoredStatistics {
+            blocked_count,
+            allowed_count,
+            data_saved,
+            domains,
+        }))
+    }
+
+    fn append_query(&mut self, entry: &QueryLogEntry) -> Result<(), Box<dyn std::error::Error>> {
+        self.connection.execute(
+            "INSERT INTO query_log (timestamp_ms, domain, verdict, reason, app)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                entry.timestamp_ms,
+                entry.domain,
+                entry.verdict.to_byte(),
+                entry.reason,
+                entry.app
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn search_queries(
+        &self,
+        filter: &QueryFilter,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<QueryLogEntry>, Box<dyn std::error::Error>> {
+        let (clause, mut values) = query_filter_clause(filter);
+        values.push(Box::new(limit.min(i64::MAX as usize)));
+        values.push(Box::new(offset));
+        let mut select = self.connection.prepare(&format!(
+            "SELECT timestamp_ms, domain, verdict, reason, app FROM query_log{clause}
+             ORDER BY id DESC LIMIT ? OFFSET ?"
+        ))?;
+        let rows = select.query_map(params_from_iter(values), |row| {
+            Ok((
+                row.get::<_, u64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, u8>(2)?,
+                row.get::<_, Option<String>>(3)?,
+                row.get::<_, Option<String>>(4)?,
+            ))
+        })?;
+        let mut entries = Vec::new();
+        for row in rows {
+            let (timestamp_ms, domain, verdict, reason, app) = row?;
+            let verdict = QueryVerdict::from_byte(verdict).ok_or("Unknown query verdict")?;
+            entries.push(QueryLogEntry {
+                timestamp_ms,
+                domain,
+                verdict,
+                reason,
+                app,
+            });
+        }
+        Ok(entries)
+    }
+
+    fn count_queries(&self, filter: &QueryFilter) -> Result<usize, Box<dyn std::error::Error>> {
+        let (clause, values) = query_filter_clause(filter);
+        Ok(self.connection.query_row(
+            &format!("SELECT COUNT(*) FROM query_log{clause}"),
+            params_from_iter(values),
+            |row| row.get(0),
+        )?)
+    }
+
+    fn prune_queries(&mut self, keep: usize) -> Result<usize, Box<dyn std::error::Error>> {
+        // Everything up to the newest entry not kept; nothing if fewer are stored
+        Ok(self.connection.execute(
+            "DELETE FROM query_log WHERE id <=
+             (SELECT id FROM query_log ORDER BY id DESC LIMIT 1 OFFSET ?1)",
+            params![keep],
+        )?)
+    }
+
+    fn clear_queries(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.connection.execute("DELETE FROM query_log", [])?;
+        Ok(())
+    }
+
+    fn save_filter_list(
+        &mut self,
+        position: usize,
+        info: &FilterListInfo,
+        content: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.connection.execute(
+            "INSERT OR REPLACE INTO filter_lists (url, position, info, content)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![info.url, position, serde_json::to_string(info)?, content],
+        )?;
+        Ok(())
+    }
+
+    fn remove_filter_list(&mut self, url: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        let removed = self
+            .connection
+            .execute("DELETE FROM filter_lists WHERE url = ?1", params![url])?;
+        Ok(removed > 0)
+    }
+
+    fn load_filter_lists(
+        &self,
+    ) -> Result<Vec<(FilterListInfo, String)>, Box<dyn std::error::Error>> {
+        let mut select = self
+            .connection
+            .prepare("SELECT info, content FROM filter_lists ORDER BY position, url")?;
+        let rows = select.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?;
+        let mut lists = Vec::new();
+        for row in rows {
+            let (info, content) = row?;
+            lists.push((serde_json::from_str(&info)?, content));
+        }
+        Ok(lists)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(timestamp_ms: u64, domain: &str, verdict: QueryVerdict) -> QueryLogEntry {
+        QueryLogEntry {
+            timestamp_ms,
+            domain: domain.to_string(),
+            verdict,
+            reason: None,
+            app: None,
+        }
+    }
+
+    #[test]
+    fn test_migrations_are_applied_once() {
+        let dir = std::env::temp_dir().join(format!("adblock-sqlite-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("adblock.db");
+
+        let mut storage = SqliteStorage::open(&path).unwrap();
+        assert_eq!(storage.schema_version().unwrap(), MIGRATIONS.len());
+        storage
+            .append_query(&entry(1, "ads.example", QueryVerdict::Blocked))
+            .unwrap();
+        drop(storage);
+
+        // Reopening keeps the data and does not rerun migrations
+        let storage = SqliteStorage::open(&path).unwrap();
+        assert_eq!(storage.count_queries(&QueryFilter::default()).unwrap(), 1);
+        drop(storage);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_query_search_and_prune() {
+        let mut storage = SqliteStorage::open_in_memory().unwrap();
+        for i in 0..10 {
+            let verdict = if i % 2 == 0 {
+                QueryVerdict::Blocked
+            } else {
+                QueryVerdict::Allowed
+            };
+            storage
+                .append_query(&entry(i, &format!("d{i}.Example.com"), verdict))
+                .unwrap();
+        }
+        let blocked = QueryFilter {
+            verdict: Some(QueryVerdict::Blocked),
+            since_ms: Some(2),
+            ..QueryFilter::default()
+        };
+        assert_eq!(storage.count_queries(&blocked).unwrap(), 4);
+        let page = storage.search_queries(&blocked, 1, 2).unwrap();
+        let domains: Vec<_> = page.iter().map(|entry| entry.domain.as_str()).collect();
+        assert_eq!(domains, ["d6.Example.com", "d4.Example.com"]);
+
+        let domain = QueryFilter {
+            domain: Some("D3.example".to_string()),
+            ..QueryFilter::default()
+        };
+        assert_eq!(storage.count_queries(&domain).unwrap(), 1);
+
+        assert_eq!(storage.prune_queries(3).unwrap(), 7);
+        assert_eq!(storage.prune_queries(3).unwrap(), 0);
+        let newest = storage
+            .search_queries(&QueryFilter::default(), 0, usize::MAX)
+            .unwrap();
+        assert_eq!(newest.len(), 3);
+        assert_eq!(newest[0].domain, "d9.Example.com");
+    }
+}
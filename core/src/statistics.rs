@@ -1,6 +1,11 @@
 //! Statistics tracking for ad blocking
 
-use std::collections::HashMap;
+use crate::category_filter::ContentCategory;
+use crate::tracker_category::{self, TrackerCategory};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 use std::time::SystemTime;
 
 /// A single block/allow event
@@ -10,39 +15,111 @@ pub struct BlockEvent {
     pub domain: String,
     pub blocked: bool,
     pub size: u64,
+    #[serde(default)]
+    pub category: TrackerCategory,
 }
 
 /// Domain-specific statistics
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Record))]
 pub struct DomainStats {
     pub domain: String,
     pub count: u64,
     pub data_saved: u64,
+    #[serde(default)]
+    pub category: TrackerCategory,
 }
 
+impl DomainStats {
+    /// Domain rendered for display in dashboards (punycode decoded when safe)
+    pub fn display_name(&self, locale: &str) -> String {
+        crate::idn::to_display(&self.domain, locale)
+    }
+}
+
+/// How much of the browsing trail statistics keep
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StatisticsPrivacy {
+    /// Domains are stored as seen
+    #[default]
+    Full,
+    /// Domains are stored as salted hashes, so top-N counts still work
+    DomainsHashed,
+    /// Only totals are kept: no per-domain counts and no recent events
+    CountsOnly,
+}
+
+/// Prefix marking a hashed domain
+const HASHED_PREFIX: char = '#';
+
 /// Configuration for statistics tracking
 #[derive(Debug, Clone)]
 pub struct StatisticsConfig {
     /// Maximum number of recent events to keep
     pub max_recent_events: usize,
+    /// Privacy level enforced when recording and exporting
+    pub privacy: StatisticsPrivacy,
+    /// Salt of hashed domains; random per instance by default
+    pub hash_salt: String,
 }
 
 impl Default for StatisticsConfig {
     fn default() -> Self {
         Self {
             max_recent_events: 1000,
+            privacy: StatisticsPrivacy::default(),
+            hash_salt: uuid::Uuid::new_v4().simple().to_string(),
+        }
+    }
+}
+
+/// Totals since the current session started
+///
+/// Sessions are not persisted: a new one starts with each `Statistics` and
+/// on [`Statistics::start_session`], e.g. when the VPN connects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct SessionStats {
+    pub started: SystemTime,
+    pub blocked_count: u64,
+    pub allowed_count: u64,
+    pub paused_count: u64,
+    pub data_saved: u64,
+}
+
+impl Default for SessionStats {
+    fn default() -> Self {
+        Self {
+            started: SystemTime::now(),
+            blocked_count: 0,
+            allowed_count: 0,
+            paused_count: 0,
+            data_saved: 0,
         }
     }
 }
 
 /// Statistics tracker for the ad blocker
+///
+/// Counts are lifetime totals, which backups carry across restarts; the
+/// current session is tracked alongside them in [`SessionStats`].
 #[derive(Debug, Clone, Default)]
 pub struct Statistics {
     blocked_count: u64,
     allowed_count: u64,
+    /// Allowed requests that were let through because blocking was paused
+    paused_count: u64,
+    /// Blocked requests that matched a malware/phishing protection list
+    malware_count: u64,
     data_saved: u64,
-    domain_stats: HashMap<String, DomainStatsInternal>,
-    recent_events: Vec<BlockEvent>,
+    domain_stats: DomainTable,
+    /// Blocked requests per tracker category
+    category_counts: BTreeMap<TrackerCategory, u64>,
+    /// Requests blocked by content category blocking, per category
+    content_category_counts: BTreeMap<ContentCategory, u64>,
+    /// Oldest first, at most `config.max_recent_events` long
+    recent_events: VecDeque<BlockEvent>,
+    session: SessionStats,
     config: StatisticsConfig,
 }
 
@@ -51,6 +128,74 @@ pub struct Statistics {
 struct DomainStatsInternal {
     count: u64,
     data_saved: u64,
+    category: TrackerCategory,
+}
+
+/// Position of a domain in the ranking: most blocked first, then most
+/// data saved
+type RankKey = (Reverse<u64>, Reverse<u64>, Arc<str>);
+
+/// Per-domain stats with a ranking kept sorted as they are recorded
+///
+/// Top-N queries walk the ranking instead of sorting every domain.
+#[derive(Debug, Default, Clone)]
+struct DomainTable {
+    stats: HashMap<Arc<str>, DomainStatsInternal>,
+    ranking: BTreeSet<RankKey>,
+}
+
+impl DomainTable {
+    /// Add blocked requests to a stored domain's stats
+    fn add(&mut self, domain: &str, count: u64, data_saved: u64, category: TrackerCategory) {
+        let key = match self.stats.get_key_value(domain) {
+            Some((key, _)) => Arc::clone(key),
+            None => Arc::from(domain),
+        };
+        let stats = self.stats.entry(Arc::clone(&key)).or_default();
+        self.ranking.remove(&(
+            Reverse(stats.count),
+            Reverse(stats.data_saved),
+            Arc::clone(&key),
+        ));
+        stats.count += count;
+        stats.data_saved += data_saved;
+        stats.category = category;
+        self.ranking
+            .insert((Reverse(stats.count), Reverse(stats.data_saved), key));
+    }
+
+    fn get(&self, domain: &str) -> Option<DomainStats> {
+        self.stats
+            .get_key_value(domain)
+            .map(|(domain, stats)| stats.to_public(domain))
+    }
+
+    /// Domains in ranking order
+    fn ranked(&self) -> impl Iterator<Item = (&RankKey, DomainStats)> {
+        self.ranking
+            .iter()
+            .map(|key| (key, self.stats[&key.2].to_public(&key.2)))
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (&str, &DomainStatsInternal)> {
+        self.stats.iter().map(|(domain, stats)| (&**domain, stats))
+    }
+
+    fn clear(&mut self) {
+        self.stats.clear();
+        self.ranking.clear();
+    }
+}
+
+impl DomainStatsInternal {
+    fn to_public(&self, domain: &str) -> DomainStats {
+        DomainStats {
+            domain: domain.to_string(),
+            count: self.count,
+            data_saved: self.data_saved,
+            category: self.category,
+        }
+    }
 }
 
 impl Statistics {
@@ -77,6 +222,16 @@ impl Statistics {
         self.allowed_count
     }
 
+    /// Get the number of requests allowed while blocking was paused
+    pub fn get_paused_count(&self) -> u64 {
+        self.paused_count
+    }
+
+    /// Get the number of requests blocked as malware or phishing
+    pub fn get_malware_count(&self) -> u64 {
+        self.malware_count
+    }
+
     /// Get data saved
     pub fn get_data_saved(&self) -> u64 {
         self.data_saved
@@ -84,44 +239,149 @@ impl Statistics {
 
     /// Record a blocked request
     pub fn record_blocked(&mut self, domain: &str, size: u64) {
+        // Categorize before the domain is hashed
+        let category = tracker_category::current().category_of(domain);
+        self.record_block(domain, size, category);
+    }
+
+    /// Record a request blocked by a malware/phishing protection list
+    ///
+    /// It counts as blocked too, in the malware category.
+    pub fn record_malware(&mut self, domain: &str, size: u64) {
+        self.malware_count += 1;
+        self.record_block(domain, size, TrackerCategory::Malware);
+    }
+
+    /// Record a request blocked because its site is in a blocked content
+    /// category
+    ///
+    /// It counts as blocked too.
+    pub fn record_category_blocked(&mut self, domain: &str, size: u64, category: ContentCategory) {
+        *self.content_category_counts.entry(category).or_default() += 1;
+        self.record_blocked(domain, size);
+    }
+
+    fn record_block(&mut self, domain: &str, size: u64, category: TrackerCategory) {
         self.blocked_count += 1;
         self.data_saved += size;
+        self.session.blocked_count += 1;
+        self.session.data_saved += size;
+
+        *self.category_counts.entry(category).or_default() += 1;
+
+        let Some(domain) = self.stored_domain(domain) else {
+            return;
+        };
 
-        // Update domain stats
-        let stats = self.domain_stats.entry(domain.to_string()).or_default();
-        stats.count += 1;
-        stats.data_saved += size;
+        self.domain_stats.add(&domain, 1, size, category);
 
         // Add to recent events
         self.add_event(BlockEvent {
             timestamp: SystemTime::now(),
-            domain: domain.to_string(),
+            domain,
             blocked: true,
             size,
+            category,
         });
     }
 
     /// Record an allowed request
     pub fn record_allowed(&mut self, domain: &str, size: u64) {
         self.allowed_count += 1;
+        self.session.allowed_count += 1;
+
+        let category = tracker_category::current().category_of(domain);
+        let Some(domain) = self.stored_domain(domain) else {
+            return;
+        };
 
         // Add to recent events
         self.add_event(BlockEvent {
             timestamp: SystemTime::now(),
-            domain: domain.to_string(),
+            domain,
             blocked: false,
             size,
+            category,
         });
     }
 
+    /// Privacy level in effect
+    pub fn privacy(&self) -> StatisticsPrivacy {
+        self.config.privacy
+    }
+
+    /// Change the privacy level, applying it to what is already recorded
+    ///
+    /// Stored domains are hashed or dropped so no plain browsing trail is
+    /// left behind. Hashed domains can't be restored by going back to
+    /// `Full`.
+    pub fn set_privacy(&mut self, privacy: StatisticsPrivacy) {
+        self.config.privacy = privacy;
+        if privacy == StatisticsPrivacy::Full {
+            return;
+        }
+        let domain_stats = std::mem::take(&mut self.domain_stats);
+        for (domain, stats) in domain_stats.iter() {
+            if let Some(domain) = self.stored_domain(domain) {
+                self.domain_stats
+                    .add(&domain, stats.count, stats.data_saved, stats.category);
+            }
+        }
+        let mut events = std::mem::take(&mut self.recent_events);
+        events.retain_mut(|event| match self.stored_domain(&event.domain) {
+            Some(domain) => {
+                event.domain = domain;
+                true
+            }
+            None => false,
+        });
+        self.recent_events = events;
+    }
+
+    /// Domain as stored under the privacy level, or `None` if not stored
+    pub fn stored_domain(&self, domain: &str) -> Option<String> {
+        stored_domain(&self.config, domain)
+    }
+
+    /// Record a request allowed because blocking was paused
+    pub fn record_paused(&mut self, domain: &str, size: u64) {
+        self.paused_count += 1;
+        self.session.paused_count += 1;
+        self.record_allowed(domain, size);
+    }
+
+    /// Totals of the current session
+    pub fn session(&self) -> SessionStats {
+        self.session
+    }
+
+    /// Start a new session, keeping the lifetime totals
+    pub fn start_session(&mut self) {
+        self.session = SessionStats::default();
+    }
+
     /// Add an event to recent events, maintaining size limit
     fn add_event(&mut self, event: BlockEvent) {
-        self.recent_events.push(event);
-
+        if self.config.max_recent_events == 0 {
+            return;
+        }
         // Keep only the configured maximum number of events
-        if self.recent_events.len() > self.config.max_recent_events {
-            self.recent_events.remove(0);
+        if self.recent_events.len() >= self.config.max_recent_events {
+            self.recent_events.pop_front();
         }
+        self.recent_events.push_back(event);
+    }
+
+    /// Maximum number of recent events kept
+    pub fn max_recent_events(&self) -> usize {
+        self.config.max_recent_events
+    }
+
+    /// Change how many recent events are kept, dropping the oldest if needed
+    pub fn set_max_recent_events(&mut self, capacity: usize) {
+        self.config.max_recent_events = capacity;
+        let excess = self.recent_events.len().saturating_sub(capacity);
+        self.recent_events.drain(..excess);
     }
 
     /// Get total blocked requests
@@ -141,31 +401,67 @@ impl Statistics {
 
     /// Get top blocked domains
     pub fn top_blocked_domains(&self, limit: usize) -> Vec<DomainStats> {
-        let mut domains: Vec<_> = self
-            .domain_stats
-            .iter()
-            .map(|(domain, stats)| DomainStats {
-                domain: domain.clone(),
-                count: stats.count,
-                data_saved: stats.data_saved,
-            })
-            .collect();
-
-        // Sort by count (descending), then by data saved as tiebreaker
-        domains.sort_by(|a, b| {
-            b.count
-                .cmp(&a.count)
-                .then_with(|| b.data_saved.cmp(&a.data_saved))
-        });
+        self.top_blocked(0, limit)
+    }
+
+    /// Page of the blocked domains, most blocked first
+    ///
+    /// Ties are broken by data saved, then by domain name.
+    pub fn top_blocked(&self, offset: usize, limit: usize) -> Vec<DomainStats> {
+        self.domain_stats
+            .ranked()
+            .skip(offset)
+            .take(limit)
+            .map(|(_, stats)| stats)
+            .collect()
+    }
+
+    /// Blocked requests to a host, or `None` if none were recorded
+    ///
+    /// The host is matched exactly, after hashing under `DomainsHashed`.
+    pub fn domain_stats(&self, domain: &str) -> Option<DomainStats> {
+        self.domain_stats.get(&self.stored_domain(domain)?)
+    }
 
-        domains.truncate(limit);
-        domains
+    /// Blocked requests per tracker category, for the dashboard pie chart
+    pub fn category_breakdown(&self) -> BTreeMap<TrackerCategory, u64> {
+        self.category_counts.clone()
+    }
+
+    /// Requests blocked per content category
+    pub fn content_category_breakdown(&self) -> BTreeMap<ContentCategory, u64> {
+        self.content_category_counts.clone()
     }
 
     /// Get recent events
     pub fn recent_events(&self, limit: usize) -> Vec<BlockEvent> {
-        let start = self.recent_events.len().saturating_sub(limit);
-        self.recent_events[start..].iter().rev().cloned().collect()
+        self.iter_recent_events().take(limit).cloned().collect()
+    }
+
+    /// Iterate over recent events, newest first, without copying them
+    pub fn iter_recent_events(
+        &self,
+    ) -> impl DoubleEndedIterator<Item = &BlockEvent> + ExactSizeIterator {
+        self.recent_events.iter().rev()
+    }
+
+    /// Drop recent events older than `max_age`, returning how many were removed
+    pub fn prune_events(&mut self, max_age: std::time::Duration) -> usize {
+        let before = self.recent_events.len();
+        let now = SystemTime::now();
+        self.recent_events.retain(|event| {
+            now.duration_since(event.timestamp)
+                .map(|age| age <= max_age)
+                .unwrap_or(true)
+        });
+        before - self.recent_events.len()
+    }
+
+    /// Drop the older half of the recent events to free memory
+    pub fn handle_memory_pressure(&mut self) -> usize {
+        let removed = self.recent_events.len() / 2;
+        self.recent_events.drain(..removed);
+        removed
     }
 
     /// Calculate block rate (0.0 - 1.0)
@@ -178,26 +474,106 @@ impl Statistics {
         }
     }
 
+    /// Replace the totals and per-domain counts, e.g. from a backup
+    ///
+    /// Recent events are cleared since a backup does not hold them, and the
+    /// category breakdown is rebuilt from the restored domains.
+    pub fn restore(
+        &mut self,
+        blocked_count: u64,
+        allowed_count: u64,
+        data_saved: u64,
+        domains: Vec<DomainStats>,
+    ) {
+        let session = self.session;
+        self.reset();
+        self.session = session;
+        self.blocked_count = blocked_count;
+        self.allowed_count = allowed_count;
+        self.data_saved = data_saved;
+        let categories = tracker_category::current();
+        for domain in domains {
+            let category = match domain.category {
+                TrackerCategory::Other => categories.category_of(&domain.domain),
+                category => category,
+            };
+            *self.category_counts.entry(category).or_default() += domain.count;
+            let Some(key) = self.stored_domain(&domain.domain) else {
+                continue;
+            };
+            self.domain_stats
+                .add(&key, domain.count, domain.data_saved, category);
+        }
+    }
+
+    /// Persist the totals and per-domain counts to a storage backend
+    pub fn save_to(
+        &self,
+        storage: &mut dyn crate::storage::Storage,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        storage.save_statistics(&crate::storage::StoredStatistics {
+            blocked_count: self.blocked_count,
+            allowed_count: self.allowed_count,
+            data_saved: self.data_saved,
+            domains: self.top_blocked_domains(usize::MAX),
+        })
+    }
+
+    /// Restore the totals and per-domain counts from a storage backend,
+    /// returning whether any were stored
+    pub fn load_from(
+        &mut self,
+        storage: &dyn crate::storage::Storage,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        let Some(stored) = storage.load_statistics()? else {
+            return Ok(false);
+        };
+        self.restore(
+            stored.blocked_count,
+            stored.allowed_count,
+            stored.data_saved,
+            stored.domains,
+        );
+        Ok(true)
+    }
+
     /// Reset all statistics
     pub fn reset(&mut self) {
         self.blocked_count = 0;
         self.allowed_count = 0;
+        self.paused_count = 0;
+        self.malware_count = 0;
         self.data_saved = 0;
         self.domain_stats.clear();
+        self.category_counts.clear();
+        self.content_category_counts.clear();
         self.recent_events.clear();
+        self.session = SessionStats::default();
     }
 
     /// Export statistics to JSON
     pub fn export_json(&self) -> Result<String, Box<dyn std::error::Error>> {
         let export_data = serde_json::json!({
             "export_date": format!("{:?}", SystemTime::now()),
+            "privacy": self.config.privacy,
             "summary": {
                 "blocked_count": self.blocked_count,
                 "allowed_count": self.allowed_count,
                 "total_count": self.blocked_count + self.allowed_count,
+                "paused_count": self.paused_count,
+                "malware_count": self.malware_count,
                 "block_rate": format!("{:.2}%", self.block_rate() * 100.0),
                 "data_saved_mb": format!("{:.2}", self.data_saved as f64 / 1024.0 / 1024.0),
             },
+            "session": {
+                "blocked_count": self.session.blocked_count,
+                "allowed_count": self.session.allowed_count,
+                "paused_count": self.session.paused_count,
+                "data_saved_mb": format!("{:.2}", self.session.data_saved as f64 / 1024.0 / 1024.0),
+                "duration_secs": self.session.started.elapsed().map_or(0, |age| age.as_secs()),
+            },
+            "categories": self.category_counts,
+            "content_categories": self.content_category_counts,
             "top_blocked_domains": self.top_blocked_domains(10),
             "recent_blocks": self.recent_events(20).iter()
                 .filter(|e| e.blocked)
@@ -243,3 +619,407 @@ impl Statistics {
         Ok(csv)
     }
 }
+
+/// Domain as stored under a config's privacy level
+fn stored_domain(config: &StatisticsConfig, domain: &str) -> Option<String> {
+    match config.privacy {
+        StatisticsPrivacy::Full => Some(domain.to_string()),
+        StatisticsPrivacy::DomainsHashed if domain.starts_with(HASHED_PREFIX) => {
+            Some(domain.to_string())
+        }
+        StatisticsPrivacy::DomainsHashed => {
+            use sha2::{Digest, Sha256};
+
+            let digest = Sha256::new()
+                .chain_update(config.hash_salt.as_bytes())
+                .chain_update(domain.as_bytes())
+                .finalize();
+            Some(format!("{HASHED_PREFIX}{}", hex::encode(&digest[..8])))
+        }
+        StatisticsPrivacy::CountsOnly => None,
+    }
+}
+
+/// Number of shards of [`SharedStatistics`]
+const SHARDS: usize = 16;
+
+/// Lifetime and session counters of [`SharedStatistics`]
+#[derive(Debug, Default)]
+struct Counters {
+    blocked: AtomicU64,
+    allowed: AtomicU64,
+    paused: AtomicU64,
+    malware: AtomicU64,
+    data_saved: AtomicU64,
+    session_blocked: AtomicU64,
+    session_allowed: AtomicU64,
+    session_paused: AtomicU64,
+    session_data_saved: AtomicU64,
+}
+
+/// Part of the per-domain stats, category counts and recent events
+#[derive(Debug, Default)]
+struct Shard {
+    domain_stats: DomainTable,
+    category_counts: BTreeMap<TrackerCategory, u64>,
+    content_category_counts: BTreeMap<ContentCategory, u64>,
+    /// Events with their sequence number, oldest first
+    recent_events: VecDeque<(u64, BlockEvent)>,
+}
+
+/// Statistics recorded from many threads without a global lock
+///
+/// Counters are atomics, while per-domain stats, category counts and recent
+/// events are spread over shards, so checks of different domains don't wait
+/// for each other. Readers get a [`Statistics`] from [`Self::snapshot`];
+/// changes to the whole state go through [`Self::update`].
+#[derive(Debug)]
+pub struct SharedStatistics {
+    /// Read while recording, written by `update`
+    config: parking_lot::RwLock<StatisticsConfig>,
+    counters: Counters,
+    session_started: parking_lot::Mutex<SystemTime>,
+    /// Domain stats are in the shard of their stored domain; events are
+    /// dealt round-robin by sequence number
+    shards: Vec<parking_lot::Mutex<Shard>>,
+    next_event: AtomicU64,
+}
+
+impl Default for SharedStatistics {
+    fn default() -> Self {
+        Self::with_config(StatisticsConfig::default())
+    }
+}
+
+impl SharedStatistics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_config(config: StatisticsConfig) -> Self {
+        Self {
+            config: parking_lot::RwLock::new(config),
+            counters: Counters::default(),
+            session_started: parking_lot::Mutex::new(SystemTime::now()),
+            shards: (0..SHARDS).map(|_| Default::default()).collect(),
+            next_event: AtomicU64::new(0),
+        }
+    }
+
+    /// Record a blocked request
+    pub fn record_blocked(&self, domain: &str, size: u64) {
+        let category = tracker_category::current().category_of(domain);
+        self.record_block(domain, size, category, None);
+    }
+
+    /// Record a request blocked by a malware/phishing protection list
+    pub fn record_malware(&self, domain: &str, size: u64) {
+        self.counters.malware.fetch_add(1, Ordering::Relaxed);
+        self.record_block(domain, size, TrackerCategory::Malware, None);
+    }
+
+    /// Record a request blocked because its site is in a blocked content
+    /// category
+    pub fn record_category_blocked(&self, domain: &str, size: u64, category: ContentCategory) {
+        let tracker_category = tracker_category::current().category_of(domain);
+        self.record_block(domain, size, tracker_category, Some(category));
+    }
+
+    fn record_block(
+        &self,
+        domain: &str,
+        size: u64,
+        category: TrackerCategory,
+        content_category: Option<ContentCategory>,
+    ) {
+        let config = self.config.read();
+        let counters = &self.counters;
+        counters.blocked.fetch_add(1, Ordering::Relaxed);
+        counters.data_saved.fetch_add(size, Ordering::Relaxed);
+        counters.session_blocked.fetch_add(1, Ordering::Relaxed);
+        counters
+            .session_data_saved
+            .fetch_add(size, Ordering::Relaxed);
+
+        let stored = stored_domain(&config, domain);
+        {
+            let mut shard = self.shard_of(stored.as_deref().unwrap_or(domain)).lock();
+            *shard.category_counts.entry(category).or_default() += 1;
+            if let Some(content_category) = content_category {
+                *shard
+                    .content_category_counts
+                    .entry(content_category)
+                    .or_default() += 1;
+            }
+            if let Some(domain) = &stored {
+                shard.domain_stats.add(domain, 1, size, category);
+            }
+        }
+        if let Some(domain) = stored {
+            self.add_event(
+                &config,
+                BlockEvent {
+                    timestamp: SystemTime::now(),
+                    domain,
+                    blocked: true,
+                    size,
+                    category,
+                },
+            );
+        }
+    }
+
+    /// Record an allowed request
+    pub fn record_allowed(&self, domain: &str, size: u64) {
+        let config = self.config.read();
+        self.counters.allowed.fetch_add(1, Ordering::Relaxed);
+        self.counters
+            .session_allowed
+            .fetch_add(1, Ordering::Relaxed);
+
+        let Some(stored) = stored_domain(&config, domain) else {
+            return;
+        };
+        let category = tracker_category::current().category_of(domain);
+        self.add_event(
+            &config,
+            BlockEvent {
+                timestamp: SystemTime::now(),
+                domain: stored,
+                blocked: false,
+                size,
+                category,
+            },
+        );
+    }
+
+    /// Record a request allowed because blocking was paused
+    pub fn record_paused(&self, domain: &str, size: u64) {
+        self.counters.paused.fetch_add(1, Ordering::Relaxed);
+        self.counters.session_paused.fetch_add(1, Ordering::Relaxed);
+        self.record_allowed(domain, size);
+    }
+
+    /// Shard holding a stored domain's stats
+    fn shard_of(&self, domain: &str) -> &parking_lot::Mutex<Shard> {
+        use std::hash::{Hash, Hasher};
+
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        domain.hash(&mut hasher);
+        &self.shards[hasher.finish() as usize % SHARDS]
+    }
+
+    fn add_event(&self, config: &StatisticsConfig, event: BlockEvent) {
+        if config.max_recent_events == 0 {
+            return;
+        }
+        // Round-robin dealing keeps the newest `max_recent_events` overall
+        // within the shards' share of it
+        let capacity = config.max_recent_events.div_ceil(SHARDS);
+        let sequence = self.next_event.fetch_add(1, Ordering::Relaxed);
+        let mut shard = self.shards[sequence as usize % SHARDS].lock();
+        if shard.recent_events.len() >= capacity {
+            shard.recent_events.pop_front();
+        }
+        shard.recent_events.push_back((sequence, event));
+    }
+
+    /// Get blocked count
+    pub fn get_blocked_count(&self) -> u64 {
+        self.counters.blocked.load(Ordering::Relaxed)
+    }
+
+    /// Get allowed count
+    pub fn get_allowed_count(&self) -> u64 {
+        self.counters.allowed.load(Ordering::Relaxed)
+    }
+
+    /// Get paused count
+    pub fn get_paused_count(&self) -> u64 {
+        self.counters.paused.load(Ordering::Relaxed)
+    }
+
+    /// Get malware count
+    pub fn get_malware_count(&self) -> u64 {
+        self.counters.malware.load(Ordering::Relaxed)
+    }
+
+    /// Get data saved in bytes
+    pub fn get_data_saved(&self) -> u64 {
+        self.counters.data_saved.load(Ordering::Relaxed)
+    }
+
+    /// Privacy level in effect
+    pub fn privacy(&self) -> StatisticsPrivacy {
+        self.config.read().privacy
+    }
+
+    /// Domain as stored under the privacy level, or `None` if not stored
+    pub fn stored_domain(&self, domain: &str) -> Option<String> {
+        stored_domain(&self.config.read(), domain)
+    }
+
+    /// Blocked requests to a host, without taking a snapshot
+    pub fn domain_stats(&self, domain: &str) -> Option<DomainStats> {
+        let key = self.stored_domain(domain)?;
+        self.shard_of(&key).lock().domain_stats.get(&key)
+    }
+
+    /// Page of the blocked domains, most blocked first, without taking a
+    /// snapshot
+    ///
+    /// Each shard's ranking is already sorted, so only the first
+    /// `offset + limit` domains of each are merged.
+    pub fn top_blocked(&self, offset: usize, limit: usize) -> Vec<DomainStats> {
+        let wanted = offset.saturating_add(limit);
+        let mut ranked = Vec::new();
+        for shard in &self.shards {
+            let shard = shard.lock();
+            ranked.extend(
+                shard
+                    .domain_stats
+                    .ranked()
+                    .take(wanted)
+                    .map(|(key, stats)| (key.clone(), stats)),
+            );
+        }
+        ranked.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+        ranked
+            .into_iter()
+            .skip(offset)
+            .take(limit)
+            .map(|(_, stats)| stats)
+            .collect()
+    }
+
+    /// Merged copy of the statistics
+    ///
+    /// Requests recorded while it is taken may be partly included.
+    pub fn snapshot(&self) -> Statistics {
+        self.collect(&self.config.read())
+    }
+
+    /// Change the statistics as a whole, e.g. to reset or restore them
+    ///
+    /// Recording waits until `f` returns, so no request is lost.
+    pub fn update<R>(&self, f: impl FnOnce(&mut Statistics) -> R) -> R {
+        let mut config = self.config.write();
+        let mut stats = self.collect(&config);
+        let result = f(&mut stats);
+        *config = stats.config.clone();
+        self.store(stats);
+        result
+    }
+
+    /// Reset all statistics
+    pub fn reset(&self) {
+        self.update(Statistics::reset);
+    }
+
+    /// Start a new session, keeping the lifetime totals
+    pub fn start_session(&self) {
+        self.update(Statistics::start_session);
+    }
+
+    /// Totals of the current session
+    pub fn session(&self) -> SessionStats {
+        let counters = &self.counters;
+        SessionStats {
+            started: *self.session_started.lock(),
+            blocked_count: counters.session_blocked.load(Ordering::Relaxed),
+            allowed_count: counters.session_allowed.load(Ordering::Relaxed),
+            paused_count: counters.session_paused.load(Ordering::Relaxed),
+            data_saved: counters.session_data_saved.load(Ordering::Relaxed),
+        }
+    }
+
+    fn collect(&self, config: &StatisticsConfig) -> Statistics {
+        let counters = &self.counters;
+        let mut stats = Statistics::with_config(config.clone());
+        stats.blocked_count = counters.blocked.load(Ordering::Relaxed);
+        stats.allowed_count = counters.allowed.load(Ordering::Relaxed);
+        stats.paused_count = counters.paused.load(Ordering::Relaxed);
+        stats.malware_count = counters.malware.load(Ordering::Relaxed);
+        stats.data_saved = counters.data_saved.load(Ordering::Relaxed);
+        stats.session = self.session();
+
+        let mut events = Vec::new();
+        for shard in &self.shards {
+            let shard = shard.lock();
+            for (domain, domain_stats) in shard.domain_stats.iter() {
+                stats.domain_stats.add(
+                    domain,
+                    domain_stats.count,
+                    domain_stats.data_saved,
+                    domain_stats.category,
+                );
+            }
+            for (category, count) in &shard.category_counts {
+                *stats.category_counts.entry(*category).or_default() += count;
+            }
+            for (category, count) in &shard.content_category_counts {
+                *stats.content_category_counts.entry(*category).or_default() += count;
+            }
+            events.extend(shard.recent_events.iter().cloned());
+        }
+        events.sort_unstable_by_key(|(sequence, _)| *sequence);
+        let excess = events.len().saturating_sub(config.max_recent_events);
+        stats.recent_events = events.into_iter().skip(excess).map(|(_, e)| e).collect();
+        stats
+    }
+
+    /// Replace the recorded state with `stats`
+    fn store(&self, stats: Statistics) {
+        let counters = &self.counters;
+        counters
+            .blocked
+            .store(stats.blocked_count, Ordering::Relaxed);
+        counters
+            .allowed
+            .store(stats.allowed_count, Ordering::Relaxed);
+        counters.paused.store(stats.paused_count, Ordering::Relaxed);
+        counters
+            .malware
+            .store(stats.malware_count, Ordering::Relaxed);
+        counters
+            .data_saved
+            .store(stats.data_saved, Ordering::Relaxed);
+        let session = stats.session;
+        *self.session_started.lock() = session.started;
+        counters
+            .session_blocked
+            .store(session.blocked_count, Ordering::Relaxed);
+        counters
+            .session_allowed
+            .store(session.allowed_count, Ordering::Relaxed);
+        counters
+            .session_paused
+            .store(session.paused_count, Ordering::Relaxed);
+        counters
+            .session_data_saved
+            .store(session.data_saved, Ordering::Relaxed);
+
+        for shard in &self.shards {
+            *shard.lock() = Shard::default();
+        }
+        for (domain, domain_stats) in stats.domain_stats.iter() {
+            self.shard_of(domain).lock().domain_stats.add(
+                domain,
+                domain_stats.count,
+                domain_stats.data_saved,
+                domain_stats.category,
+            );
+        }
+        let mut first = self.shards[0].lock();
+        first.category_counts = stats.category_counts;
+        first.content_category_counts = stats.content_category_counts;
+        drop(first);
+        for event in stats.recent_events {
+            let sequence = self.next_event.fetch_add(1, Ordering::Relaxed);
+            self.shards[sequence as usize % SHARDS]
+                .lock()
+                .recent_events
+                .push_back((sequence, event));
+        }
+    }
+}
@@ -0,0 +1,66 @@
+//! Persistent storage backends
+//!
+//! Statistics, the query log and the filter list registry can be kept in a
+//! [`Storage`] instead of JSON or log files loaded wholesale into memory.
+//! The `sqlite` feature provides `sqlite_storage::SqliteStorage`.
+
+use crate::filter_registry::FilterListInfo;
+use crate::query_log::{QueryFilter, QueryLogEntry};
+use crate::statistics::DomainStats;
+
+/// Lifetime statistics as persisted
+#[derive(Debug, Clone, Default)]
+pub struct StoredStatistics {
+    pub blocked_count: u64,
+    pub allowed_count: u64,
+    pub data_saved: u64,
+    pub domains: Vec<DomainStats>,
+}
+
+/// Backend persisting statistics, query log entries and filter lists
+pub trait Storage: Send + std::fmt::Debug {
+    /// Replace the stored statistics
+    fn save_statistics(
+        &mut self,
+        statistics: &StoredStatistics,
+    ) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Stored statistics, or `None` if none were saved yet
+    fn load_statistics(&self) -> Result<Option<StoredStatistics>, Box<dyn std::error::Error>>;
+
+    /// Append a query log entry
+    fn append_query(&mut self, entry: &QueryLogEntry) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Entries matching `filter`, newest first, skipping `offset` of them
+    fn search_queries(
+        &self,
+        filter: &QueryFilter,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<QueryLogEntry>, Box<dyn std::error::Error>>;
+
+    /// Number of entries matching `filter`
+    fn count_queries(&self, filter: &QueryFilter) -> Result<usize, Box<dyn std::error::Error>>;
+
+    /// Drop all but the newest `keep` entries, returning how many were dropped
+    fn prune_queries(&mut self, keep: usize) -> Result<usize, Box<dyn std::error::Error>>;
+
+    /// Remove every query log entry
+    fn clear_queries(&mut self) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Add or replace a filter list at a position in subscription order
+    fn save_filter_list(
+        &mut self,
+        position: usize,
+        info: &FilterListInfo,
+        content: &str,
+    ) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Remove a filter list, returning whether it was stored
+    fn remove_filter_list(&mut self, url: &str) -> Result<bool, Box<dyn std::error::Error>>;
+
+    /// Every stored filter list in subscription order
+    fn load_filter_lists(
+        &self,
+    ) -> Result<Vec<(FilterListInfo, String)>, Box<dyn std::error::Error>>;
+}
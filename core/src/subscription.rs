@@ -0,0 +1,196 @@
+//! Filter list subscription links
+//!
+//! Parses the links behind "tap to subscribe" buttons into a list URL and
+//! title: Adblock Plus `abp:subscribe?location=…&title=…` URIs (as used by
+//! filterlists.com), their `https://subscribe.adblockplus.org/?…` web form,
+//! and plain http(s) list URLs. Lists from the built-in catalog are
+//! recognized by URL so the app can enable them by ID instead.
+
+use crate::list_catalog::ListCatalog;
+use serde::Serialize;
+
+/// Longest title accepted, in characters
+pub const MAX_TITLE_LEN: usize = 256;
+
+/// Host of the web form of subscription links
+const WEB_SUBSCRIBE_HOST: &str = "subscribe.adblockplus.org";
+
+/// A list to subscribe to
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Subscription {
+    /// Download URL of the list
+    pub url: String,
+    /// Title from the link, or the catalog title of a known list
+    pub title: Option<String>,
+    /// ID of the catalog list with this URL, see `Config::list_presets`
+    pub preset_id: Option<String>,
+}
+
+/// A rejected subscription link
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SubscriptionError {
+    /// Neither a subscription link nor an http(s) URL
+    UnsupportedLink { link: String },
+    /// The subscription link has no `location` parameter
+    MissingLocation,
+    /// The list location is not an http(s) URL
+    InvalidLocation { location: String },
+    /// The title has control characters or is longer than [`MAX_TITLE_LEN`]
+    InvalidTitle,
+}
+
+impl std::fmt::Display for SubscriptionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SubscriptionError::UnsupportedLink { link } => {
+                write!(f, "Not a subscription link: {link}")
+            }
+            SubscriptionError::MissingLocation => {
+                write!(f, "Subscription link has no list location")
+            }
+            SubscriptionError::InvalidLocation { location } => {
+                write!(f, "Invalid filter list URL: {location}")
+            }
+            SubscriptionError::InvalidTitle => write!(f, "Invalid filter list title"),
+        }
+    }
+}
+
+impl std::error::Error for SubscriptionError {}
+
+/// Parse a subscription link or list URL
+pub fn parse_link(link: &str) -> Result<Subscription, SubscriptionError> {
+    let link = link.trim();
+    let (location, title) = match subscribe_query(link) {
+        Some(query) => {
+            let mut location = None;
+            let mut title = None;
+            for (name, value) in query_params(query) {
+                match name.as_str() {
+                    "location" if location.is_none() => location = Some(value),
+                    "title" if title.is_none() => title = Some(value),
+                    _ => {}
+                }
+            }
+            (location.ok_or(SubscriptionError::MissingLocation)?, title)
+        }
+        None if is_http_url(link) => (link.to_string(), None),
+        None => {
+            return Err(SubscriptionError::UnsupportedLink {
+                link: link.to_string(),
+            })
+        }
+    };
+
+    if !is_http_url(&location) {
+        return Err(SubscriptionError::InvalidLocation { location });
+    }
+    let title = match title.as_deref().map(str::trim) {
+        Some(title) if title.chars().any(char::is_control) => {
+            return Err(SubscriptionError::InvalidTitle)
+        }
+        Some(title) if title.chars().count() > MAX_TITLE_LEN => {
+            return Err(SubscriptionError::InvalidTitle)
+        }
+        Some("") | None => None,
+        Some(title) => Some(title.to_string()),
+    };
+
+    let preset = ListCatalog::builtin().find_by_url(&location);
+    Ok(Subscription {
+        title: title.or_else(|| preset.map(|list| list.title.clone())),
+        preset_id: preset.map(|list| list.id.clone()),
+        url: location,
+    })
+}
+
+/// Query of an `abp:subscribe` link or its web form
+fn subscribe_query(link: &str) -> Option<&str> {
+    let (scheme, rest) = link.split_once(':')?;
+    let rest = match scheme.to_ascii_lowercase().as_str() {
+        "abp" => rest.trim_start_matches("//").strip_prefix("subscribe")?,
+        "http" | "https" => {
+            let rest = rest.strip_prefix("//")?;
+            let host_end = rest.find(['/', '?']).unwrap_or(rest.len());
+            if !rest[..host_end].eq_ignore_ascii_case(WEB_SUBSCRIBE_HOST) {
+                return None;
+            }
+            rest[host_end..].trim_start_matches('/')
+        }
+        _ => return None,
+    };
+    let query = rest.trim_start_matches('/').strip_prefix('?')?;
+    Some(query.split('#').next().unwrap_or(query))
+}
+
+/// Decoded `name=value` pairs of a query string
+fn query_params(query: &str) -> impl Iterator<Item = (String, String)> + '_ {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (decode_component(name), decode_component(value))
+        })
+}
+
+/// Decode a form-encoded query component
+fn decode_component(component: &str) -> String {
+    crate::utils::percent_decode(&component.replace('+', " ")).into_owned()
+}
+
+fn is_http_url(url: &str) -> bool {
+    url.split_once("://").is_some_and(|(scheme, rest)| {
+        (scheme.eq_ignore_ascii_case("http") || scheme.eq_ignore_ascii_case("https"))
+            && !rest.is_empty()
+            && !rest.starts_with(['/', '?', '#'])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_subscribe_links() {
+        let subscription = parse_link(
+            "abp:subscribe?location=https%3A%2F%2Fexample.com%2Flist.txt&title=My+List%21",
+        )
+        .unwrap();
+        assert_eq!(subscription.url, "https://example.com/list.txt");
+        assert_eq!(subscription.title.as_deref(), Some("My List!"));
+        assert_eq!(subscription.preset_id, None);
+
+        let subscription = parse_link(
+            "https://subscribe.adblockplus.org/?location=https%3A%2F%2Feasylist.to%2Feasylist%2Feasylist.txt",
+        )
+        .unwrap();
+        assert_eq!(subscription.preset_id.as_deref(), Some("easylist"));
+        assert!(subscription.title.is_some());
+
+        let subscription = parse_link(" https://example.com/filters.txt ").unwrap();
+        assert_eq!(subscription.url, "https://example.com/filters.txt");
+        assert_eq!(subscription.title, None);
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_links() {
+        assert_eq!(
+            parse_link("abp:subscribe?title=List"),
+            Err(SubscriptionError::MissingLocation)
+        );
+        assert!(matches!(
+            parse_link("abp:subscribe?location=javascript%3Aalert(1)"),
+            Err(SubscriptionError::InvalidLocation { .. })
+        ));
+        assert!(matches!(
+            parse_link("file:///etc/hosts"),
+            Err(SubscriptionError::UnsupportedLink { .. })
+        ));
+        assert_eq!(
+            parse_link("abp:subscribe?location=https%3A%2F%2Fa.example%2Fl.txt&title=a%0Ab"),
+            Err(SubscriptionError::InvalidTitle)
+        );
+    }
+}
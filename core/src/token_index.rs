@@ -0,0 +1,158 @@
+//! Token index for network rules
+//!
+//! Like uBlock Origin's token buckets: each rule is filed under the rarest
+//! token of its pattern, and a URL is only checked against the rules filed
+//! under tokens it contains. A token is a run of `[A-Za-z0-9%]` bounded by
+//! literal separators, so any URL the rule matches contains it as a whole
+//! token. Rules without such a token are checked for every URL.
+
+use std::collections::HashMap;
+
+/// Rule indices grouped by the hash of their token
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct TokenIndex {
+    buckets: HashMap<u64, Vec<u32>>,
+    /// Rules without a usable token
+    untokenized: Vec<u32>,
+}
+
+impl TokenIndex {
+    /// Index `(rule index, pattern)` pairs
+    ///
+    /// Patterns starting with `||` are anchored at a host boundary, so their
+    /// leading token is usable.
+    pub fn build<'a>(rules: impl IntoIterator<Item = (u32, &'a str)>) -> Self {
+        let rules: Vec<(u32, Vec<u64>)> = rules
+            .into_iter()
+            .map(|(index, pattern)| (index, pattern_tokens(pattern)))
+            .collect();
+
+        let mut frequency: HashMap<u64, usize> = HashMap::new();
+        for token in rules.iter().flat_map(|(_, tokens)| tokens) {
+            *frequency.entry(*token).or_default() += 1;
+        }
+
+        let mut index = TokenIndex::default();
+        for (rule, tokens) in rules {
+            match tokens.iter().min_by_key(|token| frequency[token]) {
+                Some(token) => index.buckets.entry(*token).or_default().push(rule),
+                None => index.untokenized.push(rule),
+            }
+        }
+        index
+    }
+
+    /// Indices of the rules that may match `url`, ascending
+    pub fn candidates(&self, url: &str) -> Vec<u32> {
+        let mut candidates = self.untokenized.clone();
+        for token in tokens(url) {
+            if let Some(bucket) = self.buckets.get(&hash_token(token)) {
+                candidates.extend_from_slice(bucket);
+            }
+        }
+        candidates.sort_unstable();
+        candidates.dedup();
+        candidates
+    }
+
+    /// Number of indexed rules
+    pub fn len(&self) -> usize {
+        self.untokenized.len() + self.buckets.values().map(Vec::len).sum::<usize>()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of rules checked for every URL
+    pub fn untokenized_len(&self) -> usize {
+        self.untokenized.len()
+    }
+
+    /// Estimated heap memory in bytes
+    pub fn memory_usage(&self) -> usize {
+        use std::mem::size_of;
+
+        self.buckets.capacity() * size_of::<(u64, Vec<u32>)>()
+            + self
+                .buckets
+                .values()
+                .map(|bucket| bucket.capacity() * size_of::<u32>())
+                .sum::<usize>()
+            + self.untokenized.capacity() * size_of::<u32>()
+    }
+}
+
+fn is_token_char(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'%'
+}
+
+/// Tokens of a URL
+fn tokens(url: &str) -> impl Iterator<Item = &str> {
+    url.split(|c: char| !c.is_ascii() || !is_token_char(c as u8))
+        .filter(|token| !token.is_empty())
+}
+
+/// Hashes of the tokens a URL matching `pattern` must contain
+///
+/// A token touching a `*` or an unanchored end of the pattern may be part
+/// of a longer URL token, so it is left out.
+fn pattern_tokens(pattern: &str) -> Vec<u64> {
+    let (anchored, body) = match pattern.strip_prefix("||") {
+        Some(body) => (true, body),
+        None => (false, pattern),
+    };
+    let bytes = body.as_bytes();
+    let mut hashes = Vec::new();
+    let mut start = 0;
+    while start < bytes.len() {
+        if !is_token_char(bytes[start]) {
+            start += 1;
+            continue;
+        }
+        let end = (start..bytes.len())
+            .find(|&i| !is_token_char(bytes[i]))
+            .unwrap_or(bytes.len());
+        let bounded_before = if start == 0 {
+            anchored
+        } else {
+            bytes[start - 1] != b'*'
+        };
+        let bounded_after = end < bytes.len() && bytes[end] != b'*';
+        if bounded_before && bounded_after {
+            hashes.push(hash_token(&body[start..end]));
+        }
+        start = end;
+    }
+    hashes
+}
+
+/// FNV-1a hash of a token
+fn hash_token(token: &str) -> u64 {
+    token.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rules_filed_under_rarest_token() {
+        assert_eq!(pattern_tokens("||ads.example.com^").len(), 3);
+        // Tokens touching a wildcard or an unanchored end are unusable
+        assert!(pattern_tokens("*banner*").is_empty());
+        assert_eq!(pattern_tokens("banner/ads/"), vec![hash_token("ads")]);
+
+        let index = TokenIndex::build([
+            (0, "||tracker.com/pixel/"),
+            (1, "||ads.com/pixel/"),
+            (2, "*popup*"),
+        ]);
+        assert_eq!(index.len(), 3);
+        assert_eq!(index.untokenized_len(), 1);
+        assert_eq!(index.candidates("https://ads.com/pixel/1"), vec![1, 2]);
+        assert_eq!(index.candidates("https://example.org/"), vec![2]);
+    }
+}
@@ -0,0 +1,164 @@
+//! Tracker categories
+//!
+//! Maps known tracker domains to a category (ads, analytics, social,
+//! malware) so statistics can break blocked requests down for the
+//! dashboard. A small map is embedded; hosts can [`install`] a newer one
+//! from the category JSON feed at runtime.
+
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+/// Map bundled with the crate
+const EMBEDDED_MAP: &str = include_str!("../data/tracker_categories.json");
+
+static CURRENT: Lazy<RwLock<Arc<CategoryMap>>> = Lazy::new(|| {
+    RwLock::new(Arc::new(
+        CategoryMap::parse(EMBEDDED_MAP).expect("embedded tracker categories are valid"),
+    ))
+});
+
+/// Kind of tracker a domain belongs to
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Enum))]
+#[serde(rename_all = "snake_case")]
+pub enum TrackerCategory {
+    Ads,
+    Analytics,
+    Social,
+    Malware,
+    /// Not a known tracker
+    #[default]
+    Other,
+}
+
+/// Category feed: domains listed per category
+#[derive(Debug, Deserialize)]
+struct CategoryFeed {
+    #[serde(default)]
+    version: u32,
+    categories: BTreeMap<TrackerCategory, Vec<String>>,
+}
+
+/// Domains with a known tracker category
+#[derive(Debug, Clone, Default)]
+pub struct CategoryMap {
+    version: u32,
+    domains: HashMap<String, TrackerCategory>,
+}
+
+impl CategoryMap {
+    /// Parse a category feed
+    ///
+    /// The feed is a JSON object such as
+    /// `{"version": 2, "categories": {"ads": ["doubleclick.net"]}}`.
+    pub fn parse(json: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let feed: CategoryFeed = serde_json::from_str(json)?;
+        let mut domains = HashMap::new();
+        for (category, listed) in feed.categories {
+            for domain in listed {
+                let domain = domain.trim().trim_end_matches('.').to_ascii_lowercase();
+                if !domain.is_empty() {
+                    domains.insert(domain, category);
+                }
+            }
+        }
+        Ok(CategoryMap {
+            version: feed.version,
+            domains,
+        })
+    }
+
+    /// The map bundled with the crate
+    pub fn embedded() -> Self {
+        Self::parse(EMBEDDED_MAP).unwrap_or_default()
+    }
+
+    /// Feed version the map was parsed from
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Number of categorized domains
+    pub fn len(&self) -> usize {
+        self.domains.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.domains.is_empty()
+    }
+
+    /// Category of a host: that of the closest listed parent domain
+    pub fn category_of(&self, host: &str) -> TrackerCategory {
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        let mut candidate = host.as_str();
+        loop {
+            if let Some(&category) = self.domains.get(candidate) {
+                return category;
+            }
+            match candidate.split_once('.') {
+                Some((_, parent)) => candidate = parent,
+                None => return TrackerCategory::Other,
+            }
+        }
+    }
+}
+
+/// Category map in use
+pub fn current() -> Arc<CategoryMap> {
+    Arc::clone(&CURRENT.read())
+}
+
+/// Replace the map in use with a category feed, returning its domain count
+///
+/// Feeds older than the installed map are rejected.
+pub fn install(json: &str) -> Result<usize, Box<dyn std::error::Error>> {
+    let map = CategoryMap::parse(json)?;
+    if map.is_empty() {
+        return Err("Tracker category feed has no domains".into());
+    }
+    let mut current = CURRENT.write();
+    if map.version < current.version {
+        return Err(format!(
+            "Tracker category feed version {} is older than {}",
+            map.version, current.version
+        )
+        .into());
+    }
+    let count = map.len();
+    *current = Arc::new(map);
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_category_of_subdomains() {
+        let map = CategoryMap::embedded();
+        assert!(!map.is_empty());
+        assert_eq!(
+            map.category_of("stats.g.doubleclick.net"),
+            TrackerCategory::Ads
+        );
+        assert_eq!(
+            map.category_of("www.Google-Analytics.com."),
+            TrackerCategory::Analytics
+        );
+        assert_eq!(map.category_of("facebook.net"), TrackerCategory::Other);
+        assert_eq!(map.category_of("example.com"), TrackerCategory::Other);
+    }
+
+    #[test]
+    fn test_parse_rejects_unknown_category() {
+        assert!(CategoryMap::parse(r#"{"categories": {"cookies": ["a.com"]}}"#).is_err());
+        let map = CategoryMap::parse(r#"{"categories": {"social": ["A.com"]}}"#).unwrap();
+        assert_eq!(map.version(), 0);
+        assert_eq!(map.category_of("a.com"), TrackerCategory::Social);
+    }
+}
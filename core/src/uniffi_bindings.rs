@@ -0,0 +1,224 @@
+//! UniFFI bindings
+//!
+//! Generated Kotlin/Swift wrappers for the core types, as an alternative to
+//! the hand-written C API in `ffi` and the JNI glue in `jni`.
+//!
+//! Bindings are generated in library mode from the built cdylib:
+//!
+//! ```text
+//! cargo run --features uniffi-cli --bin uniffi-bindgen -- \
+//!     generate --library target/release/libadblock_core.so --language kotlin --out-dir out
+//! ```
+
+use crate::filter_engine::BlockDecision;
+use crate::statistics::DomainStats;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Errors surfaced to foreign callers
+#[derive(Debug, thiserror::Error, uniffi::Error)]
+#[uniffi(flat_error)]
+pub enum AdBlockError {
+    /// The filter engine could not be created or loaded
+    #[error("Engine error: {0}")]
+    Engine(String),
+    /// A filter list update failed
+    #[error("Update error: {0}")]
+    Update(String),
+    /// The engine lock was poisoned by a panic on another thread
+    #[error("Engine lock poisoned")]
+    Poisoned,
+}
+
+/// Statistics snapshot passed across the FFI boundary
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct StatisticsSnapshot {
+    pub blocked_count: u64,
+    pub allowed_count: u64,
+    pub data_saved: u64,
+    pub block_rate: f64,
+    pub top_blocked_domains: Vec<DomainStats>,
+}
+
+impl From<&crate::Statistics> for StatisticsSnapshot {
+    fn from(stats: &crate::Statistics) -> Self {
+        Self {
+            blocked_count: stats.get_blocked_count(),
+            allowed_count: stats.get_allowed_count(),
+            data_saved: stats.get_data_saved(),
+            block_rate: stats.block_rate(),
+            top_blocked_domains: stats.top_blocked_domains(10),
+        }
+    }
+}
+
+/// Thread-safe wrapper around [`crate::AdBlockCore`]
+///
+/// Every call only reads the core, so none of them wait for each other.
+#[derive(uniffi::Object)]
+pub struct AdBlockCore {
+    core: crate::AdBlockCore,
+}
+
+#[uniffi::export]
+impl AdBlockCore {
+    /// Create an engine with the default configuration
+    #[uniffi::constructor]
+    pub fn new() -> Result<Arc<Self>, AdBlockError> {
+        let core = crate::AdBlockCore::new(crate::Config::default())
+            .map_err(|e| AdBlockError::Engine(e.to_string()))?;
+        Ok(Arc::new(Self { core }))
+    }
+
+    /// Create an engine from a filter list
+    #[uniffi::constructor]
+    pub fn from_filter_list(filter_list: String) -> Result<Arc<Self>, AdBlockError> {
+        let core = crate::AdBlockCore::from_filter_list(&filter_list)
+            .map_err(|e| AdBlockError::Engine(e.to_string()))?;
+        Ok(Arc::new(Self { core }))
+    }
+
+    /// Replace the active rules with the rules from a filter list
+    pub fn load_filter_list(&self, filter_list: String) -> Result<(), AdBlockError> {
+        // Build before swapping so concurrent checks aren't held up
+        let engine = crate::FilterEngine::from_filter_list(&filter_list)
+            .map_err(|e| AdBlockError::Engine(e.to_string()))?;
+        self.core.swap_engine(engine);
+        Ok(())
+    }
+
+    /// Check a URL and record the decision in statistics
+    pub fn check_url(&self, url: String, size: u64) -> Result<BlockDecision, AdBlockError> {
+        Ok(self.core.check_url(&url, size))
+    }
+
+    /// Check a URL without recording statistics
+    pub fn should_block(&self, url: String) -> Result<BlockDecision, AdBlockError> {
+        Ok(self.core.should_block(&url))
+    }
+
+    /// Get a snapshot of the current statistics
+    pub fn get_statistics(&self) -> Result<StatisticsSnapshot, AdBlockError> {
+        Ok(StatisticsSnapshot::from(&self.core.get_statistics()))
+    }
+
+    /// Reset statistics
+    pub fn reset_statistics(&self) -> Result<(), AdBlockError> {
+        self.core.reset_statistics();
+        Ok(())
+    }
+}
+
+/// Host connectivity, implemented in Kotlin/Swift
+#[uniffi::export(callback_interface)]
+pub trait NetworkMonitor: Send + Sync {
+    /// Whether the current connection is metered, e.g. mobile data
+    fn is_network_metered(&self) -> bool;
+}
+
+/// Thread-safe wrapper around [`crate::FilterUpdater`]
+#[derive(uniffi::Object)]
+pub struct FilterUpdater {
+    updater: Mutex<crate::FilterUpdater>,
+}
+
+#[uniffi::export]
+impl FilterUpdater {
+    /// Create an updater for the given URLs
+    #[uniffi::constructor]
+    pub fn new(
+        urls: Vec<String>,
+        update_interval_secs: u64,
+        cache_dir: Option<String>,
+    ) -> Result<Arc<Self>, AdBlockError> {
+        let config = crate::UpdateConfig {
+            urls,
+            presets: Vec::new(),
+            mirrors: std::collections::HashMap::new(),
+            update_interval: Duration::from_secs(update_interval_secs),
+            cache_dir: cache_dir.map(PathBuf::from),
+        };
+        let updater =
+            crate::FilterUpdater::new(config).map_err(|e| AdBlockError::Update(e.to_string()))?;
+        Ok(Arc::new(Self {
+            updater: Mutex::new(updater),
+        }))
+    }
+
+    /// Check if an update is due
+    pub fn needs_update(&self) -> Result<bool, AdBlockError> {
+        let updater = self.updater.lock().map_err(|_| AdBlockError::Poisoned)?;
+        Ok(updater.needs_update())
+    }
+
+    /// Download (or load from cache) and return the merged filter list
+    pub fn auto_update(&self) -> Result<String, AdBlockError> {
+        let mut updater = self.updater.lock().map_err(|_| AdBlockError::Poisoned)?;
+        updater
+            .auto_update()
+            .map_err(|e| AdBlockError::Update(e.to_string()))
+    }
+
+    /// Download every list now, also on a metered connection
+    pub fn force_update(&self) -> Result<String, AdBlockError> {
+        let mut updater = self.updater.lock().map_err(|_| AdBlockError::Poisoned)?;
+        updater
+            .force_update()
+            .map_err(|e| AdBlockError::Update(e.to_string()))
+    }
+
+    /// Defer `auto_update` downloads while `monitor` reports a metered
+    /// connection, unless `allow_metered` is set
+    pub fn set_update_policy(
+        &self,
+        allow_metered: bool,
+        monitor: Option<Box<dyn NetworkMonitor>>,
+    ) -> Result<(), AdBlockError> {
+        let mut updater = self.updater.lock().map_err(|_| AdBlockError::Poisoned)?;
+        let monitor: Option<Arc<dyn NetworkMonitor>> = monitor.map(Arc::from);
+        updater.set_update_policy(crate::UpdatePolicy {
+            allow_metered,
+            is_network_metered: monitor.map(|monitor| {
+                Arc::new(move || monitor.is_network_metered())
+                    as crate::filter_updater::MeteredCheck
+            }),
+        });
+        Ok(())
+    }
+
+    /// Load the cached filter list
+    pub fn load_from_cache(&self) -> Result<String, AdBlockError> {
+        let updater = self.updater.lock().map_err(|_| AdBlockError::Poisoned)?;
+        updater
+            .load_from_cache()
+            .map_err(|e| AdBlockError::Update(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uniffi_core_roundtrip() {
+        let core = AdBlockCore::from_filter_list("||ads.com^".to_string()).unwrap();
+
+        assert!(
+            core.check_url("https://ads.com/banner".to_string(), 100)
+                .unwrap()
+                .should_block
+        );
+        assert!(
+            !core
+                .check_url("https://safe.com".to_string(), 50)
+                .unwrap()
+                .should_block
+        );
+
+        let stats = core.get_statistics().unwrap();
+        assert_eq!(stats.blocked_count, 1);
+        assert_eq!(stats.allowed_count, 1);
+        assert_eq!(stats.data_saved, 100);
+    }
+}
@@ -0,0 +1,271 @@
+//! Background filter list updates
+//!
+//! Runs [`FilterUpdater::auto_update`] on a background thread every
+//! `Config::update_interval` seconds plus a random jitter, so devices
+//! sharing a list server don't all update at once. It wakes earlier when
+//! a list's `! Expires:` header makes it due sooner. Downloads are skipped
+//! while the host reports the network as unavailable or, unless the
+//! updater's policy allows it, metered. Subscribers are told whenever new
+//! rules go live.
+
+use crate::filter_updater::MIN_LIST_EXPIRY;
+use crate::{AdBlockCore, Config, FilterEngine, FilterUpdater};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// Outcome of a scheduled update, sent to subscribers
+#[derive(Debug, Clone, PartialEq)]
+pub enum SchedulerEvent {
+    /// New rules were loaded into the live engine
+    RulesSwapped { rule_count: usize },
+    /// The update was skipped because the network is unavailable
+    SkippedOffline,
+    /// The update was skipped because the connection is metered
+    SkippedMetered,
+    /// Downloading or loading the lists failed
+    UpdateFailed(String),
+}
+
+/// State shared with the background thread
+struct Shared {
+    core: Arc<Mutex<AdBlockCore>>,
+    updater: Mutex<FilterUpdater>,
+    network_available: AtomicBool,
+    /// `true` once stopped
+    stopped: Mutex<bool>,
+    wake: Condvar,
+    subscribers: Mutex<Vec<Sender<SchedulerEvent>>>,
+}
+
+impl Shared {
+    fn notify(&self, event: SchedulerEvent) {
+        if let Ok(mut subscribers) = self.subscribers.lock() {
+            subscribers.retain(|subscriber| subscriber.send(event.clone()).is_ok());
+        }
+    }
+
+    fn run_once(&self, force: bool) -> SchedulerEvent {
+        let metered = || {
+            self.updater
+                .lock()
+                .is_ok_and(|updater| updater.update_policy().defers_downloads())
+        };
+        let event = if !self.network_available.load(Ordering::SeqCst) {
+            SchedulerEvent::SkippedOffline
+        } else if !force && metered() {
+            SchedulerEvent::SkippedMetered
+        } else {
+            match self.update(force) {
+                Ok(rule_count) => SchedulerEvent::RulesSwapped { rule_count },
+                Err(e) => SchedulerEvent::UpdateFailed(e.to_string()),
+            }
+        };
+        self.notify(event.clone());
+        event
+    }
+
+    fn update(&self, force: bool) -> Result<usize, Box<dyn std::error::Error>> {
+        let mut updater = self.updater.lock().map_err(|_| "Updater lock poisoned")?;
+        let content = if force {
+            updater.force_update()?
+        } else {
+            updater.auto_update()?
+        };
+        drop(updater);
+        // Build before taking the core lock so requests aren't held up
+        let engine = FilterEngine::from_filter_list(&content)?;
+        let rule_count = engine.rule_count();
+        self.core
+            .lock()
+            .map_err(|_| "Core lock poisoned")?
+            .swap_engine(engine);
+        Ok(rule_count)
+    }
+
+    /// Delay until the next update: the interval, or less once a list is
+    /// due sooner, but never under `MIN_LIST_EXPIRY` for a list that keeps
+    /// failing
+    fn update_delay(&self, interval: Duration) -> Duration {
+        match self.updater.lock() {
+            Ok(updater) => interval.min(updater.next_update_in().max(MIN_LIST_EXPIRY)),
+            Err(_) => interval,
+        }
+    }
+
+    /// Sleep for `delay` unless stopped or woken first, returning whether stopped
+    fn wait(&self, delay: Duration) -> bool {
+        let Ok(stopped) = self.stopped.lock() else {
+            return true;
+        };
+        match self
+            .wake
+            .wait_timeout_while(stopped, delay, |stopped| !*stopped)
+        {
+            Ok((stopped, _)) => *stopped,
+            Err(_) => true,
+        }
+    }
+}
+
+/// Periodic background updater
+pub struct UpdateScheduler {
+    shared: Arc<Shared>,
+    interval: Duration,
+    max_jitter: Duration,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl UpdateScheduler {
+    /// Create a scheduler updating `core` every `config.update_interval` seconds
+    ///
+    /// The jitter defaults to a tenth of the interval. The updater publishes
+    /// its updates on the core's event bus.
+    pub fn new(core: Arc<Mutex<AdBlockCore>>, mut updater: FilterUpdater, config: &Config) -> Self {
+        if let Ok(core) = core.lock() {
+            updater.set_event_bus(Arc::clone(core.events()));
+        }
+        let interval = Duration::from_secs(config.update_interval);
+        UpdateScheduler {
+            shared: Arc::new(Shared {
+                core,
+                updater: Mutex::new(updater),
+                network_available: AtomicBool::new(true),
+                stopped: Mutex::new(false),
+                wake: Condvar::new(),
+                subscribers: Mutex::new(Vec::new()),
+            }),
+            interval,
+            max_jitter: interval / 10,
+            handle: None,
+        }
+    }
+
+    /// Override the update interval
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+    }
+
+    /// Set the largest random delay added to each interval
+    pub fn set_max_jitter(&mut self, max_jitter: Duration) {
+        self.max_jitter = max_jitter;
+    }
+
+    /// Delay until the next update: the interval plus a random jitter
+    pub fn next_delay(&self) -> Duration {
+        jittered(self.interval, self.max_jitter)
+    }
+
+    /// Report network availability from the host's connectivity callback
+    ///
+    /// Updates are skipped while the network is unavailable.
+    pub fn set_network_available(&self, available: bool) {
+        self.shared
+            .network_available
+            .store(available, Ordering::SeqCst);
+    }
+
+    /// Receive an event for every scheduled update
+    pub fn subscribe(&self) -> Receiver<SchedulerEvent> {
+        let (sender, receiver) = mpsc::channel();
+        if let Ok(mut subscribers) = self.shared.subscribers.lock() {
+            subscribers.push(sender);
+        }
+        receiver
+    }
+
+    /// Run one update now on the calling thread
+    pub fn run_once(&self) -> SchedulerEvent {
+        self.shared.run_once(false)
+    }
+
+    /// Download every list now on the calling thread, also on a metered
+    /// connection, as for the refresh button
+    pub fn force_update(&self) -> SchedulerEvent {
+        self.shared.run_once(true)
+    }
+
+    /// Start the background thread
+    ///
+    /// Does nothing if already running.
+    pub fn start(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.handle.is_some() {
+            return Ok(());
+        }
+        *self
+            .shared
+            .stopped
+            .lock()
+            .map_err(|_| "Scheduler lock poisoned")? = false;
+
+        let shared = Arc::clone(&self.shared);
+        let (interval, max_jitter) = (self.interval, self.max_jitter);
+        let handle = std::thread::Builder::new()
+            .name("adblock-updater".to_string())
+            .spawn(move || {
+                while !shared.wait(jittered(shared.update_delay(interval), max_jitter)) {
+                    shared.run_once(false);
+                }
+            })?;
+        self.handle = Some(handle);
+        Ok(())
+    }
+
+    /// Stop the background thread and wait for it to finish
+    pub fn stop(&mut self) {
+        if let Ok(mut stopped) = self.shared.stopped.lock() {
+            *stopped = true;
+        }
+        self.shared.wake.notify_all();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+
+    /// Whether the background thread is running
+    pub fn is_running(&self) -> bool {
+        self.handle.is_some()
+    }
+}
+
+/// `interval` plus a random delay of at most `max_jitter`
+fn jittered(interval: Duration, max_jitter: Duration) -> Duration {
+    let jitter_ms = max_jitter.as_millis() as u64;
+    let random = uuid::Uuid::new_v4().as_u128() as u64;
+    interval + Duration::from_millis(random % (jitter_ms + 1))
+}
+
+impl Drop for UpdateScheduler {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::UpdateConfig;
+
+    #[test]
+    fn test_jitter_stays_within_bounds() {
+        let core = Arc::new(Mutex::new(AdBlockCore::with_patterns(Vec::new()).unwrap()));
+        let updater = FilterUpdater::new(UpdateConfig {
+            urls: Vec::new(),
+            presets: Vec::new(),
+            mirrors: std::collections::HashMap::new(),
+            update_interval: Duration::from_secs(3600),
+            cache_dir: None,
+        })
+        .unwrap();
+        let mut scheduler = UpdateScheduler::new(core, updater, &Config::default());
+        scheduler.set_interval(Duration::from_secs(60));
+        scheduler.set_max_jitter(Duration::from_secs(5));
+
+        for _ in 0..20 {
+            let delay = scheduler.next_delay();
+            assert!(delay >= Duration::from_secs(60) && delay <= Duration::from_secs(65));
+        }
+    }
+}
@@ -30,6 +30,205 @@ pub fn extract_domain(url: &str) -> String {
     }
 }
 
+/// Components of a normalized URL
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedUrl {
+    /// Lowercase scheme, e.g. `https`
+    pub scheme: String,
+    /// Lowercase ASCII (punycode) host; IPv6 literals keep their brackets
+    pub host: String,
+    /// Port, unless it is the scheme's default
+    pub port: Option<u16>,
+    /// Percent-decoded path, `/` if empty
+    pub path: String,
+    /// Percent-decoded query without the `?`
+    pub query: Option<String>,
+}
+
+impl std::fmt::Display for ParsedUrl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}://{}", self.scheme, self.host)?;
+        if let Some(port) = self.port {
+            write!(f, ":{port}")?;
+        }
+        f.write_str(&self.path)?;
+        if let Some(query) = &self.query {
+            write!(f, "?{query}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Default port of a scheme
+fn default_port(scheme: &str) -> Option<u16> {
+    match scheme {
+        "http" | "ws" => Some(80),
+        "https" | "wss" => Some(443),
+        "ftp" => Some(21),
+        _ => None,
+    }
+}
+
+/// Decode `%XX` escapes, keeping the input if the result isn't UTF-8
+pub(crate) fn percent_decode(input: &str) -> std::borrow::Cow<'_, str> {
+    if !input.contains('%') {
+        return input.into();
+    }
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| input.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    match String::from_utf8(decoded) {
+        Ok(decoded) => decoded.into(),
+        Err(_) => input.into(),
+    }
+}
+
+/// Parse and normalize an absolute URL
+///
+/// The scheme and host are lowercased, Unicode hosts are converted to
+/// punycode, default ports are dropped and the path and query are
+/// percent-decoded, so equivalently-encoded URLs compare equal. Userinfo
+/// and fragments are dropped.
+///
+/// # Examples
+/// ```
+/// use adblock_core::utils::parse_url;
+///
+/// let url = parse_url("HTTPS://Ads.Example.com:443/a%2Fb?x=%31#top").unwrap();
+/// assert_eq!(url.to_string(), "https://ads.example.com/a/b?x=1");
+/// ```
+pub fn parse_url(url: &str) -> Option<ParsedUrl> {
+    let (scheme, rest) = url.trim().split_once("://")?;
+    if scheme.is_empty()
+        || !scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+    {
+        return None;
+    }
+    let scheme = scheme.to_ascii_lowercase();
+
+    let rest = rest.split('#').next().unwrap_or(rest);
+    let authority_end = rest.find(['/', '?']).unwrap_or(rest.len());
+    let (authority, path_and_query) = rest.split_at(authority_end);
+    let authority = authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, host)| host);
+
+    let (host, port) = if authority.starts_with('[') {
+        let end = authority.find(']')? + 1;
+        (&authority[..end], authority[end..].strip_prefix(':'))
+    } else {
+        match authority.rsplit_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (authority, None),
+        }
+    };
+    let port = match port.filter(|port| !port.is_empty()) {
+        Some(port) => Some(port.parse::<u16>().ok()?),
+        None => None,
+    };
+    let host = crate::idn::to_ascii(percent_decode(host).trim_end_matches('.'))?;
+    if host.is_empty() {
+        return None;
+    }
+
+    let (path, query) = match path_and_query.split_once('?') {
+        Some((path, query)) => (path, Some(percent_decode(query).into_owned())),
+        None => (path_and_query, None),
+    };
+    let path = if path.is_empty() {
+        "/".to_string()
+    } else {
+        percent_decode(path).into_owned()
+    };
+
+    Some(ParsedUrl {
+        port: port.filter(|&port| Some(port) != default_port(&scheme)),
+        scheme,
+        host,
+        path,
+        query,
+    })
+}
+
+/// Normalized form of a URL for matching, or the URL itself if it can't
+/// be parsed
+pub fn normalize_url(url: &str) -> std::borrow::Cow<'_, str> {
+    match parse_url(url) {
+        Some(parsed) => parsed.to_string().into(),
+        None => url.into(),
+    }
+}
+
+/// Host of a URL without userinfo or port, lowercased
+fn extract_host(url: &str) -> String {
+    let authority = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let authority = authority.split(['/', '?', '#']).next().unwrap_or("");
+    let host = authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, host)| host);
+    let host = match host.strip_prefix('[') {
+        // Keep the brackets of an IPv6 literal, dropping any port after them
+        Some(ipv6) => &host[..ipv6.find(']').map_or(host.len(), |end| end + 2)],
+        None => host.split(':').next().unwrap_or(host),
+    };
+    host.to_ascii_lowercase()
+}
+
+/// Registrable domain (eTLD+1) of a URL, using the public suffix list
+///
+/// # Examples
+/// ```
+/// use adblock_core::utils::registrable_domain;
+///
+/// assert_eq!(registrable_domain("https://ads.example.co.uk/x").as_deref(), Some("example.co.uk"));
+/// assert_eq!(registrable_domain("https://co.uk/"), None);
+/// ```
+pub fn registrable_domain(url: &str) -> Option<String> {
+    crate::public_suffix::current().registrable_domain(&extract_host(url))
+}
+
+/// Whether two URLs or hosts belong to the same party
+///
+/// Sites are compared by registrable domain, falling back to the host when
+/// there is none (e.g. for a bare public suffix). Different sites owned by
+/// one [entity](crate::entity), such as `facebook.com` and `fbcdn.net`,
+/// are the same party.
+pub fn same_entity(a: &str, b: &str) -> bool {
+    let site = |url: &str| registrable_domain(url).unwrap_or_else(|| extract_host(url));
+    let (a, b) = (site(a), site(b));
+    if a == b {
+        return true;
+    }
+    let entities = crate::entity::current();
+    entities
+        .entity_of(&a)
+        .is_some_and(|entity| entities.entity_of(&b) == Some(entity))
+}
+
+/// Whether a request goes to a different party than the document it is
+/// made from, see [`same_entity`]
+pub fn is_third_party(request_url: &str, document_url: &str) -> bool {
+    !same_entity(request_url, document_url)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -45,4 +244,55 @@ mod tests {
         assert_eq!(extract_domain("example.com/path"), "example.com");
         assert_eq!(extract_domain("example.com"), "example.com");
     }
+
+    #[test]
+    fn test_third_party() {
+        assert_eq!(extract_host("https://user@[::1]:8080/path"), "[::1]");
+        assert_eq!(
+            extract_host("http://CDN.Example.com:81?q"),
+            "cdn.example.com"
+        );
+        assert!(!is_third_party(
+            "https://static.example.co.uk/app.js",
+            "https://www.example.co.uk/"
+        ));
+        assert!(is_third_party(
+            "https://tracker.co.uk/pixel",
+            "https://www.example.co.uk/"
+        ));
+        assert!(is_third_party(
+            "https://alice.github.io/x",
+            "https://bob.github.io/"
+        ));
+        assert!(same_entity("static.xx.fbcdn.net", "www.facebook.com"));
+        assert!(!is_third_party(
+            "https://static.xx.fbcdn.net/rsrc.js",
+            "https://www.facebook.com/"
+        ));
+        assert!(is_third_party(
+            "https://connect.facebook.net/sdk.js",
+            "https://news.example/"
+        ));
+    }
+
+    #[test]
+    fn test_parse_url() {
+        let url = parse_url("http://user:pw@EXAMPLE.com:8080/Path%20One?q=a%26b").unwrap();
+        assert_eq!(url.scheme, "http");
+        assert_eq!(url.host, "example.com");
+        assert_eq!(url.port, Some(8080));
+        assert_eq!(url.path, "/Path One");
+        assert_eq!(url.query.as_deref(), Some("q=a&b"));
+
+        assert_eq!(
+            parse_url("https://реклама.рф").unwrap().to_string(),
+            "https://xn--80aanufhx.xn--p1ai/"
+        );
+        assert_eq!(
+            parse_url("http://[::1]:80/%ff").unwrap().to_string(),
+            "http://[::1]/%ff"
+        );
+        assert!(parse_url("example.com/path").is_none());
+        assert!(parse_url("https://example.com:99999/").is_none());
+    }
 }
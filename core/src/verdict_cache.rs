@@ -0,0 +1,199 @@
+//! Verdict cache keyed by URL and request kind
+//!
+//! Pages and apps request the same trackers, fonts and scripts over and
+//! over. Caching the verdict per normalized URL and [`RequestKind`] for a
+//! short TTL skips the full rule evaluation for the repeats. The key holds
+//! everything a verdict depends on, so a cached verdict is the one a full
+//! evaluation would give until the rules change. Only exact repeats hit:
+//! each URL under an ad path gets an entry of its own.
+//!
+//! Entries keep the rules behind the verdict, so a hit is still counted in
+//! rule hits, metrics and the exception audit.
+
+use crate::filter_engine::BlockDecision;
+use crate::resource_type::RequestKind;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Default time-to-live of a cached verdict
+pub const DEFAULT_VERDICT_TTL: Duration = Duration::from_secs(30);
+
+/// Default maximum number of cached verdicts
+pub const DEFAULT_VERDICT_CAPACITY: usize = 1024;
+
+type CacheKey = (String, RequestKind);
+
+/// Verdict with the rules that decided it
+#[derive(Debug, Clone, PartialEq)]
+pub struct CachedVerdict {
+    pub decision: BlockDecision,
+    /// Matched rule, `@@`-prefixed for exceptions
+    pub rule: Option<String>,
+    /// Block rule an exception overrode, if the exception audit is enabled
+    pub overridden: Option<String>,
+}
+
+/// Cache hit/miss counters
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct VerdictCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+}
+
+/// Short-lived cache of block decisions
+#[derive(Debug)]
+pub struct VerdictCache {
+    ttl: Duration,
+    capacity: usize,
+    entries: parking_lot::Mutex<HashMap<CacheKey, (Instant, CachedVerdict)>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl VerdictCache {
+    /// Create a cache with the given TTL and capacity
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            ttl,
+            capacity: capacity.max(1),
+            entries: parking_lot::Mutex::new(HashMap::new()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Time-to-live of cached verdicts
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Maximum number of cached verdicts
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Look up a cached verdict for a normalized URL requested as `request`
+    pub fn get(&self, url: &str, request: RequestKind) -> Option<CachedVerdict> {
+        let key = (url.to_string(), request);
+        let mut entries = self.entries.lock();
+
+        match entries.get(&key) {
+            Some((stored, verdict)) if stored.elapsed() < self.ttl => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                Some(verdict.clone())
+            }
+            Some(_) => {
+                entries.remove(&key);
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    /// Store the verdict for a normalized URL requested as `request`
+    pub fn insert(&self, url: &str, request: RequestKind, verdict: &CachedVerdict) {
+        let key = (url.to_string(), request);
+        let mut entries = self.entries.lock();
+
+        if entries.len() >= self.capacity && !entries.contains_key(&key) {
+            let ttl = self.ttl;
+            entries.retain(|_, (stored, _)| stored.elapsed() < ttl);
+            if entries.len() >= self.capacity {
+                entries.clear();
+            }
+        }
+
+        entries.insert(key, (Instant::now(), verdict.clone()));
+    }
+
+    /// Drop every cached decision
+    pub fn clear(&self) {
+        self.entries.lock().clear();
+    }
+
+    /// Estimated heap memory held by cached entries in bytes
+    pub fn memory_usage(&self) -> usize {
+        let entry_size = std::mem::size_of::<(CacheKey, (Instant, CachedVerdict))>();
+        let text = |text: &Option<String>| text.as_ref().map_or(0, String::len);
+        self.entries
+            .lock()
+            .iter()
+            .map(|((url, _), (_, verdict))| {
+                entry_size
+                    + url.len()
+                    + text(&verdict.decision.reason)
+                    + text(&verdict.rule)
+                    + text(&verdict.overridden)
+            })
+            .sum()
+    }
+
+    /// Current hit/miss counters
+    pub fn stats(&self) -> VerdictCacheStats {
+        VerdictCacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: self.entries.lock().len(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verdict_cache_keys_on_url_and_request() {
+        use crate::resource_type::ResourceType;
+
+        let cache = VerdictCache::new(DEFAULT_VERDICT_TTL, 2);
+        let image = RequestKind {
+            resource_type: ResourceType::Image,
+            third_party: None,
+        };
+        let blocked = CachedVerdict {
+            decision: BlockDecision {
+                should_block: true,
+                reason: None,
+                redirect_resource: None,
+                kind: Some(crate::filter_engine::BlockKind::Ad),
+                verdict: crate::filter_engine::Verdict::BlockRequest,
+            },
+            rule: Some("||ads.com/banners/".to_string()),
+            overridden: None,
+        };
+
+        cache.insert("https://ads.com/banners/1.png", image, &blocked);
+        assert_eq!(
+            cache.get("https://ads.com/banners/1.png", image),
+            Some(blocked.clone())
+        );
+        assert_eq!(cache.get("https://ads.com/banners/2.png", image), None);
+        let third_party = RequestKind {
+            third_party: Some(true),
+            ..image
+        };
+        assert_eq!(
+            cache.get("https://ads.com/banners/1.png", third_party),
+            None
+        );
+        assert_eq!(
+            cache.stats(),
+            VerdictCacheStats {
+                hits: 1,
+                misses: 2,
+                entries: 1
+            }
+        );
+
+        let expired = VerdictCache::new(Duration::ZERO, 2);
+        expired.insert("https://ads.com/banners/1.png", image, &blocked);
+        assert_eq!(expired.get("https://ads.com/banners/1.png", image), None);
+    }
+}
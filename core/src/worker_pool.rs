@@ -0,0 +1,89 @@
+//! Bounded pool of worker threads
+//!
+//! Servers hand blocking work such as upstream round trips and client
+//! connections to a [`WorkerPool`] instead of a thread each. A fixed number
+//! of threads run the jobs; a bounded queue holds the ones waiting, and jobs
+//! beyond that are rejected so a flood of requests can't exhaust threads or
+//! memory.
+
+use std::io;
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Fixed set of threads running queued jobs
+///
+/// Dropping the pool doesn't wait: jobs already queued still run, then the
+/// threads exit.
+pub struct WorkerPool {
+    sender: SyncSender<Job>,
+}
+
+impl WorkerPool {
+    /// Start `workers` threads named `name` with room for `queue` waiting jobs
+    pub fn new(name: &str, workers: usize, queue: usize) -> io::Result<Self> {
+        let (sender, receiver) = mpsc::sync_channel::<Job>(queue);
+        let receiver = Arc::new(Mutex::new(receiver));
+        for _ in 0..workers.max(1) {
+            let receiver = Arc::clone(&receiver);
+            std::thread::Builder::new()
+                .name(name.to_string())
+                .spawn(move || run_jobs(&receiver))?;
+        }
+        Ok(Self { sender })
+    }
+
+    /// Queue a job, returning `false` if the queue is full
+    pub fn try_execute(&self, job: impl FnOnce() + Send + 'static) -> bool {
+        self.sender.try_send(Box::new(job)).is_ok()
+    }
+}
+
+fn run_jobs(receiver: &Mutex<Receiver<Job>>) {
+    loop {
+        let job = match receiver.lock() {
+            Ok(receiver) => receiver.recv(),
+            Err(_) => return,
+        };
+        match job {
+            Ok(job) => job(),
+            Err(_) => return,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Barrier;
+    use std::time::Duration;
+
+    #[test]
+    fn test_jobs_beyond_the_queue_are_rejected() {
+        let (done, ran) = mpsc::channel();
+        let gate = Arc::new(Barrier::new(2));
+        let pool = WorkerPool::new("test-worker", 1, 1).unwrap();
+
+        // Keep the only worker busy while queueing
+        let worker_gate = Arc::clone(&gate);
+        assert!(pool.try_execute(move || {
+            worker_gate.wait();
+            worker_gate.wait();
+        }));
+        gate.wait();
+
+        let accepted = (0..3)
+            .filter(|&job| {
+                let done = done.clone();
+                pool.try_execute(move || done.send(job).unwrap())
+            })
+            .count();
+        gate.wait();
+
+        // Queued jobs still run after the pool is dropped
+        drop(pool);
+        assert_eq!(accepted, 1);
+        assert_eq!(ran.recv_timeout(Duration::from_secs(5)), Ok(0));
+    }
+}
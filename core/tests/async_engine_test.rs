@@ -0,0 +1,130 @@
+//! Async Engine Tests
+//!
+//! Check URLs from concurrent tokio tasks through the async facade
+
+#![cfg(feature = "async")]
+
+use adblock_core::async_engine::AsyncAdBlock;
+use adblock_core::{FilterEngine, SharedStatistics};
+use std::sync::Arc;
+
+fn runtime() -> tokio::runtime::Runtime {
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn should_batch_statistics_of_concurrent_checks() {
+    runtime().block_on(async {
+        // Given: A facade over an engine blocking ads.example
+        let engine = FilterEngine::from_filter_list("||ads.example^").unwrap();
+        let statistics = Arc::new(SharedStatistics::new());
+        let adblock = Arc::new(AsyncAdBlock::new(Arc::new(engine), Arc::clone(&statistics)));
+
+        // When: Checking URLs from many tasks
+        let tasks: Vec<_> = (0..50)
+            .map(|i| {
+                let adblock = Arc::clone(&adblock);
+                tokio::spawn(async move {
+                    let url = match i % 2 {
+                        0 => "https://ads.example/banner.js",
+                        _ => "https://example.com/app.js",
+                    };
+                    adblock.check_url(url).should_block
+                })
+            })
+            .collect();
+        let mut blocked = 0;
+        for task in tasks {
+            blocked += task.await.unwrap() as u64;
+        }
+        adblock.flush().await;
+
+        // Then: Every check is counted once the updates are flushed
+        let stats = statistics.snapshot();
+        assert_eq!(blocked, 25);
+        assert_eq!(stats.get_blocked_count(), 25);
+        assert_eq!(stats.get_allowed_count(), 25);
+    });
+}
+
+#[test]
+fn should_check_many_urls_against_a_swapped_engine() {
+    runtime().block_on(async {
+        // Given: A facade whose engine is replaced after a reload
+        let statistics = Arc::new(SharedStatistics::new());
+        let engine = FilterEngine::from_filter_list("||ads.example^").unwrap();
+        let adblock = AsyncAdBlock::new(Arc::new(engine), Arc::clone(&statistics));
+        let reloaded = FilterEngine::from_filter_list("||tracker.example^").unwrap();
+        adblock.swap_engine(Arc::new(reloaded));
+
+        // When: Checking a batch of URLs
+        let decisions =
+            adblock.check_many(["https://ads.example/a.js", "https://tracker.example/t.gif"]);
+        adblock.flush().await;
+
+        // Then: The new engine's rules apply
+        assert!(!decisions[0].should_block);
+        assert!(decisions[1].should_block);
+        assert_eq!(statistics.snapshot().get_blocked_count(), 1);
+    });
+}
+
+#[test]
+fn should_follow_the_core_whitelist_and_pause() {
+    use adblock_core::AdBlockCore;
+    use std::time::Duration;
+
+    runtime().block_on(async {
+        // Given: A facade over a core whitelisting one blocked domain
+        let mut core = AdBlockCore::from_filter_list("||ads.example^\n||tracker.example^").unwrap();
+        let mut config = core.config().clone();
+        config.whitelist.push("tracker.example".to_string());
+        core.update_config(config).unwrap();
+        let core = Arc::new(parking_lot::RwLock::new(core));
+        let adblock = AsyncAdBlock::from_core(Arc::clone(&core));
+
+        // When: Checking blocked URLs before and during a pause
+        let active =
+            adblock.check_many(["https://ads.example/a.js", "https://tracker.example/t.gif"]);
+        core.write().pause(Duration::from_secs(60));
+        let paused = adblock.check_url("https://ads.example/a.js");
+        adblock.flush().await;
+
+        // Then: The core's decision applies and feeds its statistics
+        assert!(active[0].should_block);
+        assert!(!active[1].should_block);
+        assert!(!paused.should_block);
+        let stats = core.read().get_statistics();
+        assert_eq!(stats.get_blocked_count(), 1);
+        assert_eq!(stats.get_allowed_count(), 2);
+        assert_eq!(stats.get_paused_count(), 1);
+        assert_eq!(adblock.dropped_updates(), 0);
+    });
+}
+
+#[test]
+fn should_drop_updates_beyond_the_queue_capacity() {
+    use adblock_core::async_engine::UPDATE_QUEUE_CAPACITY;
+
+    runtime().block_on(async {
+        // Given: A facade whose statistics task can't run in between checks
+        let engine = FilterEngine::from_filter_list("||ads.example^").unwrap();
+        let statistics = Arc::new(SharedStatistics::new());
+        let adblock = AsyncAdBlock::new(Arc::new(engine), Arc::clone(&statistics));
+
+        // When: Checking more URLs than the queue holds
+        for _ in 0..UPDATE_QUEUE_CAPACITY + 10 {
+            adblock.check_url("https://ads.example/a.js");
+        }
+        adblock.flush().await;
+
+        // Then: The overflow is dropped instead of queued
+        assert_eq!(adblock.dropped_updates(), 10);
+        assert_eq!(
+            statistics.snapshot().get_blocked_count(),
+            UPDATE_QUEUE_CAPACITY as u64
+        );
+    });
+}
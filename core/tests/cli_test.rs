@@ -0,0 +1,63 @@
+//! CLI Tests - adblock-cli binary
+//!
+//! Run the command line tool against the sample filter list
+
+use std::process::Command;
+
+const SAMPLE_LIST: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/tests/fixtures/easylist_sample.txt"
+);
+
+fn run(args: &[&str]) -> (bool, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_adblock-cli"))
+        .args(args)
+        .output()
+        .expect("Failed to run adblock-cli");
+    (
+        output.status.success(),
+        String::from_utf8_lossy(&output.stdout).into_owned(),
+    )
+}
+
+#[test]
+fn should_check_urls_against_lists() {
+    // When: Checking an ad URL and a regular URL
+    let (ok, blocked) = run(&[
+        "check",
+        "https://doubleclick.net/ad.js",
+        "--lists",
+        SAMPLE_LIST,
+    ]);
+    let (_, allowed) = run(&["check", "https://github.com/", "--lists", SAMPLE_LIST]);
+
+    // Then: The verdict and matching rule are printed
+    assert!(ok);
+    assert!(blocked.starts_with("BLOCKED"));
+    assert!(blocked.contains("rule: ||doubleclick.net^"));
+    assert!(allowed.starts_with("ALLOWED"));
+}
+
+#[test]
+fn should_check_urls_against_compiled_list() {
+    // Given: A compiled copy of the sample list
+    let output = std::env::temp_dir().join(format!("adblock-cli-{}.json", std::process::id()));
+    let output = output.to_str().unwrap();
+    let (ok, message) = run(&["compile", "--lists", SAMPLE_LIST, "-o", output]);
+    assert!(ok);
+    assert!(message.starts_with("Compiled"));
+
+    // When: Checking a URL against it
+    let (ok, verdict) = run(&["check", "https://criteo.com/x", "--compiled", output]);
+    std::fs::remove_file(output).ok();
+
+    // Then: It blocks like the source list
+    assert!(ok);
+    assert!(verdict.starts_with("BLOCKED"));
+}
+
+#[test]
+fn should_reject_unknown_commands() {
+    let (ok, _) = run(&["frobnicate"]);
+    assert!(!ok);
+}
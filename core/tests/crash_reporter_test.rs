@@ -0,0 +1,40 @@
+//! Crash Reporter Tests
+//!
+//! Test panic reporting through the core. The panic hook is process-wide,
+//! so these tests live in their own binary.
+
+use adblock_core::crash_reporter::CrashType;
+use adblock_core::{AdBlockCore, Config};
+
+#[test]
+fn should_record_and_persist_panics_with_rule_count() {
+    // Given: A core persisting crash reports to a platform directory
+    let dir = std::env::temp_dir().join(format!("adblock_crash_reports_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let config = Config {
+        filter_lists: Vec::new(),
+        crash_reports_dir: Some(dir.to_string_lossy().into_owned()),
+        ..Config::default()
+    };
+    let core = AdBlockCore::new(config.clone()).unwrap();
+
+    // When: A panic occurs
+    let result = std::panic::catch_unwind(|| panic!("filter compilation blew up"));
+
+    // Then: The report carries the active rule count and is persisted
+    assert!(result.is_err());
+    let reports = core.crash_reporter().get_reports(10);
+    assert_eq!(reports.len(), 1);
+    assert!(matches!(reports[0].error_type, CrashType::Native));
+    assert!(reports[0].message.contains("filter compilation blew up"));
+    assert!(reports[0].context.filter_rules_count.unwrap() > 0);
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+    // And: A new core with the same directory loads it until cleared
+    drop(core);
+    let reloaded = AdBlockCore::new(config).unwrap();
+    assert_eq!(reloaded.crash_reporter().get_reports(10).len(), 1);
+    reloaded.crash_reporter().clear_reports();
+    assert!(reloaded.crash_reporter().get_reports(10).is_empty());
+    assert!(!dir.exists());
+}
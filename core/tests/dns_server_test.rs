@@ -0,0 +1,248 @@
+//! DNS Server Tests
+//!
+//! Resolve real queries through the local DNS proxy server
+
+use adblock_core::dns_server::{DnsServer, DnsServerConfig};
+use adblock_core::network::{BlockResponsePolicy, NetworkFilter};
+use adblock_core::SharedStatistics;
+use std::io::{Read, Write};
+use std::net::{TcpStream, UdpSocket};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Query message for a domain's A record
+fn a_query(id: u16, domain: &str) -> Vec<u8> {
+    let mut message = id.to_be_bytes().to_vec();
+    message.extend_from_slice(&[0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+    for label in domain.split('.') {
+        message.push(label.len() as u8);
+        message.extend_from_slice(label.as_bytes());
+    }
+    message.extend_from_slice(&[0, 0, 1, 0, 1]);
+    message
+}
+
+/// Upstream resolver echoing each query back with the response flag set
+fn start_upstream() -> std::net::SocketAddr {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr = socket.local_addr().unwrap();
+    std::thread::spawn(move || {
+        let mut buffer = [0u8; 512];
+        while let Ok((len, client)) = socket.recv_from(&mut buffer) {
+            buffer[2] |= 0x80;
+            socket.send_to(&buffer[..len], client).unwrap();
+        }
+    });
+    addr
+}
+
+fn start_server(
+    policy: BlockResponsePolicy,
+    statistics: Option<Arc<SharedStatistics>>,
+) -> DnsServer {
+    let mut filter = NetworkFilter::new();
+    filter.add_blocked_domain("ads.example");
+    filter.set_block_response_policy(policy);
+    let config = DnsServerConfig {
+        listen: "127.0.0.1:0".parse().unwrap(),
+        upstream: start_upstream(),
+        upstream_timeout: Duration::from_secs(2),
+        ..DnsServerConfig::default()
+    };
+    DnsServer::start(config, Arc::new(filter), statistics).unwrap()
+}
+
+fn ask_udp(server: &DnsServer, query: &[u8]) -> Vec<u8> {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    socket.send_to(query, server.udp_addr()).unwrap();
+    let mut buffer = [0u8; 512];
+    let len = socket.recv(&mut buffer).unwrap();
+    buffer[..len].to_vec()
+}
+
+#[test]
+fn should_answer_blocked_domains_locally() {
+    // Given: A DNS server blocking ads.example with the null address
+    let statistics = Arc::new(SharedStatistics::new());
+    let server = start_server(BlockResponsePolicy::NullIp, Some(Arc::clone(&statistics)));
+
+    // When: Querying a subdomain of the blocked domain
+    let query = a_query(42, "cdn.ads.example");
+    let response = ask_udp(&server, &query);
+
+    // Then: The answer is 0.0.0.0 and the block is counted
+    assert_eq!(&response[..2], &42u16.to_be_bytes());
+    assert_eq!(response[3] & 0x0F, 0);
+    assert_eq!(&response[6..8], &[0, 1]);
+    assert_eq!(&response[response.len() - 4..], &[0, 0, 0, 0]);
+    assert_eq!(statistics.snapshot().total_blocked(), 1);
+}
+
+#[test]
+fn should_answer_nxdomain_when_configured() {
+    // Given: A DNS server answering blocked domains with NXDOMAIN
+    let server = start_server(BlockResponsePolicy::Nxdomain, None);
+
+    // When: Querying the blocked domain
+    let response = ask_udp(&server, &a_query(7, "ads.example"));
+
+    // Then: The response code is NXDOMAIN without answers
+    assert_eq!(response[3] & 0x0F, 3);
+    assert_eq!(&response[6..8], &[0, 0]);
+}
+
+#[test]
+fn should_forward_allowed_queries_over_udp_and_tcp() {
+    // Given: A DNS server with an upstream resolver
+    let statistics = Arc::new(SharedStatistics::new());
+    let server = start_server(BlockResponsePolicy::NullIp, Some(Arc::clone(&statistics)));
+    let query = a_query(9, "example.com");
+
+    // When: Querying an allowed domain over UDP
+    let response = ask_udp(&server, &query);
+
+    // Then: The upstream answer is relayed
+    assert_eq!(&response[..2], &9u16.to_be_bytes());
+    assert_ne!(response[2] & 0x80, 0);
+    assert_eq!(&response[3..], &query[3..]);
+    assert_eq!(statistics.snapshot().total_allowed(), 1);
+
+    // When: Querying a blocked domain over TCP
+    let mut stream = TcpStream::connect(server.tcp_addr()).unwrap();
+    let query = a_query(10, "ads.example");
+    stream
+        .write_all(&(query.len() as u16).to_be_bytes())
+        .unwrap();
+    stream.write_all(&query).unwrap();
+    let mut len = [0u8; 2];
+    stream.read_exact(&mut len).unwrap();
+    let mut response = vec![0u8; u16::from_be_bytes(len) as usize];
+    stream.read_exact(&mut response).unwrap();
+
+    // Then: The length-prefixed answer comes from the filter
+    assert_eq!(&response[..2], &10u16.to_be_bytes());
+    assert_eq!(&response[6..8], &[0, 1]);
+}
+
+#[test]
+fn should_apply_per_domain_block_responses() {
+    use adblock_core::network::{DnsAnswer, DnsQuery, DnsQueryType, DnsResponseCode};
+
+    // Given: Refused by default, with a custom address for one domain
+    let mut filter = NetworkFilter::new();
+    filter.add_blocked_domain("ads.example");
+    filter.add_blocked_domain("tracker.example");
+    filter.set_block_response_policy(BlockResponsePolicy::Refused);
+    filter.set_domain_block_response(
+        "tracker.example",
+        Some(BlockResponsePolicy::CustomIp("10.0.0.1".parse().unwrap())),
+    );
+    let query = |domain: &str| DnsQuery {
+        domain: domain.to_string(),
+        query_type: DnsQueryType::A,
+        transaction_id: 1,
+    };
+
+    // When: Processing queries for both domains
+    let refused = filter.process_dns_query(&query("ads.example"));
+    let custom = filter.process_dns_query(&query("cdn.tracker.example"));
+
+    // Then: Each follows its own policy
+    assert_eq!(refused.response_code, DnsResponseCode::Refused);
+    assert!(refused.answers.is_empty());
+    assert_eq!(custom.response_code, DnsResponseCode::NoError);
+    assert!(matches!(
+        custom.answers.as_slice(),
+        [DnsAnswer::A(ip)] if ip.octets() == [10, 0, 0, 1]
+    ));
+
+    // And: Removing the override restores the default
+    filter.set_domain_block_response("tracker.example", None);
+    assert_eq!(
+        filter.block_response_for("tracker.example"),
+        BlockResponsePolicy::Refused
+    );
+}
+
+#[test]
+fn should_answer_aaaa_queries_with_the_v6_sink() {
+    use adblock_core::network::{DnsAnswer, DnsQuery, DnsQueryType};
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    // Given: A blocked domain redirected to an IPv4 block page
+    let mut filter = NetworkFilter::new();
+    filter.add_blocked_domain("ads.example");
+    filter.set_redirect_ip("10.0.0.1".parse().unwrap());
+    let query = |query_type| DnsQuery {
+        domain: "ads.example".to_string(),
+        query_type,
+        transaction_id: 1,
+    };
+
+    // When: Resolving its IPv6 address
+    let response = filter.process_dns_query(&query(DnsQueryType::AAAA));
+
+    // Then: The answer is the default v6 sink instead of an empty answer
+    assert!(matches!(
+        response.answers.as_slice(),
+        [DnsAnswer::AAAA(ip)] if *ip == Ipv6Addr::UNSPECIFIED
+    ));
+
+    // And: Configured sinks answer the null-address policy
+    let v6_sink: Ipv6Addr = "fd00::1".parse().unwrap();
+    filter.set_sink_addresses(Ipv4Addr::new(127, 0, 0, 2), v6_sink);
+    filter.set_block_response_policy(BlockResponsePolicy::NullIp);
+    let a = filter.process_dns_query(&query(DnsQueryType::A));
+    let aaaa = filter.process_dns_query(&query(DnsQueryType::AAAA));
+    assert!(matches!(a.answers.as_slice(), [DnsAnswer::A(ip)] if ip.octets() == [127, 0, 0, 2]));
+    assert!(matches!(aaaa.answers.as_slice(), [DnsAnswer::AAAA(ip)] if *ip == v6_sink));
+}
+
+#[test]
+fn should_bound_the_queries_forwarded_at_once() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // Given: A server forwarding one query at a time to a silent upstream
+    let upstream = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let upstream_addr = upstream.local_addr().unwrap();
+    let received = Arc::new(AtomicUsize::new(0));
+    let upstream_received = Arc::clone(&received);
+    std::thread::spawn(move || {
+        let mut buffer = [0u8; 512];
+        while upstream.recv_from(&mut buffer).is_ok() {
+            upstream_received.fetch_add(1, Ordering::SeqCst);
+        }
+    });
+    let mut filter = NetworkFilter::new();
+    filter.add_blocked_domain("ads.example");
+    let config = DnsServerConfig {
+        listen: "127.0.0.1:0".parse().unwrap(),
+        upstream: upstream_addr,
+        upstream_timeout: Duration::from_millis(300),
+        max_in_flight: 1,
+    };
+    let server = DnsServer::start(config, Arc::new(filter), None).unwrap();
+
+    // When: Sending a burst of allowed queries
+    let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+    for id in 0..20 {
+        client
+            .send_to(&a_query(id, "example.com"), server.udp_addr())
+            .unwrap();
+    }
+
+    // Then: Blocked domains are still answered locally meanwhile
+    let response = ask_udp(&server, &a_query(99, "ads.example"));
+    assert_eq!(&response[..2], &99u16.to_be_bytes());
+
+    // And: At most the query in flight and the queued one reach the upstream
+    std::thread::sleep(Duration::from_millis(1000));
+    let forwarded = received.load(Ordering::SeqCst);
+    assert!(
+        (1..=2).contains(&forwarded),
+        "{forwarded} queries forwarded"
+    );
+}
@@ -0,0 +1,74 @@
+//! FFI Allocation Tests
+//!
+//! Test the calls documented as allocation-free. The allocator is
+//! process-wide, so these tests live in their own binary.
+
+use adblock_core::ffi::*;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::ptr;
+
+/// System allocator counting the allocations of each thread
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn allocations_in(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    f();
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+#[test]
+fn should_write_stats_without_allocating() {
+    // Given: An engine that has blocked requests to many domains
+    let engine = adblock_engine_create(ptr::null_mut());
+    let list = CString::new("||ads.com^").unwrap();
+    assert_eq!(
+        adblock_engine_load_filter_list_status(engine, list.as_ptr()),
+        AdblockStatus::Ok
+    );
+    for i in 0..100 {
+        let url = CString::new(format!("https://ads.com/{i}.js")).unwrap();
+        let mut error = AdblockErrorCode::Ok;
+        adblock_engine_check_url(engine, url.as_ptr(), &mut error);
+    }
+    let mut buf = [0 as c_char; 1024];
+    let mut error = AdblockErrorCode::NullPointer;
+
+    // When: Writing the statistics into a caller buffer
+    let allocations = allocations_in(|| {
+        adblock_engine_get_stats_buf(engine, buf.as_mut_ptr(), buf.len(), &mut error);
+    });
+
+    // Then: Nothing is allocated
+    assert_eq!(error, AdblockErrorCode::Ok);
+    assert_eq!(allocations, 0);
+    let stats = unsafe { CStr::from_ptr(buf.as_ptr()) }.to_str().unwrap();
+    assert!(stats.contains(r#""blocked_count":100"#), "{stats}");
+
+    adblock_engine_destroy(engine);
+}
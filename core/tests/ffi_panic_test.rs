@@ -0,0 +1,54 @@
+//! FFI Panic Tests
+//!
+//! Test that panics stop at the C API boundary. The panic hook is
+//! process-wide, so these tests live in their own binary.
+
+use adblock_core::ffi::*;
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::ptr;
+
+#[test]
+fn should_turn_panics_into_errors_and_report_them() {
+    // Given: An engine, whose crash reporter records panics
+    let engine = adblock_engine_create(ptr::null_mut());
+    let list = CString::new("||ads.com^").unwrap();
+    assert_eq!(
+        adblock_engine_load_filter_list_status(engine, list.as_ptr()),
+        AdblockStatus::Ok
+    );
+
+    // When: Calls panic inside the boundary guard
+    let mut error = AdblockErrorCode::Ok;
+    let json: *mut c_char = catch_panic(&mut error, || panic!("intentional panic"));
+    let status: AdblockStatus = catch_panic(ptr::null_mut(), || panic!("intentional panic"));
+
+    // Then: They return their failure values instead of unwinding
+    assert!(json.is_null());
+    assert_eq!(error, AdblockErrorCode::Panicked);
+    assert_eq!(status, AdblockStatus::Panicked);
+
+    // And: The panics are recorded as crash reports
+    let json = adblock_get_crash_reports_json(engine, &mut error);
+    assert_eq!(error, AdblockErrorCode::Ok);
+    let reports: serde_json::Value =
+        serde_json::from_str(unsafe { CStr::from_ptr(json) }.to_str().unwrap()).unwrap();
+    unsafe { adblock_free_string(json) };
+    let reports = reports.as_array().unwrap();
+    assert_eq!(reports.len(), 2);
+    assert!(reports[0]["message"]
+        .as_str()
+        .unwrap()
+        .contains("intentional panic"));
+
+    // And: The engine keeps working
+    let url = CString::new("https://ads.com/banner").unwrap();
+    assert!(adblock_engine_should_block(
+        engine,
+        url.as_ptr(),
+        &mut error
+    ));
+    assert_eq!(error, AdblockErrorCode::Ok);
+
+    adblock_engine_destroy(engine);
+}
@@ -0,0 +1,336 @@
+//! FFI Tests - C API for individual rules
+//!
+//! Test adding, removing and counting rules through the C API
+
+use adblock_core::ffi::*;
+use std::ffi::CString;
+use std::ptr;
+
+fn load_list(engine: *mut std::os::raw::c_void, list: &str) {
+    let list = CString::new(list).unwrap();
+    assert_eq!(
+        adblock_engine_load_filter_list_status(engine, list.as_ptr()),
+        AdblockStatus::Ok
+    );
+}
+
+fn should_block(engine: *mut std::os::raw::c_void, url: &str) -> bool {
+    let url = CString::new(url).unwrap();
+    adblock_engine_should_block(engine, url.as_ptr(), ptr::null_mut())
+}
+
+#[test]
+fn should_add_and_remove_individual_rules() {
+    // Given: An engine with one filter list
+    let engine = adblock_engine_create(ptr::null_mut());
+    load_list(engine, "||ads.com^");
+    let mut error = AdblockErrorCode::NullPointer;
+    assert_eq!(adblock_engine_rule_count(engine, &mut error), 1);
+    assert_eq!(error, AdblockErrorCode::Ok);
+
+    // When: Adding a rule
+    let rule = CString::new("||tracker.com^").unwrap();
+    assert!(adblock_engine_add_rule(engine, rule.as_ptr(), &mut error));
+    assert_eq!(error, AdblockErrorCode::Ok);
+
+    // Then: It blocks right away and is counted once, even if added twice
+    assert!(should_block(engine, "https://tracker.com/pixel.gif"));
+    assert_eq!(
+        adblock_engine_add_rule_status(engine, rule.as_ptr()),
+        AdblockStatus::Ok
+    );
+    assert_eq!(adblock_engine_rule_count(engine, ptr::null_mut()), 2);
+
+    // When: Removing it again
+    assert!(adblock_engine_remove_rule(
+        engine,
+        rule.as_ptr(),
+        &mut error
+    ));
+
+    // Then: The rule is gone and removing it twice is reported
+    assert!(!should_block(engine, "https://tracker.com/pixel.gif"));
+    assert_eq!(adblock_engine_rule_count(engine, ptr::null_mut()), 1);
+    assert_eq!(
+        adblock_engine_remove_rule_status(engine, rule.as_ptr()),
+        AdblockStatus::NotSupported
+    );
+
+    adblock_engine_destroy(engine);
+}
+
+#[test]
+fn should_keep_added_rules_across_list_reloads() {
+    // Given: An engine with an added rule
+    let engine = adblock_engine_create(ptr::null_mut());
+    let rule = CString::new("||tracker.com^").unwrap();
+    assert_eq!(
+        adblock_engine_add_rule_status(engine, rule.as_ptr()),
+        AdblockStatus::Ok
+    );
+
+    // When: A new filter list replaces the rules
+    load_list(engine, "||ads.com^");
+
+    // Then: The added rule still applies next to the list
+    assert!(should_block(engine, "https://tracker.com/"));
+    assert!(should_block(engine, "https://ads.com/"));
+    assert_eq!(adblock_engine_rule_count(engine, ptr::null_mut()), 2);
+
+    adblock_engine_destroy(engine);
+}
+
+#[test]
+fn should_reject_invalid_rules() {
+    let engine = adblock_engine_create(ptr::null_mut());
+
+    for invalid in ["! just a comment", "||ads.com^$unknownoption", ""] {
+        let rule = CString::new(invalid).unwrap();
+        let mut error = AdblockErrorCode::Ok;
+        assert!(
+            !adblock_engine_add_rule(engine, rule.as_ptr(), &mut error),
+            "{invalid}"
+        );
+        assert_eq!(error, AdblockErrorCode::EngineFailure);
+    }
+    assert_eq!(
+        adblock_engine_add_rule_status(engine, ptr::null()),
+        AdblockStatus::NullPointer
+    );
+    let mut error = AdblockErrorCode::Ok;
+    assert_eq!(adblock_engine_rule_count(ptr::null_mut(), &mut error), 0);
+    assert_eq!(error, AdblockErrorCode::NullPointer);
+
+    adblock_engine_destroy(engine);
+}
+
+#[test]
+fn should_report_engine_info() {
+    // Given: An engine with an enabled and a disabled list
+    let engine = adblock_engine_create(ptr::null_mut());
+    for (url, content) in [
+        (
+            "https://lists.example/ads.txt",
+            "! Version: 42\n||ads.com^\n@@||ads.com/ok\nexample.com##.ad",
+        ),
+        ("https://lists.example/off.txt", "||off.com^"),
+    ] {
+        let url = CString::new(url).unwrap();
+        let content = CString::new(content).unwrap();
+        assert_eq!(
+            adblock_engine_add_filter_list_status(engine, url.as_ptr(), content.as_ptr()),
+            AdblockStatus::Ok
+        );
+    }
+    let off = CString::new("https://lists.example/off.txt").unwrap();
+    assert_eq!(
+        adblock_engine_set_filter_list_enabled_status(engine, off.as_ptr(), false),
+        AdblockStatus::Ok
+    );
+
+    // When: Getting the engine info
+    let mut error = AdblockErrorCode::NullPointer;
+    let json = adblock_engine_get_info(engine, &mut error);
+    assert_eq!(error, AdblockErrorCode::Ok);
+    let info: serde_json::Value =
+        serde_json::from_str(unsafe { std::ffi::CStr::from_ptr(json) }.to_str().unwrap()).unwrap();
+    unsafe { adblock_free_string(json) };
+
+    // Then: It counts the rules by kind and lists only the enabled list
+    assert_eq!(info["version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(info["rule_counts"]["block"], 1);
+    assert_eq!(info["rule_counts"]["exception"], 1);
+    assert_eq!(info["rule_counts"]["cosmetic"], 1);
+    let lists = info["lists"].as_array().unwrap();
+    assert_eq!(lists.len(), 1);
+    assert_eq!(lists[0]["version"], "42");
+    assert!(info["last_updated"].is_string());
+    assert!(info["memory_bytes"].as_u64().unwrap() > 0);
+
+    adblock_engine_destroy(engine);
+}
+
+#[test]
+fn should_reject_destroyed_and_unknown_handles() {
+    // Given: An engine that was destroyed
+    let engine = adblock_engine_create(ptr::null_mut());
+    load_list(engine, "||ads.com^");
+    assert!(should_block(engine, "https://ads.com/"));
+    adblock_engine_destroy(engine);
+
+    // When: The stale handle, or one that was never handed out, is used
+    let url = CString::new("https://ads.com/").unwrap();
+    let mut error = AdblockErrorCode::Ok;
+    let stale = adblock_engine_should_block(engine, url.as_ptr(), &mut error);
+
+    // Then: The call fails instead of touching freed memory
+    assert!(!stale);
+    assert_eq!(error, AdblockErrorCode::NullPointer);
+    let made_up = 0x7fff_0001usize as *mut std::os::raw::c_void;
+    assert_eq!(
+        adblock_engine_reset_stats_status(made_up),
+        AdblockStatus::NullPointer
+    );
+    adblock_engine_destroy(engine);
+
+    // And: A new engine never answers to the old handle
+    let replacement = adblock_engine_create(ptr::null_mut());
+    assert_ne!(replacement, engine);
+    assert!(!adblock_engine_should_block(
+        engine,
+        url.as_ptr(),
+        ptr::null_mut()
+    ));
+    adblock_engine_destroy(replacement);
+}
+
+#[test]
+fn should_check_requests_with_page_and_resource_type() {
+    // Given: Rules limited to third-party scripts, and element hiding
+    let engine = adblock_engine_create(ptr::null_mut());
+    load_list(
+        engine,
+        "||cdn.example^$script,third-party\nnews.example##.ad\n##.banner",
+    );
+    let check = |url: &str, page_url: Option<&str>, resource_type: Option<&str>| {
+        let url = CString::new(url).unwrap();
+        let page_url = page_url.map(|s| CString::new(s).unwrap());
+        let resource_type = resource_type.map(|s| CString::new(s).unwrap());
+        let mut error = AdblockErrorCode::NullPointer;
+        let blocked = adblock_engine_should_block_request(
+            engine,
+            url.as_ptr(),
+            page_url.as_ref().map_or(ptr::null(), |s| s.as_ptr()),
+            resource_type.as_ref().map_or(ptr::null(), |s| s.as_ptr()),
+            &mut error,
+        );
+        (blocked, error)
+    };
+
+    // When/Then: The page and type decide which rules apply
+    let ok = AdblockErrorCode::Ok;
+    let page = Some("https://news.example/");
+    assert_eq!(
+        check("https://cdn.example/lib", page, Some("script")),
+        (true, ok)
+    );
+    assert_eq!(
+        check("https://cdn.example/lib", page, Some("image")),
+        (false, ok)
+    );
+    assert_eq!(
+        check(
+            "https://cdn.example/lib",
+            Some("https://cdn.example/"),
+            Some("script")
+        ),
+        (false, ok)
+    );
+    assert_eq!(check("https://cdn.example/lib.js", page, None), (true, ok));
+    assert_eq!(
+        check("https://cdn.example/lib", page, Some("bogus")),
+        (false, AdblockErrorCode::InvalidArgument)
+    );
+
+    // And: The domain's hiding rules come as a stylesheet
+    let domain = CString::new("news.example").unwrap();
+    let mut error = AdblockErrorCode::NullPointer;
+    let css = adblock_engine_get_cosmetic_css(engine, domain.as_ptr(), &mut error);
+    assert_eq!(error, AdblockErrorCode::Ok);
+    let stylesheet = unsafe { std::ffi::CStr::from_ptr(css) }
+        .to_str()
+        .unwrap()
+        .to_string();
+    unsafe { adblock_free_string(css) };
+    assert!(stylesheet.contains(".ad { display: none !important; }"));
+    assert!(stylesheet.contains(".banner { display: none !important; }"));
+
+    adblock_engine_destroy(engine);
+}
+
+type UpdateResult = Result<usize, String>;
+
+extern "C" fn record_update(
+    rule_count: usize,
+    error_message: *const std::os::raw::c_char,
+    user_data: *mut std::os::raw::c_void,
+) {
+    let sender = unsafe { Box::from_raw(user_data as *mut std::sync::mpsc::Sender<UpdateResult>) };
+    let result = if error_message.is_null() {
+        Ok(rule_count)
+    } else {
+        let message = unsafe { std::ffi::CStr::from_ptr(error_message) };
+        Err(message.to_string_lossy().into_owned())
+    };
+    sender.send(result).unwrap();
+}
+
+fn create_with_list(url: &str) -> *mut std::os::raw::c_void {
+    let config = format!(r#"{{"filter_lists":["{url}"],"list_presets":[]}}"#);
+    let config = CString::new(config).unwrap();
+    let engine = adblock_engine_create_with_config(config.as_ptr(), ptr::null_mut());
+    assert!(!engine.is_null());
+    engine
+}
+
+fn update_filters_async(engine: *mut std::os::raw::c_void) -> UpdateResult {
+    let (sender, receiver) = std::sync::mpsc::channel::<UpdateResult>();
+    let user_data = Box::into_raw(Box::new(sender)) as *mut std::os::raw::c_void;
+    let mut error = AdblockErrorCode::NullPointer;
+    assert!(adblock_engine_update_filters_async(
+        engine,
+        Some(record_update),
+        user_data,
+        &mut error
+    ));
+    assert_eq!(error, AdblockErrorCode::Ok);
+    receiver
+        .recv_timeout(std::time::Duration::from_secs(10))
+        .expect("update callback was not called")
+}
+
+// Relies on the download stub serving sample content
+#[cfg(not(feature = "http"))]
+#[test]
+fn should_update_filters_in_the_background() {
+    // Given: An engine subscribed to a list, with nothing loaded yet
+    let engine = create_with_list("https://lists.example/ads.txt");
+    assert!(!should_block(engine, "https://downloaded-ads.com/ad.js"));
+
+    // When: Updating the lists asynchronously
+    let rule_count = update_filters_async(engine).expect("update failed");
+
+    // Then: The callback reports the new rules, which are already live
+    assert!(rule_count > 0);
+    assert_eq!(
+        adblock_engine_rule_count(engine, ptr::null_mut()),
+        rule_count
+    );
+    assert!(should_block(engine, "https://downloaded-ads.com/ad.js"));
+
+    // And: A null callback is rejected up front
+    let mut error = AdblockErrorCode::Ok;
+    assert!(!adblock_engine_update_filters_async(
+        engine,
+        None,
+        ptr::null_mut(),
+        &mut error
+    ));
+    assert_eq!(error, AdblockErrorCode::NullPointer);
+    adblock_engine_destroy(engine);
+}
+
+#[test]
+fn should_report_failed_background_updates() {
+    // Given: An engine whose only list can't be downloaded
+    let engine = create_with_list("https://invalid.example/list.txt");
+    load_list(engine, "||ads.com^");
+
+    // When: Updating the lists asynchronously
+    let result = update_filters_async(engine);
+
+    // Then: The callback gets the error and the old rules stay in place
+    assert!(result.is_err());
+    assert!(should_block(engine, "https://ads.com/banner.js"));
+    adblock_engine_destroy(engine);
+}
@@ -3,7 +3,7 @@
 //! Starting with the most basic functionality:
 //! Blocking requests from known ad domains
 
-use adblock_core::filter_engine::FilterEngine;
+use adblock_core::filter_engine::{BlockKind, FilterEngine};
 
 #[test]
 fn should_block_doubleclick_domain() {
@@ -145,3 +145,831 @@ fn should_match_subdomain_patterns() {
     );
     assert!(!engine.should_block("https://doubleclick.com/").should_block);
 }
+
+#[test]
+fn should_audit_exceptions_overriding_blocks() {
+    // Given: A filter engine with audit mode enabled
+    let mut engine = FilterEngine::new_with_patterns(vec![
+        "||ads.example.com^".to_string(),
+        "@@||ads.example.com/acceptable/*".to_string(),
+    ]);
+    engine.enable_exception_audit(10);
+
+    // When: An exception overrides a blocking rule
+    let decision = engine.should_block("https://ads.example.com/acceptable/banner.png");
+    assert!(!decision.should_block);
+
+    // And: A request is blocked normally
+    assert!(
+        engine
+            .should_block("https://ads.example.com/banner.png")
+            .should_block
+    );
+
+    // Then: Only the override is recorded
+    let audit = engine.exception_audit().expect("audit enabled");
+    let entries = audit.entries(10);
+    assert_eq!(entries.len(), 1);
+    assert_eq!(
+        entries[0].url,
+        "https://ads.example.com/acceptable/banner.png"
+    );
+    assert_eq!(
+        entries[0].exception_rule,
+        "@@||ads.example.com/acceptable/*"
+    );
+    assert_eq!(entries[0].overridden_rule, "||ads.example.com^");
+    assert!(audit.export_json().unwrap().contains("overridden_rule"));
+}
+
+#[test]
+fn should_return_scriptlets_for_domain() {
+    // Given: A filter list mixing cosmetic and scriptlet rules
+    let engine = FilterEngine::from_filter_list(
+        "example.com##.ad\nexample.com##+js(no-setTimeout-if, adblock)",
+    )
+    .unwrap();
+
+    // When: Looking up injections for the page domain
+    let scripts = engine.get_scriptlets_for_domain("www.example.com");
+
+    // Then: The scriptlet is rendered and not treated as a selector
+    assert_eq!(scripts.len(), 1);
+    assert!(scripts[0].contains("window.setTimeout"));
+    assert_eq!(
+        engine.get_cosmetic_selectors("www.example.com"),
+        vec![".ad"]
+    );
+}
+
+#[test]
+fn should_prune_json_responses() {
+    // Given: A json-prune rule next to a scriptlet rule
+    let mut engine = FilterEngine::from_filter_list(
+        "youtube.com##+js(json-prune, playerAds adPlacements)\nyoutube.com##+js(no-setTimeout-if, adblock)",
+    )
+    .unwrap();
+    let body = r#"{"adPlacements":[{"id":1}],"playerAds":[],"videoDetails":{"id":"x"}}"#;
+
+    // Then: The named properties are removed for the rule's domain only
+    assert_eq!(
+        engine.prune_json("www.youtube.com", body),
+        r#"{"videoDetails":{"id":"x"}}"#
+    );
+    assert_eq!(engine.prune_json("example.com", body), body);
+    assert_eq!(engine.get_scriptlets_for_domain("youtube.com").len(), 1);
+
+    // And: Removing the rule stops the pruning
+    assert!(engine.remove_rule("youtube.com##+js(json-prune, playerAds adPlacements)"));
+    assert_eq!(engine.prune_json("www.youtube.com", body), body);
+}
+
+#[test]
+fn should_rewrite_urls_with_removeparam() {
+    // Given: An engine with removeparam rules and an exception
+    let engine = FilterEngine::from_filter_list(
+        "||example.com^$removeparam=ref\n$removeparam=/^pk_/\n@@||shop.com^$removeparam",
+    )
+    .unwrap();
+
+    // Then: Tracking and list-specified params are stripped
+    assert_eq!(
+        engine.rewrite_url("https://example.com/a?ref=x&utm_source=y&id=1&pk_campaign=z"),
+        Some("https://example.com/a?id=1".to_string())
+    );
+
+    // And: Scoped rules don't apply elsewhere
+    assert_eq!(
+        engine.rewrite_url("https://other.com/a?ref=x&gclid=1"),
+        Some("https://other.com/a?ref=x".to_string())
+    );
+
+    // And: Exceptions keep every parameter
+    assert_eq!(engine.rewrite_url("https://shop.com/?fbclid=1"), None);
+
+    // And: removeparam rules never block
+    assert!(
+        !engine
+            .should_block("https://example.com/a?ref=x")
+            .should_block
+    );
+}
+
+#[test]
+fn should_serve_repeated_requests_from_verdict_cache() {
+    // Given: An engine with the verdict cache enabled
+    let mut engine =
+        FilterEngine::new_with_patterns(vec!["||cdn.example.com/banners/ad-".to_string()]);
+    engine.enable_verdict_cache(std::time::Duration::from_secs(30), 16);
+
+    // When: The same resource is requested several times
+    for _ in 0..5 {
+        assert!(
+            engine
+                .should_block("https://cdn.example.com/banners/ad-1.png")
+                .should_block
+        );
+    }
+
+    // Then: Only the first request needed a full evaluation
+    let stats = engine.verdict_cache().unwrap().stats();
+    assert_eq!(stats.misses, 1);
+    assert_eq!(stats.hits, 4);
+
+    // And: Sibling URLs get their own verdict
+    assert!(
+        !engine
+            .should_block("https://cdn.example.com/banners/logo.png")
+            .should_block
+    );
+
+    // And: Adding a rule invalidates cached verdicts
+    engine.add_rule("@@||cdn.example.com/banners/*");
+    assert!(
+        !engine
+            .should_block("https://cdn.example.com/banners/ad-1.png")
+            .should_block
+    );
+}
+
+#[test]
+fn should_count_verdicts_served_from_the_cache() {
+    // Given: A cached engine tracking rule hits and auditing exceptions
+    let mut engine = FilterEngine::new_with_patterns(vec![
+        "||ads.example.com^".to_string(),
+        "@@||ads.example.com/acceptable/*".to_string(),
+    ]);
+    engine.enable_verdict_cache(std::time::Duration::from_secs(30), 16);
+    engine.enable_rule_hit_tracking(1);
+    engine.enable_exception_audit(10);
+
+    // When: A blocked and an excepted URL are each requested three times
+    for _ in 0..3 {
+        engine.should_block("https://ads.example.com/banner.png");
+        engine.should_block("https://ads.example.com/acceptable/banner.png");
+    }
+
+    // Then: Cache hits count like full evaluations
+    assert_eq!(engine.verdict_cache().unwrap().stats().hits, 4);
+    let hits = engine.rule_hits().unwrap();
+    assert_eq!(hits.hits("||ads.example.com^"), 3);
+    assert_eq!(hits.hits("@@||ads.example.com/acceptable/*"), 3);
+    let metrics = engine.get_metrics().snapshot();
+    assert_eq!(metrics.blocked_requests, 3);
+    assert_eq!(metrics.matches_by_category.values().sum::<u64>(), 6);
+    assert_eq!(engine.exception_audit().unwrap().len(), 3);
+}
+
+#[test]
+fn should_attach_redirect_resource_to_blocked_requests() {
+    // Given: Redirect and redirect-rule entries
+    let engine = FilterEngine::from_filter_list(
+        "||ads.example.com/ad.js$script,redirect=noopjs\n||pixels.com^\n*/pixel.gif$redirect-rule=1x1.gif",
+    )
+    .unwrap();
+
+    // Then: $redirect blocks and carries the stub name
+    let decision = engine.should_block("https://ads.example.com/ad.js");
+    assert!(decision.should_block);
+    assert_eq!(decision.redirect_resource.as_deref(), Some("noop.js"));
+
+    // And: $redirect-rule only applies when something else blocks
+    let decision = engine.should_block("https://pixels.com/pixel.gif");
+    assert_eq!(decision.redirect_resource.as_deref(), Some("1x1.gif"));
+    assert!(
+        !engine
+            .should_block("https://example.com/pixel.gif")
+            .should_block
+    );
+}
+
+#[test]
+fn should_collect_csp_directives() {
+    // Given: CSP rules with a directive-specific exception
+    let engine = FilterEngine::from_filter_list(
+        "||news.com^$csp=script-src 'self'\n||news.com^$csp=worker-src 'none'\n$csp=worker-src 'none',domain=news.com\n@@||news.com/live/$csp=script-src 'self'\n@@||news.com/video/$csp",
+    )
+    .unwrap();
+
+    // Then: Matching directives are returned once each
+    assert_eq!(
+        engine.get_csp_directives("https://news.com/article", "news.com"),
+        vec!["script-src 'self'", "worker-src 'none'"]
+    );
+
+    // And: Exceptions remove one or all directives
+    assert_eq!(
+        engine.get_csp_directives("https://news.com/live/1", "news.com"),
+        vec!["worker-src 'none'"]
+    );
+    assert!(engine
+        .get_csp_directives("https://news.com/video/1", "news.com")
+        .is_empty());
+
+    // And: CSP rules never block the document
+    assert!(!engine.should_block("https://news.com/article").should_block);
+}
+
+#[test]
+fn should_apply_element_hiding_exceptions() {
+    // Given: Generic and specific cosmetic rules with exceptions
+    let engine = FilterEngine::from_filter_list(
+        "##.ad\nnews.com##.sidebar-ad\nnews.com#@#.ad\n@@||forum.com^$generichide\n@@||shop.com^$elemhide\nshop.com##.banner",
+    )
+    .unwrap();
+
+    // Then: #@# unhides a generic selector on one site
+    assert_eq!(
+        engine
+            .get_cosmetic_result("https://news.com/a")
+            .hide_selectors,
+        vec![".sidebar-ad"]
+    );
+
+    // And: $generichide keeps only domain-specific rules
+    let result = engine.get_cosmetic_result("https://forum.com/t/1");
+    assert!(result.generichide);
+    assert!(result.hide_selectors.is_empty());
+
+    // And: $elemhide disables all cosmetic filtering
+    let result = engine.get_cosmetic_result("https://shop.com/");
+    assert!(result.generichide && result.specifichide);
+    assert!(result.hide_selectors.is_empty());
+
+    // And: Other sites are unaffected
+    assert_eq!(
+        engine
+            .get_cosmetic_result("https://other.com/")
+            .hide_selectors,
+        vec![".ad"]
+    );
+}
+
+#[test]
+fn should_expose_procedural_filters_as_json() {
+    // Given: A list mixing plain and procedural element hiding rules
+    let engine = FilterEngine::from_filter_list(
+        "news.com##.ad\nnews.com##article:has-text(Sponsored)\n##.feed > div:has(.promoted):upward(1)",
+    )
+    .unwrap();
+
+    // Then: Plain selectors stay CSS
+    assert_eq!(engine.get_cosmetic_selectors("news.com"), vec![".ad"]);
+
+    // And: Procedural rules are returned as parsed steps
+    let json: serde_json::Value =
+        serde_json::from_str(&engine.get_procedural_filters("news.com")).unwrap();
+    let filters = json.as_array().unwrap();
+    assert_eq!(filters.len(), 2);
+    assert_eq!(filters[0]["steps"][1]["type"], "has-text");
+    assert_eq!(filters[0]["steps"][1]["arg"]["pattern"], "Sponsored");
+    assert_eq!(filters[1]["steps"][2]["arg"], 1);
+
+    // And: Domain-specific rules stay on their site
+    let json: serde_json::Value =
+        serde_json::from_str(&engine.get_procedural_filters("other.com")).unwrap();
+    assert_eq!(json.as_array().unwrap().len(), 1);
+}
+
+#[test]
+fn should_build_engine_from_reader() {
+    // Given: A filter list read through a buffered reader
+    let list =
+        "! Title: Streamed\n||ads.com^\n@@||ads.com/ok.js\nexample.com##.ad\n##+js(nostif, ad)\n";
+    let engine = FilterEngine::from_reader(std::io::Cursor::new(list)).unwrap();
+
+    // Then: It behaves like the engine built from the whole string
+    let reference = FilterEngine::from_filter_list(list).unwrap();
+    for url in [
+        "https://ads.com/x.js",
+        "https://ads.com/ok.js",
+        "https://safe.com/",
+    ] {
+        assert_eq!(
+            engine.should_block(url).should_block,
+            reference.should_block(url).should_block
+        );
+    }
+    assert_eq!(engine.get_cosmetic_selectors("example.com"), vec![".ad"]);
+    assert_eq!(engine.scriptlets().len(), 1);
+}
+
+#[test]
+fn should_attribute_blocks_to_rule_category_and_source_list() {
+    // Given: An engine loaded from two named lists
+    let mut engine = FilterEngine::from_filter_list("").unwrap();
+    engine.load_list("EasyList", "||ads.com^\n*/banner/*\n@@||ads.com/ok.js\n");
+    engine.load_list("EasyPrivacy", "||tracker.net^\n");
+
+    // When: Requests match rules of both lists
+    for url in [
+        "https://ads.com/x.js",
+        "https://ads.com/ok.js",
+        "https://tracker.net/pixel",
+        "https://tracker.net/beacon",
+        "https://cdn.example/banner/top.png",
+    ] {
+        engine.should_block(url);
+    }
+
+    // Then: Matches are broken down by category and blocks by list
+    let snapshot = engine.get_metrics().snapshot();
+    assert_eq!(snapshot.matches_by_category["subdomain"], 3);
+    assert_eq!(snapshot.matches_by_category["wildcard"], 1);
+    assert_eq!(snapshot.matches_by_category["exception"], 1);
+    assert_eq!(snapshot.blocks_by_list["EasyList"], 2);
+    assert_eq!(snapshot.blocks_by_list["EasyPrivacy"], 2);
+    assert_eq!(snapshot.list_block_share("EasyPrivacy"), 50.0);
+}
+
+#[test]
+fn should_report_and_reclaim_rule_memory() {
+    // Given: An engine with many rules
+    let list: String = (0..1000).map(|i| format!("||ads{i}.example^\n")).collect();
+    let mut engine = FilterEngine::from_filter_list(&list).unwrap();
+    let full = engine.memory_usage();
+    assert_eq!(engine.get_metrics().snapshot().memory_usage_bytes, full);
+
+    // When: Most rules are removed and the engine is compacted
+    let removed: String = (0..900).map(|i| format!("||ads{i}.example^\n")).collect();
+    engine.apply_diff(&adblock_core::filter_updater::FilterListDiff::compute(
+        &removed, "",
+    ));
+    let freed = engine.compact();
+
+    // Then: Removed rules and spare capacity are released
+    assert!(freed > 0);
+    assert!(engine.memory_usage() < full);
+    assert_eq!(
+        engine.get_metrics().snapshot().memory_usage_bytes,
+        engine.memory_usage()
+    );
+    assert!(engine.should_block("https://ads950.example/x").should_block);
+}
+
+#[test]
+fn should_match_equivalently_encoded_urls() {
+    // Given: Rules for an ad host and an ad path
+    let engine = FilterEngine::from_filter_list("||ads.example.com^\n*/ads/banner/*\n").unwrap();
+
+    // When/Then: Case, default ports and percent-encoding don't evade them
+    for url in [
+        "https://ADS.Example.COM/x",
+        "https://ads.example.com:443/x",
+        "https://ads.example.com./x",
+        "https://site.com/ads%2Fbanner%2Fimg.png",
+        "https://site.com/%61ds/banner/img.png",
+    ] {
+        assert!(engine.should_block(url).should_block, "{url} not blocked");
+    }
+    assert!(
+        !engine
+            .should_block("https://ads.example.com.evil/x")
+            .should_block
+    );
+}
+
+#[test]
+fn should_match_internationalized_domains() {
+    // Given: Rules written with Unicode hosts, including a mixed-script one
+    let mut engine = FilterEngine::from_filter_list(
+        "||реклама.рф^\n||bücher-ads.de^\n||xn--pypal-4ve.com^\n@@||реклама.рф/разрешено/\nпример.рф##.ad\n",
+    )
+    .unwrap();
+
+    // When/Then: Unicode and punycode URLs both match
+    assert!(
+        engine
+            .should_block("https://реклама.рф/banner")
+            .should_block
+    );
+    assert!(
+        engine
+            .should_block("https://xn--80aanufhx.xn--p1ai/banner")
+            .should_block
+    );
+    assert!(
+        engine
+            .should_block("https://cdn.BÜCHER-ads.de/x")
+            .should_block
+    );
+    // Cyrillic "а" in a Latin label is a different host than "paypal"
+    assert!(engine.should_block("https://pаypal.com/login").should_block);
+    assert!(!engine.should_block("https://paypal.com/login").should_block);
+
+    // And: Exceptions and cosmetic domains are normalized too
+    assert!(
+        !engine
+            .should_block("https://реклама.рф/разрешено/x")
+            .should_block
+    );
+    assert_eq!(
+        engine.get_cosmetic_selectors("xn--e1afmkfd.xn--p1ai"),
+        vec![".ad"]
+    );
+    assert_eq!(engine.get_cosmetic_selectors("пример.рф"), vec![".ad"]);
+
+    // And: Rules can be removed by their Unicode form
+    assert!(engine.remove_rule("||реклама.рф^"));
+    engine.build_domain_matcher();
+    assert!(
+        !engine
+            .should_block("https://реклама.рф/banner")
+            .should_block
+    );
+}
+
+#[test]
+fn should_apply_resource_type_options() {
+    use adblock_core::resource_type::{RequestContext, ResourceType};
+
+    // Given: Rules limited to WebSocket and WebRTC, and a non-script exception
+    let mut engine = FilterEngine::from_filter_list(
+        "||chat.example.com^$websocket\n||relay.example.com^$webrtc\n*/ads/*$~script\n@@||cdn.example.com/ads/$image\n",
+    )
+    .unwrap();
+    engine.enable_verdict_cache(std::time::Duration::from_secs(30), 64);
+
+    // When/Then: WebSocket rules only block WebSocket connections
+    assert!(
+        engine
+            .should_block("wss://chat.example.com/socket")
+            .should_block
+    );
+    assert!(
+        !engine
+            .should_block("https://chat.example.com/socket")
+            .should_block
+    );
+    let websocket = RequestContext {
+        resource_type: ResourceType::Websocket,
+        ..RequestContext::default()
+    };
+    assert!(
+        engine
+            .should_block_with_context("https://chat.example.com/socket", &websocket)
+            .should_block
+    );
+    assert_eq!(
+        engine.matching_rule("wss://chat.example.com/socket"),
+        Some("||chat.example.com^$websocket".to_string())
+    );
+
+    // And: WebRTC rules match STUN/TURN servers
+    assert!(
+        engine
+            .should_block("turn:relay.example.com:3478")
+            .should_block
+    );
+    assert!(
+        !engine
+            .should_block("https://relay.example.com/")
+            .should_block
+    );
+
+    // And: Negated types and typed exceptions are honored
+    assert!(
+        engine
+            .should_block("https://site.com/ads/banner.png")
+            .should_block
+    );
+    assert!(
+        !engine
+            .should_block("https://site.com/ads/loader.js")
+            .should_block
+    );
+    assert!(
+        !engine
+            .should_block("https://cdn.example.com/ads/banner.png")
+            .should_block
+    );
+    assert!(
+        engine
+            .should_block("https://cdn.example.com/ads/frame.html")
+            .should_block
+    );
+
+    // And: Typed rules can be removed again
+    assert!(engine.remove_rule("||chat.example.com^$websocket"));
+    engine.build_domain_matcher();
+    assert!(
+        !engine
+            .should_block("wss://chat.example.com/socket")
+            .should_block
+    );
+}
+
+#[test]
+fn should_cancel_rules_with_badfilter_across_lists() {
+    // Given: A regional list with a rule the global list cancels
+    let mut engine = FilterEngine::from_filter_list("").unwrap();
+    engine.load_list("regional", "||ads.com^\n||ads.com^$script\n||track.com^\n");
+
+    // When: The global list disables the overly broad rule
+    engine.load_list("global", "||ads.com^$badfilter\n");
+
+    // Then: Only the identical rule is dropped, and the count is reported
+    assert!(
+        !engine
+            .should_block("https://ads.com/banner.png")
+            .should_block
+    );
+    assert!(engine.should_block("https://ads.com/ad.js").should_block);
+    assert!(engine.should_block("https://track.com/pixel").should_block);
+    assert_eq!(engine.get_pattern_stats().cancelled_rules, 1);
+    assert_eq!(engine.rule_count(), 2);
+
+    // And: Rules loaded after the $badfilter rule are cancelled too
+    engine.load_list("more", "||ads.com^\n");
+    assert!(
+        !engine
+            .should_block("https://ads.com/banner.png")
+            .should_block
+    );
+    assert_eq!(engine.get_pattern_stats().cancelled_rules, 2);
+}
+
+#[test]
+fn should_report_and_prune_unused_rules() {
+    // Given: An engine counting every rule hit
+    let mut engine = FilterEngine::from_filter_list(
+        "||ads.com^\n||unused.com^\n@@||ads.com/allowed/\n*/never/*\n",
+    )
+    .unwrap();
+    assert!(engine
+        .rule_hit_report(std::time::Duration::from_secs(60))
+        .is_none());
+    engine.enable_rule_hit_tracking(1);
+
+    // When: Some rules match
+    engine.should_block("https://ads.com/banner");
+    engine.should_block("https://ads.com/banner2");
+    engine.should_block("https://ads.com/allowed/x");
+    let report = engine
+        .rule_hit_report(std::time::Duration::from_secs(60))
+        .unwrap();
+
+    // Then: The rules that never matched are reported
+    assert_eq!(engine.rule_hits().unwrap().hits("||ads.com^"), 2);
+    assert_eq!(report.total_rules, 4);
+    assert_eq!(report.unused_rules, vec!["||unused.com^", "*/never/*"]);
+
+    // And: Pruning them keeps the rules in use
+    assert_eq!(engine.prune_rules(&report.unused_rules), 2);
+    assert_eq!(engine.rule_count(), 2);
+    assert!(!engine.should_block("https://unused.com/").should_block);
+    assert!(engine.should_block("https://ads.com/banner").should_block);
+}
+
+#[test]
+fn should_check_the_most_matched_rules_first_after_reordering() {
+    // Given: Two rules matching the same URL, the later one matching more
+    let mut engine = FilterEngine::from_filter_list("*/ads/*\n*/banner/*\n").unwrap();
+    assert!(!engine.reorder_rules_by_hits());
+    engine.enable_rule_hit_tracking(1);
+    for i in 0..3 {
+        engine.should_block(&format!("https://example.com/banner/{i}.png"));
+    }
+    assert_eq!(
+        engine.matching_rule("https://example.com/ads/banner/1.png"),
+        Some("*/ads/*".to_string())
+    );
+
+    // When: The rules are reordered by their hits
+    assert!(engine.reorder_rules_by_hits());
+
+    // Then: The most matched rule is checked first
+    assert_eq!(
+        engine.matching_rule("https://example.com/ads/banner/1.png"),
+        Some("*/banner/*".to_string())
+    );
+    assert_eq!(engine.rule_count(), 2);
+}
+
+#[test]
+fn should_report_security_list_blocks_as_malware() {
+    // Given: An ad list and a malware protection list
+    let mut engine = FilterEngine::from_filter_list("").unwrap();
+    engine.load_list("Ads", "||ads.com^");
+    engine.load_security_list("Malware", "||phishing.example^");
+
+    // When/Then: Each block carries the kind of its list
+    let malware = engine.should_block("https://login.phishing.example/");
+    assert!(malware.should_block);
+    assert_eq!(malware.kind, Some(BlockKind::Malware));
+
+    let ad = engine.should_block("https://ads.com/banner.js");
+    assert_eq!(ad.kind, Some(BlockKind::Ad));
+
+    let allowed = engine.should_block("https://example.com/");
+    assert!(!allowed.should_block);
+    assert_eq!(allowed.kind, None);
+}
+
+#[test]
+fn should_explain_every_rule_matching_a_url() {
+    use adblock_core::resource_type::RequestContext;
+
+    // Given: Two lists with overlapping rules and an exception
+    let mut engine = FilterEngine::from_filter_list("").unwrap();
+    engine.load_list(
+        "EasyList",
+        "||ads.com^\n*/banner/*\n@@||ads.com/banner/ok.png\n",
+    );
+    engine.load_list("Extra", "/ok.png\n||other.net^\n");
+
+    // When: Explaining a URL matched by several of them
+    let url = "https://ads.com/banner/ok.png";
+    let explanation = engine.explain(url, &RequestContext::from_url(url));
+
+    // Then: The exception comes first, then every blocking rule with its list
+    let rules: Vec<_> = explanation
+        .matches
+        .iter()
+        .map(|m| (m.rule.as_str(), m.source.as_deref()))
+        .collect();
+    assert_eq!(
+        rules,
+        [
+            ("@@||ads.com/banner/ok.png", Some("EasyList")),
+            ("||ads.com^", Some("EasyList")),
+            ("*/banner/*", Some("EasyList")),
+            ("/ok.png", Some("Extra")),
+        ]
+    );
+    assert!(explanation.matches[0].exception);
+    assert!(!explanation.should_block);
+    assert_eq!(
+        explanation.should_block,
+        engine.should_block(url).should_block
+    );
+}
+
+#[cfg(feature = "bundled-filters")]
+#[test]
+fn should_fall_back_to_bundled_list_without_network_or_cache() {
+    // Given: A configuration whose lists can't be downloaded and no cache
+    let config = adblock_core::Config {
+        filter_lists: vec!["https://invalid.example/list.txt".to_string()],
+        ..adblock_core::Config::default()
+    };
+
+    // When: Creating the engine
+    let engine = FilterEngine::new(&config).unwrap();
+
+    // Then: Domains beyond the hard-coded defaults are blocked by the bundled list
+    assert!(
+        engine
+            .should_block("https://ads.taboola.com/widget.js")
+            .should_block
+    );
+    assert!(
+        engine
+            .should_block("https://cdn.hotjar.com/t.js")
+            .should_block
+    );
+}
+
+#[test]
+fn should_distinguish_popup_document_and_subresource_verdicts() {
+    use adblock_core::resource_type::{RequestContext, ResourceType};
+    use adblock_core::Verdict;
+
+    // Given: A plain rule, a `$popup` rule and a `$document` rule
+    let engine = FilterEngine::from_filter_list(
+        "||ads.com^\n||popunder.net^$popup\n||scam.example^$document\n",
+    )
+    .unwrap();
+    let context = |resource_type| RequestContext {
+        resource_type,
+        document_url: Some("https://news.example/".to_string()),
+    };
+    let verdict = |url, resource_type| {
+        engine
+            .should_block_with_context(url, &context(resource_type))
+            .verdict
+    };
+
+    // Then: Subresources are blocked as requests
+    assert_eq!(
+        verdict("https://ads.com/ad.js", ResourceType::Script),
+        Verdict::BlockRequest
+    );
+    // And: Popups are only blocked by `$popup` rules
+    assert_eq!(
+        verdict("https://popunder.net/", ResourceType::Popup),
+        Verdict::BlockPopup
+    );
+    assert_eq!(
+        verdict("https://ads.com/", ResourceType::Popup),
+        Verdict::Allow
+    );
+    assert_eq!(
+        verdict("https://popunder.net/img.png", ResourceType::Image),
+        Verdict::Allow
+    );
+    // And: `$document` rules block navigation only
+    assert_eq!(
+        verdict("https://scam.example/", ResourceType::Document),
+        Verdict::BlockDocument
+    );
+    assert_eq!(
+        verdict("https://scam.example/a.js", ResourceType::Script),
+        Verdict::Allow
+    );
+}
+
+#[test]
+fn should_treat_sites_of_one_entity_as_first_party() {
+    use adblock_core::resource_type::{RequestContext, ResourceType};
+
+    // Given: A rule blocking Facebook's CDN only as a third party
+    let engine = FilterEngine::from_filter_list("||fbcdn.net^$third-party\n").unwrap();
+    let on_page = |page: &str| RequestContext {
+        resource_type: ResourceType::Script,
+        document_url: Some(page.to_string()),
+    };
+    let url = "https://static.xx.fbcdn.net/rsrc.js";
+
+    // Then: It is first-party on a site of the same owner
+    assert!(
+        !engine
+            .should_block_with_context(url, &on_page("https://www.facebook.com/"))
+            .should_block
+    );
+    // And: Third-party anywhere else
+    assert!(
+        engine
+            .should_block_with_context(url, &on_page("https://news.example/"))
+            .should_block
+    );
+    // And: Without a known page the rule applies
+    assert!(engine.should_block(url).should_block);
+}
+
+#[test]
+fn should_anchor_host_rules_with_a_path_to_the_host() {
+    // Given: A `||` rule with a path, stored as a pattern rule
+    let engine = FilterEngine::from_filter_list("||cdn.example.com/ads/\n").unwrap();
+    let blocked = |url: &str| engine.should_block(url).should_block;
+
+    // Then: It matches the host and its subdomains
+    assert!(blocked("https://cdn.example.com/ads/banner.js"));
+    assert!(blocked("https://eu.cdn.example.com/ads/banner.js"));
+    // And: Not other hosts ending in the same name, or the path elsewhere
+    assert!(!blocked("https://notcdn.example.com/ads/banner.js"));
+    assert!(!blocked("https://cdn.example.com/static/ads.js"));
+    assert!(!blocked(
+        "https://news.example/?next=https://cdn.example.com/ads/banner.js"
+    ));
+}
+
+#[test]
+fn should_survive_deeply_nested_procedural_rules() {
+    // Given: A list with a procedural rule nested thousands of levels deep
+    let depth = 5_000;
+    let list = format!(
+        "||ads.com^\nexample.com##{}.ad{}\n",
+        "div:has(".repeat(depth),
+        ")".repeat(depth)
+    );
+
+    // When: Loading it on a thread with a small stack
+    let engine = std::thread::Builder::new()
+        .stack_size(256 * 1024)
+        .spawn(move || FilterEngine::from_filter_list(&list).unwrap())
+        .unwrap()
+        .join()
+        .expect("loading the list overflowed the stack");
+
+    // Then: The hostile rule is dropped and the rest of the list applies
+    assert!(
+        engine
+            .should_block("https://ads.com/banner.js")
+            .should_block
+    );
+    assert_eq!(engine.rule_counts().procedural, 0);
+}
+
+#[test]
+fn should_apply_diffs_of_hosts_format_lists() {
+    // Given: An engine loaded from a hosts file
+    let previous = "0.0.0.0 ads.com\n127.0.0.1 tracker.net pixel.io\ncdn-ads.org\n";
+    let mut engine = FilterEngine::from_filter_list(previous).unwrap();
+
+    // When: Its entries are dropped from the list
+    let removed = engine.apply_diff(&adblock_core::filter_updater::FilterListDiff::compute(
+        previous, "",
+    ));
+
+    // Then: The rules each entry was loaded as are removed
+    assert_eq!(removed, 4);
+    for url in [
+        "https://ads.com/x",
+        "https://tracker.net/t.js",
+        "https://pixel.io/p.gif",
+        "https://cdn-ads.org/a.js",
+    ] {
+        assert!(!engine.should_block(url).should_block, "{url}");
+    }
+}
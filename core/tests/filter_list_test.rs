@@ -2,6 +2,7 @@
 //!
 //! Test loading and parsing of EasyList-format filter rules
 
+use adblock_core::filter_list::FormatReport;
 use adblock_core::{FilterEngine, FilterListLoader};
 
 #[test]
@@ -170,3 +171,176 @@ example.com##.banner
     assert!(css_rules.iter().any(|r| r == ".banner"));
     assert!(!css_rules.iter().any(|r| r == ".sidebar-ad")); // excluded by ~example.com
 }
+
+#[test]
+fn should_parse_lines_lazily() {
+    // Given: Lines mixing comments, network and cosmetic rules
+    let loader = FilterListLoader::new();
+    let lines = [
+        "[Adblock Plus 2.0]",
+        "! comment",
+        "  ||ads.com^  ",
+        "example.com##.ad",
+        "",
+        "@@||ok.com^",
+    ];
+
+    // When: Parsing them as an iterator
+    let rules: Vec<&str> = loader.parse_lines(lines).collect();
+
+    // Then: Only trimmed network rules are yielded
+    assert_eq!(rules, vec!["||ads.com^", "@@||ok.com^"]);
+}
+
+#[test]
+fn should_lint_filter_list_lines() {
+    use adblock_core::lint::{LintKind, LintSeverity};
+
+    // Given: Custom rules with typical mistakes
+    let content = "! My rules\n||ads.com^$scirpt\n/ad(s/\nads\n||track.com^\n||track.com^\n||ok.com^$third-party,~image\nexample.com##.ad\n/banner[0-9]+/$image\n";
+    let loader = FilterListLoader::new();
+
+    // When: Linting them
+    let diagnostics = loader.lint(content);
+
+    // Then: Each problem is reported on its line
+    let found: Vec<(usize, &LintKind)> = diagnostics.iter().map(|d| (d.line, &d.kind)).collect();
+    assert_eq!(found.len(), 4);
+    assert_eq!(
+        found[0],
+        (2, &LintKind::UnknownOption("scirpt".to_string()))
+    );
+    assert!(matches!(found[1], (3, LintKind::InvalidRegex(_))));
+    assert_eq!(found[2], (4, &LintKind::OverlyBroad));
+    assert_eq!(found[3], (6, &LintKind::DuplicateRule(5)));
+    assert_eq!(diagnostics[0].severity, LintSeverity::Error);
+    assert_eq!(diagnostics[2].severity, LintSeverity::Warning);
+}
+
+#[test]
+fn should_read_the_expires_header() {
+    use adblock_core::filter_list::expires_header;
+    use std::time::Duration;
+
+    // Given: Lists with typical `! Expires:` headers
+    let days = "[Adblock Plus 2.0]\n! Title: Ads\n! Expires: 4 days (update frequency)\n||ads.com^";
+    let hours = "! Expires: 12h\n||ads.com^";
+    let after_rules = "||ads.com^\n! Expires: 1 day";
+
+    // Then: The interval is read from the header only
+    assert_eq!(expires_header(days), Some(Duration::from_secs(4 * 86400)));
+    assert_eq!(expires_header(hours), Some(Duration::from_secs(12 * 3600)));
+    assert_eq!(expires_header(after_rules), None);
+    assert_eq!(expires_header("! Expires: soon\n||ads.com^"), None);
+}
+
+#[test]
+fn should_route_mixed_format_lines_to_their_parsers() {
+    // Given: A user list mixing hosts entries, bare domains and EasyList rules
+    let filter_list = r#"
+# Hosts section
+127.0.0.1 localhost
+0.0.0.0 hosts-ads.com hosts-tracker.net # trailing comment
+plain-ads.org
+||abp-ads.com^
+/banner/*
+##.ad-box
+not a rule
+"#;
+
+    // When: Parsing it
+    let loader = FilterListLoader::new();
+    let (rules, report) = loader.parse_mixed(filter_list);
+
+    // Then: Each line is parsed by its own syntax and unknown lines are dropped
+    assert_eq!(
+        rules,
+        [
+            "||hosts-ads.com^",
+            "||hosts-tracker.net^",
+            "||plain-ads.org^",
+            "||abp-ads.com^",
+            "/banner/*",
+        ]
+    );
+    assert_eq!(
+        report,
+        FormatReport {
+            network: 2,
+            cosmetic: 1,
+            hosts: 1,
+            domain: 1,
+            unknown: 1,
+        }
+    );
+    assert!(report.is_mixed());
+
+    // And: The engine blocks the converted domains but not lookalikes
+    let engine = FilterEngine::from_filter_list(filter_list).unwrap();
+    assert!(
+        engine
+            .should_block("https://cdn.hosts-ads.com/a.js")
+            .should_block
+    );
+    assert!(engine.should_block("https://plain-ads.org/").should_block);
+    assert!(
+        !engine
+            .should_block("https://example.com/?ref=plain-ads.org")
+            .should_block
+    );
+    assert!(!engine.should_block("https://localhost/").should_block);
+}
+
+#[test]
+fn should_report_skipped_and_malformed_lines() {
+    use adblock_core::filter_list::{ParseMode, SkipReason, SkippedLine};
+    use adblock_core::lint::LintKind;
+
+    // Given: A custom list with a header, comment, cosmetic rule and two malformed lines
+    let content = "[Adblock Plus 2.0]\n! My rules\n||ads.com^\n\nexample.com##.ad\n||ads.com^$scirpt\nnot a rule\n0.0.0.0 tracker.net\n";
+    let loader = FilterListLoader::new();
+
+    // When: Parsing leniently
+    let (rules, report) = loader
+        .parse_with_report(content, ParseMode::Lenient)
+        .unwrap();
+
+    // Then: Good lines are kept and every other line is accounted for
+    assert_eq!(rules, ["||ads.com^", "||tracker.net^"]);
+    assert_eq!(report.accepted, 2);
+    assert_eq!(
+        report.skipped,
+        [
+            SkippedLine {
+                line: 1,
+                reason: SkipReason::Header
+            },
+            SkippedLine {
+                line: 2,
+                reason: SkipReason::Comment
+            },
+            SkippedLine {
+                line: 5,
+                reason: SkipReason::Cosmetic
+            },
+        ]
+    );
+    let errors: Vec<_> = report.errors.iter().map(|e| (e.line, &e.kind)).collect();
+    assert_eq!(
+        errors,
+        [
+            (6, &LintKind::UnknownOption("scirpt".to_string())),
+            (7, &LintKind::UnknownSyntax),
+        ]
+    );
+
+    // When/Then: Strict mode rejects the list
+    let error = loader
+        .parse_with_report(content, ParseMode::Strict)
+        .unwrap_err();
+    assert_eq!(error.errors.len(), 2);
+    assert!(error.to_string().contains("line 6"));
+    assert!(loader
+        .parse_with_report("||ads.com^\n", ParseMode::Strict)
+        .is_ok());
+}
@@ -2,7 +2,11 @@
 //!
 //! Test automatic filter list updates from remote sources
 
+use adblock_core::checksum;
+use adblock_core::compression;
+use adblock_core::filter_updater::{CacheCorrupt, DownloadError};
 use adblock_core::{FilterUpdater, UpdateConfig};
+use std::collections::HashMap;
 use std::time::Duration;
 
 #[test]
@@ -10,6 +14,8 @@ fn should_download_filter_list_from_url() {
     // Given: A filter updater with a test URL
     let config = UpdateConfig {
         urls: vec!["https://example.com/filters.txt".to_string()],
+        presets: Vec::new(),
+        mirrors: HashMap::new(),
         update_interval: Duration::from_secs(3600),
         cache_dir: None,
     };
@@ -44,6 +50,8 @@ fn should_cache_downloaded_filter_lists() {
 
     let config = UpdateConfig {
         urls: vec!["https://example.com/filters.txt".to_string()],
+        presets: Vec::new(),
+        mirrors: HashMap::new(),
         update_interval: Duration::from_secs(3600),
         cache_dir: Some(temp_dir.clone()),
     };
@@ -55,22 +63,192 @@ fn should_cache_downloaded_filter_lists() {
     updater.update_with_content(filter_content).unwrap();
 
     // Then: Cache file should exist
-    let cache_file = temp_dir.join("filters_cache.txt");
+    let cache_file = temp_dir.join("filters_cache.txt.gz");
     assert!(cache_file.exists());
 
     // And: Cache content should match
-    let cached_content = std::fs::read_to_string(&cache_file).unwrap();
-    assert!(cached_content.contains("ads.example.com"));
+    let cached = compression::gzip_decompress(&std::fs::read(&cache_file).unwrap()).unwrap();
+    assert!(String::from_utf8(cached)
+        .unwrap()
+        .contains("ads.example.com"));
+    assert_eq!(updater.load_from_cache().unwrap(), filter_content);
 
     // Cleanup
     std::fs::remove_dir_all(&temp_dir).ok();
 }
 
+#[test]
+fn should_store_the_cache_compressed() {
+    // Given: A cache holding a plain list from an earlier version
+    let temp_dir = std::env::temp_dir().join("adblock_compressed_cache");
+    std::fs::remove_dir_all(&temp_dir).ok();
+    std::fs::create_dir_all(&temp_dir).unwrap();
+    std::fs::write(temp_dir.join("filters_cache.txt"), "||legacy-ads.com^").unwrap();
+
+    let config = UpdateConfig {
+        urls: vec!["https://example.com/filters.txt".to_string()],
+        presets: Vec::new(),
+        mirrors: HashMap::new(),
+        update_interval: Duration::from_secs(3600),
+        cache_dir: Some(temp_dir.clone()),
+    };
+    let mut updater = FilterUpdater::new(config).unwrap();
+    assert_eq!(updater.load_from_cache().unwrap(), "||legacy-ads.com^");
+
+    // When: Caching a large list
+    let content: String = (0..2000)
+        .map(|i| format!("||ads{i}.example.com^$third-party\n"))
+        .collect();
+    updater.update_with_content(&content).unwrap();
+
+    // Then: It is stored at under a quarter of its size, replacing the plain cache
+    let stored = std::fs::metadata(temp_dir.join("filters_cache.txt.gz")).unwrap();
+    assert!(stored.len() * 4 < content.len() as u64);
+    assert!(!temp_dir.join("filters_cache.txt").exists());
+    assert_eq!(updater.load_from_cache().unwrap(), content);
+
+    std::fs::remove_dir_all(&temp_dir).ok();
+}
+
+#[test]
+fn should_download_again_when_the_cache_is_corrupt() {
+    // Given: A fresh cache whose list was altered after it was written
+    let temp_dir = std::env::temp_dir().join("adblock_corrupt_cache");
+    std::fs::remove_dir_all(&temp_dir).ok();
+
+    let config = UpdateConfig {
+        urls: vec!["https://example.com/filters.txt".to_string()],
+        presets: Vec::new(),
+        mirrors: HashMap::new(),
+        update_interval: Duration::from_secs(3600),
+        cache_dir: Some(temp_dir.clone()),
+    };
+    let mut updater = FilterUpdater::new(config.clone()).unwrap();
+    updater.update_with_content("||cached-ads.com^").unwrap();
+    assert!(!temp_dir.join("cache_metadata.json.tmp").exists());
+    std::fs::write(
+        temp_dir.join("filters_cache.txt.gz"),
+        compression::gzip_compress(b"||tampered.com^"),
+    )
+    .unwrap();
+
+    // When: Loading the cache and updating
+    let mut updater = FilterUpdater::new(config).unwrap();
+    assert!(!updater.needs_update());
+    let loaded = updater.load_from_cache();
+    let updated = updater.auto_update().unwrap();
+
+    // Then: The corrupt cache is rejected and replaced by a download
+    let error = loaded.unwrap_err();
+    assert!(error.downcast_ref::<CacheCorrupt>().is_some());
+    assert!(updated.contains("downloaded-ads.com"));
+    assert_eq!(updater.load_from_cache().unwrap(), updated);
+
+    std::fs::remove_dir_all(&temp_dir).ok();
+}
+
+#[test]
+fn should_cache_each_list_separately() {
+    // Given: Two lists downloaded into the cache
+    let temp_dir = std::env::temp_dir().join("adblock_per_list_cache");
+    std::fs::remove_dir_all(&temp_dir).ok();
+    let ads = "https://example.com/ads.txt";
+    let privacy = "https://example.com/easyprivacy.txt";
+    let config = |urls: &[&str]| UpdateConfig {
+        urls: urls.iter().map(|url| url.to_string()).collect(),
+        presets: Vec::new(),
+        mirrors: HashMap::new(),
+        update_interval: Duration::from_secs(3600),
+        cache_dir: Some(temp_dir.clone()),
+    };
+    let mut updater = FilterUpdater::new(config(&[ads, privacy])).unwrap();
+    let filters = updater.auto_update().unwrap();
+    assert!(filters.contains("downloaded-ads.com") && filters.contains("analytics.com"));
+    let cached = updater.cached_list(privacy).unwrap().clone();
+    assert_eq!(cached.rule_count, 2);
+    assert!(cached.etag.is_some());
+    assert_eq!(
+        std::fs::read_dir(temp_dir.join("lists")).unwrap().count(),
+        2
+    );
+
+    // When: Disabling one list
+    let mut updater = FilterUpdater::new(config(&[privacy])).unwrap();
+    let filters = updater.auto_update().unwrap();
+
+    // Then: The other is served from the cache without a download
+    assert!(!filters.contains("downloaded-ads.com") && filters.contains("analytics.com"));
+    assert_eq!(updater.cached_list(privacy), Some(&cached));
+    assert_eq!(updater.load_from_cache().unwrap(), filters);
+
+    // When: Enabling a new list before the next update is due
+    let added = "https://example.com/extra.txt";
+    let mut updater = FilterUpdater::new(config(&[privacy, added])).unwrap();
+    updater.auto_update().unwrap();
+
+    // Then: Only the new list is downloaded
+    assert_eq!(updater.cached_list(privacy), Some(&cached));
+    assert!(updater.cached_list(added).is_some());
+
+    // When: Updating once due, with the list unchanged on the server
+    let mut updater = FilterUpdater::new(UpdateConfig {
+        update_interval: Duration::from_secs(0),
+        ..config(&[privacy])
+    })
+    .unwrap();
+    let filters = updater.auto_update().unwrap();
+
+    // Then: The cached copy is confirmed by its ETag and kept
+    let confirmed = updater.cached_list(privacy).unwrap();
+    assert!(confirmed.last_fetch > cached.last_fetch);
+    assert_eq!(confirmed.content_sha256, cached.content_sha256);
+    assert!(filters.contains("analytics.com"));
+
+    std::fs::remove_dir_all(&temp_dir).ok();
+}
+
+#[test]
+fn should_update_each_list_by_its_expires_header() {
+    // Given: A cached list whose header asks for updates every 4 days
+    let temp_dir = std::env::temp_dir().join("adblock_expires_cache");
+    std::fs::remove_dir_all(&temp_dir).ok();
+    let easylist = "https://example.com/easylist.txt";
+    let other = "https://example.com/filters.txt";
+    let config = UpdateConfig {
+        urls: vec![easylist.to_string(), other.to_string()],
+        presets: Vec::new(),
+        mirrors: HashMap::new(),
+        update_interval: Duration::from_secs(0),
+        cache_dir: Some(temp_dir.clone()),
+    };
+    let mut updater = FilterUpdater::new(config).unwrap();
+    updater.auto_update().unwrap();
+    let cached = updater.cached_list(easylist).unwrap().clone();
+
+    // When: Updating again right away
+    updater.auto_update().unwrap();
+
+    // Then: Only the list without a header is downloaded again
+    assert_eq!(cached.expires, Some(Duration::from_secs(4 * 24 * 3600)));
+    assert_eq!(updater.cached_list(easylist), Some(&cached));
+    assert!(updater.cached_list(other).unwrap().last_fetch > cached.last_fetch);
+    assert_eq!(
+        updater.list_update_interval(easylist),
+        Duration::from_secs(4 * 24 * 3600)
+    );
+    assert_eq!(updater.list_update_interval(other), Duration::from_secs(0));
+    assert_eq!(updater.next_update_in(), Duration::from_secs(0));
+
+    std::fs::remove_dir_all(&temp_dir).ok();
+}
+
 #[test]
 fn should_respect_update_interval() {
     // Given: A filter updater that was recently updated
     let config = UpdateConfig {
         urls: vec!["https://example.com/filters.txt".to_string()],
+        presets: Vec::new(),
+        mirrors: HashMap::new(),
         update_interval: Duration::from_secs(3600), // 1 hour
         cache_dir: None,
     };
@@ -92,6 +270,8 @@ fn should_merge_multiple_filter_lists() {
             "https://example.com/filters1.txt".to_string(),
             "https://example.com/filters2.txt".to_string(),
         ],
+        presets: Vec::new(),
+        mirrors: HashMap::new(),
         update_interval: Duration::from_secs(3600),
         cache_dir: None,
     };
@@ -114,6 +294,8 @@ fn should_handle_download_failures_gracefully() {
     // Given: A filter updater with invalid URL
     let config = UpdateConfig {
         urls: vec!["https://invalid.example.com/nonexistent".to_string()],
+        presets: Vec::new(),
+        mirrors: HashMap::new(),
         update_interval: Duration::from_secs(3600),
         cache_dir: None,
     };
@@ -139,6 +321,8 @@ fn should_use_cached_filters_when_download_fails() {
 
     let config = UpdateConfig {
         urls: vec!["https://invalid.example.com/filters.txt".to_string()],
+        presets: Vec::new(),
+        mirrors: HashMap::new(),
         update_interval: Duration::from_secs(3600),
         cache_dir: Some(temp_dir.clone()),
     };
@@ -154,3 +338,161 @@ fn should_use_cached_filters_when_download_fails() {
     // Cleanup
     std::fs::remove_dir_all(&temp_dir).ok();
 }
+
+#[test]
+fn should_normalize_merged_filter_lists() {
+    // Given: Two overlapping lists
+    let config = UpdateConfig {
+        urls: Vec::new(),
+        presets: Vec::new(),
+        mirrors: HashMap::new(),
+        update_interval: Duration::from_secs(3600),
+        cache_dir: None,
+    };
+    let updater = FilterUpdater::new(config).unwrap();
+    let easylist = "! EasyList\n||ads.com^\n||tracker.net^\n##.ad";
+    let easyprivacy = "! EasyPrivacy\n||tracker.net^\n||pixel.tracker.net^\n||cdn.ads.com^$script";
+
+    // When: Merging with normalization
+    let (merged, report) = updater.merge_and_normalize_filter_lists(vec![easylist, easyprivacy]);
+
+    // Then: Duplicates and subsumed subdomain rules are eliminated
+    assert_eq!(report.duplicates_removed, 1);
+    assert_eq!(report.subsumed_removed, 1);
+    assert_eq!(report.eliminated(), 2);
+    assert_eq!(
+        merged,
+        "||ads.com^\n||tracker.net^\n##.ad\n||cdn.ads.com^$script\n"
+    );
+}
+
+#[test]
+fn should_apply_differential_update_from_cached_copy() {
+    // Given: A cached copy of a list and a core loaded from it
+    let temp_dir = std::env::temp_dir().join("adblock_diff_cache");
+    std::fs::create_dir_all(&temp_dir).ok();
+    let config = UpdateConfig {
+        urls: vec!["https://example.com/filters.txt".to_string()],
+        presets: Vec::new(),
+        mirrors: HashMap::new(),
+        update_interval: Duration::from_secs(3600),
+        cache_dir: Some(temp_dir.clone()),
+    };
+    let mut updater = FilterUpdater::new(config).unwrap();
+    let previous = "! Version: 1\n||ads.com^\n||tracker.net^\n##.banner";
+    updater.update_with_content(previous).unwrap();
+    let mut core = adblock_core::AdBlockCore::from_filter_list(previous).unwrap();
+
+    // When: A new version arrives
+    let diff = updater
+        .update_with_diff("! Version: 2\n||ads.com^\n||pixel.io^\n##.sponsored")
+        .unwrap();
+    core.apply_filter_diff(&diff).unwrap();
+
+    // Then: Only the changed rules are reported and applied
+    assert_eq!(diff.added, vec!["||pixel.io^", "##.sponsored"]);
+    assert_eq!(diff.removed, vec!["||tracker.net^", "##.banner"]);
+    assert_eq!(diff.summary(), "2 added, 2 removed");
+    assert!(core.should_block("https://ads.com/x").should_block);
+    assert!(core.should_block("https://pixel.io/p.gif").should_block);
+    assert!(!core.should_block("https://tracker.net/t.js").should_block);
+    assert_eq!(
+        core.engine().get_cosmetic_selectors("example.com"),
+        vec![".sponsored"]
+    );
+
+    // Cleanup
+    std::fs::remove_dir_all(&temp_dir).ok();
+}
+
+#[test]
+fn should_reject_download_with_mismatched_pinned_checksum() {
+    // Given: A cached list and a pinned checksum the next download won't match
+    let temp_dir = std::env::temp_dir().join("adblock_pinned_cache");
+    std::fs::create_dir_all(&temp_dir).ok();
+    let url = "https://example.com/filters.txt";
+    let config = UpdateConfig {
+        urls: vec![url.to_string()],
+        presets: Vec::new(),
+        mirrors: HashMap::new(),
+        update_interval: Duration::from_secs(0),
+        cache_dir: Some(temp_dir.clone()),
+    };
+    let mut updater = FilterUpdater::new(config).unwrap();
+    updater.update_with_content("||cached-ads.com^").unwrap();
+    updater.pin_checksum(url, &"0".repeat(64));
+
+    // When: Downloading and auto-updating
+    let error = updater.download_filter_list(url).unwrap_err();
+    let filters = updater.auto_update().unwrap();
+
+    // Then: The download is rejected and the previous cache retained
+    assert!(matches!(
+        error.downcast_ref::<DownloadError>(),
+        Some(DownloadError::ChecksumMismatch { .. })
+    ));
+    assert_eq!(filters, "||cached-ads.com^");
+    assert_eq!(updater.load_from_cache().unwrap(), "||cached-ads.com^");
+
+    // When: Pinning the real checksum
+    updater.pin_checksum(url, &checksum::sha256_hex("||downloaded-ads.com^"));
+
+    // Then: The download is accepted
+    assert!(updater.download_filter_list(url).is_ok());
+
+    // Cleanup
+    std::fs::remove_dir_all(&temp_dir).ok();
+}
+
+#[test]
+fn should_verify_embedded_checksum_header() {
+    // Given: A list carrying a correct `! Checksum:` header
+    let config = UpdateConfig {
+        urls: Vec::new(),
+        presets: Vec::new(),
+        mirrors: HashMap::new(),
+        update_interval: Duration::from_secs(3600),
+        cache_dir: None,
+    };
+    let updater = FilterUpdater::new(config).unwrap();
+    let body = "[Adblock Plus 2.0]\n! Title: Test\n||ads.com^";
+    let list = format!(
+        "[Adblock Plus 2.0]\n! Checksum: {}\n! Title: Test\n||ads.com^",
+        checksum::list_checksum(body)
+    );
+
+    // Then: The intact list verifies and a tampered copy is rejected
+    assert!(updater
+        .verify_download("https://example.com/list.txt", &list)
+        .is_ok());
+    let tampered = list.replace("ads.com", "evil.com");
+    assert!(matches!(
+        updater.verify_download("https://example.com/list.txt", &tampered),
+        Err(DownloadError::ChecksumMismatch { .. })
+    ));
+}
+
+#[test]
+fn should_fail_over_to_mirrors_and_demote_a_failing_url() {
+    // Given: A list whose primary URL is down, with a working mirror
+    let primary = "https://invalid.example.com/filters.txt";
+    let mirror = "https://mirror.example.com/filters.txt";
+    let config = UpdateConfig {
+        urls: vec![primary.to_string()],
+        presets: Vec::new(),
+        mirrors: HashMap::from([(primary.to_string(), vec![mirror.to_string()])]),
+        update_interval: Duration::from_secs(3600),
+        cache_dir: None,
+    };
+    let mut updater = FilterUpdater::new(config).unwrap();
+
+    // When: Updating a few times
+    for _ in 0..4 {
+        let filters = updater.auto_update().unwrap();
+        assert!(filters.contains("downloaded-ads.com"));
+    }
+
+    // Then: The primary URL is skipped once it failed repeatedly
+    assert_eq!(updater.consecutive_failures(primary), 3);
+    assert_eq!(updater.consecutive_failures(mirror), 0);
+}
@@ -3,12 +3,13 @@
 //! Test the integration between filtering and statistics tracking
 
 use adblock_core::{AdBlockCore, Config};
+use std::time::{Duration, Instant};
 
 #[test]
 fn should_track_statistics_when_blocking() {
     // Given: An AdBlockCore instance with default config
     let config = Config::default();
-    let mut core = AdBlockCore::new(config).expect("Failed to create core");
+    let core = AdBlockCore::new(config).expect("Failed to create core");
 
     // When: Checking URLs that should be blocked and allowed
     let test_cases = vec![
@@ -33,7 +34,7 @@ fn should_track_statistics_when_blocking() {
 #[test]
 fn should_track_domain_specific_statistics() {
     // Given: An AdBlockCore instance
-    let mut core = AdBlockCore::new(Config::default()).unwrap();
+    let core = AdBlockCore::new(Config::default()).unwrap();
 
     // When: Multiple requests from same domains
     core.check_url("https://ads.doubleclick.net/1", 100);
@@ -52,7 +53,7 @@ fn should_track_domain_specific_statistics() {
 #[test]
 fn should_handle_pattern_matching_with_statistics() {
     // Given: An AdBlockCore with pattern rules
-    let mut core = AdBlockCore::with_patterns(vec![
+    let core = AdBlockCore::with_patterns(vec![
         "*/ads/*".to_string(),
         "||analytics.google.com^".to_string(),
     ])
@@ -73,7 +74,7 @@ fn should_handle_pattern_matching_with_statistics() {
 #[test]
 fn should_provide_recent_blocking_history() {
     // Given: An AdBlockCore instance
-    let mut core = AdBlockCore::new(Config::default()).unwrap();
+    let core = AdBlockCore::new(Config::default()).unwrap();
 
     // When: Making several requests
     core.check_url("https://ad1.com", 100);
@@ -90,3 +91,794 @@ fn should_provide_recent_blocking_history() {
     assert_eq!(recent[1].domain, "example.com");
     assert_eq!(recent[2].domain, "ad1.com");
 }
+
+#[test]
+fn should_skip_backup_rules_covered_by_subscriptions() {
+    use adblock_core::backup::BackupData;
+    use adblock_core::rule_import::{ImportStatus, RuleImporter};
+
+    // Given: A backup whose custom rules overlap a subscribed list
+    let core = AdBlockCore::new(Config::default()).expect("Failed to create core");
+    let rules = vec![
+        "||tracker.net^".to_string(),
+        "||pixel.tracker.net^".to_string(),
+        "||my-rule.com^".to_string(),
+    ];
+    let mut backup = BackupData::create(Config::default(), rules, &core.get_statistics());
+    let mut importer = RuleImporter::new();
+    importer.add_subscription("||tracker.net^");
+
+    // When: Restoring the backup
+    let report = backup.dedup_custom_rules(&importer);
+
+    // Then: Only the uncovered rule remains
+    assert_eq!(report.count(ImportStatus::Redundant), 2);
+    assert_eq!(backup.custom_rules, vec!["||my-rule.com^"]);
+}
+
+#[test]
+fn should_publish_rule_updates_on_event_bus() {
+    use adblock_core::event_bus::EngineEvent;
+    use std::sync::{Arc, Mutex};
+
+    // Given: A core with a listener on its event bus
+    let mut core = AdBlockCore::from_filter_list("||ads.com^").expect("Failed to create core");
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let sink = Arc::clone(&received);
+    core.events().subscribe(move |event| {
+        if let EngineEvent::RuleSetUpdated { rule_count } = event {
+            sink.lock().unwrap().push(*rule_count);
+        }
+    });
+
+    // When: Reloading the filter list
+    core.load_filter_list("||ads.com^\n||tracker.net^")
+        .expect("Failed to load list");
+
+    // Then: The listener sees the new rule count
+    assert_eq!(*received.lock().unwrap(), vec![2]);
+}
+
+#[test]
+fn should_hot_reload_filters_while_checking_requests() {
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    // Given: A core with recorded statistics, checked from another thread
+    let core = AdBlockCore::from_filter_list("||ads.com^").expect("Failed to create core");
+    core.check_url("https://ads.com/banner.js", 100);
+    let core = Arc::new(core);
+    let done = Arc::new(AtomicBool::new(false));
+    let checks = Arc::new(AtomicUsize::new(0));
+    let checker = {
+        let core = Arc::clone(&core);
+        let done = Arc::clone(&done);
+        let checks = Arc::clone(&checks);
+        std::thread::spawn(move || {
+            while !done.load(Ordering::SeqCst) {
+                // ads.com is blocked by both the old and the new rules
+                assert!(core.should_block("https://ads.com/x.js").should_block);
+                checks.fetch_add(1, Ordering::SeqCst);
+            }
+        })
+    };
+    while checks.load(Ordering::SeqCst) == 0 {
+        std::thread::yield_now();
+    }
+
+    // When: Reloading the filters
+    core.reload_filters("||ads.com^\n||tracker.net^")
+        .expect("Failed to reload");
+    done.store(true, Ordering::SeqCst);
+
+    // Then: Checks kept passing, the new rules are live and stats survive
+    checker.join().unwrap();
+    assert!(core.should_block("https://tracker.net/t.js").should_block);
+    assert_eq!(core.get_statistics().get_blocked_count(), 1);
+}
+
+#[test]
+fn should_reload_filters_in_the_background() {
+    use std::sync::mpsc;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    // Given: A core shared with the host
+    let core = Arc::new(AdBlockCore::from_filter_list("||ads.com^").unwrap());
+
+    // When: Reloading the filters in the background
+    let (done, finished) = mpsc::channel();
+    core.reload_filters_in_background("||ads.com^\n||tracker.net^".to_string(), move |result| {
+        done.send(result).unwrap();
+    })
+    .expect("Failed to start the reload");
+
+    // Then: The callback reports the new rules once they are live
+    let result = finished.recv_timeout(Duration::from_secs(10)).unwrap();
+    assert_eq!(result, Ok(2));
+    assert!(core.should_block("https://tracker.net/t.js").should_block);
+}
+
+#[test]
+fn should_patch_rules_while_an_engine_is_in_use() {
+    use adblock_core::filter_updater::FilterListDiff;
+
+    // Given: A core whose engine is held by a check in flight
+    let mut core = AdBlockCore::from_filter_list("||ads.com^").unwrap();
+    let in_flight = core.engine();
+
+    // When: Applying a diff
+    let diff = FilterListDiff {
+        added: vec!["||tracker.net^".to_string()],
+        removed: vec!["||ads.com^".to_string()],
+    };
+    core.apply_filter_diff(&diff)
+        .expect("Failed to apply the diff");
+
+    // Then: The patched copy is live and the held engine is unchanged
+    assert!(core.should_block("https://tracker.net/t.js").should_block);
+    assert!(!core.should_block("https://ads.com/x.js").should_block);
+    assert!(in_flight.should_block("https://ads.com/x.js").should_block);
+}
+
+#[test]
+fn should_check_urls_from_many_threads_through_a_shared_core() {
+    use std::sync::Arc;
+
+    // Given: A core shared between threads without a lock
+    let core = Arc::new(AdBlockCore::from_filter_list("||ads.com^").unwrap());
+
+    // When: Four threads check requests at the same time
+    let threads: Vec<_> = (0..4)
+        .map(|_| {
+            let core = Arc::clone(&core);
+            std::thread::spawn(move || {
+                for _ in 0..100 {
+                    assert!(core.check_url("https://ads.com/banner.js", 10).should_block);
+                    assert!(!core.check_url("https://example.com/", 0).should_block);
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    // Then: Every check is counted
+    let stats = core.get_statistics();
+    assert_eq!(stats.get_blocked_count(), 400);
+    assert_eq!(stats.get_allowed_count(), 400);
+    assert_eq!(stats.data_saved(), 4000);
+}
+
+#[test]
+fn should_notify_the_event_listener_of_checked_requests() {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    // Given: A core with a listener counting blocked requests
+    let core = AdBlockCore::from_filter_list("||ads.com^").unwrap();
+    let blocked = Arc::new(AtomicU64::new(0));
+    let counter = Arc::clone(&blocked);
+    core.set_event_listener(Box::new(move |event| {
+        if event.blocked {
+            counter.fetch_add(1, Ordering::SeqCst);
+        }
+    }));
+
+    // When: Checking blocked and allowed requests
+    core.check_url("https://ads.com/banner.js", 0);
+    core.check_url("https://ads.com/pixel.gif", 0);
+    core.check_url("https://example.com/", 0);
+
+    // Then: The listener saw both blocks, and nothing once cleared
+    assert_eq!(blocked.load(Ordering::SeqCst), 2);
+    core.clear_event_listener();
+    core.check_url("https://ads.com/banner.js", 0);
+    assert_eq!(blocked.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn should_keep_whitelist_across_reloads() {
+    // Given: A core whose config whitelists a site
+    let config = Config {
+        filter_lists: Vec::new(),
+        whitelist: vec!["news.example".to_string()],
+        ..Config::default()
+    };
+    let core = AdBlockCore::new(config).expect("Failed to create core");
+
+    // When: Loading rules that block the site
+    core.reload_filters("||news.example^\n||ads.com^")
+        .expect("Failed to reload");
+
+    // Then: The whitelisted site and its subdomains stay allowed
+    assert!(
+        !core
+            .should_block("https://cdn.news.example/app.js")
+            .should_block
+    );
+    assert!(core.should_block("https://ads.com/banner.js").should_block);
+}
+
+#[test]
+fn should_upgrade_version_one_backup() {
+    use adblock_core::backup::BackupData;
+    use adblock_core::config::CONFIG_VERSION;
+
+    // Given: A backup written by version 1 with an unversioned config
+    let json = r#"{
+        "version": 1,
+        "created_at": {"secs_since_epoch": 1700000000, "nanos_since_epoch": 0},
+        "config": {"debug": false, "max_memory_mb": 30, "update_interval": 86400,
+                   "filter_lists": [], "custom_rules_path": ""},
+        "custom_rules": ["||my-rule.com^"],
+        "statistics": {"blocked_count": 3, "allowed_count": 5, "data_saved": 0, "top_domains": []}
+    }"#;
+
+    // When: Importing it
+    let backup = BackupData::from_json(json).expect("Failed to import backup");
+
+    // Then: Both backup and config are upgraded and valid
+    assert_eq!(backup.version, BackupData::CURRENT_VERSION);
+    assert_eq!(backup.config.version, CONFIG_VERSION);
+    assert_eq!(backup.config.custom_rules_path, None);
+    assert!(backup.validate().is_ok());
+}
+
+#[test]
+fn should_restore_custom_rules_and_statistics_from_backup() {
+    use adblock_core::backup::BackupData;
+
+    // Given: A core with custom rules, a whitelist and statistics
+    let config = Config {
+        filter_lists: Vec::new(),
+        whitelist: vec!["shop.example".to_string()],
+        ..Config::default()
+    };
+    let mut source = AdBlockCore::new(config).expect("Failed to create core");
+    source
+        .set_custom_rules(vec!["||my-tracker.io^".to_string()])
+        .expect("Failed to set custom rules");
+    source.check_url("https://my-tracker.io/t.js", 2048);
+    let gzip = source.create_backup().to_gzip().expect("Failed to export");
+
+    // When: Restoring into a fresh core
+    let mut target = AdBlockCore::with_patterns(Vec::new()).expect("Failed to create core");
+    let backup = BackupData::from_bytes(&gzip).expect("Failed to import");
+    target.restore_backup(&backup).expect("Failed to restore");
+
+    // Then: Rules, whitelist and statistics come back
+    assert!(
+        target
+            .should_block("https://my-tracker.io/p.gif")
+            .should_block
+    );
+    assert!(target.config().is_whitelisted("shop.example"));
+    assert_eq!(target.get_statistics().get_blocked_count(), 1);
+    assert_eq!(target.get_statistics().get_data_saved(), 2048);
+
+    // And: Custom rules survive a filter list reload
+    target
+        .load_filter_list("||ads.com^")
+        .expect("Failed to load list");
+    assert!(
+        target
+            .should_block("https://my-tracker.io/p.gif")
+            .should_block
+    );
+}
+
+#[test]
+fn should_keep_rolling_backups_within_retention() {
+    use adblock_core::backup::{BackupManager, RetentionPolicy};
+
+    // Given: A backup directory holding an old weekly backup and recent ones
+    let dir = std::env::temp_dir().join("adblock_rolling_backups");
+    std::fs::remove_dir_all(&dir).ok();
+    std::fs::create_dir_all(&dir).unwrap();
+    let week_ago = chrono::Utc::now() - chrono::Duration::days(8);
+    let old_name = format!(
+        "adblock_backup_{}.json",
+        week_ago.format("%Y%m%d_%H%M%S_000")
+    );
+    let ancient = "adblock_backup_20000101_000000_000.json";
+
+    let mut manager = BackupManager::new(Some(dir.clone()));
+    manager.set_retention(RetentionPolicy {
+        keep_last: 2,
+        weekly_for_weeks: 4,
+    });
+    let mut core = AdBlockCore::with_patterns(Vec::new()).expect("Failed to create core");
+    let backup = core.create_backup();
+    manager.save_backup(&backup, &old_name).unwrap();
+    manager.save_backup(&backup, ancient).unwrap();
+
+    // When: Config changes trigger automatic backups
+    core.set_auto_backup(Some(manager));
+    for interval in [3600, 7200, 10800] {
+        let config = Config {
+            update_interval: interval,
+            ..Config::default()
+        };
+        core.update_config(config).expect("Config should be valid");
+    }
+
+    // Then: The two newest and the weekly backup remain
+    let manager = BackupManager::new(Some(dir.clone()));
+    let backups = manager.list_backups().unwrap();
+    assert_eq!(backups.len(), 3);
+    assert!(backups.contains(&old_name));
+    assert!(!backups.contains(&ancient.to_string()));
+
+    // And: The newest backup holds the latest config
+    let latest = manager.restore_latest().unwrap();
+    assert_eq!(latest.config.update_interval, 10800);
+
+    // Cleanup
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn should_track_engine_lifecycle_in_analytics_when_enabled() {
+    // Given: A core with analytics left at the default (off)
+    let mut core = AdBlockCore::with_patterns(Vec::new()).expect("Failed to create core");
+    core.load_filter_list("||ads.example^").unwrap();
+    assert!(core.analytics().export_events(10).is_empty());
+
+    // When: Analytics is enabled and the rules change
+    let config = Config {
+        analytics_enabled: true,
+        ..Config::default()
+    };
+    core.update_config(config).expect("Config should be valid");
+    core.load_filter_list("||ads.example^\n||tracker.example^")
+        .unwrap();
+    core.set_custom_rules(vec!["||custom.example^".to_string()])
+        .unwrap();
+
+    // Then: The reload and the rule count change are tracked
+    let names: Vec<String> = core
+        .analytics()
+        .export_events(10)
+        .into_iter()
+        .map(|event| event.name)
+        .collect();
+    assert_eq!(names, vec!["rule_count_changed", "engine_reloaded"]);
+}
+
+#[test]
+fn should_publish_memory_pressure_when_over_the_memory_limit() {
+    // Given: A core with a large rule set and a pressure listener
+    let mut core = AdBlockCore::with_patterns(Vec::new()).expect("Failed to create core");
+    let list: String = (0..40_000)
+        .map(|i| format!("||ads{i}.example^\n"))
+        .collect();
+    core.load_filter_list(&list).unwrap();
+    let pressure = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let seen = std::sync::Arc::clone(&pressure);
+    core.events().subscribe(move |event| {
+        if let adblock_core::event_bus::EngineEvent::MemoryPressure { .. } = event {
+            seen.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        }
+    });
+    assert!(core.enforce_memory_limit());
+
+    // When: The memory limit is lowered below the engine's usage
+    let config = Config {
+        max_memory_mb: 1,
+        ..Config::default()
+    };
+    core.update_config(config).expect("Config should be valid");
+
+    // Then: Memory pressure is handled and the rules stay usable
+    assert_eq!(pressure.load(std::sync::atomic::Ordering::SeqCst), 1);
+    assert!(!core.enforce_memory_limit());
+    assert!(core.should_block("https://ads123.example/x").should_block);
+}
+
+#[test]
+fn should_check_the_memory_limit_every_thousand_requests_while_paused() {
+    use adblock_core::memory_optimization::MEMORY_CHECK_INTERVAL;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    // Given: A paused core over its memory limit with a pressure listener
+    let list: String = (0..40_000)
+        .map(|i| format!("||ads{i}.example^\n"))
+        .collect();
+    let mut core = AdBlockCore::from_filter_list(&list).expect("Failed to create core");
+    let config = Config {
+        max_memory_mb: 1,
+        ..Config::default()
+    };
+    core.update_config(config).expect("Config should be valid");
+    core.pause(Duration::from_secs(60));
+    let pressure = Arc::new(AtomicUsize::new(0));
+    let seen = Arc::clone(&pressure);
+    core.events().subscribe(move |event| {
+        if let adblock_core::event_bus::EngineEvent::MemoryPressure { .. } = event {
+            seen.fetch_add(1, Ordering::SeqCst);
+        }
+    });
+
+    // When: Checking requests the engine never sees
+    for _ in 0..MEMORY_CHECK_INTERVAL {
+        core.check_url("https://ads1.example/x.js", 0);
+    }
+
+    // Then: The limit is checked once per interval, not on every request
+    assert_eq!(pressure.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn should_back_up_in_the_background_at_blocked_milestones() {
+    use adblock_core::backup::{BackupManager, BLOCKED_MILESTONE};
+
+    // Given: A core backing up automatically into an empty directory
+    let dir = std::env::temp_dir().join("adblock_milestone_backup_test");
+    std::fs::remove_dir_all(&dir).ok();
+    let mut core = AdBlockCore::from_filter_list("||ads.com^").expect("Failed to create core");
+    core.set_auto_backup(Some(BackupManager::new(Some(dir.clone()))));
+
+    // When: The blocked count reaches the milestone
+    for _ in 0..BLOCKED_MILESTONE {
+        core.check_url("https://ads.com/banner.js", 0);
+    }
+
+    // Then: A backup with the milestone's statistics is written
+    let manager = BackupManager::new(Some(dir.clone()));
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let latest = loop {
+        if let Ok(latest) = manager.restore_latest() {
+            break latest;
+        }
+        assert!(Instant::now() < deadline, "No backup was written");
+        std::thread::sleep(Duration::from_millis(10));
+    };
+    assert_eq!(latest.statistics.blocked_count, BLOCKED_MILESTONE);
+
+    // Cleanup
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn should_import_and_export_user_rules_as_text() {
+    // Given: A core with custom rules and a whitelisted site
+    let mut source = AdBlockCore::with_patterns(Vec::new()).expect("Failed to create core");
+    source
+        .set_custom_rules(vec!["||my-tracker.io^".to_string()])
+        .expect("Failed to set custom rules");
+    let mut config = source.config().clone();
+    config.whitelist = vec!["shop.example".to_string()];
+    source
+        .update_config(config)
+        .expect("Config should be valid");
+    let text = source.export_user_rules();
+
+    // When: Importing the text, plus a duplicate and an invalid line, elsewhere
+    let mut target = AdBlockCore::with_patterns(Vec::new()).expect("Failed to create core");
+    target
+        .set_custom_rules(vec!["||my-tracker.io^".to_string()])
+        .expect("Failed to set custom rules");
+    let report = target
+        .import_user_rules(&format!("{text}||ads.example^$scirpt\n||new-ad.example^\n"))
+        .expect("Failed to import");
+
+    // Then: New rules and whitelist entries are added, the rest reported
+    assert_eq!(
+        target.custom_rules(),
+        ["||my-tracker.io^", "||new-ad.example^"]
+    );
+    assert!(target.config().is_whitelisted("shop.example"));
+    assert_eq!(report.whitelist_added, vec!["shop.example"]);
+    assert_eq!(
+        report
+            .rules
+            .count(adblock_core::rule_import::ImportStatus::Duplicate),
+        1
+    );
+    assert_eq!(report.invalid.len(), 1);
+    assert_eq!(report.invalid[0].text, "||ads.example^$scirpt");
+    assert!(
+        target
+            .should_block("https://new-ad.example/x.js")
+            .should_block
+    );
+}
+
+#[test]
+fn should_apply_per_site_blocking_modes() {
+    use adblock_core::resource_type::{RequestContext, ResourceType};
+    use adblock_core::site_policy::SiteMode;
+
+    // Given: A core with network and generic cosmetic rules
+    let mut core =
+        AdBlockCore::from_filter_list("||ads.example^\n##.banner").expect("Failed to create core");
+    let request = |page: &str| {
+        RequestContext {
+            resource_type: ResourceType::Script,
+            ..RequestContext::default()
+        }
+        .with_document_url(page)
+    };
+
+    // When: Blocking is off for one site and standard for another
+    core.set_site_mode("off.example", SiteMode::Off);
+    core.set_site_mode("news.example", SiteMode::Standard);
+
+    // Then: Requests from the disabled site are allowed
+    let url = "https://ads.example/ad.js";
+    assert!(
+        !core
+            .should_block_with_context(url, &request("https://www.off.example/"))
+            .should_block
+    );
+    assert!(
+        core.should_block_with_context(url, &request("https://news.example/"))
+            .should_block
+    );
+    assert!(
+        !core
+            .should_block_with_context(
+                "https://off.example/",
+                &RequestContext::from_url("https://off.example/")
+                    .with_document_url("https://off.example/")
+            )
+            .should_block
+    );
+
+    // And: Only aggressive sites get cosmetic rules
+    assert!(core
+        .get_cosmetic_result("https://news.example/")
+        .hide_selectors
+        .is_empty());
+    assert_eq!(
+        core.get_cosmetic_result("https://other.example/")
+            .hide_selectors,
+        vec![".banner"]
+    );
+
+    // And: Modes are persisted in the config
+    let config = adblock_core::Config::from_json(&core.config().to_json().unwrap()).unwrap();
+    assert_eq!(config.site_policy.mode_for("off.example"), SiteMode::Off);
+    assert!(core.clear_site_mode("off.example"));
+    assert_eq!(core.site_mode("off.example"), SiteMode::Aggressive);
+}
+
+#[test]
+fn should_pause_and_resume_blocking() {
+    use adblock_core::pause::PauseWindow;
+
+    // Given: A core blocking an ad domain
+    let mut core = AdBlockCore::from_filter_list("||ads.example^").expect("Failed to create core");
+    let url = "https://ads.example/banner.js";
+
+    // When: Blocking is paused for 30 minutes
+    core.pause(std::time::Duration::from_secs(30 * 60));
+
+    // Then: Requests are allowed and counted as paused
+    assert!(core.is_paused());
+    assert!(core.paused_until().is_some());
+    assert!(!core.check_url(url, 100).should_block);
+    assert_eq!(core.get_statistics().get_paused_count(), 1);
+    assert_eq!(core.get_statistics().get_allowed_count(), 1);
+
+    // And: Resuming blocks again
+    core.resume();
+    assert!(core.check_url(url, 100).should_block);
+
+    // And: A schedule window covering the whole day pauses blocking
+    let mut config = core.config().clone();
+    let midnight = chrono::NaiveTime::from_hms_opt(0, 0, 0).unwrap();
+    let almost_midnight = chrono::NaiveTime::from_hms_opt(23, 59, 59).unwrap();
+    config.pause_schedule = vec![
+        PauseWindow::new(Vec::new(), midnight, almost_midnight),
+        PauseWindow::new(Vec::new(), almost_midnight, midnight),
+    ];
+    core.update_config(config).expect("Config should be valid");
+    assert!(core.is_paused());
+    assert!(!core.should_block(url).should_block);
+}
+
+#[test]
+fn should_block_enabled_content_categories() {
+    use adblock_core::category_filter::{CategoryFilter, ContentCategory};
+    use adblock_core::network::NetworkFilter;
+    use adblock_core::BlockKind;
+    use std::sync::Arc;
+
+    // Given: Gambling and social network lists with only gambling enabled
+    let mut filter = CategoryFilter::new();
+    filter.load_list(ContentCategory::Gambling, "0.0.0.0 casino.example");
+    filter.load_list(ContentCategory::SocialNetworks, "social.example");
+    filter.set_enabled(ContentCategory::Gambling, true);
+    let filter = Arc::new(filter);
+
+    let mut core = AdBlockCore::from_filter_list("@@||casino.example^").unwrap();
+    core.set_category_filter(Some(Arc::clone(&filter))).unwrap();
+    let mut network = NetworkFilter::new();
+    network.set_category_filter(Some(filter));
+
+    // When: Requests reach the core and DNS filter
+    let decision = core.check_url("https://www.casino.example/", 0);
+    let social = core.check_url("https://social.example/", 0);
+
+    // Then: Enabled categories block despite exception rules
+    assert_eq!(
+        decision.kind,
+        Some(BlockKind::Category(ContentCategory::Gambling))
+    );
+    assert!(!social.should_block);
+    assert!(network.is_blocked("casino.example"));
+    assert!(!network.is_blocked("social.example"));
+
+    // And: Category blocks have their own statistics bucket
+    let stats = core.get_statistics();
+    assert_eq!(stats.total_blocked(), 1);
+    assert_eq!(
+        stats
+            .content_category_breakdown()
+            .get(&ContentCategory::Gambling),
+        Some(&1)
+    );
+
+    // And: The filter survives a reload
+    core.load_filter_list("||ads.com^").unwrap();
+    assert!(core.should_block("https://casino.example/").should_block);
+}
+
+#[test]
+fn should_keep_query_log_on_disk_within_privacy_mode() {
+    use adblock_core::query_log::{QueryFilter, QueryVerdict};
+    use adblock_core::statistics::StatisticsPrivacy;
+
+    // Given: A core logging queries to a file
+    let path = std::env::temp_dir().join(format!("adblock_query_log_{}", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let mut core = AdBlockCore::from_filter_list("||ads.com^").unwrap();
+    core.enable_query_log(Some(&path), 64 * 1024).unwrap();
+
+    // When: Requests are checked and the log is reopened
+    core.check_url("https://ads.com/banner.js", 0);
+    core.check_url_for_app("https://example.com/", 0, "com.example.app");
+    core.enable_query_log(Some(&path), 64 * 1024).unwrap();
+
+    // Then: The entries are searchable by verdict and domain
+    let log = core.query_log().unwrap();
+    assert_eq!(log.len(), 2);
+    let blocked = QueryFilter {
+        verdict: Some(QueryVerdict::Blocked),
+        ..QueryFilter::default()
+    };
+    let entries = log.search(&blocked, 0, 10);
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].domain, "ads.com");
+    assert!(entries[0].reason.is_some());
+    let by_domain = QueryFilter {
+        domain: Some("EXAMPLE".to_string()),
+        ..QueryFilter::default()
+    };
+    assert_eq!(
+        log.search(&by_domain, 0, 10)[0].app.as_deref(),
+        Some("com.example.app")
+    );
+    drop(log);
+
+    // When: Switching to counts-only statistics
+    let mut config = core.config().clone();
+    config.statistics_privacy = StatisticsPrivacy::CountsOnly;
+    core.update_config(config).unwrap();
+    core.check_url("https://ads.com/other.js", 0);
+
+    // Then: The log is emptied and nothing new is logged
+    assert!(core.query_log().unwrap().is_empty());
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn should_let_allowed_domains_override_dns_blocks() {
+    use adblock_core::network::NetworkFilter;
+
+    // Given: A DNS filter blocking a domain with an exception for a subdomain
+    let mut network = NetworkFilter::new();
+    network.load_from_rules(&[
+        "||ads.example^".to_string(),
+        "@@||cdn.ads.example^".to_string(),
+    ]);
+
+    // When: Whitelisting another blocked domain in the app
+    network.add_blocked_domain("shop.example");
+    network.add_allowed_domain("*.shop.example");
+
+    // Then: Allow entries take precedence, including for subdomains
+    assert!(network.is_blocked("ads.example"));
+    assert!(!network.is_blocked("img.cdn.ads.example"));
+    assert!(!network.is_blocked("www.shop.example"));
+    assert!(network.remove_allowed_domain("shop.example"));
+    assert!(network.is_blocked("www.shop.example"));
+}
+
+#[test]
+fn should_keep_dns_filter_in_sync_with_rule_reloads() {
+    use adblock_core::rule_set::RuleSet;
+
+    // Given: A core and a network filter linked to its rules
+    let mut core = AdBlockCore::from_filter_list("||ads.example^").unwrap();
+    core.set_custom_rules(vec!["||custom.example^".to_string()])
+        .unwrap();
+    let network = core.network_filter();
+    assert!(network.is_blocked("ads.example"));
+    assert!(network.is_blocked("custom.example"));
+
+    // When: Reloading the rules through the single reload entry point
+    let mut rule_set = RuleSet::new();
+    rule_set.add_list("Trackers", "||tracker.example^\n@@||ok.tracker.example^");
+    core.reload_rule_set(rule_set).unwrap();
+
+    // Then: Both the URL engine and the existing DNS filter follow
+    assert!(!network.is_blocked("ads.example"));
+    assert!(network.is_blocked("cdn.tracker.example"));
+    assert!(!network.is_blocked("ok.tracker.example"));
+    assert!(
+        core.check_url("https://cdn.tracker.example/t.js", 0)
+            .should_block
+    );
+    assert!(!core.check_url("https://ads.example/ad.js", 0).should_block);
+    assert_eq!(core.rule_set().len(), 1);
+}
+
+#[test]
+fn should_sample_rule_hits_for_rule_reordering() {
+    // Given: A core checking requests
+    let core = AdBlockCore::from_filter_list("||tracker.net^\n||ads.com^").unwrap();
+
+    // When: A rule matches, before and after a reload
+    core.check_url("https://ads.com/banner.js", 0);
+    core.reload_filters("||ads.com^").expect("Failed to reload");
+    core.check_url("https://ads.com/banner.js", 0);
+
+    // Then: The hits are counted for the maintenance task to sort rules by
+    let engine = core.engine();
+    let hits = engine.rule_hits().expect("Rule hits are tracked");
+    assert!(hits.hits("||ads.com^") > 0);
+}
+
+#[test]
+fn should_run_maintenance_in_the_background_while_idle() {
+    use adblock_core::maintenance::DeviceConditions;
+    use adblock_core::FilterEngine;
+
+    // Given: A core whose engine has cached a verdict
+    let mut core = AdBlockCore::with_patterns(Vec::new()).unwrap();
+    let mut engine = FilterEngine::from_filter_list("||ads.com^").unwrap();
+    engine.enable_verdict_cache(Duration::from_secs(60), 16);
+    core.swap_engine(engine);
+    core.check_url("https://ads.com/banner.js", 0);
+    let cached = || core.engine().verdict_cache().unwrap().stats().entries;
+    assert_eq!(cached(), 1);
+
+    // When: The host reports the device idle
+    core.set_device_conditions(DeviceConditions {
+        idle: true,
+        charging: false,
+    });
+
+    // Then: The caches are compacted in the background
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while cached() > 0 && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(cached(), 0);
+
+    // And: Tasks needing power wait for charging
+    assert!(core.run_maintenance().completed.is_empty());
+
+    // And: Stopping maintenance ends the background runs
+    core.stop_maintenance();
+    core.set_device_conditions(DeviceConditions {
+        idle: true,
+        charging: true,
+    });
+    assert!(core.run_maintenance().completed.is_empty());
+}
@@ -0,0 +1,81 @@
+//! Logging Tests
+//!
+//! Test the in-memory engine log and its debug level. Logging is
+//! process-wide, so these tests live in their own binary. They rely on the
+//! debug record of the download stub, so don't run with the `http` feature.
+
+#![cfg(not(feature = "http"))]
+
+use adblock_core::ffi::*;
+use adblock_core::{AdBlockCore, Config, FilterUpdater, UpdateConfig};
+use std::collections::HashMap;
+use std::ffi::CStr;
+use std::time::Duration;
+
+fn download(url: &str) {
+    let mut updater = FilterUpdater::new(UpdateConfig {
+        urls: vec![url.to_string()],
+        presets: Vec::new(),
+        mirrors: HashMap::new(),
+        update_interval: Duration::from_secs(3600),
+        cache_dir: None,
+    })
+    .unwrap();
+    let _ = updater.force_update();
+}
+
+fn recent_logs() -> Vec<serde_json::Value> {
+    let mut error = AdblockErrorCode::NullPointer;
+    let json = adblock_get_recent_logs(&mut error);
+    assert_eq!(error, AdblockErrorCode::Ok);
+    let logs: serde_json::Value =
+        serde_json::from_str(unsafe { CStr::from_ptr(json) }.to_str().unwrap()).unwrap();
+    unsafe { adblock_free_string(json) };
+    logs.as_array().unwrap().clone()
+}
+
+fn logged_for(url: &str) -> Vec<serde_json::Value> {
+    recent_logs()
+        .into_iter()
+        .filter(|record| record["fields"]["url"] == url)
+        .collect()
+}
+
+#[test]
+fn should_keep_recent_logs_at_the_configured_level() {
+    // Given: An engine without debug logging
+    let mut core = AdBlockCore::new(Config::default()).unwrap();
+
+    // When: Downloading a list
+    download("https://lists.example/quiet.txt");
+
+    // Then: Its debug record is not kept
+    assert!(logged_for("https://lists.example/quiet.txt").is_empty());
+
+    // When: Debug logging is turned on and a list is downloaded
+    core.update_config(Config {
+        debug: true,
+        ..Config::default()
+    })
+    .unwrap();
+    download("https://lists.example/verbose.txt");
+
+    // Then: The debug record is kept with its structured fields
+    let logged = logged_for("https://lists.example/verbose.txt");
+    assert_eq!(logged.len(), 1);
+    assert_eq!(logged[0]["level"], "DEBUG");
+    assert_eq!(logged[0]["target"], "adblock_core::filter_updater");
+    assert!(logged[0]["timestamp"].is_string());
+
+    // When: A download fails
+    download("https://invalid.example/list.txt");
+
+    // Then: The warning names the list and the error
+    let logged = logged_for("https://invalid.example/list.txt");
+    let warning = logged
+        .iter()
+        .find(|record| record["message"] == "Failed to download filter list")
+        .unwrap();
+    assert_eq!(warning["level"], "WARN");
+    assert!(warning["fields"]["error"].is_string());
+}
@@ -0,0 +1,137 @@
+//! Proxy Tests
+//!
+//! Run real HTTP traffic through the local proxy server
+
+#![cfg(feature = "proxy")]
+
+use adblock_core::proxy::ProxyServer;
+use adblock_core::FilterEngine;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+
+/// Upstream server answering one request with its request line
+fn start_upstream() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut head = Vec::new();
+            let mut byte = [0u8; 1];
+            while !head.ends_with(b"\r\n\r\n") && stream.read(&mut byte).unwrap() == 1 {
+                head.push(byte[0]);
+            }
+            let head = String::from_utf8(head).unwrap();
+            let body = head.lines().next().unwrap().to_string();
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{body}",
+                body.len()
+            )
+            .unwrap();
+        }
+    });
+    port
+}
+
+fn start_proxy() -> ProxyServer {
+    let engine = FilterEngine::from_filter_list("||ads.example^\n||blocked.localhost^").unwrap();
+    ProxyServer::start("127.0.0.1:0", Arc::new(engine)).unwrap()
+}
+
+fn send(proxy: &ProxyServer, request: &str) -> String {
+    let mut stream = TcpStream::connect(proxy.local_addr()).unwrap();
+    stream.write_all(request.as_bytes()).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+#[test]
+fn should_reject_blocked_hosts() {
+    // Given: A proxy whose engine blocks ads.example
+    let proxy = start_proxy();
+
+    // When: Tunneling or fetching through it to the blocked host
+    let connect = send(&proxy, "CONNECT ads.example:443 HTTP/1.1\r\n\r\n");
+    let get = send(
+        &proxy,
+        "GET http://ads.example/banner.js HTTP/1.1\r\nHost: ads.example\r\n\r\n",
+    );
+
+    // Then: Both are refused before any upstream connection
+    assert!(connect.starts_with("HTTP/1.1 403"));
+    assert!(get.starts_with("HTTP/1.1 403"));
+}
+
+#[test]
+fn should_forward_allowed_requests() {
+    // Given: A proxy and an upstream server
+    let upstream = start_upstream();
+    let proxy = start_proxy();
+
+    // When: Fetching an absolute URI through the proxy
+    let response = send(
+        &proxy,
+        &format!(
+            "GET http://127.0.0.1:{upstream}/page?id=1 HTTP/1.1\r\nHost: 127.0.0.1\r\nProxy-Connection: keep-alive\r\n\r\n"
+        ),
+    );
+
+    // Then: Upstream receives the origin-form request
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+    assert!(response.ends_with("GET /page?id=1 HTTP/1.1"));
+}
+
+#[test]
+fn should_tunnel_allowed_connect() {
+    // Given: A proxy and an upstream server
+    let upstream = start_upstream();
+    let proxy = start_proxy();
+
+    // When: Opening a tunnel and speaking HTTP through it
+    let mut stream = TcpStream::connect(proxy.local_addr()).unwrap();
+    write!(stream, "CONNECT 127.0.0.1:{upstream} HTTP/1.1\r\n\r\n").unwrap();
+    let mut established = [0u8; 39];
+    stream.read_exact(&mut established).unwrap();
+    stream
+        .write_all(b"GET /tunneled HTTP/1.1\r\nHost: 127.0.0.1\r\n\r\n")
+        .unwrap();
+    let mut response = vec![0u8; 64];
+    let read = stream.read(&mut response).unwrap();
+
+    // Then: The tunnel is established and bytes pass through unchanged
+    assert_eq!(&established, b"HTTP/1.1 200 Connection Established\r\n\r\n");
+    assert!(String::from_utf8_lossy(&response[..read]).starts_with("HTTP/1.1 200 OK"));
+}
+
+#[test]
+fn should_refuse_connections_beyond_the_limit() {
+    use std::time::Duration;
+
+    // Given: A proxy serving one connection, with one more waiting
+    let engine = FilterEngine::from_filter_list("||ads.example^").unwrap();
+    let proxy = ProxyServer::start_with_limit("127.0.0.1:0", Arc::new(engine), 1).unwrap();
+    let mut served = TcpStream::connect(proxy.local_addr()).unwrap();
+    std::thread::sleep(Duration::from_millis(200));
+    let mut waiting = TcpStream::connect(proxy.local_addr()).unwrap();
+
+    // When: A third client connects
+    let mut refused = String::new();
+    TcpStream::connect(proxy.local_addr())
+        .unwrap()
+        .read_to_string(&mut refused)
+        .unwrap();
+
+    // Then: It is refused, while the others are still served in turn
+    assert!(refused.starts_with("HTTP/1.1 503"), "{refused}");
+    for stream in [&mut served, &mut waiting] {
+        stream
+            .write_all(b"CONNECT ads.example:443 HTTP/1.1\r\n\r\n")
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 403"), "{response}");
+    }
+}
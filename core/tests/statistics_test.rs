@@ -2,7 +2,10 @@
 //!
 //! Track blocking statistics and provide insights
 
-use adblock_core::Statistics;
+use adblock_core::statistics::{StatisticsConfig, StatisticsPrivacy};
+use adblock_core::tracker_category::TrackerCategory;
+use adblock_core::{SharedStatistics, Statistics};
+use std::sync::Arc;
 use std::time::Duration;
 
 #[test]
@@ -45,6 +48,53 @@ fn should_track_domain_statistics() {
     assert_eq!(top_domains[1].data_saved, 150);
 }
 
+#[test]
+fn should_look_up_the_statistics_of_one_domain() {
+    // Given: Blocks recorded for two domains, in plain and shared statistics
+    let mut stats = Statistics::new();
+    let shared = SharedStatistics::new();
+    for (domain, size) in [("ads.com", 100), ("ads.com", 200), ("tracker.com", 50)] {
+        stats.record_blocked(domain, size);
+        shared.record_blocked(domain, size);
+    }
+
+    // When: Looking up a single domain
+    let ads = stats.domain_stats("ads.com").unwrap();
+    let shared_ads = shared.domain_stats("ads.com").unwrap();
+
+    // Then: Only that domain's counts are returned
+    assert_eq!((ads.count, ads.data_saved), (2, 300));
+    assert_eq!((shared_ads.count, shared_ads.data_saved), (2, 300));
+    assert!(stats.domain_stats("example.com").is_none());
+    assert!(shared.domain_stats("example.com").is_none());
+}
+
+#[test]
+fn should_page_through_the_most_blocked_domains() {
+    // Given: Blocks spread over domains with distinct counts
+    let mut stats = Statistics::new();
+    let shared = SharedStatistics::new();
+    for (domain, count) in [("a.com", 1), ("b.com", 4), ("c.com", 2), ("d.com", 3)] {
+        for _ in 0..count {
+            stats.record_blocked(domain, 10);
+            shared.record_blocked(domain, 10);
+        }
+    }
+
+    // When: Reading the second page of two
+    let page = stats.top_blocked(2, 2);
+    let shared_page = shared.top_blocked(2, 2);
+
+    // Then: The ranking continues where the first page ended
+    let domains = |page: &[adblock_core::DomainStats]| {
+        page.iter().map(|d| d.domain.clone()).collect::<Vec<_>>()
+    };
+    assert_eq!(domains(&stats.top_blocked(0, 2)), ["b.com", "d.com"]);
+    assert_eq!(domains(&page), ["c.com", "a.com"]);
+    assert_eq!(domains(&shared_page), ["c.com", "a.com"]);
+    assert!(stats.top_blocked(4, 2).is_empty());
+}
+
 #[test]
 fn should_get_recent_block_events() {
     // Given: A statistics instance
@@ -101,3 +151,221 @@ fn should_reset_statistics() {
     assert_eq!(stats.data_saved(), 0);
     assert_eq!(stats.recent_events(10).len(), 0);
 }
+
+#[test]
+fn should_enforce_statistics_privacy_levels() {
+    // Given: Statistics storing hashed domains
+    let mut stats = Statistics::with_config(StatisticsConfig {
+        privacy: StatisticsPrivacy::DomainsHashed,
+        ..Default::default()
+    });
+
+    // When: Recording blocks
+    stats.record_blocked("ads.com", 100);
+    stats.record_blocked("ads.com", 100);
+    stats.record_blocked("tracker.com", 50);
+
+    // Then: Top-N counts work without revealing the domains
+    let top = stats.top_blocked_domains(10);
+    assert_eq!(top.len(), 2);
+    assert_eq!(top[0].count, 2);
+    assert!(top.iter().all(|d| d.domain.starts_with('#')));
+    assert!(!stats.export_json().unwrap().contains("ads.com"));
+
+    // When: Switching to counts only
+    stats.set_privacy(StatisticsPrivacy::CountsOnly);
+    stats.record_allowed("example.com", 0);
+
+    // Then: Only totals remain
+    assert!(stats.top_blocked_domains(10).is_empty());
+    assert!(stats.recent_events(10).is_empty());
+    assert_eq!(stats.total_blocked(), 3);
+    assert_eq!(stats.total_allowed(), 1);
+}
+
+#[test]
+fn should_evict_oldest_events_past_capacity() {
+    // Given: Statistics keeping three recent events
+    let mut stats = Statistics::with_config(StatisticsConfig {
+        max_recent_events: 3,
+        ..Default::default()
+    });
+
+    // When: Recording more events than fit
+    for i in 0..5 {
+        stats.record_blocked(&format!("ads{i}.com"), 0);
+    }
+
+    // Then: The newest events are kept, newest first
+    let domains: Vec<_> = stats
+        .iter_recent_events()
+        .map(|event| event.domain.as_str())
+        .collect();
+    assert_eq!(domains, ["ads4.com", "ads3.com", "ads2.com"]);
+
+    // When: Shrinking the capacity
+    stats.set_max_recent_events(1);
+
+    // Then: Only the newest event remains
+    assert_eq!(stats.max_recent_events(), 1);
+    assert_eq!(stats.iter_recent_events().len(), 1);
+    assert_eq!(stats.recent_events(10)[0].domain, "ads4.com");
+}
+
+#[test]
+fn should_track_session_separately_from_lifetime() {
+    // Given: Statistics with requests from an earlier session
+    let mut stats = Statistics::new();
+    stats.record_blocked("ads.com", 100);
+    stats.record_allowed("example.com", 0);
+
+    // When: A new session starts and more requests are recorded
+    stats.start_session();
+    stats.record_blocked("tracker.com", 50);
+
+    // Then: The session counts only its own requests
+    let session = stats.session();
+    assert_eq!(session.blocked_count, 1);
+    assert_eq!(session.allowed_count, 0);
+    assert_eq!(session.data_saved, 50);
+    assert_eq!(stats.total_blocked(), 2);
+    assert_eq!(stats.data_saved(), 150);
+
+    // And: Restoring lifetime totals keeps the session
+    stats.restore(10, 5, 1000, Vec::new());
+    assert_eq!(stats.session().blocked_count, 1);
+    assert_eq!(stats.total_blocked(), 10);
+}
+
+#[test]
+fn should_break_down_blocks_by_tracker_category() {
+    // Given: Statistics and the embedded category map
+    let mut stats = Statistics::new();
+
+    // When: Blocking ads, analytics and unknown domains
+    stats.record_blocked("stats.g.doubleclick.net", 100);
+    stats.record_blocked("www.google-analytics.com", 100);
+    stats.record_blocked("www.google-analytics.com", 100);
+    stats.record_blocked("unknown-tracker.example", 100);
+
+    // Then: Events, domains and the breakdown carry the categories
+    assert_eq!(stats.recent_events(1)[0].category, TrackerCategory::Other);
+    let top = stats.top_blocked_domains(1);
+    assert_eq!(top[0].category, TrackerCategory::Analytics);
+
+    let breakdown = stats.category_breakdown();
+    assert_eq!(breakdown.get(&TrackerCategory::Ads), Some(&1));
+    assert_eq!(breakdown.get(&TrackerCategory::Analytics), Some(&2));
+    assert_eq!(breakdown.get(&TrackerCategory::Other), Some(&1));
+    assert_eq!(breakdown.get(&TrackerCategory::Social), None);
+
+    let json = stats.export_json().unwrap();
+    assert!(json.contains(r#""analytics": 2"#));
+}
+
+#[test]
+fn should_record_from_many_threads_without_losing_counts() {
+    // Given: Statistics shared between threads
+    let stats = Arc::new(SharedStatistics::new());
+
+    // When: Eight threads record blocks and allows concurrently
+    let threads: Vec<_> = (0..8)
+        .map(|thread| {
+            let stats = Arc::clone(&stats);
+            std::thread::spawn(move || {
+                for i in 0..250 {
+                    stats.record_blocked(&format!("ads{}.com", i % 5), 10);
+                    stats.record_allowed(&format!("site{thread}.com"), 0);
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    // Then: The snapshot has every request and merged domain counts
+    let snapshot = stats.snapshot();
+    assert_eq!(snapshot.total_blocked(), 2000);
+    assert_eq!(snapshot.total_allowed(), 2000);
+    assert_eq!(snapshot.data_saved(), 20000);
+    assert_eq!(snapshot.session().blocked_count, 2000);
+    let top = snapshot.top_blocked_domains(10);
+    assert_eq!(top.len(), 5);
+    assert!(top.iter().all(|domain| domain.count == 400));
+}
+
+#[test]
+fn should_keep_the_newest_events_across_shards() {
+    // Given: Shared statistics keeping 20 recent events
+    let stats = SharedStatistics::with_config(StatisticsConfig {
+        max_recent_events: 20,
+        ..Default::default()
+    });
+
+    // When: Recording more events than that
+    for i in 0..50 {
+        stats.record_blocked(&format!("ads{i}.com"), 0);
+    }
+
+    // Then: The snapshot holds the newest 20, newest first
+    let snapshot = stats.snapshot();
+    let events = snapshot.recent_events(100);
+    assert_eq!(events.len(), 20);
+    assert_eq!(events[0].domain, "ads49.com");
+    assert_eq!(events[19].domain, "ads30.com");
+}
+
+#[test]
+fn should_update_shared_statistics_as_a_whole() {
+    // Given: Shared statistics with recorded requests
+    let stats = SharedStatistics::new();
+    stats.record_blocked("ads.com", 100);
+    stats.record_paused("example.com", 0);
+
+    // When: Hashing the domains and then restoring a backup
+    stats.update(|snapshot| snapshot.set_privacy(StatisticsPrivacy::DomainsHashed));
+    let hashed = stats.snapshot().top_blocked_domains(1);
+    stats.record_blocked("ads.com", 100);
+
+    // Then: New records use the hashed domain and restores replace totals
+    assert_eq!(stats.privacy(), StatisticsPrivacy::DomainsHashed);
+    assert_eq!(
+        stats.snapshot().top_blocked_domains(1)[0].domain,
+        hashed[0].domain
+    );
+    assert_eq!(stats.snapshot().top_blocked_domains(1)[0].count, 2);
+    assert_eq!(stats.snapshot().get_paused_count(), 1);
+    stats.update(|snapshot| snapshot.restore(10, 5, 1000, Vec::new()));
+    assert_eq!(stats.get_blocked_count(), 10);
+    assert_eq!(stats.session().blocked_count, 2);
+    stats.reset();
+    assert_eq!(stats.snapshot().total_allowed(), 0);
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn should_persist_statistics_in_sqlite_storage() {
+    use adblock_core::sqlite_storage::SqliteStorage;
+
+    // Given: Statistics saved to an SQLite storage
+    let mut storage = SqliteStorage::open_in_memory().unwrap();
+    let mut stats = Statistics::new();
+    assert!(!stats.load_from(&storage).unwrap());
+    stats.record_blocked("doubleclick.net", 1024);
+    stats.record_blocked("doubleclick.net", 1024);
+    stats.record_allowed("example.com", 512);
+    stats.save_to(&mut storage).unwrap();
+
+    // When: Loading them into a fresh instance
+    let mut restored = Statistics::new();
+    assert!(restored.load_from(&storage).unwrap());
+
+    // Then: Totals and per-domain counts survive
+    assert_eq!(restored.total_blocked(), 2);
+    assert_eq!(restored.total_allowed(), 1);
+    assert_eq!(restored.data_saved(), 2048);
+    let top = restored.top_blocked_domains(1);
+    assert_eq!(top[0].domain, "doubleclick.net");
+    assert_eq!(top[0].count, 2);
+}
@@ -0,0 +1,120 @@
+//! Update Scheduler Tests
+//!
+//! Test background filter list updates and hot-swap notifications
+
+use adblock_core::update_scheduler::{SchedulerEvent, UpdateScheduler};
+use adblock_core::{AdBlockCore, Config, FilterUpdater, UpdateConfig, UpdatePolicy};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+fn scheduler(core: Arc<Mutex<AdBlockCore>>) -> UpdateScheduler {
+    scheduler_with_policy(core, UpdatePolicy::default())
+}
+
+fn scheduler_with_policy(core: Arc<Mutex<AdBlockCore>>, policy: UpdatePolicy) -> UpdateScheduler {
+    let mut updater = FilterUpdater::new(UpdateConfig {
+        urls: vec!["https://example.com/filters.txt".to_string()],
+        presets: Vec::new(),
+        mirrors: HashMap::new(),
+        update_interval: Duration::from_secs(0),
+        cache_dir: None,
+    })
+    .unwrap();
+    updater.set_update_policy(policy);
+    let mut scheduler = UpdateScheduler::new(core, updater, &Config::default());
+    scheduler.set_interval(Duration::from_millis(10));
+    scheduler.set_max_jitter(Duration::from_millis(5));
+    scheduler
+}
+
+#[test]
+fn should_swap_in_downloaded_rules_on_background_thread() {
+    // Given: A running scheduler with a subscriber
+    let core = Arc::new(Mutex::new(AdBlockCore::with_patterns(Vec::new()).unwrap()));
+    let mut scheduler = scheduler(Arc::clone(&core));
+    let events = scheduler.subscribe();
+    scheduler.start().unwrap();
+
+    // When: The first update runs
+    let event = events.recv_timeout(Duration::from_secs(5)).unwrap();
+    scheduler.stop();
+
+    // Then: The host is notified and the live engine uses the new rules
+    assert!(matches!(event, SchedulerEvent::RulesSwapped { rule_count } if rule_count > 0));
+    assert!(!scheduler.is_running());
+    assert!(
+        core.lock()
+            .unwrap()
+            .should_block("https://downloaded-ads.com/ad.js")
+            .should_block
+    );
+}
+
+#[test]
+fn should_skip_updates_while_offline() {
+    // Given: A scheduler told the network is down
+    let core = Arc::new(Mutex::new(AdBlockCore::with_patterns(Vec::new()).unwrap()));
+    let scheduler = scheduler(Arc::clone(&core));
+    scheduler.set_network_available(false);
+
+    // When: An update runs
+    let event = scheduler.run_once();
+
+    // Then: Nothing is downloaded
+    assert_eq!(event, SchedulerEvent::SkippedOffline);
+    assert!(
+        !core
+            .lock()
+            .unwrap()
+            .should_block("https://downloaded-ads.com/ad.js")
+            .should_block
+    );
+
+    // When: The network comes back
+    scheduler.set_network_available(true);
+
+    // Then: The update goes through
+    assert!(matches!(
+        scheduler.run_once(),
+        SchedulerEvent::RulesSwapped { .. }
+    ));
+}
+
+#[test]
+fn should_defer_updates_on_metered_connections_unless_forced() {
+    // Given: A scheduler on a metered connection that disallows it
+    let core = Arc::new(Mutex::new(AdBlockCore::with_patterns(Vec::new()).unwrap()));
+    let scheduler = scheduler_with_policy(
+        Arc::clone(&core),
+        UpdatePolicy {
+            allow_metered: false,
+            is_network_metered: Some(Arc::new(|| true)),
+        },
+    );
+
+    // When: A scheduled update runs
+    let event = scheduler.run_once();
+
+    // Then: Nothing is downloaded
+    assert_eq!(event, SchedulerEvent::SkippedMetered);
+    assert!(
+        !core
+            .lock()
+            .unwrap()
+            .should_block("https://downloaded-ads.com/ad.js")
+            .should_block
+    );
+
+    // When: The user presses refresh
+    let event = scheduler.force_update();
+
+    // Then: The lists are downloaded anyway
+    assert!(matches!(event, SchedulerEvent::RulesSwapped { .. }));
+    assert!(
+        core.lock()
+            .unwrap()
+            .should_block("https://downloaded-ads.com/ad.js")
+            .should_block
+    );
+}
@@ -10,13 +10,8 @@
 
 #import <Foundation/Foundation.h>
 
-// FFI function declarations
-void* adblock_engine_create(void);
-void adblock_engine_destroy(void* engine);
-bool adblock_engine_should_block(void* engine, const char* url);
-bool adblock_engine_load_filter_list(void* engine, const char* filter_list);
-char* adblock_engine_get_stats(void* engine);
-bool adblock_engine_reset_stats(void* engine);
-void adblock_free_string(char* s);
+// FFI function declarations, generated by cbindgen
+// (cargo build --features c-header in core/)
+#include "../../core/include/adblock_core.h"
 
 #endif /* AdBlock_Bridging_Header_h */
\ No newline at end of file
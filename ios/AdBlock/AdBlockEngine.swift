@@ -14,7 +14,7 @@ public final class AdBlockEngine {
     /// Initialize the engine
     /// - Throws: EngineError if initialization fails
     public init() throws {
-        guard let handle = adblock_engine_create() else {
+        guard let handle = adblock_engine_create(nil) else {
             throw EngineError.initializationFailed
         }
         self.engineHandle = handle
@@ -29,7 +29,7 @@ public final class AdBlockEngine {
     /// - Returns: true if the URL should be blocked
     public func shouldBlock(_ url: String) -> Bool {
         return queue.sync {
-            return adblock_engine_should_block(engineHandle, url)
+            return adblock_engine_should_block(engineHandle, url, nil)
         }
     }
     
@@ -39,7 +39,7 @@ public final class AdBlockEngine {
     @discardableResult
     public func loadFilterList(_ filterList: String) -> Bool {
         return queue.sync(flags: .barrier) {
-            return adblock_engine_load_filter_list(engineHandle, filterList)
+            return adblock_engine_load_filter_list(engineHandle, filterList, nil)
         }
     }
     
@@ -47,7 +47,7 @@ public final class AdBlockEngine {
     /// - Returns: Statistics object with blocking metrics
     public func getStatistics() -> Statistics {
         return queue.sync {
-            guard let statsPtr = adblock_engine_get_stats(engineHandle) else {
+            guard let statsPtr = adblock_engine_get_stats(engineHandle, nil) else {
                 return Statistics(blockedCount: 0, allowedCount: 0, dataSaved: 0)
             }
             
@@ -77,7 +77,7 @@ public final class AdBlockEngine {
     /// - Returns: PerformanceMetrics object with detailed performance data
     public func getPerformanceMetrics() -> PerformanceMetrics? {
         return queue.sync {
-            guard let metricsPtr = adblock_engine_get_metrics(engineHandle) else {
+            guard let metricsPtr = adblock_engine_get_metrics(engineHandle, nil) else {
                 return nil
             }
             
@@ -90,10 +90,54 @@ public final class AdBlockEngine {
         }
     }
     
+    /// Export the active rules as Safari content blocker JSON
+    /// - Returns: JSON suitable for an SFContentBlocker extension, or nil on failure
+    public func exportContentBlockerJSON() -> String? {
+        return queue.sync {
+            guard let jsonPtr = adblock_engine_export_content_blocker_json(engineHandle, nil) else {
+                return nil
+            }
+
+            defer {
+                adblock_free_string(jsonPtr)
+            }
+
+            return String(cString: jsonPtr)
+        }
+    }
+
+    /// Get element hiding selectors for a page domain
+    /// - Parameter domain: The page domain
+    /// - Returns: CSS selectors to hide on that domain
+    public func cosmeticSelectors(for domain: String) -> [String] {
+        return queue.sync {
+            guard let rulesPtr = adblock_engine_get_cosmetic_rules(engineHandle, domain, nil) else {
+                return []
+            }
+
+            defer {
+                adblock_free_string(rulesPtr)
+            }
+
+            guard let data = String(cString: rulesPtr).data(using: .utf8),
+                  let selectors = try? JSONSerialization.jsonObject(with: data) as? [String] else {
+                return []
+            }
+            return selectors
+        }
+    }
+
     /// Reset statistics
     public func resetStatistics() {
         queue.sync(flags: .barrier) {
-            _ = adblock_engine_reset_stats(engineHandle)
+            _ = adblock_engine_reset_stats(engineHandle, nil)
+        }
+    }
+
+    /// Start a new statistics session, e.g. when the VPN connects
+    public func startSession() {
+        queue.sync(flags: .barrier) {
+            _ = adblock_engine_start_session(engineHandle, nil)
         }
     }
 }
@@ -3,25 +3,199 @@ import Foundation
 // FFI function declarations for the Rust library
 
 @_silgen_name("adblock_engine_create")
-func adblock_engine_create() -> UnsafeMutableRawPointer?
+func adblock_engine_create(_ error: UnsafeMutablePointer<Int32>?) -> UnsafeMutableRawPointer?
 
 @_silgen_name("adblock_engine_destroy")
 func adblock_engine_destroy(_ engine: UnsafeMutableRawPointer)
 
 @_silgen_name("adblock_engine_should_block")
-func adblock_engine_should_block(_ engine: UnsafeMutableRawPointer, _ url: UnsafePointer<CChar>) -> Bool
+func adblock_engine_should_block(_ engine: UnsafeMutableRawPointer, _ url: UnsafePointer<CChar>, _ error: UnsafeMutablePointer<Int32>?) -> Bool
+
+@_silgen_name("adblock_engine_check_url")
+func adblock_engine_check_url(_ engine: UnsafeMutableRawPointer, _ url: UnsafePointer<CChar>, _ error: UnsafeMutablePointer<Int32>?) -> Int32
+
+@_silgen_name("adblock_engine_check_url_for_app")
+func adblock_engine_check_url_for_app(_ engine: UnsafeMutableRawPointer, _ url: UnsafePointer<CChar>, _ app: UnsafePointer<CChar>, _ error: UnsafeMutablePointer<Int32>?) -> Int32
+
+@_silgen_name("adblock_engine_explain")
+func adblock_engine_explain(_ engine: UnsafeMutableRawPointer, _ url: UnsafePointer<CChar>, _ error: UnsafeMutablePointer<Int32>?) -> UnsafeMutablePointer<CChar>?
 
 @_silgen_name("adblock_engine_load_filter_list")
-func adblock_engine_load_filter_list(_ engine: UnsafeMutableRawPointer, _ filterList: UnsafePointer<CChar>) -> Bool
+func adblock_engine_load_filter_list(_ engine: UnsafeMutableRawPointer, _ filterList: UnsafePointer<CChar>, _ error: UnsafeMutablePointer<Int32>?) -> Bool
+
+@_silgen_name("adblock_engine_load_filter_list_status")
+func adblock_engine_load_filter_list_status(_ engine: UnsafeMutableRawPointer, _ filterList: UnsafePointer<CChar>) -> Int32
+
+@_silgen_name("adblock_engine_add_rule_status")
+func adblock_engine_add_rule_status(_ engine: UnsafeMutableRawPointer, _ rule: UnsafePointer<CChar>) -> Int32
 
 @_silgen_name("adblock_engine_get_stats")
-func adblock_engine_get_stats(_ engine: UnsafeMutableRawPointer) -> UnsafeMutablePointer<CChar>?
+func adblock_engine_get_stats(_ engine: UnsafeMutableRawPointer, _ error: UnsafeMutablePointer<Int32>?) -> UnsafeMutablePointer<CChar>?
+
+@_silgen_name("adblock_engine_get_stats_buf")
+func adblock_engine_get_stats_buf(_ engine: UnsafeMutableRawPointer, _ buf: UnsafeMutablePointer<CChar>?, _ cap: Int, _ error: UnsafeMutablePointer<Int32>?) -> Int
+
+@_silgen_name("adblock_engine_get_domain_stats")
+func adblock_engine_get_domain_stats(_ engine: UnsafeMutableRawPointer, _ domain: UnsafePointer<CChar>, _ error: UnsafeMutablePointer<Int32>?) -> UnsafeMutablePointer<CChar>?
+
+@_silgen_name("adblock_engine_top_blocked")
+func adblock_engine_top_blocked(_ engine: UnsafeMutableRawPointer, _ offset: Int, _ limit: Int, _ error: UnsafeMutablePointer<Int32>?) -> UnsafeMutablePointer<CChar>?
 
 @_silgen_name("adblock_engine_reset_stats")
-func adblock_engine_reset_stats(_ engine: UnsafeMutableRawPointer) -> Bool
+func adblock_engine_reset_stats(_ engine: UnsafeMutableRawPointer, _ error: UnsafeMutablePointer<Int32>?) -> Bool
+
+@_silgen_name("adblock_engine_reset_stats_status")
+func adblock_engine_reset_stats_status(_ engine: UnsafeMutableRawPointer) -> Int32
+
+@_silgen_name("adblock_engine_start_session")
+func adblock_engine_start_session(_ engine: UnsafeMutableRawPointer, _ error: UnsafeMutablePointer<Int32>?) -> Bool
+
+@_silgen_name("adblock_engine_start_session_status")
+func adblock_engine_start_session_status(_ engine: UnsafeMutableRawPointer) -> Int32
 
 @_silgen_name("adblock_engine_get_metrics")
-func adblock_engine_get_metrics(_ engine: UnsafeMutableRawPointer) -> UnsafeMutablePointer<CChar>?
+func adblock_engine_get_metrics(_ engine: UnsafeMutableRawPointer, _ error: UnsafeMutablePointer<Int32>?) -> UnsafeMutablePointer<CChar>?
+
+@_silgen_name("adblock_engine_export_content_blocker_json")
+func adblock_engine_export_content_blocker_json(_ engine: UnsafeMutableRawPointer, _ error: UnsafeMutablePointer<Int32>?) -> UnsafeMutablePointer<CChar>?
+
+@_silgen_name("adblock_engine_get_cosmetic_rules")
+func adblock_engine_get_cosmetic_rules(_ engine: UnsafeMutableRawPointer, _ domain: UnsafePointer<CChar>, _ error: UnsafeMutablePointer<Int32>?) -> UnsafeMutablePointer<CChar>?
+
+@_silgen_name("adblock_engine_get_procedural_filters")
+func adblock_engine_get_procedural_filters(_ engine: UnsafeMutableRawPointer, _ domain: UnsafePointer<CChar>, _ error: UnsafeMutablePointer<Int32>?) -> UnsafeMutablePointer<CChar>?
+
+@_silgen_name("adblock_engine_set_event_callback")
+func adblock_engine_set_event_callback(_ engine: UnsafeMutableRawPointer, _ callback: (@convention(c) (UnsafePointer<CChar>?, UnsafeMutableRawPointer?) -> Void)?, _ userData: UnsafeMutableRawPointer?, _ error: UnsafeMutablePointer<Int32>?) -> Bool
+
+@_silgen_name("adblock_engine_set_block_event_callback")
+func adblock_engine_set_block_event_callback(_ engine: UnsafeMutableRawPointer, _ callback: (@convention(c) (UnsafePointer<CChar>?, UnsafeMutableRawPointer?) -> Void)?, _ userData: UnsafeMutableRawPointer?, _ error: UnsafeMutablePointer<Int32>?) -> Bool
+
+@_silgen_name("adblock_engine_add_filter_list")
+func adblock_engine_add_filter_list(_ engine: UnsafeMutableRawPointer, _ url: UnsafePointer<CChar>, _ content: UnsafePointer<CChar>, _ error: UnsafeMutablePointer<Int32>?) -> Bool
+
+@_silgen_name("adblock_engine_add_security_list")
+func adblock_engine_add_security_list(_ engine: UnsafeMutableRawPointer, _ url: UnsafePointer<CChar>, _ content: UnsafePointer<CChar>, _ error: UnsafeMutablePointer<Int32>?) -> Bool
+
+@_silgen_name("adblock_engine_add_security_list_status")
+func adblock_engine_add_security_list_status(_ engine: UnsafeMutableRawPointer, _ url: UnsafePointer<CChar>, _ content: UnsafePointer<CChar>) -> Int32
+
+@_silgen_name("adblock_engine_remove_filter_list")
+func adblock_engine_remove_filter_list(_ engine: UnsafeMutableRawPointer, _ url: UnsafePointer<CChar>, _ error: UnsafeMutablePointer<Int32>?) -> Bool
+
+@_silgen_name("adblock_engine_set_filter_list_enabled")
+func adblock_engine_set_filter_list_enabled(_ engine: UnsafeMutableRawPointer, _ url: UnsafePointer<CChar>, _ enabled: Bool, _ error: UnsafeMutablePointer<Int32>?) -> Bool
+
+@_silgen_name("adblock_engine_get_filter_lists")
+func adblock_engine_get_filter_lists(_ engine: UnsafeMutableRawPointer, _ error: UnsafeMutablePointer<Int32>?) -> UnsafeMutablePointer<CChar>?
+
+@_silgen_name("adblock_engine_create_with_config")
+func adblock_engine_create_with_config(_ configJson: UnsafePointer<CChar>, _ error: UnsafeMutablePointer<Int32>?) -> UnsafeMutableRawPointer?
+
+@_silgen_name("adblock_validate_config")
+func adblock_validate_config(_ configJson: UnsafePointer<CChar>, _ error: UnsafeMutablePointer<Int32>?) -> UnsafeMutablePointer<CChar>?
+
+@_silgen_name("adblock_engine_create_backup")
+func adblock_engine_create_backup(_ engine: UnsafeMutableRawPointer, _ error: UnsafeMutablePointer<Int32>?) -> UnsafeMutablePointer<CChar>?
+
+@_silgen_name("adblock_engine_create_backup_gzip")
+func adblock_engine_create_backup_gzip(_ engine: UnsafeMutableRawPointer, _ buf: UnsafeMutablePointer<UInt8>?, _ cap: Int, _ error: UnsafeMutablePointer<Int32>?) -> Int
+
+@_silgen_name("adblock_engine_restore_backup")
+func adblock_engine_restore_backup(_ engine: UnsafeMutableRawPointer, _ data: UnsafePointer<UInt8>, _ len: Int, _ error: UnsafeMutablePointer<Int32>?) -> Bool
+
+@_silgen_name("adblock_engine_export_user_rules")
+func adblock_engine_export_user_rules(_ engine: UnsafeMutableRawPointer, _ error: UnsafeMutablePointer<Int32>?) -> UnsafeMutablePointer<CChar>?
+
+@_silgen_name("adblock_engine_import_user_rules")
+func adblock_engine_import_user_rules(_ engine: UnsafeMutableRawPointer, _ text: UnsafePointer<CChar>, _ error: UnsafeMutablePointer<Int32>?) -> UnsafeMutablePointer<CChar>?
+
+@_silgen_name("adblock_engine_pause")
+func adblock_engine_pause(_ engine: UnsafeMutableRawPointer, _ seconds: UInt64, _ error: UnsafeMutablePointer<Int32>?) -> Bool
+
+@_silgen_name("adblock_engine_resume")
+func adblock_engine_resume(_ engine: UnsafeMutableRawPointer, _ error: UnsafeMutablePointer<Int32>?) -> Bool
+
+@_silgen_name("adblock_engine_is_paused")
+func adblock_engine_is_paused(_ engine: UnsafeMutableRawPointer, _ error: UnsafeMutablePointer<Int32>?) -> Bool
+
+@_silgen_name("adblock_engine_set_device_conditions")
+func adblock_engine_set_device_conditions(_ engine: UnsafeMutableRawPointer, _ idle: Bool, _ charging: Bool, _ error: UnsafeMutablePointer<Int32>?) -> Bool
+
+@_silgen_name("adblock_engine_set_pause_schedule")
+func adblock_engine_set_pause_schedule(_ engine: UnsafeMutableRawPointer, _ scheduleJson: UnsafePointer<CChar>, _ error: UnsafeMutablePointer<Int32>?) -> Bool
+
+@_silgen_name("adblock_get_list_catalog")
+func adblock_get_list_catalog(_ error: UnsafeMutablePointer<Int32>?) -> UnsafeMutablePointer<CChar>?
+
+@_silgen_name("adblock_engine_set_list_preset_enabled")
+func adblock_engine_set_list_preset_enabled(_ engine: UnsafeMutableRawPointer, _ id: UnsafePointer<CChar>, _ enabled: Bool, _ error: UnsafeMutablePointer<Int32>?) -> Bool
+
+@_silgen_name("adblock_engine_set_site_mode")
+func adblock_engine_set_site_mode(_ engine: UnsafeMutableRawPointer, _ domain: UnsafePointer<CChar>, _ mode: Int32, _ error: UnsafeMutablePointer<Int32>?) -> Bool
+
+@_silgen_name("adblock_engine_clear_site_mode")
+func adblock_engine_clear_site_mode(_ engine: UnsafeMutableRawPointer, _ domain: UnsafePointer<CChar>, _ error: UnsafeMutablePointer<Int32>?) -> Bool
+
+@_silgen_name("adblock_engine_get_site_mode")
+func adblock_engine_get_site_mode(_ engine: UnsafeMutableRawPointer, _ host: UnsafePointer<CChar>, _ error: UnsafeMutablePointer<Int32>?) -> Int32
+
+@_silgen_name("adblock_engine_get_site_modes")
+func adblock_engine_get_site_modes(_ engine: UnsafeMutableRawPointer, _ error: UnsafeMutablePointer<Int32>?) -> UnsafeMutablePointer<CChar>?
+
+@_silgen_name("adblock_get_crash_reports_json")
+func adblock_get_crash_reports_json(_ engine: UnsafeMutableRawPointer, _ error: UnsafeMutablePointer<Int32>?) -> UnsafeMutablePointer<CChar>?
+
+@_silgen_name("adblock_clear_crash_reports")
+func adblock_clear_crash_reports(_ engine: UnsafeMutableRawPointer, _ error: UnsafeMutablePointer<Int32>?) -> Bool
+
+@_silgen_name("adblock_analytics_set_enabled")
+func adblock_analytics_set_enabled(_ engine: UnsafeMutableRawPointer, _ enabled: Bool, _ error: UnsafeMutablePointer<Int32>?) -> Bool
+
+@_silgen_name("adblock_analytics_export_batch")
+func adblock_analytics_export_batch(_ engine: UnsafeMutableRawPointer, _ error: UnsafeMutablePointer<Int32>?) -> UnsafeMutablePointer<CChar>?
+
+@_silgen_name("adblock_analytics_mark_uploaded")
+func adblock_analytics_mark_uploaded(_ engine: UnsafeMutableRawPointer, _ batchId: UnsafePointer<CChar>, _ error: UnsafeMutablePointer<Int32>?) -> Bool
+
+@_silgen_name("adblock_engine_get_metrics_prometheus")
+func adblock_engine_get_metrics_prometheus(_ engine: UnsafeMutableRawPointer, _ error: UnsafeMutablePointer<Int32>?) -> UnsafeMutablePointer<CChar>?
+
+@_silgen_name("adblock_engine_enable_query_log")
+func adblock_engine_enable_query_log(_ engine: UnsafeMutableRawPointer, _ path: UnsafePointer<CChar>?, _ maxBytes: UInt64, _ error: UnsafeMutablePointer<Int32>?) -> Bool
+
+@_silgen_name("adblock_engine_enable_query_log_status")
+func adblock_engine_enable_query_log_status(_ engine: UnsafeMutableRawPointer, _ path: UnsafePointer<CChar>?, _ maxBytes: UInt64) -> Int32
+
+@_silgen_name("adblock_engine_clear_query_log")
+func adblock_engine_clear_query_log(_ engine: UnsafeMutableRawPointer, _ error: UnsafeMutablePointer<Int32>?) -> Bool
+
+@_silgen_name("adblock_engine_clear_query_log_status")
+func adblock_engine_clear_query_log_status(_ engine: UnsafeMutableRawPointer) -> Int32
+
+@_silgen_name("adblock_engine_search_query_log")
+func adblock_engine_search_query_log(_ engine: UnsafeMutableRawPointer, _ queryJson: UnsafePointer<CChar>, _ error: UnsafeMutablePointer<Int32>?) -> UnsafeMutablePointer<CChar>?
+
+@_silgen_name("adblock_network_filter_create")
+func adblock_network_filter_create(_ error: UnsafeMutablePointer<Int32>?) -> UnsafeMutableRawPointer?
+
+@_silgen_name("adblock_engine_create_network_filter")
+func adblock_engine_create_network_filter(_ engine: UnsafeMutableRawPointer, _ error: UnsafeMutablePointer<Int32>?) -> UnsafeMutableRawPointer?
+
+@_silgen_name("adblock_network_filter_destroy")
+func adblock_network_filter_destroy(_ filter: UnsafeMutableRawPointer)
+
+@_silgen_name("adblock_network_filter_add_blocked_domain")
+func adblock_network_filter_add_blocked_domain(_ filter: UnsafeMutableRawPointer, _ domain: UnsafePointer<CChar>, _ error: UnsafeMutablePointer<Int32>?) -> Bool
+
+@_silgen_name("adblock_network_filter_add_blocked_domain_status")
+func adblock_network_filter_add_blocked_domain_status(_ filter: UnsafeMutableRawPointer, _ domain: UnsafePointer<CChar>) -> Int32
+
+@_silgen_name("adblock_network_filter_is_domain_blocked")
+func adblock_network_filter_is_domain_blocked(_ filter: UnsafeMutableRawPointer, _ domain: UnsafePointer<CChar>, _ error: UnsafeMutablePointer<Int32>?) -> Bool
+
+@_silgen_name("adblock_network_filter_process_dns_packet")
+func adblock_network_filter_process_dns_packet(_ filter: UnsafeMutableRawPointer, _ packet: UnsafePointer<UInt8>, _ len: Int, _ buf: UnsafeMutablePointer<UInt8>?, _ cap: Int, _ error: UnsafeMutablePointer<Int32>?) -> Int
 
 @_silgen_name("adblock_free_string")
-func adblock_free_string(_ string: UnsafeMutablePointer<CChar>)
\ No newline at end of file
+func adblock_free_string(_ string: UnsafeMutablePointer<CChar>)
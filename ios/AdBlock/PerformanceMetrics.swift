@@ -8,6 +8,9 @@ public struct PerformanceMetrics: Codable {
     let avgProcessingTimeNs: Int64
     let maxProcessingTimeNs: Int64
     let minProcessingTimeNs: Int64
+    let p50ProcessingTimeNs: Int64
+    let p95ProcessingTimeNs: Int64
+    let p99ProcessingTimeNs: Int64
     let filterCount: Int
     let memoryUsageBytes: Int
     let parseErrors: Int64
@@ -15,6 +18,8 @@ public struct PerformanceMetrics: Codable {
     let cacheHits: Int64
     let cacheMisses: Int64
     let cacheSize: Int
+    let matchesByCategory: [String: Int64]
+    let blocksByList: [String: Int64]
     let blockRate: Double
     let cacheHitRate: Double
     
@@ -25,6 +30,9 @@ public struct PerformanceMetrics: Codable {
         case avgProcessingTimeNs = "avg_processing_time_ns"
         case maxProcessingTimeNs = "max_processing_time_ns"
         case minProcessingTimeNs = "min_processing_time_ns"
+        case p50ProcessingTimeNs = "p50_processing_time_ns"
+        case p95ProcessingTimeNs = "p95_processing_time_ns"
+        case p99ProcessingTimeNs = "p99_processing_time_ns"
         case filterCount = "filter_count"
         case memoryUsageBytes = "memory_usage_bytes"
         case parseErrors = "parse_errors"
@@ -32,6 +40,8 @@ public struct PerformanceMetrics: Codable {
         case cacheHits = "cache_hits"
         case cacheMisses = "cache_misses"
         case cacheSize = "cache_size"
+        case matchesByCategory = "matches_by_category"
+        case blocksByList = "blocks_by_list"
         case blockRate = "block_rate"
         case cacheHitRate = "cache_hit_rate"
     }
@@ -51,6 +61,17 @@ public struct PerformanceMetrics: Codable {
         Double(minProcessingTimeNs) / 1000.0
     }
     
+    /// Get 99th percentile processing time in microseconds
+    var p99ProcessingTimeMicros: Double {
+        Double(p99ProcessingTimeNs) / 1000.0
+    }
+    
+    /// Percentage of blocked requests caused by a filter list
+    func listBlockShare(_ list: String) -> Double {
+        guard let blocks = blocksByList[list], blockedRequests > 0 else { return 0 }
+        return Double(blocks) * 100.0 / Double(blockedRequests)
+    }
+    
     /// Get memory usage in MB
     var memoryUsageMB: Double {
         Double(memoryUsageBytes) / (1024.0 * 1024.0)